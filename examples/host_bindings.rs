@@ -0,0 +1,128 @@
+//! Defining host functions by hand means reading pointers and lengths
+//! out of the guest's memory, validating them and decoding strings.
+//! Instead, the host functions can be described in a WAI interface,
+//! from which `wai_bindgen_wasmer::export!` generates:
+//!
+//!   1. a Rust trait with one method per function, taking Rust types
+//!      like `&str`,
+//!   2. an `add_to_imports` function adding the functions of a value
+//!      implementing the trait to an import object, with all the glue
+//!      converting the arguments and the errors.
+//!
+//! In this example, the guest logs messages with a `logger` interface
+//! described in `examples/wai/logger.wai`.
+//!
+//! You can run the example directly by executing in Wasmer root:
+//!
+//! ```shell
+//! cargo run --example host-bindings --release --features "cranelift"
+//! ```
+//!
+//! Ready?
+
+use std::sync::{Arc, Mutex};
+use wasmer::{imports, wat2wasm, Instance, Module, Store, TypedFunction};
+use wasmer_compiler_cranelift::Cranelift;
+
+// Generate the `logger` module, holding the `Logger` trait and the
+// `add_to_imports` function, from the interface.
+wai_bindgen_wasmer::export!("examples/wai/logger.wai");
+
+// The host side of the interface. The messages are shared with `main`
+// to print them once the guest is done.
+struct Logger {
+    messages: Arc<Mutex<Vec<(u32, String)>>>,
+}
+
+impl logger::Logger for Logger {
+    fn log(&mut self, level: u32, message: &str) {
+        self.messages
+            .lock()
+            .unwrap()
+            .push((level, message.to_string()));
+    }
+
+    fn count(&mut self) -> u32 {
+        self.messages.lock().unwrap().len() as u32
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Let's declare the Wasm module with the text representation.
+    //
+    // The strings are passed to the host as a pointer and a length, and
+    // the module exports `canonical_abi_realloc`, which the generated
+    // code uses to pass values back to the guest.
+    let wasm_bytes = wat2wasm(
+        br#"
+(module
+  (import "logger" "log" (func $log (param i32 i32 i32)))
+  (import "logger" "count" (func $count (result i32)))
+
+  (memory (export "memory") 1)
+  (data (i32.const 16) "starting")
+  (data (i32.const 32) "done")
+
+  (global $next (mut i32) (i32.const 1024))
+  (func (export "canonical_abi_realloc")
+        (param $ptr i32) (param $old_size i32) (param $align i32) (param $size i32)
+        (result i32)
+    (global.get $next)
+    (global.set $next (i32.add (global.get $next) (local.get $size))))
+
+  (func (export "run") (result i32)
+    (call $log (i32.const 1) (i32.const 16) (i32.const 8))
+    (call $log (i32.const 2) (i32.const 32) (i32.const 4))
+    (call $count)))
+"#,
+    )?;
+
+    // Create a Store.
+    let mut store = Store::new(Cranelift::default());
+
+    println!("Compiling module...");
+    // Let's compile the Wasm module.
+    let module = Module::new(&store, wasm_bytes)?;
+
+    // Add the functions of the interface to an import object.
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let mut import_object = imports! {};
+    let initialize = logger::add_to_imports(
+        &mut store,
+        &mut import_object,
+        Logger {
+            messages: messages.clone(),
+        },
+    );
+
+    println!("Instantiating module...");
+    // Let's instantiate the Wasm module.
+    let instance = Instance::new(&mut store, &module, &import_object)?;
+
+    // The generated functions need the memory and the allocator of the
+    // instance, which are given to them once it's created.
+    initialize(&instance, &store)?;
+
+    let run: TypedFunction<(), i32> = instance.exports.get_typed_function(&store, "run")?;
+
+    println!("Calling `run` function...");
+    let count = run.call(&mut store)?;
+
+    println!("The guest logged {} messages:", count);
+    for (level, message) in messages.lock().unwrap().iter() {
+        println!("  [{}] {}", level, message);
+    }
+
+    assert_eq!(count, 2);
+    assert_eq!(
+        *messages.lock().unwrap(),
+        vec![(1, "starting".to_string()), (2, "done".to_string())]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_host_bindings() -> Result<(), Box<dyn std::error::Error>> {
+    main()
+}
@@ -373,6 +373,17 @@ impl Module {
         Self::new(_store, bytes).map_err(|e| DeserializeError::Compiler(e))
     }
 
+    /// Deserializes a serialized Module binary into a `Module`.
+    ///
+    /// Under `js` this is the same as [`Module::deserialize`].
+    #[cfg(feature = "js-serializable-module")]
+    pub unsafe fn deserialize_unchecked(
+        store: &impl AsStoreRef,
+        bytes: impl IntoBytes,
+    ) -> Result<Self, DeserializeError> {
+        Self::deserialize(store, bytes)
+    }
+
     #[cfg(feature = "compiler")]
     /// Deserializes a a serialized Module located in a `Path` into a `Module`.
     /// > Note: the module has to be serialized before with the `serialize` method.
@@ -81,7 +81,10 @@
 //!   * [`wasmer-compiler-cranelift`] provides the right balance between
 //!     compilation-time and runtime performance, useful for development,
 //!   * [`wasmer-compiler-llvm`] provides a deeply optimized executable
-//!     code with the fastest runtime speed, ideal for production.
+//!     code with the fastest runtime speed, ideal for production,
+//!   * [`wasmer-compiler-interpreter`] generates no executable code at
+//!     all, for the platforms forbidding it, at the cost of the slowest
+//!     runtime speed.
 //!     
 //! * **Headless mode** — Once a WebAssembly module has been compiled, it
 //!   is possible to serialize it in a file for example, and later execute
@@ -303,14 +306,33 @@
 #![cfg_attr(feature = "singlepass", doc = "(enabled),")]
 #![cfg_attr(not(feature = "singlepass"), doc = "(disabled),")]
 //!   enables Wasmer's [Singlepass compiler][wasmer-compiler-singlepass],
+//! - `interpreter`
+#![cfg_attr(feature = "interpreter", doc = "(enabled),")]
+#![cfg_attr(not(feature = "interpreter"), doc = "(disabled),")]
+//!   enables Wasmer's [interpreter][wasmer-compiler-interpreter],
 //! - `wat`
 #![cfg_attr(feature = "wat", doc = "(enabled),")]
 #![cfg_attr(not(feature = "wat"), doc = "(disabled),")]
 //!   enables `wasmer` to parse the WebAssembly text format,
+//! - `wasmprinter`
+#![cfg_attr(feature = "wasmprinter", doc = "(enabled),")]
+#![cfg_attr(not(feature = "wasmprinter"), doc = "(disabled),")]
+//!   enables `Module::to_wat`, which prints the modules in the
+//!   WebAssembly text format, at the cost of a copy of their binary,
 //! - `compilation`
 #![cfg_attr(feature = "compiler", doc = "(enabled),")]
 #![cfg_attr(not(feature = "compiler"), doc = "(disabled),")]
-//!   enables compilation with the wasmer engine.
+//!   enables compilation with the wasmer engine,
+//! - `tracing`
+#![cfg_attr(feature = "tracing", doc = "(enabled),")]
+#![cfg_attr(not(feature = "tracing"), doc = "(disabled),")]
+//!   emits `tracing` spans for the compilation, deserialization and
+//!   instantiation of the modules, and for the calls of the functions,
+//! - `tokio`
+#![cfg_attr(feature = "tokio", doc = "(enabled),")]
+#![cfg_attr(not(feature = "tokio"), doc = "(disabled),")]
+//!   enables `TokioAdapter`, which runs the calls of the functions and
+//!   async host functions on a Tokio runtime.
 //!
 //! The features that set defaults come in sets that are mutually exclusive.
 //!
@@ -409,6 +431,7 @@
 //! [`wasmer-compiler-singlepass`]: https://docs.rs/wasmer-compiler-singlepass/
 //! [`wasmer-compiler-llvm`]: https://docs.rs/wasmer-compiler-llvm/
 //! [`wasmer-compiler-cranelift`]: https://docs.rs/wasmer-compiler-cranelift/
+//! [`wasmer-compiler-interpreter`]: https://docs.rs/wasmer-compiler-interpreter/
 //! [`wasmer-wasi`]: https://docs.rs/wasmer-wasi/
 //! [`wasm-pack`]: https://github.com/rustwasm/wasm-pack/
 //! [`wasm-bindgen`]: https://github.com/rustwasm/wasm-bindgen
@@ -0,0 +1,184 @@
+use crate::sys::exports::ExportError;
+use crate::sys::externals::Memory;
+use crate::sys::instance::Instance;
+use crate::sys::mem_access::MemoryAccessError;
+use crate::sys::native::TypedFunction;
+use crate::sys::store::{AsStoreMut, AsStoreRef};
+use crate::sys::RuntimeError;
+use std::convert::TryFrom;
+use thiserror::Error;
+
+/// Calls the functions of an instance taking and returning byte buffers
+/// and strings, following simple conventions shared by the host and the
+/// guest.
+///
+/// The guest must export:
+/// - its memory, as `memory`,
+/// - an allocator, as `alloc`, of type `(len: i32) -> (ptr: i32)`,
+/// - optionally, the matching deallocator, as `dealloc`, of type
+///   `(ptr: i32, len: i32)`.
+///
+/// The functions called through the adapter have the type
+/// `(ptr: i32, len: i32) -> i64`: they receive the input in a buffer
+/// allocated with `alloc`, and return the buffer holding their output,
+/// its pointer in the high 32 bits of the result and its length in the
+/// low 32 bits. The input buffer stays owned by the host and the output
+/// buffer is given to the host, which frees both with `dealloc`, if the
+/// guest exports it, once the call is over.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::{imports, BufferAdapter, Instance, Module, Store};
+/// # fn main() -> anyhow::Result<()> {
+/// let mut store = Store::default();
+/// let module = Module::new(&store, r#"
+///     (module
+///         (memory (export "memory") 1)
+///         (global $next (mut i32) (i32.const 16))
+///         (func (export "alloc") (param $len i32) (result i32)
+///             (global.get $next)
+///             (global.set $next (i32.add (global.get $next) (local.get $len))))
+///         ;; Returns its input.
+///         (func (export "echo") (param $ptr i32) (param $len i32) (result i64)
+///             (i64.or
+///                 (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+///                 (i64.extend_i32_u (local.get $len)))))
+/// "#)?;
+/// let instance = Instance::new(&mut store, &module, &imports! {})?;
+/// let adapter = BufferAdapter::new(&store, &instance)?;
+/// assert_eq!(adapter.call_str(&mut store, "echo", "hello")?, "hello");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BufferAdapter {
+    instance: Instance,
+    memory: Memory,
+    alloc: TypedFunction<u32, u32>,
+    dealloc: Option<TypedFunction<(u32, u32), ()>>,
+}
+
+/// An error while calling a function through a [`BufferAdapter`].
+#[derive(Error, Debug)]
+pub enum AdapterError {
+    /// An export the adapter needs is missing or has the wrong type.
+    #[error(transparent)]
+    Export(#[from] ExportError),
+
+    /// A guest function trapped.
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+
+    /// A buffer doesn't fit in the memory of the guest, or a string
+    /// returned by the guest isn't valid UTF-8.
+    #[error(transparent)]
+    MemoryAccess(#[from] MemoryAccessError),
+}
+
+impl BufferAdapter {
+    /// Creates an adapter for `instance`, using its exports named
+    /// `memory`, `alloc` and `dealloc`.
+    pub fn new(store: &impl AsStoreRef, instance: &Instance) -> Result<Self, AdapterError> {
+        Self::with_exports(store, instance, "memory", "alloc", Some("dealloc"))
+    }
+
+    /// Creates an adapter for `instance`, using the given exports.
+    ///
+    /// When `dealloc` is given but the instance doesn't export it, the
+    /// buffers aren't freed.
+    pub fn with_exports(
+        store: &impl AsStoreRef,
+        instance: &Instance,
+        memory: &str,
+        alloc: &str,
+        dealloc: Option<&str>,
+    ) -> Result<Self, AdapterError> {
+        let exports = &instance.exports;
+        let dealloc = match dealloc.map(|name| exports.get_typed_function(store, name)) {
+            Some(Ok(dealloc)) => Some(dealloc),
+            Some(Err(ExportError::Missing(_))) | None => None,
+            Some(Err(e)) => return Err(e.into()),
+        };
+        Ok(Self {
+            instance: instance.clone(),
+            memory: exports.get_memory(memory)?.clone(),
+            alloc: exports.get_typed_function(store, alloc)?,
+            dealloc,
+        })
+    }
+
+    /// Calls the function exported as `name` with `input`, and returns
+    /// its output.
+    pub fn call_bytes(
+        &self,
+        store: &mut impl AsStoreMut,
+        name: &str,
+        input: &[u8],
+    ) -> Result<Vec<u8>, AdapterError> {
+        let func: TypedFunction<(u32, u32), u64> =
+            self.instance.exports.get_typed_function(&*store, name)?;
+        let (ptr, len) = self.write(store, input)?;
+        let result = func.call(store, ptr, len);
+        self.free(store, ptr, len)?;
+        let result = result?;
+        let (ptr, len) = ((result >> 32) as u32, result as u32);
+        let output = self.read(&*store, ptr, len);
+        self.free(store, ptr, len)?;
+        output
+    }
+
+    /// Calls the function exported as `name` with `input`, and returns
+    /// its output, which must be valid UTF-8.
+    pub fn call_str(
+        &self,
+        store: &mut impl AsStoreMut,
+        name: &str,
+        input: &str,
+    ) -> Result<String, AdapterError> {
+        let output = self.call_bytes(store, name, input.as_bytes())?;
+        String::from_utf8(output).map_err(|_| MemoryAccessError::NonUtf8String.into())
+    }
+
+    /// Copies `bytes` to a buffer allocated in the guest with `alloc`,
+    /// and returns its pointer and length.
+    ///
+    /// The buffer is owned by the caller, which can free it with
+    /// [`BufferAdapter::free`].
+    pub fn write(
+        &self,
+        store: &mut impl AsStoreMut,
+        bytes: &[u8],
+    ) -> Result<(u32, u32), AdapterError> {
+        let len = u32::try_from(bytes.len()).map_err(|_| MemoryAccessError::Overflow)?;
+        let ptr = self.alloc.call(store, len)?;
+        self.memory.view(&*store).write(ptr as u64, bytes)?;
+        Ok((ptr, len))
+    }
+
+    /// Copies the `len` bytes at `ptr` out of the guest's memory.
+    pub fn read(
+        &self,
+        store: &impl AsStoreRef,
+        ptr: u32,
+        len: u32,
+    ) -> Result<Vec<u8>, AdapterError> {
+        let mut bytes = vec![0; len as usize];
+        self.memory.view(store).read(ptr as u64, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Frees the buffer of `len` bytes at `ptr` with `dealloc`, if the
+    /// guest exports it.
+    pub fn free(
+        &self,
+        store: &mut impl AsStoreMut,
+        ptr: u32,
+        len: u32,
+    ) -> Result<(), AdapterError> {
+        if let Some(dealloc) = &self.dealloc {
+            dealloc.call(store, ptr, len)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+use crate::sys::module::Module;
+use std::fmt;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use wasmer_compiler::AsEngineRef;
+use wasmer_types::CompileError;
+
+/// A module being compiled on a thread of its own, created by
+/// [`Module::compile_in_background`].
+///
+/// The compiled module is obtained by awaiting the `CompilingModule`,
+/// which never blocks the executor, or by calling
+/// [`CompilingModule::wait`] outside of an async context.
+/// [`CompilingModule::is_finished`] tells whether it's ready without
+/// waiting for it.
+///
+/// Dropping the `CompilingModule` doesn't stop the compilation, its
+/// result is thrown away.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::{Module, Store};
+/// # fn main() -> anyhow::Result<()> {
+/// let store = Store::default();
+/// let compiling = Module::compile_in_background(&store, "(module)");
+/// // Do something else in the meantime...
+/// let module = compiling.wait()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct CompilingModule {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    finished: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    result: Option<Result<Module, CompileError>>,
+    waker: Option<Waker>,
+}
+
+impl CompilingModule {
+    pub(crate) fn spawn(
+        engine: &impl AsEngineRef,
+        bytes: impl AsRef<[u8]> + Send + 'static,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            finished: Condvar::new(),
+        });
+        let engine = engine.as_engine_ref().engine().clone();
+        let thread_shared = shared.clone();
+        let spawned = thread::Builder::new()
+            .name("wasmer-compile".to_string())
+            .spawn(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| Module::new(&engine, bytes)))
+                    .unwrap_or_else(|_| {
+                        Err(CompileError::Codegen(
+                            "the compilation panicked".to_string(),
+                        ))
+                    });
+                thread_shared.finish(result);
+            });
+        if let Err(e) = spawned {
+            shared.finish(Err(CompileError::Resource(format!(
+                "failed to spawn the compilation thread: {}",
+                e
+            ))));
+        }
+        Self { shared }
+    }
+
+    /// Returns whether the compilation is finished, in which case
+    /// neither awaiting the module nor [`CompilingModule::wait`] block.
+    pub fn is_finished(&self) -> bool {
+        self.shared.state.lock().unwrap().result.is_some()
+    }
+
+    /// Blocks the current thread until the compilation is finished, and
+    /// returns the compiled module.
+    ///
+    /// This shouldn't be called from an async context, where the
+    /// `CompilingModule` is meant to be awaited instead.
+    pub fn wait(self) -> Result<Module, CompileError> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(result) = state.result.take() {
+                return result;
+            }
+            state = self.shared.finished.wait(state).unwrap();
+        }
+    }
+}
+
+impl Shared {
+    fn finish(&self, result: Result<Module, CompileError>) {
+        let waker = {
+            let mut state = self.state.lock().unwrap();
+            state.result = Some(result);
+            state.waker.take()
+        };
+        self.finished.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl Future for CompilingModule {
+    type Output = Result<Module, CompileError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl fmt::Debug for CompilingModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompilingModule")
+            .field("finished", &self.is_finished())
+            .finish()
+    }
+}
@@ -0,0 +1,867 @@
+use crate::sys::exports::ExportError;
+use crate::sys::externals::{Function, Memory};
+use crate::sys::instance::Instance;
+use crate::sys::mem_access::MemoryAccessError;
+use crate::sys::native::TypedFunction;
+use crate::sys::store::{AsStoreMut, AsStoreRef};
+use crate::sys::value::Value;
+use crate::sys::RuntimeError;
+use std::convert::TryFrom;
+use std::fmt;
+use thiserror::Error;
+use wasmer_types::{FunctionType, Type};
+
+/// The maximum number of core parameters of a function, above which its
+/// parameters are passed in the linear memory.
+const MAX_FLAT_PARAMS: usize = 16;
+
+/// The maximum number of core results of a function, above which its
+/// results are returned in the linear memory.
+const MAX_FLAT_RESULTS: usize = 1;
+
+/// The type of a value taken or returned by a function of a WIT
+/// interface.
+///
+/// Only the types with a representation in the linear memory are
+/// supported: the scalar types, strings, lists and records.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InterfaceType {
+    /// `bool`
+    Bool,
+    /// `s8`
+    S8,
+    /// `u8`
+    U8,
+    /// `s16`
+    S16,
+    /// `u16`
+    U16,
+    /// `s32`
+    S32,
+    /// `u32`
+    U32,
+    /// `s64`
+    S64,
+    /// `u64`
+    U64,
+    /// `float32`
+    Float32,
+    /// `float64`
+    Float64,
+    /// `char`
+    Char,
+    /// `string`, encoded in UTF-8.
+    String,
+    /// `list<T>`
+    List(Box<InterfaceType>),
+    /// `record { ... }`, with the names and types of its fields.
+    Record(Vec<(String, InterfaceType)>),
+}
+
+impl InterfaceType {
+    /// Returns the size of the values of this type in the linear memory.
+    pub fn size(&self) -> u32 {
+        match self {
+            Self::Bool | Self::S8 | Self::U8 => 1,
+            Self::S16 | Self::U16 => 2,
+            Self::S32 | Self::U32 | Self::Float32 | Self::Char => 4,
+            Self::S64 | Self::U64 | Self::Float64 => 8,
+            Self::String | Self::List(_) => 8,
+            Self::Record(fields) => Layout::of(fields.iter().map(|(_, ty)| ty)).size,
+        }
+    }
+
+    /// Returns the alignment of the values of this type in the linear
+    /// memory.
+    pub fn alignment(&self) -> u32 {
+        match self {
+            Self::Bool | Self::S8 | Self::U8 => 1,
+            Self::S16 | Self::U16 => 2,
+            Self::S32 | Self::U32 | Self::Float32 | Self::Char => 4,
+            Self::S64 | Self::U64 | Self::Float64 => 8,
+            Self::String | Self::List(_) => 4,
+            Self::Record(fields) => Layout::of(fields.iter().map(|(_, ty)| ty)).align,
+        }
+    }
+
+    /// Appends the core types the values of this type are passed as.
+    fn flatten(&self, flat: &mut Vec<Type>) {
+        match self {
+            Self::Bool
+            | Self::S8
+            | Self::U8
+            | Self::S16
+            | Self::U16
+            | Self::S32
+            | Self::U32
+            | Self::Char => flat.push(Type::I32),
+            Self::S64 | Self::U64 => flat.push(Type::I64),
+            Self::Float32 => flat.push(Type::F32),
+            Self::Float64 => flat.push(Type::F64),
+            Self::String | Self::List(_) => flat.extend([Type::I32, Type::I32]),
+            Self::Record(fields) => fields.iter().for_each(|(_, ty)| ty.flatten(flat)),
+        }
+    }
+}
+
+impl fmt::Display for InterfaceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool => write!(f, "bool"),
+            Self::S8 => write!(f, "s8"),
+            Self::U8 => write!(f, "u8"),
+            Self::S16 => write!(f, "s16"),
+            Self::U16 => write!(f, "u16"),
+            Self::S32 => write!(f, "s32"),
+            Self::U32 => write!(f, "u32"),
+            Self::S64 => write!(f, "s64"),
+            Self::U64 => write!(f, "u64"),
+            Self::Float32 => write!(f, "float32"),
+            Self::Float64 => write!(f, "float64"),
+            Self::Char => write!(f, "char"),
+            Self::String => write!(f, "string"),
+            Self::List(ty) => write!(f, "list<{}>", ty),
+            Self::Record(fields) => {
+                write!(f, "record {{")?;
+                for (i, (name, ty)) in fields.iter().enumerate() {
+                    let sep = if i == 0 { " " } else { ", " };
+                    write!(f, "{}{}: {}", sep, name, ty)?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
+/// A value taken or returned by a function of a WIT interface.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InterfaceValue {
+    /// A `bool`.
+    Bool(bool),
+    /// An `s8`.
+    S8(i8),
+    /// A `u8`.
+    U8(u8),
+    /// An `s16`.
+    S16(i16),
+    /// A `u16`.
+    U16(u16),
+    /// An `s32`.
+    S32(i32),
+    /// A `u32`.
+    U32(u32),
+    /// An `s64`.
+    S64(i64),
+    /// A `u64`.
+    U64(u64),
+    /// A `float32`.
+    Float32(f32),
+    /// A `float64`.
+    Float64(f64),
+    /// A `char`.
+    Char(char),
+    /// A `string`.
+    String(String),
+    /// A `list`.
+    List(Vec<InterfaceValue>),
+    /// A `record`, with the values of its fields in the order of its type.
+    Record(Vec<InterfaceValue>),
+}
+
+macro_rules! interface_value_from {
+    ($($ty:ty => $variant:ident),*) => ($(
+        impl From<$ty> for InterfaceValue {
+            fn from(val: $ty) -> Self {
+                Self::$variant(val)
+            }
+        }
+    )*)
+}
+
+interface_value_from!(
+    bool => Bool, i8 => S8, u8 => U8, i16 => S16, u16 => U16, i32 => S32, u32 => U32,
+    i64 => S64, u64 => U64, f32 => Float32, f64 => Float64, char => Char, String => String
+);
+
+impl From<&str> for InterfaceValue {
+    fn from(val: &str) -> Self {
+        Self::String(val.to_string())
+    }
+}
+
+/// An error while calling a function through a [`CanonicalFunction`].
+#[derive(Error, Debug)]
+pub enum CanonicalError {
+    /// An export the function needs is missing or has the wrong type.
+    #[error(transparent)]
+    Export(#[from] ExportError),
+
+    /// The guest trapped.
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+
+    /// A value doesn't fit in the memory of the guest, or a string
+    /// returned by the guest isn't valid UTF-8.
+    #[error(transparent)]
+    MemoryAccess(#[from] MemoryAccessError),
+
+    /// The core function doesn't have the type the interface function
+    /// is lowered to.
+    #[error("the core function has the type {given}, expected {expected}")]
+    Signature {
+        /// The type the interface function is lowered to.
+        expected: FunctionType,
+        /// The type of the core function.
+        given: FunctionType,
+    },
+
+    /// The number of arguments doesn't match the number of parameters.
+    #[error("expected {expected} arguments, got {given}")]
+    Arity {
+        /// The number of parameters.
+        expected: usize,
+        /// The number of arguments.
+        given: usize,
+    },
+
+    /// An argument doesn't have the type of its parameter.
+    #[error("expected a value of type {expected}, got {given:?}")]
+    Type {
+        /// The expected type.
+        expected: InterfaceType,
+        /// The value given instead.
+        given: InterfaceValue,
+    },
+
+    /// The guest returned a value which isn't valid for its type, or a
+    /// misaligned pointer.
+    #[error("invalid value returned by the guest: {0}")]
+    InvalidValue(String),
+}
+
+/// A function exported by a core module implementing a function of a
+/// WIT interface, called with [`InterfaceValue`]s following the
+/// canonical ABI of the component model.
+///
+/// The values are lowered to core values and lifted back as the
+/// canonical ABI describes: scalars are passed as core values, strings
+/// and lists as a pointer and a length into the linear memory, and
+/// records field by field. Parameters which would take more than 16
+/// core values, and results which would take more than one, are passed
+/// in the linear memory instead.
+///
+/// The module must export:
+/// - its memory, as `memory`, and its allocator, as `cabi_realloc`, of
+///   type `(old_ptr: i32, old_len: i32, align: i32, new_len: i32) -> i32`,
+///   when strings, lists or parameters are passed in the memory,
+/// - optionally, a function named `cabi_post_` followed by the name of
+///   the function, which is called with the core results once they're
+///   lifted, for the guest to free them.
+///
+/// Component binaries aren't supported, see [`is_component`]: the
+/// function has to be exported by one of the core modules the component
+/// embeds, such as the modules built by `wit-bindgen` before they're
+/// wrapped into a component.
+///
+/// [`is_component`]: crate::is_component
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::{
+/// #     imports, CanonicalFunction, Instance, InterfaceType, InterfaceValue, Module, Store,
+/// # };
+/// # fn main() -> anyhow::Result<()> {
+/// let mut store = Store::default();
+/// let module = Module::new(&store, r#"
+///     (module
+///         (memory (export "memory") 1)
+///         (global $next (mut i32) (i32.const 16))
+///         (func (export "cabi_realloc") (param i32 i32 i32 i32) (result i32)
+///             (global.get $next)
+///             (global.set $next (i32.add (global.get $next) (local.get 3))))
+///         ;; len: func(s: string) -> u32
+///         (func (export "len") (param $ptr i32) (param $len i32) (result i32)
+///             (local.get $len)))
+/// "#)?;
+/// let instance = Instance::new(&mut store, &module, &imports! {})?;
+/// let len = CanonicalFunction::new(
+///     &store,
+///     &instance,
+///     "len",
+///     vec![InterfaceType::String],
+///     vec![InterfaceType::U32],
+/// )?;
+/// assert_eq!(
+///     len.call(&mut store, &["hello".into()])?,
+///     [InterfaceValue::U32(5)]
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CanonicalFunction {
+    func: Function,
+    post_return: Option<Function>,
+    memory: Option<Memory>,
+    realloc: Option<TypedFunction<(u32, u32, u32, u32), u32>>,
+    params: Vec<InterfaceType>,
+    results: Vec<InterfaceType>,
+    params_in_memory: bool,
+    results_in_memory: bool,
+}
+
+impl CanonicalFunction {
+    /// Looks up the function exported as `name` by `instance`, which
+    /// implements an interface function with the given parameter and
+    /// result types.
+    pub fn new(
+        store: &impl AsStoreRef,
+        instance: &Instance,
+        name: &str,
+        params: Vec<InterfaceType>,
+        results: Vec<InterfaceType>,
+    ) -> Result<Self, CanonicalError> {
+        let exports = &instance.exports;
+        let func = exports.get_function(name)?.clone();
+
+        let flat_params = flatten(&params);
+        let flat_results = flatten(&results);
+        let params_in_memory = flat_params.len() > MAX_FLAT_PARAMS;
+        let results_in_memory = flat_results.len() > MAX_FLAT_RESULTS;
+        let expected = FunctionType::new(
+            if params_in_memory {
+                vec![Type::I32]
+            } else {
+                flat_params
+            },
+            if results_in_memory {
+                vec![Type::I32]
+            } else {
+                flat_results
+            },
+        );
+        let given = func.ty(store);
+        if given != expected {
+            return Err(CanonicalError::Signature { expected, given });
+        }
+
+        let post_return = optional(exports.get_function(&format!("cabi_post_{}", name)))?;
+        if let Some(post_return) = &post_return {
+            let expected = FunctionType::new(expected.results(), vec![]);
+            let given = post_return.ty(store);
+            if given != expected {
+                return Err(CanonicalError::Signature { expected, given });
+            }
+        }
+
+        Ok(Self {
+            func,
+            post_return: post_return.cloned(),
+            memory: optional(exports.get_memory("memory"))?.cloned(),
+            realloc: optional(exports.get_typed_function(store, "cabi_realloc"))?,
+            params,
+            results,
+            params_in_memory,
+            results_in_memory,
+        })
+    }
+
+    /// Returns the types of the parameters of the function.
+    pub fn params(&self) -> &[InterfaceType] {
+        &self.params
+    }
+
+    /// Returns the types of the results of the function.
+    pub fn results(&self) -> &[InterfaceType] {
+        &self.results
+    }
+
+    /// Calls the function with `args`, and returns its results.
+    pub fn call(
+        &self,
+        store: &mut impl AsStoreMut,
+        args: &[InterfaceValue],
+    ) -> Result<Vec<InterfaceValue>, CanonicalError> {
+        if args.len() != self.params.len() {
+            return Err(CanonicalError::Arity {
+                expected: self.params.len(),
+                given: args.len(),
+            });
+        }
+
+        let mut flat = Vec::new();
+        if self.params_in_memory {
+            let layout = Layout::of(&self.params);
+            let mut bytes = vec![0; layout.size as usize];
+            for ((ty, value), offset) in self.params.iter().zip(args).zip(layout.offsets) {
+                let range = offset as usize..(offset + ty.size()) as usize;
+                self.store(store, ty, value, &mut bytes[range])?;
+            }
+            let ptr = self.lower_bytes(store, &bytes, layout.align)?;
+            flat.push(Value::I32(ptr as i32));
+        } else {
+            for (ty, value) in self.params.iter().zip(args) {
+                self.lower_flat(store, ty, value, &mut flat)?;
+            }
+        }
+
+        let core_results = self.func.call(store, &flat)?;
+
+        let results = if self.results_in_memory {
+            let ptr = next_flat(&mut core_results.iter().cloned(), Value::i32)? as u32;
+            let layout = Layout::of(&self.results);
+            let bytes = self.read(&*store, ptr, layout.size, layout.align)?;
+            self.results
+                .iter()
+                .zip(layout.offsets)
+                .map(|(ty, offset)| {
+                    let range = offset as usize..(offset + ty.size()) as usize;
+                    self.load(&*store, ty, &bytes[range])
+                })
+                .collect::<Result<_, _>>()?
+        } else {
+            let mut flat = core_results.iter().cloned();
+            self.results
+                .iter()
+                .map(|ty| self.lift_flat(&*store, ty, &mut flat))
+                .collect::<Result<_, _>>()?
+        };
+
+        if let Some(post_return) = &self.post_return {
+            post_return.call(store, &core_results)?;
+        }
+        Ok(results)
+    }
+
+    fn memory(&self) -> Result<&Memory, CanonicalError> {
+        self.memory
+            .as_ref()
+            .ok_or_else(|| ExportError::Missing("memory".to_string()).into())
+    }
+
+    /// Appends the core values `value` is passed as.
+    fn lower_flat(
+        &self,
+        store: &mut impl AsStoreMut,
+        ty: &InterfaceType,
+        value: &InterfaceValue,
+        flat: &mut Vec<Value>,
+    ) -> Result<(), CanonicalError> {
+        use InterfaceType as T;
+        use InterfaceValue as V;
+        match (ty, value) {
+            (T::Bool, V::Bool(v)) => flat.push(Value::I32(*v as i32)),
+            (T::S8, V::S8(v)) => flat.push(Value::I32(*v as i32)),
+            (T::U8, V::U8(v)) => flat.push(Value::I32(*v as i32)),
+            (T::S16, V::S16(v)) => flat.push(Value::I32(*v as i32)),
+            (T::U16, V::U16(v)) => flat.push(Value::I32(*v as i32)),
+            (T::S32, V::S32(v)) => flat.push(Value::I32(*v)),
+            (T::U32, V::U32(v)) => flat.push(Value::I32(*v as i32)),
+            (T::S64, V::S64(v)) => flat.push(Value::I64(*v)),
+            (T::U64, V::U64(v)) => flat.push(Value::I64(*v as i64)),
+            (T::Float32, V::Float32(v)) => flat.push(Value::F32(*v)),
+            (T::Float64, V::Float64(v)) => flat.push(Value::F64(*v)),
+            (T::Char, V::Char(v)) => flat.push(Value::I32(*v as i32)),
+            (T::String, V::String(s)) => {
+                let len = len_u32(s.len())?;
+                let ptr = self.lower_bytes(store, s.as_bytes(), 1)?;
+                flat.extend([Value::I32(ptr as i32), Value::I32(len as i32)]);
+            }
+            (T::List(elem), V::List(values)) => {
+                let (ptr, len) = self.lower_list(store, elem, values)?;
+                flat.extend([Value::I32(ptr as i32), Value::I32(len as i32)]);
+            }
+            (T::Record(fields), V::Record(values)) if fields.len() == values.len() => {
+                for ((_, ty), value) in fields.iter().zip(values) {
+                    self.lower_flat(store, ty, value, flat)?;
+                }
+            }
+            _ => return Err(type_error(ty, value)),
+        }
+        Ok(())
+    }
+
+    /// Stores `value` in `bytes`, which has the size of `ty`.
+    fn store(
+        &self,
+        store: &mut impl AsStoreMut,
+        ty: &InterfaceType,
+        value: &InterfaceValue,
+        bytes: &mut [u8],
+    ) -> Result<(), CanonicalError> {
+        use InterfaceType as T;
+        use InterfaceValue as V;
+        match (ty, value) {
+            (T::Bool, V::Bool(v)) => bytes[0] = *v as u8,
+            (T::S8, V::S8(v)) => bytes.copy_from_slice(&v.to_le_bytes()),
+            (T::U8, V::U8(v)) => bytes.copy_from_slice(&v.to_le_bytes()),
+            (T::S16, V::S16(v)) => bytes.copy_from_slice(&v.to_le_bytes()),
+            (T::U16, V::U16(v)) => bytes.copy_from_slice(&v.to_le_bytes()),
+            (T::S32, V::S32(v)) => bytes.copy_from_slice(&v.to_le_bytes()),
+            (T::U32, V::U32(v)) => bytes.copy_from_slice(&v.to_le_bytes()),
+            (T::S64, V::S64(v)) => bytes.copy_from_slice(&v.to_le_bytes()),
+            (T::U64, V::U64(v)) => bytes.copy_from_slice(&v.to_le_bytes()),
+            (T::Float32, V::Float32(v)) => bytes.copy_from_slice(&v.to_le_bytes()),
+            (T::Float64, V::Float64(v)) => bytes.copy_from_slice(&v.to_le_bytes()),
+            (T::Char, V::Char(v)) => bytes.copy_from_slice(&(*v as u32).to_le_bytes()),
+            (T::String, V::String(s)) => {
+                let len = len_u32(s.len())?;
+                let ptr = self.lower_bytes(store, s.as_bytes(), 1)?;
+                store_pair(bytes, ptr, len);
+            }
+            (T::List(elem), V::List(values)) => {
+                let (ptr, len) = self.lower_list(store, elem, values)?;
+                store_pair(bytes, ptr, len);
+            }
+            (T::Record(fields), V::Record(values)) if fields.len() == values.len() => {
+                let layout = Layout::of(fields.iter().map(|(_, ty)| ty));
+                for (((_, ty), value), offset) in fields.iter().zip(values).zip(layout.offsets) {
+                    let range = offset as usize..(offset + ty.size()) as usize;
+                    self.store(store, ty, value, &mut bytes[range])?;
+                }
+            }
+            _ => return Err(type_error(ty, value)),
+        }
+        Ok(())
+    }
+
+    /// Copies the elements of a list to the memory of the guest, and
+    /// returns its pointer and length.
+    fn lower_list(
+        &self,
+        store: &mut impl AsStoreMut,
+        elem: &InterfaceType,
+        values: &[InterfaceValue],
+    ) -> Result<(u32, u32), CanonicalError> {
+        let len = len_u32(values.len())?;
+        let size = elem.size() as usize;
+        let byte_len = values
+            .len()
+            .checked_mul(size)
+            .ok_or(MemoryAccessError::Overflow)?;
+        len_u32(byte_len)?;
+        let mut bytes = vec![0; byte_len];
+        for (i, value) in values.iter().enumerate() {
+            self.store(store, elem, value, &mut bytes[i * size..(i + 1) * size])?;
+        }
+        let ptr = self.lower_bytes(store, &bytes, elem.alignment())?;
+        Ok((ptr, len))
+    }
+
+    /// Copies `bytes` to a buffer allocated in the guest with
+    /// `cabi_realloc`, and returns its pointer.
+    fn lower_bytes(
+        &self,
+        store: &mut impl AsStoreMut,
+        bytes: &[u8],
+        align: u32,
+    ) -> Result<u32, CanonicalError> {
+        let realloc = self
+            .realloc
+            .as_ref()
+            .ok_or_else(|| ExportError::Missing("cabi_realloc".to_string()))?;
+        let ptr = realloc.call(store, 0, 0, align, len_u32(bytes.len())?)?;
+        if ptr % align != 0 {
+            return Err(CanonicalError::InvalidValue(format!(
+                "cabi_realloc returned the pointer {}, which isn't aligned to {}",
+                ptr, align
+            )));
+        }
+        self.memory()?.view(&*store).write(ptr as u64, bytes)?;
+        Ok(ptr)
+    }
+
+    /// Lifts a value of type `ty` from the core values it's passed as.
+    fn lift_flat(
+        &self,
+        store: &impl AsStoreRef,
+        ty: &InterfaceType,
+        flat: &mut impl Iterator<Item = Value>,
+    ) -> Result<InterfaceValue, CanonicalError> {
+        use InterfaceType as T;
+        use InterfaceValue as V;
+        Ok(match ty {
+            T::Bool => V::Bool(next_flat(flat, Value::i32)? != 0),
+            T::S8 => V::S8(next_flat(flat, Value::i32)? as i8),
+            T::U8 => V::U8(next_flat(flat, Value::i32)? as u8),
+            T::S16 => V::S16(next_flat(flat, Value::i32)? as i16),
+            T::U16 => V::U16(next_flat(flat, Value::i32)? as u16),
+            T::S32 => V::S32(next_flat(flat, Value::i32)?),
+            T::U32 => V::U32(next_flat(flat, Value::i32)? as u32),
+            T::S64 => V::S64(next_flat(flat, Value::i64)?),
+            T::U64 => V::U64(next_flat(flat, Value::i64)? as u64),
+            T::Float32 => V::Float32(next_flat(flat, Value::f32)?),
+            T::Float64 => V::Float64(next_flat(flat, Value::f64)?),
+            T::Char => V::Char(lift_char(next_flat(flat, Value::i32)? as u32)?),
+            T::String | T::List(_) => {
+                let ptr = next_flat(flat, Value::i32)? as u32;
+                let len = next_flat(flat, Value::i32)? as u32;
+                self.lift_pair(store, ty, ptr, len)?
+            }
+            T::Record(fields) => V::Record(
+                fields
+                    .iter()
+                    .map(|(_, ty)| self.lift_flat(store, ty, flat))
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+
+    /// Loads a value of type `ty` from `bytes`, which has its size.
+    fn load(
+        &self,
+        store: &impl AsStoreRef,
+        ty: &InterfaceType,
+        bytes: &[u8],
+    ) -> Result<InterfaceValue, CanonicalError> {
+        use InterfaceType as T;
+        use InterfaceValue as V;
+        Ok(match ty {
+            T::Bool => V::Bool(bytes[0] != 0),
+            T::S8 => V::S8(bytes[0] as i8),
+            T::U8 => V::U8(bytes[0]),
+            T::S16 => V::S16(i16::from_le_bytes(array(bytes))),
+            T::U16 => V::U16(u16::from_le_bytes(array(bytes))),
+            T::S32 => V::S32(i32::from_le_bytes(array(bytes))),
+            T::U32 => V::U32(u32::from_le_bytes(array(bytes))),
+            T::S64 => V::S64(i64::from_le_bytes(array(bytes))),
+            T::U64 => V::U64(u64::from_le_bytes(array(bytes))),
+            T::Float32 => V::Float32(f32::from_le_bytes(array(bytes))),
+            T::Float64 => V::Float64(f64::from_le_bytes(array(bytes))),
+            T::Char => V::Char(lift_char(u32::from_le_bytes(array(bytes)))?),
+            T::String | T::List(_) => {
+                let ptr = u32::from_le_bytes(array(&bytes[..4]));
+                let len = u32::from_le_bytes(array(&bytes[4..]));
+                self.lift_pair(store, ty, ptr, len)?
+            }
+            T::Record(fields) => {
+                let layout = Layout::of(fields.iter().map(|(_, ty)| ty));
+                V::Record(
+                    fields
+                        .iter()
+                        .zip(layout.offsets)
+                        .map(|((_, ty), offset)| {
+                            let range = offset as usize..(offset + ty.size()) as usize;
+                            self.load(store, ty, &bytes[range])
+                        })
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+        })
+    }
+
+    /// Lifts the string or list at `ptr`, of `len` bytes or elements.
+    fn lift_pair(
+        &self,
+        store: &impl AsStoreRef,
+        ty: &InterfaceType,
+        ptr: u32,
+        len: u32,
+    ) -> Result<InterfaceValue, CanonicalError> {
+        match ty {
+            InterfaceType::List(elem) => {
+                let size = elem.size();
+                // The length of a list of zero-sized elements isn't bounded
+                // by the size of the memory.
+                if size == 0 && len != 0 {
+                    return Err(CanonicalError::InvalidValue(format!(
+                        "the list of {} has {} zero-sized elements",
+                        elem, len
+                    )));
+                }
+                let byte_len = len.checked_mul(size).ok_or(MemoryAccessError::Overflow)?;
+                let bytes = self.read(store, ptr, byte_len, elem.alignment())?;
+                let size = size as usize;
+                let values = (0..len as usize)
+                    .map(|i| self.load(store, elem, &bytes[i * size..(i + 1) * size]))
+                    .collect::<Result<_, _>>()?;
+                Ok(InterfaceValue::List(values))
+            }
+            _ => {
+                let bytes = self.read(store, ptr, len, 1)?;
+                let s = String::from_utf8(bytes).map_err(|_| MemoryAccessError::NonUtf8String)?;
+                Ok(InterfaceValue::String(s))
+            }
+        }
+    }
+
+    /// Copies the `len` bytes at `ptr`, which must be aligned to
+    /// `align`, out of the guest's memory.
+    fn read(
+        &self,
+        store: &impl AsStoreRef,
+        ptr: u32,
+        len: u32,
+        align: u32,
+    ) -> Result<Vec<u8>, CanonicalError> {
+        if ptr % align != 0 {
+            return Err(CanonicalError::InvalidValue(format!(
+                "the pointer {} isn't aligned to {}",
+                ptr, align
+            )));
+        }
+        let view = self.memory()?.view(store);
+        let end = u64::from(ptr)
+            .checked_add(u64::from(len))
+            .ok_or(MemoryAccessError::Overflow)?;
+        if end > view.data_size() {
+            return Err(MemoryAccessError::HeapOutOfBounds.into());
+        }
+        let mut bytes = vec![0; len as usize];
+        view.read(ptr as u64, &mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+impl fmt::Debug for CanonicalFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CanonicalFunction")
+            .field("params", &self.params)
+            .field("results", &self.results)
+            .finish()
+    }
+}
+
+/// The layout of the fields of a record, or of the parameters or results
+/// of a function passed in the linear memory.
+struct Layout {
+    offsets: Vec<u32>,
+    size: u32,
+    align: u32,
+}
+
+impl Layout {
+    fn of<'a>(types: impl IntoIterator<Item = &'a InterfaceType>) -> Self {
+        let mut offsets = Vec::new();
+        let mut size = 0;
+        let mut align = 1;
+        for ty in types {
+            let ty_align = ty.alignment();
+            size = align_to(size, ty_align);
+            offsets.push(size);
+            size += ty.size();
+            align = align.max(ty_align);
+        }
+        Self {
+            offsets,
+            size: align_to(size, align),
+            align,
+        }
+    }
+}
+
+fn align_to(offset: u32, align: u32) -> u32 {
+    (offset + align - 1) & !(align - 1)
+}
+
+fn flatten(types: &[InterfaceType]) -> Vec<Type> {
+    let mut flat = Vec::new();
+    for ty in types {
+        ty.flatten(&mut flat);
+    }
+    flat
+}
+
+/// Turns a missing export into `None`.
+fn optional<T>(export: Result<T, ExportError>) -> Result<Option<T>, CanonicalError> {
+    match export {
+        Ok(export) => Ok(Some(export)),
+        Err(ExportError::Missing(_)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn type_error(ty: &InterfaceType, value: &InterfaceValue) -> CanonicalError {
+    CanonicalError::Type {
+        expected: ty.clone(),
+        given: value.clone(),
+    }
+}
+
+fn len_u32(len: usize) -> Result<u32, CanonicalError> {
+    u32::try_from(len).map_err(|_| MemoryAccessError::Overflow.into())
+}
+
+fn store_pair(bytes: &mut [u8], ptr: u32, len: u32) {
+    bytes[..4].copy_from_slice(&ptr.to_le_bytes());
+    bytes[4..].copy_from_slice(&len.to_le_bytes());
+}
+
+fn array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    <[u8; N]>::try_from(bytes).unwrap()
+}
+
+fn lift_char(code: u32) -> Result<char, CanonicalError> {
+    char::from_u32(code)
+        .ok_or_else(|| CanonicalError::InvalidValue(format!("{:#x} isn't a valid char", code)))
+}
+
+/// Takes the next core value, of the type `get` returns.
+fn next_flat<T>(
+    flat: &mut impl Iterator<Item = Value>,
+    get: impl FnOnce(&Value) -> Option<T>,
+) -> Result<T, CanonicalError> {
+    flat.next()
+        .as_ref()
+        .and_then(get)
+        .ok_or_else(|| CanonicalError::InvalidValue("missing core value".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(fields: &[InterfaceType]) -> InterfaceType {
+        InterfaceType::Record(
+            fields
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| (format!("f{}", i), ty.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn layout() {
+        use InterfaceType::*;
+        assert_eq!((String.size(), String.alignment()), (8, 4));
+        assert_eq!((record(&[]).size(), record(&[]).alignment()), (0, 1));
+
+        let ty = record(&[U8, U32, U16]);
+        assert_eq!((ty.size(), ty.alignment()), (12, 4));
+        let ty = record(&[U8, record(&[U64, Bool])]);
+        assert_eq!((ty.size(), ty.alignment()), (24, 8));
+        assert_eq!(Layout::of(&[U8, S16, Float64]).offsets, [0, 2, 8]);
+    }
+
+    #[test]
+    fn flattening() {
+        use InterfaceType::*;
+        let ty = record(&[Bool, List(Box::new(U8)), S64, Float32]);
+        assert_eq!(
+            flatten(&[ty, Float64]),
+            [
+                Type::I32,
+                Type::I32,
+                Type::I32,
+                Type::I64,
+                Type::F32,
+                Type::F64
+            ]
+        );
+    }
+
+    #[test]
+    fn display() {
+        use InterfaceType::*;
+        let ty = Record(vec![
+            ("name".to_string(), String),
+            ("scores".to_string(), List(Box::new(Float32))),
+        ]);
+        assert_eq!(
+            ty.to_string(),
+            "record { name: string, scores: list<float32> }"
+        );
+    }
+}
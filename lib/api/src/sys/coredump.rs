@@ -0,0 +1,240 @@
+use crate::sys::encoding::{DecodeError, Reader, Writer};
+use crate::sys::externals::Extern;
+use crate::sys::instance::Instance;
+use crate::sys::store::AsStoreMut;
+use crate::sys::{MemoryAccessError, RuntimeError, Value};
+use thiserror::Error;
+use wasmer_types::entity::EntityRef;
+use wasmer_types::{ExportIndex, GlobalIndex, MemoryIndex};
+
+/// The magic number at the start of the serialized core dumps.
+const MAGIC: [u8; 8] = *b"\0wasmcd\0";
+
+/// The version of the format of the serialized core dumps.
+const VERSION: u32 = 1;
+
+/// A post-mortem snapshot of an instance which trapped, to examine it
+/// offline: the trap, the WebAssembly backtrace, the values of the
+/// globals and the contents of the memories.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::{imports, CoreDump, Instance, Module, Store, TypedFunction};
+/// # fn main() -> anyhow::Result<()> {
+/// let mut store = Store::default();
+/// let module = Module::new(&store, "(module (memory 1) (func (export \"run\") unreachable))")?;
+/// let instance = Instance::new(&mut store, &module, &imports! {})?;
+/// let run: TypedFunction<(), ()> = instance.exports.get_typed_function(&store, "run")?;
+/// let error = run.call(&mut store).unwrap_err();
+///
+/// let dump = CoreDump::capture(&mut store, &instance, &error)?;
+/// let bytes = dump.serialize();
+/// assert_eq!(CoreDump::deserialize(&bytes)?, dump);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreDump {
+    /// The name of the module of the instance.
+    pub module_name: String,
+    /// A hash of the module, set by the embedder, to match the dump with
+    /// the module it comes from.
+    pub module_hash: Option<String>,
+    /// The message of the trap.
+    pub message: String,
+    /// The WebAssembly frames of the trap, innermost first.
+    pub frames: Vec<CoreDumpFrame>,
+    /// The values of the globals, imported ones included, by index.
+    pub globals: Vec<CoreDumpValue>,
+    /// The contents of the memories, imported ones included, by index.
+    pub memories: Vec<Vec<u8>>,
+}
+
+/// A WebAssembly frame of a [`CoreDump`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreDumpFrame {
+    /// The index of the function.
+    pub func_index: u32,
+    /// The name of the function, if known.
+    pub function_name: Option<String>,
+    /// The offset of the instruction in the module.
+    pub module_offset: usize,
+    /// The offset of the instruction in the function.
+    pub func_offset: usize,
+}
+
+/// The value of a global in a [`CoreDump`].
+///
+/// The references can't outlive their store, so only whether they
+/// are null is kept.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoreDumpValue {
+    /// A 32-bit integer.
+    I32(i32),
+    /// A 64-bit integer.
+    I64(i64),
+    /// A 32-bit float.
+    F32(f32),
+    /// A 64-bit float.
+    F64(f64),
+    /// A 128-bit vector.
+    V128(u128),
+    /// A function reference, non-null if `true`.
+    FuncRef(bool),
+    /// An external reference, non-null if `true`.
+    ExternRef(bool),
+}
+
+impl From<&Value> for CoreDumpValue {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::I32(value) => Self::I32(*value),
+            Value::I64(value) => Self::I64(*value),
+            Value::F32(value) => Self::F32(*value),
+            Value::F64(value) => Self::F64(*value),
+            Value::V128(value) => Self::V128(*value),
+            Value::FuncRef(value) => Self::FuncRef(value.is_some()),
+            Value::ExternRef(value) => Self::ExternRef(value.is_some()),
+        }
+    }
+}
+
+/// An error while reading a serialized [`CoreDump`].
+#[derive(Error, Debug)]
+pub enum CoreDumpError {
+    /// The data isn't a core dump.
+    #[error("the data isn't a WebAssembly core dump")]
+    InvalidMagic,
+    /// The core dump was written in another version of the format.
+    #[error("unsupported core dump version {0} (expected {})", VERSION)]
+    UnsupportedVersion(u32),
+    /// The core dump is truncated or corrupted.
+    #[error("the core dump is corrupted")]
+    Corrupted,
+}
+
+impl From<DecodeError> for CoreDumpError {
+    fn from(_: DecodeError) -> Self {
+        Self::Corrupted
+    }
+}
+
+impl CoreDump {
+    /// Takes a snapshot of `instance` after it trapped with `error`.
+    pub fn capture(
+        store: &mut impl AsStoreMut,
+        instance: &Instance,
+        error: &RuntimeError,
+    ) -> Result<Self, MemoryAccessError> {
+        let info = instance.module().info();
+        let globals = (0..info.globals.len())
+            .map(|index| {
+                match instance
+                    .lookup_by_declaration(store, ExportIndex::Global(GlobalIndex::new(index)))
+                {
+                    Extern::Global(global) => CoreDumpValue::from(&global.get(store)),
+                    _ => unreachable!("the index of a global doesn't lead to a global"),
+                }
+            })
+            .collect();
+        let memories = (0..info.memories.len())
+            .map(|index| {
+                match instance
+                    .lookup_by_declaration(store, ExportIndex::Memory(MemoryIndex::new(index)))
+                {
+                    Extern::Memory(memory) => memory.view(store).copy_to_vec(),
+                    _ => unreachable!("the index of a memory doesn't lead to a memory"),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            module_name: info.name(),
+            module_hash: None,
+            message: error.message(),
+            frames: error
+                .trace()
+                .iter()
+                .map(|frame| CoreDumpFrame {
+                    func_index: frame.func_index(),
+                    function_name: frame.function_name().map(ToString::to_string),
+                    module_offset: frame.module_offset(),
+                    func_offset: frame.func_offset(),
+                })
+                .collect(),
+            globals,
+            memories,
+        })
+    }
+
+    /// Sets the hash of the module the dump comes from.
+    pub fn with_module_hash(mut self, module_hash: impl Into<String>) -> Self {
+        self.module_hash = Some(module_hash.into());
+        self
+    }
+
+    /// Serializes the dump, to be written to a file.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut writer = Writer::new(&MAGIC);
+        writer.u32(VERSION);
+        writer.string(&self.module_name);
+        writer.optional_string(self.module_hash.as_deref());
+        writer.string(&self.message);
+        writer.u32(self.frames.len() as u32);
+        for frame in &self.frames {
+            writer.u32(frame.func_index);
+            writer.optional_string(frame.function_name.as_deref());
+            writer.u64(frame.module_offset as u64);
+            writer.u64(frame.func_offset as u64);
+        }
+        writer.u32(self.globals.len() as u32);
+        for global in &self.globals {
+            writer.value(global);
+        }
+        writer.u32(self.memories.len() as u32);
+        for memory in &self.memories {
+            writer.bytes(memory);
+        }
+        writer.into_bytes()
+    }
+
+    /// Deserializes a dump written with [`CoreDump::serialize`].
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, CoreDumpError> {
+        let mut reader = Reader::new(bytes, &MAGIC).ok_or(CoreDumpError::InvalidMagic)?;
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(CoreDumpError::UnsupportedVersion(version));
+        }
+
+        let module_name = reader.string()?;
+        let module_hash = reader.optional_string()?;
+        let message = reader.string()?;
+        let frames = (0..reader.u32()?)
+            .map(|_| {
+                Ok(CoreDumpFrame {
+                    func_index: reader.u32()?,
+                    function_name: reader.optional_string()?,
+                    module_offset: reader.u64()? as usize,
+                    func_offset: reader.u64()? as usize,
+                })
+            })
+            .collect::<Result<_, DecodeError>>()?;
+        let globals = (0..reader.u32()?)
+            .map(|_| reader.value())
+            .collect::<Result<_, _>>()?;
+        let memories = (0..reader.u32()?)
+            .map(|_| reader.bytes().map(<[u8]>::to_vec))
+            .collect::<Result<_, _>>()?;
+        reader.finish()?;
+
+        Ok(Self {
+            module_name,
+            module_hash,
+            message,
+            frames,
+            globals,
+            memories,
+        })
+    }
+}
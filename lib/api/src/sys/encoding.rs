@@ -0,0 +1,153 @@
+//! The little-endian encoding shared by the files written by the
+//! runtime for offline use, like the core dumps and the execution
+//! logs.
+
+use crate::sys::coredump::CoreDumpValue;
+use std::convert::TryFrom;
+
+/// The data is truncated or holds an invalid field.
+#[derive(Debug)]
+pub(crate) struct DecodeError;
+
+/// Writes the fields of a file.
+pub(crate) struct Writer(Vec<u8>);
+
+impl Writer {
+    /// Starts a file with `magic`.
+    pub(crate) fn new(magic: &[u8]) -> Self {
+        Self(magic.to_vec())
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.u64(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn string(&mut self, string: &str) {
+        self.bytes(string.as_bytes());
+    }
+
+    pub(crate) fn optional_string(&mut self, string: Option<&str>) {
+        match string {
+            Some(string) => {
+                self.u8(1);
+                self.string(string);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    pub(crate) fn value(&mut self, value: &CoreDumpValue) {
+        match *value {
+            CoreDumpValue::I32(value) => self.tagged(0, &value.to_le_bytes()),
+            CoreDumpValue::I64(value) => self.tagged(1, &value.to_le_bytes()),
+            CoreDumpValue::F32(value) => self.tagged(2, &value.to_bits().to_le_bytes()),
+            CoreDumpValue::F64(value) => self.tagged(3, &value.to_bits().to_le_bytes()),
+            CoreDumpValue::V128(value) => self.tagged(4, &value.to_le_bytes()),
+            CoreDumpValue::FuncRef(non_null) => self.tagged(5, &[non_null as u8]),
+            CoreDumpValue::ExternRef(non_null) => self.tagged(6, &[non_null as u8]),
+        }
+    }
+
+    fn tagged(&mut self, tag: u8, bytes: &[u8]) {
+        self.u8(tag);
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+/// Reads the fields written by `Writer`.
+pub(crate) struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    /// Reads a file starting with `magic`, or returns `None` if it
+    /// doesn't.
+    pub(crate) fn new(bytes: &'a [u8], magic: &[u8]) -> Option<Self> {
+        if bytes.starts_with(magic) {
+            Some(Self(&bytes[magic.len()..]))
+        } else {
+            None
+        }
+    }
+
+    /// Checks that the whole file has been read.
+    pub(crate) fn finish(self) -> Result<(), DecodeError> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(DecodeError)
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.0.len() < len {
+            return Err(DecodeError);
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn bytes(&mut self) -> Result<&'a [u8], DecodeError> {
+        let len = usize::try_from(self.u64()?).map_err(|_| DecodeError)?;
+        self.take(len)
+    }
+
+    pub(crate) fn string(&mut self) -> Result<String, DecodeError> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| DecodeError)
+    }
+
+    pub(crate) fn optional_string(&mut self) -> Result<Option<String>, DecodeError> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.string()?)),
+            _ => Err(DecodeError),
+        }
+    }
+
+    pub(crate) fn value(&mut self) -> Result<CoreDumpValue, DecodeError> {
+        Ok(match self.u8()? {
+            0 => CoreDumpValue::I32(i32::from_le_bytes(self.array()?)),
+            1 => CoreDumpValue::I64(i64::from_le_bytes(self.array()?)),
+            2 => CoreDumpValue::F32(f32::from_bits(u32::from_le_bytes(self.array()?))),
+            3 => CoreDumpValue::F64(f64::from_bits(u64::from_le_bytes(self.array()?))),
+            4 => CoreDumpValue::V128(u128::from_le_bytes(self.array()?)),
+            5 => CoreDumpValue::FuncRef(self.u8()? != 0),
+            6 => CoreDumpValue::ExternRef(self.u8()? != 0),
+            _ => return Err(DecodeError),
+        })
+    }
+}
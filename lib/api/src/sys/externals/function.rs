@@ -48,6 +48,14 @@ pub struct Function {
     pub(crate) handle: StoreHandle<VMFunction>,
 }
 
+impl std::cmp::PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle == other.handle
+    }
+}
+
+impl std::cmp::Eq for Function {}
+
 impl From<StoreHandle<VMFunction>> for Function {
     fn from(handle: StoreHandle<VMFunction>) -> Self {
         Self { handle }
@@ -571,6 +579,8 @@ impl Function {
         store: &mut impl AsStoreMut,
         params: &[Value],
     ) -> Result<Box<[Value]>, RuntimeError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("call").entered();
         let trampoline = unsafe {
             self.handle
                 .get(store.as_store_ref().objects())
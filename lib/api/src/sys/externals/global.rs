@@ -5,7 +5,12 @@ use crate::sys::value::Value;
 use crate::sys::GlobalType;
 use crate::sys::Mutability;
 use crate::sys::RuntimeError;
-use wasmer_vm::{InternalStoreHandle, StoreHandle, VMExtern, VMGlobal};
+use std::ptr::NonNull;
+use std::sync::Arc;
+use wasmer_vm::{
+    InternalStoreHandle, StoreHandle, VMExtern, VMGlobal, VMGlobalDefinition,
+    VMSharedGlobalDefinition,
+};
 
 /// A WebAssembly `global` instance.
 ///
@@ -199,6 +204,28 @@ impl Global {
         }
     }
 
+    /// Returns a pointer to the storage of the value of the global, as
+    /// it is read and written by the generated code.
+    ///
+    /// The pointer stays valid as long as the store the global belongs
+    /// to is alive. It allows to update the global from outside of the
+    /// store, e.g. from another thread, with atomic accesses.
+    pub fn vm_definition(&self, store: &impl AsStoreRef) -> NonNull<VMGlobalDefinition> {
+        self.handle.get(store.as_store_ref().objects()).vmglobal()
+    }
+
+    /// Returns the storage of the value of the global, as it is read and
+    /// written by the generated code.
+    ///
+    /// Unlike [`Global::vm_definition`], the storage stays allocated as
+    /// long as the returned reference is alive, even after the store is
+    /// dropped.
+    pub fn shared_definition(&self, store: &impl AsStoreRef) -> Arc<VMSharedGlobalDefinition> {
+        self.handle
+            .get(store.as_store_ref().objects())
+            .shared_definition()
+    }
+
     /// Checks whether this `Global` can be used with the given context.
     pub fn is_from_store(&self, store: &impl AsStoreRef) -> bool {
         self.handle.store_id() == store.as_store_ref().objects().id()
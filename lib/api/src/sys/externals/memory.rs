@@ -7,11 +7,15 @@ use std::convert::TryInto;
 use std::marker::PhantomData;
 use std::mem;
 use std::mem::MaybeUninit;
+use std::ptr::NonNull;
 use std::slice;
 #[cfg(feature = "tracing")]
 use tracing::warn;
 use wasmer_types::Pages;
-use wasmer_vm::{InternalStoreHandle, LinearMemory, MemoryError, StoreHandle, VMExtern, VMMemory};
+use wasmer_vm::{
+    InternalStoreHandle, LinearMemory, MemoryError, StoreHandle, VMExtern, VMMemory,
+    VMMemoryDefinition,
+};
 
 use super::MemoryView;
 
@@ -173,6 +177,16 @@ impl Memory {
         }
     }
 
+    /// Returns a pointer to the definition of the memory, with its
+    /// base address and its current size in bytes.
+    ///
+    /// The pointer stays valid as long as the store the memory belongs
+    /// to is alive. It allows to read the size of the memory from
+    /// outside of the store, e.g. from another thread.
+    pub fn vm_definition(&self, store: &impl AsStoreRef) -> NonNull<VMMemoryDefinition> {
+        self.handle.get(store.as_store_ref().objects()).vmmemory()
+    }
+
     /// Checks whether this `Memory` can be used with the given context.
     pub fn is_from_store(&self, store: &impl AsStoreRef) -> bool {
         self.handle.store_id() == store.as_store_ref().objects().id()
@@ -1,9 +1,11 @@
 //! The import module contains the implementation data structures and helper functions used to
 //! manipulate and access a wasm module's imports including memories, tables, globals, and
 //! functions.
-use crate::{Exports, Extern, Module};
+use crate::{AsStoreMut, Exports, Extern, Module, StoreMut};
+use once_cell::sync::OnceCell;
 use std::collections::HashMap;
 use std::fmt;
+use std::sync::Arc;
 use wasmer_compiler::LinkError;
 use wasmer_types::ImportError;
 #[cfg(feature = "compiler")]
@@ -38,7 +40,65 @@ use wasmer_vm::VMSharedMemory;
 /// ```
 #[derive(Clone, Default)]
 pub struct Imports {
-    map: HashMap<(String, String), Extern>,
+    namespaces: HashMap<String, Namespace>,
+}
+
+/// Creates the externs of a namespace registered with
+/// [`Imports::register_lazy_namespace`].
+type LazyExports = dyn Fn(&mut StoreMut<'_>) -> Exports + Send + Sync;
+
+/// The externs of a namespace.
+///
+/// The namespaces are shared by the clones of an `Imports`, and only
+/// copied when one of them is modified.
+#[derive(Clone, Default)]
+struct Namespace {
+    externs: Arc<HashMap<String, Extern>>,
+    lazy: Option<LazyNamespace>,
+}
+
+#[derive(Clone)]
+struct LazyNamespace {
+    init: Arc<LazyExports>,
+    externs: Arc<OnceCell<HashMap<String, Extern>>>,
+}
+
+impl Namespace {
+    /// Gets an extern, the ones defined up front taking precedence over
+    /// the lazy ones.
+    fn get(&self, name: &str) -> Option<&Extern> {
+        self.externs.get(name).or_else(|| {
+            self.lazy
+                .as_ref()
+                .and_then(|lazy| lazy.externs.get())
+                .and_then(|externs| externs.get(name))
+        })
+    }
+
+    /// Gets an extern, creating the lazy externs of the namespace if
+    /// they weren't yet.
+    fn resolve(&self, store: &mut impl AsStoreMut, name: &str) -> Option<&Extern> {
+        if let Some(extern_) = self.externs.get(name) {
+            return Some(extern_);
+        }
+        let lazy = self.lazy.as_ref()?;
+        lazy.externs
+            .get_or_init(|| (lazy.init)(&mut store.as_store_mut()).into_iter().collect())
+            .get(name)
+    }
+
+    /// Iterates through the externs, the ones of a lazy namespace only
+    /// once they were created.
+    fn iter(&self) -> impl Iterator<Item = (&String, &Extern)> {
+        let lazy = self
+            .lazy
+            .as_ref()
+            .and_then(|lazy| lazy.externs.get())
+            .into_iter()
+            .flatten()
+            .filter(move |(name, _)| !self.externs.contains_key(*name));
+        self.externs.iter().chain(lazy)
+    }
 }
 
 impl Imports {
@@ -49,6 +109,10 @@ impl Imports {
 
     /// Gets an export given a module and a name
     ///
+    /// The externs of a namespace registered with
+    /// [`Imports::register_lazy_namespace`] are only found once they
+    /// were created.
+    ///
     /// # Usage
     /// ```no_run
     /// # use wasmer::Imports;
@@ -56,11 +120,7 @@ impl Imports {
     /// import_object.get_export("module", "name");
     /// ```
     pub fn get_export(&self, module: &str, name: &str) -> Option<Extern> {
-        if self.exists(module, name) {
-            let ext = &self.map[&(module.to_string(), name.to_string())];
-            return Some(ext.clone());
-        }
-        None
+        self.namespaces.get(module)?.get(name).cloned()
     }
 
     /// Returns if an export exist for a given module and name.
@@ -72,13 +132,22 @@ impl Imports {
     /// import_object.exists("module", "name");
     /// ```
     pub fn exists(&self, module: &str, name: &str) -> bool {
-        self.map
-            .contains_key(&(module.to_string(), name.to_string()))
+        self.namespaces
+            .get(module)
+            .map_or(false, |namespace| namespace.get(name).is_some())
     }
 
     /// Returns true if the Imports contains namespace with the provided name.
     pub fn contains_namespace(&self, name: &str) -> bool {
-        self.map.keys().any(|(k, _)| (k == name))
+        self.namespaces.contains_key(name)
+    }
+
+    fn namespace_mut(&mut self, ns: &str) -> &mut HashMap<String, Extern> {
+        if !self.namespaces.contains_key(ns) {
+            self.namespaces.insert(ns.to_string(), Namespace::default());
+        }
+        let namespace = self.namespaces.get_mut(ns).unwrap();
+        Arc::make_mut(&mut namespace.externs)
     }
 
     /// Register a list of externs into a namespace.
@@ -100,9 +169,42 @@ impl Imports {
         ns: &str,
         contents: impl IntoIterator<Item = (String, Extern)>,
     ) {
-        for (name, extern_) in contents.into_iter() {
-            self.map.insert((ns.to_string(), name.clone()), extern_);
-        }
+        self.namespace_mut(ns).extend(contents);
+    }
+
+    /// Register a namespace whose externs are only created the first
+    /// time a module importing from it is instantiated with these
+    /// imports, by calling `init`.
+    ///
+    /// This avoids creating the host functions of the namespaces a
+    /// module doesn't use, e.g. when the same big import object is
+    /// created for every instance. The externs are created once, and
+    /// shared by the clones of the `Imports`. The externs defined in the
+    /// namespace with [`Imports::define`] take precedence over them.
+    ///
+    /// # Usage:
+    /// ```no_run
+    /// # use wasmer::{Function, Imports, namespace};
+    /// fn foo(n: i32) -> i32 {
+    ///     n
+    /// }
+    ///
+    /// let mut import_object = Imports::new();
+    /// import_object.register_lazy_namespace("env", |store| {
+    ///     namespace! {
+    ///         "foo" => Function::new_typed(store, foo),
+    ///     }
+    /// });
+    /// ```
+    pub fn register_lazy_namespace(
+        &mut self,
+        ns: &str,
+        init: impl Fn(&mut StoreMut<'_>) -> Exports + Send + Sync + 'static,
+    ) {
+        self.namespaces.entry(ns.to_string()).or_default().lazy = Some(LazyNamespace {
+            init: Arc::new(init),
+            externs: Arc::new(OnceCell::new()),
+        });
     }
 
     /// Add a single import with a namespace `ns` and name `name`.
@@ -119,8 +221,7 @@ impl Imports {
     /// import_object.define("env", "foo", Function::new_typed(&mut store, foo));
     /// ```
     pub fn define(&mut self, ns: &str, name: &str, val: impl Into<Extern>) {
-        self.map
-            .insert((ns.to_string(), name.to_string()), val.into());
+        self.namespace_mut(ns).insert(name.to_string(), val.into());
     }
 
     /// Imports (any) shared memory into the imports.
@@ -159,10 +260,10 @@ impl Imports {
     /// Returns `None` if the namespace doesn't exist.
     pub fn get_namespace_exports(&self, name: &str) -> Option<Exports> {
         let ret: Exports = self
-            .map
+            .namespaces
+            .get(name)?
             .iter()
-            .filter(|((ns, _), _)| ns == name)
-            .map(|((_, name), e)| (name.clone(), e.clone()))
+            .map(|(name, e)| (name.clone(), e.clone()))
             .collect();
         if ret.is_empty() {
             None
@@ -174,13 +275,34 @@ impl Imports {
     /// Resolve and return a vector of imports in the order they are defined in the `module`'s source code.
     ///
     /// This means the returned `Vec<Extern>` might be a subset of the imports contained in `self`.
+    /// The lazy namespaces are only resolved once their externs were
+    /// created, see [`Imports::register_lazy_namespace`].
     pub fn imports_for_module(&self, module: &Module) -> Result<Vec<Extern>, LinkError> {
+        self.resolve(module, |namespace, name| namespace.get(name))
+    }
+
+    /// Like [`Imports::imports_for_module`], creating the externs of the
+    /// lazy namespaces the module imports from.
+    pub(crate) fn resolve_for_module(
+        &self,
+        store: &mut impl AsStoreMut,
+        module: &Module,
+    ) -> Result<Vec<Extern>, LinkError> {
+        self.resolve(module, |namespace, name| namespace.resolve(store, name))
+    }
+
+    fn resolve<'a>(
+        &'a self,
+        module: &Module,
+        mut get: impl FnMut(&'a Namespace, &str) -> Option<&'a Extern>,
+    ) -> Result<Vec<Extern>, LinkError> {
         let mut ret = vec![];
         for import in module.imports() {
-            if let Some(imp) = self
-                .map
-                .get(&(import.module().to_string(), import.name().to_string()))
-            {
+            let extern_ = self
+                .namespaces
+                .get(import.module())
+                .and_then(|namespace| get(namespace, import.name()));
+            if let Some(imp) = extern_ {
                 ret.push(imp.clone());
             } else {
                 return Err(LinkError::Import(
@@ -200,13 +322,19 @@ impl Imports {
 }
 
 pub struct ImportsIterator<'a> {
-    iter: std::collections::hash_map::Iter<'a, (String, String), Extern>,
+    iter: Box<dyn Iterator<Item = (&'a str, &'a str, &'a Extern)> + 'a>,
 }
 
 impl<'a> ImportsIterator<'a> {
     fn new(imports: &'a Imports) -> Self {
-        let iter = imports.map.iter();
-        Self { iter }
+        let iter = imports.namespaces.iter().flat_map(|(ns, namespace)| {
+            namespace
+                .iter()
+                .map(move |(name, extern_)| (ns.as_str(), name.as_str(), extern_))
+        });
+        Self {
+            iter: Box::new(iter),
+        }
     }
 }
 
@@ -214,18 +342,19 @@ impl<'a> Iterator for ImportsIterator<'a> {
     type Item = (&'a str, &'a str, &'a Extern);
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter
-            .next()
-            .map(|(k, v)| (k.0.as_str(), k.1.as_str(), v))
+        self.iter.next()
     }
 }
 
 impl IntoIterator for &Imports {
-    type IntoIter = std::collections::hash_map::IntoIter<(String, String), Extern>;
+    type IntoIter = std::vec::IntoIter<((String, String), Extern)>;
     type Item = ((String, String), Extern);
 
     fn into_iter(self) -> Self::IntoIter {
-        self.map.clone().into_iter()
+        self.iter()
+            .map(|(ns, name, extern_)| ((ns.to_string(), name.to_string()), extern_.clone()))
+            .collect::<Vec<_>>()
+            .into_iter()
     }
 }
 
@@ -264,7 +393,7 @@ impl fmt::Debug for Imports {
         }
 
         f.debug_struct("Imports")
-            .field("map", &SecretMap::new(self.map.len()))
+            .field("map", &SecretMap::new(self.iter().count()))
             .finish()
     }
 }
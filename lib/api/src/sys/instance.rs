@@ -3,12 +3,13 @@ use crate::sys::module::Module;
 use crate::sys::{LinkError, RuntimeError};
 use std::fmt;
 use thiserror::Error;
+use wasmer_types::ExportIndex;
 use wasmer_vm::{StoreHandle, VMInstance};
 
-#[cfg(feature = "compiler")]
 use super::store::AsStoreMut;
+use crate::sys::externals::Extern;
 #[cfg(feature = "compiler")]
-use crate::sys::{externals::Extern, imports::Imports};
+use crate::sys::imports::Imports;
 
 /// A WebAssembly Instance is a stateful, executable
 /// instance of a WebAssembly [`Module`].
@@ -122,7 +123,7 @@ impl Instance {
         imports: &Imports,
     ) -> Result<Self, InstantiationError> {
         let externs = imports
-            .imports_for_module(module)
+            .resolve_for_module(store, module)
             .map_err(InstantiationError::Link)?;
         let mut handle = module.instantiate(store, &externs)?;
         let mut exports = module
@@ -197,10 +198,81 @@ impl Instance {
         Ok(instance)
     }
 
+    /// Restores the instance to the state it was in right after being
+    /// instantiated, to reuse it without instantiating the module again,
+    /// e.g. across requests.
+    ///
+    /// The memories and tables defined by the module are shrunk back to
+    /// their initial size and cleared, the pages the memories grew by
+    /// being given back to the system, and the data and element segments
+    /// are copied into them again. The globals are set to their initial
+    /// values, and the start function of the module, if any, is run
+    /// again. The imported memories, tables and globals are left as is,
+    /// apart from the segments copied into them.
+    ///
+    /// The exports of the instance stay valid. The instances defining a
+    /// shared memory, which other threads may be using, can't be reset.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{imports, Instance, Module, Store, TypedFunction};
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut store = Store::default();
+    /// let module = Module::new(&store, r#"
+    ///     (module
+    ///         (global $counter (mut i32) (i32.const 0))
+    ///         (func (export "increment") (result i32)
+    ///             (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+    ///             (global.get $counter)))
+    /// "#)?;
+    /// let instance = Instance::new(&mut store, &module, &imports! {})?;
+    /// let increment: TypedFunction<(), i32> =
+    ///     instance.exports.get_typed_function(&store, "increment")?;
+    /// assert_eq!(increment.call(&mut store)?, 1);
+    /// assert_eq!(increment.call(&mut store)?, 2);
+    ///
+    /// // No function of the instance is running.
+    /// unsafe { instance.reset(&mut store)? };
+    /// assert_eq!(increment.call(&mut store)?, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// The function can return [`InstantiationError`]s, like
+    /// [`Instance::new`]. The instance shouldn't be used anymore if it
+    /// couldn't be reset.
+    ///
+    /// # Safety
+    ///
+    /// None of the functions of the instance may be running, e.g. when
+    /// the instance is reset from a host function it called, or while
+    /// it's suspended by the `yielding` middleware: they would resume on
+    /// memories and tables shrunk under their feet.
+    pub unsafe fn reset(&self, store: &mut impl AsStoreMut) -> Result<(), InstantiationError> {
+        self.module.reset_instance(store, &self._handle)
+    }
+
     /// Gets the [`Module`] associated with this instance.
     pub fn module(&self) -> &Module {
         &self.module
     }
+
+    /// Gets the entity of the instance at `index`, whether it's exported
+    /// or not.
+    pub(crate) fn lookup_by_declaration(
+        &self,
+        store: &mut impl AsStoreMut,
+        index: ExportIndex,
+    ) -> Extern {
+        let vm_extern = self
+            ._handle
+            .get_mut(store.objects_mut())
+            .lookup_by_declaration(index);
+        Extern::from_vm_extern(store, vm_extern)
+    }
 }
 
 impl fmt::Debug for Instance {
@@ -0,0 +1,205 @@
+use crate::sys::externals::Extern;
+use crate::sys::imports::Imports;
+use crate::sys::instance::{Instance, InstantiationError};
+use crate::sys::module::Module;
+use crate::sys::store::AsStoreMut;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Instantiates modules importing from each other by name.
+///
+/// The externs a module imports are looked up in the namespaces of the
+/// linker, which hold:
+/// - the externs defined by the host, with [`Linker::define`] and
+///   [`Linker::define_namespace`],
+/// - the exports of the instances registered with
+///   [`Linker::register_instance`], the namespace being the name of the
+///   instance,
+/// - the exports of the modules registered with
+///   [`Linker::register_module`], which are instantiated the first time
+///   a module imports from them, after their own dependencies.
+///
+/// Each registered module is instantiated once, so the memories, tables
+/// and globals it exports are shared by all the modules importing them.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::{Linker, Module, Store, TypedFunction};
+/// # fn main() -> anyhow::Result<()> {
+/// let mut store = Store::default();
+/// let math = Module::new(&store, r#"
+///     (module
+///         (func (export "double") (param i32) (result i32)
+///             (i32.mul (local.get 0) (i32.const 2))))
+/// "#)?;
+/// let app = Module::new(&store, r#"
+///     (module
+///         (import "math" "double" (func $double (param i32) (result i32)))
+///         (func (export "quadruple") (param i32) (result i32)
+///             (call $double (call $double (local.get 0)))))
+/// "#)?;
+///
+/// let mut linker = Linker::new();
+/// linker.register_module("math", &math);
+/// let instance = linker.instantiate(&mut store, &app)?;
+/// let quadruple: TypedFunction<i32, i32> =
+///     instance.exports.get_typed_function(&store, "quadruple")?;
+/// assert_eq!(quadruple.call(&mut store, 3)?, 12);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct Linker {
+    imports: Imports,
+    modules: HashMap<String, Module>,
+    instances: HashMap<String, Instance>,
+}
+
+/// An error while instantiating a module with a [`Linker`].
+#[derive(Error, Debug)]
+pub enum LinkerError {
+    /// The registered modules import from each other in a cycle, given
+    /// as the names of the modules, from the first module of the cycle
+    /// back to it.
+    #[error("the modules import from each other in a cycle: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+
+    /// No module nor instance is registered under this name.
+    #[error("no module is registered as {0}")]
+    UnknownModule(String),
+
+    /// A registered module couldn't be instantiated.
+    #[error("cannot instantiate the module registered as {name}: {error}")]
+    Dependency {
+        /// The name of the module.
+        name: String,
+        /// The error instantiating it.
+        #[source]
+        error: InstantiationError,
+    },
+
+    /// The module couldn't be instantiated.
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+}
+
+impl Linker {
+    /// Creates an empty `Linker`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defines an extern of the host, as `name` in the namespace `ns`.
+    pub fn define(&mut self, ns: &str, name: &str, val: impl Into<Extern>) -> &mut Self {
+        self.imports.define(ns, name, val);
+        self
+    }
+
+    /// Defines externs of the host in the namespace `ns`.
+    pub fn define_namespace(
+        &mut self,
+        ns: &str,
+        contents: impl IntoIterator<Item = (String, Extern)>,
+    ) -> &mut Self {
+        self.imports.register_namespace(ns, contents);
+        self
+    }
+
+    /// Registers the exports of `instance` as the namespace `name`.
+    pub fn register_instance(&mut self, name: &str, instance: &Instance) -> &mut Self {
+        self.imports
+            .register_namespace(name, instance.exports.clone());
+        self.instances.insert(name.to_string(), instance.clone());
+        self
+    }
+
+    /// Registers `module`, to be instantiated as the namespace `name`
+    /// the first time a module imports from it.
+    pub fn register_module(&mut self, name: &str, module: &Module) -> &mut Self {
+        self.modules.insert(name.to_string(), module.clone());
+        self
+    }
+
+    /// Returns the imports defined so far, including the exports of the
+    /// registered modules which were instantiated.
+    pub fn imports(&self) -> &Imports {
+        &self.imports
+    }
+
+    /// Instantiates `module`, after the registered modules it imports
+    /// from.
+    ///
+    /// The instance isn't registered, see
+    /// [`Linker::instantiate_registered`] for that.
+    pub fn instantiate(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        module: &Module,
+    ) -> Result<Instance, LinkerError> {
+        self.instantiate_with_dependencies(store, module, &mut Vec::new())
+    }
+
+    /// Returns the instance registered as `name`, instantiating the
+    /// module registered as `name` first if needed.
+    pub fn instantiate_registered(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        name: &str,
+    ) -> Result<Instance, LinkerError> {
+        self.instantiate_dependency(store, name, &mut Vec::new())
+    }
+
+    /// Instantiates `module`, `path` being the names of the registered
+    /// modules being instantiated because they're imported from.
+    fn instantiate_with_dependencies(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        module: &Module,
+        path: &mut Vec<String>,
+    ) -> Result<Instance, LinkerError> {
+        for import in module.imports() {
+            let ns = import.module();
+            if !self.imports.contains_namespace(ns) && self.modules.contains_key(ns) {
+                self.instantiate_dependency(store, ns, path)?;
+            }
+        }
+        Ok(Instance::new(store, module, &self.imports)?)
+    }
+
+    fn instantiate_dependency(
+        &mut self,
+        store: &mut impl AsStoreMut,
+        name: &str,
+        path: &mut Vec<String>,
+    ) -> Result<Instance, LinkerError> {
+        if let Some(instance) = self.instances.get(name) {
+            return Ok(instance.clone());
+        }
+        let module = self
+            .modules
+            .get(name)
+            .cloned()
+            .ok_or_else(|| LinkerError::UnknownModule(name.to_string()))?;
+        if let Some(start) = path.iter().position(|n| n == name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(name.to_string());
+            return Err(LinkerError::Cycle(cycle));
+        }
+
+        path.push(name.to_string());
+        let instance = self
+            .instantiate_with_dependencies(store, &module, path)
+            .map_err(|e| match e {
+                LinkerError::Instantiation(error) => LinkerError::Dependency {
+                    name: name.to_string(),
+                    error,
+                },
+                e => e,
+            })?;
+        path.pop();
+
+        self.register_instance(name, &instance);
+        Ok(instance)
+    }
+}
@@ -102,16 +102,19 @@ impl<'a, T: ValueType> WasmRef<'a, T> {
     /// Reads the location pointed to by this `WasmRef`.
     #[inline]
     pub fn read(self) -> Result<T, MemoryAccessError> {
-        let mut out = MaybeUninit::uninit();
+        let mut out = MaybeUninit::<T>::uninit();
         let buf =
             unsafe { slice::from_raw_parts_mut(out.as_mut_ptr() as *mut u8, mem::size_of::<T>()) };
         self.buffer.read(self.offset, buf)?;
-        Ok(unsafe { out.assume_init() })
+        let mut val = unsafe { out.assume_init() };
+        val.convert_endianness();
+        Ok(val)
     }
 
     /// Writes to the location pointed to by this `WasmRef`.
     #[inline]
-    pub fn write(self, val: T) -> Result<(), MemoryAccessError> {
+    pub fn write(self, mut val: T) -> Result<(), MemoryAccessError> {
+        val.convert_endianness();
         let mut data = MaybeUninit::new(val);
         let data = unsafe {
             slice::from_raw_parts_mut(
@@ -269,6 +272,7 @@ impl<'a, T: ValueType> WasmSlice<'a, T> {
             )
         };
         self.buffer.read_uninit(self.offset, bytes)?;
+        buf.iter_mut().for_each(T::convert_endianness);
         Ok(())
     }
 
@@ -294,7 +298,9 @@ impl<'a, T: ValueType> WasmSlice<'a, T> {
             )
         };
         self.buffer.read_uninit(self.offset, bytes)?;
-        Ok(unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut T, buf.len()) })
+        let buf = unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut T, buf.len()) };
+        buf.iter_mut().for_each(T::convert_endianness);
+        Ok(buf)
     }
 
     /// Write the given slice into this `WasmSlice`.
@@ -307,6 +313,15 @@ impl<'a, T: ValueType> WasmSlice<'a, T> {
             self.len,
             "slice length doesn't match WasmSlice length"
         );
+        #[cfg(target_endian = "big")]
+        let data = &data
+            .iter()
+            .map(|val| {
+                let mut val = *val;
+                val.convert_endianness();
+                val
+            })
+            .collect::<Vec<T>>()[..];
         let bytes = unsafe {
             slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * mem::size_of::<T>())
         };
@@ -336,6 +351,7 @@ impl<'a, T: ValueType> WasmSlice<'a, T> {
         unsafe {
             vec.set_len(len);
         }
+        vec.iter_mut().for_each(T::convert_endianness);
         Ok(vec)
     }
 
@@ -1,18 +1,35 @@
+mod adapter;
+#[cfg(feature = "compiler")]
+mod background;
+mod canonical;
+mod coredump;
+mod encoding;
 mod exports;
 mod extern_ref;
 mod externals;
 mod function_env;
 mod imports;
 mod instance;
+#[cfg(feature = "compiler")]
+mod linker;
 mod mem_access;
 mod module;
 mod native;
 mod native_type;
 mod ptr;
+mod replay;
+mod snapshot;
 mod store;
+#[cfg(feature = "tokio")]
+mod tokio_adapter;
 mod tunables;
 mod value;
 
+pub use crate::sys::adapter::{AdapterError, BufferAdapter};
+#[cfg(feature = "compiler")]
+pub use crate::sys::background::CompilingModule;
+pub use crate::sys::canonical::{CanonicalError, CanonicalFunction, InterfaceType, InterfaceValue};
+pub use crate::sys::coredump::{CoreDump, CoreDumpError, CoreDumpFrame, CoreDumpValue};
 pub use crate::sys::exports::{ExportError, Exportable, Exports, ExportsIterator};
 pub use crate::sys::extern_ref::ExternRef;
 pub use crate::sys::externals::{
@@ -22,6 +39,8 @@ pub use crate::sys::externals::{
 pub use crate::sys::function_env::{FunctionEnv, FunctionEnvMut};
 pub use crate::sys::imports::Imports;
 pub use crate::sys::instance::{Instance, InstantiationError};
+#[cfg(feature = "compiler")]
+pub use crate::sys::linker::{Linker, LinkerError};
 pub use crate::sys::mem_access::{MemoryAccessError, WasmRef, WasmSlice, WasmSliceIter};
 pub use crate::sys::module::{IoCompileError, Module};
 pub use crate::sys::native::TypedFunction;
@@ -29,17 +48,23 @@ pub use crate::sys::native_type::NativeWasmTypeInto;
 pub use crate::sys::store::{AsStoreMut, AsStoreRef, StoreMut, StoreRef};
 
 pub use crate::sys::ptr::{Memory32, Memory64, MemorySize, WasmPtr, WasmPtr64};
+pub use crate::sys::replay::{ExecutionLog, ExecutionLogError, Recorder, Replayer};
+pub use crate::sys::snapshot::{InstanceSnapshot, SnapshotError};
 pub use crate::sys::store::Store;
-pub use crate::sys::tunables::BaseTunables;
-pub use crate::sys::value::Value;
+#[cfg(feature = "tokio")]
+pub use crate::sys::tokio_adapter::{AsyncCallError, HostFuture, TokioAdapter};
+pub use crate::sys::tunables::{BaseTunables, MemoryStylePolicy};
+pub use crate::sys::value::{CoercionError, Value};
 pub use target_lexicon::{Architecture, CallingConvention, OperatingSystem, Triple, HOST};
 #[cfg(feature = "compiler")]
 pub use wasmer_compiler::{
-    wasmparser, CompilerConfig, FunctionMiddleware, MiddlewareReaderState, ModuleMiddleware,
+    wasmparser, CompilerConfig, FilteredMiddleware, FunctionFilter, FunctionMetadata,
+    FunctionMiddleware, FunctionSelector, MiddlewareLocals, MiddlewareReaderState,
+    ModuleMiddleware,
 };
-pub use wasmer_compiler::{Features, FrameInfo, LinkError, RuntimeError, Tunables};
+pub use wasmer_compiler::{BacktraceFrame, Features, FrameInfo, LinkError, RuntimeError, Tunables};
 pub use wasmer_derive::ValueType;
-pub use wasmer_types::is_wasm;
+pub use wasmer_types::{is_component, is_wasm};
 // TODO: OnCalledAction is needed for asyncify. It will be refactored with https://github.com/wasmerio/wasmer/issues/3451
 pub use wasmer_types::{
     CpuFeature, ExportType, ExternType, FunctionType, GlobalType, ImportType, MemoryType,
@@ -48,9 +73,15 @@ pub use wasmer_types::{
 
 pub use wasmer_types::{
     Bytes, CompileError, DeserializeError, ExportIndex, GlobalInit, LocalFunctionIndex,
-    MiddlewareError, Pages, ParseCpuFeatureError, SerializeError, ValueType, WasmError, WasmResult,
-    WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
+    MiddlewareError, Pages, ParseCpuFeatureError, SerializeError, TrapCode, ValueType, WasmError,
+    WasmResult, WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
 };
+// The types that the middlewares see
+#[cfg(feature = "compiler")]
+pub use wasmer_types::{FunctionIndex, GlobalIndex, ModuleInfo, SignatureIndex, TableIndex};
+
+pub use wasmer_vm::metrics;
+pub use wasmer_vm::{InstancePool, InstancePoolConfig, MemorySnapshot};
 
 // TODO: should those be moved into wasmer::vm as well?
 pub use wasmer_vm::{raise_user_trap, MemoryError};
@@ -58,8 +89,9 @@ pub mod vm {
     //! The `vm` module re-exports wasmer-vm types.
 
     pub use wasmer_vm::{
-        MemoryError, MemoryStyle, TableStyle, VMExtern, VMMemory, VMMemoryDefinition,
-        VMOwnedMemory, VMSharedMemory, VMTable, VMTableDefinition,
+        MemoryError, MemoryStyle, TableStyle, VMExtern, VMGlobalDefinition, VMMemory,
+        VMMemoryDefinition, VMOwnedMemory, VMSharedGlobalDefinition, VMSharedMemory, VMTable,
+        VMTableDefinition,
     };
 }
 
@@ -75,6 +107,9 @@ pub use wasmer_compiler_cranelift::{Cranelift, CraneliftOptLevel};
 #[cfg(feature = "llvm")]
 pub use wasmer_compiler_llvm::{LLVMOptLevel, LLVM};
 
+#[cfg(feature = "interpreter")]
+pub use wasmer_compiler_interpreter::Interpreter;
+
 #[cfg(feature = "compiler")]
 pub use wasmer_compiler::{Artifact, EngineBuilder};
 pub use wasmer_compiler::{AsEngineRef, Engine, EngineRef};
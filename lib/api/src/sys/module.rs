@@ -7,17 +7,21 @@ use thiserror::Error;
 use wasmer_compiler::Artifact;
 use wasmer_compiler::ArtifactCreate;
 use wasmer_compiler::AsEngineRef;
-#[cfg(feature = "wat")]
+#[cfg(any(feature = "wat", feature = "wasmprinter"))]
 use wasmer_types::WasmError;
 use wasmer_types::{
     CompileError, DeserializeError, ExportsIterator, ImportsIterator, ModuleInfo, SerializeError,
 };
 use wasmer_types::{ExportType, ImportType};
 
+#[cfg(feature = "compiler")]
+use crate::sys::background::CompilingModule;
 #[cfg(feature = "compiler")]
 use crate::{sys::InstantiationError, AsStoreMut, AsStoreRef, IntoBytes};
 #[cfg(feature = "compiler")]
-use wasmer_vm::VMInstance;
+use wasmer_types::is_component;
+#[cfg(feature = "compiler")]
+use wasmer_vm::{StoreHandle, VMInstance};
 
 /// IO Error on a Module Compilation
 #[derive(Error, Debug)]
@@ -38,6 +42,11 @@ pub enum IoCompileError {
 ///
 /// Cloning a module is cheap: it does a shallow copy of the compiled
 /// contents rather than a deep copy.
+///
+/// Likewise, the modules compiled or deserialized from the same bytes by
+/// the same engine share their compiled code while any of them is alive,
+/// so running many instances of a module loaded several times doesn't
+/// map its code several times.
 #[derive(Clone)]
 pub struct Module {
     // The field ordering here is actually significant because of the drop
@@ -56,6 +65,9 @@ pub struct Module {
     // ownership of the code and its metadata.
     artifact: Arc<Artifact>,
     module_info: Arc<ModuleInfo>,
+    /// The binary the module was compiled from, for [`Module::to_wat`].
+    #[cfg(feature = "wasmprinter")]
+    binary: Option<Bytes>,
 }
 
 impl Module {
@@ -165,8 +177,12 @@ impl Module {
     /// the WebAssembly text format (if the "wat" feature is enabled for
     /// this crate).
     pub fn from_binary(engine: &impl AsEngineRef, binary: &[u8]) -> Result<Self, CompileError> {
-        Self::validate(engine, binary)?;
-        unsafe { Self::from_binary_unchecked(engine, binary) }
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("compile", size = binary.len()).entered();
+        Self::check_core_module(binary)?;
+        // The module is validated while it's translated, so it's only parsed once.
+        let artifact = engine.as_engine_ref().engine().compile_validated(binary)?;
+        Ok(Self::from_artifact(artifact).with_binary(binary))
     }
 
     #[cfg(feature = "compiler")]
@@ -193,13 +209,44 @@ impl Module {
     /// WebAssembly features in the Store Engine to assure deterministic
     /// validation of the Module.
     pub fn validate(engine: &impl AsEngineRef, binary: &[u8]) -> Result<(), CompileError> {
+        Self::check_core_module(binary)?;
         engine.as_engine_ref().engine().validate(binary)
     }
 
+    #[cfg(feature = "compiler")]
+    /// Starts compiling a WebAssembly module on a thread of its own, like
+    /// [`Module::new`] would.
+    ///
+    /// The returned [`CompilingModule`] can be awaited, without blocking
+    /// the async runtime for the duration of the compilation, or polled
+    /// with [`CompilingModule::is_finished`].
+    pub fn compile_in_background(
+        engine: &impl AsEngineRef,
+        bytes: impl AsRef<[u8]> + Send + 'static,
+    ) -> CompilingModule {
+        CompilingModule::spawn(engine, bytes)
+    }
+
     #[cfg(feature = "compiler")]
     fn compile(engine: &impl AsEngineRef, binary: &[u8]) -> Result<Self, CompileError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("compile", size = binary.len()).entered();
+        Self::check_core_module(binary)?;
         let artifact = engine.as_engine_ref().engine().compile(binary)?;
-        Ok(Self::from_artifact(artifact))
+        Ok(Self::from_artifact(artifact).with_binary(binary))
+    }
+
+    #[cfg(feature = "compiler")]
+    /// Components can't be compiled as modules: they have to be split
+    /// into the core modules they embed first.
+    fn check_core_module(binary: &[u8]) -> Result<(), CompileError> {
+        if is_component(binary) {
+            return Err(CompileError::UnsupportedFeature(
+                "the binary is a WebAssembly component, only core modules can be compiled"
+                    .to_string(),
+            ));
+        }
+        Ok(())
     }
 
     /// Serializes a module into a binary representation that the `Engine`
@@ -246,13 +293,18 @@ impl Module {
     /// Deserializes a serialized Module binary into a `Module`.
     /// > Note: the module has to be serialized before with the `serialize` method.
     ///
+    /// The integrity checksum and the structure of the serialized data
+    /// are validated, so corrupted bytes result in an error. Use
+    /// [`Module::deserialize_unchecked`] to skip this validation for
+    /// trusted artifacts.
+    ///
     /// # Safety
     ///
-    /// This function is inherently **unsafe** as the provided bytes:
-    /// 1. Are going to be deserialized directly into Rust objects.
-    /// 2. Contains the function assembly bodies and, if intercepted,
-    ///    a malicious actor could inject code into executable
-    ///    memory.
+    /// This function is inherently **unsafe** as the provided bytes
+    /// contain the function assembly bodies and, if intercepted,
+    /// a malicious actor could inject code into executable memory.
+    /// The validation doesn't protect against this: the bytes must come
+    /// from a trusted source.
     ///
     /// And as such, the `deserialize` method is unsafe.
     ///
@@ -271,6 +323,8 @@ impl Module {
         bytes: impl IntoBytes,
     ) -> Result<Self, DeserializeError> {
         let bytes = bytes.into_bytes();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("deserialize", size = bytes.len()).entered();
         let artifact = engine.as_engine_ref().engine().deserialize(&bytes)?;
         Ok(Self::from_artifact(artifact))
     }
@@ -279,9 +333,14 @@ impl Module {
     /// Deserializes a a serialized Module located in a `Path` into a `Module`.
     /// > Note: the module has to be serialized before with the `serialize` method.
     ///
+    /// The file is mapped in memory and its metadata is read in place
+    /// instead of being deserialized to the heap. The compiled code is
+    /// still copied to executable memory, as it has to be relocated.
+    ///
     /// # Safety
     ///
-    /// Please check [`Module::deserialize`].
+    /// Please check [`Module::deserialize`]. Additionally, the file must
+    /// not be modified while the module is alive.
     ///
     /// # Usage
     ///
@@ -297,6 +356,8 @@ impl Module {
         engine: &impl AsEngineRef,
         path: impl AsRef<Path>,
     ) -> Result<Self, DeserializeError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("deserialize", path = %path.as_ref().display()).entered();
         let artifact = engine
             .as_engine_ref()
             .engine()
@@ -304,19 +365,83 @@ impl Module {
         Ok(Self::from_artifact(artifact))
     }
 
+    #[cfg(feature = "compiler")]
+    /// Deserializes a serialized Module binary into a `Module`, without
+    /// validating the serialized data.
+    ///
+    /// This is faster than [`Module::deserialize`], and is meant for
+    /// artifacts whose integrity is already guaranteed, for instance
+    /// because they were produced and stored by the same process.
+    ///
+    /// # Safety
+    ///
+    /// In addition to the requirements of [`Module::deserialize`], the
+    /// bytes must have been produced by [`Module::serialize`] with the
+    /// same version of Wasmer and must not be corrupted. Otherwise, the
+    /// behavior is undefined.
+    pub unsafe fn deserialize_unchecked(
+        engine: &impl AsEngineRef,
+        bytes: impl IntoBytes,
+    ) -> Result<Self, DeserializeError> {
+        let bytes = bytes.into_bytes();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("deserialize", size = bytes.len()).entered();
+        let artifact = engine
+            .as_engine_ref()
+            .engine()
+            .deserialize_unchecked(&bytes)?;
+        Ok(Self::from_artifact(artifact))
+    }
+
+    #[cfg(feature = "compiler")]
+    /// Deserializes a serialized Module located in a `Path` into a
+    /// `Module`, without validating the serialized data.
+    ///
+    /// # Safety
+    ///
+    /// Please check [`Module::deserialize_unchecked`].
+    pub unsafe fn deserialize_from_file_unchecked(
+        engine: &impl AsEngineRef,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, DeserializeError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("deserialize", path = %path.as_ref().display()).entered();
+        let artifact = engine
+            .as_engine_ref()
+            .engine()
+            .deserialize_from_file_unchecked(path.as_ref())?;
+        Ok(Self::from_artifact(artifact))
+    }
+
     fn from_artifact(artifact: Arc<Artifact>) -> Self {
         Self {
             module_info: Arc::new(artifact.create_module_info()),
             artifact,
+            #[cfg(feature = "wasmprinter")]
+            binary: None,
         }
     }
 
+    /// Keeps the binary the module was compiled from, for [`Module::to_wat`].
+    #[cfg(all(feature = "compiler", feature = "wasmprinter"))]
+    fn with_binary(mut self, binary: &[u8]) -> Self {
+        self.binary = Some(Bytes::copy_from_slice(binary));
+        self
+    }
+
+    #[cfg(all(feature = "compiler", not(feature = "wasmprinter")))]
+    fn with_binary(self, _binary: &[u8]) -> Self {
+        self
+    }
+
     #[cfg(feature = "compiler")]
     pub(crate) fn instantiate(
         &self,
         store: &mut impl AsStoreMut,
         imports: &[crate::Extern],
     ) -> Result<VMInstance, InstantiationError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("instantiate", module = ?self.name()).entered();
         if !self.artifact.allocated() {
             // Return an error mentioning that the artifact is compiled for a different
             // platform.
@@ -354,6 +479,31 @@ impl Module {
         }
     }
 
+    /// Restores `handle`, an instance of this module, to the state it was
+    /// in right after [`Module::instantiate`], running the start function
+    /// again.
+    ///
+    /// # Safety
+    ///
+    /// See [`Instance::reset`](crate::Instance::reset).
+    pub(crate) unsafe fn reset_instance(
+        &self,
+        store: &mut impl AsStoreMut,
+        handle: &StoreHandle<VMInstance>,
+    ) -> Result<(), InstantiationError> {
+        let signal_handler = store.as_store_ref().signal_handler();
+        let instance_handle = handle.get_mut(store.objects_mut());
+        instance_handle.reset().map_err(|e| {
+            InstantiationError::Link(crate::sys::LinkError::Resource(format!(
+                "Failed to reset memory: {}",
+                e
+            )))
+        })?;
+        self.artifact
+            .finish_instantiation(signal_handler, instance_handle)?;
+        Ok(())
+    }
+
     /// Returns the name of the current module.
     ///
     /// This name is normally set in the WebAssembly bytecode by some
@@ -469,6 +619,33 @@ impl Module {
         self.module_info.custom_sections(name)
     }
 
+    /// Prints the module in the WebAssembly text format, e.g. to dump it
+    /// while debugging.
+    ///
+    /// The binary of the module is kept for this purpose when it's
+    /// compiled with the `wasmprinter` feature, so this fails for the
+    /// modules which were deserialized.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let module = Module::new(&store, b"\0asm\x01\0\0\0")?;
+    /// assert!(module.to_wat()?.starts_with("(module"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "wasmprinter")]
+    pub fn to_wat(&self) -> Result<String, WasmError> {
+        let binary = self.binary.as_ref().ok_or_else(|| {
+            WasmError::Generic("the binary of a deserialized module can't be printed".to_string())
+        })?;
+        wasmprinter::print_bytes(binary)
+            .map_err(|e| WasmError::Generic(format!("Error when printing wat: {}", e)))
+    }
+
     /// The ABI of the ModuleInfo is very unstable, we refactor it very often.
     /// This function is public because in some cases it can be useful to get some
     /// extra information from the module.
@@ -478,6 +655,15 @@ impl Module {
     pub fn info(&self) -> &ModuleInfo {
         &self.module_info
     }
+
+    /// The artifact the module was compiled or deserialized into, e.g.
+    /// to read the compiler and CPU features of a precompiled module.
+    ///
+    /// Like [`Module::info`], its usage is discouraged.
+    #[doc(hidden)]
+    pub fn artifact(&self) -> &Arc<Artifact> {
+        &self.artifact
+    }
 }
 
 impl fmt::Debug for Module {
@@ -57,6 +57,8 @@ macro_rules! impl_native_traits {
             #[allow(unused_mut)]
             #[allow(clippy::too_many_arguments)]
             pub fn call(&self, store: &mut impl AsStoreMut, $( $x: $x, )* ) -> Result<Rets, RuntimeError> {
+                #[cfg(feature = "tracing")]
+                let _span = tracing::trace_span!("call").entered();
                 let anyfunc = unsafe {
                     *self.func
                         .handle
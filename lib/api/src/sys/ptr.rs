@@ -243,6 +243,10 @@ where
 
 unsafe impl<T: ValueType, M: MemorySize> ValueType for WasmPtr<T, M> {
     fn zero_padding_bytes(&self, _bytes: &mut [mem::MaybeUninit<u8>]) {}
+
+    fn convert_endianness(&mut self) {
+        self.offset.convert_endianness();
+    }
 }
 
 impl<T: ValueType, M: MemorySize> Clone for WasmPtr<T, M> {
@@ -0,0 +1,488 @@
+use crate::sys::coredump::CoreDumpValue;
+use crate::sys::encoding::{DecodeError, Reader, Writer};
+use crate::sys::externals::{Extern, Function, Memory};
+use crate::sys::function_env::{FunctionEnv, FunctionEnvMut};
+use crate::sys::imports::Imports;
+use crate::sys::store::AsStoreMut;
+use crate::sys::{RuntimeError, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use wasmer_types::{FunctionType, Pages};
+
+/// The magic number at the start of the serialized execution logs.
+const MAGIC: [u8; 8] = *b"\0wasmrr\0";
+
+/// The version of the format of the serialized execution logs.
+const VERSION: u32 = 1;
+
+/// The granularity of the recorded memory writes.
+const WRITE_CHUNK_SIZE: usize = 64;
+
+/// The log of the nondeterministic inputs of an execution: the results
+/// of the host functions called by an instance, and the bytes they
+/// wrote to its memory.
+///
+/// The results of the WASI syscalls, including the clock reads and the
+/// random bytes, are recorded like the results of any other imported
+/// function.
+///
+/// A log is written by a [`Recorder`], and read by a [`Replayer`] to run
+/// the instance again without calling the host functions, reproducing
+/// the execution.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecutionLog {
+    calls: Vec<HostCall>,
+}
+
+/// A recorded call to a host function.
+#[derive(Debug, Clone, PartialEq)]
+struct HostCall {
+    module: String,
+    name: String,
+    /// The size of the memory after the call, if it grew.
+    memory_size: Option<Pages>,
+    /// The parts of the memory which changed during the call, with
+    /// their offsets.
+    memory_writes: Vec<(u64, Vec<u8>)>,
+    /// The returned values, or the message of the error.
+    outcome: Result<Vec<CoreDumpValue>, String>,
+}
+
+/// An error while reading a serialized [`ExecutionLog`].
+#[derive(Error, Debug)]
+pub enum ExecutionLogError {
+    /// The data isn't an execution log.
+    #[error("the data isn't a WebAssembly execution log")]
+    InvalidMagic,
+    /// The log was written in another version of the format.
+    #[error("unsupported execution log version {0} (expected {})", VERSION)]
+    UnsupportedVersion(u32),
+    /// The log is truncated or corrupted.
+    #[error("the execution log is corrupted")]
+    Corrupted,
+}
+
+impl From<DecodeError> for ExecutionLogError {
+    fn from(_: DecodeError) -> Self {
+        Self::Corrupted
+    }
+}
+
+impl ExecutionLog {
+    /// The number of recorded host calls.
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    /// Whether no host call has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Serializes the log, to be written to a file.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut writer = Writer::new(&MAGIC);
+        writer.u32(VERSION);
+        writer.u64(self.calls.len() as u64);
+        for call in &self.calls {
+            writer.string(&call.module);
+            writer.string(&call.name);
+            match call.memory_size {
+                Some(pages) => {
+                    writer.u8(1);
+                    writer.u32(pages.0);
+                }
+                None => writer.u8(0),
+            }
+            writer.u32(call.memory_writes.len() as u32);
+            for (offset, bytes) in &call.memory_writes {
+                writer.u64(*offset);
+                writer.bytes(bytes);
+            }
+            match &call.outcome {
+                Ok(values) => {
+                    writer.u8(0);
+                    writer.u32(values.len() as u32);
+                    for value in values {
+                        writer.value(value);
+                    }
+                }
+                Err(message) => {
+                    writer.u8(1);
+                    writer.string(message);
+                }
+            }
+        }
+        writer.into_bytes()
+    }
+
+    /// Deserializes a log written with [`ExecutionLog::serialize`].
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, ExecutionLogError> {
+        let mut reader = Reader::new(bytes, &MAGIC).ok_or(ExecutionLogError::InvalidMagic)?;
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(ExecutionLogError::UnsupportedVersion(version));
+        }
+
+        let calls = (0..reader.u64()?)
+            .map(|_| {
+                let module = reader.string()?;
+                let name = reader.string()?;
+                let memory_size = match reader.u8()? {
+                    0 => None,
+                    1 => Some(Pages(reader.u32()?)),
+                    _ => return Err(DecodeError),
+                };
+                let memory_writes = (0..reader.u32()?)
+                    .map(|_| Ok((reader.u64()?, reader.bytes()?.to_vec())))
+                    .collect::<Result<_, _>>()?;
+                let outcome = match reader.u8()? {
+                    0 => Ok((0..reader.u32()?)
+                        .map(|_| reader.value())
+                        .collect::<Result<_, _>>()?),
+                    1 => Err(reader.string()?),
+                    _ => return Err(DecodeError),
+                };
+                Ok(HostCall {
+                    module,
+                    name,
+                    memory_size,
+                    memory_writes,
+                    outcome,
+                })
+            })
+            .collect::<Result<_, DecodeError>>()?;
+        reader.finish()?;
+
+        Ok(Self { calls })
+    }
+}
+
+/// Records the host calls of an instance to an [`ExecutionLog`].
+///
+/// The functions of the imports are wrapped with
+/// [`Recorder::wrap_imports`], and the memory which the host functions
+/// write to is given with [`Recorder::set_memory`] once the instance is
+/// created.
+///
+/// The memory is copied before each host call to find the bytes the
+/// call wrote, so recording slows down the modules making a lot of host
+/// calls with a large memory. When a host function calls back into the
+/// instance, only the outermost host call is recorded, and the globals
+/// changed by the callback aren't.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::{imports, Function, Instance, Module, Recorder, Replayer, Store, TypedFunction};
+/// # fn main() -> anyhow::Result<()> {
+/// let mut store = Store::default();
+/// let module = Module::new(
+///     &store,
+///     r#"(module
+///         (import "host" "random" (func $random (result i32)))
+///         (func (export "run") (result i32) (call $random)))"#,
+/// )?;
+/// let imports = imports! {
+///     "host" => { "random" => Function::new_typed(&mut store, || 4) },
+/// };
+///
+/// let recorder = Recorder::new();
+/// let imports = recorder.wrap_imports(&mut store, &imports);
+/// let instance = Instance::new(&mut store, &module, &imports)?;
+/// let run: TypedFunction<(), i32> = instance.exports.get_typed_function(&store, "run")?;
+/// assert_eq!(run.call(&mut store)?, 4);
+///
+/// // The replayed calls return the recorded results, whatever the host does.
+/// let imports = imports! {
+///     "host" => { "random" => Function::new_typed(&mut store, || 5) },
+/// };
+/// let replayer = Replayer::new(recorder.log());
+/// let imports = replayer.wrap_imports(&mut store, &imports);
+/// let instance = Instance::new(&mut store, &module, &imports)?;
+/// let run: TypedFunction<(), i32> = instance.exports.get_typed_function(&store, "run")?;
+/// assert_eq!(run.call(&mut store)?, 4);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    state: Arc<Mutex<RecorderState>>,
+}
+
+#[derive(Debug, Default)]
+struct RecorderState {
+    memory: Option<Memory>,
+    log: ExecutionLog,
+    /// The number of host calls in progress.
+    depth: usize,
+}
+
+impl Recorder {
+    /// Creates a `Recorder` with an empty log.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps the functions of `imports`, to record their calls.
+    pub fn wrap_imports(&self, store: &mut impl AsStoreMut, imports: &Imports) -> Imports {
+        let env = FunctionEnv::new(store, ());
+        let mut wrapped = Imports::new();
+        for ((module, name), import) in imports {
+            let import = match import {
+                Extern::Function(function) => {
+                    let ty = function.ty(store);
+                    let state = self.state.clone();
+                    let (module, name) = (module.clone(), name.clone());
+                    Extern::Function(Function::new_with_env(
+                        store,
+                        &env,
+                        ty,
+                        move |mut env, args| {
+                            record_call(&state, &mut env, &function, &module, &name, args)
+                        },
+                    ))
+                }
+                import => import,
+            };
+            wrapped.define(&module, &name, import);
+        }
+        wrapped
+    }
+
+    /// Sets the memory whose changes by the host functions are recorded.
+    pub fn set_memory(&self, memory: Memory) {
+        self.state.lock().unwrap().memory = Some(memory);
+    }
+
+    /// Gets the log of the calls recorded so far.
+    pub fn log(&self) -> ExecutionLog {
+        self.state.lock().unwrap().log.clone()
+    }
+}
+
+fn record_call(
+    state: &Mutex<RecorderState>,
+    env: &mut FunctionEnvMut<()>,
+    function: &Function,
+    module: &str,
+    name: &str,
+    args: &[Value],
+) -> Result<Vec<Value>, RuntimeError> {
+    let (memory, outermost) = {
+        let mut state = state.lock().unwrap();
+        state.depth += 1;
+        (state.memory.clone(), state.depth == 1)
+    };
+    let before = match &memory {
+        Some(memory) if outermost => Some(copy_memory(env, memory)),
+        _ => None,
+    };
+    let result = function.call(env, args);
+    state.lock().unwrap().depth -= 1;
+    if !outermost {
+        return result.map(Vec::from);
+    }
+
+    let mut call = HostCall {
+        module: module.to_string(),
+        name: name.to_string(),
+        memory_size: None,
+        memory_writes: Vec::new(),
+        outcome: match &result {
+            Ok(values) => Ok(values.iter().map(CoreDumpValue::from).collect()),
+            Err(error) => Err(error.message()),
+        },
+    };
+    if let (Some(memory), Some(before)) = (&memory, before) {
+        let before = before?;
+        let after = copy_memory(env, memory)?;
+        if after.len() != before.len() {
+            call.memory_size = Some(memory.view(env).size());
+        }
+        call.memory_writes = memory_writes(&before, &after);
+    }
+    state.lock().unwrap().log.calls.push(call);
+
+    result.map(Vec::from)
+}
+
+fn copy_memory(env: &FunctionEnvMut<()>, memory: &Memory) -> Result<Vec<u8>, RuntimeError> {
+    memory
+        .view(env)
+        .copy_to_vec()
+        .map_err(|error| RuntimeError::new(error.to_string()))
+}
+
+/// The chunks of `after` which differ from `before`, merged when they
+/// are contiguous. The bytes past the end of `before` are compared with
+/// zeros, like the pages the memory grew by.
+fn memory_writes(before: &[u8], after: &[u8]) -> Vec<(u64, Vec<u8>)> {
+    let mut writes: Vec<(u64, Vec<u8>)> = Vec::new();
+    for (index, chunk) in after.chunks(WRITE_CHUNK_SIZE).enumerate() {
+        let offset = index * WRITE_CHUNK_SIZE;
+        let unchanged = match before.get(offset..offset + chunk.len()) {
+            Some(old) => old == chunk,
+            None => before.len() <= offset && chunk.iter().all(|byte| *byte == 0),
+        };
+        if unchanged {
+            continue;
+        }
+        match writes.last_mut() {
+            Some((start, bytes)) if *start as usize + bytes.len() == offset => {
+                bytes.extend_from_slice(chunk)
+            }
+            _ => writes.push((offset as u64, chunk.to_vec())),
+        }
+    }
+    writes
+}
+
+/// Replays an [`ExecutionLog`]: the functions of the imports are
+/// replaced with [`Replayer::wrap_imports`] by functions returning the
+/// recorded results, after writing the recorded bytes to the memory
+/// given with [`Replayer::set_memory`].
+///
+/// The calls must happen in the recorded order: the replayed functions
+/// trap when the execution diverges from the log.
+#[derive(Debug, Clone)]
+pub struct Replayer {
+    state: Arc<Mutex<ReplayerState>>,
+}
+
+#[derive(Debug)]
+struct ReplayerState {
+    memory: Option<Memory>,
+    calls: VecDeque<HostCall>,
+}
+
+impl Replayer {
+    /// Creates a `Replayer` of `log`.
+    pub fn new(log: ExecutionLog) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ReplayerState {
+                memory: None,
+                calls: log.calls.into(),
+            })),
+        }
+    }
+
+    /// Replaces the functions of `imports` by functions replaying their
+    /// calls. The other imports are kept.
+    pub fn wrap_imports(&self, store: &mut impl AsStoreMut, imports: &Imports) -> Imports {
+        let env = FunctionEnv::new(store, ());
+        let mut wrapped = Imports::new();
+        for ((module, name), import) in imports {
+            let import = match import {
+                Extern::Function(function) => {
+                    let ty = function.ty(store);
+                    let state = self.state.clone();
+                    let (module, name) = (module.clone(), name.clone());
+                    let function_ty = ty.clone();
+                    Extern::Function(Function::new_with_env(
+                        store,
+                        &env,
+                        ty,
+                        move |mut env, _args| {
+                            replay_call(&state, &mut env, &function_ty, &module, &name)
+                        },
+                    ))
+                }
+                import => import,
+            };
+            wrapped.define(&module, &name, import);
+        }
+        wrapped
+    }
+
+    /// Sets the memory the recorded bytes are written to.
+    pub fn set_memory(&self, memory: Memory) {
+        self.state.lock().unwrap().memory = Some(memory);
+    }
+
+    /// The number of recorded calls which haven't been replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.state.lock().unwrap().calls.len()
+    }
+}
+
+fn replay_call(
+    state: &Mutex<ReplayerState>,
+    env: &mut FunctionEnvMut<()>,
+    ty: &FunctionType,
+    module: &str,
+    name: &str,
+) -> Result<Vec<Value>, RuntimeError> {
+    let mut state = state.lock().unwrap();
+    let call = state.calls.pop_front().ok_or_else(|| {
+        RuntimeError::new(format!(
+            "the replay diverged: `{}`.`{}` was called past the end of the log",
+            module, name
+        ))
+    })?;
+    if call.module != module || call.name != name {
+        return Err(RuntimeError::new(format!(
+            "the replay diverged: `{}`.`{}` was called instead of `{}`.`{}`",
+            module, name, call.module, call.name
+        )));
+    }
+
+    if let Some(memory) = &state.memory {
+        if let Some(size) = call.memory_size {
+            let current = memory.view(env).size();
+            if size > current {
+                memory
+                    .grow(env, size - current)
+                    .map_err(|error| RuntimeError::new(error.to_string()))?;
+            }
+        }
+        let view = memory.view(env);
+        for (offset, bytes) in &call.memory_writes {
+            view.write(*offset, bytes)
+                .map_err(|error| RuntimeError::new(error.to_string()))?;
+        }
+    }
+
+    let values = call.outcome.map_err(RuntimeError::new)?;
+    if values.len() != ty.results().len() {
+        return Err(RuntimeError::new(format!(
+            "the replay diverged: `{}`.`{}` returned {} values instead of {}",
+            module,
+            name,
+            values.len(),
+            ty.results().len()
+        )));
+    }
+    values
+        .iter()
+        .zip(ty.results())
+        .map(|(value, ty)| {
+            let value = match *value {
+                CoreDumpValue::I32(value) => Value::I32(value),
+                CoreDumpValue::I64(value) => Value::I64(value),
+                CoreDumpValue::F32(value) => Value::F32(value),
+                CoreDumpValue::F64(value) => Value::F64(value),
+                CoreDumpValue::V128(value) => Value::V128(value),
+                CoreDumpValue::FuncRef(false) => Value::FuncRef(None),
+                CoreDumpValue::ExternRef(false) => Value::ExternRef(None),
+                CoreDumpValue::FuncRef(true) | CoreDumpValue::ExternRef(true) => {
+                    return Err(RuntimeError::new(format!(
+                        "the references returned by `{}`.`{}` can't be replayed",
+                        module, name
+                    )))
+                }
+            };
+            if value.ty() != *ty {
+                return Err(RuntimeError::new(format!(
+                    "the replay diverged: `{}`.`{}` returned a {} instead of a {}",
+                    module,
+                    name,
+                    value.ty(),
+                    ty
+                )));
+            }
+            Ok(value)
+        })
+        .collect()
+}
@@ -0,0 +1,157 @@
+use crate::sys::externals::{Extern, Global, Memory};
+#[cfg(feature = "compiler")]
+use crate::sys::imports::Imports;
+use crate::sys::instance::{Instance, InstantiationError};
+use crate::sys::module::Module;
+use crate::sys::store::{AsStoreMut, AsStoreRef};
+use crate::sys::{RuntimeError, Value};
+use std::sync::Arc;
+use thiserror::Error;
+use wasmer_types::{ExportIndex, GlobalIndex, MemoryIndex, Mutability};
+use wasmer_vm::{LinearMemory, MemoryError, MemorySnapshot};
+
+/// A snapshot of an initialized instance, to create new instances of
+/// its module in the same state without running the initialization of
+/// the guest again, e.g. once per request.
+///
+/// The snapshot holds the values of the mutable globals and the
+/// contents of the memories defined by the module, the imported ones
+/// being left to the host. On Linux, the memories of the new instances
+/// map the snapshot copy-on-write, see [`MemorySnapshot`].
+///
+/// The tables aren't part of the snapshot, so the instance shouldn't
+/// modify them before it's captured. The start function of the module
+/// is run again by [`InstanceSnapshot::instantiate`].
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::{imports, Instance, InstanceSnapshot, Module, Store, TypedFunction};
+/// # fn main() -> anyhow::Result<()> {
+/// let mut store = Store::default();
+/// let module = Module::new(&store, r#"
+///     (module
+///         (global $counter (mut i32) (i32.const 0))
+///         (func (export "increment") (result i32)
+///             (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+///             (global.get $counter)))
+/// "#)?;
+/// let instance = Instance::new(&mut store, &module, &imports! {})?;
+/// let increment: TypedFunction<(), i32> =
+///     instance.exports.get_typed_function(&store, "increment")?;
+/// increment.call(&mut store)?;
+///
+/// let snapshot = InstanceSnapshot::capture(&mut store, &instance)?;
+/// let instance = snapshot.instantiate(&mut store, &imports! {})?;
+/// let increment: TypedFunction<(), i32> =
+///     instance.exports.get_typed_function(&store, "increment")?;
+/// assert_eq!(increment.call(&mut store)?, 2);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct InstanceSnapshot {
+    module: Module,
+    globals: Vec<(GlobalIndex, Value)>,
+    memories: Vec<(MemoryIndex, Arc<MemorySnapshot>)>,
+}
+
+/// An error while capturing or restoring an [`InstanceSnapshot`].
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    /// A global holds a reference, which can't outlive its store.
+    #[error("the global {0} holds a reference")]
+    Reference(u32),
+
+    /// The instance couldn't be created.
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+
+    /// A memory couldn't be restored.
+    #[error(transparent)]
+    Memory(#[from] MemoryError),
+
+    /// A global couldn't be restored.
+    #[error(transparent)]
+    Global(#[from] RuntimeError),
+}
+
+fn global(store: &mut impl AsStoreMut, instance: &Instance, index: GlobalIndex) -> Global {
+    match instance.lookup_by_declaration(store, ExportIndex::Global(index)) {
+        Extern::Global(global) => global,
+        _ => unreachable!("the index of a global doesn't lead to a global"),
+    }
+}
+
+fn memory(store: &mut impl AsStoreMut, instance: &Instance, index: MemoryIndex) -> Memory {
+    match instance.lookup_by_declaration(store, ExportIndex::Memory(index)) {
+        Extern::Memory(memory) => memory,
+        _ => unreachable!("the index of a memory doesn't lead to a memory"),
+    }
+}
+
+impl InstanceSnapshot {
+    /// Takes a snapshot of the current state of `instance`.
+    pub fn capture(
+        store: &mut impl AsStoreMut,
+        instance: &Instance,
+    ) -> Result<Self, SnapshotError> {
+        let info = instance.module().info();
+
+        let mut globals = Vec::new();
+        for (index, ty) in info.globals.iter().skip(info.num_imported_globals) {
+            if ty.mutability != Mutability::Var {
+                continue;
+            }
+            let value = global(store, instance, index).get(store);
+            if let Value::FuncRef(Some(_)) | Value::ExternRef(Some(_)) = value {
+                return Err(SnapshotError::Reference(index.as_u32()));
+            }
+            globals.push((index, value));
+        }
+
+        let memories = info
+            .memories
+            .keys()
+            .skip(info.num_imported_memories)
+            .map(|index| {
+                let memory = memory(store, instance, index);
+                let vm_memory = memory.handle.get(store.as_store_ref().objects());
+                (index, Arc::new(MemorySnapshot::capture(vm_memory)))
+            })
+            .collect();
+
+        Ok(Self {
+            module: instance.module().clone(),
+            globals,
+            memories,
+        })
+    }
+
+    /// Gets the [`Module`] of the snapshot.
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    #[cfg(feature = "compiler")]
+    /// Creates a new instance of the module with `imports`, in the state
+    /// of the snapshot.
+    pub fn instantiate(
+        &self,
+        store: &mut impl AsStoreMut,
+        imports: &Imports,
+    ) -> Result<Instance, SnapshotError> {
+        let instance = Instance::new(store, &self.module, imports)?;
+        for (index, snapshot) in &self.memories {
+            let memory = memory(store, &instance, *index);
+            memory
+                .handle
+                .get_mut(store.objects_mut())
+                .restore_snapshot(snapshot)?;
+        }
+        for (index, value) in &self.globals {
+            global(store, &instance, *index).set(store, value.clone())?;
+        }
+        Ok(instance)
+    }
+}
@@ -0,0 +1,197 @@
+use crate::sys::externals::Function;
+use crate::sys::function_env::{FunctionEnv, FunctionEnvMut};
+use crate::sys::store::AsStoreMut;
+use crate::sys::value::Value;
+use crate::sys::RuntimeError;
+use std::future::Future;
+use std::pin::Pin;
+use thiserror::Error;
+use tokio::runtime::Handle;
+use tokio_util::sync::CancellationToken;
+use wasmer_types::FunctionType;
+
+/// The future returned by an async host function taking an environment,
+/// see [`TokioAdapter::function_with_env`].
+pub type HostFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<Value>, RuntimeError>> + 'a>>;
+
+/// Runs WebAssembly functions, and the async host functions they import,
+/// on a Tokio runtime.
+///
+/// The host functions created with [`TokioAdapter::function`] and
+/// [`TokioAdapter::function_with_env`] are async: the guest calling them
+/// waits for their future, which runs on the runtime of the adapter.
+/// [`TokioAdapter::call_async`] calls a function from an async context,
+/// running the guest with [`tokio::task::block_in_place`] so that the
+/// other tasks of the runtime keep running meanwhile.
+///
+/// The calls are cancelled with the [`CancellationToken`] of the
+/// adapter. The guest itself can't be interrupted, so the cancellation
+/// takes effect when it calls an async host function, or while it waits
+/// for one: the future of the host function is dropped, and the call
+/// fails with [`AsyncCallError::Cancelled`]. Cancelled adapters don't
+/// start new calls.
+///
+/// An adapter is meant to be used with a single store, and a cancellation
+/// token for the calls made in it, e.g. a child of the token of the
+/// request being served.
+///
+/// # Example
+///
+/// ```
+/// # use wasmer::{imports, Instance, Module, Store, TokioAdapter, Type, Value};
+/// # fn main() -> anyhow::Result<()> {
+/// # let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+/// # runtime.block_on(async {
+/// let mut store = Store::default();
+/// let adapter = TokioAdapter::current();
+/// let double = adapter.function(&mut store, ([Type::I32], [Type::I32]), |args| async move {
+///     tokio::task::yield_now().await;
+///     Ok(vec![Value::I32(args[0].unwrap_i32() * 2)])
+/// });
+/// let module = Module::new(&store, r#"
+///     (module
+///         (import "host" "double" (func $double (param i32) (result i32)))
+///         (func (export "quadruple") (param i32) (result i32)
+///             (call $double (call $double (local.get 0)))))
+/// "#)?;
+/// let imports = imports! { "host" => { "double" => double } };
+/// let instance = Instance::new(&mut store, &module, &imports)?;
+/// let quadruple = instance.exports.get_function("quadruple")?;
+/// let results = adapter.call_async(&mut store, quadruple, &[Value::I32(3)]).await?;
+/// assert_eq!(results[0].unwrap_i32(), 12);
+/// # Ok::<_, anyhow::Error>(())
+/// # })
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct TokioAdapter {
+    handle: Handle,
+    token: CancellationToken,
+}
+
+/// An error while calling a function with [`TokioAdapter::call_async`].
+#[derive(Error, Debug)]
+pub enum AsyncCallError {
+    /// The cancellation token of the adapter was cancelled.
+    #[error("the call was cancelled")]
+    Cancelled,
+
+    /// The guest trapped, or a host function failed.
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+}
+
+/// The error the async host functions fail with once the call is
+/// cancelled, turned into [`AsyncCallError::Cancelled`].
+#[derive(Error, Debug)]
+#[error("the call was cancelled")]
+struct Cancelled;
+
+impl TokioAdapter {
+    /// Creates an adapter running the futures of the host functions on
+    /// the runtime of `handle`, and cancelling the calls with `token`.
+    pub fn new(handle: Handle, token: CancellationToken) -> Self {
+        Self { handle, token }
+    }
+
+    /// Creates an adapter for the current runtime, with a new
+    /// cancellation token.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a Tokio runtime.
+    pub fn current() -> Self {
+        Self::new(Handle::current(), CancellationToken::new())
+    }
+
+    /// Returns the token cancelling the calls.
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Creates a host function of type `ty` returning the output of the
+    /// future returned by `func`.
+    ///
+    /// The guest calling the function blocks its thread until the future
+    /// is ready, so it must not be called from an async context, but
+    /// through [`TokioAdapter::call_async`] or from a thread of the
+    /// blocking pool.
+    pub fn function<FT, F, Fut>(&self, store: &mut impl AsStoreMut, ty: FT, func: F) -> Function
+    where
+        FT: Into<FunctionType>,
+        F: Fn(Vec<Value>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Vec<Value>, RuntimeError>>,
+    {
+        let env = FunctionEnv::new(store, ());
+        let adapter = self.clone();
+        Function::new_with_env(store, &env, ty, move |_env, args| {
+            adapter.block_on(func(args.to_vec()))
+        })
+    }
+
+    /// Creates a host function of type `ty`, with the environment `env`,
+    /// returning the output of the future returned by `func`.
+    ///
+    /// See [`TokioAdapter::function`].
+    pub fn function_with_env<FT, F, T: Send + 'static>(
+        &self,
+        store: &mut impl AsStoreMut,
+        env: &FunctionEnv<T>,
+        ty: FT,
+        func: F,
+    ) -> Function
+    where
+        FT: Into<FunctionType>,
+        F: for<'a> Fn(FunctionEnvMut<'a, T>, &'a [Value]) -> HostFuture<'a> + Send + Sync + 'static,
+    {
+        let adapter = self.clone();
+        Function::new_with_env(store, env, ty, move |env, args| {
+            adapter.block_on(func(env, args))
+        })
+    }
+
+    /// Calls `func` with `params`, and returns its results.
+    ///
+    /// The guest runs on the current thread, which is handed off to the
+    /// blocking pool of the runtime for the duration of the call.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called from a runtime with the `current_thread`
+    /// flavor, see [`tokio::task::block_in_place`].
+    pub async fn call_async(
+        &self,
+        store: &mut impl AsStoreMut,
+        func: &Function,
+        params: &[Value],
+    ) -> Result<Box<[Value]>, AsyncCallError> {
+        if self.token.is_cancelled() {
+            return Err(AsyncCallError::Cancelled);
+        }
+        tokio::task::block_in_place(|| func.call(store, params)).map_err(|e| {
+            if e.is::<Cancelled>() {
+                AsyncCallError::Cancelled
+            } else {
+                e.into()
+            }
+        })
+    }
+
+    /// Blocks the current thread on the future of a host function, until
+    /// it's ready or the call is cancelled.
+    fn block_on(
+        &self,
+        future: impl Future<Output = Result<Vec<Value>, RuntimeError>>,
+    ) -> Result<Vec<Value>, RuntimeError> {
+        let cancelled = || RuntimeError::user(Box::new(Cancelled));
+        if self.token.is_cancelled() {
+            return Err(cancelled());
+        }
+        self.handle.block_on(async {
+            tokio::select! {
+                result = future => result,
+                _ = self.token.cancelled() => Err(cancelled()),
+            }
+        })
+    }
+}
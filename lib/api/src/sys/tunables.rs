@@ -1,4 +1,4 @@
-pub use wasmer_compiler::BaseTunables;
+pub use wasmer_compiler::{BaseTunables, MemoryStylePolicy};
 
 // All BaseTunable definition now is in wasmer_compile crate
 // Tests are still here
@@ -18,11 +18,7 @@ mod tests {
 
     #[test]
     fn memory_style() {
-        let tunables = BaseTunables {
-            static_memory_bound: Pages(2048),
-            static_memory_offset_guard_size: 128,
-            dynamic_memory_offset_guard_size: 256,
-        };
+        let tunables = BaseTunables::new(Pages(2048), 128, 256);
 
         // No maximum
         let requested = MemoryType::new(3, None, true);
@@ -55,6 +51,38 @@ mod tests {
         }
     }
 
+    #[test]
+    fn memory_style_policy() {
+        let tunables = BaseTunables::new(Pages(2048), 128, 256);
+
+        // Small maximum, forced dynamic
+        let requested = MemoryType::new(3, Some(16), true);
+        let style = tunables
+            .clone()
+            .with_memory_style_policy(MemoryStylePolicy::Dynamic)
+            .memory_style(&requested);
+        match style {
+            MemoryStyle::Dynamic { offset_guard_size } => assert_eq!(offset_guard_size, 256),
+            s => panic!("Unexpected memory style: {:?}", s),
+        }
+
+        // Large maximum, forced static
+        let requested = MemoryType::new(3, Some(5_000), true);
+        let style = tunables
+            .with_memory_style_policy(MemoryStylePolicy::Static)
+            .memory_style(&requested);
+        match style {
+            MemoryStyle::Static {
+                bound,
+                offset_guard_size,
+            } => {
+                assert_eq!(bound, Pages(5_000));
+                assert_eq!(offset_guard_size, 128);
+            }
+            s => panic!("Unexpected memory style: {:?}", s),
+        }
+    }
+
     #[derive(Debug)]
     struct VMTinyMemory {
         mem: Vec<u8>,
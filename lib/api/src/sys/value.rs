@@ -1,6 +1,7 @@
 use std::convert::TryFrom;
 use std::fmt;
 use std::string::{String, ToString};
+use thiserror::Error;
 
 use wasmer_types::Type;
 #[cfg(feature = "compiler")]
@@ -158,6 +159,129 @@ impl Value {
         (FuncRef(&Option<Function>) funcref unwrap_funcref e)
         (V128(u128) v128 unwrap_v128 *e)
     }
+
+    /// Converts the value to a value of type `ty`, if it can be without
+    /// losing information.
+    ///
+    /// This lets the bindings of dynamic languages, which usually have a
+    /// single integer and a single floating-point type, build values
+    /// without knowing the exact Wasm type expected:
+    /// - integers are converted to other integer types, an `i64` fitting
+    ///   in an `i32` only when it's in the range of either `i32` or `u32`,
+    /// - integers and floats are converted to each other when the number
+    ///   is exactly representable in the target type,
+    /// - null references are converted to any reference type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{Type, Value};
+    /// assert_eq!(Value::I64(42).coerce(Type::I32).unwrap(), Value::I32(42));
+    /// assert_eq!(Value::F64(2.0).coerce(Type::I64).unwrap(), Value::I64(2));
+    /// assert!(Value::F64(2.5).coerce(Type::I64).is_err());
+    /// assert!(Value::I64(1 << 40).coerce(Type::I32).is_err());
+    /// ```
+    pub fn coerce(self, ty: Type) -> Result<Self, CoercionError> {
+        let out_of_range = |value: &Self| CoercionError::Range {
+            value: value.to_string(),
+            to: ty,
+        };
+        Ok(match (self, ty) {
+            (value, ty) if value.ty() == ty => value,
+            (Self::I32(i), Type::I64) => Self::I64(i.into()),
+            (Self::I64(i), Type::I32) => match i32::try_from(i) {
+                Ok(i) => Self::I32(i),
+                Err(_) => {
+                    Self::I32(u32::try_from(i).map_err(|_| out_of_range(&Self::I64(i)))? as i32)
+                }
+            },
+            (Self::I32(i), Type::F32 | Type::F64) => Self::I64(i.into()).coerce(ty)?,
+            (Self::I64(i), Type::F32) => {
+                let f = i as f32;
+                if !(-I64_BOUND..I64_BOUND).contains(&f64::from(f)) || f as i64 != i {
+                    return Err(out_of_range(&Self::I64(i)));
+                }
+                Self::F32(f)
+            }
+            (Self::I64(i), Type::F64) => {
+                let f = i as f64;
+                if !(-I64_BOUND..I64_BOUND).contains(&f) || f as i64 != i {
+                    return Err(out_of_range(&Self::I64(i)));
+                }
+                Self::F64(f)
+            }
+            (Self::F32(f), Type::F64) => Self::F64(f.into()),
+            (Self::F64(f), Type::F32) => {
+                if !f.is_nan() && f64::from(f as f32) != f {
+                    return Err(out_of_range(&Self::F64(f)));
+                }
+                Self::F32(f as f32)
+            }
+            (Self::F32(f), Type::I32 | Type::I64) => Self::F64(f.into()).coerce(ty)?,
+            (Self::F64(f), Type::I32 | Type::I64) => {
+                if f.fract() != 0.0 || !(-I64_BOUND..I64_BOUND).contains(&f) {
+                    return Err(out_of_range(&Self::F64(f)));
+                }
+                Self::I64(f as i64)
+                    .coerce(ty)
+                    .map_err(|_| out_of_range(&Self::F64(f)))?
+            }
+            (Self::ExternRef(None) | Self::FuncRef(None), Type::ExternRef) => Self::ExternRef(None),
+            (Self::ExternRef(None) | Self::FuncRef(None), Type::FuncRef) => Self::FuncRef(None),
+            (value, ty) => {
+                return Err(CoercionError::Type {
+                    from: value.ty(),
+                    to: ty,
+                })
+            }
+        })
+    }
+
+    /// Converts `values` to the given `types` with [`Value::coerce`],
+    /// e.g. to the parameters of a function before calling it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{imports, Instance, Module, Store, Value};
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let mut store = Store::default();
+    /// # let module = Module::new(&store, r#"
+    /// #     (module
+    /// #         (func (export "scale") (param i32 f32) (result f32)
+    /// #             (f32.mul (f32.convert_i32_s (local.get 0)) (local.get 1))))
+    /// # "#)?;
+    /// # let instance = Instance::new(&mut store, &module, &imports! {})?;
+    /// let scale = instance.exports.get_function("scale")?;
+    /// let ty = scale.ty(&store);
+    /// let params = Value::coerce_all(ty.params(), vec![Value::I64(3), Value::F64(0.5)])?;
+    /// assert_eq!(scale.call(&mut store, &params)?[0], Value::F32(1.5));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn coerce_all(
+        types: &[Type],
+        values: impl IntoIterator<Item = Self>,
+    ) -> Result<Vec<Self>, CoercionError> {
+        let values = values.into_iter().collect::<Vec<_>>();
+        if values.len() != types.len() {
+            return Err(CoercionError::Arity {
+                expected: types.len(),
+                given: values.len(),
+            });
+        }
+        values
+            .into_iter()
+            .zip(types)
+            .enumerate()
+            .map(|(index, (value, ty))| {
+                value.coerce(*ty).map_err(|error| CoercionError::Value {
+                    index,
+                    error: Box::new(error),
+                })
+            })
+            .collect()
+    }
 }
 
 impl fmt::Debug for Value {
@@ -241,6 +365,12 @@ impl From<f64> for Value {
     }
 }
 
+impl From<u128> for Value {
+    fn from(val: u128) -> Self {
+        Self::V128(val)
+    }
+}
+
 impl From<Function> for Value {
     fn from(val: Function) -> Self {
         Self::FuncRef(Some(val))
@@ -265,10 +395,55 @@ impl From<Option<ExternRef>> for Value {
     }
 }
 
+/// The bound of the range of `i64`, `2^63`, as a float.
+const I64_BOUND: f64 = 9_223_372_036_854_775_808.0;
+
+/// An error while converting a [`Value`] to another type with
+/// [`Value::coerce`] or [`Value::coerce_all`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CoercionError {
+    /// The value can't be converted to the type.
+    #[error("a value of type {from} can't be converted to {to}")]
+    Type {
+        /// The type of the value.
+        from: Type,
+        /// The type it was converted to.
+        to: Type,
+    },
+
+    /// The value isn't exactly representable in the type.
+    #[error("the value {value} isn't representable in {to}")]
+    Range {
+        /// The value.
+        value: String,
+        /// The type it was converted to.
+        to: Type,
+    },
+
+    /// A value of the list couldn't be converted.
+    #[error("value {index}: {error}")]
+    Value {
+        /// The index of the value in the list.
+        index: usize,
+        /// The error converting it.
+        error: Box<CoercionError>,
+    },
+
+    /// The list doesn't have as many values as types.
+    #[error("expected {expected} values, got {given}")]
+    Arity {
+        /// The number of types.
+        expected: usize,
+        /// The number of values.
+        given: usize,
+    },
+}
+
 const NOT_I32: &str = "Value is not of Wasm type i32";
 const NOT_I64: &str = "Value is not of Wasm type i64";
 const NOT_F32: &str = "Value is not of Wasm type f32";
 const NOT_F64: &str = "Value is not of Wasm type f64";
+const NOT_V128: &str = "Value is not of Wasm type v128";
 const NOT_FUNCREF: &str = "Value is not of Wasm type funcref";
 const NOT_EXTERNREF: &str = "Value is not of Wasm type externref";
 
@@ -320,6 +495,14 @@ impl TryFrom<Value> for f64 {
     }
 }
 
+impl TryFrom<Value> for u128 {
+    type Error = &'static str;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value.v128().ok_or(NOT_V128)
+    }
+}
+
 impl TryFrom<Value> for Option<Function> {
     type Error = &'static str;
 
@@ -473,4 +656,109 @@ mod tests {
         let result = f64::try_from(value);
         assert_eq!(result.unwrap_err(), "Value is not of Wasm type f64");
     }
+
+    #[test]
+    fn convert_value_to_u128() {
+        let value = Value::from(u128::MAX);
+        let result = u128::try_from(value);
+        assert_eq!(result.unwrap(), u128::MAX);
+
+        let value = Value::I64(42);
+        let result = u128::try_from(value);
+        assert_eq!(result.unwrap_err(), "Value is not of Wasm type v128");
+    }
+
+    #[test]
+    fn coerce_integers() {
+        assert_eq!(Value::I32(-1).coerce(Type::I64), Ok(Value::I64(-1)));
+        assert_eq!(Value::I64(-1).coerce(Type::I32), Ok(Value::I32(-1)));
+        assert_eq!(
+            Value::I64(u32::MAX.into()).coerce(Type::I32),
+            Ok(Value::I32(-1))
+        );
+        assert!(matches!(
+            Value::I64(1 << 32).coerce(Type::I32),
+            Err(CoercionError::Range { to: Type::I32, .. })
+        ));
+        assert!(Value::I64(i64::from(i32::MIN) - 1)
+            .coerce(Type::I32)
+            .is_err());
+
+        assert_eq!(Value::I32(3).coerce(Type::F32), Ok(Value::F32(3.0)));
+        assert_eq!(
+            Value::I64(1 << 53).coerce(Type::F64),
+            Ok(Value::F64(9007199254740992.0))
+        );
+        assert!(Value::I64((1 << 53) + 1).coerce(Type::F64).is_err());
+        assert!(Value::I64(i64::MAX).coerce(Type::F64).is_err());
+        assert!(Value::I64((1 << 24) + 1).coerce(Type::F32).is_err());
+    }
+
+    #[test]
+    fn coerce_floats() {
+        assert_eq!(Value::F32(1.5).coerce(Type::F64), Ok(Value::F64(1.5)));
+        assert_eq!(Value::F64(1.5).coerce(Type::F32), Ok(Value::F32(1.5)));
+        assert!(Value::F64(0.1).coerce(Type::F32).is_err());
+        assert!(matches!(
+            Value::F64(f64::NAN).coerce(Type::F32),
+            Ok(Value::F32(f)) if f.is_nan()
+        ));
+
+        assert_eq!(Value::F64(-2.0).coerce(Type::I32), Ok(Value::I32(-2)));
+        assert_eq!(
+            Value::F32(4e9).coerce(Type::I32),
+            Ok(Value::I32(4e9 as u32 as i32))
+        );
+        assert_eq!(
+            Value::F64(-1e18).coerce(Type::I64),
+            Ok(Value::I64(-1_000_000_000_000_000_000))
+        );
+        assert!(Value::F64(0.5).coerce(Type::I32).is_err());
+        assert!(Value::F64(1e10).coerce(Type::I32).is_err());
+        assert!(Value::F64(9.3e18).coerce(Type::I64).is_err());
+        assert!(Value::F64(f64::NAN).coerce(Type::I64).is_err());
+        assert!(Value::F64(f64::INFINITY).coerce(Type::I64).is_err());
+    }
+
+    #[test]
+    fn coerce_references() {
+        // References are never equal to each other, even the null ones.
+        assert!(matches!(
+            Value::null().coerce(Type::FuncRef),
+            Ok(Value::FuncRef(None))
+        ));
+        assert!(matches!(
+            Value::FuncRef(None).coerce(Type::ExternRef),
+            Ok(Value::ExternRef(None))
+        ));
+        assert_eq!(
+            Value::I32(0).coerce(Type::FuncRef).unwrap_err(),
+            CoercionError::Type {
+                from: Type::I32,
+                to: Type::FuncRef
+            }
+        );
+        assert!(Value::V128(0).coerce(Type::I64).is_err());
+        assert!(Value::null().coerce(Type::I32).is_err());
+    }
+
+    #[test]
+    fn coerce_all_values() {
+        let types = [Type::I32, Type::F64, Type::ExternRef];
+        let values =
+            Value::coerce_all(&types, vec![Value::I64(1), Value::I32(2), Value::null()]).unwrap();
+        assert_eq!(values[..2], [Value::I32(1), Value::F64(2.0)]);
+        assert!(matches!(values[2], Value::ExternRef(None)));
+        assert_eq!(
+            Value::coerce_all(&types, vec![Value::I64(1)]),
+            Err(CoercionError::Arity {
+                expected: 3,
+                given: 1
+            })
+        );
+        assert!(matches!(
+            Value::coerce_all(&types, vec![Value::I64(1), Value::F64(0.5), Value::I32(0)]),
+            Err(CoercionError::Value { index: 2, .. })
+        ));
+    }
 }
@@ -290,3 +290,31 @@ fn module_custom_sections() -> Result<(), String> {
     );
     Ok(())
 }
+
+#[cfg(all(feature = "sys", feature = "wasmprinter"))]
+#[test]
+fn module_to_wat() -> Result<(), String> {
+    let store = Store::default();
+    let wat = r#"(module
+(func (export "add_one") (param i32) (result i32)
+  (i32.add (local.get 0) (i32.const 1)))
+)"#;
+    let module = Module::new(&store, wat).map_err(|e| format!("{e:?}"))?;
+    let printed = module.to_wat().map_err(|e| format!("{e:?}"))?;
+    assert!(printed.contains("(export \"add_one\""));
+
+    // The printed module is equivalent to the original one.
+    let reparsed = Module::new(&store, &printed).map_err(|e| format!("{e:?}"))?;
+    assert_eq!(
+        reparsed.exports().collect::<Vec<_>>(),
+        module.exports().collect::<Vec<_>>()
+    );
+
+    // Deserialized modules don't have their binary anymore.
+    let serialized = module.serialize().map_err(|e| format!("{e:?}"))?;
+    let deserialized =
+        unsafe { Module::deserialize(&store, serialized) }.map_err(|e| format!("{e:?}"))?;
+    assert!(deserialized.to_wat().is_err());
+
+    Ok(())
+}
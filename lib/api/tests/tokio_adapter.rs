@@ -0,0 +1,100 @@
+#[cfg(feature = "tokio")]
+mod tokio_adapter {
+    use anyhow::Result;
+    use std::time::Duration;
+    use wasmer::*;
+
+    const WAT: &str = r#"
+        (module
+            (import "host" "double" (func $double (param i32) (result i32)))
+            (import "host" "add" (func $add (param i32)))
+            (import "host" "wait" (func $wait))
+            (func (export "run") (param $x i32) (result i32)
+                (call $add (local.get $x))
+                (call $double (local.get $x)))
+            (func (export "wait")
+                (call $wait)))
+    "#;
+
+    fn instantiate(
+        store: &mut Store,
+        adapter: &TokioAdapter,
+    ) -> Result<(Instance, FunctionEnv<i32>)> {
+        let env = FunctionEnv::new(store, 0);
+        let imports = imports! {
+            "host" => {
+                "double" => adapter.function(store, ([Type::I32], [Type::I32]), |args| async move {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                    Ok(vec![Value::I32(args[0].unwrap_i32() * 2)])
+                }),
+                "add" => adapter.function_with_env(store, &env, ([Type::I32], []), |mut env, args| {
+                    Box::pin(async move {
+                        tokio::task::yield_now().await;
+                        *env.data_mut() += args[0].unwrap_i32();
+                        Ok(vec![])
+                    })
+                }),
+                "wait" => adapter.function(store, ([], []), |_| std::future::pending()),
+            },
+        };
+        let module = Module::new(store, WAT)?;
+        let instance = Instance::new(store, &module, &imports)?;
+        Ok((instance, env))
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn async_host_functions_are_awaited() -> Result<()> {
+        let mut store = Store::default();
+        let adapter = TokioAdapter::current();
+        let (instance, env) = instantiate(&mut store, &adapter)?;
+        let run = instance.exports.get_function("run")?;
+
+        let results = adapter
+            .call_async(&mut store, run, &[Value::I32(21)])
+            .await?;
+        assert_eq!(results[0].unwrap_i32(), 42);
+        let results = adapter
+            .call_async(&mut store, run, &[Value::I32(1)])
+            .await?;
+        assert_eq!(results[0].unwrap_i32(), 2);
+        assert_eq!(*env.as_ref(&store), 22);
+
+        // The guest can also be run from the blocking pool.
+        let handle = tokio::task::spawn_blocking(move || {
+            let run = instance.exports.get_function("run").unwrap();
+            run.call(&mut store, &[Value::I32(5)])
+                .map(|r| r[0].unwrap_i32())
+        });
+        assert_eq!(handle.await??, 10);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn calls_are_cancelled() -> Result<()> {
+        let mut store = Store::default();
+        let adapter = TokioAdapter::current();
+        let (instance, env) = instantiate(&mut store, &adapter)?;
+        let run = instance.exports.get_function("run")?;
+        let wait = instance.exports.get_function("wait")?;
+
+        let token = adapter.cancellation_token().clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            token.cancel();
+        });
+        assert!(matches!(
+            adapter.call_async(&mut store, wait, &[]).await,
+            Err(AsyncCallError::Cancelled)
+        ));
+
+        // Cancelled adapters don't start new calls.
+        assert!(matches!(
+            adapter.call_async(&mut store, run, &[Value::I32(1)]).await,
+            Err(AsyncCallError::Cancelled)
+        ));
+        assert_eq!(*env.as_ref(&store), 0);
+
+        Ok(())
+    }
+}
@@ -1,12 +1,17 @@
 //! Utilities to read errors.
 //!
-//! Only one error can be registered at a time. Error are registered
-//! by Rust only, and are usually read by C or C++.
+//! Only one error can be registered at a time, per thread. Error are
+//! registered by Rust only, and are usually read by C or C++.
 //!
 //! Reading an error from C or C++ happens in 2 steps: Getting the
 //! error's length with [`wasmer_last_error_length`], and then reading
 //! the actual error with [`wasmer_last_error_message`].
 //!
+//! The kind of the error can be read with [`wasmer_last_error_code`]
+//! beforehand, and the trap that caused it, if any, with
+//! [`wasmer_last_error_trap`], so that bindings can map errors to
+//! their own exceptions without parsing the message.
+//!
 //! # Example
 //!
 //! ```rust
@@ -47,14 +52,55 @@
 //! # }
 //! ```
 
+use crate::wasm_c_api::trap::wasm_trap_t;
 use libc::{c_char, c_int};
 use std::cell::RefCell;
 use std::fmt::Display;
 use std::ptr::{self, NonNull};
 use std::slice;
+use wasmer_api::RuntimeError;
 
 thread_local! {
-    static LAST_ERROR: RefCell<Option<String>> = RefCell::new(None);
+    static LAST_ERROR: RefCell<Option<LastError>> = RefCell::new(None);
+}
+
+/// The kind of the last error, see [`wasmer_last_error_code`].
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum wasmer_error_code_t {
+    /// There is no error.
+    WASMER_ERROR_NONE = 0,
+
+    /// An error that doesn't fit in any of the other kinds.
+    WASMER_ERROR_GENERIC = 1,
+
+    /// The module is invalid, or could not be compiled.
+    WASMER_ERROR_COMPILE = 2,
+
+    /// The imports don't match what the module expects.
+    WASMER_ERROR_LINK = 3,
+
+    /// The module could not be instantiated for another reason than
+    /// its imports, e.g. the host doesn't support the CPU features it
+    /// was compiled for.
+    WASMER_ERROR_INSTANTIATION = 4,
+
+    /// A trap was raised while running WebAssembly code, see
+    /// [`wasmer_last_error_trap`].
+    WASMER_ERROR_TRAP = 5,
+
+    /// A memory access is out of the bounds of the memory.
+    WASMER_ERROR_MEMORY_ACCESS = 6,
+
+    /// A module could not be serialized or deserialized.
+    WASMER_ERROR_SERIALIZATION = 7,
+}
+
+struct LastError {
+    code: wasmer_error_code_t,
+    message: String,
+    trap: Option<RuntimeError>,
 }
 
 /// Rust function to register a new error.
@@ -67,14 +113,36 @@ thread_local! {
 /// update_last_error("Hello, World!");
 /// ```
 pub fn update_last_error<E: Display>(err: E) {
+    update_last_error_with_code(wasmer_error_code_t::WASMER_ERROR_GENERIC, err);
+}
+
+/// Rust function to register a new error of a given kind.
+pub(crate) fn update_last_error_with_code<E: Display>(code: wasmer_error_code_t, err: E) {
+    set_last_error(LastError {
+        code,
+        message: err.to_string(),
+        trap: None,
+    });
+}
+
+/// Rust function to register a trap as the new error.
+pub(crate) fn update_last_error_with_trap(trap: RuntimeError) {
+    set_last_error(LastError {
+        code: wasmer_error_code_t::WASMER_ERROR_TRAP,
+        message: trap.message(),
+        trap: Some(trap),
+    });
+}
+
+fn set_last_error(error: LastError) {
     LAST_ERROR.with(|prev| {
-        *prev.borrow_mut() = Some(err.to_string());
+        *prev.borrow_mut() = Some(error);
     });
 }
 
 /// Retrieve the most recent error, clearing it in the process.
 pub(crate) fn take_last_error() -> Option<String> {
-    LAST_ERROR.with(|prev| prev.borrow_mut().take())
+    LAST_ERROR.with(|prev| prev.borrow_mut().take().map(|error| error.message))
 }
 
 /// Gets the length in bytes of the last error if any, zero otherwise. This
@@ -90,7 +158,7 @@ pub(crate) fn take_last_error() -> Option<String> {
 #[no_mangle]
 pub extern "C" fn wasmer_last_error_length() -> c_int {
     LAST_ERROR.with(|prev| match *prev.borrow() {
-        Some(ref err) => err.len() as c_int + 1,
+        Some(ref err) => err.message.len() as c_int + 1,
         None => 0,
     })
 }
@@ -156,3 +224,104 @@ pub unsafe extern "C" fn wasmer_last_error_message(
 
     error_message.len() as c_int + 1
 }
+
+/// Gets the kind of the last error, or `WASMER_ERROR_NONE` if there
+/// is none. The error is not cleared.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// #
+/// int main() {
+///     // Create the engine and the store.
+///     wasm_engine_t* engine = wasm_engine_new();
+///     wasm_store_t* store = wasm_store_new(engine);
+///
+///     // Create a module whose start function traps.
+///     wasm_byte_vec_t wat;
+///     wasmer_byte_vec_new_from_string(&wat, "(module (func $f unreachable) (start $f))");
+///     wasm_byte_vec_t wasm;
+///     wat2wasm(&wat, &wasm);
+///     wasm_module_t* module = wasm_module_new(store, &wasm);
+///     assert(module);
+///
+///     // Instantiating it fails with a trap.
+///     wasm_extern_vec_t imports = WASM_EMPTY_VEC;
+///     wasm_instance_t* instance = wasm_instance_new(store, module, &imports, NULL);
+///     assert(!instance);
+///     assert(wasmer_last_error_code() == WASMER_ERROR_TRAP);
+///
+///     // The trap tells why.
+///     wasm_trap_t* trap = wasmer_last_error_trap();
+///     assert(trap);
+///     wasmer_trap_code_t code;
+///     assert(wasmer_trap_code(trap, &code));
+///     assert(code == WASMER_TRAP_CODE_UNREACHABLE_CODE_REACHED);
+///
+///     wasm_frame_vec_t trace;
+///     wasm_trap_trace(trap, &trace);
+///     assert(trace.size > 0);
+///
+///     // Clear the error.
+///     wasmer_last_error_clear();
+///     assert(wasmer_last_error_code() == WASMER_ERROR_NONE);
+///
+///     // Free everything.
+///     wasm_frame_vec_delete(&trace);
+///     wasm_trap_delete(trap);
+///     wasm_module_delete(module);
+///     wasm_byte_vec_delete(&wasm);
+///     wasm_byte_vec_delete(&wat);
+///     wasm_store_delete(store);
+///     wasm_engine_delete(engine);
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub extern "C" fn wasmer_last_error_code() -> wasmer_error_code_t {
+    LAST_ERROR.with(|prev| match *prev.borrow() {
+        Some(ref err) => err.code,
+        None => wasmer_error_code_t::WASMER_ERROR_NONE,
+    })
+}
+
+/// Gets a copy of the trap that caused the last error, if the last
+/// error is a `WASMER_ERROR_TRAP`, null otherwise. The error is not
+/// cleared.
+///
+/// The trap gives access to the trap code with `wasmer_trap_code` and
+/// to the WebAssembly stack trace with `wasm_trap_trace`. It must be
+/// freed with `wasm_trap_delete`.
+///
+/// # Example
+///
+/// See [`wasmer_last_error_code`].
+#[no_mangle]
+pub extern "C" fn wasmer_last_error_trap() -> Option<Box<wasm_trap_t>> {
+    LAST_ERROR.with(|prev| {
+        prev.borrow()
+            .as_ref()
+            .and_then(|err| err.trap.clone())
+            .map(|trap| Box::new(trap.into()))
+    })
+}
+
+/// Clears the last error, if any.
+///
+/// # Example
+///
+/// See [`wasmer_last_error_code`].
+#[no_mangle]
+pub extern "C" fn wasmer_last_error_clear() {
+    LAST_ERROR.with(|prev| {
+        prev.borrow_mut().take();
+    });
+}
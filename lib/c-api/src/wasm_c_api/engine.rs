@@ -1,6 +1,9 @@
 #[cfg(feature = "compiler")]
 pub use super::unstable::engine::wasmer_is_compiler_available;
-pub use super::unstable::engine::{wasm_config_set_features, wasm_config_set_target};
+use super::unstable::engine::CodeMemoryCallbacks;
+pub use super::unstable::engine::{
+    wasm_config_set_code_memory_allocator, wasm_config_set_features, wasm_config_set_target,
+};
 use super::unstable::features::wasmer_features_t;
 #[cfg(feature = "middlewares")]
 pub use super::unstable::middlewares::wasm_config_push_middleware;
@@ -82,6 +85,7 @@ pub struct wasm_config_t {
     pub(super) nan_canonicalization: bool,
     pub(super) features: Option<Box<wasmer_features_t>>,
     pub(super) target: Option<Box<wasmer_target_t>>,
+    pub(super) code_memory_allocator: Option<CodeMemoryCallbacks>,
 }
 
 /// Create a new default Wasmer configuration.
@@ -253,7 +257,7 @@ pub struct wasm_engine_t {
 use wasmer_api::CompilerConfig;
 
 #[cfg(all(feature = "compiler", any(feature = "compiler", feature = "dylib")))]
-fn get_default_compiler_config() -> Box<dyn CompilerConfig> {
+pub(crate) fn get_default_compiler_config() -> Box<dyn CompilerConfig> {
     cfg_if! {
         if #[cfg(feature = "cranelift")] {
             Box::new(wasmer_compiler_cranelift::Cranelift::default())
@@ -403,7 +407,7 @@ pub extern "C" fn wasm_engine_new_with_config(
                 compiler_config.canonicalize_nans(true);
             }
 
-            let inner: Engine =
+            let mut inner: Engine =
                          {
                             let mut builder = EngineBuilder::new(compiler_config);
 
@@ -417,9 +421,14 @@ pub extern "C" fn wasm_engine_new_with_config(
 
                             builder.engine()
                         };
+
+            if let Some(allocator) = config.code_memory_allocator {
+                inner.set_code_memory_allocator(allocator);
+            }
+
             Some(Box::new(wasm_engine_t { inner }))
         } else {
-            let inner: Engine =
+            let mut inner: Engine =
                      {
                             let mut builder = EngineBuilder::headless();
 
@@ -433,6 +442,11 @@ pub extern "C" fn wasm_engine_new_with_config(
 
                             builder.engine()
                     };
+
+            if let Some(allocator) = config.code_memory_allocator {
+                inner.set_code_memory_allocator(allocator);
+            }
+
             Some(Box::new(wasm_engine_t { inner }))
         }
     }
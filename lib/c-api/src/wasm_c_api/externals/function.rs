@@ -96,6 +96,76 @@ pub unsafe extern "C" fn wasm_func_new(
     }))
 }
 
+/// Creates a host function whose callback receives `env`, a pointer to
+/// data owned by the embedder.
+///
+/// `env` may be null, in which case the callback receives a null
+/// pointer. `env_finalizer`, if any, is called with `env` once the
+/// store and all the functions referring to it have been deleted, so
+/// that `env` can be freed without keeping it in global state.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// #
+/// typedef struct {
+///     int calls;
+///     int finalized;
+/// } counter_t;
+///
+/// wasm_trap_t* count(void* env, const wasm_val_vec_t* args, wasm_val_vec_t* results) {
+///     counter_t* counter = (counter_t*) env;
+///     counter->calls += 1;
+///     results->data[0].kind = WASM_I32;
+///     results->data[0].of.i32 = counter->calls;
+///
+///     return NULL;
+/// }
+///
+/// void finalize(void* env) {
+///     ((counter_t*) env)->finalized = 1;
+/// }
+///
+/// int main() {
+///     // Create the engine and the store.
+///     wasm_engine_t* engine = wasm_engine_new();
+///     wasm_store_t* store = wasm_store_new(engine);
+///
+///     // Create a host function with an environment.
+///     counter_t counter = { 0, 0 };
+///     wasm_functype_t* type = wasm_functype_new_0_1(wasm_valtype_new_i32());
+///     wasm_func_t* func = wasm_func_new_with_env(store, type, count, &counter, finalize);
+///     assert(func);
+///
+///     // Call it twice.
+///     wasm_val_vec_t args = WASM_EMPTY_VEC;
+///     wasm_val_t results_val[1] = { WASM_INIT_VAL };
+///     wasm_val_vec_t results = WASM_ARRAY_VEC(results_val);
+///     assert(wasm_func_call(func, &args, &results) == NULL);
+///     assert(wasm_func_call(func, &args, &results) == NULL);
+///     assert(results_val[0].of.i32 == 2);
+///     assert(counter.calls == 2);
+///
+///     // The environment is finalized with the store.
+///     wasm_func_delete(func);
+///     wasm_functype_delete(type);
+///     assert(counter.finalized == 0);
+///     wasm_store_delete(store);
+///     assert(counter.finalized == 1);
+///
+///     // Free everything.
+///     wasm_engine_delete(engine);
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
 #[no_mangle]
 pub unsafe extern "C" fn wasm_func_new_with_env(
     store: Option<&mut wasm_store_t>,
@@ -115,7 +185,7 @@ pub unsafe extern "C" fn wasm_func_new_with_env(
     #[derive(Clone)]
     #[repr(C)]
     struct WrapperEnv {
-        env: FunctionCEnv,
+        env: *mut c_void,
         env_finalizer: Arc<Mutex<Option<wasm_env_finalizer_t>>>,
     }
 
@@ -129,7 +199,7 @@ pub unsafe extern "C" fn wasm_func_new_with_env(
             if let Ok(mut guard) = self.env_finalizer.lock() {
                 if Arc::strong_count(&self.env_finalizer) == 1 {
                     if let Some(env_finalizer) = guard.take() {
-                        unsafe { (env_finalizer)(self.env.as_ptr()) };
+                        unsafe { (env_finalizer)(self.env) };
                     }
                 }
             }
@@ -154,7 +224,7 @@ pub unsafe extern "C" fn wasm_func_new_with_env(
         ]
         .into();
 
-        let trap = callback(env.data().env.as_ptr(), &processed_args, &mut results);
+        let trap = callback(env.data().env, &processed_args, &mut results);
 
         if let Some(trap) = trap {
             return Err(trap.inner);
@@ -172,10 +242,7 @@ pub unsafe extern "C" fn wasm_func_new_with_env(
     let env = FunctionEnv::new(
         &mut store_mut,
         WrapperEnv {
-            env: FunctionCEnv::new(c_try!(
-                std::ptr::NonNull::new(env),
-                "Function environment cannot be a null pointer."
-            )),
+            env,
             env_finalizer: Arc::new(Mutex::new(env_finalizer)),
         },
     );
@@ -227,6 +294,14 @@ pub unsafe extern "C" fn wasm_func_call(
     }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn wasm_func_same(
+    wasm_func1: &wasm_func_t,
+    wasm_func2: &wasm_func_t,
+) -> bool {
+    wasm_func1.extern_.function() == wasm_func2.extern_.function()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn wasm_func_param_arity(func: &wasm_func_t) -> usize {
     func.extern_
@@ -1,5 +1,7 @@
 use super::super::types::wasm_memorytype_t;
 use super::{super::store::wasm_store_t, wasm_extern_t};
+use crate::error::update_last_error;
+use std::slice;
 use wasmer_api::{Extern, Memory, Pages};
 
 #[allow(non_camel_case_types)]
@@ -60,7 +62,12 @@ pub unsafe extern "C" fn wasm_memory_type(
     )))
 }
 
-// get a raw pointer into bytes
+/// Returns a raw pointer to the first byte of the memory.
+///
+/// The pointer is invalidated when the memory grows, and must be
+/// queried again after any call that may grow it, including calls into
+/// WebAssembly. Prefer [`wasmer_memory_read`] and [`wasmer_memory_write`]
+/// which are checked against the current size of the memory.
 #[no_mangle]
 pub unsafe extern "C" fn wasm_memory_data(memory: &mut wasm_memory_t) -> *mut u8 {
     memory
@@ -70,7 +77,11 @@ pub unsafe extern "C" fn wasm_memory_data(memory: &mut wasm_memory_t) -> *mut u8
         .data_ptr()
 }
 
-// size in bytes
+/// Returns the current size of the memory in bytes.
+///
+/// The size always reflects the latest growth of the memory, so the
+/// range `[0, wasm_memory_data_size(memory))` is valid for the pointer
+/// returned by `wasm_memory_data` as long as the memory doesn't grow.
 #[no_mangle]
 pub unsafe extern "C" fn wasm_memory_data_size(memory: &wasm_memory_t) -> usize {
     memory
@@ -102,3 +113,132 @@ pub unsafe extern "C" fn wasm_memory_grow(memory: &mut wasm_memory_t, delta: u32
         .grow(&mut memory.extern_.store.store_mut(), Pages(delta))
         .is_ok()
 }
+
+/// Copies `length` bytes of the memory, starting at `offset`, into
+/// `buffer`.
+///
+/// Returns `false` and sets the last error, leaving `buffer`
+/// untouched, if the memory is null or if the range is out of the
+/// bounds of the memory.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// #
+/// int main() {
+///     // Create the engine and the store.
+///     wasm_engine_t* engine = wasm_engine_new();
+///     wasm_store_t* store = wasm_store_new(engine);
+///
+///     // Create a memory of one page.
+///     wasm_limits_t limits = { 1, 2 };
+///     wasm_memorytype_t* memory_type = wasm_memorytype_new(&limits);
+///     wasm_memory_t* memory = wasm_memory_new(store, memory_type);
+///
+///     // Write to it and read it back.
+///     const uint8_t input[4] = { 1, 2, 3, 4 };
+///     uint8_t output[4] = { 0 };
+///     assert(wasmer_memory_write(memory, 100, input, 4));
+///     assert(wasmer_memory_read(memory, 100, output, 4));
+///     assert(output[0] == 1 && output[3] == 4);
+///
+///     // A null memory is rejected...
+///     assert(!wasmer_memory_read(NULL, 0, output, 4));
+///     assert(wasmer_last_error_length() > 0);
+///
+///     // ... and so are the accesses past the end of the memory...
+///     assert(!wasmer_memory_read(memory, 65535, output, 4));
+///     assert(wasmer_last_error_length() > 0);
+///
+///     // ... until it grows.
+///     assert(wasm_memory_grow(memory, 1));
+///     assert(wasm_memory_data_size(memory) == 2 * 65536);
+///     assert(wasmer_memory_read(memory, 65535, output, 4));
+///
+///     // Free everything.
+///     wasm_memory_delete(memory);
+///     wasm_memorytype_delete(memory_type);
+///     wasm_store_delete(store);
+///     wasm_engine_delete(engine);
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_memory_read(
+    memory: Option<&wasm_memory_t>,
+    offset: u64,
+    buffer: *mut u8,
+    length: usize,
+) -> bool {
+    let memory = match memory {
+        Some(memory) => memory,
+        None => {
+            update_last_error("The memory cannot be a null pointer.");
+            return false;
+        }
+    };
+    if buffer.is_null() && length > 0 {
+        update_last_error("The buffer cannot be a null pointer.");
+        return false;
+    }
+    let buffer: &mut [u8] = if length == 0 {
+        &mut []
+    } else {
+        slice::from_raw_parts_mut(buffer, length)
+    };
+    c_try!(memory
+        .extern_
+        .memory()
+        .view(&memory.extern_.store.store())
+        .read(offset, buffer); code WASMER_ERROR_MEMORY_ACCESS; otherwise false);
+    true
+}
+
+/// Copies `length` bytes from `buffer` into the memory, starting at
+/// `offset`.
+///
+/// Returns `false` and sets the last error, leaving the memory
+/// untouched, if the memory is null or if the range is out of the
+/// bounds of the memory.
+///
+/// # Example
+///
+/// See [`wasmer_memory_read`].
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_memory_write(
+    memory: Option<&mut wasm_memory_t>,
+    offset: u64,
+    buffer: *const u8,
+    length: usize,
+) -> bool {
+    let memory = match memory {
+        Some(memory) => memory,
+        None => {
+            update_last_error("The memory cannot be a null pointer.");
+            return false;
+        }
+    };
+    if buffer.is_null() && length > 0 {
+        update_last_error("The buffer cannot be a null pointer.");
+        return false;
+    }
+    let buffer: &[u8] = if length == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(buffer, length)
+    };
+    c_try!(memory
+        .extern_
+        .memory()
+        .view(&memory.extern_.store.store())
+        .write(offset, buffer); code WASMER_ERROR_MEMORY_ACCESS; otherwise false);
+    true
+}
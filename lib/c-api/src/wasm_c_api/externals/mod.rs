@@ -86,6 +86,18 @@ pub unsafe extern "C" fn wasm_extern_copy(r#extern: &wasm_extern_t) -> Box<wasm_
 #[no_mangle]
 pub unsafe extern "C" fn wasm_extern_delete(_extern: Option<Box<wasm_extern_t>>) {}
 
+/// Check whether two externs refer to the same entity.
+#[no_mangle]
+pub extern "C" fn wasm_extern_same(extern1: &wasm_extern_t, extern2: &wasm_extern_t) -> bool {
+    match (&extern1.inner, &extern2.inner) {
+        (Extern::Function(f1), Extern::Function(f2)) => f1 == f2,
+        (Extern::Global(g1), Extern::Global(g2)) => g1 == g2,
+        (Extern::Memory(m1), Extern::Memory(m2)) => m1 == m2,
+        (Extern::Table(t1), Extern::Table(t2)) => t1 == t2,
+        _ => false,
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn wasm_func_as_extern(func: Option<&wasm_func_t>) -> Option<&wasm_extern_t> {
     Some(&func?.extern_)
@@ -1,7 +1,13 @@
 use super::super::store::wasm_store_t;
 use super::super::types::{wasm_ref_t, wasm_table_size_t, wasm_tabletype_t};
-use super::wasm_extern_t;
-use wasmer_api::Extern;
+use super::{wasm_extern_t, wasm_func_t};
+use crate::error::update_last_error;
+use wasmer_api::{Extern, Table, Type, Value};
+
+/// `wasm_ref_t` can't be built yet, so the only reference the
+/// standard functions accept is the null reference.
+const REFERENCES_UNSUPPORTED: &str =
+    "Only null references are supported, use `wasmer_table_set` to store functions in a table.";
 
 #[allow(non_camel_case_types)]
 #[repr(C)]
@@ -19,13 +25,52 @@ impl wasm_table_t {
     }
 }
 
+/// Get the table of `funcref`s of `table`, or set the last error if
+/// it contains another type of elements.
+unsafe fn funcref_table(table: &wasm_table_t) -> Option<Table> {
+    let inner = table.extern_.table();
+    if inner.ty(&table.extern_.store.store()).ty != Type::FuncRef {
+        update_last_error("The table doesn't contain functions.");
+        return None;
+    }
+
+    Some(inner)
+}
+
+/// The null reference of the type of the elements of a table.
+fn null_value(ty: Type) -> Value {
+    match ty {
+        Type::FuncRef => Value::FuncRef(None),
+        _ => Value::ExternRef(None),
+    }
+}
+
+/// Creates a new table, whose elements are all set to `init`.
+///
+/// Only a null `init` is supported for the moment.
 #[no_mangle]
 pub unsafe extern "C" fn wasm_table_new(
-    _store: Option<&wasm_store_t>,
-    _table_type: Option<&wasm_tabletype_t>,
-    _init: *const wasm_ref_t,
+    store: Option<&mut wasm_store_t>,
+    table_type: Option<&wasm_tabletype_t>,
+    init: *const wasm_ref_t,
 ) -> Option<Box<wasm_table_t>> {
-    todo!("get val from init somehow");
+    let store = store?;
+    let table_type = table_type?.inner().table_type;
+    if !init.is_null() {
+        update_last_error(REFERENCES_UNSUPPORTED);
+        return None;
+    }
+
+    let mut store_mut = store.inner.store_mut();
+    let table = c_try!(Table::new(
+        &mut store_mut,
+        table_type,
+        null_value(table_type.ty)
+    ));
+
+    Some(Box::new(wasm_table_t {
+        extern_: wasm_extern_t::new(store.inner.clone(), table.into()),
+    }))
 }
 
 #[no_mangle]
@@ -50,13 +95,217 @@ pub unsafe extern "C" fn wasm_table_same(
     wasm_table1.extern_.table() == wasm_table2.extern_.table()
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn wasm_table_type(
+    table: Option<&wasm_table_t>,
+) -> Option<Box<wasm_tabletype_t>> {
+    let table = table?;
+    Some(Box::new(wasm_tabletype_t::new(
+        table.extern_.table().ty(&table.extern_.store.store()),
+    )))
+}
+
+/// Grows the table by `delta` elements, set to `init`.
+///
+/// Only a null `init` is supported for the moment, see
+/// [`wasmer_table_grow`] to grow a table with a function.
 #[no_mangle]
 pub unsafe extern "C" fn wasm_table_grow(
-    _table: &mut wasm_table_t,
-    _delta: wasm_table_size_t,
-    _init: *mut wasm_ref_t,
+    table: &mut wasm_table_t,
+    delta: wasm_table_size_t,
+    init: *mut wasm_ref_t,
+) -> bool {
+    if !init.is_null() {
+        update_last_error(REFERENCES_UNSUPPORTED);
+        return false;
+    }
+
+    let inner = table.extern_.table();
+    let init = null_value(inner.ty(&table.extern_.store.store()).ty);
+    c_try!(inner.grow(&mut table.extern_.store.store_mut(), delta, init); otherwise false);
+
+    true
+}
+
+/// Unstable non-standard Wasmer-specific API to get the function at
+/// `index` in a table of `funcref`s.
+///
+/// Returns null if the element is a null reference, or if it can't be
+/// read, in which case the last error is set: the index is out of
+/// bounds, or the table doesn't contain functions.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// #
+/// wasm_trap_t* forty_two(const wasm_val_vec_t* args, wasm_val_vec_t* results) {
+///     results->data[0].kind = WASM_I32;
+///     results->data[0].of.i32 = 42;
+///
+///     return NULL;
+/// }
+///
+/// int main() {
+///     // Create the engine and the store.
+///     wasm_engine_t* engine = wasm_engine_new();
+///     wasm_store_t* store = wasm_store_new(engine);
+///
+///     // A module calling the functions of its table by index.
+///     wasm_byte_vec_t wat;
+///     wasmer_byte_vec_new_from_string(
+///         &wat,
+///         "(module\n"
+///         "  (type $t (func (result i32)))\n"
+///         "  (table (export \"table\") 1 funcref)\n"
+///         "  (func (export \"dispatch\") (param i32) (result i32)\n"
+///         "    local.get 0\n"
+///         "    call_indirect (type $t)))"
+///     );
+///     wasm_byte_vec_t wasm;
+///     wat2wasm(&wat, &wasm);
+///     wasm_module_t* module = wasm_module_new(store, &wasm);
+///     assert(module);
+///
+///     wasm_extern_vec_t imports = WASM_EMPTY_VEC;
+///     wasm_instance_t* instance = wasm_instance_new(store, module, &imports, NULL);
+///     assert(instance);
+///
+///     wasm_extern_vec_t exports;
+///     wasm_instance_exports(instance, &exports);
+///     wasm_table_t* table = wasm_extern_as_table(exports.data[0]);
+///     const wasm_func_t* dispatch = wasm_extern_as_func(exports.data[1]);
+///
+///     // The table starts with a null element.
+///     assert(wasm_table_size(table) == 1);
+///     assert(wasmer_table_get(table, 0) == NULL);
+///
+///     // Add a host function to the table.
+///     wasm_functype_t* type = wasm_functype_new_0_1(wasm_valtype_new_i32());
+///     wasm_func_t* host_func = wasm_func_new(store, type, forty_two);
+///     assert(wasmer_table_grow(table, 1, host_func));
+///     assert(wasm_table_size(table) == 2);
+///
+///     wasm_func_t* element = wasmer_table_get(table, 1);
+///     assert(element);
+///
+///     // The module can call it.
+///     wasm_val_t arguments_val[1] = { WASM_I32_VAL(1) };
+///     wasm_val_vec_t arguments = WASM_ARRAY_VEC(arguments_val);
+///     wasm_val_t results_val[1] = { WASM_INIT_VAL };
+///     wasm_val_vec_t results = WASM_ARRAY_VEC(results_val);
+///     assert(wasm_func_call(dispatch, &arguments, &results) == NULL);
+///     assert(results_val[0].of.i32 == 42);
+///
+///     // Remove it.
+///     assert(wasmer_table_set(table, 1, NULL));
+///     assert(wasmer_table_get(table, 1) == NULL);
+///
+///     // Out of bounds accesses are reported.
+///     assert(!wasmer_table_set(table, 2, host_func));
+///     assert(wasmer_last_error_length() > 0);
+///
+///     // Free everything.
+///     wasm_func_delete(element);
+///     wasm_func_delete(host_func);
+///     wasm_functype_delete(type);
+///     wasm_extern_vec_delete(&exports);
+///     wasm_instance_delete(instance);
+///     wasm_module_delete(module);
+///     wasm_byte_vec_delete(&wasm);
+///     wasm_byte_vec_delete(&wat);
+///     wasm_store_delete(store);
+///     wasm_engine_delete(engine);
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_table_get(
+    table: Option<&wasm_table_t>,
+    index: wasm_table_size_t,
+) -> Option<Box<wasm_func_t>> {
+    let table = table?;
+    let inner = funcref_table(table)?;
+    let mut store = table.extern_.store.clone();
+    let value = c_try!(inner
+        .get(&mut store.store_mut(), index)
+        .ok_or_else(|| format!("The table index {} is out of bounds.", index)));
+
+    match value {
+        Value::FuncRef(Some(function)) => Some(Box::new(wasm_func_t {
+            extern_: wasm_extern_t::new(store, function.into()),
+        })),
+        _ => None,
+    }
+}
+
+/// Unstable non-standard Wasmer-specific API to set the element at
+/// `index` in a table of `funcref`s to `func`, or to a null reference
+/// if `func` is null.
+///
+/// Returns `false` and sets the last error if the index is out of
+/// bounds, if the table doesn't contain functions, or if `func`
+/// belongs to another store.
+///
+/// # Example
+///
+/// See [`wasmer_table_get`].
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_table_set(
+    table: Option<&mut wasm_table_t>,
+    index: wasm_table_size_t,
+    func: Option<&wasm_func_t>,
 ) -> bool {
-    // TODO: maybe need to look at result to return `true`; also maybe report error here
-    //wasm_table.inner.grow(delta, init).is_ok()
-    todo!("Blocked on transforming ExternRef into a val type")
+    let table = match table {
+        Some(table) => table,
+        None => return false,
+    };
+    let inner = match funcref_table(table) {
+        Some(inner) => inner,
+        None => return false,
+    };
+    let value = Value::FuncRef(func.map(|func| func.extern_.function()));
+
+    c_try!(inner.set(&mut table.extern_.store.store_mut(), index, value); otherwise false);
+
+    true
+}
+
+/// Unstable non-standard Wasmer-specific API to grow a table of
+/// `funcref`s by `delta` elements, set to `init`, or to a null
+/// reference if `init` is null.
+///
+/// Returns `false` and sets the last error if the table can't grow
+/// by `delta` elements, if the table doesn't contain functions, or if
+/// `init` belongs to another store.
+///
+/// # Example
+///
+/// See [`wasmer_table_get`].
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_table_grow(
+    table: Option<&mut wasm_table_t>,
+    delta: wasm_table_size_t,
+    init: Option<&wasm_func_t>,
+) -> bool {
+    let table = match table {
+        Some(table) => table,
+        None => return false,
+    };
+    let inner = match funcref_table(table) {
+        Some(inner) => inner,
+        None => return false,
+    };
+    let init = Value::FuncRef(init.map(|init| init.extern_.function()));
+
+    c_try!(inner.grow(&mut table.extern_.store.store_mut(), delta, init); otherwise false);
+
+    true
 }
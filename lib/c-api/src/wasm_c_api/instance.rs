@@ -2,6 +2,7 @@ use super::externals::{wasm_extern_t, wasm_extern_vec_t};
 use super::module::wasm_module_t;
 use super::store::{wasm_store_t, StoreRef};
 use super::trap::wasm_trap_t;
+use crate::error::{update_last_error_with_code, update_last_error_with_trap, wasmer_error_code_t};
 use wasmer_api::{Extern, Instance, InstantiationError};
 
 /// Opaque type representing a WebAssembly instance.
@@ -42,13 +43,10 @@ pub unsafe extern "C" fn wasm_instance_new(
     trap: Option<&mut *mut wasm_trap_t>,
 ) -> Option<Box<wasm_instance_t>> {
     let store = store?;
-    let mut store_mut = store.inner.store_mut();
     let module = module?;
     let imports = imports?;
 
-    let wasm_module = &module.inner;
-    let module_imports = wasm_module.imports();
-    let module_import_count = module_imports.len();
+    let module_import_count = module.inner.imports().len();
     let externs = imports
         .as_slice()
         .iter()
@@ -56,16 +54,31 @@ pub unsafe extern "C" fn wasm_instance_new(
         .take(module_import_count)
         .collect::<Vec<Extern>>();
 
-    let instance = match Instance::new_by_index(&mut store_mut, wasm_module, &externs) {
+    instantiate(&mut store.inner, module, &externs, trap)
+}
+
+/// Instantiates `module` in `store`, with the given imports, and
+/// reports the errors like [`wasm_instance_new`] does.
+pub(crate) unsafe fn instantiate(
+    store: &mut StoreRef,
+    module: &wasm_module_t,
+    externs: &[Extern],
+    trap: Option<&mut *mut wasm_trap_t>,
+) -> Option<Box<wasm_instance_t>> {
+    let mut store_mut = store.store_mut();
+
+    let instance = match Instance::new_by_index(&mut store_mut, &module.inner, externs) {
         Ok(instance) => instance,
 
         Err(InstantiationError::Link(link_error)) => {
-            crate::error::update_last_error(link_error);
+            update_last_error_with_code(wasmer_error_code_t::WASMER_ERROR_LINK, link_error);
 
             return None;
         }
 
         Err(InstantiationError::Start(runtime_error)) => {
+            update_last_error_with_trap(runtime_error.clone());
+
             if let Some(trap) = trap {
                 let this_trap: Box<wasm_trap_t> = Box::new(runtime_error.into());
                 *trap = Box::into_raw(this_trap);
@@ -75,26 +88,26 @@ pub unsafe extern "C" fn wasm_instance_new(
         }
 
         Err(e @ InstantiationError::CpuFeature(_)) => {
-            crate::error::update_last_error(e);
+            update_last_error_with_code(wasmer_error_code_t::WASMER_ERROR_INSTANTIATION, e);
 
             return None;
         }
 
         Err(e @ InstantiationError::DifferentStores) => {
-            crate::error::update_last_error(e);
+            update_last_error_with_code(wasmer_error_code_t::WASMER_ERROR_INSTANTIATION, e);
 
             return None;
         }
 
         Err(e @ InstantiationError::DifferentArchOS) => {
-            crate::error::update_last_error(e);
+            update_last_error_with_code(wasmer_error_code_t::WASMER_ERROR_INSTANTIATION, e);
 
             return None;
         }
     };
 
     Some(Box::new(wasm_instance_t {
-        store: store.inner.clone(),
+        store: store.clone(),
         inner: instance,
     }))
 }
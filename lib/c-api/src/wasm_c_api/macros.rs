@@ -317,6 +317,22 @@ macro_rules! c_try {
             }
         }
     }};
+    ($expr:expr; code $code:ident; otherwise $return:expr) => {{
+        let res: Result<_, _> = $expr;
+        match res {
+            Ok(val) => val,
+            Err(err) => {
+                crate::error::update_last_error_with_code(
+                    crate::error::wasmer_error_code_t::$code,
+                    err,
+                );
+                return $return;
+            }
+        }
+    }};
+    ($expr:expr; code $code:ident) => {{
+        c_try!($expr; code $code; otherwise None)
+    }};
     ($expr:expr) => {{
         c_try!($expr; otherwise None)
     }};
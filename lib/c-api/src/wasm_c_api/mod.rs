@@ -20,6 +20,16 @@
 //!
 //! Every module comes with examples and entry points to guide the
 //! discovery of this API.
+//!
+//! # Thread Safety
+//!
+//! An [engine][engine::wasm_engine_t] and a [module][module::wasm_module_t]
+//! can be used from several threads at the same time, e.g. to
+//! instantiate the same module concurrently. A [store][store::wasm_store_t],
+//! and everything that is created in it (instances, functions,
+//! memories, etc.), must only be used by one thread at a time, so
+//! each thread should use its own store; see
+//! [`wasmer_instance_new_from_module`][unstable::instance::wasmer_instance_new_from_module].
 
 /// `Context`.
 mod function_env;
@@ -4,12 +4,25 @@ use std::ptr::NonNull;
 use wasmer_api::Module;
 
 /// Opaque type representing a WebAssembly module.
+///
+/// A module is immutable once it's created, except by
+/// `wasmer_module_set_name`, and can be used from several threads
+/// at the same time.
 #[derive(Clone)]
 #[allow(non_camel_case_types)]
 pub struct wasm_module_t {
     pub(crate) inner: Module,
 }
 
+// Modules and engines are shared between threads by the users of
+// the C API.
+fn _assert_shareable() {
+    fn _assert_send_sync<T: Send + Sync>() {}
+    _assert_send_sync::<wasm_module_t>();
+    _assert_send_sync::<wasm_shared_module_t>();
+    _assert_send_sync::<super::engine::wasm_engine_t>();
+}
+
 /// A WebAssembly module contains stateless WebAssembly code that has
 /// already been compiled and can be instantiated multiple times.
 ///
@@ -32,7 +45,7 @@ pub unsafe extern "C" fn wasm_module_new(
     let store = store?.inner.store_mut();
     let bytes = bytes?;
 
-    let module = c_try!(Module::from_binary(&store, bytes.as_slice()));
+    let module = c_try!(Module::from_binary(&store, bytes.as_slice()); code WASMER_ERROR_COMPILE);
 
     Some(Box::new(wasm_module_t { inner: module }))
 }
@@ -458,7 +471,10 @@ pub unsafe extern "C" fn wasm_module_deserialize(
 ) -> Option<NonNull<wasm_module_t>> {
     let bytes = bytes?;
 
-    let module = c_try!(Module::deserialize(&store.inner.store(), bytes.as_slice()));
+    let module = c_try!(
+        Module::deserialize(&store.inner.store(), bytes.as_slice());
+        code WASMER_ERROR_SERIALIZATION
+    );
 
     Some(NonNull::new_unchecked(Box::into_raw(Box::new(
         wasm_module_t { inner: module },
@@ -474,10 +490,109 @@ pub unsafe extern "C" fn wasm_module_deserialize(
 /// See [`wasm_module_deserialize`].
 #[no_mangle]
 pub unsafe extern "C" fn wasm_module_serialize(module: &wasm_module_t, out: &mut wasm_byte_vec_t) {
-    let byte_vec = c_try!(module.inner.serialize(); otherwise ());
+    let byte_vec = c_try!(module.inner.serialize(); code WASMER_ERROR_SERIALIZATION; otherwise ());
     out.set_buffer(byte_vec.to_vec());
 }
 
+/// Opaque type representing a WebAssembly module that can be sent to
+/// another thread, see [`wasm_module_share`].
+#[allow(non_camel_case_types)]
+pub struct wasm_shared_module_t {
+    pub(crate) inner: Module,
+}
+
+/// Deletes a shared WebAssembly module.
+///
+/// # Example
+///
+/// See [`wasm_module_share`].
+#[no_mangle]
+pub unsafe extern "C" fn wasm_shared_module_delete(_module: Option<Box<wasm_shared_module_t>>) {}
+
+/// Shares a module, so that it can be obtained in another store,
+/// possibly from another thread, with [`wasm_module_obtain`], without
+/// being compiled again.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// #
+/// int main() {
+///     // Create the engine and the store.
+///     wasm_engine_t* engine = wasm_engine_new();
+///     wasm_store_t* store = wasm_store_new(engine);
+///
+///     // Create a WebAssembly module from a WAT definition.
+///     wasm_byte_vec_t wat;
+///     wasmer_byte_vec_new_from_string(&wat, "(module (func (export \"f\")))");
+///     wasm_byte_vec_t wasm;
+///     wat2wasm(&wat, &wasm);
+///
+///     wasm_module_t* module = wasm_module_new(store, &wasm);
+///     assert(module);
+///
+///     // Share the module, and obtain it in another store.
+///     wasm_shared_module_t* shared_module = wasm_module_share(module);
+///     assert(shared_module);
+///
+///     wasm_store_t* other_store = wasm_store_new(engine);
+///     wasm_module_t* obtained_module = wasm_module_obtain(other_store, shared_module);
+///     assert(obtained_module);
+///
+///     wasm_exporttype_vec_t export_types;
+///     wasm_module_exports(obtained_module, &export_types);
+///     assert(export_types.size == 1);
+///
+///     // Free everything.
+///     wasm_exporttype_vec_delete(&export_types);
+///     wasm_module_delete(obtained_module);
+///     wasm_shared_module_delete(shared_module);
+///     wasm_module_delete(module);
+///     wasm_byte_vec_delete(&wasm);
+///     wasm_byte_vec_delete(&wat);
+///     wasm_store_delete(other_store);
+///     wasm_store_delete(store);
+///     wasm_engine_delete(engine);
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_share(
+    module: Option<&wasm_module_t>,
+) -> Option<Box<wasm_shared_module_t>> {
+    let module = module?;
+
+    Some(Box::new(wasm_shared_module_t {
+        inner: module.inner.clone(),
+    }))
+}
+
+/// Obtains a module shared with [`wasm_module_share`] in the given
+/// [store][super::store].
+///
+/// # Example
+///
+/// See [`wasm_module_share`].
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_obtain(
+    _store: Option<&mut wasm_store_t>,
+    shared_module: Option<&wasm_shared_module_t>,
+) -> Option<Box<wasm_module_t>> {
+    let shared_module = shared_module?;
+
+    Some(Box::new(wasm_module_t {
+        inner: shared_module.inner.clone(),
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(not(target_os = "windows"))]
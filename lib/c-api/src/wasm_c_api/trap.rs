@@ -2,6 +2,7 @@ use super::store::wasm_store_t;
 use super::types::{wasm_byte_vec_t, wasm_frame_t, wasm_frame_vec_t, wasm_message_t};
 use std::ffi::CString;
 use wasmer_api::RuntimeError;
+use wasmer_types::TrapCode;
 
 // opaque type which is a `RuntimeError`
 #[allow(non_camel_case_types)]
@@ -142,6 +143,88 @@ pub unsafe extern "C" fn wasm_trap_trace(
     );
 }
 
+/// The reason of a trap raised by the generated code, see
+/// [`wasmer_trap_code`].
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum wasmer_trap_code_t {
+    /// The current stack space was exhausted.
+    WASMER_TRAP_CODE_STACK_OVERFLOW = 0,
+
+    /// An out-of-bounds memory access.
+    WASMER_TRAP_CODE_HEAP_ACCESS_OUT_OF_BOUNDS = 1,
+
+    /// A misaligned heap access.
+    WASMER_TRAP_CODE_HEAP_MISALIGNED = 2,
+
+    /// An out-of-bounds access to a table.
+    WASMER_TRAP_CODE_TABLE_ACCESS_OUT_OF_BOUNDS = 3,
+
+    /// An indirect call to a null table entry.
+    WASMER_TRAP_CODE_INDIRECT_CALL_TO_NULL = 4,
+
+    /// A signature mismatch on an indirect call.
+    WASMER_TRAP_CODE_BAD_SIGNATURE = 5,
+
+    /// An integer arithmetic operation caused an overflow.
+    WASMER_TRAP_CODE_INTEGER_OVERFLOW = 6,
+
+    /// An integer division by zero.
+    WASMER_TRAP_CODE_INTEGER_DIVISION_BY_ZERO = 7,
+
+    /// A failed float-to-int conversion.
+    WASMER_TRAP_CODE_BAD_CONVERSION_TO_INTEGER = 8,
+
+    /// Code that was supposed to have been unreachable was reached.
+    WASMER_TRAP_CODE_UNREACHABLE_CODE_REACHED = 9,
+
+    /// An atomic memory access was attempted with an unaligned
+    /// pointer.
+    WASMER_TRAP_CODE_UNALIGNED_ATOMIC = 10,
+}
+
+impl From<TrapCode> for wasmer_trap_code_t {
+    fn from(other: TrapCode) -> Self {
+        match other {
+            TrapCode::StackOverflow => Self::WASMER_TRAP_CODE_STACK_OVERFLOW,
+            TrapCode::HeapAccessOutOfBounds => Self::WASMER_TRAP_CODE_HEAP_ACCESS_OUT_OF_BOUNDS,
+            TrapCode::HeapMisaligned => Self::WASMER_TRAP_CODE_HEAP_MISALIGNED,
+            TrapCode::TableAccessOutOfBounds => Self::WASMER_TRAP_CODE_TABLE_ACCESS_OUT_OF_BOUNDS,
+            TrapCode::IndirectCallToNull => Self::WASMER_TRAP_CODE_INDIRECT_CALL_TO_NULL,
+            TrapCode::BadSignature => Self::WASMER_TRAP_CODE_BAD_SIGNATURE,
+            TrapCode::IntegerOverflow => Self::WASMER_TRAP_CODE_INTEGER_OVERFLOW,
+            TrapCode::IntegerDivisionByZero => Self::WASMER_TRAP_CODE_INTEGER_DIVISION_BY_ZERO,
+            TrapCode::BadConversionToInteger => Self::WASMER_TRAP_CODE_BAD_CONVERSION_TO_INTEGER,
+            TrapCode::UnreachableCodeReached => Self::WASMER_TRAP_CODE_UNREACHABLE_CODE_REACHED,
+            TrapCode::UnalignedAtomic => Self::WASMER_TRAP_CODE_UNALIGNED_ATOMIC,
+        }
+    }
+}
+
+/// Gets the trap code of the trap into `out`.
+///
+/// Returns `false` if the trap was not raised by the generated code,
+/// e.g. when it was created with `wasm_trap_new` or raised by a host
+/// function, in which case `out` is left untouched.
+///
+/// # Example
+///
+/// See [`wasmer_last_error_code`][crate::error::wasmer_last_error_code].
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_trap_code(
+    trap: &wasm_trap_t,
+    out: &mut wasmer_trap_code_t,
+) -> bool {
+    match trap.inner.clone().to_trap() {
+        Some(code) => {
+            *out = code.into();
+            true
+        }
+        None => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(not(target_os = "windows"))]
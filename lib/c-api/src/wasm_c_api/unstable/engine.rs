@@ -4,6 +4,13 @@
 #[cfg(feature = "compiler")]
 use super::super::engine::wasmer_compiler_t;
 use super::super::engine::{wasm_config_t, wasmer_engine_t};
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+use std::ptr::NonNull;
+#[cfg(feature = "compiler")]
+use wasmer_api::CompilerConfig;
+use wasmer_compiler::CodeMemoryAllocator;
+use wasmer_types::Features;
 
 use super::features::wasmer_features_t;
 use super::target_lexicon::wasmer_target_t;
@@ -137,6 +144,175 @@ pub extern "C" fn wasm_config_canonicalize_nans(config: &mut wasm_config_t, enab
     config.nan_canonicalization = enable;
 }
 
+/// Function type to allocate `size` bytes of page-aligned, readable
+/// and writable memory for the executable code, see
+/// [`wasm_config_set_code_memory_allocator`]. It returns `NULL` on
+/// failure.
+#[allow(non_camel_case_types)]
+pub type wasmer_code_memory_allocate_t =
+    unsafe extern "C" fn(env: *mut c_void, size: usize) -> *mut c_void;
+
+/// Function type to make the pages holding the `size` first bytes of
+/// the memory at `ptr` readable and executable, see
+/// [`wasm_config_set_code_memory_allocator`]. It returns `false` on
+/// failure.
+#[allow(non_camel_case_types)]
+pub type wasmer_code_memory_make_executable_t =
+    unsafe extern "C" fn(env: *mut c_void, ptr: *mut c_void, size: usize) -> bool;
+
+/// Function type to free the `size` bytes of memory at `ptr`, see
+/// [`wasm_config_set_code_memory_allocator`].
+#[allow(non_camel_case_types)]
+pub type wasmer_code_memory_deallocate_t =
+    unsafe extern "C" fn(env: *mut c_void, ptr: *mut c_void, size: usize);
+
+/// The callbacks given to [`wasm_config_set_code_memory_allocator`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CodeMemoryCallbacks {
+    allocate: wasmer_code_memory_allocate_t,
+    make_executable: wasmer_code_memory_make_executable_t,
+    deallocate: wasmer_code_memory_deallocate_t,
+    env: *mut c_void,
+}
+
+// The callbacks are documented to be callable from any thread.
+unsafe impl Send for CodeMemoryCallbacks {}
+unsafe impl Sync for CodeMemoryCallbacks {}
+
+impl CodeMemoryAllocator for CodeMemoryCallbacks {
+    fn allocate(&self, size: usize) -> Result<NonNull<u8>, String> {
+        let ptr = unsafe { (self.allocate)(self.env, size) };
+
+        NonNull::new(ptr as *mut u8)
+            .ok_or_else(|| format!("failed to allocate {} bytes of code memory", size))
+    }
+
+    fn make_executable(&self, ptr: NonNull<u8>, size: usize) -> Result<(), String> {
+        if unsafe { (self.make_executable)(self.env, ptr.as_ptr() as *mut c_void, size) } {
+            Ok(())
+        } else {
+            Err(format!(
+                "failed to make {} bytes of code memory executable",
+                size
+            ))
+        }
+    }
+
+    fn deallocate(&self, ptr: NonNull<u8>, size: usize) {
+        unsafe { (self.deallocate)(self.env, ptr.as_ptr() as *mut c_void, size) }
+    }
+}
+
+/// Unstable non-standard Wasmer-specific API to update the
+/// configuration so that the engine allocates and protects the pages
+/// holding the executable code with the given callbacks, instead of
+/// `mmap` and `mprotect` (or their equivalents), e.g. on platforms
+/// with a custom JIT policy.
+///
+/// `env` is passed to every callback. The callbacks can be called
+/// from any thread, and as long as the engine or a module created with
+/// it is alive.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// # #include <sys/mman.h>
+/// #
+/// void* allocate(void* env, size_t size) {
+///     int* allocations = (int*) env;
+///     ++*allocations;
+///
+///     void* ptr = mmap(NULL, size, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+///
+///     return ptr == MAP_FAILED ? NULL : ptr;
+/// }
+///
+/// bool make_executable(void* env, void* ptr, size_t size) {
+///     return mprotect(ptr, size, PROT_READ | PROT_EXEC) == 0;
+/// }
+///
+/// void deallocate(void* env, void* ptr, size_t size) {
+///     munmap(ptr, size);
+/// }
+///
+/// int main() {
+///     int allocations = 0;
+///
+///     // Create the configuration with the allocator.
+///     wasm_config_t* config = wasm_config_new();
+///     wasm_config_set_code_memory_allocator(config, allocate, make_executable, deallocate, &allocations);
+///
+///     wasm_engine_t* engine = wasm_engine_new_with_config(config);
+///     wasm_store_t* store = wasm_store_new(engine);
+///
+///     // Compile a module and call it.
+///     wasm_byte_vec_t wat;
+///     wasmer_byte_vec_new_from_string(
+///         &wat,
+///         "(module\n"
+///         "  (func (export \"add_one\") (param i32) (result i32)\n"
+///         "    local.get 0\n"
+///         "    i32.const 1\n"
+///         "    i32.add))"
+///     );
+///     wasm_byte_vec_t wasm;
+///     wat2wasm(&wat, &wasm);
+///
+///     wasm_module_t* module = wasm_module_new(store, &wasm);
+///     assert(module);
+///     assert(allocations > 0);
+///
+///     wasm_extern_vec_t imports = WASM_EMPTY_VEC;
+///     wasm_instance_t* instance = wasm_instance_new(store, module, &imports, NULL);
+///     assert(instance);
+///
+///     wasm_extern_vec_t exports;
+///     wasm_instance_exports(instance, &exports);
+///     const wasm_func_t* add_one = wasm_extern_as_func(exports.data[0]);
+///
+///     wasm_val_t arguments_val[1] = { WASM_I32_VAL(41) };
+///     wasm_val_vec_t arguments = WASM_ARRAY_VEC(arguments_val);
+///     wasm_val_t results_val[1] = { WASM_INIT_VAL };
+///     wasm_val_vec_t results = WASM_ARRAY_VEC(results_val);
+///
+///     assert(!wasm_func_call(add_one, &arguments, &results));
+///     assert(results_val[0].of.i32 == 42);
+///
+///     // Free everything.
+///     wasm_extern_vec_delete(&exports);
+///     wasm_instance_delete(instance);
+///     wasm_module_delete(module);
+///     wasm_byte_vec_delete(&wasm);
+///     wasm_byte_vec_delete(&wat);
+///     wasm_store_delete(store);
+///     wasm_engine_delete(engine);
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub extern "C" fn wasm_config_set_code_memory_allocator(
+    config: &mut wasm_config_t,
+    allocate: wasmer_code_memory_allocate_t,
+    make_executable: wasmer_code_memory_make_executable_t,
+    deallocate: wasmer_code_memory_deallocate_t,
+    env: *mut c_void,
+) {
+    config.code_memory_allocator = Some(CodeMemoryCallbacks {
+        allocate,
+        make_executable,
+        deallocate,
+        env,
+    });
+}
+
 /// Check whether the given compiler is available, i.e. part of this
 /// compiled library.
 #[no_mangle]
@@ -164,6 +340,118 @@ pub extern "C" fn wasmer_is_engine_available(engine: wasmer_engine_t) -> bool {
     matches!(engine, wasmer_engine_t::UNIVERSAL if cfg!(feature = "compiler"))
 }
 
+/// Check whether the backend named `name` is available, i.e. part of
+/// this compiled library.
+///
+/// The backends are named after the compilers: `cranelift`, `llvm`
+/// and `singlepass`. It returns false for an unknown or null name.
+///
+/// This is a Wasmer-specific function.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// #
+/// int main() {
+///     // Pick the compiler by name, e.g. from a command-line option.
+///     if (wasmer_backend_available("cranelift")) {
+///         assert(wasmer_is_compiler_available(CRANELIFT));
+///     }
+///
+///     assert(!wasmer_backend_available("unknown"));
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_backend_available(name: *const c_char) -> bool {
+    if name.is_null() {
+        return false;
+    }
+
+    match CStr::from_ptr(name).to_bytes() {
+        b"cranelift" => cfg!(feature = "cranelift"),
+        b"llvm" => cfg!(feature = "llvm"),
+        b"singlepass" => cfg!(feature = "singlepass"),
+        _ => false,
+    }
+}
+
+/// Check whether the WebAssembly feature named `feature` is enabled by
+/// default, i.e. whether modules using it can be compiled with the
+/// engine returned by `wasm_engine_new` on this host.
+///
+/// The features are named as the `wasmer_features_*` functions, e.g.
+/// `simd`, `threads` or `bulk_memory`. It returns false for an
+/// unknown or null name.
+///
+/// This is a Wasmer-specific function.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// #
+/// int main() {
+///     // Only load the SIMD flavor of a module if it can run.
+///     const char* module_path = wasmer_feature_supported("simd") ? "simd.wasm" : "scalar.wasm";
+///     assert(module_path);
+///
+///     assert(!wasmer_feature_supported("unknown"));
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_feature_supported(feature: *const c_char) -> bool {
+    if feature.is_null() {
+        return false;
+    }
+
+    let features = default_features();
+
+    match CStr::from_ptr(feature).to_bytes() {
+        b"threads" => features.threads,
+        b"reference_types" => features.reference_types,
+        b"simd" => features.simd,
+        b"bulk_memory" => features.bulk_memory,
+        b"multi_value" => features.multi_value,
+        b"tail_call" => features.tail_call,
+        b"module_linking" => features.module_linking,
+        b"multi_memory" => features.multi_memory,
+        b"memory64" => features.memory64,
+        b"exceptions" => features.exceptions,
+        b"relaxed_simd" => features.relaxed_simd,
+        b"extended_const" => features.extended_const,
+        _ => false,
+    }
+}
+
+/// The features enabled by the default compiler for the host.
+fn default_features() -> Features {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "compiler")] {
+            super::super::engine::get_default_compiler_config()
+                .default_features_for_target(&wasmer_api::Target::default())
+        } else {
+            Features::default()
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(not(target_os = "windows"))]
@@ -254,4 +542,48 @@ mod tests {
 
         remove_var("UNIVERSAL");
     }
+
+    #[test]
+    fn test_wasmer_backend_available() {
+        set_var(
+            "SINGLEPASS",
+            if cfg!(feature = "singlepass") {
+                "1"
+            } else {
+                "0"
+            },
+        );
+
+        (assert_c! {
+            #include "tests/wasmer.h"
+            #include <stdlib.h>
+
+            int main() {
+                assert(wasmer_backend_available("singlepass") == (getenv("SINGLEPASS")[0] == '1'));
+                assert(!wasmer_backend_available("Singlepass"));
+                assert(!wasmer_backend_available(NULL));
+
+                return 0;
+            }
+        })
+        .success();
+
+        remove_var("SINGLEPASS");
+    }
+
+    #[test]
+    fn test_wasmer_feature_supported() {
+        (assert_c! {
+            #include "tests/wasmer.h"
+
+            int main() {
+                assert(wasmer_feature_supported("bulk_memory"));
+                assert(!wasmer_feature_supported("module_linking"));
+                assert(!wasmer_feature_supported(NULL));
+
+                return 0;
+            }
+        })
+        .success();
+    }
 }
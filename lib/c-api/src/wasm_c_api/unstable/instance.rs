@@ -0,0 +1,169 @@
+//! Unstable non-standard Wasmer-specific extensions to instantiate
+//! modules concurrently.
+//!
+//! A [`wasm_module_t`] and a [`wasm_engine_t`] can be used from
+//! several threads at the same time, but a store, and everything
+//! created in it, must only be used by one thread at a time. The
+//! functions of this module create the instance in a store that is
+//! not shared with any other instance, so that threads can
+//! instantiate the same module without a global lock around Wasmer.
+
+use super::super::engine::wasm_engine_t;
+use super::super::externals::wasm_extern_vec_t;
+use super::super::instance::{instantiate, wasm_instance_new, wasm_instance_t};
+use super::super::module::{wasm_module_t, wasm_shared_module_t};
+use super::super::store::{wasm_store_new, wasm_store_t};
+use super::super::trap::wasm_trap_t;
+
+/// Unstable non-standard Wasmer-specific API to instantiate a module
+/// that has no imports in a new store, created from `engine` and
+/// owned by the instance.
+///
+/// `engine` and `module` can be used concurrently by other threads
+/// while this function runs, and the returned instance can be sent to
+/// another thread. The errors are reported like with
+/// `wasm_instance_new`.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// #
+/// int main() {
+///     // Create the engine and the store used for the compilation.
+///     wasm_engine_t* engine = wasm_engine_new();
+///     wasm_store_t* store = wasm_store_new(engine);
+///
+///     // Create a WebAssembly module from a WAT definition.
+///     wasm_byte_vec_t wat;
+///     wasmer_byte_vec_new_from_string(
+///         &wat,
+///         "(module\n"
+///         "  (func (export \"add_one\") (param i32) (result i32)\n"
+///         "    local.get 0\n"
+///         "    i32.const 1\n"
+///         "    i32.add))"
+///     );
+///     wasm_byte_vec_t wasm;
+///     wat2wasm(&wat, &wasm);
+///
+///     wasm_module_t* module = wasm_module_new(store, &wasm);
+///     assert(module);
+///
+///     // Instantiate the module; every thread can do it with the same
+///     // engine and module.
+///     wasm_trap_t* trap = NULL;
+///     wasm_instance_t* instance = wasmer_instance_new_from_module(engine, module, &trap);
+///     assert(instance);
+///
+///     // Call the function.
+///     wasm_extern_vec_t exports;
+///     wasm_instance_exports(instance, &exports);
+///     const wasm_func_t* add_one = wasm_extern_as_func(exports.data[0]);
+///
+///     wasm_val_t arguments_val[1] = { WASM_I32_VAL(41) };
+///     wasm_val_vec_t arguments = WASM_ARRAY_VEC(arguments_val);
+///     wasm_val_t results_val[1] = { WASM_INIT_VAL };
+///     wasm_val_vec_t results = WASM_ARRAY_VEC(results_val);
+///
+///     assert(!wasm_func_call(add_one, &arguments, &results));
+///     assert(results_val[0].of.i32 == 42);
+///
+///     // Free everything.
+///     wasm_extern_vec_delete(&exports);
+///     wasm_instance_delete(instance);
+///     wasm_module_delete(module);
+///     wasm_byte_vec_delete(&wasm);
+///     wasm_byte_vec_delete(&wat);
+///     wasm_store_delete(store);
+///     wasm_engine_delete(engine);
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_instance_new_from_module(
+    engine: Option<&wasm_engine_t>,
+    module: Option<&wasm_module_t>,
+    trap: Option<&mut *mut wasm_trap_t>,
+) -> Option<Box<wasm_instance_t>> {
+    let mut store = wasm_store_new(engine)?;
+    let module = module?;
+
+    instantiate(&mut store.inner, module, &[], trap)
+}
+
+/// Unstable non-standard Wasmer-specific API to instantiate a module
+/// shared with `wasm_module_share` directly in `store`, with the
+/// given imports, without obtaining a `wasm_module_t` first.
+///
+/// It's meant to be used with one store per thread: `shared_module`
+/// can be used concurrently by other threads while this function
+/// runs. The errors are reported like with `wasm_instance_new`.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// #
+/// int main() {
+///     // Create the engine and the store used for the compilation.
+///     wasm_engine_t* engine = wasm_engine_new();
+///     wasm_store_t* store = wasm_store_new(engine);
+///
+///     // Create a WebAssembly module from a WAT definition.
+///     wasm_byte_vec_t wat;
+///     wasmer_byte_vec_new_from_string(&wat, "(module (func (export \"f\")))");
+///     wasm_byte_vec_t wasm;
+///     wat2wasm(&wat, &wasm);
+///
+///     wasm_module_t* module = wasm_module_new(store, &wasm);
+///     assert(module);
+///
+///     wasm_shared_module_t* shared_module = wasm_module_share(module);
+///     assert(shared_module);
+///
+///     // In another thread, with its own store.
+///     wasm_store_t* thread_store = wasm_store_new(engine);
+///     wasm_extern_vec_t imports = WASM_EMPTY_VEC;
+///     wasm_instance_t* instance = wasmer_instance_new_from_shared_module(thread_store, shared_module, &imports, NULL);
+///     assert(instance);
+///
+///     // Free everything.
+///     wasm_instance_delete(instance);
+///     wasm_store_delete(thread_store);
+///     wasm_shared_module_delete(shared_module);
+///     wasm_module_delete(module);
+///     wasm_byte_vec_delete(&wasm);
+///     wasm_byte_vec_delete(&wat);
+///     wasm_store_delete(store);
+///     wasm_engine_delete(engine);
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_instance_new_from_shared_module(
+    store: Option<&mut wasm_store_t>,
+    shared_module: Option<&wasm_shared_module_t>,
+    imports: Option<&wasm_extern_vec_t>,
+    trap: Option<&mut *mut wasm_trap_t>,
+) -> Option<Box<wasm_instance_t>> {
+    let module = wasm_module_t {
+        inner: shared_module?.inner.clone(),
+    };
+
+    wasm_instance_new(store, Some(&module), imports, trap)
+}
@@ -0,0 +1,241 @@
+//! Unstable non-standard Wasmer-specific API that contains everything
+//! to interrupt the execution of an instance, e.g. from a watchdog
+//! thread to enforce a wall-clock timeout.
+//!
+//! The module must be compiled with the interrupt middleware, which
+//! makes the instance check a flag at the entry of every function and
+//! at every iteration of every loop. The flag is set with
+//! [`wasmer_interrupt`], which can be called from any thread, and the
+//! instance then traps until the flag is cleared.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use wasmer_inline_c::assert_c;
+//! # fn main() {
+//! #    (assert_c! {
+//! # #include "tests/wasmer.h"
+//! #
+//! int main() {
+//!     // Create the interrupt middleware and push it in the configuration.
+//!     wasmer_interrupt_t* interrupt = wasmer_interrupt_new();
+//!     wasmer_middleware_t* middleware = wasmer_interrupt_as_middleware(interrupt);
+//!
+//!     wasm_config_t* config = wasm_config_new();
+//!     wasm_config_push_middleware(config, middleware);
+//!
+//!     wasm_engine_t* engine = wasm_engine_new_with_config(config);
+//!     wasm_store_t* store = wasm_store_new(engine);
+//!
+//!     // Create the module and instantiate it.
+//!     wasm_byte_vec_t wat;
+//!     wasmer_byte_vec_new_from_string(
+//!         &wat,
+//!         "(module\n"
+//!         "  (func (export \"add_one\") (param i32) (result i32)\n"
+//!         "    local.get 0\n"
+//!         "    i32.const 1\n"
+//!         "    i32.add))"
+//!     );
+//!     wasm_byte_vec_t wasm;
+//!     wat2wasm(&wat, &wasm);
+//!
+//!     wasm_module_t* module = wasm_module_new(store, &wasm);
+//!     assert(module);
+//!
+//!     wasm_extern_vec_t imports = WASM_EMPTY_VEC;
+//!     wasm_instance_t* instance = wasm_instance_new(store, module, &imports, NULL);
+//!     assert(instance);
+//!
+//!     wasm_extern_vec_t exports;
+//!     wasm_instance_exports(instance, &exports);
+//!     const wasm_func_t* add_one = wasm_extern_as_func(exports.data[0]);
+//!
+//!     wasm_val_t arguments_val[1] = { WASM_I32_VAL(41) };
+//!     wasm_val_vec_t arguments = WASM_ARRAY_VEC(arguments_val);
+//!     wasm_val_t results_val[1] = { WASM_INIT_VAL };
+//!     wasm_val_vec_t results = WASM_ARRAY_VEC(results_val);
+//!
+//!     // Get a handle, usually handed to another thread.
+//!     wasmer_interrupt_handle_t* handle = wasmer_instance_interrupt_handle(instance);
+//!     assert(handle);
+//!
+//!     // Once interrupted, the instance traps.
+//!     wasmer_interrupt(handle);
+//!     assert(wasmer_interrupt_handle_is_interrupted(handle));
+//!
+//!     wasm_trap_t* trap = wasm_func_call(add_one, &arguments, &results);
+//!     assert(trap);
+//!     wasm_trap_delete(trap);
+//!
+//!     // Until the handle is cleared.
+//!     wasmer_interrupt_handle_clear(handle);
+//!
+//!     trap = wasm_func_call(add_one, &arguments, &results);
+//!     assert(!trap);
+//!     assert(results_val[0].of.i32 == 42);
+//!
+//!     // Free everything.
+//!     wasmer_interrupt_handle_delete(handle);
+//!     wasm_extern_vec_delete(&exports);
+//!     wasm_instance_delete(instance);
+//!     wasm_module_delete(module);
+//!     wasm_byte_vec_delete(&wasm);
+//!     wasm_byte_vec_delete(&wat);
+//!     wasm_store_delete(store);
+//!     wasm_engine_delete(engine);
+//!
+//!     return 0;
+//! }
+//! #    })
+//! #    .success();
+//! # }
+//! ```
+
+use super::super::super::instance::wasm_instance_t;
+use super::wasmer_middleware_t;
+use crate::error::update_last_error;
+use std::sync::Arc;
+use wasmer_middlewares::{
+    interrupt::{get_interrupt_handle, InterruptHandle},
+    Interrupt,
+};
+
+/// Opaque type representing an interrupt middleware.
+///
+/// To transform this specific middleware into a generic one, please
+/// see [`wasmer_interrupt_as_middleware`].
+///
+/// # Example
+///
+/// See module's documentation.
+#[allow(non_camel_case_types)]
+pub struct wasmer_interrupt_t {
+    pub(crate) inner: Arc<Interrupt>,
+}
+
+/// Opaque type representing a handle to interrupt an instance, see
+/// [`wasmer_instance_interrupt_handle`].
+///
+/// The handle keeps the interrupt flag of the instance alive, and can
+/// be used from any thread.
+///
+/// # Example
+///
+/// See module's documentation.
+#[allow(non_camel_case_types)]
+pub struct wasmer_interrupt_handle_t {
+    inner: InterruptHandle,
+}
+
+/// Creates a new interrupt middleware.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_interrupt_new() -> Box<wasmer_interrupt_t> {
+    Box::new(wasmer_interrupt_t {
+        inner: Arc::new(Interrupt::new()),
+    })
+}
+
+/// Deletes a [`wasmer_interrupt_t`].
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_interrupt_delete(_interrupt: Option<Box<wasmer_interrupt_t>>) {}
+
+/// Transforms a [`wasmer_interrupt_t`] into a generic
+/// [`wasmer_middleware_t`], to then be pushed in the configuration with
+/// [`wasm_config_push_middleware`][super::wasm_config_push_middleware].
+///
+/// This function takes ownership of `interrupt`.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_interrupt_as_middleware(
+    interrupt: Option<Box<wasmer_interrupt_t>>,
+) -> Option<Box<wasmer_middleware_t>> {
+    let interrupt = interrupt?;
+
+    Some(Box::new(wasmer_middleware_t {
+        inner: interrupt.inner,
+    }))
+}
+
+/// Gets a handle to interrupt `instance`.
+///
+/// Returns null and sets the last error if the module of the instance
+/// has not been compiled with the interrupt middleware.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_instance_interrupt_handle(
+    instance: &wasm_instance_t,
+) -> Option<Box<wasmer_interrupt_handle_t>> {
+    if instance
+        .inner
+        .exports
+        .get_global("wasmer_interrupted")
+        .is_err()
+    {
+        update_last_error("The instance has not been compiled with the interrupt middleware.");
+        return None;
+    }
+
+    Some(Box::new(wasmer_interrupt_handle_t {
+        inner: get_interrupt_handle(&instance.store.store(), &instance.inner),
+    }))
+}
+
+/// Deletes a [`wasmer_interrupt_handle_t`].
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_interrupt_handle_delete(_handle: Option<Box<wasmer_interrupt_handle_t>>) {}
+
+/// Interrupts the instance of `handle`: it traps at the next function
+/// call or loop iteration, and at every call that starts afterwards,
+/// until [`wasmer_interrupt_handle_clear`] is called.
+///
+/// This function can be called from any thread.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_interrupt(handle: &wasmer_interrupt_handle_t) {
+    handle.inner.interrupt();
+}
+
+/// Lets the instance of `handle` run again after an interruption.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_interrupt_handle_clear(handle: &wasmer_interrupt_handle_t) {
+    handle.inner.clear();
+}
+
+/// Returns true if the instance of `handle` has been interrupted,
+/// i.e. if the trap of a call comes from an interruption.
+///
+/// # Example
+///
+/// See module's documentation.
+#[no_mangle]
+pub extern "C" fn wasmer_interrupt_handle_is_interrupted(
+    handle: &wasmer_interrupt_handle_t,
+) -> bool {
+    handle.inner.is_interrupted()
+}
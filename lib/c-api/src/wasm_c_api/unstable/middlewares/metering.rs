@@ -134,6 +134,7 @@
 use super::super::super::instance::wasm_instance_t;
 use super::super::parser::operator::wasmer_parser_operator_t;
 use super::wasmer_middleware_t;
+use crate::error::update_last_error;
 use std::sync::Arc;
 use wasmer_api::wasmparser::Operator;
 use wasmer_middlewares::{
@@ -141,6 +142,8 @@ use wasmer_middlewares::{
     Metering,
 };
 
+const NOT_METERED: &str = "The instance has not been compiled with the metering middleware.";
+
 /// Opaque type representing a metering middleware.
 ///
 /// To transform this specific middleware into a generic one, please
@@ -169,18 +172,25 @@ pub type wasmer_metering_cost_function_t =
 /// cost), in addition to a cost function. The cost function defines
 /// the cost of an operation, that will decrease the initial limit.
 ///
+/// If `cost_function` is null, every operator costs 1 point.
+///
 /// # Example
 ///
 /// See module's documentation.
 #[no_mangle]
 pub extern "C" fn wasmer_metering_new(
     initial_limit: u64,
-    cost_function: wasmer_metering_cost_function_t,
+    cost_function: Option<wasmer_metering_cost_function_t>,
 ) -> Box<wasmer_metering_t> {
-    let cost_function = move |operator: &Operator| -> u64 { cost_function(operator.into()) };
+    let cost_function: Box<dyn Fn(&Operator) -> u64 + Send + Sync> = match cost_function {
+        Some(cost_function) => {
+            Box::new(move |operator: &Operator| -> u64 { cost_function(operator.into()) })
+        }
+        None => Box::new(|_: &Operator| -> u64 { 1 }),
+    };
 
     Box::new(wasmer_metering_t {
-        inner: Arc::new(Metering::new(initial_limit, Box::new(cost_function))),
+        inner: Arc::new(Metering::new(initial_limit, cost_function)),
     })
 }
 
@@ -192,11 +202,97 @@ pub extern "C" fn wasmer_metering_new(
 #[no_mangle]
 pub extern "C" fn wasmer_metering_delete(_metering: Option<Box<wasmer_metering_t>>) {}
 
+/// Checks whether the module of `instance` has been compiled with the
+/// metering middleware, i.e. whether the other `wasmer_metering_*`
+/// functions can be used with `instance`.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// #
+/// int main() {
+///     // Without a cost function, every operator costs 1 point.
+///     wasmer_metering_t* metering = wasmer_metering_new(3, NULL);
+///     wasmer_middleware_t* middleware = wasmer_metering_as_middleware(metering);
+///
+///     wasm_config_t* config = wasm_config_new();
+///     wasm_config_push_middleware(config, middleware);
+///     wasm_engine_t* engine = wasm_engine_new_with_config(config);
+///     wasm_store_t* store = wasm_store_new(engine);
+///
+///     wasm_byte_vec_t wat;
+///     wasmer_byte_vec_new_from_string(
+///         &wat,
+///         "(module\n"
+///         "  (func (export \"f\") (result i32)\n"
+///         "    i32.const 1))"
+///     );
+///     wasm_byte_vec_t wasm;
+///     wat2wasm(&wat, &wasm);
+///     wasm_module_t* module = wasm_module_new(store, &wasm);
+///     assert(module);
+///
+///     wasm_extern_vec_t imports = WASM_EMPTY_VEC;
+///     wasm_instance_t* instance = wasm_instance_new(store, module, &imports, NULL);
+///     assert(instance);
+///
+///     // The instance is metered.
+///     assert(wasmer_metering_is_enabled(instance));
+///
+///     wasm_extern_vec_t exports;
+///     wasm_instance_exports(instance, &exports);
+///     const wasm_func_t* f = wasm_extern_as_func(exports.data[0]);
+///
+///     wasm_val_vec_t arguments = WASM_EMPTY_VEC;
+///     wasm_val_t results_val[1] = { WASM_INIT_VAL };
+///     wasm_val_vec_t results = WASM_ARRAY_VEC(results_val);
+///
+///     // `i32.const` and `end` cost 1 point each.
+///     assert(wasm_func_call(f, &arguments, &results) == NULL);
+///     assert(wasmer_metering_get_remaining_points(instance) == 1);
+///
+///     wasm_extern_vec_delete(&exports);
+///     wasm_instance_delete(instance);
+///     wasm_module_delete(module);
+///     wasm_byte_vec_delete(&wasm);
+///     wasm_byte_vec_delete(&wat);
+///     wasm_store_delete(store);
+///     wasm_engine_delete(engine);
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub extern "C" fn wasmer_metering_is_enabled(instance: &wasm_instance_t) -> bool {
+    is_metered(instance)
+}
+
+fn is_metered(instance: &wasm_instance_t) -> bool {
+    let exports = &instance.inner.exports;
+
+    exports
+        .get_global("wasmer_metering_remaining_points")
+        .is_ok()
+        && exports
+            .get_global("wasmer_metering_points_exhausted")
+            .is_ok()
+}
+
 /// Returns the remaining metering points. `u64::MAX` means
 /// points are exhausted, otherwise it returns the number of
 /// points. Notice that it could include zero! Zero doesn't mean
 /// points are exhausted _yet_.
 ///
+/// `u64::MAX` is also returned, and the last error is set, if the
+/// instance is not metered, see [`wasmer_metering_is_enabled`].
+///
 /// # Example
 ///
 /// See module's documentation.
@@ -204,13 +300,19 @@ pub extern "C" fn wasmer_metering_delete(_metering: Option<Box<wasmer_metering_t
 pub unsafe extern "C" fn wasmer_metering_get_remaining_points(
     instance: &mut wasm_instance_t,
 ) -> u64 {
+    if !is_metered(instance) {
+        update_last_error(NOT_METERED);
+        return std::u64::MAX;
+    }
+
     match get_remaining_points(&mut instance.store.store_mut(), &instance.inner) {
         MeteringPoints::Remaining(value) => value,
         MeteringPoints::Exhausted => std::u64::MAX,
     }
 }
 
-/// Returns true if the remaning points are exhausted, false otherwise.
+/// Returns true if the remaning points are exhausted, false otherwise,
+/// including when the instance is not metered.
 ///
 /// # Example
 ///
@@ -219,6 +321,11 @@ pub unsafe extern "C" fn wasmer_metering_get_remaining_points(
 pub unsafe extern "C" fn wasmer_metering_points_are_exhausted(
     instance: &mut wasm_instance_t,
 ) -> bool {
+    if !is_metered(instance) {
+        update_last_error(NOT_METERED);
+        return false;
+    }
+
     matches!(
         get_remaining_points(&mut instance.store.store_mut(), &instance.inner),
         MeteringPoints::Exhausted,
@@ -227,6 +334,9 @@ pub unsafe extern "C" fn wasmer_metering_points_are_exhausted(
 
 /// Set a new amount of points for the given metering middleware.
 ///
+/// Returns `false` and sets the last error if the instance is not
+/// metered, see [`wasmer_metering_is_enabled`].
+///
 /// # Example
 ///
 /// This example only illustrates the
@@ -281,7 +391,7 @@ pub unsafe extern "C" fn wasmer_metering_points_are_exhausted(
 ///     assert(wasmer_metering_get_remaining_points(instance) == 7);
 ///
 ///     // Set a new number of points.
-///     wasmer_metering_set_remaining_points(instance, 42);
+///     assert(wasmer_metering_set_remaining_points(instance, 42));
 ///
 ///     // Read the number of points.
 ///     assert(wasmer_metering_get_remaining_points(instance) == 42);
@@ -301,8 +411,15 @@ pub unsafe extern "C" fn wasmer_metering_points_are_exhausted(
 pub unsafe extern "C" fn wasmer_metering_set_remaining_points(
     instance: &mut wasm_instance_t,
     new_limit: u64,
-) {
+) -> bool {
+    if !is_metered(instance) {
+        update_last_error(NOT_METERED);
+        return false;
+    }
+
     set_remaining_points(&mut instance.store.store_mut(), &instance.inner, new_limit);
+
+    true
 }
 
 /// Transforms a [`wasmer_metering_t`] into a generic
@@ -1,5 +1,6 @@
 pub mod engine;
 pub mod features;
+pub mod instance;
 #[cfg(feature = "middlewares")]
 pub mod middlewares;
 pub mod module;
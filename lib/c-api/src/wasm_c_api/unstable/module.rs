@@ -1,9 +1,13 @@
 //! Unstable non-standard Wasmer-specific extensions to the Wasm C API.
 
 use super::super::module::wasm_module_t;
-use super::super::types::wasm_name_t;
+use super::super::store::wasm_store_t;
+use super::super::types::{wasm_byte_vec_t, wasm_name_t};
+use crate::error::{update_last_error, update_last_error_with_code, wasmer_error_code_t};
 use std::ptr;
+use std::slice;
 use std::str;
+use wasmer_api::{Artifact, Module};
 
 /// Unstable non-standard Wasmer-specific API to get the module's
 /// name, otherwise `out->size` is set to `0` and `out->data` to
@@ -76,6 +80,10 @@ pub unsafe extern "C" fn wasmer_module_name(
 /// name. The function returns `true` if the name has been updated,
 /// `false` otherwise.
 ///
+/// The name can only be updated before the module is shared or
+/// instantiated, and this function must not be called while another
+/// thread uses the module.
+///
 /// # Example
 ///
 /// ```rust
@@ -150,3 +158,220 @@ pub unsafe extern "C" fn wasmer_module_set_name(
 
     module.inner.set_name(name)
 }
+
+/// Unstable non-standard Wasmer-specific API to read the custom
+/// section named `name` of the module.
+///
+/// Following the WebAssembly spec, one name can have multiple custom
+/// sections, so `index` selects one of them, in the order they appear
+/// in the module. The function returns `true` and copies the content of
+/// the section into `out` if it exists, otherwise it returns `false`,
+/// `out->size` is set to `0` and `out->data` to `NULL`.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// #
+/// int main() {
+///     // Create the engine and the store.
+///     wasm_engine_t* engine = wasm_engine_new();
+///     wasm_store_t* store = wasm_store_new(engine);
+///
+///     // Create a WebAssembly module from a WAT definition.
+///     wasm_byte_vec_t wat;
+///     wasmer_byte_vec_new_from_string(
+///         &wat,
+///         "(module\n"
+///         "  (@custom \"manifest\" \"v1\")\n"
+///         "  (@custom \"manifest\" \"v2\"))"
+///     );
+///     wasm_byte_vec_t wasm;
+///     wat2wasm(&wat, &wasm);
+///
+///     // Create the module.
+///     wasm_module_t* module = wasm_module_new(store, &wasm);
+///     assert(module);
+///
+///     // Read the custom sections named `manifest`.
+///     wasm_name_t name;
+///     wasmer_byte_vec_new_from_string(&name, "manifest");
+///
+///     wasm_byte_vec_t section;
+///
+///     assert(wasmer_module_custom_section(module, &name, 0, &section));
+///     wasmer_assert_name(&section, "v1");
+///     wasm_byte_vec_delete(&section);
+///
+///     assert(wasmer_module_custom_section(module, &name, 1, &section));
+///     wasmer_assert_name(&section, "v2");
+///     wasm_byte_vec_delete(&section);
+///
+///     // There is no third one.
+///     assert(!wasmer_module_custom_section(module, &name, 2, &section));
+///     assert(section.size == 0);
+///
+///     // Free everything.
+///     wasm_byte_vec_delete(&name);
+///     wasm_module_delete(module);
+///     wasm_byte_vec_delete(&wasm);
+///     wasm_byte_vec_delete(&wat);
+///     wasm_store_delete(store);
+///     wasm_engine_delete(engine);
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_module_custom_section(
+    module: &wasm_module_t,
+    name: &wasm_name_t,
+    index: usize,
+    // own
+    out: &mut wasm_byte_vec_t,
+) -> bool {
+    let section = str::from_utf8(name.as_slice())
+        .ok()
+        .and_then(|name| module.inner.custom_sections(name).nth(index));
+
+    match section {
+        Some(section) => {
+            out.set_buffer(section.into_vec());
+
+            true
+        }
+        None => {
+            out.data = ptr::null_mut();
+            out.size = 0;
+
+            false
+        }
+    }
+}
+
+/// Unstable non-standard Wasmer-specific API to check whether
+/// `bytes` starts with the header of a module serialized with
+/// `wasm_module_serialize`.
+///
+/// It's a cheap check that allows a cache to tell serialized modules
+/// from WebAssembly bytes without deserializing them. It doesn't
+/// validate the rest of the bytes, which is done by
+/// [`wasmer_module_deserialize`].
+///
+/// # Example
+///
+/// See [`wasmer_module_deserialize`].
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_module_is_deserializable(bytes: *const u8, length: usize) -> bool {
+    if bytes.is_null() {
+        return false;
+    }
+
+    Artifact::is_deserializable(slice::from_raw_parts(bytes, length))
+}
+
+/// Unstable non-standard Wasmer-specific API to deserialize a module
+/// from the `length` bytes at `bytes`, produced by
+/// `wasm_module_serialize`.
+///
+/// The header and the metadata of the serialized module are validated
+/// before it's loaded, and the module must have been serialized by
+/// the same version of Wasmer, for a target compatible with the
+/// host. Otherwise, `NULL` is returned and the last error is set to
+/// `WASMER_ERROR_SERIALIZATION`.
+///
+/// # Safety
+///
+/// The serialized module contains machine code that is going to be
+/// executed, so it must come from a trusted source, see
+/// `wasm_module_deserialize`.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// #
+/// int main() {
+///     // Create the engine and the store.
+///     wasm_engine_t* engine = wasm_engine_new();
+///     wasm_store_t* store = wasm_store_new(engine);
+///
+///     // Create a WebAssembly module from a WAT definition.
+///     wasm_byte_vec_t wat;
+///     wasmer_byte_vec_new_from_string(&wat, "(module (func (export \"f\")))");
+///     wasm_byte_vec_t wasm;
+///     wat2wasm(&wat, &wasm);
+///     wasm_module_t* module = wasm_module_new(store, &wasm);
+///     assert(module);
+///
+///     // Serialize the module, e.g. to cache it.
+///     wasm_byte_vec_t serialized;
+///     wasm_module_serialize(module, &serialized);
+///
+///     // WebAssembly bytes are not a serialized module.
+///     assert(!wasmer_module_is_deserializable((const uint8_t*) wasm.data, wasm.size));
+///     assert(wasmer_module_is_deserializable((const uint8_t*) serialized.data, serialized.size));
+///
+///     // Deserialize the module.
+///     wasm_module_t* deserialized = wasmer_module_deserialize(
+///         store,
+///         (const uint8_t*) serialized.data,
+///         serialized.size
+///     );
+///     assert(deserialized);
+///
+///     // Truncated bytes are rejected.
+///     assert(!wasmer_module_deserialize(store, (const uint8_t*) serialized.data, serialized.size / 2));
+///     assert(wasmer_last_error_code() == WASMER_ERROR_SERIALIZATION);
+///
+///     // Free everything.
+///     wasm_module_delete(deserialized);
+///     wasm_byte_vec_delete(&serialized);
+///     wasm_module_delete(module);
+///     wasm_byte_vec_delete(&wasm);
+///     wasm_byte_vec_delete(&wat);
+///     wasm_store_delete(store);
+///     wasm_engine_delete(engine);
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_module_deserialize(
+    store: Option<&wasm_store_t>,
+    bytes: *const u8,
+    length: usize,
+) -> Option<Box<wasm_module_t>> {
+    let store = store?;
+    if bytes.is_null() {
+        update_last_error("The bytes cannot be a null pointer.");
+        return None;
+    }
+    let bytes = slice::from_raw_parts(bytes, length);
+    if !Artifact::is_deserializable(bytes) {
+        update_last_error_with_code(
+            wasmer_error_code_t::WASMER_ERROR_SERIALIZATION,
+            "The bytes are not a serialized module.",
+        );
+        return None;
+    }
+
+    let module = c_try!(
+        Module::deserialize(&store.inner.store(), bytes);
+        code WASMER_ERROR_SERIALIZATION
+    );
+
+    Some(Box::new(wasm_module_t { inner: module }))
+}
@@ -11,16 +11,16 @@ use super::{
     types::wasm_byte_vec_t,
 };
 use crate::error::update_last_error;
-use lazy_static::__Deref;
 use std::convert::TryFrom;
 use std::ffi::CStr;
+use std::io::{self, Read};
 use std::os::raw::c_char;
 use std::slice;
 #[cfg(feature = "webc_runner")]
 use wasmer_api::{AsStoreMut, Imports, Module};
 use wasmer_wasi::{
-    default_fs_backing, get_wasi_version, wasmer_vfs::AsyncReadExt, Pipe, VirtualTaskManager,
-    WasiEnv, WasiEnvBuilder, WasiFile, WasiFunctionEnv, WasiVersion,
+    default_fs_backing, get_wasi_version, Pipe, WasiEnv, WasiEnvBuilder, WasiFunctionEnv,
+    WasiVersion,
 };
 
 #[derive(Debug)]
@@ -32,6 +32,60 @@ pub struct wasi_config_t {
     builder: WasiEnvBuilder,
 }
 
+impl wasi_config_t {
+    /// Connects the captured standard streams of the module to pipes,
+    /// and returns the ends of the pipes used by the host.
+    fn capture_stdio(&mut self) -> CapturedStdio {
+        let output_pipe = || {
+            let (module_end, host_end) = Pipe::channel();
+            // The host reads what has been written so far, without
+            // waiting for more.
+            (module_end, host_end.with_blocking(false))
+        };
+
+        let stdout = if self.inherit_stdout {
+            None
+        } else {
+            let (module_end, host_end) = output_pipe();
+            self.builder.set_stdout(Box::new(module_end));
+            Some(host_end)
+        };
+
+        let stderr = if self.inherit_stderr {
+            None
+        } else {
+            let (module_end, host_end) = output_pipe();
+            self.builder.set_stderr(Box::new(module_end));
+            Some(host_end)
+        };
+
+        let stdin = if self.inherit_stdin {
+            None
+        } else {
+            let (module_end, host_end) = Pipe::channel();
+            self.builder.set_stdin(Box::new(module_end));
+            Some(host_end)
+        };
+
+        CapturedStdio {
+            stdin,
+            stdout,
+            stderr,
+        }
+    }
+}
+
+/// The ends of the pipes connected to the captured standard streams of
+/// a module, used by the host.
+struct CapturedStdio {
+    /// Written by the host.
+    stdin: Option<Pipe>,
+    /// Read by the host.
+    stdout: Option<Pipe>,
+    /// Read by the host.
+    stderr: Option<Pipe>,
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn wasi_config_new(
     program_name: *const c_char,
@@ -133,6 +187,8 @@ pub unsafe extern "C" fn wasi_config_mapdir(
     true
 }
 
+/// Capture the standard output of the module, so that it can be read
+/// with [`wasi_env_read_stdout`] or [`wasi_env_collect_stdout`].
 #[no_mangle]
 pub extern "C" fn wasi_config_capture_stdout(config: &mut wasi_config_t) {
     config.inherit_stdout = false;
@@ -143,6 +199,8 @@ pub extern "C" fn wasi_config_inherit_stdout(config: &mut wasi_config_t) {
     config.inherit_stdout = true;
 }
 
+/// Capture the standard error of the module, so that it can be read
+/// with [`wasi_env_read_stderr`] or [`wasi_env_collect_stderr`].
 #[no_mangle]
 pub extern "C" fn wasi_config_capture_stderr(config: &mut wasi_config_t) {
     config.inherit_stderr = false;
@@ -153,16 +211,41 @@ pub extern "C" fn wasi_config_inherit_stderr(config: &mut wasi_config_t) {
     config.inherit_stderr = true;
 }
 
-//#[no_mangle]
-//pub extern "C" fn wasi_config_capture_stdin(config: &mut wasi_config_t) {
-//    config.inherit_stdin = false;
-//}
+/// Capture the standard input of the module, so that it can be written
+/// with [`wasi_env_write_stdin`].
+#[no_mangle]
+pub extern "C" fn wasi_config_capture_stdin(config: &mut wasi_config_t) {
+    config.inherit_stdin = false;
+}
 
 #[no_mangle]
 pub extern "C" fn wasi_config_inherit_stdin(config: &mut wasi_config_t) {
     config.inherit_stdin = true;
 }
 
+/// Force the WASI version of the imports, instead of detecting it from
+/// the imports of the module. The imports of the version are provided
+/// under both the `wasi_unstable` and `wasi_snapshot_preview1`
+/// namespaces, for modules mixing or mislabelling them.
+///
+/// Returns `false` and sets the last error if the version is invalid.
+#[no_mangle]
+pub extern "C" fn wasi_config_set_version(
+    config: &mut wasi_config_t,
+    version: wasi_version_t,
+) -> bool {
+    match WasiVersion::try_from(version) {
+        Ok(version) => {
+            config.builder.set_wasi_version(version);
+            true
+        }
+        Err(e) => {
+            update_last_error(e);
+            false
+        }
+    }
+}
+
 #[repr(C)]
 pub struct wasi_filesystem_t {
     ptr: *const c_char,
@@ -222,6 +305,8 @@ unsafe fn wasi_env_with_filesystem_inner(
     let module = &module.as_ref()?.inner;
     let imports = imports?;
 
+    let mut config = config;
+    let stdio = config.capture_stdio();
     let (wasi_env, import_object) = prepare_webc_env(
         config,
         &mut store.store_mut(),
@@ -236,6 +321,7 @@ unsafe fn wasi_env_with_filesystem_inner(
     Some(Box::new(wasi_env_t {
         inner: wasi_env,
         store: store.clone(),
+        stdio,
     }))
 }
 
@@ -271,14 +357,6 @@ fn prepare_webc_env(
     let filesystem = Box::new(StaticFileSystem::init(slice, &package_name)?);
     let mut builder = config.builder;
 
-    if !config.inherit_stdout {
-        builder.set_stdout(Box::new(Pipe::channel().0));
-    }
-
-    if !config.inherit_stderr {
-        builder.set_stderr(Box::new(Pipe::channel().0));
-    }
-
     builder.set_fs(filesystem);
 
     for f_name in top_level_dirs.iter() {
@@ -297,6 +375,8 @@ pub struct wasi_env_t {
     /// cbindgen:ignore
     pub(super) inner: WasiFunctionEnv,
     pub(super) store: StoreRef,
+    /// cbindgen:ignore
+    stdio: CapturedStdio,
 }
 
 /// Create a new WASI environment.
@@ -309,21 +389,14 @@ pub unsafe extern "C" fn wasi_env_new(
 ) -> Option<Box<wasi_env_t>> {
     let store = &mut store?.inner;
     let mut store_mut = store.store_mut();
-    if !config.inherit_stdout {
-        config.builder.set_stdout(Box::new(Pipe::channel().0));
-    }
-
-    if !config.inherit_stderr {
-        config.builder.set_stderr(Box::new(Pipe::channel().0));
-    }
-
-    // TODO: impl capturer for stdin
+    let stdio = config.capture_stdio();
 
     let env = c_try!(config.builder.finalize(&mut store_mut));
 
     Some(Box::new(wasi_env_t {
         inner: env,
         store: store.clone(),
+        stdio,
     }))
 }
 
@@ -346,72 +419,215 @@ pub unsafe extern "C" fn wasi_env_set_memory(_env: &mut wasi_env_t, _memory: &wa
     panic!("wasmer_env_set_memory() is not supported");
 }
 
+/// Read the standard output of the module, captured with
+/// [`wasi_config_capture_stdout`], into `buffer`.
+///
+/// Returns the number of bytes read, which is `0` once everything
+/// written so far by the module has been read, or `-1` and sets the
+/// last error on failure.
 #[no_mangle]
 pub unsafe extern "C" fn wasi_env_read_stdout(
     env: &mut wasi_env_t,
     buffer: *mut c_char,
     buffer_len: usize,
 ) -> isize {
-    let inner_buffer = slice::from_raw_parts_mut(buffer as *mut _, buffer_len as usize);
-    let store = env.store.store();
-
-    let (stdout, tasks) = {
-        let data = env.inner.data(&store);
-        (data.stdout(), data.tasks().clone())
-    };
+    let inner_buffer = slice::from_raw_parts_mut(buffer as *mut u8, buffer_len);
 
-    if let Ok(mut stdout) = stdout {
-        if let Some(stdout) = stdout.as_mut() {
-            read_inner(tasks.deref(), stdout, inner_buffer)
-        } else {
-            update_last_error("could not find a file handle for `stdout`");
-            -1
-        }
-    } else {
-        update_last_error("could not find a file handle for `stdout`");
-        -1
-    }
+    read_captured(env.stdio.stdout.as_mut(), "stdout", inner_buffer)
 }
 
+/// Read the standard error of the module, captured with
+/// [`wasi_config_capture_stderr`], into `buffer`.
+///
+/// See [`wasi_env_read_stdout`] to learn more.
 #[no_mangle]
 pub unsafe extern "C" fn wasi_env_read_stderr(
     env: &mut wasi_env_t,
     buffer: *mut c_char,
     buffer_len: usize,
 ) -> isize {
-    let inner_buffer = slice::from_raw_parts_mut(buffer as *mut _, buffer_len as usize);
-    let store = env.store.store();
-    let (stderr, tasks) = {
-        let data = env.inner.data(&store);
-        (data.stderr(), data.tasks().clone())
-    };
-    if let Ok(mut stderr) = stderr {
-        if let Some(stderr) = stderr.as_mut() {
-            read_inner(tasks.deref(), stderr, inner_buffer)
-        } else {
-            update_last_error("could not find a file handle for `stderr`");
-            -1
-        }
-    } else {
-        update_last_error("could not find a file handle for `stderr`");
-        -1
-    }
+    let inner_buffer = slice::from_raw_parts_mut(buffer as *mut u8, buffer_len);
+
+    read_captured(env.stdio.stderr.as_mut(), "stderr", inner_buffer)
 }
 
-fn read_inner(
-    tasks: &dyn VirtualTaskManager,
-    wasi_file: &mut Box<dyn WasiFile + Send + Sync + 'static>,
-    inner_buffer: &mut [u8],
+/// Collect everything written by the module to its standard output,
+/// captured with [`wasi_config_capture_stdout`], since the last read,
+/// e.g. once the module has run.
+///
+/// Returns `false` and sets the last error on failure.
+///
+/// # Example
+///
+/// ```rust
+/// # use wasmer_inline_c::assert_c;
+/// # fn main() {
+/// #    (assert_c! {
+/// # #include "tests/wasmer.h"
+/// #
+/// int main() {
+///     wasm_engine_t* engine = wasm_engine_new();
+///     wasm_store_t* store = wasm_store_new(engine);
+///
+///     // A module writing `hello` to its standard output.
+///     wasm_byte_vec_t wat;
+///     wasmer_byte_vec_new_from_string(
+///         &wat,
+///         "(module\n"
+///         "  (import \"wasi_snapshot_preview1\" \"fd_write\" (func $fd_write (param i32 i32 i32 i32) (result i32)))\n"
+///         "  (memory (export \"memory\") 1)\n"
+///         "  (data (i32.const 0) \"\\08\\00\\00\\00\\05\\00\\00\\00hello\")\n"
+///         "  (func (export \"_start\")\n"
+///         "    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 16)))))"
+///     );
+///     wasm_byte_vec_t wasm;
+///     wat2wasm(&wat, &wasm);
+///
+///     wasm_module_t* module = wasm_module_new(store, &wasm);
+///     assert(module);
+///
+///     // Capture the standard output.
+///     wasi_config_t* config = wasi_config_new("example_program");
+///     wasi_config_capture_stdout(config);
+///
+///     wasi_env_t* wasi_env = wasi_env_new(store, config);
+///     assert(wasi_env);
+///
+///     wasm_extern_vec_t imports;
+///     assert(wasi_get_imports(store, wasi_env, module, &imports));
+///
+///     wasm_instance_t* instance = wasm_instance_new(store, module, &imports, NULL);
+///     assert(instance);
+///     assert(wasi_env_initialize_instance(wasi_env, store, instance));
+///
+///     // Run the module.
+///     wasm_func_t* start = wasi_get_start_function(instance);
+///     wasm_val_vec_t arguments = WASM_EMPTY_VEC;
+///     wasm_val_vec_t results = WASM_EMPTY_VEC;
+///     assert(!wasm_func_call(start, &arguments, &results));
+///
+///     // Read what it wrote.
+///     wasm_byte_vec_t output;
+///     assert(wasi_env_collect_stdout(wasi_env, &output));
+///     wasmer_assert_name(&output, "hello");
+///
+///     // Free everything.
+///     wasm_byte_vec_delete(&output);
+///     wasm_func_delete(start);
+///     wasm_instance_delete(instance);
+///     wasm_extern_vec_delete(&imports);
+///     wasi_env_delete(wasi_env);
+///     wasm_module_delete(module);
+///     wasm_byte_vec_delete(&wasm);
+///     wasm_byte_vec_delete(&wat);
+///     wasm_store_delete(store);
+///     wasm_engine_delete(engine);
+///
+///     return 0;
+/// }
+/// #    })
+/// #    .success();
+/// # }
+/// ```
+#[no_mangle]
+pub extern "C" fn wasi_env_collect_stdout(
+    env: &mut wasi_env_t,
+    // own
+    out: &mut wasm_byte_vec_t,
+) -> bool {
+    collect_captured(env.stdio.stdout.as_mut(), "stdout", out)
+}
+
+/// Collect everything written by the module to its standard error,
+/// captured with [`wasi_config_capture_stderr`], since the last read.
+///
+/// See [`wasi_env_collect_stdout`] to learn more.
+#[no_mangle]
+pub extern "C" fn wasi_env_collect_stderr(
+    env: &mut wasi_env_t,
+    // own
+    out: &mut wasm_byte_vec_t,
+) -> bool {
+    collect_captured(env.stdio.stderr.as_mut(), "stderr", out)
+}
+
+/// Write to the standard input of the module, captured with
+/// [`wasi_config_capture_stdin`].
+///
+/// Returns the number of bytes written, or `-1` and sets the last error
+/// on failure.
+#[no_mangle]
+pub unsafe extern "C" fn wasi_env_write_stdin(
+    env: &mut wasi_env_t,
+    buffer: *const c_char,
+    buffer_len: usize,
 ) -> isize {
-    tasks.block_on(async {
-        match wasi_file.read(inner_buffer).await {
-            Ok(a) => a as isize,
+    let inner_buffer = slice::from_raw_parts(buffer as *const u8, buffer_len);
+    match env.stdio.stdin.as_mut() {
+        Some(stdin) => match std::io::Write::write(stdin, inner_buffer) {
+            Ok(written) => written as isize,
             Err(err) => {
-                update_last_error(format!("failed to read wasi_file: {}", err));
+                update_last_error(format!("failed to write to `stdin`: {}", err));
                 -1
             }
+        },
+        None => {
+            update_last_error("`stdin` is not captured or has been closed");
+            -1
         }
-    })
+    }
+}
+
+/// Close the captured standard input of the module, so that it reads the
+/// end of the file once it has read everything written with
+/// [`wasi_env_write_stdin`].
+#[no_mangle]
+pub extern "C" fn wasi_env_close_stdin(env: &mut wasi_env_t) {
+    env.stdio.stdin = None;
+}
+
+fn read_captured(pipe: Option<&mut Pipe>, name: &str, buffer: &mut [u8]) -> isize {
+    let pipe = match pipe {
+        Some(pipe) => pipe,
+        None => {
+            update_last_error(format!("`{}` is not captured", name));
+            return -1;
+        }
+    };
+
+    match pipe.read(buffer) {
+        Ok(read) => read as isize,
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => 0,
+        Err(err) => {
+            update_last_error(format!("failed to read `{}`: {}", name, err));
+            -1
+        }
+    }
+}
+
+fn collect_captured(pipe: Option<&mut Pipe>, name: &str, out: &mut wasm_byte_vec_t) -> bool {
+    let pipe = match pipe {
+        Some(pipe) => pipe,
+        None => {
+            update_last_error(format!("`{}` is not captured", name));
+            return false;
+        }
+    };
+
+    let mut output = Vec::new();
+
+    match pipe.read_to_end(&mut output) {
+        Ok(_) => {}
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+        Err(err) => {
+            update_last_error(format!("failed to read `{}`: {}", name, err));
+            return false;
+        }
+    }
+
+    out.set_buffer(output);
+
+    true
 }
 
 /// The version of WASI. This is determined by the imports namespace
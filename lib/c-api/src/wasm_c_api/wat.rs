@@ -1,4 +1,6 @@
 use super::types::wasm_byte_vec_t;
+#[cfg(feature = "wat")]
+use crate::error::{update_last_error_with_code, wasmer_error_code_t};
 
 /// Parses in-memory bytes as either the WAT format, or a binary Wasm
 /// module. This is wasmer-specific.
@@ -17,7 +19,7 @@ pub unsafe extern "C" fn wat2wasm(wat: &wasm_byte_vec_t, out: &mut wasm_byte_vec
     match wasmer_api::wat2wasm(wat.as_slice()) {
         Ok(val) => out.set_buffer(val.into_owned()),
         Err(err) => {
-            crate::error::update_last_error(err);
+            update_last_error_with_code(wasmer_error_code_t::WASMER_ERROR_COMPILE, err);
             out.data = std::ptr::null_mut();
             out.size = 0;
         }
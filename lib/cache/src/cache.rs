@@ -4,7 +4,7 @@
 
 use crate::hash::Hash;
 use std::error::Error;
-use wasmer::{AsEngineRef, Module};
+use wasmer::{AsEngineRef, CompileError, Module};
 
 /// A generic cache for storing and loading compiled wasm modules.
 pub trait Cache {
@@ -26,3 +26,76 @@ pub trait Cache {
     /// Store a [`Module`] into the cache with the given [`Hash`].
     fn store(&mut self, key: Hash, module: &Module) -> Result<(), Self::SerializeError>;
 }
+
+/// Compile a module, reusing the artifact stored in `cache` if any.
+///
+/// The key is derived from `bytes` and the engine configuration with
+/// [`Hash::generate_for_engine`]. If the module can't be loaded from the
+/// cache, it is compiled and stored into the cache. Failing to store the
+/// module isn't an error, as the cache is only an optimization.
+///
+/// # Safety
+/// This function is unsafe as the cache store could be tampered with.
+pub unsafe fn compile_with_cache(
+    engine: &impl AsEngineRef,
+    bytes: impl AsRef<[u8]>,
+    cache: &mut impl Cache,
+) -> Result<Module, CompileError> {
+    let bytes = bytes.as_ref();
+    let key = Hash::generate_for_engine(engine, bytes);
+    if let Ok(module) = cache.load(engine, key) {
+        return Ok(module);
+    }
+    let module = Module::new(engine, bytes)?;
+    let _ = cache.store(key, &module);
+    Ok(module)
+}
+
+/// An asynchronous cache for storing and loading compiled wasm modules.
+///
+/// This is the counterpart of [`Cache`] for caches backed by a remote
+/// storage, such as an object store, which must not block the executor
+/// thread of an async service while loading or storing modules.
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+pub trait AsyncCache {
+    /// The serialization error for the implementation
+    type SerializeError: Error + Send + Sync;
+    /// The deserialization error for the implementation
+    type DeserializeError: Error + Send + Sync;
+
+    /// Loads a module using the provided [`Store`] and [`Hash`].
+    ///
+    /// # Safety
+    /// This function is unsafe as the cache store could be tampered with.
+    async unsafe fn load(
+        &self,
+        engine: &(impl AsEngineRef + Sync),
+        key: Hash,
+    ) -> Result<Module, Self::DeserializeError>;
+
+    /// Store a [`Module`] into the cache with the given [`Hash`].
+    async fn store(&mut self, key: Hash, module: &Module) -> Result<(), Self::SerializeError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+    use crate::{InMemoryCache, InstrumentedCache};
+
+    #[test]
+    fn compiles_with_cache() {
+        let engine = test_utils::engine();
+        let mut cache = InstrumentedCache::new(InMemoryCache::new());
+        let bytes = test_utils::wasm("f");
+
+        for _ in 0..2 {
+            let module = unsafe { compile_with_cache(&engine, &bytes, &mut cache) }.unwrap();
+            assert_eq!(module.exports().next().unwrap().name(), "f");
+        }
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.stores), (1, 1, 1));
+    }
+}
@@ -0,0 +1,89 @@
+#![cfg_attr(not(feature = "filesystem"), allow(unused))]
+use crate::DeserializeError;
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+use std::io;
+
+/// Magic bytes at the start of compressed cache files, followed by one
+/// byte identifying the [`Compression`] algorithm.
+const MAGIC: &[u8; 8] = b"WASMERZ\0";
+
+/// The length of the header of compressed cache files.
+pub(crate) const HEADER_LEN: usize = MAGIC.len() + 1;
+
+const ZSTD_ID: u8 = 1;
+const LZ4_ID: u8 = 2;
+
+/// The compression algorithms that can be used to store serialized
+/// modules in a cache.
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compression {
+    /// Zstandard, which gives the best compression ratio.
+    #[cfg(feature = "zstd")]
+    Zstd,
+    /// LZ4, which is the fastest to decompress.
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+impl Compression {
+    fn id(self) -> u8 {
+        match self {
+            #[cfg(feature = "zstd")]
+            Self::Zstd => ZSTD_ID,
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => LZ4_ID,
+        }
+    }
+
+    /// Compress `bytes`, prefixed with the header identifying the
+    /// algorithm.
+    pub(crate) fn compress(self, bytes: &[u8]) -> io::Result<Vec<u8>> {
+        let mut compressed = Vec::with_capacity(HEADER_LEN + bytes.len() / 2);
+        compressed.extend_from_slice(MAGIC);
+        compressed.push(self.id());
+        match self {
+            #[cfg(feature = "zstd")]
+            Self::Zstd => zstd::stream::copy_encode(bytes, &mut compressed, 0)?,
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => compressed.extend(lz4_flex::compress_prepend_size(bytes)),
+        }
+        Ok(compressed)
+    }
+}
+
+/// Check whether `header`, the start of a cache file, identifies a
+/// compressed module.
+pub(crate) fn is_compressed(header: &[u8]) -> bool {
+    header.len() >= HEADER_LEN && header.starts_with(MAGIC)
+}
+
+/// Decompress a cache file starting with a compression header.
+pub(crate) fn decompress(bytes: &[u8]) -> Result<Vec<u8>, DeserializeError> {
+    debug_assert!(is_compressed(bytes));
+    #[cfg_attr(not(any(feature = "zstd", feature = "lz4")), allow(unused_variables))]
+    let payload = &bytes[HEADER_LEN..];
+    match bytes[MAGIC.len()] {
+        #[cfg(feature = "zstd")]
+        ZSTD_ID => zstd::stream::decode_all(payload).map_err(|e| {
+            DeserializeError::CorruptedBinary(format!("invalid zstd compressed module: {}", e))
+        }),
+        #[cfg(not(feature = "zstd"))]
+        ZSTD_ID => Err(DeserializeError::Generic(
+            "the module is compressed with zstd, but the `zstd` feature is disabled".to_string(),
+        )),
+        #[cfg(feature = "lz4")]
+        LZ4_ID => lz4_flex::decompress_size_prepended(payload).map_err(|e| {
+            DeserializeError::CorruptedBinary(format!("invalid lz4 compressed module: {}", e))
+        }),
+        #[cfg(not(feature = "lz4"))]
+        LZ4_ID => Err(DeserializeError::Generic(
+            "the module is compressed with lz4, but the `lz4` feature is disabled".to_string(),
+        )),
+        id => Err(DeserializeError::CorruptedBinary(format!(
+            "unknown compression algorithm: {}",
+            id
+        ))),
+    }
+}
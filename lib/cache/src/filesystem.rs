@@ -1,9 +1,17 @@
 #![cfg_attr(not(feature = "filesystem"), allow(unused))]
 use crate::cache::Cache;
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+use crate::compression::Compression;
+use crate::compression::{self, HEADER_LEN};
 use crate::hash::Hash;
+use crate::signing;
+#[cfg(feature = "signing")]
+use crate::signing::{SigningKey, VerifyingKey};
+use filetime::FileTime;
 use std::fs::{create_dir_all, File};
-use std::io::{self, Write};
-use std::path::PathBuf;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use wasmer::metrics::{self, Counter};
 use wasmer::{AsEngineRef, DeserializeError, Module, SerializeError};
 
 /// Representation of a directory that contains compiled wasm artifacts.
@@ -34,6 +42,13 @@ use wasmer::{AsEngineRef, DeserializeError, Module, SerializeError};
 pub struct FileSystemCache {
     path: PathBuf,
     ext: Option<String>,
+    max_size: Option<u64>,
+    #[cfg(any(feature = "zstd", feature = "lz4"))]
+    compression: Option<Compression>,
+    #[cfg(feature = "signing")]
+    signing_key: Option<SigningKey>,
+    #[cfg(feature = "signing")]
+    verifying_key: Option<VerifyingKey>,
 }
 
 #[cfg(feature = "filesystem")]
@@ -45,7 +60,17 @@ impl FileSystemCache {
             let metadata = path.metadata()?;
             if metadata.is_dir() {
                 if !metadata.permissions().readonly() {
-                    Ok(Self { path, ext: None })
+                    Ok(Self {
+                        path,
+                        ext: None,
+                        max_size: None,
+                        #[cfg(any(feature = "zstd", feature = "lz4"))]
+                        compression: None,
+                        #[cfg(feature = "signing")]
+                        signing_key: None,
+                        #[cfg(feature = "signing")]
+                        verifying_key: None,
+                    })
                 } else {
                     // This directory is readonly.
                     Err(io::Error::new(
@@ -72,7 +97,17 @@ impl FileSystemCache {
                     format!("failed to create cache directory: {}", path.display()),
                 ))
             } else {
-                Ok(Self { path, ext: None })
+                Ok(Self {
+                    path,
+                    ext: None,
+                    max_size: None,
+                    #[cfg(any(feature = "zstd", feature = "lz4"))]
+                    compression: None,
+                    #[cfg(feature = "signing")]
+                    signing_key: None,
+                    #[cfg(feature = "signing")]
+                    verifying_key: None,
+                })
             }
         }
     }
@@ -84,6 +119,157 @@ impl FileSystemCache {
     pub fn set_cache_extension(&mut self, ext: Option<impl ToString>) {
         self.ext = ext.map(|ext| ext.to_string());
     }
+
+    /// Set the maximum size, in bytes, of the cached files.
+    ///
+    /// When storing a module makes the cache grow over this size, the
+    /// least recently used modules are evicted. Loading a module marks
+    /// it as used. By default, the size of the cache is not limited.
+    pub fn set_max_size(&mut self, max_size: Option<u64>) {
+        self.max_size = max_size;
+    }
+
+    /// Set the compression algorithm used to store modules.
+    ///
+    /// Loading a module detects whether it is compressed, and with
+    /// which algorithm, so the compression can be changed without
+    /// clearing the cache. By default, modules are not compressed.
+    #[cfg(any(feature = "zstd", feature = "lz4"))]
+    pub fn set_compression(&mut self, compression: Option<Compression>) {
+        self.compression = compression;
+    }
+
+    /// Set the key used to sign the stored modules.
+    ///
+    /// By default, modules are not signed.
+    #[cfg(feature = "signing")]
+    pub fn set_signing_key(&mut self, signing_key: Option<SigningKey>) {
+        self.signing_key = signing_key;
+    }
+
+    /// Set the key used to verify the loaded modules.
+    ///
+    /// When set, only modules signed with the matching
+    /// [`SigningKey`] are loaded: unsigned or tampered modules are
+    /// rejected before being mapped in memory. By default, signatures
+    /// are not verified.
+    #[cfg(feature = "signing")]
+    pub fn set_verifying_key(&mut self, verifying_key: Option<VerifyingKey>) {
+        self.verifying_key = verifying_key;
+    }
+
+    /// Get the total size, in bytes, of the cached files.
+    pub fn size(&self) -> io::Result<u64> {
+        Ok(self.entries()?.iter().map(|(_, len, _)| len).sum())
+    }
+
+    /// Read the header of the cached file at `path`.
+    fn read_header(path: &Path) -> io::Result<Vec<u8>> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        File::open(path)?
+            .take(HEADER_LEN as u64)
+            .read_to_end(&mut header)?;
+        Ok(header)
+    }
+
+    /// Load the module stored under `key` at `path`, verifying its
+    /// signature and decompressing it if needed.
+    unsafe fn load_from_file(
+        &self,
+        engine: &impl AsEngineRef,
+        key: Hash,
+        path: &Path,
+    ) -> Result<Module, DeserializeError> {
+        let header = Self::read_header(path)?;
+        let signed = signing::is_signed(&header);
+        #[cfg(feature = "signing")]
+        if self.verifying_key.is_some() && !signed {
+            return Err(DeserializeError::Incompatible(
+                "the module is not signed".to_string(),
+            ));
+        }
+        if !signed && !compression::is_compressed(&header) {
+            return Module::deserialize_from_file(engine, path);
+        }
+
+        let mut bytes = std::fs::read(path)?;
+        if signed {
+            let (signature, payload) = signing::split(&bytes)?;
+            #[cfg(feature = "signing")]
+            if let Some(verifying_key) = &self.verifying_key {
+                verifying_key.verify(key, signature, payload)?;
+            }
+            #[cfg(not(feature = "signing"))]
+            let _ = (key, signature);
+            bytes = payload.to_vec();
+        }
+        if compression::is_compressed(&bytes) {
+            bytes = compression::decompress(&bytes)?;
+        }
+        Module::deserialize(engine, bytes)
+    }
+
+    /// Whether the file at `path` is a cached module, named after its
+    /// key with the extension of the cache.
+    fn is_entry(&self, path: &Path) -> bool {
+        let (key, ext) = match self.ext {
+            Some(_) => (path.file_stem(), path.extension()),
+            None => (path.file_name(), None),
+        };
+        ext.and_then(|ext| ext.to_str()) == self.ext.as_deref()
+            && key
+                .and_then(|key| key.to_str())
+                .map_or(false, |key| key.parse::<Hash>().is_ok())
+    }
+
+    /// List the cached files with their size and last use time.
+    fn entries(&self) -> io::Result<Vec<(PathBuf, u64, FileTime)>> {
+        let mut entries = vec![];
+        for entry in self.path.read_dir()? {
+            let entry = entry?;
+            if !self.is_entry(&entry.path()) {
+                continue;
+            }
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                // Removed meanwhile, e.g. by another process using the cache
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            if metadata.is_file() {
+                entries.push((
+                    entry.path(),
+                    metadata.len(),
+                    FileTime::from_last_modification_time(&metadata),
+                ));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Evict the least recently used files until the cache fits in
+    /// `max_size`, keeping the file at `keep`.
+    fn evict(&self, max_size: u64, keep: &Path) -> io::Result<()> {
+        let mut entries = self.entries()?;
+        let mut size: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort_by_key(|(_, _, last_used)| *last_used);
+        for (path, len, _) in entries {
+            if size <= max_size {
+                break;
+            }
+            if path == keep {
+                continue;
+            }
+            match std::fs::remove_file(path) {
+                Ok(()) => {}
+                // Already evicted, e.g. by another process using the cache
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            size -= len;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(feature = "filesystem")]
@@ -96,33 +282,166 @@ impl Cache for FileSystemCache {
         engine: &impl AsEngineRef,
         key: Hash,
     ) -> Result<Module, Self::DeserializeError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("cache_load", key = %key.to_string()).entered();
         let filename = if let Some(ref ext) = self.ext {
             format!("{}.{}", key.to_string(), ext)
         } else {
             key.to_string()
         };
         let path = self.path.join(filename);
-        let ret = Module::deserialize_from_file(engine, path.clone());
+        let ret = self.load_from_file(engine, key, &path);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(hit = ret.is_ok(), "looked up the module in the cache");
+        metrics::increment_counter(if ret.is_ok() {
+            Counter::CacheHits
+        } else {
+            Counter::CacheMisses
+        });
         if ret.is_err() {
             // If an error occurs while deserializing then we can not trust it anymore
             // so delete the cache file
             let _ = std::fs::remove_file(path);
+        } else {
+            // Mark the module as recently used, for the eviction
+            let _ = filetime::set_file_mtime(path, FileTime::now());
         }
         ret
     }
 
     fn store(&mut self, key: Hash, module: &Module) -> Result<(), Self::SerializeError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("cache_store", key = %key.to_string()).entered();
         let filename = if let Some(ref ext) = self.ext {
             format!("{}.{}", key.to_string(), ext)
         } else {
             key.to_string()
         };
         let path = self.path.join(filename);
-        let mut file = File::create(path)?;
+        let mut file = File::create(&path)?;
 
         let buffer = module.serialize()?;
+        #[cfg(any(feature = "zstd", feature = "lz4"))]
+        let buffer = match self.compression {
+            Some(compression) => compression.compress(&buffer)?.into(),
+            None => buffer,
+        };
+        #[cfg(feature = "signing")]
+        let buffer = match &self.signing_key {
+            Some(signing_key) => signing_key.sign(key, &buffer).into(),
+            None => buffer,
+        };
         file.write_all(&buffer)?;
 
+        if let Some(max_size) = self.max_size {
+            self.evict(max_size, &path)?;
+        }
+
         Ok(())
     }
 }
+
+#[cfg(all(test, feature = "filesystem"))]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+
+    #[test]
+    fn evicts_least_recently_used_modules() {
+        let engine = test_utils::engine();
+        let dir = tempfile::tempdir().unwrap();
+        let mut fs_cache = FileSystemCache::new(dir.path()).unwrap();
+
+        let modules = ["f0", "f1", "f2"]
+            .iter()
+            .map(|name| test_utils::module(&engine, name))
+            .collect::<Vec<_>>();
+
+        // Files that aren't cached modules are left alone.
+        let unrelated = dir.path().join("README");
+        std::fs::write(&unrelated, vec![0; 1 << 20]).unwrap();
+        filetime::set_file_mtime(&unrelated, FileTime::from_unix_time(0, 0)).unwrap();
+
+        fs_cache.store(modules[0].0, &modules[0].1).unwrap();
+        let module_size = fs_cache.size().unwrap();
+        assert!(module_size > 0);
+        fs_cache.store(modules[1].0, &modules[1].1).unwrap();
+
+        // Make the first module the least recently used one.
+        let first = dir.path().join(modules[0].0.to_string());
+        filetime::set_file_mtime(first, FileTime::from_unix_time(0, 0)).unwrap();
+
+        fs_cache.set_max_size(Some(module_size * 5 / 2));
+        fs_cache.store(modules[2].0, &modules[2].1).unwrap();
+        assert!(fs_cache.size().unwrap() <= module_size * 5 / 2);
+
+        unsafe {
+            assert!(fs_cache.load(&engine, modules[0].0).is_err());
+            assert!(fs_cache.load(&engine, modules[1].0).is_ok());
+            assert!(fs_cache.load(&engine, modules[2].0).is_ok());
+        }
+        assert!(unrelated.exists());
+    }
+
+    #[cfg(any(feature = "zstd", feature = "lz4"))]
+    #[test]
+    fn loads_compressed_modules() {
+        let engine = test_utils::engine();
+        let dir = tempfile::tempdir().unwrap();
+        let mut fs_cache = FileSystemCache::new(dir.path()).unwrap();
+
+        let (key, module) = test_utils::module(&engine, "f");
+
+        let mut compressions = vec![];
+        #[cfg(feature = "zstd")]
+        compressions.push(Compression::Zstd);
+        #[cfg(feature = "lz4")]
+        compressions.push(Compression::Lz4);
+
+        for compression in compressions {
+            fs_cache.set_compression(Some(compression));
+            fs_cache.store(key, &module).unwrap();
+            let stored = std::fs::read(dir.path().join(key.to_string())).unwrap();
+            assert!(compression::is_compressed(&stored));
+
+            // The compression is detected when loading.
+            fs_cache.set_compression(None);
+            let loaded = unsafe { fs_cache.load(&engine, key) }.unwrap();
+            assert_eq!(loaded.exports().count(), 1);
+        }
+    }
+
+    #[cfg(feature = "signing")]
+    #[test]
+    fn verifies_signed_modules() {
+        let engine = test_utils::engine();
+        let dir = tempfile::tempdir().unwrap();
+        let mut fs_cache = FileSystemCache::new(dir.path()).unwrap();
+
+        let (key, module) = test_utils::module(&engine, "f");
+        let path = dir.path().join(key.to_string());
+
+        let signing_key = SigningKey::from_seed(&[1; 32]).unwrap();
+        let other_key = SigningKey::from_seed(&[2; 32]).unwrap();
+        fs_cache.set_verifying_key(Some(signing_key.verifying_key()));
+
+        // Unsigned modules are rejected.
+        fs_cache.store(key, &module).unwrap();
+        assert!(unsafe { fs_cache.load(&engine, key) }.is_err());
+
+        // Modules signed with another key are rejected.
+        fs_cache.set_signing_key(Some(other_key));
+        fs_cache.store(key, &module).unwrap();
+        assert!(unsafe { fs_cache.load(&engine, key) }.is_err());
+
+        fs_cache.set_signing_key(Some(signing_key));
+        fs_cache.store(key, &module).unwrap();
+        assert!(unsafe { fs_cache.load(&engine, key) }.is_ok());
+
+        // Tampered modules are rejected.
+        let mut stored = std::fs::read(&path).unwrap();
+        *stored.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, stored).unwrap();
+        assert!(unsafe { fs_cache.load(&engine, key) }.is_err());
+    }
+}
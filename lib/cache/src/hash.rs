@@ -1,6 +1,7 @@
 use crate::DeserializeError;
 use std::str::FromStr;
 use std::string::ToString;
+use wasmer::AsEngineRef;
 
 /// A hash used as a key when loading and storing modules in a
 /// [`Cache`].
@@ -22,6 +23,35 @@ impl Hash {
         Self::new(hash.into())
     }
 
+    /// Creates a new hash from a slice of bytes and the engine the
+    /// module is compiled with.
+    ///
+    /// The hash covers the wasmer version and the engine's
+    /// [`deterministic_id`](wasmer::Engine::deterministic_id) (compiler,
+    /// compiler settings, middlewares, Wasm features, target and CPU
+    /// features), so an artifact is never loaded by an engine that would
+    /// have compiled it differently, e.g. after an upgrade or on another
+    /// machine.
+    ///
+    /// Headless engines can't tell how the artifacts they load were
+    /// compiled, so their hashes only match the ones of other headless
+    /// engines for the same target.
+    pub fn generate_for_engine(engine: &impl AsEngineRef, bytes: &[u8]) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.update(&[0]);
+        hasher.update(
+            engine
+                .as_engine_ref()
+                .engine()
+                .deterministic_id()
+                .as_bytes(),
+        );
+        hasher.update(&[0]);
+        hasher.update(bytes);
+        Self::new(hasher.finalize().into())
+    }
+
     pub(crate) fn to_array(self) -> [u8; 32] {
         self.0
     }
@@ -71,4 +101,65 @@ mod tests {
         let hash = Hash::new(original);
         assert_eq!(hash.to_array(), original);
     }
+
+    #[test]
+    fn hash_for_engine_depends_on_engine() {
+        use crate::test_utils;
+        use wasmer::Engine;
+        use wasmer_compiler_singlepass::Singlepass;
+
+        let bytes = &test_utils::wasm("f");
+        let engine = test_utils::engine();
+        let mut config = Singlepass::default();
+        config.canonicalize_nans(false);
+        let other_engine: Engine = config.into();
+
+        assert_eq!(
+            Hash::generate_for_engine(&engine, bytes),
+            Hash::generate_for_engine(&engine, bytes)
+        );
+        assert_ne!(
+            Hash::generate_for_engine(&engine, bytes),
+            Hash::generate(bytes)
+        );
+        assert_ne!(
+            Hash::generate_for_engine(&engine, bytes),
+            Hash::generate_for_engine(&other_engine, bytes)
+        );
+    }
+
+    #[test]
+    fn hash_for_engine_depends_on_middlewares() {
+        use crate::test_utils;
+        use std::sync::Arc;
+        use wasmer::{
+            CompilerConfig, Engine, FunctionMiddleware, LocalFunctionIndex, ModuleMiddleware,
+        };
+        use wasmer_compiler_singlepass::Singlepass;
+
+        #[derive(Debug)]
+        struct Noop;
+
+        impl FunctionMiddleware for Noop {}
+
+        impl ModuleMiddleware for Noop {
+            fn generate_function_middleware(
+                &self,
+                _: LocalFunctionIndex,
+            ) -> Box<dyn FunctionMiddleware> {
+                Box::new(Noop)
+            }
+        }
+
+        let bytes = &test_utils::wasm("f");
+        let engine = test_utils::engine();
+        let mut config = Singlepass::default();
+        config.push_middleware(Arc::new(Noop));
+        let instrumented_engine: Engine = config.into();
+
+        assert_ne!(
+            Hash::generate_for_engine(&engine, bytes),
+            Hash::generate_for_engine(&instrumented_engine, bytes)
+        );
+    }
 }
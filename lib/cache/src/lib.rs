@@ -19,13 +19,27 @@
 )]
 
 mod cache;
+mod compression;
 mod filesystem;
 mod hash;
+mod memory;
+mod signing;
+mod stats;
+#[cfg(test)]
+mod test_utils;
 
-pub use crate::cache::Cache;
+#[cfg(feature = "async")]
+pub use crate::cache::AsyncCache;
+pub use crate::cache::{compile_with_cache, Cache};
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+pub use crate::compression::Compression;
 #[cfg(feature = "filesystem")]
 pub use crate::filesystem::FileSystemCache;
 pub use crate::hash::Hash;
+pub use crate::memory::InMemoryCache;
+#[cfg(feature = "signing")]
+pub use crate::signing::{SigningKey, VerifyingKey};
+pub use crate::stats::{CacheEvent, CacheStats, InstrumentedCache};
 
 // We re-export those for convinience of users
 pub use wasmer::{DeserializeError, SerializeError};
@@ -0,0 +1,217 @@
+use crate::cache::Cache;
+use crate::hash::Hash;
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use wasmer::metrics::{self, Counter};
+use wasmer::{AsEngineRef, DeserializeError, Module, SerializeError};
+
+/// A cache keeping compiled wasm artifacts in memory.
+///
+/// The `InMemoryCache` type implements the [`Cache`] trait. Cloning it is
+/// cheap and the clones share the same cached modules, so it can be
+/// cloned into every thread that needs it.
+///
+/// # Usage
+///
+/// ```
+/// use wasmer::{DeserializeError, SerializeError};
+/// use wasmer_cache::{Cache, InMemoryCache, Hash};
+///
+/// # use wasmer::{Module};
+/// fn store_module(module: &Module, bytes: &[u8]) -> Result<(), SerializeError> {
+///     // Create a new in-memory cache, keeping at most 100 modules.
+///     let mut cache = InMemoryCache::with_capacity(100);
+///
+///     // Compute a key for a given WebAssembly binary
+///     let key = Hash::generate(bytes);
+///
+///     // Store a module into the cache given a key
+///     cache.store(key, module)?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryCache {
+    inner: Arc<Mutex<InMemoryCacheInner>>,
+}
+
+#[derive(Debug, Default)]
+struct InMemoryCacheInner {
+    /// The serialized modules, with the tick of their last use.
+    modules: HashMap<Hash, (Arc<[u8]>, u64)>,
+    /// The maximum number of modules, if any.
+    capacity: Option<usize>,
+    /// Incremented on every use of a module.
+    tick: u64,
+}
+
+impl InMemoryCache {
+    /// Construct a new, unbounded `InMemoryCache`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Construct a new `InMemoryCache` keeping at most `capacity`
+    /// modules. When a new module is stored in a full cache, the least
+    /// recently used module is evicted.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let cache = Self::default();
+        cache.inner.lock().unwrap().capacity = Some(capacity);
+        cache
+    }
+
+    /// Get the number of modules in the cache.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().modules.len()
+    }
+
+    /// Check whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all the modules from the cache.
+    pub fn clear(&self) {
+        self.inner.lock().unwrap().modules.clear();
+    }
+}
+
+impl InMemoryCacheInner {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+impl Cache for InMemoryCache {
+    type DeserializeError = DeserializeError;
+    type SerializeError = SerializeError;
+
+    unsafe fn load(
+        &self,
+        engine: &impl AsEngineRef,
+        key: Hash,
+    ) -> Result<Module, Self::DeserializeError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("cache_load", key = %key.to_string()).entered();
+        let bytes = {
+            let mut inner = self.inner.lock().unwrap();
+            let tick = inner.next_tick();
+            match inner.modules.get_mut(&key) {
+                Some((bytes, last_used)) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(hit = true, "looked up the module in the cache");
+                    metrics::increment_counter(Counter::CacheHits);
+                    *last_used = tick;
+                    bytes.clone()
+                }
+                None => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(hit = false, "looked up the module in the cache");
+                    metrics::increment_counter(Counter::CacheMisses);
+                    return Err(DeserializeError::Io(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("module {} is not in the cache", key.to_string()),
+                    )));
+                }
+            }
+        };
+        // The modules never leave the process memory, so they don't need
+        // to be validated again.
+        Module::deserialize_unchecked(engine, &*bytes)
+    }
+
+    fn store(&mut self, key: Hash, module: &Module) -> Result<(), Self::SerializeError> {
+        let bytes = Arc::from(&*module.serialize()?);
+
+        let mut inner = self.inner.lock().unwrap();
+        let tick = inner.next_tick();
+        inner.modules.insert(key, (bytes, tick));
+        if let Some(capacity) = inner.capacity {
+            while inner.modules.len() > capacity {
+                let least_recently_used = *inner
+                    .modules
+                    .iter()
+                    .min_by_key(|(_, (_, last_used))| last_used)
+                    .unwrap()
+                    .0;
+                inner.modules.remove(&least_recently_used);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The in-memory cache never blocks on I/O, so it can be used directly
+/// from async code.
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl crate::cache::AsyncCache for InMemoryCache {
+    type DeserializeError = DeserializeError;
+    type SerializeError = SerializeError;
+
+    async unsafe fn load(
+        &self,
+        engine: &(impl AsEngineRef + Sync),
+        key: Hash,
+    ) -> Result<Module, Self::DeserializeError> {
+        Cache::load(self, engine, key)
+    }
+
+    async fn store(&mut self, key: Hash, module: &Module) -> Result<(), Self::SerializeError> {
+        Cache::store(self, key, module)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+
+    #[test]
+    fn evicts_least_recently_used_modules() {
+        let engine = test_utils::engine();
+        let mut cache = InMemoryCache::with_capacity(2);
+
+        let modules = ["f0", "f1", "f2"]
+            .iter()
+            .map(|name| test_utils::module(&engine, name))
+            .collect::<Vec<_>>();
+
+        cache.store(modules[0].0, &modules[0].1).unwrap();
+        cache.store(modules[1].0, &modules[1].1).unwrap();
+        // Use the first module, so that the second one is evicted.
+        assert!(unsafe { cache.load(&engine, modules[0].0) }.is_ok());
+
+        // Clones share the cached modules.
+        let mut other = cache.clone();
+        other.store(modules[2].0, &modules[2].1).unwrap();
+        assert_eq!(cache.len(), 2);
+
+        unsafe {
+            assert!(cache.load(&engine, modules[0].0).is_ok());
+            assert!(cache.load(&engine, modules[1].0).is_err());
+            let module = cache.load(&engine, modules[2].0).unwrap();
+            assert_eq!(module.exports().next().unwrap().name(), "f2");
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn loads_modules_asynchronously() {
+        use crate::cache::AsyncCache;
+
+        let engine = test_utils::engine();
+        let mut cache = InMemoryCache::new();
+        let (key, module) = test_utils::module(&engine, "f");
+
+        futures::executor::block_on(async {
+            AsyncCache::store(&mut cache, key, &module).await.unwrap();
+            let module = unsafe { AsyncCache::load(&cache, &engine, key).await }.unwrap();
+            assert_eq!(module.exports().next().unwrap().name(), "f");
+        });
+    }
+}
@@ -0,0 +1,113 @@
+#![cfg_attr(not(feature = "filesystem"), allow(unused))]
+#[cfg(feature = "signing")]
+use crate::hash::Hash;
+use crate::DeserializeError;
+#[cfg(feature = "signing")]
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+
+/// Magic bytes at the start of signed cache files, followed by the
+/// ed25519 signature of the rest of the file.
+const MAGIC: &[u8; 8] = b"WASMERS\0";
+
+/// The length of an ed25519 signature.
+const SIGNATURE_LEN: usize = 64;
+
+/// The length of the header of signed cache files.
+pub(crate) const HEADER_LEN: usize = MAGIC.len() + SIGNATURE_LEN;
+
+/// An ed25519 key used to sign the modules stored in a cache.
+#[cfg(feature = "signing")]
+pub struct SigningKey(Ed25519KeyPair);
+
+#[cfg(feature = "signing")]
+impl SigningKey {
+    /// Creates a signing key from its 32 bytes seed.
+    pub fn from_seed(seed: &[u8; 32]) -> Result<Self, String> {
+        Ed25519KeyPair::from_seed_unchecked(seed)
+            .map(Self)
+            .map_err(|e| format!("invalid ed25519 seed: {}", e))
+    }
+
+    /// Creates a signing key from a PKCS#8 v2 document, as generated
+    /// by most tools.
+    pub fn from_pkcs8(pkcs8: &[u8]) -> Result<Self, String> {
+        Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8)
+            .map(Self)
+            .map_err(|e| format!("invalid ed25519 PKCS#8 document: {}", e))
+    }
+
+    /// The key verifying the signatures of this key.
+    pub fn verifying_key(&self) -> VerifyingKey {
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(self.0.public_key().as_ref());
+        VerifyingKey(bytes)
+    }
+
+    /// Sign `bytes`, stored under `key`, and prefix them with the
+    /// signature header.
+    pub(crate) fn sign(&self, key: Hash, bytes: &[u8]) -> Vec<u8> {
+        let mut signed = Vec::with_capacity(HEADER_LEN + bytes.len());
+        signed.extend_from_slice(MAGIC);
+        signed.extend_from_slice(self.0.sign(&message(key, bytes)).as_ref());
+        signed.extend_from_slice(bytes);
+        signed
+    }
+}
+
+/// An ed25519 public key used to verify the modules loaded from a
+/// cache.
+#[cfg(feature = "signing")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VerifyingKey([u8; 32]);
+
+#[cfg(feature = "signing")]
+impl VerifyingKey {
+    /// Creates a verifying key from its 32 bytes representation.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// The 32 bytes representation of this key.
+    pub fn to_bytes(self) -> [u8; 32] {
+        self.0
+    }
+
+    /// Verify the `signature` of `payload`, stored under `key`.
+    pub(crate) fn verify(
+        &self,
+        key: Hash,
+        signature: &[u8],
+        payload: &[u8],
+    ) -> Result<(), DeserializeError> {
+        UnparsedPublicKey::new(&ED25519, self.0)
+            .verify(&message(key, payload), signature)
+            .map_err(|_| DeserializeError::Incompatible("invalid module signature".to_string()))
+    }
+}
+
+/// The signed message: the signature covers the cache key too, so that
+/// a signed module can't be swapped with another one.
+#[cfg(feature = "signing")]
+fn message(key: Hash, bytes: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(32 + bytes.len());
+    message.extend_from_slice(&key.to_array());
+    message.extend_from_slice(bytes);
+    message
+}
+
+/// Check whether `header`, the start of a cache file, identifies a
+/// signed module.
+pub(crate) fn is_signed(header: &[u8]) -> bool {
+    header.starts_with(MAGIC)
+}
+
+/// Split a signed cache file into its signature and its content.
+pub(crate) fn split(bytes: &[u8]) -> Result<(&[u8], &[u8]), DeserializeError> {
+    debug_assert!(is_signed(bytes));
+    if bytes.len() < HEADER_LEN {
+        return Err(DeserializeError::CorruptedBinary(
+            "truncated module signature".to_string(),
+        ));
+    }
+    Ok(bytes[MAGIC.len()..].split_at(SIGNATURE_LEN))
+}
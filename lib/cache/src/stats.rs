@@ -0,0 +1,217 @@
+use crate::cache::Cache;
+use crate::hash::Hash;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use wasmer::{AsEngineRef, Module};
+
+/// Statistics about the use of a cache.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of modules successfully loaded from the cache.
+    pub hits: u64,
+    /// The number of modules that failed to load from the cache.
+    pub misses: u64,
+    /// The number of modules stored into the cache.
+    pub stores: u64,
+    /// The total time spent loading modules.
+    pub load_time: Duration,
+    /// The total time spent storing modules.
+    pub store_time: Duration,
+}
+
+/// An operation on a cache, reported to the callback of an
+/// [`InstrumentedCache`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CacheEvent {
+    /// A module was loaded from the cache.
+    Hit {
+        /// The key of the module.
+        key: Hash,
+        /// The time spent loading the module.
+        duration: Duration,
+    },
+    /// A module failed to load from the cache.
+    Miss {
+        /// The key of the module.
+        key: Hash,
+        /// The time spent trying to load the module.
+        duration: Duration,
+    },
+    /// A module was stored into the cache.
+    Store {
+        /// The key of the module.
+        key: Hash,
+        /// The time spent storing the module.
+        duration: Duration,
+    },
+}
+
+type Callback = Box<dyn Fn(&CacheEvent) + Send + Sync>;
+
+/// A cache wrapping another [`Cache`] and recording statistics about
+/// its use.
+///
+/// # Usage
+///
+/// ```
+/// use wasmer_cache::{CacheEvent, InMemoryCache, InstrumentedCache};
+///
+/// let mut cache = InstrumentedCache::new(InMemoryCache::new());
+/// cache.set_callback(|event| {
+///     if let CacheEvent::Miss { key, .. } = event {
+///         eprintln!("cache miss: {}", key.to_string());
+///     }
+/// });
+///
+/// // Use the cache...
+///
+/// let stats = cache.stats();
+/// println!("{} hits, {} misses", stats.hits, stats.misses);
+/// ```
+pub struct InstrumentedCache<C: Cache> {
+    cache: C,
+    stats: Mutex<CacheStats>,
+    callback: Option<Callback>,
+}
+
+impl<C: Cache> InstrumentedCache<C> {
+    /// Wrap `cache`, recording statistics about its use.
+    pub fn new(cache: C) -> Self {
+        Self {
+            cache,
+            stats: Mutex::new(CacheStats::default()),
+            callback: None,
+        }
+    }
+
+    /// Set a callback called on every operation on the cache.
+    pub fn set_callback(&mut self, callback: impl Fn(&CacheEvent) + Send + Sync + 'static) {
+        self.callback = Some(Box::new(callback));
+    }
+
+    /// Get the statistics recorded so far.
+    pub fn stats(&self) -> CacheStats {
+        *self.stats.lock().unwrap()
+    }
+
+    /// Reset the statistics recorded so far.
+    pub fn reset_stats(&self) {
+        *self.stats.lock().unwrap() = CacheStats::default();
+    }
+
+    /// Get a reference to the wrapped cache.
+    pub fn get_ref(&self) -> &C {
+        &self.cache
+    }
+
+    /// Get a mutable reference to the wrapped cache.
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.cache
+    }
+
+    /// Unwrap the wrapped cache.
+    pub fn into_inner(self) -> C {
+        self.cache
+    }
+
+    fn record(&self, event: CacheEvent) {
+        {
+            let mut stats = self.stats.lock().unwrap();
+            match event {
+                CacheEvent::Hit { duration, .. } => {
+                    stats.hits += 1;
+                    stats.load_time += duration;
+                }
+                CacheEvent::Miss { duration, .. } => {
+                    stats.misses += 1;
+                    stats.load_time += duration;
+                }
+                CacheEvent::Store { duration, .. } => {
+                    stats.stores += 1;
+                    stats.store_time += duration;
+                }
+            }
+        }
+        if let Some(callback) = &self.callback {
+            callback(&event);
+        }
+    }
+}
+
+impl<C: Cache + fmt::Debug> fmt::Debug for InstrumentedCache<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstrumentedCache")
+            .field("cache", &self.cache)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl<C: Cache> Cache for InstrumentedCache<C> {
+    type DeserializeError = C::DeserializeError;
+    type SerializeError = C::SerializeError;
+
+    unsafe fn load(
+        &self,
+        engine: &impl AsEngineRef,
+        key: Hash,
+    ) -> Result<Module, Self::DeserializeError> {
+        let start = Instant::now();
+        let result = self.cache.load(engine, key);
+        let duration = start.elapsed();
+        self.record(match result {
+            Ok(_) => CacheEvent::Hit { key, duration },
+            Err(_) => CacheEvent::Miss { key, duration },
+        });
+        result
+    }
+
+    fn store(&mut self, key: Hash, module: &Module) -> Result<(), Self::SerializeError> {
+        let start = Instant::now();
+        self.cache.store(key, module)?;
+        self.record(CacheEvent::Store {
+            key,
+            duration: start.elapsed(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::InMemoryCache;
+    use crate::test_utils;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn records_hits_and_misses() {
+        let engine = test_utils::engine();
+        let mut cache = InstrumentedCache::new(InMemoryCache::new());
+        let misses = Arc::new(AtomicU64::new(0));
+        cache.set_callback({
+            let misses = misses.clone();
+            move |event| {
+                if let CacheEvent::Miss { .. } = event {
+                    misses.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+
+        let (key, module) = test_utils::module(&engine, "f");
+
+        assert!(unsafe { cache.load(&engine, key) }.is_err());
+        cache.store(key, &module).unwrap();
+        assert!(unsafe { cache.load(&engine, key) }.is_ok());
+        assert!(unsafe { cache.load(&engine, key) }.is_ok());
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.stores), (2, 1, 1));
+        assert_eq!(misses.load(Ordering::SeqCst), 1);
+
+        cache.reset_stats();
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+}
@@ -0,0 +1,21 @@
+//! Fixtures shared by the tests of the caches.
+
+use crate::hash::Hash;
+use wasmer::{Engine, Module};
+use wasmer_compiler_singlepass::Singlepass;
+
+/// The engine the test modules are compiled with.
+pub(crate) fn engine() -> Engine {
+    Singlepass::default().into()
+}
+
+/// The binary of a module exporting an empty function named `name`.
+pub(crate) fn wasm(name: &str) -> Vec<u8> {
+    wat::parse_str(format!(r#"(module (func (export "{}")))"#, name)).unwrap()
+}
+
+/// Compiles the module of [`wasm`], returning it with its key.
+pub(crate) fn module(engine: &Engine, name: &str) -> (Hash, Module) {
+    let bytes = wasm(name);
+    (Hash::generate(&bytes), Module::new(engine, bytes).unwrap())
+}
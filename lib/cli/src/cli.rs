@@ -2,15 +2,16 @@
 
 #[cfg(target_os = "linux")]
 use crate::commands::Binfmt;
-#[cfg(feature = "compiler")]
-use crate::commands::Compile;
 #[cfg(any(feature = "static-artifact-create", feature = "wasmer-artifact-create"))]
 use crate::commands::CreateExe;
 #[cfg(feature = "wast")]
 use crate::commands::Wast;
 use crate::commands::{
-    Add, Cache, Config, Init, Inspect, List, Login, Publish, Run, SelfUpdate, Validate, Whoami,
+    Add, Cache, Completions, Config, Coredump, Init, Inspect, List, Login, Publish, Repl, Run,
+    SelfUpdate, Validate, Whoami,
 };
+#[cfg(feature = "compiler")]
+use crate::commands::{Bench, Compile};
 #[cfg(feature = "static-artifact-create")]
 use crate::commands::{CreateObj, GenCHeader};
 use crate::error::PrettyError;
@@ -61,6 +62,11 @@ enum WasmerCLIOptions {
     #[cfg(feature = "compiler")]
     Compile(Compile),
 
+    /// Measure the compilation, instantiation and execution times of a
+    /// WebAssembly function with each compiler
+    #[cfg(feature = "compiler")]
+    Bench(Bench),
+
     /// Compile a WebAssembly binary into a native executable
     ///
     /// To use, you need to set the `WASMER_DIR` environment variable
@@ -143,6 +149,13 @@ enum WasmerCLIOptions {
     /// Inspect a WebAssembly file
     Inspect(Inspect),
 
+    /// Examine a core dump written by `wasmer run --coredump-on-trap`
+    Coredump(Coredump),
+
+    /// Instantiate a WebAssembly module and interactively call its
+    /// exports, inspect its memories and read its globals
+    Repl(Repl),
+
     /// Initializes a new wasmer.toml file
     #[clap(name = "init")]
     Init(Init),
@@ -160,6 +173,9 @@ enum WasmerCLIOptions {
 
     /// Add a WAPM package's bindings to your application.
     Add(Add),
+
+    /// Generate the shell completions for the wasmer CLI
+    Completions(Completions),
 }
 
 impl WasmerCLIOptions {
@@ -171,12 +187,16 @@ impl WasmerCLIOptions {
             Self::Validate(validate) => validate.execute(),
             #[cfg(feature = "compiler")]
             Self::Compile(compile) => compile.execute(),
+            #[cfg(feature = "compiler")]
+            Self::Bench(bench) => bench.execute(),
             #[cfg(any(feature = "static-artifact-create", feature = "wasmer-artifact-create"))]
             Self::CreateExe(create_exe) => create_exe.execute(),
             #[cfg(feature = "static-artifact-create")]
             Self::CreateObj(create_obj) => create_obj.execute(),
             Self::Config(config) => config.execute(),
             Self::Inspect(inspect) => inspect.execute(),
+            Self::Coredump(coredump) => coredump.execute(),
+            Self::Repl(repl) => repl.execute(),
             Self::Init(init) => init.execute(),
             Self::List(list) => list.execute(),
             Self::Login(login) => login.execute(),
@@ -189,6 +209,7 @@ impl WasmerCLIOptions {
             Self::Binfmt(binfmt) => binfmt.execute(),
             Self::Whoami(whoami) => whoami.execute(),
             Self::Add(install) => install.execute(),
+            Self::Completions(completions) => completions.execute(&mut Self::command()),
         }
     }
 }
@@ -240,9 +261,10 @@ fn wasmer_main_inner() -> Result<(), anyhow::Error> {
         WasmerCLIOptions::Run(Run::from_binfmt_args())
     } else {
         match command.unwrap_or(&"".to_string()).as_ref() {
-            "add" | "cache" | "compile" | "config" | "create-obj" | "create-exe" | "help"
-            | "gen-c-header" | "inspect" | "init" | "run" | "self-update" | "validate" | "wast"
-            | "binfmt" | "list" | "login" | "publish" => WasmerCLIOptions::parse(),
+            "add" | "bench" | "cache" | "compile" | "completions" | "config" | "coredump"
+            | "create-obj" | "create-exe" | "help" | "gen-c-header" | "inspect" | "init"
+            | "repl" | "run" | "self-update" | "validate" | "wast" | "binfmt" | "list"
+            | "login" | "publish" => WasmerCLIOptions::parse(),
             _ => {
                 WasmerCLIOptions::try_parse_from(args.iter()).unwrap_or_else(|e| {
                     match e.kind() {
@@ -1,11 +1,15 @@
 //! The commands available in the Wasmer binary.
 mod add;
+#[cfg(feature = "compiler")]
+mod bench;
 #[cfg(target_os = "linux")]
 mod binfmt;
 mod cache;
 #[cfg(feature = "compiler")]
 mod compile;
+mod completions;
 mod config;
+mod coredump;
 #[cfg(any(feature = "static-artifact-create", feature = "wasmer-artifact-create"))]
 mod create_exe;
 #[cfg(feature = "static-artifact-create")]
@@ -17,6 +21,7 @@ mod inspect;
 mod list;
 mod login;
 mod publish;
+mod repl;
 mod run;
 mod self_update;
 mod validate;
@@ -26,17 +31,17 @@ mod whoami;
 
 #[cfg(target_os = "linux")]
 pub use binfmt::*;
-#[cfg(feature = "compiler")]
-pub use compile::*;
 #[cfg(any(feature = "static-artifact-create", feature = "wasmer-artifact-create"))]
 pub use create_exe::*;
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "wast")]
 pub use wast::*;
 pub use {
-    add::*, cache::*, config::*, init::*, inspect::*, list::*, login::*, publish::*, run::*,
-    self_update::*, validate::*, whoami::*,
+    add::*, cache::*, completions::*, config::*, coredump::*, init::*, inspect::*, list::*,
+    login::*, publish::*, repl::*, run::*, self_update::*, validate::*, whoami::*,
 };
+#[cfg(feature = "compiler")]
+pub use {bench::*, compile::*};
 #[cfg(feature = "static-artifact-create")]
 pub use {create_obj::*, gen_c_header::*};
 
@@ -67,3 +72,30 @@ impl std::str::FromStr for ObjectFormat {
         }
     }
 }
+
+/// The format in which a command prints its results.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text.
+    Text,
+    /// Machine-readable JSON.
+    Json,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat::Text
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err("must be one of two options: `text` or `json`."),
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use super::run::invoke::{format_value, parse_value};
+use super::OutputFormat;
+use crate::store::{CompilerType, StoreOptions};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use serde::{Serialize, Serializer};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use wasmer::*;
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer bench` subcommand
+pub struct Bench {
+    /// The module to benchmark
+    #[clap(name = "FILE", parse(from_os_str))]
+    path: PathBuf,
+
+    /// The function to call on each iteration
+    #[clap(long = "invoke", short = 'i')]
+    invoke: String,
+
+    /// How many times to call the function
+    #[clap(long = "iterations", default_value = "10")]
+    iterations: usize,
+
+    /// The format of the results: `text` or `json`, with the durations
+    /// in seconds
+    #[clap(long = "format", default_value = "text")]
+    format: OutputFormat,
+
+    /// The compiler to benchmark with, all the enabled ones if none is
+    /// selected
+    #[clap(flatten)]
+    store: StoreOptions,
+
+    /// The arguments of the function
+    #[clap(value_name = "ARGS")]
+    args: Vec<String>,
+}
+
+/// The timings measured for one compiler.
+#[derive(Serialize)]
+struct BackendReport {
+    compiler: String,
+    #[serde(serialize_with = "secs")]
+    compilation: Duration,
+    #[serde(serialize_with = "secs")]
+    instantiation: Duration,
+    execution: ExecutionStats,
+    result: String,
+}
+
+/// Statistics over the duration of the calls to the function.
+#[derive(Serialize)]
+struct ExecutionStats {
+    #[serde(serialize_with = "secs")]
+    mean: Duration,
+    #[serde(serialize_with = "secs")]
+    median: Duration,
+    #[serde(serialize_with = "secs")]
+    min: Duration,
+    #[serde(serialize_with = "secs")]
+    max: Duration,
+    #[serde(serialize_with = "secs")]
+    std_dev: Duration,
+}
+
+fn secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+impl ExecutionStats {
+    fn new(mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        let count = samples.len() as f64;
+        let mean = samples.iter().map(Duration::as_secs_f64).sum::<f64>() / count;
+        let variance = samples
+            .iter()
+            .map(|sample| (sample.as_secs_f64() - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        Self {
+            mean: Duration::from_secs_f64(mean),
+            median: samples[samples.len() / 2],
+            min: samples[0],
+            max: samples[samples.len() - 1],
+            std_dev: Duration::from_secs_f64(variance.sqrt()),
+        }
+    }
+}
+
+impl Bench {
+    /// Runs logic for the `bench` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context(format!("failed to benchmark `{}`", self.path.display()))
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        if self.iterations == 0 {
+            bail!("`--iterations` must be at least 1");
+        }
+        let contents = std::fs::read(&self.path)?;
+        let compilers = if self.store.is_compiler_selected() {
+            vec![self.store.for_module(&contents)?.get_compiler()?]
+        } else {
+            CompilerType::enabled()
+        };
+
+        let reports = compilers
+            .into_iter()
+            .map(|compiler| {
+                self.bench_compiler(&contents, compiler.clone())
+                    .with_context(|| format!("with {}", compiler.to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        match self.format {
+            OutputFormat::Text => print_reports(&reports),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+        }
+        Ok(())
+    }
+
+    fn bench_compiler(&self, contents: &[u8], compiler: CompilerType) -> Result<BackendReport> {
+        let (mut store, _) = self.store.with_compiler(compiler.clone()).get_store()?;
+
+        let start = Instant::now();
+        let module = Module::new(&store, contents)?;
+        let compilation = start.elapsed();
+
+        let start = Instant::now();
+        let instance = self.instantiate(&mut store, &module)?;
+        let instantiation = start.elapsed();
+
+        let func = instance
+            .exports
+            .get_function(&self.invoke)
+            .map_err(|e| anyhow!("can't call `{}`: {}", self.invoke, e))?
+            .clone();
+        let params = func.ty(&store).params().to_vec();
+        if params.len() != self.args.len() {
+            bail!(
+                "Function expected {} arguments, but received {}: \"{}\"",
+                params.len(),
+                self.args.len(),
+                self.args.join(" ")
+            );
+        }
+        let args = self
+            .args
+            .iter()
+            .zip(params.iter())
+            .map(|(arg, ty)| parse_value(arg, ty))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut samples = Vec::with_capacity(self.iterations);
+        let mut result = Box::default();
+        for _ in 0..self.iterations {
+            let start = Instant::now();
+            result = func.call(&mut store, &args)?;
+            samples.push(start.elapsed());
+        }
+
+        Ok(BackendReport {
+            compiler: compiler.to_string(),
+            compilation,
+            instantiation,
+            execution: ExecutionStats::new(samples),
+            result: result
+                .iter()
+                .map(format_value)
+                .collect::<Vec<_>>()
+                .join(" "),
+        })
+    }
+
+    #[cfg(feature = "wasi")]
+    fn instantiate(&self, store: &mut Store, module: &Module) -> Result<Instance> {
+        if wasmer_wasi::get_wasi_versions(module, false).is_some() {
+            let program_name = self
+                .path
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let (instance, _env) =
+                wasmer_wasi::WasiEnv::builder(program_name).instantiate(module.clone(), store)?;
+            return Ok(instance);
+        }
+        Ok(Instance::new(store, module, &imports! {})?)
+    }
+
+    #[cfg(not(feature = "wasi"))]
+    fn instantiate(&self, store: &mut Store, module: &Module) -> Result<Instance> {
+        Ok(Instance::new(store, module, &imports! {})?)
+    }
+}
+
+fn print_reports(reports: &[BackendReport]) {
+    use prettytable::{format, row, Table};
+    let rows = reports
+        .iter()
+        .map(|report| {
+            let execution = &report.execution;
+            row![
+                report.compiler,
+                format!("{:?}", report.compilation),
+                format!("{:?}", report.instantiation),
+                format!("{:?}", execution.mean),
+                format!("{:?}", execution.median),
+                format!("{:?}", execution.min),
+                format!("{:?}", execution.max),
+                format!("{:?}", execution.std_dev),
+                report.result,
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let mut table = Table::init(rows);
+    table.set_titles(row![
+        "Backend",
+        "Compile",
+        "Instantiate",
+        "Mean",
+        "Median",
+        "Min",
+        "Max",
+        "Std dev",
+        "Result"
+    ]);
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_format(*format::consts::FORMAT_NO_COLSEP);
+    table.printstd();
+}
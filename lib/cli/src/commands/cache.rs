@@ -1,44 +1,257 @@
 use crate::common::get_cache_dir;
+#[cfg(feature = "cache")]
+use crate::common::get_compiler_cache;
+#[cfg(feature = "cache")]
+use crate::store::StoreOptions;
+use crate::utils::parse_duration;
 use anyhow::{Context, Result};
+use bytesize::ByteSize;
 use clap::Parser;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "cache")]
+use wasmer::Module;
+#[cfg(feature = "cache")]
+use wasmer_cache::{Cache as _, Hash};
 
 #[derive(Debug, Parser)]
 /// The options for the `wasmer cache` subcommand
 pub enum Cache {
     /// Clear the cache
     #[clap(name = "clean")]
-    Clean,
+    Clean(Clean),
 
     /// Display the location of the cache
     #[clap(name = "dir")]
     Dir,
+
+    /// List the cached modules
+    #[clap(name = "ls")]
+    Ls,
+
+    /// Compile modules and store them in the cache, so that running
+    /// them later doesn't pay the compilation time
+    #[cfg(feature = "cache")]
+    #[clap(name = "precompile")]
+    Precompile(Precompile),
+}
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer cache clean` subcommand
+pub struct Clean {
+    /// Only remove the entries that were last written longer ago
+    /// than this duration, e.g. `30m` or `7d`
+    #[clap(long = "older-than", parse(try_from_str = parse_duration))]
+    older_than: Option<Duration>,
+}
+
+#[cfg(feature = "cache")]
+#[derive(Debug, Parser)]
+/// The options for the `wasmer cache precompile` subcommand
+pub struct Precompile {
+    /// The modules to compile
+    #[clap(name = "FILES", parse(from_os_str), required = true)]
+    paths: Vec<PathBuf>,
+
+    #[clap(flatten)]
+    store: StoreOptions,
 }
 
 impl Cache {
     /// Execute the cache command
     pub fn execute(&self) -> Result<()> {
         match &self {
-            Cache::Clean => {
-                self.clean().context("failed to clean wasmer cache.")?;
+            Cache::Clean(clean) => {
+                clean.execute().context("failed to clean wasmer cache.")?;
             }
             Cache::Dir => {
                 self.dir()?;
             }
+            Cache::Ls => {
+                self.ls().context("failed to list wasmer cache.")?;
+            }
+            #[cfg(feature = "cache")]
+            Cache::Precompile(precompile) => {
+                precompile
+                    .execute()
+                    .context("failed to precompile the modules.")?;
+            }
         }
         Ok(())
     }
-    fn clean(&self) -> Result<()> {
+    fn dir(&self) -> Result<()> {
+        println!("{}", get_cache_dir().to_string_lossy());
+        Ok(())
+    }
+    fn ls(&self) -> Result<()> {
+        use prettytable::{format, row, Table};
+        let now = SystemTime::now();
+        let rows = cache_entries(&get_cache_dir())?
+            .into_iter()
+            .map(|entry| {
+                row![
+                    entry.key,
+                    ByteSize(entry.size),
+                    entry.backend,
+                    format_age(entry.age(now))
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let empty_table = rows.is_empty();
+        let mut table = Table::init(rows);
+        table.set_titles(row!["Key", "Size", "Backend", "Age"]);
+        table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+        table.set_format(*format::consts::FORMAT_NO_COLSEP);
+        if empty_table {
+            table.add_empty_row();
+        }
+        table.printstd();
+        Ok(())
+    }
+}
+
+impl Clean {
+    fn execute(&self) -> Result<()> {
         let cache_dir = get_cache_dir();
-        if cache_dir.exists() {
-            fs::remove_dir_all(cache_dir.clone())?;
+        match self.older_than {
+            None => {
+                if cache_dir.exists() {
+                    fs::remove_dir_all(cache_dir.clone())?;
+                }
+                fs::create_dir_all(cache_dir)?;
+                eprintln!("Wasmer cache cleaned successfully.");
+            }
+            Some(older_than) => {
+                let now = SystemTime::now();
+                let mut removed = 0;
+                for entry in cache_entries(&cache_dir)? {
+                    if entry.age(now) > older_than {
+                        fs::remove_file(&entry.path).with_context(|| {
+                            format!("failed to remove `{}`", entry.path.display())
+                        })?;
+                        removed += 1;
+                    }
+                }
+                eprintln!("Removed {} entries from the Wasmer cache.", removed);
+            }
         }
-        fs::create_dir_all(cache_dir)?;
-        eprintln!("Wasmer cache cleaned successfully.");
         Ok(())
     }
-    fn dir(&self) -> Result<()> {
-        println!("{}", get_cache_dir().to_string_lossy());
+}
+
+/// A module stored in the cache directory.
+struct CacheEntry {
+    path: PathBuf,
+    key: String,
+    size: u64,
+    backend: String,
+    modified: SystemTime,
+}
+
+impl CacheEntry {
+    fn age(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.modified).unwrap_or_default()
+    }
+}
+
+/// Collect the cached modules, which live in one subdirectory of the
+/// cache directory per backend.
+fn cache_entries(cache_dir: &Path) -> Result<Vec<CacheEntry>> {
+    let mut entries = Vec::new();
+    if !cache_dir.exists() {
+        return Ok(entries);
+    }
+    for backend_dir in fs::read_dir(cache_dir)? {
+        let backend_dir = backend_dir?;
+        if !backend_dir.file_type()?.is_dir() {
+            continue;
+        }
+        let backend = backend_dir.file_name().to_string_lossy().into_owned();
+        for file in fs::read_dir(backend_dir.path())? {
+            let file = file?;
+            let metadata = file.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let path = file.path();
+            let key = path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            entries.push(CacheEntry {
+                key,
+                size: metadata.len(),
+                backend: backend.clone(),
+                modified: metadata.modified()?,
+                path,
+            });
+        }
+    }
+    entries.sort_by(|a, b| b.modified.cmp(&a.modified));
+    Ok(entries)
+}
+
+/// Format a duration with its largest whole unit, e.g. `3d` or `12m`.
+fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    match seconds {
+        s if s >= 7 * 24 * 60 * 60 => format!("{}w", s / (7 * 24 * 60 * 60)),
+        s if s >= 24 * 60 * 60 => format!("{}d", s / (24 * 60 * 60)),
+        s if s >= 60 * 60 => format!("{}h", s / (60 * 60)),
+        s if s >= 60 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+#[cfg(feature = "cache")]
+impl Precompile {
+    fn execute(&self) -> Result<()> {
+        let (store, compiler_type) = self.store.get_store()?;
+        let mut cache = get_compiler_cache(&compiler_type)?;
+        for path in &self.paths {
+            let contents =
+                fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+            let module = Module::new(&store, &contents)
+                .with_context(|| format!("failed to compile `{}`", path.display()))?;
+            let hash = Hash::generate_for_engine(&store, &contents);
+            cache.store(hash, &module)?;
+            eprintln!(
+                "Precompiled `{}` with {} ({}).",
+                path.display(),
+                compiler_type.to_string(),
+                hash.to_string()
+            );
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::cache_entries;
+    use std::fs;
+
+    #[test]
+    fn test_cache_entries() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        assert!(cache_entries(&cache_dir.path().join("missing"))
+            .unwrap()
+            .is_empty());
+
+        let backend_dir = cache_dir.path().join("cranelift");
+        fs::create_dir(&backend_dir).unwrap();
+        fs::create_dir(backend_dir.join("nested")).unwrap();
+        fs::write(backend_dir.join("0123abcd.wasmu"), [0; 16]).unwrap();
+        fs::write(cache_dir.path().join("stray-file"), [0; 4]).unwrap();
+
+        let entries = cache_entries(cache_dir.path()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key, "0123abcd");
+        assert_eq!(entries[0].size, 16);
+        assert_eq!(entries[0].backend, "cranelift");
+        assert_eq!(entries[0].path, backend_dir.join("0123abcd.wasmu"));
+    }
+}
@@ -0,0 +1,20 @@
+use clap::Parser;
+use clap_complete::Shell;
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer completions` subcommand
+pub struct Completions {
+    /// The shell to generate the completions for
+    #[clap(name = "SHELL", value_enum)]
+    shell: Shell,
+}
+
+impl Completions {
+    /// Execute `wasmer completions`, printing the completion script of
+    /// `cmd` for the requested shell
+    pub fn execute(&self, cmd: &mut clap::Command) -> Result<(), anyhow::Error> {
+        let name = cmd.get_name().to_string();
+        clap_complete::generate(self.shell, cmd, name, &mut std::io::stdout());
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::fmt::Write;
+use std::path::PathBuf;
+use wasmer::{CoreDump, CoreDumpValue, WASM_PAGE_SIZE};
+
+/// The zero bytes after which the data of a memory is split in another
+/// segment.
+const MAX_ZERO_RUN: usize = 32;
+
+/// The maximum size of the printed data segments.
+const MAX_SEGMENT_SIZE: usize = 64;
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer coredump` subcommand
+pub struct Coredump {
+    /// Core dump to examine, as written by `wasmer run --coredump-on-trap`
+    #[clap(name = "FILE", parse(from_os_str))]
+    path: PathBuf,
+
+    /// Also print the non-zero bytes of the memories, as data segments
+    #[clap(long)]
+    data: bool,
+}
+
+impl Coredump {
+    /// Runs logic for the `coredump` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context(format!("failed to examine `{}`", self.path.display()))
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        let contents = std::fs::read(&self.path)?;
+        let dump = CoreDump::deserialize(&contents)?;
+        print!("{}", to_wat(&dump, self.data));
+        Ok(())
+    }
+}
+
+/// Prints a core dump in the style of the WebAssembly text format.
+fn to_wat(dump: &CoreDump, data: bool) -> String {
+    let mut out = String::new();
+    writeln!(out, "(coredump").unwrap();
+    writeln!(out, "  (module {})", string(dump.module_name.as_bytes())).unwrap();
+    if let Some(hash) = &dump.module_hash {
+        writeln!(out, "  (hash {})", string(hash.as_bytes())).unwrap();
+    }
+    writeln!(out, "  (trap {})", string(dump.message.as_bytes())).unwrap();
+    for frame in &dump.frames {
+        let name = match &frame.function_name {
+            Some(name) => format!(" ${}", name),
+            None => String::new(),
+        };
+        writeln!(
+            out,
+            "  (frame (func {}{}) (offset 0x{:x}))",
+            frame.func_index, name, frame.module_offset
+        )
+        .unwrap();
+    }
+    for (index, global) in dump.globals.iter().enumerate() {
+        writeln!(out, "  (global {} {})", index, value(global)).unwrap();
+    }
+    for (index, memory) in dump.memories.iter().enumerate() {
+        writeln!(
+            out,
+            "  (memory {} (pages {}))",
+            index,
+            memory.len() / WASM_PAGE_SIZE
+        )
+        .unwrap();
+    }
+    if data {
+        for (index, memory) in dump.memories.iter().enumerate() {
+            for (offset, bytes) in segments(memory) {
+                writeln!(
+                    out,
+                    "  (data (memory {}) (i32.const {}) {})",
+                    index,
+                    offset,
+                    string(bytes)
+                )
+                .unwrap();
+            }
+        }
+    }
+    writeln!(out, ")").unwrap();
+    out
+}
+
+/// Prints a value as a constant instruction.
+fn value(value: &CoreDumpValue) -> String {
+    match *value {
+        CoreDumpValue::I32(value) => format!("(i32.const {})", value),
+        CoreDumpValue::I64(value) => format!("(i64.const {})", value),
+        CoreDumpValue::F32(value) => format!("(f32.const {})", float(value)),
+        CoreDumpValue::F64(value) => format!("(f64.const {})", float(value)),
+        CoreDumpValue::V128(value) => format!(
+            "(v128.const i32x4 0x{:08x} 0x{:08x} 0x{:08x} 0x{:08x})",
+            value as u32,
+            (value >> 32) as u32,
+            (value >> 64) as u32,
+            (value >> 96) as u32
+        ),
+        CoreDumpValue::FuncRef(false) => "(ref.null func)".to_string(),
+        CoreDumpValue::FuncRef(true) => "(ref func)".to_string(),
+        CoreDumpValue::ExternRef(false) => "(ref.null extern)".to_string(),
+        CoreDumpValue::ExternRef(true) => "(ref extern)".to_string(),
+    }
+}
+
+/// Prints a float the way the text format reads it.
+fn float<T: Into<f64> + ToString + Copy>(value: T) -> String {
+    let wide = value.into();
+    if wide.is_nan() {
+        "nan".to_string()
+    } else if wide.is_infinite() {
+        if wide > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Prints bytes as a string of the text format.
+fn string(bytes: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for byte in bytes {
+        match byte {
+            b'"' | b'\\' => write!(out, "\\{}", *byte as char).unwrap(),
+            0x20..=0x7e => out.push(*byte as char),
+            _ => write!(out, "\\{:02x}", byte).unwrap(),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Splits the non-zero parts of a memory into segments, with their
+/// offsets.
+fn segments(memory: &[u8]) -> Vec<(usize, &[u8])> {
+    let mut segments = Vec::new();
+    let mut offset = 0;
+    while offset < memory.len() {
+        let start = match memory[offset..].iter().position(|byte| *byte != 0) {
+            Some(position) => offset + position,
+            None => break,
+        };
+        // The segment ends at the first long enough run of zeros.
+        let mut end = start;
+        let mut zeros = 0;
+        while end < memory.len() && end - start < MAX_SEGMENT_SIZE && zeros < MAX_ZERO_RUN {
+            zeros = if memory[end] == 0 { zeros + 1 } else { 0 };
+            end += 1;
+        }
+        segments.push((start, &memory[start..end - zeros]));
+        offset = end;
+    }
+    segments
+}
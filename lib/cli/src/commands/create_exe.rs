@@ -17,7 +17,7 @@ use tar::Archive;
 use wasmer::*;
 use wasmer_object::{emit_serialized, get_object_for_target};
 use wasmer_types::compilation::symbols::ModuleMetadataSymbolRegistry;
-use wasmer_types::{ModuleInfo, SymbolRegistry};
+use wasmer_types::{MetadataHeader, ModuleInfo, SerializableModule, SymbolRegistry};
 use webc::{ParseOptions, WebCMmap};
 
 const LINK_SYSTEM_LIBRARIES_WINDOWS: &[&str] = &["userenv", "Ws2_32", "advapi32", "bcrypt"];
@@ -203,7 +203,17 @@ impl CreateExe {
         let starting_cd = env::current_dir()?;
         let input_path = starting_cd.join(&path);
         let output_path = starting_cd.join(&self.output);
-        let object_format = self.object_format.unwrap_or_default();
+        let object_format = match self.object_format {
+            Some(object_format) => object_format,
+            // Precompiled modules can only be embedded serialized
+            None if std::fs::read(&input_path)
+                .map(|bytes| Artifact::is_deserializable(&bytes))
+                .unwrap_or(false) =>
+            {
+                ObjectFormat::Serialized
+            }
+            None => ObjectFormat::default(),
+        };
 
         let url_or_version = match self
             .use_wasmer_release
@@ -752,6 +762,11 @@ fn compile_atoms(
         }
         let (store, _) = compiler.get_store_for_target(target.clone())?;
         match object_format {
+            ObjectFormat::Symbols if Artifact::is_deserializable(data) => {
+                anyhow::bail!(
+                    "atom {a:?} is precompiled, it can only be linked with the `serialized` object format"
+                );
+            }
             ObjectFormat::Symbols => {
                 let engine = store.engine();
                 let engine_inner = engine.inner();
@@ -778,8 +793,14 @@ fn compile_atoms(
                     prefix: prefix.clone(),
                 }
                 .symbol_to_name(wasmer_types::Symbol::Metadata);
-                let module = Module::from_binary(&store, data).context("failed to compile Wasm")?;
-                let bytes = module.serialize()?;
+                // Precompiled artifacts are embedded as they are
+                let bytes = if Artifact::is_deserializable(data) {
+                    data.to_vec()
+                } else {
+                    let module =
+                        Module::from_binary(&store, data).context("failed to compile Wasm")?;
+                    module.serialize()?.to_vec()
+                };
                 let mut obj = get_object_for_target(target.triple())?;
                 emit_serialized(&mut obj, &bytes, target.triple(), &module_name)?;
 
@@ -895,6 +916,12 @@ pub(super) fn prepare_directory_from_single_wasm_file(
     debug: bool,
 ) -> anyhow::Result<Vec<(String, Vec<u8>)>, anyhow::Error> {
     let bytes = std::fs::read(wasm_file)?;
+    #[cfg(feature = "wat")]
+    let bytes = if is_wasm(&bytes) || Artifact::is_deserializable(&bytes) {
+        bytes
+    } else {
+        wat2wasm(&bytes)?.into_owned()
+    };
     let target = &utils::target_triple_to_target(triple, cpu_features);
 
     std::fs::create_dir_all(target_dir)
@@ -960,6 +987,19 @@ pub(super) fn prepare_directory_from_single_wasm_file(
     Ok(all_files)
 }
 
+/// Get the module info of a Wasm module or of a precompiled artifact.
+fn get_module_info(bytes: &[u8]) -> Result<ModuleInfo, anyhow::Error> {
+    if Artifact::is_deserializable(bytes) {
+        let bytes = &bytes[wasmer_compiler::ArtifactBuild::MAGIC_HEADER.len()..];
+        let metadata = MetadataHeader::parse_checked(bytes)?;
+        Ok(SerializableModule::deserialize(metadata)?
+            .compile_info
+            .module)
+    } else {
+        Ok(Engine::get_module_info(bytes)?)
+    }
+}
+
 // Given the input file paths, correctly resolves the .wasm files,
 // reads the module info from the wasm module and writes the ModuleInfo for each file
 // into the entrypoint.json file
@@ -973,7 +1013,7 @@ fn get_module_infos(
 
     let mut module_infos = BTreeMap::new();
     for (atom_name, atom_bytes) in atoms {
-        let module_info = Engine::get_module_info(atom_bytes.as_slice())
+        let module_info = get_module_info(atom_bytes.as_slice())
             .map_err(|e| anyhow::anyhow!("could not get module info for atom {atom_name}: {e}"))?;
 
         if let Some(s) = entrypoint
@@ -111,7 +111,7 @@ impl GenCHeader {
             .serialize()
             .map_err(|e| anyhow::anyhow!("failed to serialize: {e}"))?;
         let mut metadata_binary = vec![];
-        metadata_binary.extend(MetadataHeader::new(serialized_data.len()).into_bytes());
+        metadata_binary.extend(MetadataHeader::new(&serialized_data).into_bytes());
         metadata_binary.extend(serialized_data);
         let metadata_length = metadata_binary.len();
 
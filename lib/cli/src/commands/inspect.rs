@@ -1,69 +1,279 @@
+use crate::commands::OutputFormat;
+#[cfg(feature = "compiler")]
+use crate::common::{set_wasm_proposal, WASM_PROPOSALS};
 use crate::store::StoreOptions;
 use anyhow::{Context, Result};
 use bytesize::ByteSize;
 use clap::Parser;
+use serde::Serialize;
 use std::path::PathBuf;
 use wasmer::*;
+use wasmer_compiler::ArtifactCreate;
+use wasmer_types::MetadataHeader;
 
 #[derive(Debug, Parser)]
-/// The options for the `wasmer validate` subcommand
+/// The options for the `wasmer inspect` subcommand
 pub struct Inspect {
-    /// File to validate as WebAssembly
+    /// File to inspect
     #[clap(name = "FILE", parse(from_os_str))]
     path: PathBuf,
 
+    /// The format of the report: `text` or `json`
+    #[clap(long = "format", default_value = "text")]
+    format: OutputFormat,
+
     #[clap(flatten)]
     store: StoreOptions,
 }
 
+/// What `wasmer inspect` reports about a module.
+#[derive(Debug, Serialize)]
+struct Report {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    artifact: Option<ArtifactReport>,
+    imports: Vec<ImportReport>,
+    exports: Vec<ExportReport>,
+    memories: Vec<String>,
+    tables: Vec<String>,
+    globals: Vec<String>,
+    custom_sections: Vec<String>,
+    /// `None` when the features can't be detected, e.g. for precompiled
+    /// artifacts.
+    features: Option<Vec<&'static str>>,
+    wasi_versions: Vec<&'static str>,
+}
+
+/// The details of a precompiled (`.wasmu`) artifact.
+#[derive(Debug, Serialize)]
+struct ArtifactReport {
+    format_version: u32,
+    compiler: String,
+    cpu_features: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ImportReport {
+    module: String,
+    name: String,
+    kind: &'static str,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportReport {
+    name: String,
+    kind: &'static str,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
 impl Inspect {
-    /// Runs logic for the `validate` subcommand
+    /// Runs logic for the `inspect` subcommand
     pub fn execute(&self) -> Result<()> {
         self.inner_execute()
             .context(format!("failed to inspect `{}`", self.path.display()))
     }
     fn inner_execute(&self) -> Result<()> {
-        let (store, _compiler_type) = self.store.get_store()?;
         let module_contents = std::fs::read(&self.path)?;
-        let iswasm = is_wasm(&module_contents);
-        let module_len = module_contents.len();
-        let module = Module::new(&store, module_contents)?;
-        println!("Type: {}", if !iswasm { "wat" } else { "wasm" });
-        println!("Size: {}", ByteSize(module_len as _));
-        println!("Imports:");
-        println!("  Functions:");
-        for f in module.imports().functions() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
-        }
-        println!("  Memories:");
-        for f in module.imports().memories() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
-        }
-        println!("  Tables:");
-        for f in module.imports().tables() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
-        }
-        println!("  Globals:");
-        for f in module.imports().globals() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
-        }
-        println!("Exports:");
-        println!("  Functions:");
-        for f in module.exports().functions() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+        let size = module_contents.len();
+        let (module, kind, artifact, features) =
+            if wasmer_compiler::Artifact::is_deserializable(&module_contents) {
+                let engine = wasmer_compiler::EngineBuilder::headless().engine();
+                let module = unsafe { Module::deserialize(&engine, module_contents)? };
+                let artifact = ArtifactReport {
+                    format_version: MetadataHeader::CURRENT_VERSION,
+                    compiler: module.artifact().compiler().to_string(),
+                    cpu_features: module
+                        .artifact()
+                        .cpu_features()
+                        .iter()
+                        .map(|feature| feature.to_string())
+                        .collect(),
+                };
+                (module, "wasmu", Some(artifact), None)
+            } else {
+                let (store, _compiler_type) = self.store.get_store()?;
+                let kind = if is_wasm(&module_contents) {
+                    "wasm"
+                } else {
+                    "wat"
+                };
+                let module = Module::new(&store, &module_contents)?;
+                let features = required_features(&store, &module_contents);
+                (module, kind, None, features)
+            };
+
+        let report = Report {
+            kind,
+            size,
+            artifact,
+            imports: module
+                .imports()
+                .map(|import| {
+                    let (kind, ty) = describe(import.ty());
+                    ImportReport {
+                        module: import.module().to_string(),
+                        name: import.name().to_string(),
+                        kind,
+                        ty,
+                    }
+                })
+                .collect(),
+            exports: module
+                .exports()
+                .map(|export| {
+                    let (kind, ty) = describe(export.ty());
+                    ExportReport {
+                        name: export.name().to_string(),
+                        kind,
+                        ty,
+                    }
+                })
+                .collect(),
+            memories: defined(&module.info().memories, module.info().num_imported_memories),
+            tables: defined(&module.info().tables, module.info().num_imported_tables),
+            globals: defined(&module.info().globals, module.info().num_imported_globals),
+            custom_sections: module.info().custom_sections.keys().cloned().collect(),
+            features,
+            wasi_versions: wasi_versions(&module),
+        };
+
+        if self.format == OutputFormat::Json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print_report(&report);
         }
-        println!("  Memories:");
-        for f in module.exports().memories() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+        Ok(())
+    }
+}
+
+/// Describe an extern type as its kind and its signature.
+fn describe(ty: &ExternType) -> (&'static str, String) {
+    match ty {
+        ExternType::Function(ty) => ("function", ty.to_string()),
+        ExternType::Global(ty) => ("global", ty.to_string()),
+        ExternType::Table(ty) => ("table", ty.to_string()),
+        ExternType::Memory(ty) => ("memory", ty.to_string()),
+    }
+}
+
+/// Describe the entities defined by the module, skipping the imported ones.
+fn defined<K, V>(
+    entities: &wasmer_types::entity::PrimaryMap<K, V>,
+    num_imported: usize,
+) -> Vec<String>
+where
+    K: wasmer_types::entity::EntityRef,
+    V: ToString,
+{
+    entities
+        .values()
+        .skip(num_imported)
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Detect the WebAssembly proposals the module relies on, by validating it
+/// again with each of them disabled in turn.
+#[cfg(feature = "compiler")]
+fn required_features(store: &Store, bytes: &[u8]) -> Option<Vec<&'static str>> {
+    let engine = store.engine().inner();
+    let compiler = engine.compiler().ok()?;
+    #[cfg(feature = "wat")]
+    let bytes = wat2wasm(bytes).ok()?;
+    let mut all = Features::new();
+    for proposal in WASM_PROPOSALS {
+        set_wasm_proposal(&mut all, proposal, true).ok()?;
+    }
+    compiler.validate_module(&all, &bytes).ok()?;
+
+    Some(
+        WASM_PROPOSALS
+            .iter()
+            .copied()
+            .filter(|proposal| {
+                let mut features = all.clone();
+                set_wasm_proposal(&mut features, proposal, false).is_ok()
+                    && compiler.validate_module(&features, &bytes).is_err()
+            })
+            .collect(),
+    )
+}
+
+#[cfg(not(feature = "compiler"))]
+fn required_features(_store: &Store, _bytes: &[u8]) -> Option<Vec<&'static str>> {
+    None
+}
+
+#[cfg(feature = "wasi")]
+fn wasi_versions(module: &Module) -> Vec<&'static str> {
+    wasmer_wasi::get_wasi_versions(module, false)
+        .unwrap_or_default()
+        .iter()
+        .map(|version| version.get_namespace_str())
+        .collect()
+}
+
+#[cfg(not(feature = "wasi"))]
+fn wasi_versions(_module: &Module) -> Vec<&'static str> {
+    Vec::new()
+}
+
+fn print_report(report: &Report) {
+    println!("Type: {}", report.kind);
+    println!("Size: {}", ByteSize(report.size as _));
+    if let Some(artifact) = &report.artifact {
+        println!("Format version: {}", artifact.format_version);
+        println!("Compiler: {}", artifact.compiler);
+        println!("CPU features: {}", artifact.cpu_features.join(", "));
+    }
+    println!("Imports:");
+    for (title, kind) in KINDS {
+        println!("  {}:", title);
+        for import in report.imports.iter().filter(|import| import.kind == kind) {
+            println!(
+                "    \"{}\".\"{}\": {}",
+                import.module, import.name, import.ty
+            );
         }
-        println!("  Tables:");
-        for f in module.exports().tables() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+    }
+    println!("Exports:");
+    for (title, kind) in KINDS {
+        println!("  {}:", title);
+        for export in report.exports.iter().filter(|export| export.kind == kind) {
+            println!("    \"{}\": {}", export.name, export.ty);
         }
-        println!("  Globals:");
-        for f in module.exports().globals() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+    }
+    for (title, entities) in [
+        ("Memories", &report.memories),
+        ("Tables", &report.tables),
+        ("Globals", &report.globals),
+        ("Custom sections", &report.custom_sections),
+    ] {
+        println!("{}:", title);
+        for entity in entities {
+            println!("  {}", entity);
         }
-        Ok(())
+    }
+    match &report.features {
+        Some(features) if features.is_empty() => println!("Features: none"),
+        Some(features) => println!("Features: {}", features.join(", ")),
+        None => println!("Features: unknown"),
+    }
+    if report.wasi_versions.is_empty() {
+        println!("WASI: none");
+    } else {
+        println!("WASI: {}", report.wasi_versions.join(", "));
     }
 }
+
+const KINDS: [(&str, &str); 4] = [
+    ("Functions", "function"),
+    ("Memories", "memory"),
+    ("Tables", "table"),
+    ("Globals", "global"),
+];
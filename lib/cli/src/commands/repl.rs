@@ -0,0 +1,253 @@
+use super::run::invoke::{format_value, parse_value};
+use crate::store::StoreOptions;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use wasmer::*;
+
+#[derive(Debug, Parser)]
+/// The options for the `wasmer repl` subcommand
+pub struct Repl {
+    /// The module to instantiate
+    #[clap(name = "FILE", parse(from_os_str))]
+    path: PathBuf,
+
+    #[clap(flatten)]
+    store: StoreOptions,
+
+    /// The arguments passed to WASI modules
+    #[clap(value_name = "ARGS")]
+    args: Vec<String>,
+}
+
+const HELP: &str = "\
+Commands:
+  <function> [ARGS...]             call an exported function
+  memory [NAME] <OFFSET> <LENGTH>  dump a range of an exported memory
+  global <NAME>                    read an exported global
+  exports                          list the exports of the instance
+  help                             print this message
+  quit                             exit the REPL";
+
+/// The state kept between the commands of the REPL.
+struct Session {
+    store: Store,
+    instance: Instance,
+}
+
+impl Repl {
+    /// Runs logic for the `repl` subcommand
+    pub fn execute(&self) -> Result<()> {
+        let mut session = self
+            .instantiate()
+            .context(format!("failed to instantiate `{}`", self.path.display()))?;
+
+        eprintln!(
+            "Instantiated `{}`, type `help` for the list of commands.",
+            self.path.display()
+        );
+        let stdin = std::io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            eprint!("> ");
+            std::io::stderr().flush()?;
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => break,
+            };
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let result = match words.as_slice() {
+                [] => continue,
+                ["quit"] | ["exit"] => break,
+                ["help"] => {
+                    println!("{}", HELP);
+                    Ok(())
+                }
+                ["exports"] => {
+                    session.print_exports();
+                    Ok(())
+                }
+                ["memory", args @ ..] => session.dump_memory(args),
+                ["global", name] => session.read_global(name),
+                [name, args @ ..] => session.call(name, args),
+            };
+            // Errors don't end the session, the instance can still be used
+            if let Err(error) = result {
+                eprintln!("error: {:#}", error);
+            }
+        }
+        Ok(())
+    }
+
+    fn instantiate(&self) -> Result<Session> {
+        let (mut store, _compiler_type) = self.store.get_store()?;
+        let contents = std::fs::read(&self.path)?;
+        let module = Module::new(&store, contents)?;
+        let instance = self.instantiate_module(&mut store, &module)?;
+        Ok(Session { store, instance })
+    }
+
+    #[cfg(feature = "wasi")]
+    fn instantiate_module(&self, store: &mut Store, module: &Module) -> Result<Instance> {
+        if wasmer_wasi::get_wasi_versions(module, false).is_some() {
+            let program_name = self
+                .path
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let (instance, _env) = wasmer_wasi::WasiEnv::builder(program_name)
+                .args(&self.args)
+                .instantiate(module.clone(), store)?;
+            return Ok(instance);
+        }
+        Ok(Instance::new(store, module, &imports! {})?)
+    }
+
+    #[cfg(not(feature = "wasi"))]
+    fn instantiate_module(&self, store: &mut Store, module: &Module) -> Result<Instance> {
+        Ok(Instance::new(store, module, &imports! {})?)
+    }
+}
+
+impl Session {
+    fn call(&mut self, name: &str, args: &[&str]) -> Result<()> {
+        let func = self
+            .instance
+            .exports
+            .get_function(name)
+            .map_err(|e| anyhow!("can't call `{}`: {}", name, e))?
+            .clone();
+        let params = func.ty(&self.store).params().to_vec();
+        if params.len() != args.len() {
+            bail!(
+                "`{}` expects {} arguments, but received {}",
+                name,
+                params.len(),
+                args.len()
+            );
+        }
+        let args = args
+            .iter()
+            .zip(params.iter())
+            .map(|(arg, ty)| parse_value(arg, ty))
+            .collect::<Result<Vec<_>>>()?;
+        let result = func.call(&mut self.store, &args)?;
+        println!(
+            "{}",
+            result
+                .iter()
+                .map(format_value)
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        Ok(())
+    }
+
+    /// Dump a range of a memory, the only memory of the instance if no
+    /// name is given.
+    fn dump_memory(&self, args: &[&str]) -> Result<()> {
+        let (memory, offset, length) = match args {
+            [offset, length] => {
+                let mut memories = self.instance.exports.iter().memories();
+                match (memories.next(), memories.next()) {
+                    (Some((_, memory)), None) => (memory, offset, length),
+                    (None, _) => bail!("the instance exports no memory"),
+                    (Some(_), Some(_)) => {
+                        bail!("the instance exports several memories, pass the name of one")
+                    }
+                }
+            }
+            [name, offset, length] => (self.instance.exports.get_memory(name)?, offset, length),
+            _ => bail!("usage: memory [NAME] <OFFSET> <LENGTH>"),
+        };
+        let offset = parse_u64(offset)?;
+        let length = parse_u64(length)?;
+        let view = memory.view(&self.store);
+        let size = view.data_size();
+        match offset.checked_add(length) {
+            Some(end) if end <= size => {}
+            _ => bail!(
+                "can't read {} bytes at {:#x}: the memory is {:#x} bytes long",
+                length,
+                offset,
+                size
+            ),
+        }
+        let mut bytes = vec![0; length as usize];
+        view.read(offset, &mut bytes)
+            .map_err(|e| anyhow!("can't read {} bytes at {:#x}: {}", length, offset, e))?;
+        for (i, chunk) in bytes.chunks(16).enumerate() {
+            println!("{}", hexdump_line(offset + 16 * i as u64, chunk));
+        }
+        Ok(())
+    }
+
+    fn read_global(&mut self, name: &str) -> Result<()> {
+        let global = self.instance.exports.get_global(name)?;
+        println!("{}", format_value(&global.get(&mut self.store)));
+        Ok(())
+    }
+
+    fn print_exports(&self) {
+        for (name, export) in self.instance.exports.iter() {
+            let ty = match export {
+                Extern::Function(f) => format!("function {}", f.ty(&self.store)),
+                Extern::Global(g) => format!("global {}", g.ty(&self.store)),
+                Extern::Memory(m) => format!("memory {}", m.ty(&self.store)),
+                Extern::Table(t) => format!("table {}", t.ty(&self.store)),
+            };
+            println!("{}: {}", name, ty);
+        }
+    }
+}
+
+/// Parse an offset or a length, in decimal or in hexadecimal with a `0x`
+/// prefix.
+fn parse_u64(arg: &str) -> Result<u64> {
+    match arg.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => arg.parse(),
+    }
+    .map_err(|_| anyhow!("`{}` is not a valid offset or length", arg))
+}
+
+/// Format up to 16 bytes as in `hexdump -C`.
+fn hexdump_line(offset: u64, bytes: &[u8]) -> String {
+    let hex = bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let ascii = bytes
+        .iter()
+        .map(|&byte| {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            }
+        })
+        .collect::<String>();
+    format!("{:08x}  {:<47}  |{}|", offset, hex, ascii)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hexdump_line, parse_u64};
+
+    #[test]
+    fn test_parse_u64() {
+        assert_eq!(parse_u64("16").unwrap(), 16);
+        assert_eq!(parse_u64("0x10").unwrap(), 16);
+        assert!(parse_u64("-1").is_err());
+    }
+
+    #[test]
+    fn test_hexdump_line() {
+        assert_eq!(
+            hexdump_line(0x20, b"hello\0"),
+            "00000020  68 65 6c 6c 6f 00                                |hello.|"
+        );
+    }
+}
@@ -1,5 +1,7 @@
+use crate::commands::OutputFormat;
 #[cfg(feature = "cache")]
-use crate::common::get_cache_dir;
+use crate::common::get_compiler_cache;
+use crate::error::PrettyError;
 #[cfg(feature = "debug")]
 use crate::logging;
 use crate::package_source::PackageSource;
@@ -8,22 +10,32 @@ use crate::suggestions::suggest_function_exports;
 use crate::warning;
 use anyhow::{anyhow, Context, Result};
 use clap::Parser;
+use std::collections::BTreeMap;
 use std::ops::Deref;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 #[cfg(feature = "cache")]
 use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
 #[cfg(feature = "emscripten")]
 use wasmer::FunctionEnv;
 use wasmer::*;
 #[cfg(feature = "cache")]
-use wasmer_cache::{Cache, FileSystemCache, Hash};
-use wasmer_types::Type as ValueType;
+use wasmer_cache::{Cache, Hash};
 #[cfg(feature = "webc_runner")]
 use wasmer_wasi::runners::{Runner, WapmContainer};
 
+pub(crate) mod invoke;
+mod limits;
+mod replay;
+mod report;
 #[cfg(feature = "wasi")]
 mod wasi;
 
+use invoke::{format_value, parse_value};
+use limits::Limits;
+use replay::{HostCalls, RecordReplay};
+use report::RunReport;
+
 #[cfg(feature = "wasi")]
 use wasi::Wasi;
 
@@ -50,6 +62,23 @@ pub struct RunWithoutFile {
     #[clap(long = "disable-cache")]
     pub(crate) disable_cache: bool,
 
+    /// Start running the module compiled with Singlepass, while it gets
+    /// compiled with an optimizing compiler in the background; the
+    /// optimized module is cached and used by the next runs
+    #[cfg(all(feature = "cache", feature = "singlepass"))]
+    #[clap(long = "tiered")]
+    pub(crate) tiered: bool,
+
+    /// Run the module again each time it changes
+    #[clap(long = "watch")]
+    pub(crate) watch: bool,
+
+    /// With `--watch`, also run the module again when the content of the
+    /// directories mapped with `--dir` or `--mapdir` changes
+    #[cfg(feature = "wasi")]
+    #[clap(long = "watch-dirs", requires = "watch")]
+    pub(crate) watch_dirs: bool,
+
     /// Invoke a specified function
     #[clap(long = "invoke", short = 'i')]
     pub(crate) invoke: Option<String>,
@@ -70,6 +99,17 @@ pub struct RunWithoutFile {
     #[clap(flatten)]
     pub(crate) store: StoreOptions,
 
+    #[clap(flatten)]
+    pub(crate) limits: Limits,
+
+    #[clap(flatten)]
+    pub(crate) record_replay: RecordReplay,
+
+    /// Print a JSON report of the run (exit code, trap, timings) to stderr
+    /// when it ends, with `--format json`
+    #[clap(long = "format", default_value = "text")]
+    pub(crate) format: OutputFormat,
+
     // TODO: refactor WASI structure to allow shared options with Emscripten
     #[cfg(feature = "wasi")]
     #[clap(flatten)]
@@ -89,6 +129,16 @@ pub struct RunWithoutFile {
     #[clap(long = "verbose")]
     pub(crate) verbose: Option<u8>,
 
+    /// Write the names of the compiled functions to `/tmp/perf-<pid>.map`,
+    /// so that `perf report` attributes the samples to them (Linux only)
+    #[clap(long = "perf-map")]
+    pub(crate) perf_map: bool,
+
+    /// Write a core dump of the instance to this file when it traps, to
+    /// be examined with `wasmer coredump`
+    #[clap(long = "coredump-on-trap", parse(from_os_str))]
+    pub(crate) coredump_on_trap: Option<PathBuf>,
+
     /// Application arguments
     #[clap(value_name = "ARGS")]
     pub(crate) args: Vec<String>,
@@ -154,20 +204,157 @@ impl RunWithPathBuf {
         if self.debug {
             logging::set_up_logging(self_clone.verbose.unwrap_or(0)).unwrap();
         }
-        self_clone.inner_execute().with_context(|| {
+        if self_clone.perf_map {
+            wasmer_compiler::enable_perf_map();
+        }
+        if self_clone.watch {
+            return self_clone.watch();
+        }
+        let exit_code = self_clone.run()?;
+        if exit_code != 0 {
+            std::io::Write::flush(&mut std::io::stdout())?;
+            std::process::exit(exit_code);
+        }
+        Ok(())
+    }
+
+    /// Run the module once, returning the code to exit with.
+    fn run(&self) -> Result<i32> {
+        let mut report = RunReport::default();
+        let result = self.inner_execute(&mut report).with_context(|| {
             format!(
                 "failed to run `{}`{}",
-                self_clone.path.display(),
+                self.path.display(),
                 if CompilerType::enabled().is_empty() {
                     " (no compilers enabled)"
                 } else {
                     ""
                 }
             )
-        })
+        });
+        match self.format {
+            OutputFormat::Text => result.map(|()| report.exit_code),
+            OutputFormat::Json => {
+                // The report goes to stderr, so that it doesn't get mixed
+                // with the output of the module
+                if let Err(error) = &result {
+                    report.record_error(error);
+                }
+                eprintln!("{}", serde_json::to_string(&report)?);
+                Ok(report.exit_code)
+            }
+        }
+    }
+
+    /// Run the module each time the watched files change, until wasmer
+    /// is interrupted.
+    fn watch(&self) -> Result<()> {
+        const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+        let mut snapshot = self.watched_files();
+        loop {
+            match self.run() {
+                Ok(0) => {}
+                Ok(exit_code) => warning!("the module exited with code {}", exit_code),
+                Err(error) => PrettyError::print(error),
+            }
+            eprintln!("Watching `{}` for changes...", self.path.display());
+            // Wait for a change, then for the files to stop changing, so
+            // that a module that is still being written doesn't get run
+            let mut changed = false;
+            loop {
+                std::thread::sleep(POLL_INTERVAL);
+                let current = self.watched_files();
+                if current != snapshot {
+                    snapshot = current;
+                    changed = true;
+                } else if changed {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// The modification times of the module and, with `--watch-dirs`, of
+    /// the files in the mapped directories.
+    fn watched_files(&self) -> BTreeMap<PathBuf, Option<SystemTime>> {
+        let mut roots = vec![self.path.clone()];
+        #[cfg(feature = "wasi")]
+        {
+            if self.watch_dirs {
+                roots.extend(self.wasi.host_paths());
+            }
+        }
+        roots
+            .iter()
+            .flat_map(|root| walkdir::WalkDir::new(root).into_iter())
+            .filter_map(|entry| entry.ok())
+            .map(|entry| {
+                let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
+                (entry.into_path(), modified)
+            })
+            .collect()
+    }
+
+    fn inner_module_run(
+        &self,
+        store: &mut Store,
+        instance: Instance,
+        host_calls: &HostCalls,
+        report: &mut RunReport,
+    ) -> Result<()> {
+        host_calls.attach(&instance);
+        let start = Instant::now();
+        let result = self.call_module(store, &instance, host_calls, report);
+        report.timings.execution = Some(start.elapsed().as_secs_f64());
+        if let Err(log_error) = host_calls.finish() {
+            warning!("{:#}", log_error);
+        }
+        if let (Some(path), Err(error)) = (&self.coredump_on_trap, &result) {
+            if let Err(dump_error) = self.write_coredump(store, &instance, error, path) {
+                warning!("{:#}", dump_error);
+            }
+        }
+        self.limits.check_result(store, &instance, result)
+    }
+
+    /// Writes a core dump of `instance` to `path`, if `error` is a trap.
+    fn write_coredump(
+        &self,
+        store: &mut Store,
+        instance: &Instance,
+        error: &anyhow::Error,
+        path: &Path,
+    ) -> Result<()> {
+        use sha2::{Digest, Sha256};
+
+        let trap = error
+            .chain()
+            .find_map(|error| error.downcast_ref::<RuntimeError>())
+            .filter(|error| (*error).clone().to_trap().is_some());
+        let trap = match trap {
+            Some(trap) => trap,
+            None => return Ok(()),
+        };
+
+        let mut dump =
+            CoreDump::capture(store, instance, trap).context("failed to capture the core dump")?;
+        if let Ok(contents) = std::fs::read(&self.path) {
+            dump = dump.with_module_hash(hex::encode(Sha256::digest(&contents)));
+        }
+        std::fs::write(path, dump.serialize())
+            .with_context(|| format!("failed to write the core dump to `{}`", path.display()))?;
+        eprintln!("Core dump written to `{}`", path.display());
+        Ok(())
     }
 
-    fn inner_module_run(&self, store: &mut Store, instance: Instance) -> Result<()> {
+    fn call_module(
+        &self,
+        store: &mut Store,
+        instance: &Instance,
+        host_calls: &HostCalls,
+        report: &mut RunReport,
+    ) -> Result<()> {
         // If this module exports an _initialize function, run that first.
         if let Ok(initialize) = instance.exports.get_function("_initialize") {
             initialize
@@ -177,18 +364,29 @@ impl RunWithPathBuf {
 
         // Do we want to invoke a function?
         if let Some(ref invoke) = self.invoke {
-            let result = self.invoke_function(store, &instance, invoke, &self.args)?;
-            println!(
-                "{}",
-                result
-                    .iter()
-                    .map(|val| val.to_string())
-                    .collect::<Vec<String>>()
-                    .join(" ")
-            );
+            let result = self.invoke_function(store, instance, invoke, &self.args)?;
+            let result = result.iter().map(format_value).collect::<Vec<String>>();
+            match self.format {
+                OutputFormat::Text => println!("{}", result.join(" ")),
+                OutputFormat::Json => report.result = Some(result),
+            }
         } else {
-            let start: Function = self.try_find_function(&instance, "_start", &[])?;
+            let start: Function = self.try_find_function(instance, "_start", &[])?;
             let result = start.call(store, &[]);
+            // Exiting is left to the caller when the run is reported in
+            // JSON or watched, since it isn't the end of wasmer then
+            #[cfg(feature = "wasi")]
+            {
+                let exit_code = result.as_ref().err().and_then(Wasi::exit_code);
+                if let Some(exit_code) = exit_code {
+                    if self.format == OutputFormat::Json || self.watch {
+                        report.exit_code = exit_code as i32;
+                        return Ok(());
+                    }
+                }
+            }
+            // Exiting ends the process, so the log is written beforehand
+            host_calls.save_log()?;
             #[cfg(feature = "wasi")]
             self.wasi.handle_result(result)?;
             #[cfg(not(feature = "wasi"))]
@@ -198,7 +396,7 @@ impl RunWithPathBuf {
         Ok(())
     }
 
-    fn inner_execute(&self) -> Result<()> {
+    fn inner_execute(&self, report: &mut RunReport) -> Result<()> {
         #[cfg(feature = "webc_runner")]
         {
             if let Ok(pf) = WapmContainer::new(self.path.clone()) {
@@ -211,7 +409,10 @@ impl RunWithPathBuf {
                     .map_err(|e| anyhow!("Could not run PiritaFile: {e}"));
             }
         }
+        let host_calls = self.record_replay.host_calls()?;
+        let start = Instant::now();
         let (mut store, module) = self.get_store_module()?;
+        report.timings.compilation = Some(start.elapsed().as_secs_f64());
         #[cfg(feature = "emscripten")]
         {
             use wasmer_emscripten::{
@@ -220,17 +421,33 @@ impl RunWithPathBuf {
             };
             // TODO: refactor this
             if is_emscripten_module(&module) {
+                if !host_calls.is_live() {
+                    bail!("recording or replaying Emscripten modules isn't supported");
+                }
                 let em_env = EmEnv::new();
-                for (k, v) in self.wasi.env_vars.iter() {
+                for (k, v) in self.wasi.get_env_vars()?.iter() {
                     em_env.set_env_var(k, v);
                 }
+                em_env.set_networking(self.wasi.networking);
                 // create an EmEnv with default global
                 let env = FunctionEnv::new(&mut store, em_env);
                 let mut emscripten_globals = EmscriptenGlobals::new(&mut store, &env, &module)
                     .map_err(|e| anyhow!("{}", e))?;
+                // The module only sees the directories given with `--dir`
+                // and `--mapdir`
                 env.as_mut(&mut store).set_data(
                     &emscripten_globals.data,
-                    self.wasi.mapped_dirs.clone().into_iter().collect(),
+                    self.wasi
+                        .pre_opened_directories
+                        .iter()
+                        .map(|(dir, _)| (dir.to_string_lossy().into_owned(), dir.clone()))
+                        .chain(
+                            self.wasi
+                                .mapped_dirs
+                                .iter()
+                                .map(|(alias, dir, _)| (alias.clone(), dir.clone())),
+                        )
+                        .collect(),
                 );
                 let import_object =
                     generate_emscripten_env(&mut store, &env, &mut emscripten_globals);
@@ -270,7 +487,7 @@ impl RunWithPathBuf {
             use std::collections::BTreeSet;
             use wasmer_wasi::WasiVersion;
 
-            let wasi_versions = Wasi::get_versions(&module);
+            let wasi_versions = self.wasi.get_versions_for(&module);
             match wasi_versions {
                 Some(wasi_versions) if !wasi_versions.is_empty() => {
                     if wasi_versions.len() >= 2 {
@@ -299,19 +516,30 @@ impl RunWithPathBuf {
                                 .map(|f| f.to_string_lossy().to_string())
                         })
                         .unwrap_or_default();
+                    let start = Instant::now();
                     let (ctx, instance) = self
                         .wasi
-                        .instantiate(&mut store, &module, program_name, self.args.clone())
+                        .instantiate(
+                            &mut store,
+                            &module,
+                            program_name,
+                            self.args.clone(),
+                            &host_calls,
+                        )
                         .with_context(|| "failed to instantiate WASI module")?;
-                    let res = self.inner_module_run(&mut store, instance);
+                    report.timings.instantiation = Some(start.elapsed().as_secs_f64());
+                    let res = self.inner_module_run(&mut store, instance, &host_calls, report);
 
                     ctx.cleanup(&mut store, None);
                     res
                 }
                 // not WASI
                 _ => {
-                    let instance = Instance::new(&mut store, &module, &imports! {})?;
-                    self.inner_module_run(&mut store, instance)
+                    let start = Instant::now();
+                    let imports = host_calls.wrap_imports(&mut store, &imports! {});
+                    let instance = Instance::new(&mut store, &module, &imports)?;
+                    report.timings.instantiation = Some(start.elapsed().as_secs_f64());
+                    self.inner_module_run(&mut store, instance, &host_calls, report)
                 }
             }
         };
@@ -333,7 +561,7 @@ impl RunWithPathBuf {
                     "{}",
                     result
                         .iter()
-                        .map(|val| val.to_string())
+                        .map(format_value)
                         .collect::<Vec<String>>()
                         .join(" ")
                 );
@@ -401,18 +629,35 @@ impl RunWithPathBuf {
     fn get_store_module(&self) -> Result<(Store, Module)> {
         let contents = std::fs::read(self.path.clone())?;
         if wasmer_compiler::Artifact::is_deserializable(&contents) {
+            if self.limits.gas.is_some() {
+                bail!("`--gas` can't be used with precompiled modules");
+            }
             let engine = wasmer_compiler::EngineBuilder::headless();
-            let store = Store::new(engine);
+            let store = self.limits.limit_store(Store::new(engine));
             let module = unsafe { Module::deserialize_from_file(&store, &self.path)? };
             return Ok((store, module));
         }
+        #[cfg(all(feature = "cache", feature = "singlepass"))]
+        if self.tiered {
+            return self.get_tiered_store_module(&contents);
+        }
+        #[cfg(feature = "compiler")]
+        let (store, compiler_type) = self
+            .store
+            .for_module(&contents)?
+            .get_store_with_middlewares(self.limits.middlewares())?;
+        #[cfg(not(feature = "compiler"))]
         let (store, compiler_type) = self.store.get_store()?;
+        let store = self.limits.limit_store(store);
+        // The metered modules can't be cached, as the gas limit is part of
+        // the compiled code.
         #[cfg(feature = "cache")]
-        let module_result: Result<Module> = if !self.disable_cache && contents.len() > 0x1000 {
-            self.get_module_from_cache(&store, &contents, &compiler_type)
-        } else {
-            Module::new(&store, contents).map_err(|e| e.into())
-        };
+        let module_result: Result<Module> =
+            if !self.disable_cache && self.limits.gas.is_none() && contents.len() > 0x1000 {
+                self.get_module_from_cache(&store, &contents, &compiler_type)
+            } else {
+                Module::new(&store, contents).map_err(|e| e.into())
+            };
         #[cfg(not(feature = "cache"))]
         let module_result = Module::new(&store, &contents);
 
@@ -428,6 +673,51 @@ impl RunWithPathBuf {
         Ok((store, module))
     }
 
+    /// Gets the module compiled with an optimizing compiler from the
+    /// cache, or compiles it with Singlepass while starting the optimizing
+    /// compilation in the background
+    #[cfg(all(feature = "cache", feature = "singlepass"))]
+    fn get_tiered_store_module(&self, contents: &[u8]) -> Result<(Store, Module)> {
+        if self.limits.gas.is_some() {
+            bail!("`--tiered` can't be used with `--gas`");
+        }
+        let store_options = self.store.for_module(contents)?;
+        let (store, compiler_type) = store_options.get_store()?;
+        if compiler_type == CompilerType::Singlepass {
+            bail!("`--tiered` needs an optimizing compiler, like `--backend cranelift`");
+        }
+        let mut cache = get_compiler_cache(&compiler_type)?;
+        let hash = Hash::generate_for_engine(&store, contents);
+        let name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        if let Ok(mut module) = unsafe { cache.load(&store, hash) } {
+            module.set_name(&name);
+            return Ok((self.limits.limit_store(store), module));
+        }
+
+        // The optimizing compilation runs in its own process, so that it
+        // completes even if the module exits first
+        let spawned = std::process::Command::new(std::env::current_exe()?)
+            .args(["cache", "precompile"])
+            .args(store_options.to_args()?)
+            .arg(&self.path)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+        if let Err(e) = spawned {
+            warning!("failed to start the optimizing compilation: {}", e);
+        }
+
+        let (store, _) = store_options
+            .with_compiler(CompilerType::Singlepass)
+            .get_store()?;
+        let store = self.limits.limit_store(store);
+        let mut module = Module::new(&store, contents)
+            .context("module instantiation failed (compiler: singlepass)")?;
+        module.set_name(&name);
+        Ok((store, module))
+    }
+
     #[cfg(feature = "cache")]
     fn get_module_from_cache(
         &self,
@@ -439,14 +729,14 @@ impl RunWithPathBuf {
         // and the file length is greater than 4KB.
         // For files smaller than 4KB caching is not worth,
         // as it takes space and the speedup is minimal.
-        let mut cache = self.get_cache(compiler_type)?;
+        let mut cache = get_compiler_cache(compiler_type)?;
         // Try to get the hash from the provided `--cache-key`, otherwise
         // generate one from the provided file `.wasm` contents.
         let hash = self
             .cache_key
             .as_ref()
             .and_then(|key| Hash::from_str(key).ok())
-            .unwrap_or_else(|| Hash::generate(contents));
+            .unwrap_or_else(|| Hash::generate_for_engine(store, contents));
         match unsafe { cache.load(store, hash) } {
             Ok(module) => Ok(module),
             Err(e) => {
@@ -454,6 +744,9 @@ impl RunWithPathBuf {
                     DeserializeError::Io(_) => {
                         // Do not notify on IO errors
                     }
+                    DeserializeError::Incompatible(err) => {
+                        warning!("cached module is incompatible, recompiling: {}", err);
+                    }
                     err => {
                         warning!("cached module is corrupted: {}", err);
                     }
@@ -466,18 +759,6 @@ impl RunWithPathBuf {
         }
     }
 
-    #[cfg(feature = "cache")]
-    /// Get the Compiler Filesystem cache
-    fn get_cache(&self, compiler_type: &CompilerType) -> Result<FileSystemCache> {
-        let mut cache_dir_root = get_cache_dir();
-        cache_dir_root.push(compiler_type.to_string());
-        let mut cache = FileSystemCache::new(cache_dir_root)?;
-
-        let extension = "wasmu";
-        cache.set_cache_extension(Some(extension));
-        Ok(cache)
-    }
-
     fn try_find_function(
         &self,
         instance: &Instance,
@@ -549,33 +830,7 @@ impl RunWithPathBuf {
         let invoke_args = args
             .iter()
             .zip(func_ty.params().iter())
-            .map(|(arg, param_type)| match param_type {
-                ValueType::I32 => {
-                    Ok(Value::I32(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a i32", arg)
-                    })?))
-                }
-                ValueType::I64 => {
-                    Ok(Value::I64(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a i64", arg)
-                    })?))
-                }
-                ValueType::F32 => {
-                    Ok(Value::F32(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a f32", arg)
-                    })?))
-                }
-                ValueType::F64 => {
-                    Ok(Value::F64(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a f64", arg)
-                    })?))
-                }
-                _ => Err(anyhow!(
-                    "Don't know how to convert {} into {:?}",
-                    arg,
-                    param_type
-                )),
-            })
+            .map(|(arg, param_type)| parse_value(arg, param_type))
             .collect::<Result<Vec<_>>>()?;
         Ok(func.call(ctx, &invoke_args)?)
     }
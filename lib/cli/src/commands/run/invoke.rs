@@ -0,0 +1,139 @@
+//! Conversion of the `--invoke` arguments and results from and to
+//! their textual representation.
+use anyhow::{anyhow, Result};
+use wasmer::Value;
+use wasmer_types::Type as ValueType;
+
+/// Parse an `--invoke` argument according to the type of the parameter
+/// it is passed as.
+///
+/// Integers may be negative and written in hexadecimal with a `0x`
+/// prefix, in which case they are taken as a bit pattern, so
+/// `0xffffffff` is a valid `i32`.
+pub(crate) fn parse_value(arg: &str, ty: &ValueType) -> Result<Value> {
+    let value = match ty {
+        ValueType::I32 => arg
+            .parse()
+            .ok()
+            .or_else(|| parse_hex(arg, 32).map(|v| v as u32 as i32))
+            .map(Value::I32),
+        ValueType::I64 => arg
+            .parse()
+            .ok()
+            .or_else(|| parse_hex(arg, 64).map(|v| v as u64 as i64))
+            .map(Value::I64),
+        ValueType::F32 => arg.parse().ok().map(Value::F32),
+        ValueType::F64 => arg.parse().ok().map(Value::F64),
+        ValueType::V128 => arg
+            .parse()
+            .ok()
+            .or_else(|| parse_hex(arg, 128))
+            .map(Value::V128),
+        _ => return Err(anyhow!("Don't know how to convert {} into {:?}", arg, ty)),
+    };
+    value.ok_or_else(|| anyhow!("Can't convert `{}` into a {}", arg, type_name(ty)))
+}
+
+/// Format a value returned by an invoked function, so that its type can
+/// be told apart: floats always have a decimal point, and vectors are
+/// printed in hexadecimal.
+pub(crate) fn format_value(value: &Value) -> String {
+    match value {
+        Value::I32(v) => v.to_string(),
+        Value::I64(v) => v.to_string(),
+        Value::F32(v) => format!("{:?}", v),
+        Value::F64(v) => format!("{:?}", v),
+        Value::V128(v) => format!("{:#034x}", v),
+        Value::ExternRef(None) | Value::FuncRef(None) => "null".to_string(),
+        Value::ExternRef(Some(_)) => "externref".to_string(),
+        Value::FuncRef(Some(_)) => "funcref".to_string(),
+    }
+}
+
+/// Parse a possibly negated `0x`-prefixed bit pattern of at most `bits`
+/// bits, truncating the negation to `bits` bits.
+fn parse_hex(arg: &str, bits: u32) -> Option<u128> {
+    let (negative, digits) = match arg.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, arg),
+    };
+    let hex = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))?;
+    let value = u128::from_str_radix(hex, 16).ok()?;
+    if bits < 128 && value >> bits != 0 {
+        return None;
+    }
+    Some(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+fn type_name(ty: &ValueType) -> &'static str {
+    match ty {
+        ValueType::I32 => "i32",
+        ValueType::I64 => "i64",
+        ValueType::F32 => "f32",
+        ValueType::F64 => "f64",
+        ValueType::V128 => "v128",
+        ValueType::ExternRef => "externref",
+        ValueType::FuncRef => "funcref",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_value, parse_value};
+    use wasmer::Value;
+    use wasmer_types::Type as ValueType;
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(parse_value("-7", &ValueType::I32).unwrap(), Value::I32(-7));
+        assert_eq!(
+            parse_value("0x10", &ValueType::I32).unwrap(),
+            Value::I32(16)
+        );
+        assert_eq!(
+            parse_value("-0x10", &ValueType::I32).unwrap(),
+            Value::I32(-16)
+        );
+        assert_eq!(
+            parse_value("0xffffffff", &ValueType::I32).unwrap(),
+            Value::I32(-1)
+        );
+        assert_eq!(
+            parse_value("0x7fffffffffffffff", &ValueType::I64).unwrap(),
+            Value::I64(i64::MAX)
+        );
+        assert_eq!(
+            parse_value("-1.5", &ValueType::F32).unwrap(),
+            Value::F32(-1.5)
+        );
+        assert_eq!(parse_value("2", &ValueType::F64).unwrap(), Value::F64(2.0));
+        assert_eq!(
+            parse_value("1.5", &ValueType::I32).unwrap_err().to_string(),
+            "Can't convert `1.5` into a i32"
+        );
+        assert_eq!(
+            parse_value("0x100000000", &ValueType::I32)
+                .unwrap_err()
+                .to_string(),
+            "Can't convert `0x100000000` into a i32"
+        );
+    }
+
+    #[test]
+    fn test_format_value() {
+        assert_eq!(format_value(&Value::I32(-1)), "-1");
+        assert_eq!(format_value(&Value::I64(1 << 40)), "1099511627776");
+        assert_eq!(format_value(&Value::F32(1.0)), "1.0");
+        assert_eq!(format_value(&Value::F64(-0.5)), "-0.5");
+        assert_eq!(
+            format_value(&Value::V128(1)),
+            "0x00000000000000000000000000000001"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+#[cfg(feature = "compiler")]
+use anyhow::anyhow;
+use anyhow::Result;
+use bytesize::ByteSize;
+use clap::Parser;
+use std::ptr::NonNull;
+#[cfg(feature = "compiler")]
+use std::sync::Arc;
+use wasmer::vm::{
+    self, MemoryError, MemoryStyle, TableStyle, VMMemoryDefinition, VMTableDefinition,
+};
+use wasmer::*;
+#[cfg(feature = "compiler")]
+use wasmer_middlewares::{
+    metering::{get_remaining_points, MeteringPoints},
+    Metering,
+};
+
+#[derive(Debug, Parser, Clone, Default)]
+/// Resource limits
+pub struct Limits {
+    /// Maximum size of each linear memory, e.g. `64MiB`
+    #[clap(long = "max-memory", name = "BYTES")]
+    pub(crate) max_memory: Option<ByteSize>,
+
+    /// Maximum number of elements of each table
+    #[clap(long = "max-table-elements", name = "ELEMENTS")]
+    pub(crate) max_table_elements: Option<u32>,
+
+    /// Abort the module once it executed the given number of operators
+    #[clap(long = "gas", name = "POINTS")]
+    pub(crate) gas: Option<u64>,
+}
+
+impl Limits {
+    /// The middlewares to compile the modules with.
+    #[cfg(feature = "compiler")]
+    pub fn middlewares(&self) -> Vec<Arc<dyn ModuleMiddleware>> {
+        match self.gas {
+            Some(gas) => vec![Arc::new(Metering::new(gas, |_: &wasmparser::Operator| 1))],
+            None => Vec::new(),
+        }
+    }
+
+    /// Enforce the memory and table limits on the instances created in
+    /// `store`.
+    pub fn limit_store(&self, store: Store) -> Store {
+        if self.max_memory.is_none() && self.max_table_elements.is_none() {
+            return store;
+        }
+        let mut engine = store.engine().clone();
+        let base = BaseTunables::for_target(engine.target());
+        engine.set_tunables(LimitingTunables {
+            max_memory: self
+                .max_memory
+                .map(|bytes| Pages((bytes.as_u64() / WASM_PAGE_SIZE as u64) as u32)),
+            max_table_elements: self.max_table_elements,
+            base,
+        });
+        Store::new(engine)
+    }
+
+    /// Explain a failure of `instance` caused by the module running out of gas.
+    #[cfg(feature = "compiler")]
+    pub fn check_result(
+        &self,
+        store: &mut Store,
+        instance: &Instance,
+        result: Result<()>,
+    ) -> Result<()> {
+        match (self.gas, result) {
+            (Some(gas), Err(error)) => match get_remaining_points(store, instance) {
+                MeteringPoints::Exhausted => Err(error.context(anyhow!(
+                    "the module ran out of gas after executing {} operators (see `--gas`)",
+                    gas
+                ))),
+                MeteringPoints::Remaining(_) => Err(error),
+            },
+            (_, result) => result,
+        }
+    }
+
+    /// Modules can't be metered without a compiler.
+    #[cfg(not(feature = "compiler"))]
+    pub fn check_result(
+        &self,
+        _store: &mut Store,
+        _instance: &Instance,
+        result: Result<()>,
+    ) -> Result<()> {
+        result
+    }
+}
+
+/// Tunables capping the size of the memories and tables, and delegating
+/// everything else to `base`.
+struct LimitingTunables<T: Tunables> {
+    max_memory: Option<Pages>,
+    max_table_elements: Option<u32>,
+    base: T,
+}
+
+impl<T: Tunables> LimitingTunables<T> {
+    /// Cap the maximum of a memory to the limit.
+    fn adjust_memory(&self, requested: &MemoryType) -> Result<MemoryType, MemoryError> {
+        let mut adjusted = *requested;
+        if let Some(limit) = self.max_memory {
+            if requested.minimum > limit {
+                return Err(MemoryError::Generic(format!(
+                    "the module requires {} of memory, more than the {} allowed by `--max-memory`",
+                    ByteSize(requested.minimum.bytes().0 as u64),
+                    ByteSize(limit.bytes().0 as u64),
+                )));
+            }
+            adjusted.maximum = Some(requested.maximum.map_or(limit, |max| max.min(limit)));
+        }
+        Ok(adjusted)
+    }
+
+    /// Cap the maximum of a table to the limit.
+    fn adjust_table(&self, requested: &TableType) -> Result<TableType, String> {
+        let mut adjusted = *requested;
+        if let Some(limit) = self.max_table_elements {
+            if requested.minimum > limit {
+                return Err(format!(
+                    "the module requires a table of {} elements, more than the {} allowed by `--max-table-elements`",
+                    requested.minimum, limit,
+                ));
+            }
+            adjusted.maximum = Some(requested.maximum.map_or(limit, |max| max.min(limit)));
+        }
+        Ok(adjusted)
+    }
+}
+
+impl<T: Tunables> Tunables for LimitingTunables<T> {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        match self.adjust_memory(memory) {
+            Ok(adjusted) => self.base.memory_style(&adjusted),
+            Err(_) => self.base.memory_style(memory),
+        }
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.base.table_style(table)
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<vm::VMMemory, MemoryError> {
+        self.base
+            .create_host_memory(&self.adjust_memory(ty)?, style)
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<vm::VMMemory, MemoryError> {
+        self.base
+            .create_vm_memory(&self.adjust_memory(ty)?, style, vm_definition_location)
+    }
+
+    fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<vm::VMTable, String> {
+        self.base.create_host_table(&self.adjust_table(ty)?, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<vm::VMTable, String> {
+        self.base
+            .create_vm_table(&self.adjust_table(ty)?, style, vm_definition_location)
+    }
+}
@@ -0,0 +1,106 @@
+use crate::warning;
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::path::PathBuf;
+use wasmer::*;
+
+#[derive(Debug, Parser, Clone, Default)]
+/// Recording and replaying of the host calls
+pub struct RecordReplay {
+    /// Record the results of the host calls, WASI syscalls included, to
+    /// the given file, to run the module again with `--replay`
+    #[clap(
+        long = "record",
+        value_name = "LOG",
+        parse(from_os_str),
+        conflicts_with = "replay"
+    )]
+    pub(crate) record: Option<PathBuf>,
+
+    /// Run the module with the results of the host calls recorded with
+    /// `--record` instead of calling the host, reproducing the recorded
+    /// execution (the output of the module isn't printed again)
+    #[clap(long = "replay", value_name = "LOG", parse(from_os_str))]
+    pub(crate) replay: Option<PathBuf>,
+}
+
+/// How the host functions are called.
+pub enum HostCalls {
+    /// The host functions are called.
+    Live,
+    /// The host functions are called, and their results are recorded to
+    /// the file.
+    Record(Recorder, PathBuf),
+    /// The host functions are replaced with the calls of a log.
+    Replay(Replayer),
+}
+
+impl RecordReplay {
+    /// Read the log to replay, if any.
+    pub fn host_calls(&self) -> Result<HostCalls> {
+        if let Some(path) = &self.record {
+            return Ok(HostCalls::Record(Recorder::new(), path.clone()));
+        }
+        if let Some(path) = &self.replay {
+            let contents = std::fs::read(path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?;
+            let log = ExecutionLog::deserialize(&contents)
+                .with_context(|| format!("failed to read the log `{}`", path.display()))?;
+            return Ok(HostCalls::Replay(Replayer::new(log)));
+        }
+        Ok(HostCalls::Live)
+    }
+}
+
+impl HostCalls {
+    pub fn is_live(&self) -> bool {
+        matches!(self, Self::Live)
+    }
+
+    /// Wrap the functions of `imports` to record or replay their calls.
+    pub fn wrap_imports(&self, store: &mut impl AsStoreMut, imports: &Imports) -> Imports {
+        match self {
+            Self::Live => imports.clone(),
+            Self::Record(recorder, _) => recorder.wrap_imports(store, imports),
+            Self::Replay(replayer) => replayer.wrap_imports(store, imports),
+        }
+    }
+
+    /// Record or replay the writes of the host functions to the memory
+    /// of `instance`.
+    pub fn attach(&self, instance: &Instance) {
+        let memory = match instance.exports.get_memory("memory") {
+            Ok(memory) => memory.clone(),
+            Err(_) => return,
+        };
+        match self {
+            Self::Live => {}
+            Self::Record(recorder, _) => recorder.set_memory(memory),
+            Self::Replay(replayer) => replayer.set_memory(memory),
+        }
+    }
+
+    /// Write the calls recorded so far to the log.
+    pub fn save_log(&self) -> Result<()> {
+        if let Self::Record(recorder, path) = self {
+            std::fs::write(path, recorder.log().serialize())
+                .with_context(|| format!("failed to write the log to `{}`", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Write the log once the module ran, or warn about the recorded
+    /// calls which weren't replayed.
+    pub fn finish(&self) -> Result<()> {
+        match self {
+            Self::Replay(replayer) if replayer.remaining() > 0 => {
+                warning!(
+                    "the replay ended before {} recorded host calls",
+                    replayer.remaining()
+                );
+                Ok(())
+            }
+            _ => self.save_log(),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! The report printed by `wasmer run --format json`, so that the outcome
+//! of a run can be told apart from the output of the module.
+use crate::error::PrettyError;
+use serde::Serialize;
+use wasmer::RuntimeError;
+
+/// The outcome of a run.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct RunReport {
+    /// The code wasmer exits with.
+    pub exit_code: i32,
+    /// The values returned by the `--invoke`d function.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Vec<String>>,
+    /// The error that stopped the run, with its causes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The trap raised by the module, if the run failed because of one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trap: Option<TrapReport>,
+    pub timings: Timings,
+}
+
+/// How long each step of the run took, in seconds. The steps that were
+/// not reached are left out.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct Timings {
+    /// Compiling the module, or loading it from the cache.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compilation: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instantiation: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub execution: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct TrapReport {
+    message: String,
+    /// The trap code, for the traps raised by the generated code rather
+    /// than by a host function.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+    frames: Vec<FrameReport>,
+}
+
+#[derive(Debug, Serialize)]
+struct FrameReport {
+    module: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function: Option<String>,
+    func_index: u32,
+    module_offset: usize,
+}
+
+impl RunReport {
+    /// Record the failure of the run.
+    pub fn record_error(&mut self, error: &anyhow::Error) {
+        self.exit_code = PrettyError::exit_code(error);
+        self.error = Some(format!("{:#}", error));
+        self.trap = error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<RuntimeError>())
+            .map(TrapReport::new);
+    }
+}
+
+impl TrapReport {
+    fn new(error: &RuntimeError) -> Self {
+        Self {
+            message: error.message(),
+            code: error.clone().to_trap().map(|code| format!("{:?}", code)),
+            frames: error
+                .trace()
+                .iter()
+                .map(|frame| FrameReport {
+                    module: frame.module_name().to_string(),
+                    function: frame.function_name().map(str::to_string),
+                    func_index: frame.func_index(),
+                    module_offset: frame.module_offset(),
+                })
+                .collect(),
+        }
+    }
+}
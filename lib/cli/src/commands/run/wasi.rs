@@ -1,12 +1,13 @@
-use crate::utils::{parse_envvar, parse_mapdir};
-use anyhow::Result;
+use super::replay::HostCalls;
+use crate::utils::{parse_dir, parse_env_file, parse_envvar, parse_mapdir};
+use anyhow::{bail, Context, Result};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Component, PathBuf};
 use std::sync::Arc;
 use std::{collections::BTreeSet, path::Path};
 use wasmer::{AsStoreMut, Instance, Module, RuntimeError, Value};
-use wasmer_vfs::FileSystem;
-use wasmer_vfs::{DeviceFile, PassthruFileSystem, RootFileSystemBuilder};
+use wasmer_vfs::{host_fs, FileSystem, FsError, VirtualFile};
+use wasmer_vfs::{DeviceFile, PassthruFileSystem, RootFileSystemBuilder, TmpFileSystem};
 use wasmer_wasi::types::__WASI_STDIN_FILENO;
 use wasmer_wasi::{
     default_fs_backing, get_wasi_versions, PluggableRuntimeImplementation, WasiEnv, WasiError,
@@ -18,17 +19,23 @@ use clap::Parser;
 #[derive(Debug, Parser, Clone, Default)]
 /// WASI Options
 pub struct Wasi {
-    /// WASI pre-opened directory
-    #[clap(long = "dir", name = "DIR", group = "wasi")]
-    pub(crate) pre_opened_directories: Vec<PathBuf>,
+    /// WASI pre-opened directory, read-only if suffixed with `:ro`
+    #[clap(
+        long = "dir",
+        name = "DIR",
+        group = "wasi",
+        parse(try_from_str = parse_dir),
+    )]
+    pub(crate) pre_opened_directories: Vec<(PathBuf, bool)>,
 
-    /// Map a host directory to a different location for the Wasm module
+    /// Map a host directory or file to a different location for the Wasm
+    /// module, read-only if suffixed with `:ro`
     #[clap(
         long = "mapdir",
-        name = "GUEST_DIR:HOST_DIR",
+        name = "GUEST_DIR:HOST_DIR[:ro]",
         parse(try_from_str = parse_mapdir),
     )]
-    pub(crate) mapped_dirs: Vec<(String, PathBuf)>,
+    pub(crate) mapped_dirs: Vec<(String, PathBuf, bool)>,
 
     /// Pass custom environment variables
     #[clap(
@@ -38,6 +45,22 @@ pub struct Wasi {
     )]
     pub(crate) env_vars: Vec<(String, String)>,
 
+    /// Pass the environment variables defined in a file, one `KEY=VALUE` per line
+    #[clap(long = "env-file", name = "ENV_FILE", parse(from_os_str))]
+    pub(crate) env_files: Vec<PathBuf>,
+
+    /// Read the standard input of the module from a file instead of the terminal
+    #[clap(long = "stdin", name = "STDIN_FILE", parse(from_os_str))]
+    pub(crate) stdin: Option<PathBuf>,
+
+    /// Write the standard output of the module to a file instead of the terminal
+    #[clap(long = "stdout", name = "STDOUT_FILE", parse(from_os_str))]
+    pub(crate) stdout: Option<PathBuf>,
+
+    /// Write the standard error of the module to a file instead of the terminal
+    #[clap(long = "stderr", name = "STDERR_FILE", parse(from_os_str))]
+    pub(crate) stderr: Option<PathBuf>,
+
     /// List of other containers this module depends on
     #[clap(long = "use", name = "USE")]
     uses: Vec<String>,
@@ -56,7 +79,8 @@ pub struct Wasi {
 
     /// Enable networking with the host network.
     ///
-    /// Allows WASI modules to open TCP and UDP connections, create sockets, ...
+    /// Allows WASI and Emscripten modules to open TCP and UDP connections,
+    /// create sockets, ...
     #[clap(long = "net")]
     pub networking: bool,
 
@@ -73,18 +97,328 @@ pub struct Wasi {
     /// Require WASI modules to only import 1 version of WASI.
     #[clap(long = "deny-multiple-wasi-versions")]
     pub deny_multiple_wasi_versions: bool,
+
+    /// Force the WASI version of the module instead of detecting it from
+    /// its imports: `wasi_unstable`, `wasi_snapshot_preview1` or `none`
+    /// to run it without WASI
+    #[clap(long = "wasi-version", name = "WASI_VERSION")]
+    pub wasi_version: Option<WasiVersionOverride>,
+}
+
+/// The value of the `--wasi-version` option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasiVersionOverride {
+    /// Provide this version under both the `wasi_unstable` and
+    /// `wasi_snapshot_preview1` namespaces.
+    Force(WasiVersion),
+    /// Don't provide WASI to the module at all.
+    Disabled,
+}
+
+impl std::str::FromStr for WasiVersionOverride {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wasi_unstable" | "unstable" | "snapshot0" => Ok(Self::Force(WasiVersion::Snapshot0)),
+            "wasi_snapshot_preview1" | "snapshot_preview1" | "snapshot1" => {
+                Ok(Self::Force(WasiVersion::Snapshot1))
+            }
+            "none" => Ok(Self::Disabled),
+            _ => Err(
+                "must be one of three options: `wasi_unstable`, `wasi_snapshot_preview1` or `none`.",
+            ),
+        }
+    }
+}
+
+/// A host directory or file made visible to the guest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Mount {
+    guest: PathBuf,
+    host: PathBuf,
+    read_only: bool,
+    is_file: bool,
+}
+
+impl Mount {
+    /// The guest path of the mount inside of the sandboxed filesystem,
+    /// relative paths being relative to the working directory `cwd`.
+    fn absolute_guest(&self, cwd: &Path) -> PathBuf {
+        if self.guest == Path::new(".") {
+            cwd.to_path_buf()
+        } else {
+            cwd.join(&self.guest)
+        }
+    }
+}
+
+/// Normalize a guest path, so that aliases like `data`, `./data` and
+/// `data/` are recognized as the same mount.
+fn normalize_guest_path(path: &Path) -> PathBuf {
+    let normalized = path
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect::<PathBuf>();
+    if normalized.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        normalized
+    }
+}
+
+/// Check that the mounts don't conflict with each other, dropping the
+/// exact duplicates.
+///
+/// Directories may be nested inside other directories, the mount with
+/// the longest guest path taking precedence, but two different host
+/// paths can't be mounted at the same guest path and nothing can be
+/// mounted inside of a file.
+fn validate_mounts(mounts: Vec<Mount>) -> Result<Vec<Mount>> {
+    let mut validated: Vec<Mount> = Vec::with_capacity(mounts.len());
+    for mount in mounts {
+        if let Some(existing) = validated.iter().find(|m| m.guest == mount.guest) {
+            if *existing == mount {
+                continue;
+            }
+            bail!(
+                "`{}` and `{}` are both mounted at `{}`",
+                existing.host.display(),
+                mount.host.display(),
+                mount.guest.display()
+            );
+        }
+        for other in &validated {
+            let (outer, inner) = if mount.guest.starts_with(&other.guest) {
+                (other, &mount)
+            } else if other.guest.starts_with(&mount.guest) {
+                (&mount, other)
+            } else {
+                continue;
+            };
+            if outer.is_file {
+                bail!(
+                    "`{}` can't be mounted at `{}`, inside of the file mounted at `{}`",
+                    inner.host.display(),
+                    inner.guest.display(),
+                    outer.guest.display()
+                );
+            }
+        }
+        validated.push(mount);
+    }
+    Ok(validated)
 }
 
 #[allow(dead_code)]
 impl Wasi {
     pub fn map_dir(&mut self, alias: &str, target_on_disk: PathBuf) {
-        self.mapped_dirs.push((alias.to_string(), target_on_disk));
+        self.mapped_dirs
+            .push((alias.to_string(), target_on_disk, false));
     }
 
     pub fn set_env(&mut self, key: &str, value: &str) {
         self.env_vars.push((key.to_string(), value.to_string()));
     }
 
+    /// Gets the environment variables from the env files and the ones passed
+    /// with `--env`, the latter taking precedence
+    pub fn get_env_vars(&self) -> Result<Vec<(String, String)>> {
+        let mut env_vars = Vec::new();
+        for path in &self.env_files {
+            let contents = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?;
+            env_vars.extend(
+                parse_env_file(&contents)
+                    .with_context(|| format!("failed to parse `{}`", path.display()))?,
+            );
+        }
+        env_vars.extend(self.env_vars.iter().cloned());
+
+        // Keep the last definition of each variable
+        let mut deduplicated: Vec<(String, String)> = Vec::with_capacity(env_vars.len());
+        for (key, value) in env_vars {
+            deduplicated.retain(|(existing, _)| *existing != key);
+            deduplicated.push((key, value));
+        }
+        Ok(deduplicated)
+    }
+
+    /// Gets the directories and files to expose to the module, from both
+    /// `--dir` and `--mapdir`, after checking that they don't conflict
+    fn get_mounts(&self) -> Result<Vec<Mount>> {
+        let dirs = self
+            .pre_opened_directories
+            .iter()
+            .map(|(dir, read_only)| Mount {
+                guest: normalize_guest_path(dir),
+                host: dir.clone(),
+                read_only: *read_only,
+                is_file: false,
+            });
+        let mapped_dirs = self
+            .mapped_dirs
+            .iter()
+            .map(|(alias, host, read_only)| Mount {
+                guest: normalize_guest_path(Path::new(alias)),
+                host: host.clone(),
+                read_only: *read_only,
+                is_file: host.is_file(),
+            });
+        validate_mounts(dirs.chain(mapped_dirs).collect())
+    }
+
+    /// Builds the sandboxed filesystem of WASIX modules, with the mounts
+    /// grafted onto it, and returns it along with the working directory
+    /// of the module.
+    ///
+    /// The working directory is the root of the filesystem, unless a
+    /// directory is mounted at `.`: it's then mounted at its absolute
+    /// path on the host, and becomes the working directory.
+    fn build_sandbox_fs(&self, mounts: &[Mount]) -> Result<(TmpFileSystem, PathBuf)> {
+        let root_fs = RootFileSystemBuilder::new()
+            .with_tty(Box::new(DeviceFile::new(__WASI_STDIN_FILENO)))
+            .build();
+        let fs_backing: Arc<dyn FileSystem + Send + Sync> =
+            Arc::new(PassthruFileSystem::new(default_fs_backing()));
+        let cwd = match mounts.iter().find(|m| m.guest == Path::new(".")) {
+            Some(mount) => {
+                let host = mount
+                    .host
+                    .canonicalize()
+                    .with_context(|| format!("failed to resolve `{}`", mount.host.display()))?;
+                Path::new("/").join(
+                    host.components()
+                        .filter(|c| matches!(c, Component::Normal(_)))
+                        .collect::<PathBuf>(),
+                )
+            }
+            None => PathBuf::from("/"),
+        };
+        for mount in mounts {
+            let guest = mount.absolute_guest(&cwd);
+            if guest.file_name().is_none() {
+                bail!(
+                    "`{}` can't be mounted at the root of the filesystem of WASIX modules",
+                    mount.host.display()
+                );
+            }
+            if let Some(outer) = mounts.iter().find(|m| {
+                let outer = m.absolute_guest(&cwd);
+                outer != guest && guest.starts_with(&outer)
+            }) {
+                bail!(
+                    "Nested mounts are not supported for WASIX modules: `{}` is inside of `{}`",
+                    mount.guest.display(),
+                    outer.guest.display()
+                );
+            }
+            // Create the parent directories of the mount point
+            let parents = guest
+                .ancestors()
+                .skip(1)
+                .filter(|p| p.file_name().is_some())
+                .collect::<Vec<_>>();
+            for parent in parents.into_iter().rev() {
+                match root_fs.create_dir(parent) {
+                    Ok(()) | Err(FsError::AlreadyExists) => {}
+                    Err(e) => bail!("failed to create `{}`: {}", parent.display(), e),
+                }
+            }
+            if mount.is_file {
+                root_fs
+                    .new_open_options_ext()
+                    .insert_device_file(guest, open_mapped_file(mount)?)?;
+            } else {
+                if mount.read_only {
+                    bail!(
+                        "Read-only directory mappings are not supported for WASIX modules: {}",
+                        mount.guest.display()
+                    );
+                }
+                root_fs.mount(guest, &fs_backing, mount.host.clone())?;
+            }
+        }
+        Ok((root_fs, cwd))
+    }
+
+    /// Builds the filesystem of the other modules when they map single
+    /// files, which can't be preopened from the host: the files are
+    /// grafted onto an in-memory directory per guest directory, next to
+    /// the directories mounted from the host.
+    ///
+    /// Returns the filesystem with the directories to preopen, whose host
+    /// path is replaced by their path in the filesystem.
+    fn build_mapped_files_fs(&self, mounts: &[Mount]) -> Result<(TmpFileSystem, Vec<Mount>)> {
+        let root_fs = TmpFileSystem::new();
+        let fs_backing: Arc<dyn FileSystem + Send + Sync> =
+            Arc::new(PassthruFileSystem::new(default_fs_backing()));
+        root_fs.create_dir(Path::new("/mounts"))?;
+        root_fs.create_dir(Path::new("/files"))?;
+
+        let mut preopens = Vec::with_capacity(mounts.len());
+        for (index, mount) in mounts.iter().filter(|m| !m.is_file).enumerate() {
+            let path = PathBuf::from(format!("/mounts/{}", index));
+            root_fs.mount(path.clone(), &fs_backing, mount.host.clone())?;
+            preopens.push(Mount {
+                host: path,
+                ..mount.clone()
+            });
+        }
+
+        let mut file_dirs: Vec<Mount> = Vec::new();
+        for mount in mounts.iter().filter(|m| m.is_file) {
+            let name = match mount.guest.file_name() {
+                Some(name) => name,
+                None => bail!(
+                    "The file `{}` can't be mapped at `{}`",
+                    mount.host.display(),
+                    mount.guest.display()
+                ),
+            };
+            let parent =
+                normalize_guest_path(mount.guest.parent().unwrap_or_else(|| Path::new("")));
+            if let Some(dir) = preopens.iter().find(|m| m.guest == parent) {
+                bail!(
+                    "`{}` can't be mapped at `{}`, directly inside of the directory mounted at `{}`",
+                    mount.host.display(),
+                    mount.guest.display(),
+                    dir.guest.display()
+                );
+            }
+            let dir = match file_dirs.iter().position(|m| m.guest == parent) {
+                Some(index) => &mut file_dirs[index],
+                None => {
+                    let path = PathBuf::from(format!("/files/{}", file_dirs.len()));
+                    root_fs.create_dir(&path)?;
+                    file_dirs.push(Mount {
+                        guest: parent,
+                        host: path,
+                        read_only: true,
+                        is_file: false,
+                    });
+                    file_dirs.last_mut().unwrap()
+                }
+            };
+            // The directory is writable as soon as one of its files is
+            dir.read_only &= mount.read_only;
+            root_fs
+                .new_open_options_ext()
+                .insert_device_file(dir.host.join(name), open_mapped_file(mount)?)?;
+        }
+        preopens.extend(file_dirs);
+        Ok((root_fs, preopens))
+    }
+
+    /// Gets the host directories and files made visible to the module
+    pub fn host_paths(&self) -> Vec<PathBuf> {
+        self.pre_opened_directories
+            .iter()
+            .map(|(path, _)| path.clone())
+            .chain(self.mapped_dirs.iter().map(|(_, path, _)| path.clone()))
+            .collect()
+    }
+
     /// Gets the WASI version (if any) for the provided module
     pub fn get_versions(module: &Module) -> Option<BTreeSet<WasiVersion>> {
         // Get the wasi version in non-strict mode, so multiple wasi versions
@@ -94,6 +428,16 @@ impl Wasi {
         get_wasi_versions(module, false)
     }
 
+    /// Gets the WASI versions to run the module with, which are the
+    /// detected ones unless `--wasi-version` is passed
+    pub fn get_versions_for(&self, module: &Module) -> Option<BTreeSet<WasiVersion>> {
+        match self.wasi_version {
+            Some(WasiVersionOverride::Force(version)) => Some(BTreeSet::from([version])),
+            Some(WasiVersionOverride::Disabled) => None,
+            None => Self::get_versions(module),
+        }
+    }
+
     /// Checks if a given module has any WASI imports at all.
     pub fn has_wasi_imports(module: &Module) -> bool {
         // Get the wasi version in non-strict mode, so no other imports
@@ -108,6 +452,7 @@ impl Wasi {
         module: &Module,
         program_name: String,
         args: Vec<String>,
+        host_calls: &HostCalls,
     ) -> Result<(WasiFunctionEnv, Instance)> {
         let args = args.iter().cloned().map(|arg| arg.into_bytes());
 
@@ -134,40 +479,80 @@ impl Wasi {
         let builder = WasiEnv::builder(program_name)
             .runtime(Arc::new(rt))
             .args(args)
-            .envs(self.env_vars.clone())
+            .envs(self.get_env_vars()?)
             .uses(self.uses.clone())
             .map_commands(map_commands);
 
+        let mounts = self.get_mounts()?;
         let mut builder = if wasmer_wasi::is_wasix_module(module) {
             // If we preopen anything from the host then shallow copy it over
-            let root_fs = RootFileSystemBuilder::new()
-                .with_tty(Box::new(DeviceFile::new(__WASI_STDIN_FILENO)))
-                .build();
-            if !self.mapped_dirs.is_empty() {
-                let fs_backing: Arc<dyn FileSystem + Send + Sync> =
-                    Arc::new(PassthruFileSystem::new(default_fs_backing()));
-                for (src, dst) in self.mapped_dirs.clone() {
-                    let src = match src.starts_with('/') {
-                        true => src,
-                        false => format!("/{}", src),
-                    };
-                    root_fs.mount(PathBuf::from(src), &fs_backing, dst)?;
-                }
-            }
+            let (root_fs, cwd) = self.build_sandbox_fs(&mounts)?;
 
             // Open the root of the new filesystem
             builder
                 .sandbox_fs(root_fs)
                 .preopen_dir(Path::new("/"))
                 .unwrap()
-                .map_dir(".", "/")?
+                .map_dir(".", cwd)?
         } else {
+            let (builder, preopens) = if mounts.iter().any(|m| m.is_file) {
+                let (root_fs, preopens) = self.build_mapped_files_fs(&mounts)?;
+                (builder.fs(Box::new(root_fs)), preopens)
+            } else {
+                (builder.fs(default_fs_backing()), mounts)
+            };
+            let mut builder = builder;
+            // Read-only directories are preopened without the write rights
+            for preopen in preopens.iter() {
+                builder.add_preopen_build(|p| {
+                    p.directory(&preopen.host)
+                        .alias(&preopen.guest.to_string_lossy())
+                        .read(true)
+                        .write(!preopen.read_only)
+                        .create(!preopen.read_only)
+                })?;
+            }
             builder
-                .fs(default_fs_backing())
-                .preopen_dirs(self.pre_opened_directories.clone())?
-                .map_dirs(self.mapped_dirs.clone())?
         };
 
+        if let Some(WasiVersionOverride::Force(version)) = self.wasi_version {
+            builder.set_wasi_version(version);
+        }
+
+        if let Some(path) = &self.stdin {
+            let file = std::fs::File::open(path)
+                .with_context(|| format!("failed to open `{}` as stdin", path.display()))?;
+            builder.set_stdin(Box::new(host_fs::File::new(
+                file,
+                path.clone(),
+                true,
+                false,
+                false,
+            )));
+        }
+        if let Some(path) = &self.stdout {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("failed to create `{}` as stdout", path.display()))?;
+            builder.set_stdout(Box::new(host_fs::File::new(
+                file,
+                path.clone(),
+                false,
+                true,
+                false,
+            )));
+        }
+        if let Some(path) = &self.stderr {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("failed to create `{}` as stderr", path.display()))?;
+            builder.set_stderr(Box::new(host_fs::File::new(
+                file,
+                path.clone(),
+                false,
+                true,
+                false,
+            )));
+        }
+
         if self.http_client {
             let caps = wasmer_wasi::http::HttpClientCapabilityV1::new_allow_all();
             builder.capabilities_mut().http_client = caps;
@@ -181,7 +566,21 @@ impl Wasi {
             }
         }
 
-        let (instance, wasi_env) = builder.instantiate(module.clone(), store)?;
+        if host_calls.is_live() {
+            let (instance, wasi_env) = builder.instantiate(module.clone(), store)?;
+            return Ok((wasi_env, instance));
+        }
+
+        // The WASI imports are wrapped to be recorded or replayed, which
+        // leaves out the modules importing their memory
+        if module.imports().memories().next().is_some() {
+            bail!("recording or replaying modules importing their memory isn't supported");
+        }
+        let mut wasi_env = builder.finalize(store)?;
+        let imports = wasi_env.import_object_for_all_wasi_versions(store, module)?;
+        let imports = host_calls.wrap_imports(store, &imports);
+        let instance = Instance::new(store, module, &imports)?;
+        wasi_env.initialize(store, instance.clone())?;
         Ok((wasi_env, instance))
     }
 
@@ -203,6 +602,15 @@ impl Wasi {
         }
     }
 
+    /// Gets the code the module exited with through `proc_exit`, if that
+    /// is what stopped it
+    pub fn exit_code(error: &RuntimeError) -> Option<u32> {
+        match error.downcast_ref::<WasiError>() {
+            Some(WasiError::Exit(exit_code)) => Some(*exit_code),
+            _ => None,
+        }
+    }
+
     pub fn for_binfmt_interpreter() -> Result<Self> {
         use std::env;
         let dir = env::var_os("WASMER_BINFMT_MISC_PREOPEN")
@@ -211,8 +619,212 @@ impl Wasi {
         Ok(Self {
             deny_multiple_wasi_versions: true,
             env_vars: env::vars().collect(),
-            pre_opened_directories: vec![dir],
+            pre_opened_directories: vec![(dir, false)],
             ..Self::default()
         })
     }
 }
+
+/// Opens a file mapped with `--mapdir`, without the write rights if
+/// it's read-only.
+fn open_mapped_file(mount: &Mount) -> Result<Box<dyn VirtualFile + Send + Sync>> {
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(!mount.read_only)
+        .open(&mount.host)
+        .with_context(|| format!("failed to open `{}`", mount.host.display()))?;
+    Ok(Box::new(host_fs::File::new(
+        file,
+        mount.host.clone(),
+        true,
+        !mount.read_only,
+        false,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_guest_path, validate_mounts, Mount, Wasi, WasiVersionOverride};
+    use std::path::{Path, PathBuf};
+    use wasmer_vfs::FileSystem;
+    use wasmer_wasi::WasiVersion;
+
+    fn mount(guest: &str, host: &str, is_file: bool) -> Mount {
+        Mount {
+            guest: normalize_guest_path(Path::new(guest)),
+            host: PathBuf::from(host),
+            read_only: false,
+            is_file,
+        }
+    }
+
+    #[test]
+    fn test_normalize_guest_path() {
+        assert_eq!(
+            normalize_guest_path(Path::new("./data/")),
+            Path::new("data")
+        );
+        assert_eq!(normalize_guest_path(Path::new("./")), Path::new("."));
+        assert_eq!(
+            normalize_guest_path(Path::new("/data/.")),
+            Path::new("/data")
+        );
+    }
+
+    #[test]
+    fn test_validate_mounts() {
+        // Aliases of the same mount are deduplicated
+        assert_eq!(
+            validate_mounts(vec![
+                mount("data", "a", false),
+                mount("./data/", "a", false)
+            ])
+            .unwrap(),
+            vec![mount("data", "a", false)]
+        );
+        // Nested directories are allowed
+        assert_eq!(
+            validate_mounts(vec![
+                mount("/data", "a", false),
+                mount("/data/b", "b", false)
+            ])
+            .unwrap()
+            .len(),
+            2
+        );
+        assert_eq!(
+            validate_mounts(vec![mount("data", "a", false), mount("data", "b", false)])
+                .unwrap_err()
+                .to_string(),
+            "`a` and `b` are both mounted at `data`"
+        );
+        assert_eq!(
+            validate_mounts(vec![
+                mount("/data/b", "b", false),
+                mount("/data", "a", true)
+            ])
+            .unwrap_err()
+            .to_string(),
+            "`b` can't be mounted at `/data/b`, inside of the file mounted at `/data`"
+        );
+    }
+
+    #[test]
+    fn test_build_mapped_files_fs() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.toml");
+        std::fs::write(&file, "key = 1").unwrap();
+        let host = |path: &Path| path.to_str().unwrap().to_string();
+        let mut mounts = vec![
+            Mount {
+                read_only: true,
+                ..mount(".", &host(dir.path()), false)
+            },
+            Mount {
+                read_only: true,
+                ..mount("etc/app.toml", &host(&file), true)
+            },
+        ];
+
+        // The directories and the parents of the files are preopened
+        let (fs, preopens) = Wasi::default().build_mapped_files_fs(&mounts).unwrap();
+        assert_eq!(
+            preopens,
+            vec![
+                Mount {
+                    read_only: true,
+                    ..mount(".", "/mounts/0", false)
+                },
+                Mount {
+                    read_only: true,
+                    ..mount("etc", "/files/0", false)
+                },
+            ]
+        );
+        assert!(fs.metadata(Path::new("/mounts/0/config.toml")).is_ok());
+        assert!(fs.metadata(Path::new("/files/0/app.toml")).is_ok());
+
+        // Files can't be added to the directories mounted from the host
+        mounts.push(mount("other.toml", &host(&file), true));
+        assert_eq!(
+            Wasi::default()
+                .build_mapped_files_fs(&mounts)
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "`{}` can't be mapped at `other.toml`, directly inside of the directory mounted at `.`",
+                file.display()
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_build_sandbox_fs_working_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("config.toml"), "key = 1").unwrap();
+        let host = dir.path().to_str().unwrap().to_string();
+        let mut mounts = vec![mount(".", &host, false)];
+
+        // The directory mounted at `.` becomes the working directory
+        let (fs, cwd) = Wasi::default().build_sandbox_fs(&mounts).unwrap();
+        assert_eq!(cwd, dir.path().canonicalize().unwrap());
+        assert!(fs.metadata(&cwd.join("config.toml")).is_ok());
+
+        // Relative paths are relative to the working directory
+        mounts.push(mount("data", &host, false));
+        assert_eq!(
+            Wasi::default()
+                .build_sandbox_fs(&mounts)
+                .unwrap_err()
+                .to_string(),
+            "Nested mounts are not supported for WASIX modules: `data` is inside of `.`"
+        );
+    }
+
+    #[test]
+    fn test_build_sandbox_fs_read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.toml");
+        std::fs::write(&file, "key = 1").unwrap();
+        let host = |path: &Path| path.to_str().unwrap().to_string();
+
+        // Read-only files are mapped, opened without the write rights
+        let mounts = vec![Mount {
+            read_only: true,
+            ..mount("etc/app.toml", &host(&file), true)
+        }];
+        let (fs, _) = Wasi::default().build_sandbox_fs(&mounts).unwrap();
+        assert!(fs.metadata(Path::new("/etc/app.toml")).is_ok());
+
+        // Read-only directories can't be mapped
+        let mounts = vec![Mount {
+            read_only: true,
+            ..mount("data", &host(dir.path()), false)
+        }];
+        assert_eq!(
+            Wasi::default()
+                .build_sandbox_fs(&mounts)
+                .unwrap_err()
+                .to_string(),
+            "Read-only directory mappings are not supported for WASIX modules: data"
+        );
+    }
+
+    #[test]
+    fn test_parse_wasi_version_override() {
+        assert_eq!(
+            "wasi_unstable".parse::<WasiVersionOverride>(),
+            Ok(WasiVersionOverride::Force(WasiVersion::Snapshot0))
+        );
+        assert_eq!(
+            "wasi_snapshot_preview1".parse::<WasiVersionOverride>(),
+            Ok(WasiVersionOverride::Force(WasiVersion::Snapshot1))
+        );
+        assert_eq!(
+            "none".parse::<WasiVersionOverride>(),
+            Ok(WasiVersionOverride::Disabled)
+        );
+        assert!("wasix_32v1".parse::<WasiVersionOverride>().is_err());
+    }
+}
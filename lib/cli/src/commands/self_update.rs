@@ -1,12 +1,89 @@
 //! When wasmer self-update is executed, this is what gets executed
-use anyhow::{Context, Result};
+use crate::VERSION;
+use anyhow::{anyhow, bail, Context, Result};
 use clap::Parser;
-#[cfg(not(target_os = "windows"))]
-use std::process::{Command, Stdio};
+use serde::Deserialize;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const RELEASES_URL: &str = "https://api.github.com/repos/wasmerio/wasmer/releases";
+
+/// The minisign public key (in base64) the release archives are signed
+/// with, embedded by the release builds.
+const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("WASMER_RELEASE_PUBLIC_KEY");
 
 /// The options for the `wasmer self-update` subcommand
 #[derive(Debug, Parser)]
-pub struct SelfUpdate {}
+pub struct SelfUpdate {
+    /// The release channel to update from: `stable` or `prerelease`
+    #[clap(long = "channel", default_value = "stable")]
+    channel: Channel,
+
+    /// Install this exact version instead of the latest one of the channel
+    #[clap(long = "version")]
+    version: Option<semver::Version>,
+
+    /// Only check whether a newer version is available
+    #[clap(long = "check")]
+    check: bool,
+
+    /// Reinstall even if the current version is already up to date
+    #[clap(long = "force")]
+    force: bool,
+
+    /// A minisign public key (in base64) to verify the signature of the
+    /// downloaded archive with, instead of the release key embedded in
+    /// wasmer
+    #[clap(long = "public-key")]
+    public_key: Option<String>,
+}
+
+/// The release channels `wasmer self-update` can pick releases from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Channel {
+    /// Only the releases that are not marked as prereleases.
+    Stable,
+    /// All the releases, including the prereleases.
+    Prerelease,
+}
+
+impl std::str::FromStr for Channel {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stable" => Ok(Self::Stable),
+            "prerelease" => Ok(Self::Prerelease),
+            _ => Err("must be one of two options: `stable` or `prerelease`."),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    prerelease: bool,
+    draft: bool,
+    assets: Vec<Asset>,
+}
+
+impl Release {
+    fn version(&self) -> Option<semver::Version> {
+        semver::Version::parse(self.tag_name.trim_start_matches('v')).ok()
+    }
+
+    fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
 
 impl SelfUpdate {
     /// Runs logic for the `self-update` subcommand
@@ -14,26 +91,277 @@ impl SelfUpdate {
         self.inner_execute().context("failed to self-update wasmer")
     }
 
-    #[cfg(not(target_os = "windows"))]
     fn inner_execute(&self) -> Result<()> {
-        println!("Fetching latest installer");
-        let cmd = Command::new("curl")
-            .arg("https://get.wasmer.io")
-            .arg("-sSfL")
-            .stdout(Stdio::piped())
-            .spawn()?;
-
-        let mut process = Command::new("sh")
-            .stdin(cmd.stdout.unwrap())
-            .stdout(Stdio::inherit())
-            .spawn()?;
-
-        process.wait().unwrap();
+        let current = semver::Version::parse(VERSION)?;
+        let release = self.find_release()?;
+        let version = release
+            .version()
+            .ok_or_else(|| anyhow!("invalid release tag `{}`", release.tag_name))?;
+
+        if self.check {
+            if version > current {
+                println!("A new version of wasmer is available: {current} -> {version}");
+            } else {
+                println!("wasmer {current} is up to date");
+            }
+            return Ok(());
+        }
+        if version <= current && self.version.is_none() && !self.force {
+            println!("wasmer {current} is up to date");
+            return Ok(());
+        }
+
+        let public_key = self
+            .public_key
+            .as_deref()
+            .or(RELEASE_PUBLIC_KEY)
+            .ok_or_else(|| {
+                anyhow!("this build of wasmer has no release key to verify the update with, pass one with `--public-key`")
+            })?;
+        let asset_name = release_asset_name()?;
+        let asset = release
+            .asset(&asset_name)
+            .ok_or_else(|| anyhow!("release {} has no `{asset_name}` asset", release.tag_name))?;
+        let checksum = release
+            .asset(&format!("{asset_name}.sha256"))
+            .ok_or_else(|| {
+                anyhow!(
+                    "release {} has no checksum for `{asset_name}`",
+                    release.tag_name
+                )
+            })?;
+        let signature = release
+            .asset(&format!("{asset_name}.minisig"))
+            .ok_or_else(|| {
+                anyhow!(
+                    "release {} has no signature for `{asset_name}`",
+                    release.tag_name
+                )
+            })?;
+
+        eprintln!("Downloading wasmer {version} ({asset_name})");
+        let archive = download(&asset.browser_download_url)?;
+        let checksum = String::from_utf8(download(&checksum.browser_download_url)?)
+            .context("the checksum file is not valid UTF-8")?;
+        verify_checksum(&archive, &checksum)?;
+        let signature = String::from_utf8(download(&signature.browser_download_url)?)
+            .context("the signature file is not valid UTF-8")?;
+        verify_signature(&archive, &signature, public_key)?;
+
+        let binary = extract_binary(&archive)?;
+        let current_exe = std::env::current_exe()
+            .and_then(|path| path.canonicalize())
+            .context("could not locate the current wasmer executable")?;
+        replace_executable(&current_exe, &binary)?;
+
+        eprintln!("Updated wasmer from {current} to {version}");
         Ok(())
     }
 
-    #[cfg(target_os = "windows")]
-    fn inner_execute(&self) -> Result<()> {
-        bail!("Self update is not supported on Windows. Use install instructions on the Wasmer homepage: https://wasmer.io");
+    /// Pick the release to install from the GitHub releases of the
+    /// channel.
+    fn find_release(&self) -> Result<Release> {
+        let mut request = http_client()?
+            .get(RELEASES_URL)
+            .header("Accept", "application/vnd.github.v3+json");
+        // Increases rate-limiting in GitHub CI
+        if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+            request = request.bearer_auth(token);
+        }
+        let releases: Vec<Release> = request
+            .send()
+            .and_then(|response| response.error_for_status())
+            .context("Could not lookup wasmer repository on Github.")?
+            .json()
+            .context("Could not parse the Github API response.")?;
+
+        let mut releases = releases
+            .into_iter()
+            .filter(|release| !release.draft)
+            .filter_map(|release| release.version().map(|version| (version, release)))
+            .collect::<Vec<_>>();
+
+        if let Some(version) = &self.version {
+            return releases
+                .into_iter()
+                .find(|(v, _)| v == version)
+                .map(|(_, release)| release)
+                .ok_or_else(|| anyhow!("could not find release version {version}"));
+        }
+
+        releases.retain(|(_, release)| self.channel == Channel::Prerelease || !release.prerelease);
+        releases
+            .into_iter()
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, release)| release)
+            .ok_or_else(|| anyhow!("no release found in the {:?} channel", self.channel))
+    }
+}
+
+fn http_client() -> Result<reqwest::blocking::Client> {
+    reqwest::blocking::Client::builder()
+        .user_agent("wasmerio")
+        .redirect(reqwest::redirect::Policy::limited(10))
+        .timeout(Duration::from_secs(300))
+        .build()
+        .map_err(anyhow::Error::new)
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    let response = http_client()?
+        .get(url)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("could not download `{url}`"))?;
+    Ok(response.bytes()?.to_vec())
+}
+
+/// The name of the release archive built for the platform wasmer is
+/// running on.
+fn release_asset_name() -> Result<String> {
+    let platform = match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") if cfg!(target_env = "musl") => "linux-musl-amd64",
+        ("linux", "x86_64") => "linux-amd64",
+        ("linux", "aarch64") => "linux-aarch64",
+        ("macos", "x86_64") => "darwin-amd64",
+        ("macos", "aarch64") => "darwin-arm64",
+        ("windows", "x86_64") => "windows-amd64",
+        (os, arch) => bail!("no prebuilt wasmer release for {os}-{arch}"),
+    };
+    Ok(format!("wasmer-{platform}.tar.gz"))
+}
+
+/// Check `contents` against a checksum file in the `sha256sum` format,
+/// i.e. the hexadecimal digest optionally followed by the file name.
+fn verify_checksum(contents: &[u8], checksum: &str) -> Result<()> {
+    use sha2::{Digest, Sha256};
+
+    let expected = checksum
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| anyhow!("the checksum file is empty"))?;
+    let actual = hex::encode(Sha256::digest(contents));
+    if !expected.eq_ignore_ascii_case(&actual) {
+        bail!("checksum mismatch: expected {expected}, got {actual}");
+    }
+    Ok(())
+}
+
+/// Check the minisign `signature` of `contents` with `public_key`.
+fn verify_signature(contents: &[u8], signature: &str, public_key: &str) -> Result<()> {
+    let public_key = minisign::PublicKey::from_base64(public_key)
+        .map_err(|e| anyhow!("invalid public key: {e}"))?;
+    let signature = minisign::SignatureBox::from_string(signature)
+        .map_err(|e| anyhow!("invalid signature: {e}"))?;
+    minisign::verify(
+        &public_key,
+        &signature,
+        std::io::Cursor::new(contents),
+        true,
+        false,
+        false,
+    )
+    .map_err(|e| anyhow!("signature verification failed: {e}"))
+}
+
+/// Extract the `bin/wasmer` executable out of a release archive.
+fn extract_binary(archive: &[u8]) -> Result<Vec<u8>> {
+    let binary_name = if cfg!(windows) {
+        "wasmer.exe"
+    } else {
+        "wasmer"
+    };
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if path.parent().and_then(Path::file_name) == Some("bin".as_ref())
+            && path.file_name() == Some(binary_name.as_ref())
+        {
+            let mut binary = Vec::new();
+            entry.read_to_end(&mut binary)?;
+            return Ok(binary);
+        }
+    }
+    bail!("the release archive does not contain `bin/{binary_name}`")
+}
+
+/// Atomically swap `current_exe` with `binary`, by writing it next to
+/// the executable and renaming it into place.
+fn replace_executable(current_exe: &Path, binary: &[u8]) -> Result<()> {
+    let staged = sibling_path(current_exe, "new");
+    fs::write(&staged, binary)
+        .with_context(|| format!("could not write `{}`", staged.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions = fs::metadata(current_exe)?.permissions().mode();
+        fs::set_permissions(&staged, fs::Permissions::from_mode(permissions | 0o111))?;
+    }
+
+    // A running executable can't be overwritten on Windows, but it can
+    // be renamed out of the way.
+    #[cfg(windows)]
+    {
+        let old = sibling_path(current_exe, "old");
+        let _ = fs::remove_file(&old);
+        fs::rename(current_exe, &old)
+            .with_context(|| format!("could not move `{}`", current_exe.display()))?;
+    }
+
+    if let Err(e) = fs::rename(&staged, current_exe) {
+        let _ = fs::remove_file(&staged);
+        return Err(e).with_context(|| format!("could not replace `{}`", current_exe.display()));
+    }
+    Ok(())
+}
+
+fn sibling_path(path: &Path, extension: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".");
+    file_name.push(extension);
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_checksum, verify_signature, Channel};
+
+    #[test]
+    fn test_verify_checksum() {
+        let digest = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert!(verify_checksum(b"hello", digest).is_ok());
+        assert!(
+            verify_checksum(b"hello", &format!("{digest}  wasmer-linux-amd64.tar.gz\n")).is_ok()
+        );
+        assert_eq!(
+            verify_checksum(b"world", digest).unwrap_err().to_string(),
+            format!(
+                "checksum mismatch: expected {digest}, got 486ea46224d1bb4fb680f34f7c9ad96a8f24ec88be73ea8e5a6c65260e9cb8a7"
+            )
+        );
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let keypair = minisign::KeyPair::generate_unencrypted_keypair().unwrap();
+        let public_key = keypair.pk.to_base64();
+        let signature = minisign::sign(Some(&keypair.pk), &keypair.sk, &b"hello"[..], None, None)
+            .unwrap()
+            .into_string();
+        assert!(verify_signature(b"hello", &signature, &public_key).is_ok());
+        assert!(verify_signature(b"world", &signature, &public_key).is_err());
+
+        let other_keypair = minisign::KeyPair::generate_unencrypted_keypair().unwrap();
+        let other_public_key = other_keypair.pk.to_base64();
+        assert!(verify_signature(b"hello", &signature, &other_public_key).is_err());
+    }
+
+    #[test]
+    fn test_parse_channel() {
+        assert_eq!("stable".parse::<Channel>(), Ok(Channel::Stable));
+        assert_eq!("prerelease".parse::<Channel>(), Ok(Channel::Prerelease));
+        assert!("nightly".parse::<Channel>().is_err());
     }
 }
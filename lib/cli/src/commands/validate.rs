@@ -1,6 +1,10 @@
+use crate::commands::OutputFormat;
+#[cfg(feature = "compiler")]
+use crate::common::{set_wasm_proposal, WASM_PROPOSALS};
 use crate::store::StoreOptions;
 use anyhow::{bail, Context, Result};
 use clap::Parser;
+use serde::Serialize;
 use std::path::PathBuf;
 use wasmer::*;
 
@@ -11,24 +15,207 @@ pub struct Validate {
     #[clap(name = "FILE", parse(from_os_str))]
     path: PathBuf,
 
+    /// Only allow the given WebAssembly proposals, e.g. `simd,threads`
+    ///
+    /// By default, the proposals enabled by the compiler are allowed.
+    #[clap(long, use_value_delimiter = true, value_delimiter = ',')]
+    features: Option<Vec<String>>,
+
+    /// The format of the result: `text` or `json`
+    #[clap(long = "format", default_value = "text")]
+    format: OutputFormat,
+
     #[clap(flatten)]
     store: StoreOptions,
 }
 
+/// The result of `wasmer validate --format json`.
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    path: &'a std::path::Path,
+    valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
 impl Validate {
     /// Runs logic for the `validate` subcommand
     pub fn execute(&self) -> Result<()> {
-        self.inner_execute()
-            .context(format!("failed to validate `{}`", self.path.display()))
+        let result = self
+            .inner_execute()
+            .context(format!("failed to validate `{}`", self.path.display()));
+        if self.format == OutputFormat::Json {
+            let report = Report {
+                path: &self.path,
+                valid: result.is_ok(),
+                error: result.as_ref().err().map(|error| format!("{:#}", error)),
+            };
+            println!("{}", serde_json::to_string(&report)?);
+            if !report.valid {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        result
     }
     fn inner_execute(&self) -> Result<()> {
         let (store, _compiler_type) = self.store.get_store()?;
         let module_contents = std::fs::read(&self.path)?;
+        // Modules in the text format are validated once converted, so the
+        // offsets of the errors are the ones of the binary format
+        #[cfg(feature = "wat")]
+        let module_contents = wat2wasm(&module_contents)?;
         if !is_wasm(&module_contents) {
             bail!("`wasmer validate` only validates WebAssembly files");
         }
-        Module::validate(&store, &module_contents)?;
-        eprintln!("Validation passed for `{}`.", self.path.display());
+        let result = match &self.features {
+            Some(proposals) => validate_with_proposals(&store, &module_contents, proposals)?,
+            None => Module::validate(&store, &module_contents),
+        };
+        if let Err(CompileError::Validate(message)) = &result {
+            bail!(diagnose(&module_contents, message));
+        }
+        result?;
+        if self.format == OutputFormat::Text {
+            eprintln!("Validation passed for `{}`.", self.path.display());
+        }
         Ok(())
     }
 }
+
+/// Validate `bytes` allowing only the given proposals.
+#[cfg(feature = "compiler")]
+fn validate_with_proposals(
+    store: &Store,
+    bytes: &[u8],
+    proposals: &[String],
+) -> Result<Result<(), CompileError>> {
+    let mut features = Features::new();
+    for proposal in WASM_PROPOSALS {
+        set_wasm_proposal(&mut features, proposal, false)?;
+    }
+    for proposal in proposals {
+        set_wasm_proposal(&mut features, proposal, true)?;
+    }
+    let engine = store.engine().inner();
+    let compiler = engine.compiler()?;
+    Ok(compiler.validate_module(&features, bytes))
+}
+
+#[cfg(not(feature = "compiler"))]
+fn validate_with_proposals(
+    _store: &Store,
+    _bytes: &[u8],
+    _proposals: &[String],
+) -> Result<Result<(), CompileError>> {
+    bail!("selecting the WebAssembly proposals requires a compiler")
+}
+
+/// Turn a validation error into a message locating the error in the module.
+fn diagnose(bytes: &[u8], message: &str) -> String {
+    let (reason, offset) = match message.rsplit_once(" (at offset ") {
+        Some((reason, offset)) => (
+            reason,
+            offset
+                .strip_suffix(')')
+                .and_then(|offset| offset.parse().ok()),
+        ),
+        None => (message, None),
+    };
+    match offset {
+        Some(offset) => match section_at(bytes, offset) {
+            Some(section) => format!("{} at offset {:#x}: {}", section, offset, reason),
+            None => format!("at offset {:#x}: {}", offset, reason),
+        },
+        None => reason.to_string(),
+    }
+}
+
+/// Name the section of the WebAssembly binary `bytes` containing `offset`.
+fn section_at(bytes: &[u8], offset: usize) -> Option<String> {
+    const HEADER_LEN: usize = 8;
+    if offset < HEADER_LEN {
+        return Some("module header".to_string());
+    }
+    let mut position = HEADER_LEN;
+    while position < bytes.len() {
+        let id = bytes[position];
+        let (size, size_len) = read_u32(bytes.get(position + 1..)?)?;
+        let start = position + 1 + size_len;
+        let end = start.checked_add(size as usize)?;
+        if offset < end {
+            let name = match id {
+                0 => {
+                    let (name_len, name_len_len) = read_u32(bytes.get(start..)?)?;
+                    let name_start = start + name_len_len;
+                    let name = bytes.get(name_start..name_start + name_len as usize)?;
+                    return Some(format!(
+                        "custom section `{}`",
+                        String::from_utf8_lossy(name)
+                    ));
+                }
+                1 => "type",
+                2 => "import",
+                3 => "function",
+                4 => "table",
+                5 => "memory",
+                6 => "global",
+                7 => "export",
+                8 => "start",
+                9 => "element",
+                10 => "code",
+                11 => "data",
+                12 => "data count",
+                13 => "tag",
+                _ => return Some(format!("unknown section {}", id)),
+            };
+            return Some(format!("{} section", name));
+        }
+        position = end;
+    }
+    None
+}
+
+/// Read an unsigned LEB128 encoded `u32`, returning it with its length.
+fn read_u32(bytes: &[u8]) -> Option<(u32, usize)> {
+    let mut result = 0u32;
+    for (i, byte) in bytes.iter().take(5).enumerate() {
+        result |= u32::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((result, i + 1));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnose() {
+        // (module (func (result i32) (i64.const 0)) (@custom "name" ""))
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01,
+            0x7f, 0x03, 0x02, 0x01, 0x00, 0x0a, 0x06, 0x01, 0x04, 0x00, 0x42, 0x00, 0x0b, 0x00,
+            0x05, 0x04, b'n', b'a', b'm', b'e',
+        ];
+        assert_eq!(
+            diagnose(&bytes, "type mismatch (at offset 26)"),
+            "code section at offset 0x1a: type mismatch"
+        );
+        assert_eq!(
+            diagnose(&bytes, "malformed (at offset 30)"),
+            "custom section `name` at offset 0x1e: malformed"
+        );
+        assert_eq!(
+            diagnose(&bytes, "bad magic (at offset 0)"),
+            "module header at offset 0x0: bad magic"
+        );
+        assert_eq!(
+            diagnose(&bytes, "unexpected end (at offset 64)"),
+            "at offset 0x40: unexpected end"
+        );
+        assert_eq!(diagnose(&bytes, "invalid"), "invalid");
+    }
+}
@@ -1,5 +1,7 @@
 //! Common module with common used structures across different
 //! commands.
+#[cfg(feature = "cache")]
+use crate::store::CompilerType;
 use crate::VERSION;
 use clap::Parser;
 use std::env;
@@ -52,6 +54,63 @@ pub fn get_cache_dir() -> PathBuf {
     }
 }
 
+/// Get the filesystem cache of the artifacts compiled with `compiler_type`
+#[cfg(feature = "cache")]
+pub(crate) fn get_compiler_cache(
+    compiler_type: &CompilerType,
+) -> anyhow::Result<wasmer_cache::FileSystemCache> {
+    let mut cache_dir_root = get_cache_dir();
+    cache_dir_root.push(compiler_type.to_string());
+    let mut cache = wasmer_cache::FileSystemCache::new(cache_dir_root)?;
+
+    let extension = "wasmu";
+    cache.set_cache_extension(Some(extension));
+    Ok(cache)
+}
+
+/// The names of the WebAssembly proposals that can be toggled, as
+/// accepted on the command line.
+pub(crate) const WASM_PROPOSALS: [&str; 11] = [
+    "threads",
+    "reference-types",
+    "simd",
+    "bulk-memory",
+    "multi-value",
+    "tail-call",
+    "multi-memory",
+    "memory64",
+    "exceptions",
+    "relaxed-simd",
+    "extended-const",
+];
+
+/// Enable or disable the WebAssembly proposal called `name`.
+pub(crate) fn set_wasm_proposal(
+    features: &mut wasmer::Features,
+    name: &str,
+    enable: bool,
+) -> anyhow::Result<()> {
+    match name {
+        "threads" => features.threads = enable,
+        "reference-types" => features.reference_types = enable,
+        "simd" => features.simd = enable,
+        "bulk-memory" => features.bulk_memory = enable,
+        "multi-value" => features.multi_value = enable,
+        "tail-call" => features.tail_call = enable,
+        "multi-memory" => features.multi_memory = enable,
+        "memory64" => features.memory64 = enable,
+        "exceptions" => features.exceptions = enable,
+        "relaxed-simd" => features.relaxed_simd = enable,
+        "extended-const" => features.extended_const = enable,
+        _ => anyhow::bail!(
+            "unknown WebAssembly proposal `{}`, expected one of: {}",
+            name,
+            WASM_PROPOSALS.join(", ")
+        ),
+    }
+    Ok(())
+}
+
 pub(crate) fn normalize_path(s: &str) -> String {
     wasmer_registry::utils::normalize_path(s)
 }
@@ -2,7 +2,7 @@
 
 use anyhow::{Chain, Error};
 use colored::*;
-use std::fmt::{self, Debug, Write};
+use std::fmt::{self, Debug, Display, Write};
 use wasmer::RuntimeError;
 
 /// A `PrettyError` for printing `anyhow::Error` nicely.
@@ -26,22 +26,80 @@ impl PrettyError {
         std::process::exit(match result {
             Ok(_t) => 0,
             Err(error) => {
-                let runtime: Option<&RuntimeError> = error.downcast_ref();
-                let trapcode = runtime.map(|e| e.clone().to_trap());
-                eprintln!("{:?}", PrettyError { error });
-                // we don't use process:abort() here to avoid message from rust
-                // that could interfer with testing tools
-                // but still exit with the expected error code
-                match trapcode {
-                    #[cfg(target_os = "windows")]
-                    Some(_) => 3,
-                    #[cfg(not(target_os = "windows"))]
-                    Some(_) => 128 + libc::SIGABRT,
-                    _ => 1,
-                }
+                let code = Self::exit_code(&error);
+                Self::print(error);
+                code
             }
         });
     }
+
+    /// Print an error without exiting the process
+    pub fn print(error: Error) {
+        eprintln!("{:?}", PrettyError { error });
+    }
+
+    /// The code the process exits with because of `error`
+    pub fn exit_code(error: &Error) -> i32 {
+        let runtime: Option<&RuntimeError> = error.downcast_ref();
+        let trapcode = runtime.map(|e| e.clone().to_trap());
+        // we don't use process:abort() here to avoid message from rust
+        // that could interfer with testing tools
+        // but still exit with the expected error code
+        match trapcode {
+            #[cfg(target_os = "windows")]
+            Some(_) => 3,
+            #[cfg(not(target_os = "windows"))]
+            Some(_) => 128 + libc::SIGABRT,
+            _ => 1,
+        }
+    }
+}
+
+/// How the backtraces of the runtime errors are printed, set like
+/// `RUST_BACKTRACE` with the `WASMER_BACKTRACE` environment variable:
+/// `0` prints none, `full` prints the host frames along the WebAssembly
+/// frames, and anything else the WebAssembly frames only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BacktraceStyle {
+    Off,
+    Wasm,
+    Full,
+}
+
+impl BacktraceStyle {
+    fn from_env() -> Self {
+        match std::env::var("WASMER_BACKTRACE").as_deref() {
+            Ok("0") => Self::Off,
+            Ok("full") => Self::Full,
+            _ => Self::Wasm,
+        }
+    }
+}
+
+/// Displays an error, with its backtrace if it's a `RuntimeError`.
+struct ErrorDisplay<'a> {
+    error: &'a (dyn std::error::Error + 'static),
+    style: BacktraceStyle,
+}
+
+impl Display for ErrorDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let runtime_error = match self.error.downcast_ref::<RuntimeError>() {
+            Some(runtime_error) => runtime_error,
+            None => return write!(f, "{}", self.error),
+        };
+        match self.style {
+            BacktraceStyle::Wasm => write!(f, "{}", runtime_error),
+            BacktraceStyle::Off => write!(f, "RuntimeError: {}", runtime_error.message()),
+            BacktraceStyle::Full => {
+                write!(f, "RuntimeError: {}", runtime_error.message())?;
+                for frame in runtime_error.backtrace() {
+                    write!(f, "\n    at {}", frame)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 impl Debug for PrettyError {
@@ -52,7 +110,12 @@ impl Debug for PrettyError {
             return Debug::fmt(&error, f);
         }
 
-        write!(f, "{}", format!("{}: {}", "error".red(), error).bold())?;
+        let style = BacktraceStyle::from_env();
+        let top = ErrorDisplay {
+            error: &**error,
+            style,
+        };
+        write!(f, "{}", format!("{}: {}", "error".red(), top).bold())?;
         // write!(f, "{}", error)?;
 
         if let Some(cause) = error.source() {
@@ -67,7 +130,7 @@ impl Debug for PrettyError {
                     is_last: n == total_errors - 1,
                     started: false,
                 };
-                write!(indented, "{}", error)?;
+                write!(indented, "{}", ErrorDisplay { error, style })?;
             }
         }
         Ok(())
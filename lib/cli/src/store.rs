@@ -8,6 +8,7 @@ use crate::common::WasmFeatures;
 use clap::Parser;
 #[allow(unused_imports)]
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::string::ToString;
 #[allow(unused_imports)]
 use std::sync::Arc;
@@ -40,6 +41,12 @@ pub struct CompilerOptions {
     #[clap(long, conflicts_with_all = &["singlepass", "cranelift"])]
     llvm: bool,
 
+    /// Use the given compiler: `singlepass`, `cranelift`, `llvm` or
+    /// `interpreter`, or `auto` to choose it according to the size and
+    /// features of the module.
+    #[clap(long, conflicts_with_all = &["singlepass", "cranelift", "llvm"])]
+    backend: Option<Backend>,
+
     /// Enable compiler internal verification.
     #[clap(long)]
     #[cfg(any(feature = "singlepass", feature = "cranelift", feature = "llvm"))]
@@ -54,31 +61,74 @@ pub struct CompilerOptions {
     features: WasmFeatures,
 }
 
+/// Modules at least this large are compiled with Singlepass by
+/// `--backend auto`, as it compiles them much faster than the optimizing
+/// compilers do.
+#[cfg(all(
+    feature = "singlepass",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+const AUTO_SINGLEPASS_MIN_SIZE: usize = 10 * 1024 * 1024;
+
 #[cfg(feature = "compiler")]
 impl CompilerOptions {
     fn get_compiler(&self) -> Result<CompilerType> {
-        if self.cranelift {
+        if let Some(Backend::Compiler(backend)) = &self.backend {
+            Ok(backend.clone())
+        } else if self.cranelift {
             Ok(CompilerType::Cranelift)
         } else if self.llvm {
             Ok(CompilerType::LLVM)
         } else if self.singlepass {
             Ok(CompilerType::Singlepass)
         } else {
-            // Auto mode, we choose the best compiler for that platform
-            cfg_if::cfg_if! {
-                if #[cfg(all(feature = "cranelift", any(target_arch = "x86_64", target_arch = "aarch64")))] {
-                    Ok(CompilerType::Cranelift)
-                }
-                else if #[cfg(all(feature = "singlepass", target_arch = "x86_64"))] {
-                    Ok(CompilerType::Singlepass)
-                }
-                else if #[cfg(feature = "llvm")] {
-                    Ok(CompilerType::LLVM)
-                } else {
-                    bail!("There are no available compilers for your architecture");
+            Self::default_compiler()
+        }
+    }
+
+    /// The best compiler for the platform
+    fn default_compiler() -> Result<CompilerType> {
+        cfg_if::cfg_if! {
+            if #[cfg(all(feature = "cranelift", any(target_arch = "x86_64", target_arch = "aarch64")))] {
+                Ok(CompilerType::Cranelift)
+            }
+            else if #[cfg(all(feature = "singlepass", target_arch = "x86_64"))] {
+                Ok(CompilerType::Singlepass)
+            }
+            else if #[cfg(feature = "llvm")] {
+                Ok(CompilerType::LLVM)
+            } else {
+                bail!("There are no available compilers for your architecture");
+            }
+        }
+    }
+
+    /// The compiler `--backend auto` chooses for `wasm`: Singlepass for
+    /// the large modules it supports, the best compiler for the platform
+    /// otherwise
+    #[allow(unused_variables)]
+    fn auto_compiler(&self, wasm: &[u8]) -> Result<CompilerType> {
+        #[cfg(all(
+            feature = "singlepass",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))]
+        {
+            if wasm.len() >= AUTO_SINGLEPASS_MIN_SIZE {
+                let config = wasmer_compiler_singlepass::Singlepass::new();
+                let mut features =
+                    self.get_features(config.default_features_for_target(&Target::default()))?;
+                // Singlepass doesn't implement SIMD
+                features.simd(false);
+                if Box::new(config)
+                    .compiler()
+                    .validate_module(&features, wasm)
+                    .is_ok()
+                {
+                    return Ok(CompilerType::Singlepass);
                 }
             }
         }
+        Self::default_compiler()
     }
 
     /// Get the enaled Wasm features.
@@ -249,7 +299,14 @@ impl CompilerOptions {
                 }
                 Box::new(config)
             }
-            #[cfg(not(all(feature = "singlepass", feature = "cranelift", feature = "llvm",)))]
+            #[cfg(feature = "interpreter")]
+            CompilerType::Interpreter => Box::new(wasmer_compiler_interpreter::Interpreter::new()),
+            #[cfg(not(all(
+                feature = "singlepass",
+                feature = "cranelift",
+                feature = "llvm",
+                feature = "interpreter",
+            )))]
             compiler => {
                 bail!(
                     "The `{}` compiler is not included in this binary.",
@@ -264,7 +321,7 @@ impl CompilerOptions {
 }
 
 /// The compiler used for the store
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CompilerType {
     /// Singlepass compiler
     Singlepass,
@@ -272,6 +329,8 @@ pub enum CompilerType {
     Cranelift,
     /// LLVM compiler
     LLVM,
+    /// Interpreter, which doesn't generate machine code
+    Interpreter,
     /// Headless compiler
     Headless,
 }
@@ -286,6 +345,8 @@ impl CompilerType {
             Self::Cranelift,
             #[cfg(feature = "llvm")]
             Self::LLVM,
+            #[cfg(feature = "interpreter")]
+            Self::Interpreter,
         ]
     }
 }
@@ -296,19 +357,127 @@ impl ToString for CompilerType {
             Self::Singlepass => "singlepass".to_string(),
             Self::Cranelift => "cranelift".to_string(),
             Self::LLVM => "llvm".to_string(),
+            Self::Interpreter => "interpreter".to_string(),
             Self::Headless => "headless".to_string(),
         }
     }
 }
 
+impl FromStr for CompilerType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "singlepass" => Ok(Self::Singlepass),
+            "cranelift" => Ok(Self::Cranelift),
+            "llvm" => Ok(Self::LLVM),
+            "interpreter" => Ok(Self::Interpreter),
+            _ => anyhow::bail!(
+                "unknown compiler `{}`, expected `singlepass`, `cranelift`, `llvm` or `interpreter`",
+                s
+            ),
+        }
+    }
+}
+
+/// The value of the `--backend` option
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backend {
+    /// Choose the compiler according to the module to compile
+    Auto,
+    /// Use the given compiler
+    Compiler(CompilerType),
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            _ => s.parse().map(Self::Compiler).map_err(|_| {
+                anyhow::anyhow!(
+                    "unknown backend `{}`, expected `auto`, `singlepass`, `cranelift`, `llvm` or `interpreter`",
+                    s
+                )
+            }),
+        }
+    }
+}
+
 #[cfg(all(feature = "compiler"))]
 impl StoreOptions {
+    /// Resolves `--backend auto` into the compiler best suited to `wasm`
+    pub fn for_module(&self, wasm: &[u8]) -> Result<Self> {
+        let mut options = self.clone();
+        if options.compiler.backend == Some(Backend::Auto) {
+            let compiler = options.compiler.auto_compiler(wasm)?;
+            options.compiler.backend = Some(Backend::Compiler(compiler));
+        }
+        Ok(options)
+    }
+
+    /// The same options, compiling with `compiler` instead
+    pub fn with_compiler(&self, compiler: CompilerType) -> Self {
+        let mut options = self.clone();
+        options.compiler.backend = Some(Backend::Compiler(compiler));
+        options
+    }
+
+    /// Whether a compiler was explicitly chosen, including with
+    /// `--backend auto`, as opposed to left to the default
+    pub fn is_compiler_selected(&self) -> bool {
+        let compiler = &self.compiler;
+        compiler.singlepass || compiler.cranelift || compiler.llvm || compiler.backend.is_some()
+    }
+
+    /// Gets the compiler selected by the options
+    pub fn get_compiler(&self) -> Result<CompilerType> {
+        self.compiler.get_compiler()
+    }
+
+    /// The command line arguments selecting the same compiler and
+    /// features as these options
+    pub fn to_args(&self) -> Result<Vec<String>> {
+        let features = &self.compiler.features;
+        let mut args = vec![format!("--backend={}", self.get_compiler()?.to_string())];
+        for (enabled, flag) in [
+            (features.simd, "--enable-simd"),
+            (features.threads, "--enable-threads"),
+            (features.reference_types, "--enable-reference-types"),
+            (features.multi_value, "--enable-multi-value"),
+            (features.bulk_memory, "--enable-bulk-memory"),
+            (features.all, "--enable-all"),
+        ] {
+            if enabled {
+                args.push(flag.to_string());
+            }
+        }
+        Ok(args)
+    }
+
     /// Gets the store for the host target, with the compiler name selected
     pub fn get_store(&self) -> Result<(Store, CompilerType)> {
         let target = Target::default();
         self.get_store_for_target(target)
     }
 
+    /// Gets the store for the host target, with the compiler name selected,
+    /// compiling the modules with the given middlewares
+    pub fn get_store_with_middlewares(
+        &self,
+        middlewares: impl IntoIterator<Item = Arc<dyn ModuleMiddleware>>,
+    ) -> Result<(Store, CompilerType)> {
+        let target = Target::default();
+        let (mut compiler_config, compiler_type) = self.compiler.get_compiler_config()?;
+        for middleware in middlewares {
+            compiler_config.push_middleware(middleware);
+        }
+        let engine = self.get_engine_with_compiler(target, compiler_config)?;
+        let store = Store::new(engine);
+        Ok((store, compiler_type))
+    }
+
     /// Gets the store for a given target, with the compiler name selected.
     pub fn get_store_for_target(&self, target: Target) -> Result<(Store, CompilerType)> {
         let (compiler_config, compiler_type) = self.compiler.get_compiler_config()?;
@@ -1,7 +1,8 @@
 //! Utility functions for the WebAssembly module
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use std::env;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// Whether or not Wasmer should print with color
 pub fn wasmer_should_print_color() -> bool {
@@ -14,8 +15,11 @@ pub fn wasmer_should_print_color() -> bool {
 fn retrieve_alias_pathbuf(alias: &str, real_dir: &str) -> Result<(String, PathBuf)> {
     let pb = PathBuf::from(&real_dir);
     if let Ok(pb_metadata) = pb.metadata() {
-        if !pb_metadata.is_dir() {
-            bail!("\"{}\" exists, but it is not a directory", &real_dir);
+        if !pb_metadata.is_dir() && !pb_metadata.is_file() {
+            bail!(
+                "\"{}\" exists, but it is neither a directory nor a file",
+                &real_dir
+            );
         }
     } else {
         bail!("Directory \"{}\" does not exist", &real_dir);
@@ -23,21 +27,47 @@ fn retrieve_alias_pathbuf(alias: &str, real_dir: &str) -> Result<(String, PathBu
     Ok((alias.to_string(), pb))
 }
 
-/// Parses a mapdir from a string
-pub fn parse_mapdir(entry: &str) -> Result<(String, PathBuf)> {
-    // We try first splitting by `::`
-    if let [alias, real_dir] = entry.split("::").collect::<Vec<&str>>()[..] {
-        retrieve_alias_pathbuf(alias, real_dir)
+/// Splits the `:ro` suffix marking a directory as read-only off an entry.
+fn split_read_only(entry: &str) -> (&str, bool) {
+    match entry.strip_suffix(":ro") {
+        Some(entry) => (entry, true),
+        None => (entry, false),
     }
-    // And then we try splitting by `:` (for compatibility with previous API)
-    else if let [alias, real_dir] = entry.splitn(2, ':').collect::<Vec<&str>>()[..] {
-        retrieve_alias_pathbuf(alias, real_dir)
-    } else {
-        bail!(
-            "Directory mappings must consist of two paths separate by a `::` or `:`. Found {}",
-            &entry
-        )
+}
+
+/// Parses a pre-opened directory from a string, with an optional `:ro`
+/// suffix marking it as read-only
+pub fn parse_dir(entry: &str) -> Result<(PathBuf, bool)> {
+    let (dir, read_only) = split_read_only(entry);
+    if dir.is_empty() {
+        bail!("Expected a directory before `:ro`. Found {}", &entry);
     }
+    Ok((PathBuf::from(dir), read_only))
+}
+
+/// Parses a mapdir from a string, with an optional `:ro` suffix marking
+/// it as read-only. The host path may be a directory or a single file.
+pub fn parse_mapdir(entry: &str) -> Result<(String, PathBuf, bool)> {
+    let (mapping, read_only) = match split_read_only(entry) {
+        // `alias:ro` and `alias::ro` map the host directory `ro`
+        (mapping, true) if mapping.contains(':') && !mapping.ends_with(':') => (mapping, true),
+        _ => (entry, false),
+    };
+    // We try first splitting by `::`
+    let (alias, real_dir) =
+        if let [alias, real_dir] = mapping.split("::").collect::<Vec<&str>>()[..] {
+            retrieve_alias_pathbuf(alias, real_dir)?
+        }
+        // And then we try splitting by `:` (for compatibility with previous API)
+        else if let [alias, real_dir] = mapping.splitn(2, ':').collect::<Vec<&str>>()[..] {
+            retrieve_alias_pathbuf(alias, real_dir)?
+        } else {
+            bail!(
+                "Directory mappings must consist of two paths separate by a `::` or `:`. Found {}",
+                &entry
+            )
+        };
+    Ok((alias, real_dir, read_only))
 }
 
 /// Parses an environment variable.
@@ -64,9 +94,109 @@ pub fn parse_envvar(entry: &str) -> Result<(String, String)> {
     }
 }
 
+/// Parses the contents of an env file: one `KEY=VALUE` per line, with
+/// optional `export` prefixes, `#` comments and single or double quoted
+/// values.
+pub fn parse_env_file(contents: &str) -> Result<Vec<(String, String)>> {
+    let mut vars = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim_start()),
+            None => bail!(
+                "line {}: expected `<name>=<value>`, found `{}`",
+                index + 1,
+                line
+            ),
+        };
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            bail!("line {}: invalid variable name `{}`", index + 1, key);
+        }
+        let value = parse_env_file_value(value)
+            .with_context(|| format!("line {}: invalid value for `{}`", index + 1, key))?;
+        vars.push((key.to_string(), value));
+    }
+    Ok(vars)
+}
+
+/// Parses the value of a variable of an env file.
+fn parse_env_file_value(value: &str) -> Result<String> {
+    let (value, rest) = if let Some(quoted) = value.strip_prefix('\'') {
+        match quoted.split_once('\'') {
+            Some((value, rest)) => (value.to_string(), rest),
+            None => bail!("missing closing quote"),
+        }
+    } else if let Some(quoted) = value.strip_prefix('"') {
+        let mut unescaped = String::new();
+        let mut chars = quoted.char_indices();
+        let rest = loop {
+            match chars.next() {
+                Some((i, '"')) => break &quoted[i + 1..],
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, 'n')) => unescaped.push('\n'),
+                    Some((_, 't')) => unescaped.push('\t'),
+                    Some((_, 'r')) => unescaped.push('\r'),
+                    Some((_, c)) => unescaped.push(c),
+                    None => bail!("missing closing quote"),
+                },
+                Some((_, c)) => unescaped.push(c),
+                None => bail!("missing closing quote"),
+            }
+        };
+        (unescaped, rest)
+    } else {
+        // Unquoted values end at the first ` #` comment
+        let value = match value.find(" #") {
+            Some(comment) => &value[..comment],
+            None => value,
+        };
+        return Ok(value.trim_end().to_string());
+    };
+    let rest = rest.trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        bail!("unexpected `{}` after the closing quote", rest);
+    }
+    Ok(value)
+}
+
+/// Parses a duration made of a number and a unit: `s`, `m`, `h`, `d` or `w`,
+/// e.g. `30m` or `7d`.
+pub fn parse_duration(entry: &str) -> Result<Duration> {
+    let entry = entry.trim();
+    let unit_start = entry
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(entry.len());
+    let (amount, unit) = entry.split_at(unit_start);
+    let amount: u64 = match amount.parse() {
+        Ok(amount) => amount,
+        Err(_) => bail!("Durations must start with a number, e.g. `7d`; found `{}`", entry),
+    };
+    let seconds = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => bail!(
+            "Durations must end with a unit among `s`, `m`, `h`, `d` and `w`; found `{}`",
+            entry
+        ),
+    };
+    match amount.checked_mul(seconds) {
+        Some(seconds) => Ok(Duration::from_secs(seconds)),
+        None => bail!("The duration `{}` is too long", entry),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::parse_envvar;
+    use super::{parse_dir, parse_duration, parse_env_file, parse_envvar, parse_mapdir};
+    use std::path::PathBuf;
+    use std::time::Duration;
 
     #[test]
     fn test_parse_envvar() {
@@ -89,4 +219,92 @@ mod tests {
             ("A".into(), "B=C=D".into())
         );
     }
+
+    #[test]
+    fn test_parse_dir() {
+        assert_eq!(parse_dir("data").unwrap(), (PathBuf::from("data"), false));
+        assert_eq!(parse_dir("data:ro").unwrap(), (PathBuf::from("data"), true));
+        assert!(parse_dir(":ro").is_err());
+    }
+
+    #[test]
+    fn test_parse_mapdir() {
+        assert_eq!(
+            parse_mapdir("/data:.").unwrap(),
+            ("/data".into(), PathBuf::from("."), false)
+        );
+        assert_eq!(
+            parse_mapdir("/data::.:ro").unwrap(),
+            ("/data".into(), PathBuf::from("."), true)
+        );
+        assert_eq!(
+            parse_mapdir("/data:.:ro").unwrap(),
+            ("/data".into(), PathBuf::from("."), true)
+        );
+        assert_eq!(
+            parse_mapdir("/data:ro").unwrap_err().to_string(),
+            "Directory \"ro\" does not exist"
+        );
+        assert_eq!(
+            parse_mapdir("/Cargo.toml:Cargo.toml:ro").unwrap(),
+            ("/Cargo.toml".into(), PathBuf::from("Cargo.toml"), true)
+        );
+    }
+
+    #[test]
+    fn test_parse_env_file() {
+        let contents = r#"
+# A comment
+A=B
+export C = D E # a comment
+QUOTED="a \"quoted\"\nvalue" # a comment
+SINGLE='no \n escapes'
+EMPTY=
+URL=http://localhost/#anchor
+"#;
+        assert_eq!(
+            parse_env_file(contents).unwrap(),
+            vec![
+                ("A".into(), "B".into()),
+                ("C".into(), "D E".into()),
+                ("QUOTED".into(), "a \"quoted\"\nvalue".into()),
+                ("SINGLE".into(), "no \\n escapes".into()),
+                ("EMPTY".into(), "".into()),
+                ("URL".into(), "http://localhost/#anchor".into()),
+            ]
+        );
+        assert_eq!(
+            parse_env_file("A=B\nC").unwrap_err().to_string(),
+            "line 2: expected `<name>=<value>`, found `C`"
+        );
+        assert_eq!(
+            parse_env_file("A=\"B").unwrap_err().to_string(),
+            "line 1: invalid value for `A`"
+        );
+        assert_eq!(
+            parse_env_file("A B=C").unwrap_err().to_string(),
+            "line 1: invalid variable name `A B`"
+        );
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("45s").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(30 * 60));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(7 * 86400));
+        assert_eq!(
+            parse_duration("d").unwrap_err().to_string(),
+            "Durations must start with a number, e.g. `7d`; found `d`"
+        );
+        assert_eq!(
+            parse_duration("7").unwrap_err().to_string(),
+            "Durations must end with a unit among `s`, `m`, `h`, `d` and `w`; found `7`"
+        );
+        assert_eq!(
+            parse_duration("18446744073709551615w")
+                .unwrap_err()
+                .to_string(),
+            "The duration `18446744073709551615w` is too long"
+        );
+    }
 }
@@ -6,7 +6,8 @@ use crate::config::Cranelift;
 use crate::dwarf::WriterRelocate;
 use crate::func_environ::{get_function_name, FuncEnvironment};
 use crate::trampoline::{
-    make_trampoline_dynamic_function, make_trampoline_function_call, FunctionBuilderContext,
+    make_compile_stub, make_trampoline_dynamic_function, make_trampoline_function_call,
+    FunctionBuilderContext,
 };
 use crate::translator::{
     compiled_function_unwind_info, irlibcall_to_libcall, irreloc_to_relocationkind,
@@ -27,9 +28,9 @@ use wasmer_compiler::{
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
     CallingConvention, Compilation, CompileError, CompileModuleInfo, CompiledFunction,
-    CompiledFunctionFrameInfo, CompiledFunctionUnwindInfo, Dwarf, FunctionBody, FunctionIndex,
-    LocalFunctionIndex, ModuleInfo, Relocation, RelocationTarget, SectionIndex, SignatureIndex,
-    Target, TrapCode, TrapInformation,
+    CompiledFunctionFrameInfo, CompiledFunctionUnwindInfo, CustomSections, Dwarf, FunctionBody,
+    FunctionIndex, LocalFunctionIndex, ModuleInfo, Relocation, RelocationTarget, SectionIndex,
+    SignatureIndex, Target, TrapCode, TrapInformation, VMOffsets,
 };
 
 /// A compiler that compiles a WebAssembly module with Cranelift, translating the Wasm to Cranelift IR,
@@ -55,6 +56,10 @@ impl Compiler for CraneliftCompiler {
         "cranelift"
     }
 
+    fn deterministic_id(&self) -> String {
+        format!("cranelift-{}", self.config.deterministic_id())
+    }
+
     /// Get the middlewares for this compiler
     fn get_middlewares(&self) -> &[Arc<dyn ModuleMiddleware>] {
         &self.config.middlewares
@@ -69,6 +74,124 @@ impl Compiler for CraneliftCompiler {
         module_translation_state: &ModuleTranslationState,
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
     ) -> Result<Compilation, CompileError> {
+        let isa = self
+            .config()
+            .isa(target)
+            .map_err(|error| CompileError::Codegen(error.to_string()))?;
+        let frontend_config = isa.frontend_config();
+        let module = &compile_info.module;
+
+        let (functions, custom_sections, dwarf) = self.compile_functions(
+            target,
+            compile_info,
+            module_translation_state,
+            function_body_inputs.iter().collect(),
+            self.config.enable_lazy_compilation,
+        )?;
+
+        // function call trampolines (only for local functions, by signature)
+        #[cfg(not(feature = "rayon"))]
+        let mut cx = FunctionBuilderContext::new();
+        #[cfg(not(feature = "rayon"))]
+        let function_call_trampolines = module
+            .signatures
+            .values()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|sig| make_trampoline_function_call(&*isa, &mut cx, sig))
+            .collect::<Result<Vec<FunctionBody>, CompileError>>()?
+            .into_iter()
+            .collect::<PrimaryMap<SignatureIndex, FunctionBody>>();
+        #[cfg(feature = "rayon")]
+        let function_call_trampolines = module
+            .signatures
+            .values()
+            .collect::<Vec<_>>()
+            .par_iter()
+            .map_init(FunctionBuilderContext::new, |cx, sig| {
+                make_trampoline_function_call(&*isa, cx, sig)
+            })
+            .collect::<Result<Vec<FunctionBody>, CompileError>>()?
+            .into_iter()
+            .collect::<PrimaryMap<SignatureIndex, FunctionBody>>();
+
+        let offsets = VMOffsets::new_for_trampolines(frontend_config.pointer_bytes());
+        // dynamic function trampolines (only for imported functions)
+        #[cfg(not(feature = "rayon"))]
+        let mut cx = FunctionBuilderContext::new();
+        #[cfg(not(feature = "rayon"))]
+        let dynamic_function_trampolines = module
+            .imported_function_types()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|func_type| make_trampoline_dynamic_function(&*isa, &offsets, &mut cx, &func_type))
+            .collect::<Result<Vec<_>, CompileError>>()?
+            .into_iter()
+            .collect::<PrimaryMap<FunctionIndex, FunctionBody>>();
+        #[cfg(feature = "rayon")]
+        let dynamic_function_trampolines = module
+            .imported_function_types()
+            .collect::<Vec<_>>()
+            .par_iter()
+            .map_init(FunctionBuilderContext::new, |cx, func_type| {
+                make_trampoline_dynamic_function(&*isa, &offsets, cx, func_type)
+            })
+            .collect::<Result<Vec<_>, CompileError>>()?
+            .into_iter()
+            .collect::<PrimaryMap<FunctionIndex, FunctionBody>>();
+
+        Ok(Compilation {
+            functions: functions.into_iter().collect(),
+            custom_sections,
+            function_call_trampolines,
+            dynamic_function_trampolines,
+            debug: dwarf,
+        })
+    }
+
+    fn compiles_lazily(&self) -> bool {
+        self.config.enable_lazy_compilation
+    }
+
+    /// Compile a function of a module compiled lazily using Cranelift.
+    fn compile_function(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        index: LocalFunctionIndex,
+        function_body_input: &FunctionBodyData<'_>,
+    ) -> Result<Compilation, CompileError> {
+        let (functions, custom_sections, dwarf) = self.compile_functions(
+            target,
+            compile_info,
+            module_translation_state,
+            vec![(index, function_body_input)],
+            false,
+        )?;
+        Ok(Compilation {
+            functions: functions.into_iter().collect(),
+            custom_sections,
+            function_call_trampolines: PrimaryMap::new(),
+            dynamic_function_trampolines: PrimaryMap::new(),
+            debug: dwarf,
+        })
+    }
+}
+
+impl CraneliftCompiler {
+    /// Compile the functions `function_body_inputs` of a module, or their
+    /// compile stubs if `stubs` is set, with the custom section holding
+    /// their unwind information.
+    #[allow(clippy::type_complexity)]
+    fn compile_functions(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        function_body_inputs: Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>,
+        stubs: bool,
+    ) -> Result<(Vec<CompiledFunction>, CustomSections, Option<Dwarf>), CompileError> {
         let isa = self
             .config()
             .isa(target)
@@ -82,6 +205,7 @@ impl Compiler for CraneliftCompiler {
             .iter()
             .map(|(_sig_index, func_type)| signature_to_cranelift_ir(func_type, frontend_config))
             .collect::<PrimaryMap<SignatureIndex, ir::Signature>>();
+        let offsets = VMOffsets::new(frontend_config.pointer_bytes(), module);
 
         // Generate the frametable
         #[cfg(feature = "unwind")]
@@ -113,8 +237,6 @@ impl Compiler for CraneliftCompiler {
         let mut func_translator = FuncTranslator::new();
         #[cfg(not(feature = "rayon"))]
         let (functions, fdes): (Vec<CompiledFunction>, Vec<_>) = function_body_inputs
-            .iter()
-            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
             .into_iter()
             .map(|(i, input)| {
                 let func_index = module.func_index(i);
@@ -125,6 +247,8 @@ impl Compiler for CraneliftCompiler {
                     &signatures,
                     &memory_styles,
                     &table_styles,
+                    self.config.enable_soft_float,
+                    self.config.enable_lazy_compilation,
                 );
                 context.func.name = match get_function_name(func_index) {
                     ExternalName::User(nameref) => {
@@ -149,14 +273,29 @@ impl Compiler for CraneliftCompiler {
                         .middlewares
                         .generate_function_middleware_chain(i),
                 );
+                reader.set_num_params(
+                    module.signatures[module.functions[func_index]]
+                        .params()
+                        .len() as u32,
+                );
 
-                func_translator.translate(
-                    module_translation_state,
-                    &mut reader,
-                    &mut context.func,
-                    &mut func_env,
-                    i,
-                )?;
+                if stubs {
+                    make_compile_stub(
+                        &mut context.func,
+                        &mut FunctionBuilderContext::new(),
+                        isa.pointer_type(),
+                        &offsets,
+                        i,
+                    );
+                } else {
+                    func_translator.translate(
+                        module_translation_state,
+                        &mut reader,
+                        &mut context.func,
+                        &mut func_env,
+                        i,
+                    )?;
+                }
 
                 let mut code_buf: Vec<u8> = Vec::new();
                 context
@@ -225,8 +364,6 @@ impl Compiler for CraneliftCompiler {
             .unzip();
         #[cfg(feature = "rayon")]
         let (functions, fdes): (Vec<CompiledFunction>, Vec<_>) = function_body_inputs
-            .iter()
-            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
             .par_iter()
             .map_init(FuncTranslator::new, |func_translator, (i, input)| {
                 let func_index = module.func_index(*i);
@@ -237,6 +374,8 @@ impl Compiler for CraneliftCompiler {
                     &signatures,
                     memory_styles,
                     table_styles,
+                    self.config.enable_soft_float,
+                    self.config.enable_lazy_compilation,
                 );
                 context.func.name = match get_function_name(func_index) {
                     ExternalName::User(nameref) => {
@@ -261,14 +400,29 @@ impl Compiler for CraneliftCompiler {
                         .middlewares
                         .generate_function_middleware_chain(*i),
                 );
+                reader.set_num_params(
+                    module.signatures[module.functions[func_index]]
+                        .params()
+                        .len() as u32,
+                );
 
-                func_translator.translate(
-                    module_translation_state,
-                    &mut reader,
-                    &mut context.func,
-                    &mut func_env,
-                    *i,
-                )?;
+                if stubs {
+                    make_compile_stub(
+                        &mut context.func,
+                        &mut FunctionBuilderContext::new(),
+                        isa.pointer_type(),
+                        &offsets,
+                        *i,
+                    );
+                } else {
+                    func_translator.translate(
+                        module_translation_state,
+                        &mut reader,
+                        &mut context.func,
+                        &mut func_env,
+                        *i,
+                    )?;
+                }
 
                 let mut code_buf: Vec<u8> = Vec::new();
                 context
@@ -353,65 +507,7 @@ impl Compiler for CraneliftCompiler {
         #[cfg(not(feature = "unwind"))]
         let dwarf = None;
 
-        // function call trampolines (only for local functions, by signature)
-        #[cfg(not(feature = "rayon"))]
-        let mut cx = FunctionBuilderContext::new();
-        #[cfg(not(feature = "rayon"))]
-        let function_call_trampolines = module
-            .signatures
-            .values()
-            .collect::<Vec<_>>()
-            .into_iter()
-            .map(|sig| make_trampoline_function_call(&*isa, &mut cx, sig))
-            .collect::<Result<Vec<FunctionBody>, CompileError>>()?
-            .into_iter()
-            .collect::<PrimaryMap<SignatureIndex, FunctionBody>>();
-        #[cfg(feature = "rayon")]
-        let function_call_trampolines = module
-            .signatures
-            .values()
-            .collect::<Vec<_>>()
-            .par_iter()
-            .map_init(FunctionBuilderContext::new, |cx, sig| {
-                make_trampoline_function_call(&*isa, cx, sig)
-            })
-            .collect::<Result<Vec<FunctionBody>, CompileError>>()?
-            .into_iter()
-            .collect::<PrimaryMap<SignatureIndex, FunctionBody>>();
-
-        use wasmer_types::VMOffsets;
-        let offsets = VMOffsets::new_for_trampolines(frontend_config.pointer_bytes());
-        // dynamic function trampolines (only for imported functions)
-        #[cfg(not(feature = "rayon"))]
-        let mut cx = FunctionBuilderContext::new();
-        #[cfg(not(feature = "rayon"))]
-        let dynamic_function_trampolines = module
-            .imported_function_types()
-            .collect::<Vec<_>>()
-            .into_iter()
-            .map(|func_type| make_trampoline_dynamic_function(&*isa, &offsets, &mut cx, &func_type))
-            .collect::<Result<Vec<_>, CompileError>>()?
-            .into_iter()
-            .collect::<PrimaryMap<FunctionIndex, FunctionBody>>();
-        #[cfg(feature = "rayon")]
-        let dynamic_function_trampolines = module
-            .imported_function_types()
-            .collect::<Vec<_>>()
-            .par_iter()
-            .map_init(FunctionBuilderContext::new, |cx, func_type| {
-                make_trampoline_dynamic_function(&*isa, &offsets, cx, func_type)
-            })
-            .collect::<Result<Vec<_>, CompileError>>()?
-            .into_iter()
-            .collect::<PrimaryMap<FunctionIndex, FunctionBody>>();
-
-        Ok(Compilation {
-            functions: functions.into_iter().collect(),
-            custom_sections,
-            function_call_trampolines,
-            dynamic_function_trampolines,
-            debug: dwarf,
-        })
+        Ok((functions, custom_sections, dwarf))
     }
 }
 
@@ -32,6 +32,8 @@ pub struct Cranelift {
     enable_nan_canonicalization: bool,
     enable_verifier: bool,
     enable_pic: bool,
+    pub(crate) enable_soft_float: bool,
+    pub(crate) enable_lazy_compilation: bool,
     opt_level: CraneliftOptLevel,
     /// The middleware chain.
     pub(crate) middlewares: Vec<Arc<dyn ModuleMiddleware>>,
@@ -46,10 +48,25 @@ impl Cranelift {
             enable_verifier: false,
             opt_level: CraneliftOptLevel::Speed,
             enable_pic: false,
+            enable_soft_float: false,
+            enable_lazy_compilation: false,
             middlewares: vec![],
         }
     }
 
+    /// A string identifying the settings that affect the generated code.
+    pub(crate) fn deterministic_id(&self) -> String {
+        format!(
+            "{:?}-nan{}-verifier{}-pic{}-softfloat{}-lazy{}",
+            self.opt_level,
+            self.enable_nan_canonicalization,
+            self.enable_verifier,
+            self.enable_pic,
+            self.enable_soft_float,
+            self.enable_lazy_compilation,
+        )
+    }
+
     /// Enable NaN canonicalization.
     ///
     /// NaN canonicalization is useful when trying to run WebAssembly
@@ -59,6 +76,34 @@ impl Cranelift {
         self
     }
 
+    /// Route the `f32`/`f64` operations (arithmetic, rounding, sign,
+    /// comparisons and conversions) through the runtime's software
+    /// implementations instead of native floating point instructions.
+    ///
+    /// This is useful for targets whose CPUs lack (some of) the floating
+    /// point instructions Cranelift requires, at the cost of a call per
+    /// operation. The software implementations only use integer
+    /// arithmetic, and their results still follow the WebAssembly rounding
+    /// rules, while the trapping conversions still trap. The floats are only moved and
+    /// reinterpreted natively. SIMD operations aren't affected, and the
+    /// results of the software implementations aren't canonicalized.
+    pub fn soft_float(&mut self, enable: bool) -> &mut Self {
+        self.enable_soft_float = enable;
+        self
+    }
+
+    /// Compile the functions on their first call instead of up front.
+    ///
+    /// The module is still validated up front, but each function is
+    /// compiled to a compile stub, which compiles the function the first
+    /// time it's called. This reduces the compilation time of the large
+    /// modules of which only a few functions are run, at the cost of an
+    /// indirection on each call. Such modules can't be serialized.
+    pub fn lazy_compilation(&mut self, enable: bool) -> &mut Self {
+        self.enable_lazy_compilation = enable;
+        self
+    }
+
     /// The optimization levels when optimizing the IR.
     pub fn opt_level(&mut self, opt_level: CraneliftOptLevel) -> &mut Self {
         self.opt_level = opt_level;
@@ -73,8 +118,9 @@ impl Cranelift {
         let cpu_features = target.cpu_features();
         if target.triple().architecture == Architecture::X86_64
             && !cpu_features.contains(CpuFeature::SSE2)
+            && !self.enable_soft_float
         {
-            panic!("x86 support requires SSE2");
+            panic!("x86 support requires SSE2, or enabling soft-float");
         }
         if cpu_features.contains(CpuFeature::SSE3) {
             builder.enable("has_sse3").expect("should be valid flag");
@@ -20,7 +20,7 @@ use wasmer_types::VMBuiltinFunctionIndex;
 use wasmer_types::VMOffsets;
 use wasmer_types::{
     FunctionIndex, FunctionType, GlobalIndex, LocalFunctionIndex, MemoryIndex, ModuleInfo,
-    SignatureIndex, TableIndex, Type as WasmerType,
+    SignatureIndex, SoftFloatConversion, SoftFloatOp, TableIndex, Type as WasmerType,
 };
 use wasmer_types::{MemoryStyle, TableStyle};
 use wasmer_types::{WasmError, WasmResult};
@@ -113,6 +113,22 @@ pub struct FuncEnvironment<'module_environment> {
     /// The external function signature for implementing wasm's `memory32.atomic.notify`.
     memory32_atomic_notify_sig: Option<ir::SigRef>,
 
+    /// The external function signature for the `f32` soft-float builtin.
+    f32_soft_op_sig: Option<ir::SigRef>,
+
+    /// The external function signature for the `f64` soft-float builtin.
+    f64_soft_op_sig: Option<ir::SigRef>,
+
+    /// The external function signature for the soft-float conversion builtin.
+    soft_float_convert_sig: Option<ir::SigRef>,
+
+    /// Whether float arithmetic is routed through the soft-float builtins.
+    enable_soft_float: bool,
+
+    /// Whether the functions are compiled lazily, on their first call, so
+    /// they aren't allocated next to each other.
+    lazy_compilation: bool,
+
     /// Offsets to struct fields accessed by JIT code.
     offsets: VMOffsets,
 
@@ -130,6 +146,8 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         signatures: &'module_environment PrimaryMap<SignatureIndex, ir::Signature>,
         memory_styles: &'module_environment PrimaryMap<MemoryIndex, MemoryStyle>,
         table_styles: &'module_environment PrimaryMap<TableIndex, TableStyle>,
+        enable_soft_float: bool,
+        lazy_compilation: bool,
     ) -> Self {
         Self {
             target_config,
@@ -155,6 +173,11 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             memory32_atomic_wait32_sig: None,
             memory32_atomic_wait64_sig: None,
             memory32_atomic_notify_sig: None,
+            f32_soft_op_sig: None,
+            f64_soft_op_sig: None,
+            soft_float_convert_sig: None,
+            enable_soft_float,
+            lazy_compilation,
             offsets: VMOffsets::new(target_config.pointer_bytes(), module),
             memory_styles,
             table_styles,
@@ -829,6 +852,53 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         }
     }
 
+    fn get_soft_float_op_sig(&mut self, func: &mut Function, bits_ty: ir::Type) -> ir::SigRef {
+        let cached = if bits_ty == I32 {
+            self.f32_soft_op_sig
+        } else {
+            self.f64_soft_op_sig
+        };
+        let sig = cached.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Operation
+                    AbiParam::new(I32),
+                    // Bits of the left-hand side operand
+                    AbiParam::new(bits_ty),
+                    // Bits of the right-hand side operand
+                    AbiParam::new(bits_ty),
+                ],
+                returns: vec![AbiParam::new(bits_ty)],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        if bits_ty == I32 {
+            self.f32_soft_op_sig = Some(sig);
+        } else {
+            self.f64_soft_op_sig = Some(sig);
+        }
+        sig
+    }
+
+    fn get_soft_float_convert_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.soft_float_convert_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Conversion
+                    AbiParam::new(I32),
+                    // Bits of the operand
+                    AbiParam::new(I64),
+                ],
+                returns: vec![AbiParam::new(I64)],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.soft_float_convert_sig = Some(sig);
+        sig
+    }
+
     /// Translates load of builtin function and returns a pair of values `vmctx`
     /// and address of the loaded function.
     fn translate_load_builtin_function_address(
@@ -1196,7 +1266,9 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         Ok(func.import_function(ir::ExtFuncData {
             name,
             signature,
-            colocated: true,
+            // The functions compiled lazily may be too far from the compile
+            // stubs they call for a relative call.
+            colocated: !self.lazy_compilation,
         }))
     }
 
@@ -1573,6 +1645,80 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
     }
 
+    fn translate_soft_float_op(
+        &mut self,
+        mut pos: FuncCursor,
+        op: SoftFloatOp,
+        lhs: ir::Value,
+        rhs: ir::Value,
+    ) -> WasmResult<Option<ir::Value>> {
+        if !self.enable_soft_float {
+            return Ok(None);
+        }
+        let ty = pos.func.dfg.value_type(lhs);
+        let (bits_ty, func_idx) = match ty {
+            F32 => (I32, VMBuiltinFunctionIndex::get_f32_soft_op_index()),
+            F64 => (I64, VMBuiltinFunctionIndex::get_f64_soft_op_index()),
+            _ => return Ok(None),
+        };
+        let func_sig = self.get_soft_float_op_sig(pos.func, bits_ty);
+        let op_code = pos.ins().iconst(I32, op as i64);
+        let lhs = pos.ins().bitcast(bits_ty, ir::MemFlags::new(), lhs);
+        let rhs = pos.ins().bitcast(bits_ty, ir::MemFlags::new(), rhs);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(&mut pos, func_idx);
+        let call_inst = pos
+            .ins()
+            .call_indirect(func_sig, func_addr, &[vmctx, op_code, lhs, rhs]);
+        let result = *pos.func.dfg.inst_results(call_inst).first().unwrap();
+        Ok(Some(if !op.is_comparison() {
+            pos.ins().bitcast(ty, ir::MemFlags::new(), result)
+        } else if bits_ty == I64 {
+            pos.ins().ireduce(I32, result)
+        } else {
+            result
+        }))
+    }
+
+    fn translate_soft_float_conversion(
+        &mut self,
+        mut pos: FuncCursor,
+        conversion: SoftFloatConversion,
+        val: ir::Value,
+        result_ty: ir::Type,
+    ) -> WasmResult<Option<ir::Value>> {
+        if !self.enable_soft_float {
+            return Ok(None);
+        }
+        let func_sig = self.get_soft_float_convert_sig(pos.func);
+        let conversion = pos.ins().iconst(I32, conversion as i64);
+        let bits = match pos.func.dfg.value_type(val) {
+            F32 => {
+                let bits = pos.ins().bitcast(I32, ir::MemFlags::new(), val);
+                pos.ins().uextend(I64, bits)
+            }
+            F64 => pos.ins().bitcast(I64, ir::MemFlags::new(), val),
+            I32 => pos.ins().uextend(I64, val),
+            _ => val,
+        };
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_soft_float_convert_index(),
+        );
+        let call_inst = pos
+            .ins()
+            .call_indirect(func_sig, func_addr, &[vmctx, conversion, bits]);
+        let result = *pos.func.dfg.inst_results(call_inst).first().unwrap();
+        Ok(Some(match result_ty {
+            F32 => {
+                let bits = pos.ins().ireduce(I32, result);
+                pos.ins().bitcast(F32, ir::MemFlags::new(), bits)
+            }
+            F64 => pos.ins().bitcast(F64, ir::MemFlags::new(), result),
+            I32 => pos.ins().ireduce(I32, result),
+            _ => result,
+        }))
+    }
+
     fn get_global_type(&self, global_index: GlobalIndex) -> Option<WasmerType> {
         Some(self.module.globals.get(global_index)?.ty)
     }
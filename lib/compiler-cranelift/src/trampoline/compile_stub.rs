@@ -0,0 +1,65 @@
+//! A compile stub for the functions compiled on their first call.
+//!
+//! The stub has the signature of its function. It gets the body of the
+//! function from the `lazy_compile` builtin, which compiles it on its first
+//! call, and calls it with its own arguments.
+use cranelift_codegen::ir;
+use cranelift_codegen::ir::InstBuilder;
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use wasmer_types::{LocalFunctionIndex, VMBuiltinFunctionIndex, VMOffsets};
+
+/// Build the compile stub of the local function `index` into `func`, which
+/// must have the signature of the function.
+pub fn make_compile_stub(
+    func: &mut ir::Function,
+    fn_builder_ctx: &mut FunctionBuilderContext,
+    pointer_type: ir::Type,
+    offsets: &VMOffsets,
+    index: LocalFunctionIndex,
+) {
+    let mut compile_sig = ir::Signature::new(func.signature.call_conv);
+    // The `vmctx` parameter.
+    compile_sig.params.push(ir::AbiParam::special(
+        pointer_type,
+        ir::ArgumentPurpose::VMContext,
+    ));
+    // The `function_index` parameter.
+    compile_sig.params.push(ir::AbiParam::new(ir::types::I32));
+    // The body of the function.
+    compile_sig.returns.push(ir::AbiParam::new(pointer_type));
+    let function_sig = func.signature.clone();
+
+    let mut builder = FunctionBuilder::new(func, fn_builder_ctx);
+    let block0 = builder.create_block();
+
+    builder.append_block_params_for_function_params(block0);
+    builder.switch_to_block(block0);
+    builder.seal_block(block0);
+
+    let args = builder.func.dfg.block_params(block0).to_vec();
+    let vmctx_ptr_val = args[0];
+
+    let mut mem_flags = ir::MemFlags::trusted();
+    mem_flags.set_readonly();
+    let lazy_compile_offset =
+        offsets.vmctx_builtin_function(VMBuiltinFunctionIndex::get_lazy_compile_index()) as i32;
+    let lazy_compile =
+        builder
+            .ins()
+            .load(pointer_type, mem_flags, vmctx_ptr_val, lazy_compile_offset);
+    let compile_sig = builder.import_signature(compile_sig);
+    let function_index = builder
+        .ins()
+        .iconst(ir::types::I32, i64::from(index.as_u32()));
+    let call =
+        builder
+            .ins()
+            .call_indirect(compile_sig, lazy_compile, &[vmctx_ptr_val, function_index]);
+    let body = builder.func.dfg.inst_results(call)[0];
+
+    let function_sig = builder.import_signature(function_sig);
+    let call = builder.ins().call_indirect(function_sig, body, &args);
+    let results = builder.func.dfg.inst_results(call).to_vec();
+    builder.ins().return_(&results);
+    builder.finalize();
+}
@@ -1,8 +1,10 @@
 #![allow(missing_docs)]
 
+mod compile_stub;
 mod dynamic_function;
 mod function_call;
 
+pub use self::compile_stub::make_compile_stub;
 pub use self::dynamic_function::make_trampoline_dynamic_function;
 pub use self::function_call::make_trampoline_function_call;
 
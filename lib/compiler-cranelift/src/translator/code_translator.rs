@@ -95,7 +95,8 @@ use std::vec::Vec;
 use wasmer_compiler::wasmparser::{MemoryImmediate, Operator};
 use wasmer_compiler::{from_binaryreadererror_wasmerror, wasm_unsupported, ModuleTranslationState};
 use wasmer_types::{
-    FunctionIndex, GlobalIndex, MemoryIndex, SignatureIndex, TableIndex, WasmResult,
+    FunctionIndex, GlobalIndex, MemoryIndex, SignatureIndex, SoftFloatConversion, SoftFloatOp,
+    TableIndex, WasmError, WasmResult,
 };
 
 // Clippy warns about "align: _" but its important to document that the align field is ignored
@@ -770,87 +771,161 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         }
         Operator::F32Sqrt | Operator::F64Sqrt => {
             let arg = state.pop1();
-            state.push1(builder.ins().sqrt(arg));
+            let val =
+                translate_float_op(SoftFloatOp::Sqrt, arg, arg, builder, environ, |b, x, _| {
+                    b.ins().sqrt(x)
+                })?;
+            state.push1(val);
         }
         Operator::F32Ceil | Operator::F64Ceil => {
             let arg = state.pop1();
-            state.push1(builder.ins().ceil(arg));
+            let val =
+                translate_float_op(SoftFloatOp::Ceil, arg, arg, builder, environ, |b, x, _| {
+                    b.ins().ceil(x)
+                })?;
+            state.push1(val);
         }
         Operator::F32Floor | Operator::F64Floor => {
             let arg = state.pop1();
-            state.push1(builder.ins().floor(arg));
+            let val =
+                translate_float_op(SoftFloatOp::Floor, arg, arg, builder, environ, |b, x, _| {
+                    b.ins().floor(x)
+                })?;
+            state.push1(val);
         }
         Operator::F32Trunc | Operator::F64Trunc => {
             let arg = state.pop1();
-            state.push1(builder.ins().trunc(arg));
+            let val =
+                translate_float_op(SoftFloatOp::Trunc, arg, arg, builder, environ, |b, x, _| {
+                    b.ins().trunc(x)
+                })?;
+            state.push1(val);
         }
         Operator::F32Nearest | Operator::F64Nearest => {
             let arg = state.pop1();
-            state.push1(builder.ins().nearest(arg));
+            let val = translate_float_op(
+                SoftFloatOp::Nearest,
+                arg,
+                arg,
+                builder,
+                environ,
+                |b, x, _| b.ins().nearest(x),
+            )?;
+            state.push1(val);
         }
         Operator::F32Abs | Operator::F64Abs => {
-            let val = state.pop1();
-            state.push1(builder.ins().fabs(val));
+            let arg = state.pop1();
+            let val =
+                translate_float_op(SoftFloatOp::Abs, arg, arg, builder, environ, |b, x, _| {
+                    b.ins().fabs(x)
+                })?;
+            state.push1(val);
         }
         Operator::F32Neg | Operator::F64Neg => {
             let arg = state.pop1();
-            state.push1(builder.ins().fneg(arg));
+            let val =
+                translate_float_op(SoftFloatOp::Neg, arg, arg, builder, environ, |b, x, _| {
+                    b.ins().fneg(x)
+                })?;
+            state.push1(val);
         }
         Operator::F64ConvertI64U | Operator::F64ConvertI32U => {
             let val = state.pop1();
-            state.push1(builder.ins().fcvt_from_uint(F64, val));
+            let val = translate_float_conversion(op, val, F64, builder, environ, |b, x| {
+                b.ins().fcvt_from_uint(F64, x)
+            })?;
+            state.push1(val);
         }
         Operator::F64ConvertI64S | Operator::F64ConvertI32S => {
             let val = state.pop1();
-            state.push1(builder.ins().fcvt_from_sint(F64, val));
+            let val = translate_float_conversion(op, val, F64, builder, environ, |b, x| {
+                b.ins().fcvt_from_sint(F64, x)
+            })?;
+            state.push1(val);
         }
         Operator::F32ConvertI64S | Operator::F32ConvertI32S => {
             let val = state.pop1();
-            state.push1(builder.ins().fcvt_from_sint(F32, val));
+            let val = translate_float_conversion(op, val, F32, builder, environ, |b, x| {
+                b.ins().fcvt_from_sint(F32, x)
+            })?;
+            state.push1(val);
         }
         Operator::F32ConvertI64U | Operator::F32ConvertI32U => {
             let val = state.pop1();
-            state.push1(builder.ins().fcvt_from_uint(F32, val));
+            let val = translate_float_conversion(op, val, F32, builder, environ, |b, x| {
+                b.ins().fcvt_from_uint(F32, x)
+            })?;
+            state.push1(val);
         }
         Operator::F64PromoteF32 => {
             let val = state.pop1();
-            state.push1(builder.ins().fpromote(F64, val));
+            let val = translate_float_conversion(op, val, F64, builder, environ, |b, x| {
+                b.ins().fpromote(F64, x)
+            })?;
+            state.push1(val);
         }
         Operator::F32DemoteF64 => {
             let val = state.pop1();
-            state.push1(builder.ins().fdemote(F32, val));
+            let val = translate_float_conversion(op, val, F32, builder, environ, |b, x| {
+                b.ins().fdemote(F32, x)
+            })?;
+            state.push1(val);
         }
         Operator::I64TruncF64S | Operator::I64TruncF32S => {
             let val = state.pop1();
-            state.push1(builder.ins().fcvt_to_sint(I64, val));
+            let val = translate_float_conversion(op, val, I64, builder, environ, |b, x| {
+                b.ins().fcvt_to_sint(I64, x)
+            })?;
+            state.push1(val);
         }
         Operator::I32TruncF64S | Operator::I32TruncF32S => {
             let val = state.pop1();
-            state.push1(builder.ins().fcvt_to_sint(I32, val));
+            let val = translate_float_conversion(op, val, I32, builder, environ, |b, x| {
+                b.ins().fcvt_to_sint(I32, x)
+            })?;
+            state.push1(val);
         }
         Operator::I64TruncF64U | Operator::I64TruncF32U => {
             let val = state.pop1();
-            state.push1(builder.ins().fcvt_to_uint(I64, val));
+            let val = translate_float_conversion(op, val, I64, builder, environ, |b, x| {
+                b.ins().fcvt_to_uint(I64, x)
+            })?;
+            state.push1(val);
         }
         Operator::I32TruncF64U | Operator::I32TruncF32U => {
             let val = state.pop1();
-            state.push1(builder.ins().fcvt_to_uint(I32, val));
+            let val = translate_float_conversion(op, val, I32, builder, environ, |b, x| {
+                b.ins().fcvt_to_uint(I32, x)
+            })?;
+            state.push1(val);
         }
         Operator::I64TruncSatF64S | Operator::I64TruncSatF32S => {
             let val = state.pop1();
-            state.push1(builder.ins().fcvt_to_sint_sat(I64, val));
+            let val = translate_float_conversion(op, val, I64, builder, environ, |b, x| {
+                b.ins().fcvt_to_sint_sat(I64, x)
+            })?;
+            state.push1(val);
         }
         Operator::I32TruncSatF64S | Operator::I32TruncSatF32S => {
             let val = state.pop1();
-            state.push1(builder.ins().fcvt_to_sint_sat(I32, val));
+            let val = translate_float_conversion(op, val, I32, builder, environ, |b, x| {
+                b.ins().fcvt_to_sint_sat(I32, x)
+            })?;
+            state.push1(val);
         }
         Operator::I64TruncSatF64U | Operator::I64TruncSatF32U => {
             let val = state.pop1();
-            state.push1(builder.ins().fcvt_to_uint_sat(I64, val));
+            let val = translate_float_conversion(op, val, I64, builder, environ, |b, x| {
+                b.ins().fcvt_to_uint_sat(I64, x)
+            })?;
+            state.push1(val);
         }
         Operator::I32TruncSatF64U | Operator::I32TruncSatF32U => {
             let val = state.pop1();
-            state.push1(builder.ins().fcvt_to_uint_sat(I32, val));
+            let val = translate_float_conversion(op, val, I32, builder, environ, |b, x| {
+                b.ins().fcvt_to_uint_sat(I32, x)
+            })?;
+            state.push1(val);
         }
         Operator::F32ReinterpretI32 => {
             let val = state.pop1();
@@ -937,7 +1012,11 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         }
         Operator::F32Add | Operator::F64Add => {
             let (arg1, arg2) = state.pop2();
-            state.push1(builder.ins().fadd(arg1, arg2));
+            let val =
+                translate_float_op(SoftFloatOp::Add, arg1, arg2, builder, environ, |b, x, y| {
+                    b.ins().fadd(x, y)
+                })?;
+            state.push1(val);
         }
         Operator::I32Sub | Operator::I64Sub => {
             let (arg1, arg2) = state.pop2();
@@ -945,7 +1024,11 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         }
         Operator::F32Sub | Operator::F64Sub => {
             let (arg1, arg2) = state.pop2();
-            state.push1(builder.ins().fsub(arg1, arg2));
+            let val =
+                translate_float_op(SoftFloatOp::Sub, arg1, arg2, builder, environ, |b, x, y| {
+                    b.ins().fsub(x, y)
+                })?;
+            state.push1(val);
         }
         Operator::I32Mul | Operator::I64Mul => {
             let (arg1, arg2) = state.pop2();
@@ -953,11 +1036,19 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         }
         Operator::F32Mul | Operator::F64Mul => {
             let (arg1, arg2) = state.pop2();
-            state.push1(builder.ins().fmul(arg1, arg2));
+            let val =
+                translate_float_op(SoftFloatOp::Mul, arg1, arg2, builder, environ, |b, x, y| {
+                    b.ins().fmul(x, y)
+                })?;
+            state.push1(val);
         }
         Operator::F32Div | Operator::F64Div => {
             let (arg1, arg2) = state.pop2();
-            state.push1(builder.ins().fdiv(arg1, arg2));
+            let val =
+                translate_float_op(SoftFloatOp::Div, arg1, arg2, builder, environ, |b, x, y| {
+                    b.ins().fdiv(x, y)
+                })?;
+            state.push1(val);
         }
         Operator::I32DivS | Operator::I64DivS => {
             let (arg1, arg2) = state.pop2();
@@ -977,15 +1068,31 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         }
         Operator::F32Min | Operator::F64Min => {
             let (arg1, arg2) = state.pop2();
-            state.push1(builder.ins().fmin(arg1, arg2));
+            let val =
+                translate_float_op(SoftFloatOp::Min, arg1, arg2, builder, environ, |b, x, y| {
+                    b.ins().fmin(x, y)
+                })?;
+            state.push1(val);
         }
         Operator::F32Max | Operator::F64Max => {
             let (arg1, arg2) = state.pop2();
-            state.push1(builder.ins().fmax(arg1, arg2));
+            let val =
+                translate_float_op(SoftFloatOp::Max, arg1, arg2, builder, environ, |b, x, y| {
+                    b.ins().fmax(x, y)
+                })?;
+            state.push1(val);
         }
         Operator::F32Copysign | Operator::F64Copysign => {
             let (arg1, arg2) = state.pop2();
-            state.push1(builder.ins().fcopysign(arg1, arg2));
+            let val = translate_float_op(
+                SoftFloatOp::Copysign,
+                arg1,
+                arg2,
+                builder,
+                environ,
+                |b, x, y| b.ins().fcopysign(x, y),
+            )?;
+            state.push1(val);
         }
         /**************************** Comparison Operators **********************************/
         Operator::I32LtS | Operator::I64LtS => {
@@ -1018,17 +1125,37 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             state.push1(builder.ins().uextend(I32, val));
         }
         Operator::I32Eq | Operator::I64Eq => translate_icmp(IntCC::Equal, builder, state),
-        Operator::F32Eq | Operator::F64Eq => translate_fcmp(FloatCC::Equal, builder, state),
-        Operator::I32Ne | Operator::I64Ne => translate_icmp(IntCC::NotEqual, builder, state),
-        Operator::F32Ne | Operator::F64Ne => translate_fcmp(FloatCC::NotEqual, builder, state),
-        Operator::F32Gt | Operator::F64Gt => translate_fcmp(FloatCC::GreaterThan, builder, state),
-        Operator::F32Ge | Operator::F64Ge => {
-            translate_fcmp(FloatCC::GreaterThanOrEqual, builder, state)
+        Operator::F32Eq | Operator::F64Eq => {
+            translate_fcmp(FloatCC::Equal, SoftFloatOp::Eq, builder, state, environ)?
         }
-        Operator::F32Lt | Operator::F64Lt => translate_fcmp(FloatCC::LessThan, builder, state),
-        Operator::F32Le | Operator::F64Le => {
-            translate_fcmp(FloatCC::LessThanOrEqual, builder, state)
+        Operator::I32Ne | Operator::I64Ne => translate_icmp(IntCC::NotEqual, builder, state),
+        Operator::F32Ne | Operator::F64Ne => {
+            translate_fcmp(FloatCC::NotEqual, SoftFloatOp::Ne, builder, state, environ)?
         }
+        Operator::F32Gt | Operator::F64Gt => translate_fcmp(
+            FloatCC::GreaterThan,
+            SoftFloatOp::Gt,
+            builder,
+            state,
+            environ,
+        )?,
+        Operator::F32Ge | Operator::F64Ge => translate_fcmp(
+            FloatCC::GreaterThanOrEqual,
+            SoftFloatOp::Ge,
+            builder,
+            state,
+            environ,
+        )?,
+        Operator::F32Lt | Operator::F64Lt => {
+            translate_fcmp(FloatCC::LessThan, SoftFloatOp::Lt, builder, state, environ)?
+        }
+        Operator::F32Le | Operator::F64Le => translate_fcmp(
+            FloatCC::LessThanOrEqual,
+            SoftFloatOp::Le,
+            builder,
+            state,
+            environ,
+        )?,
         Operator::RefNull { ty } => state.push1(environ.translate_ref_null(builder.cursor(), *ty)?),
         Operator::RefIsNull => {
             let value = state.pop1();
@@ -2351,6 +2478,39 @@ fn mem_op_size(opcode: ir::Opcode, ty: Type) -> u32 {
     }
 }
 
+/// Translate a floating point arithmetic operator, giving the environment the
+/// chance to replace it with a call to a soft-float implementation first.
+fn translate_float_op<FE: FuncEnvironment + ?Sized>(
+    op: SoftFloatOp,
+    arg1: Value,
+    arg2: Value,
+    builder: &mut FunctionBuilder,
+    environ: &mut FE,
+    native: impl FnOnce(&mut FunctionBuilder, Value, Value) -> Value,
+) -> WasmResult<Value> {
+    match environ.translate_soft_float_op(builder.cursor(), op, arg1, arg2)? {
+        Some(val) => Ok(val),
+        None => Ok(native(builder, arg1, arg2)),
+    }
+}
+
+/// Translate a conversion from or to a float, giving the environment the
+/// chance to replace it with a call to a soft-float implementation first.
+fn translate_float_conversion<FE: FuncEnvironment + ?Sized>(
+    op: &Operator,
+    val: Value,
+    result_ty: Type,
+    builder: &mut FunctionBuilder,
+    environ: &mut FE,
+    native: impl FnOnce(&mut FunctionBuilder, Value) -> Value,
+) -> WasmResult<Value> {
+    let conversion = soft_float_conversion(op)?;
+    match environ.translate_soft_float_conversion(builder.cursor(), conversion, val, result_ty)? {
+        Some(val) => Ok(val),
+        None => Ok(native(builder, val)),
+    }
+}
+
 fn translate_icmp(cc: IntCC, builder: &mut FunctionBuilder, state: &mut FuncTranslationState) {
     let (arg0, arg1) = state.pop2();
     let val = builder.ins().icmp(cc, arg0, arg1);
@@ -2631,10 +2791,23 @@ fn translate_vector_icmp(
     state.push1(builder.ins().icmp(cc, bitcast_a, bitcast_b))
 }
 
-fn translate_fcmp(cc: FloatCC, builder: &mut FunctionBuilder, state: &mut FuncTranslationState) {
+fn translate_fcmp<FE: FuncEnvironment + ?Sized>(
+    cc: FloatCC,
+    op: SoftFloatOp,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
     let (arg0, arg1) = state.pop2();
-    let val = builder.ins().fcmp(cc, arg0, arg1);
-    state.push1(builder.ins().uextend(I32, val));
+    let val = match environ.translate_soft_float_op(builder.cursor(), op, arg0, arg1)? {
+        Some(val) => val,
+        None => {
+            let val = builder.ins().fcmp(cc, arg0, arg1);
+            builder.ins().uextend(I32, val)
+        }
+    };
+    state.push1(val);
+    Ok(())
 }
 
 fn translate_vector_fcmp(
@@ -2685,6 +2858,44 @@ fn translate_br_if_args(
     (br_destination, inputs)
 }
 
+/// Determine the soft-float conversion implementing a WebAssembly operator
+fn soft_float_conversion(operator: &Operator) -> WasmResult<SoftFloatConversion> {
+    Ok(match operator {
+        Operator::F32ConvertI32S => SoftFloatConversion::F32ConvertI32S,
+        Operator::F32ConvertI32U => SoftFloatConversion::F32ConvertI32U,
+        Operator::F32ConvertI64S => SoftFloatConversion::F32ConvertI64S,
+        Operator::F32ConvertI64U => SoftFloatConversion::F32ConvertI64U,
+        Operator::F64ConvertI32S => SoftFloatConversion::F64ConvertI32S,
+        Operator::F64ConvertI32U => SoftFloatConversion::F64ConvertI32U,
+        Operator::F64ConvertI64S => SoftFloatConversion::F64ConvertI64S,
+        Operator::F64ConvertI64U => SoftFloatConversion::F64ConvertI64U,
+        Operator::F32DemoteF64 => SoftFloatConversion::F32DemoteF64,
+        Operator::F64PromoteF32 => SoftFloatConversion::F64PromoteF32,
+        Operator::I32TruncF32S => SoftFloatConversion::I32TruncF32S,
+        Operator::I32TruncF32U => SoftFloatConversion::I32TruncF32U,
+        Operator::I32TruncF64S => SoftFloatConversion::I32TruncF64S,
+        Operator::I32TruncF64U => SoftFloatConversion::I32TruncF64U,
+        Operator::I64TruncF32S => SoftFloatConversion::I64TruncF32S,
+        Operator::I64TruncF32U => SoftFloatConversion::I64TruncF32U,
+        Operator::I64TruncF64S => SoftFloatConversion::I64TruncF64S,
+        Operator::I64TruncF64U => SoftFloatConversion::I64TruncF64U,
+        Operator::I32TruncSatF32S => SoftFloatConversion::I32TruncSatF32S,
+        Operator::I32TruncSatF32U => SoftFloatConversion::I32TruncSatF32U,
+        Operator::I32TruncSatF64S => SoftFloatConversion::I32TruncSatF64S,
+        Operator::I32TruncSatF64U => SoftFloatConversion::I32TruncSatF64U,
+        Operator::I64TruncSatF32S => SoftFloatConversion::I64TruncSatF32S,
+        Operator::I64TruncSatF32U => SoftFloatConversion::I64TruncSatF32U,
+        Operator::I64TruncSatF64S => SoftFloatConversion::I64TruncSatF64S,
+        Operator::I64TruncSatF64U => SoftFloatConversion::I64TruncSatF64U,
+        _ => {
+            return Err(WasmError::Generic(format!(
+                "{:?} is not a float conversion",
+                operator
+            )))
+        }
+    })
+}
+
 /// Determine the returned value type of a WebAssembly operator
 fn type_of(operator: &Operator) -> Type {
     match operator {
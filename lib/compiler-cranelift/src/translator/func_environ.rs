@@ -15,7 +15,7 @@ use cranelift_frontend::FunctionBuilder;
 use wasmer_compiler::wasmparser::{Operator, Type};
 use wasmer_types::{
     FunctionIndex, FunctionType, GlobalIndex, LocalFunctionIndex, MemoryIndex, SignatureIndex,
-    TableIndex, Type as WasmerType, WasmResult,
+    SoftFloatConversion, SoftFloatOp, TableIndex, Type as WasmerType, WasmResult,
 };
 
 /// The value of a WebAssembly global variable.
@@ -438,6 +438,37 @@ pub trait FuncEnvironment: TargetEnvironment {
         count: ir::Value,
     ) -> WasmResult<ir::Value>;
 
+    /// Translate a floating point arithmetic operator or comparison into a
+    /// call to its software implementation. For unary operators `rhs` is the
+    /// same value as `lhs`, and comparisons return an `i32`.
+    ///
+    /// Returns `None` if the operator should be translated to native
+    /// instructions instead, which is the default.
+    fn translate_soft_float_op(
+        &mut self,
+        _pos: FuncCursor,
+        _op: SoftFloatOp,
+        _lhs: ir::Value,
+        _rhs: ir::Value,
+    ) -> WasmResult<Option<ir::Value>> {
+        Ok(None)
+    }
+
+    /// Translate a conversion from or to a float into a call to its
+    /// software implementation, returning a value of type `result_ty`.
+    ///
+    /// Returns `None` if the conversion should be translated to native
+    /// instructions instead, which is the default.
+    fn translate_soft_float_conversion(
+        &mut self,
+        _pos: FuncCursor,
+        _conversion: SoftFloatConversion,
+        _val: ir::Value,
+        _result_ty: ir::Type,
+    ) -> WasmResult<Option<ir::Value>> {
+        Ok(None)
+    }
+
     /// Emit code at the beginning of every wasm loop.
     ///
     /// This can be used to insert explicit interrupt or safepoint checking at
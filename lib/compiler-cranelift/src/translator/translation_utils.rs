@@ -85,6 +85,8 @@ pub fn irreloc_to_relocationkind(reloc: Reloc) -> RelocationKind {
         Reloc::X86CallPLTRel4 => RelocationKind::X86CallPLTRel4,
         Reloc::X86GOTPCRel4 => RelocationKind::X86GOTPCRel4,
         Reloc::Arm64Call => RelocationKind::Arm64Call,
+        Reloc::S390xPCRel32Dbl => RelocationKind::S390xPCRel32Dbl,
+        Reloc::S390xPLTRel32Dbl => RelocationKind::S390xPLTRel32Dbl,
         _ => panic!("The relocation {} is not yet supported.", reloc),
     }
 }
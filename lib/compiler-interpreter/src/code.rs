@@ -0,0 +1,279 @@
+//! The code of the interpreter, which the function bodies of the Wasm
+//! modules are compiled to.
+//!
+//! The operators of a function are translated to a sequence of
+//! instructions working on a stack of 64-bit values, where the
+//! branches refer to the index of their target instruction and to the
+//! values they keep, and the locals to their place in the frame, so
+//! that no control structure needs to be tracked when running it.
+
+use rkyv::ser::serializers::AllocSerializer;
+use rkyv::ser::Serializer;
+use rkyv::{AlignedVec, Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
+use wasmer_types::{CompileError, DeserializeError};
+
+/// The code of a function.
+#[derive(RkyvSerialize, RkyvDeserialize, Archive, Debug, Clone, PartialEq, Eq)]
+#[archive_attr(derive(rkyv::CheckBytes))]
+pub(crate) struct FunctionCode {
+    /// The number of locals, excluding the parameters.
+    pub num_locals: u32,
+    /// The instructions of the function, which ends with a `Return`.
+    pub instrs: Vec<Instr>,
+}
+
+impl FunctionCode {
+    /// Serializes the code into the bytes of a function body.
+    pub fn serialize(&self) -> Result<Vec<u8>, CompileError> {
+        let mut serializer = AllocSerializer::<4096>::default();
+        serializer
+            .serialize_value(self)
+            .map_err(|e| CompileError::Codegen(format!("failed to serialize the code: {}", e)))?;
+        Ok(serializer.into_serializer().into_inner().to_vec())
+    }
+
+    /// Deserializes the code from the bytes of a function body.
+    pub fn deserialize(body: &[u8]) -> Result<Self, DeserializeError> {
+        // The bytes of the body aren't aligned in the artifact.
+        let mut aligned = AlignedVec::with_capacity(body.len());
+        aligned.extend_from_slice(body);
+        rkyv::from_bytes::<Self>(&aligned)
+            .map_err(|e| DeserializeError::CorruptedBinary(format!("invalid code: {}", e)))
+    }
+}
+
+/// A branch to the instruction `target`, moving the `keep` values on
+/// the top of the stack down by `drop` values.
+#[derive(RkyvSerialize, RkyvDeserialize, Archive, Debug, Clone, Copy, PartialEq, Eq)]
+#[archive_attr(derive(rkyv::CheckBytes))]
+pub(crate) struct Branch {
+    pub target: u32,
+    pub drop: u32,
+    pub keep: u32,
+}
+
+/// An instruction of the interpreter.
+///
+/// The numeric instructions are named after the operators they run,
+/// the `i32` values being zero-extended and the floats stored as their
+/// bits on the stack, so that the reinterpretations have nothing to do.
+/// The references are their raw value.
+#[derive(RkyvSerialize, RkyvDeserialize, Archive, Debug, Clone, PartialEq, Eq)]
+#[archive_attr(derive(rkyv::CheckBytes))]
+pub(crate) enum Instr {
+    Unreachable,
+    /// Branches unconditionally.
+    Br(Branch),
+    /// Pops a condition, and branches if it's not zero.
+    BrIf(Branch),
+    /// Pops a condition, and jumps to the instruction if it's zero.
+    BrIfNot(u32),
+    /// Pops an index, and takes the branch at this index, or the last
+    /// one if it's out of bounds.
+    BrTable(Vec<Branch>),
+    /// Returns the values on the top of the stack.
+    Return,
+    /// Calls the function with this `FunctionIndex`.
+    Call(u32),
+    /// Pops an index, and calls the function of the table at this index,
+    /// after checking its signature.
+    CallIndirect {
+        signature: u32,
+        table: u32,
+    },
+    Drop,
+    Select,
+    /// Pushes this raw value.
+    Const(u64),
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+
+    // The loads and stores of the memory 0, with the offset of their
+    // access.
+    I32Load(u32),
+    I64Load(u32),
+    F32Load(u32),
+    F64Load(u32),
+    I32Load8S(u32),
+    I32Load8U(u32),
+    I32Load16S(u32),
+    I32Load16U(u32),
+    I64Load8S(u32),
+    I64Load8U(u32),
+    I64Load16S(u32),
+    I64Load16U(u32),
+    I64Load32S(u32),
+    I64Load32U(u32),
+    I32Store(u32),
+    I64Store(u32),
+    F32Store(u32),
+    F64Store(u32),
+    I32Store8(u32),
+    I32Store16(u32),
+    I64Store8(u32),
+    I64Store16(u32),
+    I64Store32(u32),
+    MemorySize,
+    MemoryGrow,
+    MemoryInit(u32),
+    DataDrop(u32),
+    MemoryCopy,
+    MemoryFill,
+
+    RefIsNull,
+    RefFunc(u32),
+    TableGet(u32),
+    TableSet(u32),
+    TableSize(u32),
+    TableGrow(u32),
+    TableFill(u32),
+    TableCopy {
+        dst: u32,
+        src: u32,
+    },
+    TableInit {
+        segment: u32,
+        table: u32,
+    },
+    ElemDrop(u32),
+
+    I32Eqz,
+    I32Eq,
+    I32Ne,
+    I32LtS,
+    I32LtU,
+    I32GtS,
+    I32GtU,
+    I32LeS,
+    I32LeU,
+    I32GeS,
+    I32GeU,
+    I64Eqz,
+    I64Eq,
+    I64Ne,
+    I64LtS,
+    I64LtU,
+    I64GtS,
+    I64GtU,
+    I64LeS,
+    I64LeU,
+    I64GeS,
+    I64GeU,
+    F32Eq,
+    F32Ne,
+    F32Lt,
+    F32Gt,
+    F32Le,
+    F32Ge,
+    F64Eq,
+    F64Ne,
+    F64Lt,
+    F64Gt,
+    F64Le,
+    F64Ge,
+    I32Clz,
+    I32Ctz,
+    I32Popcnt,
+    I32Add,
+    I32Sub,
+    I32Mul,
+    I32DivS,
+    I32DivU,
+    I32RemS,
+    I32RemU,
+    I32And,
+    I32Or,
+    I32Xor,
+    I32Shl,
+    I32ShrS,
+    I32ShrU,
+    I32Rotl,
+    I32Rotr,
+    I64Clz,
+    I64Ctz,
+    I64Popcnt,
+    I64Add,
+    I64Sub,
+    I64Mul,
+    I64DivS,
+    I64DivU,
+    I64RemS,
+    I64RemU,
+    I64And,
+    I64Or,
+    I64Xor,
+    I64Shl,
+    I64ShrS,
+    I64ShrU,
+    I64Rotl,
+    I64Rotr,
+    F32Abs,
+    F32Neg,
+    F32Ceil,
+    F32Floor,
+    F32Trunc,
+    F32Nearest,
+    F32Sqrt,
+    F32Add,
+    F32Sub,
+    F32Mul,
+    F32Div,
+    F32Min,
+    F32Max,
+    F32Copysign,
+    F64Abs,
+    F64Neg,
+    F64Ceil,
+    F64Floor,
+    F64Trunc,
+    F64Nearest,
+    F64Sqrt,
+    F64Add,
+    F64Sub,
+    F64Mul,
+    F64Div,
+    F64Min,
+    F64Max,
+    F64Copysign,
+    I32WrapI64,
+    I32TruncF32S,
+    I32TruncF32U,
+    I32TruncF64S,
+    I32TruncF64U,
+    I64ExtendI32S,
+    I64ExtendI32U,
+    I64TruncF32S,
+    I64TruncF32U,
+    I64TruncF64S,
+    I64TruncF64U,
+    F32ConvertI32S,
+    F32ConvertI32U,
+    F32ConvertI64S,
+    F32ConvertI64U,
+    F32DemoteF64,
+    F64ConvertI32S,
+    F64ConvertI32U,
+    F64ConvertI64S,
+    F64ConvertI64U,
+    F64PromoteF32,
+    I32Extend8S,
+    I32Extend16S,
+    I64Extend8S,
+    I64Extend16S,
+    I64Extend32S,
+    I32TruncSatF32S,
+    I32TruncSatF32U,
+    I32TruncSatF64S,
+    I32TruncSatF64U,
+    I64TruncSatF32S,
+    I64TruncSatF32U,
+    I64TruncSatF64S,
+    I64TruncSatF64U,
+    /// Replaces a NaN `f32` on the top of the stack by the canonical one.
+    F32CanonicalizeNan,
+    /// Replaces a NaN `f64` on the top of the stack by the canonical one.
+    F64CanonicalizeNan,
+}
@@ -0,0 +1,139 @@
+//! Support for compiling the modules for the interpreter.
+
+use crate::config::Interpreter;
+use crate::runtime::Loader;
+use crate::translator::FunctionTranslator;
+use enumset::EnumSet;
+use std::sync::Arc;
+use wasmer_compiler::wptype_to_type;
+use wasmer_compiler::{
+    Compiler, FunctionBinaryReader, FunctionBodyData, FunctionInterpreter, MiddlewareBinaryReader,
+    ModuleMiddleware, ModuleMiddlewareChain, ModuleTranslationState,
+};
+use wasmer_types::entity::PrimaryMap;
+use wasmer_types::{
+    Compilation, CompileError, CompileModuleInfo, CompiledFunction, CompiledFunctionFrameInfo,
+    CpuFeature, FunctionBody, LocalFunctionIndex, ModuleInfo, Target, Type,
+};
+
+/// A compiler that compiles a WebAssembly module to the code of an
+/// interpreter, which runs without generating machine code.
+pub struct InterpreterCompiler {
+    config: Interpreter,
+}
+
+impl InterpreterCompiler {
+    /// Creates a new interpreter compiler
+    pub fn new(config: Interpreter) -> Self {
+        Self { config }
+    }
+}
+
+impl Compiler for InterpreterCompiler {
+    fn name(&self) -> &str {
+        "interpreter"
+    }
+
+    /// Get the middlewares for this compiler
+    fn get_middlewares(&self) -> &[Arc<dyn ModuleMiddleware>] {
+        &self.config.middlewares
+    }
+
+    /// Compile the module to the code of the interpreter. The code is the
+    /// same for every target, and there are no trampolines nor
+    /// relocations: the interpreter calls every function.
+    fn compile_module(
+        &self,
+        _target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) -> Result<Compilation, CompileError> {
+        let module = &compile_info.module;
+        check_module(module)?;
+        let functions = function_body_inputs
+            .iter()
+            .map(|(i, input)| {
+                let middleware_chain = self
+                    .config
+                    .middlewares
+                    .generate_function_middleware_chain(i);
+                let mut reader =
+                    MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
+                reader.set_middleware_chain(middleware_chain);
+                let index = module.func_index(i);
+                reader.set_num_params(
+                    module.signatures[module.functions[index]].params().len() as u32
+                );
+
+                // The locals exclude the parameters.
+                let mut num_locals = 0;
+                for _ in 0..reader.read_local_count()? {
+                    let (count, ty) = reader.read_local_decl()?;
+                    check_type(wptype_to_type(ty)?)?;
+                    num_locals += count;
+                }
+
+                let mut translator = FunctionTranslator::new(
+                    module,
+                    module_translation,
+                    index,
+                    num_locals,
+                    self.config.enable_nan_canonicalization,
+                );
+                while translator.has_control_frames() {
+                    let op = reader.read_operator()?;
+                    translator.feed_operator(op)?;
+                }
+
+                Ok(CompiledFunction {
+                    body: FunctionBody {
+                        body: translator.finalize().serialize()?,
+                        unwind_info: None,
+                    },
+                    relocations: vec![],
+                    frame_info: CompiledFunctionFrameInfo::default(),
+                })
+            })
+            .collect::<Result<PrimaryMap<LocalFunctionIndex, _>, CompileError>>()?;
+
+        Ok(Compilation {
+            functions,
+            custom_sections: PrimaryMap::new(),
+            function_call_trampolines: PrimaryMap::new(),
+            dynamic_function_trampolines: PrimaryMap::new(),
+            debug: None,
+        })
+    }
+
+    fn interpreter(&self) -> Option<&dyn FunctionInterpreter> {
+        Some(&Loader)
+    }
+
+    fn get_cpu_features_used(&self, _cpu_features: &EnumSet<CpuFeature>) -> EnumSet<CpuFeature> {
+        EnumSet::new()
+    }
+}
+
+/// Checks that the interpreter supports the types of the signatures
+/// and of the globals of `module`.
+fn check_module(module: &ModuleInfo) -> Result<(), CompileError> {
+    for signature in module.signatures.values() {
+        for ty in signature.params().iter().chain(signature.results()) {
+            check_type(*ty)?;
+        }
+    }
+    for global in module.globals.values() {
+        check_type(global.ty)?;
+    }
+    Ok(())
+}
+
+fn check_type(ty: Type) -> Result<(), CompileError> {
+    if ty == Type::V128 {
+        return Err(CompileError::UnsupportedFeature(
+            "SIMD with the interpreter".to_string(),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,21 @@
+//! A WebAssembly `Compiler` implementation running the functions with
+//! an interpreter.
+//!
+//! The functions are compiled to the code of the interpreter instead of
+//! machine code, so the modules run on the platforms forbidding to
+//! generate code at runtime, e.g. iOS, some game consoles or locked-down
+//! servers. Being much simpler than the other compilers, it's also a
+//! reference to test them against.
+//!
+//! Compared to Cranelift, LLVM and Singlepass, the interpreter compiles
+//! fast but runs the code much slower, and supports neither SIMD nor
+//! threads.
+
+mod code;
+mod compiler;
+mod config;
+mod runtime;
+mod translator;
+
+pub use crate::compiler::InterpreterCompiler;
+pub use crate::config::Interpreter;
@@ -0,0 +1,1020 @@
+//! The interpreter running the code of the functions.
+//!
+//! Every function is called through [`call_trampoline`], which runs it
+//! with its own stacks of values and frames. The calls between the
+//! interpreted functions stay in the interpreter, the other ones go
+//! through the call trampoline of the function called, like the calls
+//! from the host.
+
+use crate::code::{Branch, FunctionCode, Instr};
+use std::any::Any;
+use std::cmp;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use wasmer_compiler::{FunctionInterpreter, InterpretedFunctions};
+use wasmer_types::entity::PrimaryMap;
+use wasmer_types::{
+    CompileError, FunctionIndex, FunctionType, GlobalIndex, LocalFunctionIndex, ModuleInfo,
+    RawValue, SignatureIndex, TableIndex, TrapCode, Type, VMOffsets,
+};
+use wasmer_vm::libcalls::{
+    wasmer_vm_data_drop, wasmer_vm_elem_drop, wasmer_vm_f32_nearest, wasmer_vm_f64_nearest,
+    wasmer_vm_func_ref, wasmer_vm_imported_memory32_grow, wasmer_vm_imported_table_grow,
+    wasmer_vm_memory32_grow, wasmer_vm_memory32_init, wasmer_vm_table_copy, wasmer_vm_table_fill,
+    wasmer_vm_table_grow, wasmer_vm_table_init,
+};
+use wasmer_vm::{
+    catch_traps, raise_lib_trap, resume_panic, FunctionBodyPtr, TableElement, Trap,
+    VMCallerCheckedAnyfunc, VMContext, VMFuncRef, VMFunctionBody, VMGlobalDefinition,
+    VMMemoryDefinition, VMSharedSignatureIndex, VMTableDefinition, VMTrampoline,
+};
+
+/// The maximum number of frames on the stack of the interpreter.
+const MAX_FRAMES: usize = 100_000;
+
+/// The maximum number of values on the stack of the interpreter.
+const MAX_VALUES: usize = 1 << 22;
+
+/// The size of a page of the memories.
+const WASM_PAGE_SIZE: usize = 0x10000;
+
+/// The location of a memory or a table in the `VMContext`.
+#[derive(Debug, Clone, Copy)]
+enum Location {
+    /// The offset of its definition.
+    Local(u32),
+    /// The offset of the pointer to its definition.
+    Imported(u32),
+}
+
+impl Location {
+    unsafe fn definition<T>(self, vmctx: *mut VMContext) -> *mut T {
+        match self {
+            Self::Local(offset) => (vmctx as *mut u8).add(offset as usize) as *mut T,
+            Self::Imported(offset) => *((vmctx as *const u8).add(offset as usize) as *const *mut T),
+        }
+    }
+}
+
+/// A module loaded by the interpreter.
+struct Module {
+    info: ModuleInfo,
+    offsets: VMOffsets,
+    /// The offset of the pointer to the definition of each global, with
+    /// its type.
+    globals: PrimaryMap<GlobalIndex, (u32, Type)>,
+    tables: PrimaryMap<TableIndex, Location>,
+    /// The location of the memory 0.
+    memory: Option<Location>,
+    functions: PrimaryMap<LocalFunctionIndex, Function>,
+}
+
+/// A function loaded by the interpreter, whose pointer is the body of
+/// the function passed to [`call_trampoline`].
+struct Function {
+    /// The module of the function, which owns it.
+    module: *const Module,
+    signature: SignatureIndex,
+    num_params: u32,
+    num_locals: u32,
+    instrs: Vec<Instr>,
+}
+
+// The functions are only read once loaded, from any thread.
+unsafe impl Send for Module {}
+unsafe impl Sync for Module {}
+
+/// Loads the function bodies compiled by the interpreter compiler.
+pub(crate) struct Loader;
+
+impl FunctionInterpreter for Loader {
+    fn load(
+        &self,
+        module: &ModuleInfo,
+        function_bodies: &[&[u8]],
+    ) -> Result<InterpretedFunctions, CompileError> {
+        let offsets = VMOffsets::new(mem::size_of::<usize>() as u8, module);
+        let globals = module
+            .globals
+            .iter()
+            .map(|(index, global)| {
+                let offset = match module.local_global_index(index) {
+                    Some(local) => offsets.vmctx_vmglobal_definition(local),
+                    None => offsets.vmctx_vmglobal_import_definition(index),
+                };
+                (offset, global.ty)
+            })
+            .collect();
+        let tables = module
+            .tables
+            .keys()
+            .map(|index| match module.local_table_index(index) {
+                Some(local) => Location::Local(offsets.vmctx_vmtable_definition(local)),
+                None => Location::Imported(offsets.vmctx_vmtable_import_definition(index)),
+            })
+            .collect();
+        let memory =
+            module
+                .memories
+                .keys()
+                .next()
+                .map(|index| match module.local_memory_index(index) {
+                    Some(local) => Location::Local(offsets.vmctx_vmmemory_definition(local)),
+                    None => Location::Imported(offsets.vmctx_vmmemory_import_definition(index)),
+                });
+
+        let mut loaded = Box::new(Module {
+            info: module.clone(),
+            offsets,
+            globals,
+            tables,
+            memory,
+            functions: PrimaryMap::new(),
+        });
+        let module_ptr = &*loaded as *const Module;
+        for (i, body) in function_bodies.iter().enumerate() {
+            let code = FunctionCode::deserialize(body)
+                .map_err(|e| CompileError::Codegen(e.to_string()))?;
+            let index = module.func_index(LocalFunctionIndex::from_u32(i as u32));
+            let signature = module.functions[index];
+            loaded.functions.push(Function {
+                module: module_ptr,
+                signature,
+                num_params: module.signatures[signature].params().len() as u32,
+                num_locals: code.num_locals,
+                instrs: code.instrs,
+            });
+        }
+
+        let function_bodies = loaded
+            .functions
+            .values()
+            .map(|function| FunctionBodyPtr(function as *const Function as *const VMFunctionBody))
+            .collect();
+        Ok(InterpretedFunctions {
+            function_bodies,
+            call_trampoline,
+            code: loaded as Box<dyn Any + Send + Sync>,
+        })
+    }
+}
+
+/// The call trampoline of the interpreted functions, running the
+/// function `callee` with the arguments in `values`, and writing its
+/// results there.
+unsafe extern "C" fn call_trampoline(
+    vmctx: *mut VMContext,
+    callee: *const VMFunctionBody,
+    values: *mut RawValue,
+) {
+    let function = &*(callee as *const Function);
+    // The panics of the host functions called can't unwind through
+    // this function, they're resumed once it's left.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| Machine::call(vmctx, function, values)));
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(trap)) => raise_lib_trap(trap),
+        Err(payload) => resume_panic(payload),
+    }
+}
+
+/// Reads a value of type `ty` on the stack from a raw value.
+unsafe fn from_raw_value(raw: RawValue, ty: Type) -> u64 {
+    match ty {
+        Type::I32 | Type::F32 => raw.u32 as u64,
+        Type::I64 | Type::F64 => raw.u64,
+        Type::FuncRef | Type::ExternRef => raw.funcref as u64,
+        Type::V128 => unreachable!("the interpreter doesn't support SIMD"),
+    }
+}
+
+/// Writes a value of type `ty` on the stack to a raw value.
+fn to_raw_value(value: u64, ty: Type) -> RawValue {
+    let mut raw = RawValue { u128: 0 };
+    match ty {
+        Type::I32 | Type::F32 => raw.u32 = value as u32,
+        Type::I64 | Type::F64 => raw.u64 = value,
+        Type::FuncRef | Type::ExternRef => raw.funcref = value as usize,
+        Type::V128 => unreachable!("the interpreter doesn't support SIMD"),
+    }
+    raw
+}
+
+/// A value on the stack, with the type it has for an instruction.
+trait Value: Sized {
+    fn from_stack(value: u64) -> Self;
+    fn into_stack(self) -> u64;
+}
+
+macro_rules! value {
+    ($($ty:ty => |$value:ident| $from:expr, |$this:ident| $into:expr;)*) => {
+        $(impl Value for $ty {
+            fn from_stack($value: u64) -> Self {
+                $from
+            }
+
+            fn into_stack(self) -> u64 {
+                let $this = self;
+                $into
+            }
+        })*
+    };
+}
+
+value! {
+    i32 => |value| value as i32, |this| this as u32 as u64;
+    u32 => |value| value as u32, |this| this as u64;
+    i64 => |value| value as i64, |this| this as u64;
+    u64 => |value| value, |this| this;
+    f32 => |value| f32::from_bits(value as u32), |this| this.to_bits() as u64;
+    f64 => |value| f64::from_bits(value), |this| this.to_bits();
+    bool => |value| value != 0, |this| this as u64;
+}
+
+/// A function being called, below the one running.
+struct Frame {
+    function: *const Function,
+    vmctx: *mut VMContext,
+    /// The index of the instruction following the call.
+    pc: usize,
+    /// The index of the first local of the function on the stack.
+    base: usize,
+}
+
+/// The state of the interpreter during a call from the host.
+struct Machine {
+    stack: Vec<u64>,
+    frames: Vec<Frame>,
+}
+
+impl Machine {
+    /// Calls `function` with the arguments in `values`, and writes its
+    /// results there.
+    unsafe fn call(
+        vmctx: *mut VMContext,
+        function: &Function,
+        values: *mut RawValue,
+    ) -> Result<(), Trap> {
+        let module = &*function.module;
+        let signature = &module.info.signatures[function.signature];
+        let mut machine = Self {
+            stack: Vec::with_capacity(256),
+            frames: vec![],
+        };
+        for (i, ty) in signature.params().iter().enumerate() {
+            machine.stack.push(from_raw_value(*values.add(i), *ty));
+        }
+        machine.run(function, vmctx)?;
+        for (i, ty) in signature.results().iter().enumerate() {
+            *values.add(i) = to_raw_value(machine.stack[i], *ty);
+        }
+        Ok(())
+    }
+
+    /// Runs `function`, whose parameters are on the stack, until it
+    /// returns its results at the bottom of the stack.
+    unsafe fn run(&mut self, function: &Function, vmctx: *mut VMContext) -> Result<(), Trap> {
+        let mut function = function as *const Function;
+        let mut vmctx = vmctx;
+        let mut pc = 0;
+        let mut base = 0;
+        self.enter(&*function)?;
+
+        loop {
+            let current = &*function;
+            let module = &*current.module;
+            let instr = &current.instrs[pc];
+            pc += 1;
+            match instr {
+                Instr::Unreachable => return Err(Trap::lib(TrapCode::UnreachableCodeReached)),
+                Instr::Br(branch) => pc = self.branch(branch),
+                Instr::BrIf(branch) => {
+                    if self.pop() != 0 {
+                        pc = self.branch(branch);
+                    }
+                }
+                Instr::BrIfNot(target) => {
+                    if self.pop() == 0 {
+                        pc = *target as usize;
+                    }
+                }
+                Instr::BrTable(branches) => {
+                    let index = self.pop() as u32 as usize;
+                    let branch = branches
+                        .get(index)
+                        .unwrap_or_else(|| branches.last().unwrap());
+                    pc = self.branch(branch);
+                }
+                Instr::Return => {
+                    let signature = &module.info.signatures[current.signature];
+                    let num_results = signature.results().len();
+                    let len = self.stack.len();
+                    self.stack.copy_within(len - num_results..len, base);
+                    self.stack.truncate(base + num_results);
+                    match self.frames.pop() {
+                        Some(frame) => {
+                            function = frame.function;
+                            vmctx = frame.vmctx;
+                            pc = frame.pc;
+                            base = frame.base;
+                        }
+                        None => return Ok(()),
+                    }
+                }
+                Instr::Call(index) => {
+                    let index = FunctionIndex::from_u32(*index);
+                    match module.info.local_func_index(index) {
+                        Some(local) => {
+                            self.frames.push(Frame {
+                                function,
+                                vmctx,
+                                pc,
+                                base,
+                            });
+                            function = &module.functions[local];
+                            base = self.enter(&*function)?;
+                            pc = 0;
+                        }
+                        None => {
+                            let anyfunc = wasmer_vm_func_ref(vmctx, index.as_u32()).0.as_ref();
+                            let signature = &module.info.signatures[module.info.functions[index]];
+                            if let Some(callee) = self.call_anyfunc(anyfunc, signature)? {
+                                self.frames.push(Frame {
+                                    function,
+                                    vmctx,
+                                    pc,
+                                    base,
+                                });
+                                function = callee;
+                                vmctx = anyfunc.vmctx.vmctx;
+                                base = self.enter(&*function)?;
+                                pc = 0;
+                            }
+                        }
+                    }
+                }
+                Instr::CallIndirect { signature, table } => {
+                    let index = self.pop() as u32;
+                    let raw = *self.table_element(module, vmctx, *table, index)?;
+                    if raw == 0 {
+                        return Err(Trap::lib(TrapCode::IndirectCallToNull));
+                    }
+                    let anyfunc = &*(raw as *const VMCallerCheckedAnyfunc);
+                    let signature = SignatureIndex::from_u32(*signature);
+                    let expected = *((vmctx as *const u8)
+                        .add(module.offsets.vmctx_vmshared_signature_id(signature) as usize)
+                        as *const VMSharedSignatureIndex);
+                    if anyfunc.type_index != expected {
+                        return Err(Trap::lib(TrapCode::BadSignature));
+                    }
+                    let signature = &module.info.signatures[signature];
+                    if let Some(callee) = self.call_anyfunc(anyfunc, signature)? {
+                        self.frames.push(Frame {
+                            function,
+                            vmctx,
+                            pc,
+                            base,
+                        });
+                        function = callee;
+                        vmctx = anyfunc.vmctx.vmctx;
+                        base = self.enter(&*function)?;
+                        pc = 0;
+                    }
+                }
+                Instr::Drop => {
+                    self.pop();
+                }
+                Instr::Select => {
+                    let condition = self.pop();
+                    let other = self.pop();
+                    if condition == 0 {
+                        *self.top() = other;
+                    }
+                }
+                Instr::Const(value) => self.stack.push(*value),
+                Instr::LocalGet(index) => self.stack.push(self.stack[base + *index as usize]),
+                Instr::LocalSet(index) => {
+                    let value = self.pop();
+                    self.stack[base + *index as usize] = value;
+                }
+                Instr::LocalTee(index) => {
+                    let value = *self.top();
+                    self.stack[base + *index as usize] = value;
+                }
+                Instr::GlobalGet(index) => {
+                    let (global, ty) = module.global(vmctx, *index);
+                    self.stack.push(from_raw_value((*global).val, ty));
+                }
+                Instr::GlobalSet(index) => {
+                    let (global, ty) = module.global(vmctx, *index);
+                    (*global).val = to_raw_value(self.pop(), ty);
+                }
+
+                Instr::I32Load(offset) => self.load(module, vmctx, *offset, i32::from_le_bytes)?,
+                Instr::I64Load(offset) => self.load(module, vmctx, *offset, i64::from_le_bytes)?,
+                Instr::F32Load(offset) => self.load(module, vmctx, *offset, f32::from_le_bytes)?,
+                Instr::F64Load(offset) => self.load(module, vmctx, *offset, f64::from_le_bytes)?,
+                Instr::I32Load8S(offset) => {
+                    self.load(module, vmctx, *offset, |b| i8::from_le_bytes(b) as i32)?
+                }
+                Instr::I32Load8U(offset) => {
+                    self.load(module, vmctx, *offset, |b| u8::from_le_bytes(b) as u32)?
+                }
+                Instr::I32Load16S(offset) => {
+                    self.load(module, vmctx, *offset, |b| i16::from_le_bytes(b) as i32)?
+                }
+                Instr::I32Load16U(offset) => {
+                    self.load(module, vmctx, *offset, |b| u16::from_le_bytes(b) as u32)?
+                }
+                Instr::I64Load8S(offset) => {
+                    self.load(module, vmctx, *offset, |b| i8::from_le_bytes(b) as i64)?
+                }
+                Instr::I64Load8U(offset) => {
+                    self.load(module, vmctx, *offset, |b| u8::from_le_bytes(b) as u64)?
+                }
+                Instr::I64Load16S(offset) => {
+                    self.load(module, vmctx, *offset, |b| i16::from_le_bytes(b) as i64)?
+                }
+                Instr::I64Load16U(offset) => {
+                    self.load(module, vmctx, *offset, |b| u16::from_le_bytes(b) as u64)?
+                }
+                Instr::I64Load32S(offset) => {
+                    self.load(module, vmctx, *offset, |b| i32::from_le_bytes(b) as i64)?
+                }
+                Instr::I64Load32U(offset) => {
+                    self.load(module, vmctx, *offset, |b| u32::from_le_bytes(b) as u64)?
+                }
+                Instr::I32Store(offset) => self.store(module, vmctx, *offset, i32::to_le_bytes)?,
+                Instr::I64Store(offset) => self.store(module, vmctx, *offset, i64::to_le_bytes)?,
+                Instr::F32Store(offset) => self.store(module, vmctx, *offset, f32::to_le_bytes)?,
+                Instr::F64Store(offset) => self.store(module, vmctx, *offset, f64::to_le_bytes)?,
+                Instr::I32Store8(offset) => {
+                    self.store(module, vmctx, *offset, |v: i32| (v as i8).to_le_bytes())?
+                }
+                Instr::I32Store16(offset) => {
+                    self.store(module, vmctx, *offset, |v: i32| (v as i16).to_le_bytes())?
+                }
+                Instr::I64Store8(offset) => {
+                    self.store(module, vmctx, *offset, |v: i64| (v as i8).to_le_bytes())?
+                }
+                Instr::I64Store16(offset) => {
+                    self.store(module, vmctx, *offset, |v: i64| (v as i16).to_le_bytes())?
+                }
+                Instr::I64Store32(offset) => {
+                    self.store(module, vmctx, *offset, |v: i64| (v as i32).to_le_bytes())?
+                }
+                Instr::MemorySize => {
+                    let memory = &*module.memory(vmctx);
+                    self.stack
+                        .push((memory.current_length / WASM_PAGE_SIZE) as u64);
+                }
+                Instr::MemoryGrow => {
+                    let delta = self.pop() as u32;
+                    let previous = match module.memory.unwrap() {
+                        Location::Local(_) => wasmer_vm_memory32_grow(vmctx, delta, 0),
+                        Location::Imported(_) => wasmer_vm_imported_memory32_grow(vmctx, delta, 0),
+                    };
+                    self.stack.push(previous as u64);
+                }
+                Instr::MemoryInit(segment) => {
+                    let (dst, src, len) = self.pop3();
+                    catch_traps(None, || {
+                        wasmer_vm_memory32_init(vmctx, 0, *segment, dst, src, len)
+                    })?;
+                }
+                Instr::DataDrop(segment) => wasmer_vm_data_drop(vmctx, *segment),
+                Instr::MemoryCopy => {
+                    let (dst, src, len) = self.pop3();
+                    let memory = &*module.memory(vmctx);
+                    let dst = memory_range(memory, dst, len)?;
+                    let src = memory_range(memory, src, len)?;
+                    ptr::copy(src, dst, len as usize);
+                }
+                Instr::MemoryFill => {
+                    let (dst, value, len) = self.pop3();
+                    let memory = &*module.memory(vmctx);
+                    let dst = memory_range(memory, dst, len)?;
+                    ptr::write_bytes(dst, value as u8, len as usize);
+                }
+
+                Instr::RefIsNull => {
+                    let value = self.top();
+                    *value = (*value == 0) as u64;
+                }
+                Instr::RefFunc(index) => {
+                    let func_ref = wasmer_vm_func_ref(vmctx, *index);
+                    self.stack.push(func_ref.0.as_ptr() as u64);
+                }
+                Instr::TableGet(table) => {
+                    let index = self.pop() as u32;
+                    let value = *self.table_element(module, vmctx, *table, index)?;
+                    self.stack.push(value as u64);
+                }
+                Instr::TableSet(table) => {
+                    let value = self.pop();
+                    let index = self.pop() as u32;
+                    *self.table_element(module, vmctx, *table, index)? = value as usize;
+                }
+                Instr::TableSize(table) => {
+                    let table = &*module.table(vmctx, *table);
+                    self.stack.push(table.current_elements as u64);
+                }
+                Instr::TableGrow(table) => {
+                    let delta = self.pop() as u32;
+                    let init = table_element(self.pop());
+                    let index = TableIndex::from_u32(*table);
+                    let previous = match module.info.local_table_index(index) {
+                        Some(local) => {
+                            wasmer_vm_table_grow(vmctx, init.into(), delta, local.as_u32())
+                        }
+                        None => wasmer_vm_imported_table_grow(vmctx, init.into(), delta, *table),
+                    };
+                    self.stack.push(previous as u64);
+                }
+                Instr::TableFill(table) => {
+                    let len = self.pop() as u32;
+                    let value = table_element(self.pop());
+                    let start = self.pop() as u32;
+                    catch_traps(None, || {
+                        wasmer_vm_table_fill(vmctx, *table, start, value.into(), len)
+                    })?;
+                }
+                Instr::TableCopy { dst, src } => {
+                    let (dst_index, src_index, len) = self.pop3();
+                    catch_traps(None, || {
+                        wasmer_vm_table_copy(vmctx, *dst, *src, dst_index, src_index, len)
+                    })?;
+                }
+                Instr::TableInit { segment, table } => {
+                    let (dst, src, len) = self.pop3();
+                    catch_traps(None, || {
+                        wasmer_vm_table_init(vmctx, *table, *segment, dst, src, len)
+                    })?;
+                }
+                Instr::ElemDrop(segment) => wasmer_vm_elem_drop(vmctx, *segment),
+
+                Instr::I32Eqz => self.unary(|a: i32| a == 0),
+                Instr::I32Eq => self.binary(|a: i32, b| a == b),
+                Instr::I32Ne => self.binary(|a: i32, b| a != b),
+                Instr::I32LtS => self.binary(|a: i32, b| a < b),
+                Instr::I32LtU => self.binary(|a: u32, b| a < b),
+                Instr::I32GtS => self.binary(|a: i32, b| a > b),
+                Instr::I32GtU => self.binary(|a: u32, b| a > b),
+                Instr::I32LeS => self.binary(|a: i32, b| a <= b),
+                Instr::I32LeU => self.binary(|a: u32, b| a <= b),
+                Instr::I32GeS => self.binary(|a: i32, b| a >= b),
+                Instr::I32GeU => self.binary(|a: u32, b| a >= b),
+                Instr::I64Eqz => self.unary(|a: i64| a == 0),
+                Instr::I64Eq => self.binary(|a: i64, b| a == b),
+                Instr::I64Ne => self.binary(|a: i64, b| a != b),
+                Instr::I64LtS => self.binary(|a: i64, b| a < b),
+                Instr::I64LtU => self.binary(|a: u64, b| a < b),
+                Instr::I64GtS => self.binary(|a: i64, b| a > b),
+                Instr::I64GtU => self.binary(|a: u64, b| a > b),
+                Instr::I64LeS => self.binary(|a: i64, b| a <= b),
+                Instr::I64LeU => self.binary(|a: u64, b| a <= b),
+                Instr::I64GeS => self.binary(|a: i64, b| a >= b),
+                Instr::I64GeU => self.binary(|a: u64, b| a >= b),
+                Instr::F32Eq => self.binary(|a: f32, b| a == b),
+                Instr::F32Ne => self.binary(|a: f32, b| a != b),
+                Instr::F32Lt => self.binary(|a: f32, b| a < b),
+                Instr::F32Gt => self.binary(|a: f32, b| a > b),
+                Instr::F32Le => self.binary(|a: f32, b| a <= b),
+                Instr::F32Ge => self.binary(|a: f32, b| a >= b),
+                Instr::F64Eq => self.binary(|a: f64, b| a == b),
+                Instr::F64Ne => self.binary(|a: f64, b| a != b),
+                Instr::F64Lt => self.binary(|a: f64, b| a < b),
+                Instr::F64Gt => self.binary(|a: f64, b| a > b),
+                Instr::F64Le => self.binary(|a: f64, b| a <= b),
+                Instr::F64Ge => self.binary(|a: f64, b| a >= b),
+
+                Instr::I32Clz => self.unary(|a: u32| a.leading_zeros()),
+                Instr::I32Ctz => self.unary(|a: u32| a.trailing_zeros()),
+                Instr::I32Popcnt => self.unary(|a: u32| a.count_ones()),
+                Instr::I32Add => self.binary(|a: i32, b| a.wrapping_add(b)),
+                Instr::I32Sub => self.binary(|a: i32, b| a.wrapping_sub(b)),
+                Instr::I32Mul => self.binary(|a: i32, b| a.wrapping_mul(b)),
+                Instr::I32DivS => self.try_binary(|a: i32, b| {
+                    if b == 0 {
+                        return Err(TrapCode::IntegerDivisionByZero);
+                    }
+                    a.checked_div(b).ok_or(TrapCode::IntegerOverflow)
+                })?,
+                Instr::I32DivU => self.try_binary(|a: u32, b| {
+                    a.checked_div(b).ok_or(TrapCode::IntegerDivisionByZero)
+                })?,
+                Instr::I32RemS => self.try_binary(|a: i32, b| {
+                    if b == 0 {
+                        return Err(TrapCode::IntegerDivisionByZero);
+                    }
+                    Ok(a.wrapping_rem(b))
+                })?,
+                Instr::I32RemU => self.try_binary(|a: u32, b| {
+                    a.checked_rem(b).ok_or(TrapCode::IntegerDivisionByZero)
+                })?,
+                Instr::I32And => self.binary(|a: u32, b| a & b),
+                Instr::I32Or => self.binary(|a: u32, b| a | b),
+                Instr::I32Xor => self.binary(|a: u32, b| a ^ b),
+                Instr::I32Shl => self.binary(|a: u32, b| a.wrapping_shl(b)),
+                Instr::I32ShrS => self.binary(|a: i32, b| a.wrapping_shr(b as u32)),
+                Instr::I32ShrU => self.binary(|a: u32, b| a.wrapping_shr(b)),
+                Instr::I32Rotl => self.binary(|a: u32, b| a.rotate_left(b % 32)),
+                Instr::I32Rotr => self.binary(|a: u32, b| a.rotate_right(b % 32)),
+                Instr::I64Clz => self.unary(|a: u64| a.leading_zeros() as u64),
+                Instr::I64Ctz => self.unary(|a: u64| a.trailing_zeros() as u64),
+                Instr::I64Popcnt => self.unary(|a: u64| a.count_ones() as u64),
+                Instr::I64Add => self.binary(|a: i64, b| a.wrapping_add(b)),
+                Instr::I64Sub => self.binary(|a: i64, b| a.wrapping_sub(b)),
+                Instr::I64Mul => self.binary(|a: i64, b| a.wrapping_mul(b)),
+                Instr::I64DivS => self.try_binary(|a: i64, b| {
+                    if b == 0 {
+                        return Err(TrapCode::IntegerDivisionByZero);
+                    }
+                    a.checked_div(b).ok_or(TrapCode::IntegerOverflow)
+                })?,
+                Instr::I64DivU => self.try_binary(|a: u64, b| {
+                    a.checked_div(b).ok_or(TrapCode::IntegerDivisionByZero)
+                })?,
+                Instr::I64RemS => self.try_binary(|a: i64, b| {
+                    if b == 0 {
+                        return Err(TrapCode::IntegerDivisionByZero);
+                    }
+                    Ok(a.wrapping_rem(b))
+                })?,
+                Instr::I64RemU => self.try_binary(|a: u64, b| {
+                    a.checked_rem(b).ok_or(TrapCode::IntegerDivisionByZero)
+                })?,
+                Instr::I64And => self.binary(|a: u64, b| a & b),
+                Instr::I64Or => self.binary(|a: u64, b| a | b),
+                Instr::I64Xor => self.binary(|a: u64, b| a ^ b),
+                Instr::I64Shl => self.binary(|a: u64, b| a.wrapping_shl(b as u32)),
+                Instr::I64ShrS => self.binary(|a: i64, b| a.wrapping_shr(b as u32)),
+                Instr::I64ShrU => self.binary(|a: u64, b| a.wrapping_shr(b as u32)),
+                Instr::I64Rotl => self.binary(|a: u64, b| a.rotate_left((b % 64) as u32)),
+                Instr::I64Rotr => self.binary(|a: u64, b| a.rotate_right((b % 64) as u32)),
+
+                Instr::F32Abs => self.unary(f32::abs),
+                Instr::F32Neg => self.unary(|a: f32| -a),
+                Instr::F32Ceil => self.unary(|a: f32| round_f32(a, f32::ceil)),
+                Instr::F32Floor => self.unary(|a: f32| round_f32(a, f32::floor)),
+                Instr::F32Trunc => self.unary(|a: f32| round_f32(a, f32::trunc)),
+                Instr::F32Nearest => {
+                    self.unary(|a: f32| round_f32(a, |a| wasmer_vm_f32_nearest(a)))
+                }
+                Instr::F32Sqrt => self.unary(f32::sqrt),
+                Instr::F32Add => self.binary(|a: f32, b| a + b),
+                Instr::F32Sub => self.binary(|a: f32, b| a - b),
+                Instr::F32Mul => self.binary(|a: f32, b| a * b),
+                Instr::F32Div => self.binary(|a: f32, b| a / b),
+                Instr::F32Min => self.binary(|a: f32, b| {
+                    if a.is_nan() || b.is_nan() {
+                        a + b
+                    } else if a == b {
+                        // The minimum of the zeros is the negative one.
+                        f32::from_bits(a.to_bits() | b.to_bits())
+                    } else {
+                        a.min(b)
+                    }
+                }),
+                Instr::F32Max => self.binary(|a: f32, b| {
+                    if a.is_nan() || b.is_nan() {
+                        a + b
+                    } else if a == b {
+                        // The maximum of the zeros is the positive one.
+                        f32::from_bits(a.to_bits() & b.to_bits())
+                    } else {
+                        a.max(b)
+                    }
+                }),
+                Instr::F32Copysign => self.binary(f32::copysign),
+                Instr::F64Abs => self.unary(f64::abs),
+                Instr::F64Neg => self.unary(|a: f64| -a),
+                Instr::F64Ceil => self.unary(|a: f64| round_f64(a, f64::ceil)),
+                Instr::F64Floor => self.unary(|a: f64| round_f64(a, f64::floor)),
+                Instr::F64Trunc => self.unary(|a: f64| round_f64(a, f64::trunc)),
+                Instr::F64Nearest => {
+                    self.unary(|a: f64| round_f64(a, |a| wasmer_vm_f64_nearest(a)))
+                }
+                Instr::F64Sqrt => self.unary(f64::sqrt),
+                Instr::F64Add => self.binary(|a: f64, b| a + b),
+                Instr::F64Sub => self.binary(|a: f64, b| a - b),
+                Instr::F64Mul => self.binary(|a: f64, b| a * b),
+                Instr::F64Div => self.binary(|a: f64, b| a / b),
+                Instr::F64Min => self.binary(|a: f64, b| {
+                    if a.is_nan() || b.is_nan() {
+                        a + b
+                    } else if a == b {
+                        f64::from_bits(a.to_bits() | b.to_bits())
+                    } else {
+                        a.min(b)
+                    }
+                }),
+                Instr::F64Max => self.binary(|a: f64, b| {
+                    if a.is_nan() || b.is_nan() {
+                        a + b
+                    } else if a == b {
+                        f64::from_bits(a.to_bits() & b.to_bits())
+                    } else {
+                        a.max(b)
+                    }
+                }),
+                Instr::F64Copysign => self.binary(f64::copysign),
+
+                Instr::I32WrapI64 => self.unary(|a: i64| a as i32),
+                Instr::I32TruncF32S => self.try_unary(|a: f32| {
+                    trunc(a as f64, -2147483648.0, 2147483648.0).map(|a| a as i32)
+                })?,
+                Instr::I32TruncF32U => {
+                    self.try_unary(|a: f32| trunc(a as f64, 0.0, 4294967296.0).map(|a| a as u32))?
+                }
+                Instr::I32TruncF64S => self
+                    .try_unary(|a: f64| trunc(a, -2147483648.0, 2147483648.0).map(|a| a as i32))?,
+                Instr::I32TruncF64U => {
+                    self.try_unary(|a: f64| trunc(a, 0.0, 4294967296.0).map(|a| a as u32))?
+                }
+                Instr::I64ExtendI32S => self.unary(|a: i32| a as i64),
+                Instr::I64ExtendI32U => self.unary(|a: u32| a as u64),
+                Instr::I64TruncF32S => self.try_unary(|a: f32| {
+                    trunc(a as f64, -9223372036854775808.0, 9223372036854775808.0).map(|a| a as i64)
+                })?,
+                Instr::I64TruncF32U => self.try_unary(|a: f32| {
+                    trunc(a as f64, 0.0, 18446744073709551616.0).map(|a| a as u64)
+                })?,
+                Instr::I64TruncF64S => self.try_unary(|a: f64| {
+                    trunc(a, -9223372036854775808.0, 9223372036854775808.0).map(|a| a as i64)
+                })?,
+                Instr::I64TruncF64U => self
+                    .try_unary(|a: f64| trunc(a, 0.0, 18446744073709551616.0).map(|a| a as u64))?,
+                Instr::F32ConvertI32S => self.unary(|a: i32| a as f32),
+                Instr::F32ConvertI32U => self.unary(|a: u32| a as f32),
+                Instr::F32ConvertI64S => self.unary(|a: i64| a as f32),
+                Instr::F32ConvertI64U => self.unary(|a: u64| a as f32),
+                Instr::F32DemoteF64 => self.unary(|a: f64| a as f32),
+                Instr::F64ConvertI32S => self.unary(|a: i32| a as f64),
+                Instr::F64ConvertI32U => self.unary(|a: u32| a as f64),
+                Instr::F64ConvertI64S => self.unary(|a: i64| a as f64),
+                Instr::F64ConvertI64U => self.unary(|a: u64| a as f64),
+                Instr::F64PromoteF32 => self.unary(|a: f32| a as f64),
+                Instr::I32Extend8S => self.unary(|a: i32| a as i8 as i32),
+                Instr::I32Extend16S => self.unary(|a: i32| a as i16 as i32),
+                Instr::I64Extend8S => self.unary(|a: i64| a as i8 as i64),
+                Instr::I64Extend16S => self.unary(|a: i64| a as i16 as i64),
+                Instr::I64Extend32S => self.unary(|a: i64| a as i32 as i64),
+                // The casts of the floats to the integers saturate.
+                Instr::I32TruncSatF32S => self.unary(|a: f32| a as i32),
+                Instr::I32TruncSatF32U => self.unary(|a: f32| a as u32),
+                Instr::I32TruncSatF64S => self.unary(|a: f64| a as i32),
+                Instr::I32TruncSatF64U => self.unary(|a: f64| a as u32),
+                Instr::I64TruncSatF32S => self.unary(|a: f32| a as i64),
+                Instr::I64TruncSatF32U => self.unary(|a: f32| a as u64),
+                Instr::I64TruncSatF64S => self.unary(|a: f64| a as i64),
+                Instr::I64TruncSatF64U => self.unary(|a: f64| a as u64),
+                Instr::F32CanonicalizeNan => self.unary(|a: f32| {
+                    if a.is_nan() {
+                        f32::from_bits(0x7fc0_0000)
+                    } else {
+                        a
+                    }
+                }),
+                Instr::F64CanonicalizeNan => self.unary(|a: f64| {
+                    if a.is_nan() {
+                        f64::from_bits(0x7ff8_0000_0000_0000)
+                    } else {
+                        a
+                    }
+                }),
+            }
+        }
+    }
+
+    /// Pushes the locals of `function`, whose parameters are on the
+    /// stack, and returns the index of its first local.
+    fn enter(&mut self, function: &Function) -> Result<usize, Trap> {
+        if self.frames.len() >= MAX_FRAMES || self.stack.len() >= MAX_VALUES {
+            return Err(Trap::lib(TrapCode::StackOverflow));
+        }
+        let base = self.stack.len() - function.num_params as usize;
+        self.stack
+            .resize(self.stack.len() + function.num_locals as usize, 0);
+        Ok(base)
+    }
+
+    /// Calls the function of `anyfunc`, whose arguments are on the
+    /// stack. The interpreted functions are returned to be run by the
+    /// interpreter, the other ones are called through their call
+    /// trampoline, leaving their results on the stack.
+    unsafe fn call_anyfunc<'a>(
+        &mut self,
+        anyfunc: &'a VMCallerCheckedAnyfunc,
+        signature: &FunctionType,
+    ) -> Result<Option<&'a Function>, Trap> {
+        if anyfunc.call_trampoline as usize == call_trampoline as VMTrampoline as usize {
+            return Ok(Some(&*(anyfunc.func_ptr as *const Function)));
+        }
+        let (params, results) = (signature.params(), signature.results());
+        let mut values = vec![RawValue { u128: 0 }; cmp::max(params.len(), results.len())];
+        let first = self.stack.len() - params.len();
+        for ((value, ty), arg) in values.iter_mut().zip(params).zip(&self.stack[first..]) {
+            *value = to_raw_value(*arg, *ty);
+        }
+        self.stack.truncate(first);
+        catch_traps(None, || {
+            (anyfunc.call_trampoline)(anyfunc.vmctx.vmctx, anyfunc.func_ptr, values.as_mut_ptr())
+        })?;
+        for (value, ty) in values.iter().zip(results) {
+            self.stack.push(from_raw_value(*value, *ty));
+        }
+        Ok(None)
+    }
+
+    /// Takes `branch`, returning the index of its target.
+    fn branch(&mut self, branch: &Branch) -> usize {
+        if branch.drop > 0 {
+            let len = self.stack.len();
+            let keep = len - branch.keep as usize;
+            self.stack
+                .copy_within(keep..len, keep - branch.drop as usize);
+            self.stack.truncate(len - branch.drop as usize);
+        }
+        branch.target as usize
+    }
+
+    fn pop(&mut self) -> u64 {
+        self.stack.pop().unwrap()
+    }
+
+    /// Pops the three `i32` operands of the bulk memory and table
+    /// instructions.
+    fn pop3(&mut self) -> (u32, u32, u32) {
+        let c = self.pop() as u32;
+        let b = self.pop() as u32;
+        let a = self.pop() as u32;
+        (a, b, c)
+    }
+
+    fn top(&mut self) -> &mut u64 {
+        self.stack.last_mut().unwrap()
+    }
+
+    fn unary<A: Value, R: Value>(&mut self, f: impl FnOnce(A) -> R) {
+        let top = self.top();
+        *top = f(A::from_stack(*top)).into_stack();
+    }
+
+    fn binary<A: Value, R: Value>(&mut self, f: impl FnOnce(A, A) -> R) {
+        let b = A::from_stack(self.pop());
+        let top = self.top();
+        *top = f(A::from_stack(*top), b).into_stack();
+    }
+
+    fn try_unary<A: Value, R: Value>(
+        &mut self,
+        f: impl FnOnce(A) -> Result<R, TrapCode>,
+    ) -> Result<(), Trap> {
+        let top = self.top();
+        *top = f(A::from_stack(*top)).map_err(Trap::lib)?.into_stack();
+        Ok(())
+    }
+
+    fn try_binary<A: Value, R: Value>(
+        &mut self,
+        f: impl FnOnce(A, A) -> Result<R, TrapCode>,
+    ) -> Result<(), Trap> {
+        let b = A::from_stack(self.pop());
+        let top = self.top();
+        *top = f(A::from_stack(*top), b).map_err(Trap::lib)?.into_stack();
+        Ok(())
+    }
+
+    /// Pops an address, and pushes the value of the memory there read
+    /// by `f`.
+    unsafe fn load<R: Value, const N: usize>(
+        &mut self,
+        module: &Module,
+        vmctx: *mut VMContext,
+        offset: u32,
+        f: impl FnOnce([u8; N]) -> R,
+    ) -> Result<(), Trap> {
+        let address = self.pop() as u32;
+        let memory = &*module.memory(vmctx);
+        let bytes = memory_access::<N>(memory, address, offset)?;
+        self.stack
+            .push(f(ptr::read_unaligned(bytes as *const [u8; N])).into_stack());
+        Ok(())
+    }
+
+    /// Pops a value and an address, and writes the value to the memory
+    /// there as `f` turns it into bytes.
+    unsafe fn store<A: Value, const N: usize>(
+        &mut self,
+        module: &Module,
+        vmctx: *mut VMContext,
+        offset: u32,
+        f: impl FnOnce(A) -> [u8; N],
+    ) -> Result<(), Trap> {
+        let value = A::from_stack(self.pop());
+        let address = self.pop() as u32;
+        let memory = &*module.memory(vmctx);
+        let bytes = memory_access::<N>(memory, address, offset)?;
+        ptr::write_unaligned(bytes as *mut [u8; N], f(value));
+        Ok(())
+    }
+
+    /// Returns the element `index` of the table `table`.
+    unsafe fn table_element(
+        &self,
+        module: &Module,
+        vmctx: *mut VMContext,
+        table: u32,
+        index: u32,
+    ) -> Result<*mut usize, Trap> {
+        let table = &*module.table(vmctx, table);
+        if index >= table.current_elements {
+            return Err(Trap::lib(TrapCode::TableAccessOutOfBounds));
+        }
+        Ok((table.base as *mut usize).add(index as usize))
+    }
+}
+
+impl Module {
+    /// Returns the definition of the global `index`, with its type.
+    unsafe fn global(&self, vmctx: *mut VMContext, index: u32) -> (*mut VMGlobalDefinition, Type) {
+        let (offset, ty) = self.globals[GlobalIndex::from_u32(index)];
+        let global = *((vmctx as *const u8).add(offset as usize) as *const *mut VMGlobalDefinition);
+        (global, ty)
+    }
+
+    unsafe fn memory(&self, vmctx: *mut VMContext) -> *const VMMemoryDefinition {
+        self.memory
+            .expect("the memory is validated")
+            .definition(vmctx)
+    }
+
+    unsafe fn table(&self, vmctx: *mut VMContext, index: u32) -> *const VMTableDefinition {
+        self.tables[TableIndex::from_u32(index)].definition(vmctx)
+    }
+}
+
+/// A table element whose raw value is `value`.
+unsafe fn table_element(value: u64) -> TableElement {
+    // The raw table elements are the same for both kinds of references,
+    // their kind is the one of the table.
+    TableElement::FuncRef(VMFuncRef::from_raw(RawValue {
+        funcref: value as usize,
+    }))
+}
+
+/// Returns the `N` bytes of `memory` accessed at `address + offset`.
+fn memory_access<const N: usize>(
+    memory: &VMMemoryDefinition,
+    address: u32,
+    offset: u32,
+) -> Result<*mut u8, Trap> {
+    let start = address as u64 + offset as u64;
+    if start + N as u64 > memory.current_length as u64 {
+        return Err(Trap::lib(TrapCode::HeapAccessOutOfBounds));
+    }
+    Ok(unsafe { memory.base.add(start as usize) })
+}
+
+/// Returns the `len` bytes of `memory` at `start`.
+fn memory_range(memory: &VMMemoryDefinition, start: u32, len: u32) -> Result<*mut u8, Trap> {
+    if start as u64 + len as u64 > memory.current_length as u64 {
+        return Err(Trap::lib(TrapCode::HeapAccessOutOfBounds));
+    }
+    Ok(unsafe { memory.base.add(start as usize) })
+}
+
+/// Rounds `value` with `round`, which keeps a signaling NaN as is
+/// where the operators of Wasm must return a quiet one.
+fn round_f32(value: f32, round: impl FnOnce(f32) -> f32) -> f32 {
+    if value.is_nan() {
+        f32::from_bits(value.to_bits() | 0x0040_0000)
+    } else {
+        round(value)
+    }
+}
+
+/// Rounds `value` with `round`, which keeps a signaling NaN as is
+/// where the operators of Wasm must return a quiet one.
+fn round_f64(value: f64, round: impl FnOnce(f64) -> f64) -> f64 {
+    if value.is_nan() {
+        f64::from_bits(value.to_bits() | 0x0008_0000_0000_0000)
+    } else {
+        round(value)
+    }
+}
+
+/// Truncates `value` for the conversion to an integer in
+/// `min..max_exclusive`, or returns the trap of the conversion.
+fn trunc(value: f64, min: f64, max_exclusive: f64) -> Result<f64, TrapCode> {
+    if value.is_nan() {
+        return Err(TrapCode::BadConversionToInteger);
+    }
+    let value = value.trunc();
+    if value < min || value >= max_exclusive {
+        return Err(TrapCode::IntegerOverflow);
+    }
+    Ok(value)
+}
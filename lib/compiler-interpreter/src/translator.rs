@@ -0,0 +1,552 @@
+//! Translation of the operators of a function to the code of the
+//! interpreter.
+//!
+//! The height of the stack of operands is known at each operator, since
+//! the function is valid, so the branches know how many values they
+//! drop when leaving their blocks.
+
+use crate::code::{Branch, FunctionCode, Instr};
+use std::convert::TryFrom;
+use wasmer_compiler::wasmparser::{MemoryImmediate, Operator};
+use wasmer_compiler::{from_binaryreadererror_wasmerror, ModuleTranslationState};
+use wasmer_types::{CompileError, FunctionIndex, ModuleInfo, SignatureIndex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ControlKind {
+    /// A `block`, or the body of the function.
+    Block,
+    Loop,
+    If,
+    Else,
+}
+
+/// A branch to the end of a block, whose target is set once the end is
+/// reached.
+#[derive(Debug, Clone, Copy)]
+enum Fixup {
+    /// The `Br` or `BrIf` at this index.
+    Instr(usize),
+    /// The branch of the `BrTable` at this index.
+    Table(usize, usize),
+}
+
+/// A block being translated.
+#[derive(Debug)]
+struct Control {
+    kind: ControlKind,
+    /// The height of the stack below the parameters of the block.
+    height: u32,
+    num_params: u32,
+    num_results: u32,
+    /// The first instruction of a loop, the target of its branches.
+    start: u32,
+    /// The branches to the end of the block.
+    fixups: Vec<Fixup>,
+    /// The `BrIfNot` of an `if` jumping to its `else` or to its end.
+    else_fixup: Option<usize>,
+    /// Whether the rest of the block is unreachable.
+    unreachable: bool,
+}
+
+/// Translates the operators of a function to the code of the
+/// interpreter, one at a time.
+pub(crate) struct FunctionTranslator<'a> {
+    module: &'a ModuleInfo,
+    translation: &'a ModuleTranslationState,
+    num_locals: u32,
+    canonicalize_nans: bool,
+    instrs: Vec<Instr>,
+    controls: Vec<Control>,
+    /// The height of the stack of operands, above the locals.
+    height: u32,
+    /// The number of blocks nested in the unreachable code being skipped.
+    unreachable_depth: u32,
+}
+
+impl<'a> FunctionTranslator<'a> {
+    /// Creates a translator of the function `index` of `module`, with
+    /// `num_locals` locals besides its parameters, canonicalizing the
+    /// NaNs the float operators return if `canonicalize_nans` is set.
+    pub fn new(
+        module: &'a ModuleInfo,
+        translation: &'a ModuleTranslationState,
+        index: FunctionIndex,
+        num_locals: u32,
+        canonicalize_nans: bool,
+    ) -> Self {
+        let num_results = module.signatures[module.functions[index]].results().len() as u32;
+        Self {
+            module,
+            translation,
+            num_locals,
+            canonicalize_nans,
+            instrs: vec![],
+            controls: vec![Control {
+                kind: ControlKind::Block,
+                height: 0,
+                num_params: 0,
+                num_results,
+                start: 0,
+                fixups: vec![],
+                else_fixup: None,
+                unreachable: false,
+            }],
+            height: 0,
+            unreachable_depth: 0,
+        }
+    }
+
+    /// Whether the end of the function isn't reached yet.
+    pub fn has_control_frames(&self) -> bool {
+        !self.controls.is_empty()
+    }
+
+    /// Returns the code of the function, once all its operators are
+    /// translated.
+    pub fn finalize(self) -> FunctionCode {
+        FunctionCode {
+            num_locals: self.num_locals,
+            instrs: self.instrs,
+        }
+    }
+
+    /// Translates the next operator of the function.
+    pub fn feed_operator(&mut self, op: Operator) -> Result<(), CompileError> {
+        if self.controls.last().unwrap().unreachable {
+            match op {
+                Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
+                    self.unreachable_depth += 1;
+                    return Ok(());
+                }
+                Operator::End if self.unreachable_depth > 0 => {
+                    self.unreachable_depth -= 1;
+                    return Ok(());
+                }
+                Operator::Else | Operator::End if self.unreachable_depth == 0 => {}
+                _ => return Ok(()),
+            }
+        }
+
+        if let Some((instr, num_operands)) = numeric(&op) {
+            self.height -= num_operands - 1;
+            self.instrs.push(instr);
+            if self.canonicalize_nans {
+                if let Some(instr) = canonicalization(&op) {
+                    self.instrs.push(instr);
+                }
+            }
+            return Ok(());
+        }
+
+        match op {
+            Operator::Nop => {}
+            Operator::Unreachable => {
+                self.instrs.push(Instr::Unreachable);
+                self.set_unreachable();
+            }
+            Operator::Block { ty } => {
+                let (params, results) = self.translation.blocktype_params_results(ty)?;
+                let (num_params, num_results) = (params.len() as u32, results.len() as u32);
+                self.push_control(ControlKind::Block, num_params, num_results);
+            }
+            Operator::Loop { ty } => {
+                let (params, results) = self.translation.blocktype_params_results(ty)?;
+                let (num_params, num_results) = (params.len() as u32, results.len() as u32);
+                self.push_control(ControlKind::Loop, num_params, num_results);
+            }
+            Operator::If { ty } => {
+                let (params, results) = self.translation.blocktype_params_results(ty)?;
+                let (num_params, num_results) = (params.len() as u32, results.len() as u32);
+                self.height -= 1;
+                let else_fixup = self.instrs.len();
+                self.instrs.push(Instr::BrIfNot(0));
+                self.push_control(ControlKind::If, num_params, num_results);
+                self.controls.last_mut().unwrap().else_fixup = Some(else_fixup);
+            }
+            Operator::Else => {
+                let control = self.controls.last().unwrap();
+                if !control.unreachable {
+                    // The end of the `then` block jumps over the `else` one.
+                    let fixup = Fixup::Instr(self.instrs.len());
+                    self.controls.last_mut().unwrap().fixups.push(fixup);
+                    self.instrs.push(Instr::Br(Branch {
+                        target: 0,
+                        drop: 0,
+                        keep: 0,
+                    }));
+                }
+                let target = self.instrs.len() as u32;
+                let control = self.controls.last_mut().unwrap();
+                let else_fixup = control.else_fixup.take();
+                control.kind = ControlKind::Else;
+                control.unreachable = false;
+                self.height = control.height + control.num_params;
+                if let Some(else_fixup) = else_fixup {
+                    self.instrs[else_fixup] = Instr::BrIfNot(target);
+                }
+            }
+            Operator::End => {
+                let control = self.controls.pop().unwrap();
+                let target = self.instrs.len() as u32;
+                if let Some(else_fixup) = control.else_fixup {
+                    self.instrs[else_fixup] = Instr::BrIfNot(target);
+                }
+                for fixup in control.fixups {
+                    self.set_target(fixup, target);
+                }
+                self.height = control.height + control.num_results;
+                if self.controls.is_empty() {
+                    self.instrs.push(Instr::Return);
+                }
+            }
+            Operator::Br { relative_depth } => {
+                let instr = Instr::Br(self.branch(relative_depth, Fixup::Instr));
+                self.instrs.push(instr);
+                self.set_unreachable();
+            }
+            Operator::BrIf { relative_depth } => {
+                self.height -= 1;
+                let instr = Instr::BrIf(self.branch(relative_depth, Fixup::Instr));
+                self.instrs.push(instr);
+            }
+            Operator::BrTable { table } => {
+                self.height -= 1;
+                let mut depths = table
+                    .targets()
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(from_binaryreadererror_wasmerror)?;
+                depths.push(table.default());
+                let index = self.instrs.len();
+                let branches = depths
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, depth)| self.branch(depth, |_| Fixup::Table(index, i)))
+                    .collect();
+                self.instrs.push(Instr::BrTable(branches));
+                self.set_unreachable();
+            }
+            Operator::Return => {
+                self.instrs.push(Instr::Return);
+                self.set_unreachable();
+            }
+            Operator::Call { function_index } => {
+                let signature = self.module.functions[FunctionIndex::from_u32(function_index)];
+                self.call(signature, 0);
+                self.instrs.push(Instr::Call(function_index));
+            }
+            Operator::CallIndirect { index, table_index } => {
+                self.call(SignatureIndex::from_u32(index), 1);
+                self.instrs.push(Instr::CallIndirect {
+                    signature: index,
+                    table: table_index,
+                });
+            }
+            Operator::Drop => {
+                self.height -= 1;
+                self.instrs.push(Instr::Drop);
+            }
+            Operator::Select | Operator::TypedSelect { .. } => {
+                self.height -= 2;
+                self.instrs.push(Instr::Select);
+            }
+            Operator::LocalGet { local_index } => {
+                self.height += 1;
+                self.instrs.push(Instr::LocalGet(local_index));
+            }
+            Operator::LocalSet { local_index } => {
+                self.height -= 1;
+                self.instrs.push(Instr::LocalSet(local_index));
+            }
+            Operator::LocalTee { local_index } => {
+                self.instrs.push(Instr::LocalTee(local_index));
+            }
+            Operator::GlobalGet { global_index } => {
+                self.height += 1;
+                self.instrs.push(Instr::GlobalGet(global_index));
+            }
+            Operator::GlobalSet { global_index } => {
+                self.height -= 1;
+                self.instrs.push(Instr::GlobalSet(global_index));
+            }
+            Operator::I32Const { value } => self.push_const(value as u32 as u64),
+            Operator::I64Const { value } => self.push_const(value as u64),
+            Operator::F32Const { value } => self.push_const(value.bits() as u64),
+            Operator::F64Const { value } => self.push_const(value.bits()),
+            Operator::RefNull { .. } => self.push_const(0),
+            Operator::RefIsNull => self.instrs.push(Instr::RefIsNull),
+            Operator::RefFunc { function_index } => {
+                self.height += 1;
+                self.instrs.push(Instr::RefFunc(function_index));
+            }
+
+            Operator::I32Load { memarg } => self.load(Instr::I32Load, memarg)?,
+            Operator::I64Load { memarg } => self.load(Instr::I64Load, memarg)?,
+            Operator::F32Load { memarg } => self.load(Instr::F32Load, memarg)?,
+            Operator::F64Load { memarg } => self.load(Instr::F64Load, memarg)?,
+            Operator::I32Load8S { memarg } => self.load(Instr::I32Load8S, memarg)?,
+            Operator::I32Load8U { memarg } => self.load(Instr::I32Load8U, memarg)?,
+            Operator::I32Load16S { memarg } => self.load(Instr::I32Load16S, memarg)?,
+            Operator::I32Load16U { memarg } => self.load(Instr::I32Load16U, memarg)?,
+            Operator::I64Load8S { memarg } => self.load(Instr::I64Load8S, memarg)?,
+            Operator::I64Load8U { memarg } => self.load(Instr::I64Load8U, memarg)?,
+            Operator::I64Load16S { memarg } => self.load(Instr::I64Load16S, memarg)?,
+            Operator::I64Load16U { memarg } => self.load(Instr::I64Load16U, memarg)?,
+            Operator::I64Load32S { memarg } => self.load(Instr::I64Load32S, memarg)?,
+            Operator::I64Load32U { memarg } => self.load(Instr::I64Load32U, memarg)?,
+            Operator::I32Store { memarg } => self.store(Instr::I32Store, memarg)?,
+            Operator::I64Store { memarg } => self.store(Instr::I64Store, memarg)?,
+            Operator::F32Store { memarg } => self.store(Instr::F32Store, memarg)?,
+            Operator::F64Store { memarg } => self.store(Instr::F64Store, memarg)?,
+            Operator::I32Store8 { memarg } => self.store(Instr::I32Store8, memarg)?,
+            Operator::I32Store16 { memarg } => self.store(Instr::I32Store16, memarg)?,
+            Operator::I64Store8 { memarg } => self.store(Instr::I64Store8, memarg)?,
+            Operator::I64Store16 { memarg } => self.store(Instr::I64Store16, memarg)?,
+            Operator::I64Store32 { memarg } => self.store(Instr::I64Store32, memarg)?,
+            Operator::MemorySize { mem, .. } => {
+                check_memory(mem)?;
+                self.height += 1;
+                self.instrs.push(Instr::MemorySize);
+            }
+            Operator::MemoryGrow { mem, .. } => {
+                check_memory(mem)?;
+                self.instrs.push(Instr::MemoryGrow);
+            }
+            Operator::MemoryInit { segment, mem } => {
+                check_memory(mem)?;
+                self.height -= 3;
+                self.instrs.push(Instr::MemoryInit(segment));
+            }
+            Operator::DataDrop { segment } => self.instrs.push(Instr::DataDrop(segment)),
+            Operator::MemoryCopy { src, dst } => {
+                check_memory(src)?;
+                check_memory(dst)?;
+                self.height -= 3;
+                self.instrs.push(Instr::MemoryCopy);
+            }
+            Operator::MemoryFill { mem } => {
+                check_memory(mem)?;
+                self.height -= 3;
+                self.instrs.push(Instr::MemoryFill);
+            }
+
+            Operator::TableGet { table } => self.instrs.push(Instr::TableGet(table)),
+            Operator::TableSet { table } => {
+                self.height -= 2;
+                self.instrs.push(Instr::TableSet(table));
+            }
+            Operator::TableSize { table } => {
+                self.height += 1;
+                self.instrs.push(Instr::TableSize(table));
+            }
+            Operator::TableGrow { table } => {
+                self.height -= 1;
+                self.instrs.push(Instr::TableGrow(table));
+            }
+            Operator::TableFill { table } => {
+                self.height -= 3;
+                self.instrs.push(Instr::TableFill(table));
+            }
+            Operator::TableCopy {
+                dst_table,
+                src_table,
+            } => {
+                self.height -= 3;
+                self.instrs.push(Instr::TableCopy {
+                    dst: dst_table,
+                    src: src_table,
+                });
+            }
+            Operator::TableInit { segment, table } => {
+                self.height -= 3;
+                self.instrs.push(Instr::TableInit { segment, table });
+            }
+            Operator::ElemDrop { segment } => self.instrs.push(Instr::ElemDrop(segment)),
+
+            // The values keep their bits on the stack.
+            Operator::I32ReinterpretF32
+            | Operator::F32ReinterpretI32
+            | Operator::I64ReinterpretF64
+            | Operator::F64ReinterpretI64 => {}
+
+            op => {
+                return Err(CompileError::UnsupportedFeature(format!(
+                    "the {:?} operator with the interpreter",
+                    op
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn push_control(&mut self, kind: ControlKind, num_params: u32, num_results: u32) {
+        self.controls.push(Control {
+            kind,
+            height: self.height - num_params,
+            num_params,
+            num_results,
+            start: self.instrs.len() as u32,
+            fixups: vec![],
+            else_fixup: None,
+            unreachable: false,
+        });
+    }
+
+    fn set_unreachable(&mut self) {
+        self.controls.last_mut().unwrap().unreachable = true;
+    }
+
+    /// Returns the branch to the block at `relative_depth`, registering
+    /// the fixup returned by `fixup` for the index of the next
+    /// instruction if it branches to the end of the block.
+    fn branch(&mut self, relative_depth: u32, fixup: impl FnOnce(usize) -> Fixup) -> Branch {
+        let index = self.controls.len() - 1 - relative_depth as usize;
+        let next = self.instrs.len();
+        let control = &mut self.controls[index];
+        let (target, keep) = if control.kind == ControlKind::Loop {
+            (control.start, control.num_params)
+        } else {
+            control.fixups.push(fixup(next));
+            (0, control.num_results)
+        };
+        Branch {
+            target,
+            drop: self.height - keep - control.height,
+            keep,
+        }
+    }
+
+    fn set_target(&mut self, fixup: Fixup, target: u32) {
+        match fixup {
+            Fixup::Instr(index) => match &mut self.instrs[index] {
+                Instr::Br(branch) | Instr::BrIf(branch) => branch.target = target,
+                instr => unreachable!("{:?} isn't a branch", instr),
+            },
+            Fixup::Table(index, i) => match &mut self.instrs[index] {
+                Instr::BrTable(branches) => branches[i].target = target,
+                instr => unreachable!("{:?} isn't a branch table", instr),
+            },
+        }
+    }
+
+    fn call(&mut self, signature: SignatureIndex, num_operands: u32) {
+        let signature = &self.module.signatures[signature];
+        self.height -= signature.params().len() as u32 + num_operands;
+        self.height += signature.results().len() as u32;
+    }
+
+    fn push_const(&mut self, value: u64) {
+        self.height += 1;
+        self.instrs.push(Instr::Const(value));
+    }
+
+    fn load(
+        &mut self,
+        instr: fn(u32) -> Instr,
+        memarg: MemoryImmediate,
+    ) -> Result<(), CompileError> {
+        self.instrs.push(instr(offset(memarg)?));
+        Ok(())
+    }
+
+    fn store(
+        &mut self,
+        instr: fn(u32) -> Instr,
+        memarg: MemoryImmediate,
+    ) -> Result<(), CompileError> {
+        self.height -= 2;
+        self.instrs.push(instr(offset(memarg)?));
+        Ok(())
+    }
+}
+
+/// Checks that the memory `index` is the only one the interpreter
+/// supports.
+fn check_memory(index: u32) -> Result<(), CompileError> {
+    if index != 0 {
+        return Err(CompileError::UnsupportedFeature(
+            "multiple memories with the interpreter".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the offset of the access to the memory of `memarg`.
+fn offset(memarg: MemoryImmediate) -> Result<u32, CompileError> {
+    check_memory(memarg.memory)?;
+    u32::try_from(memarg.offset).map_err(|_| {
+        CompileError::UnsupportedFeature("64-bit memories with the interpreter".to_string())
+    })
+}
+
+macro_rules! numeric {
+    ($op:expr, unary: [$($unary:ident),* $(,)?], binary: [$($binary:ident),* $(,)?]) => {
+        match $op {
+            $(Operator::$unary => Some((Instr::$unary, 1)),)*
+            $(Operator::$binary => Some((Instr::$binary, 2)),)*
+            _ => None,
+        }
+    };
+}
+
+/// Returns the instruction of a numeric operator, with the number of
+/// its operands, replaced by its result.
+fn numeric(op: &Operator) -> Option<(Instr, u32)> {
+    numeric!(op,
+        unary: [
+            I32Eqz, I64Eqz, I32Clz, I32Ctz, I32Popcnt, I64Clz, I64Ctz, I64Popcnt,
+            F32Abs, F32Neg, F32Ceil, F32Floor, F32Trunc, F32Nearest, F32Sqrt,
+            F64Abs, F64Neg, F64Ceil, F64Floor, F64Trunc, F64Nearest, F64Sqrt,
+            I32WrapI64, I32TruncF32S, I32TruncF32U, I32TruncF64S, I32TruncF64U,
+            I64ExtendI32S, I64ExtendI32U, I64TruncF32S, I64TruncF32U, I64TruncF64S,
+            I64TruncF64U, F32ConvertI32S, F32ConvertI32U, F32ConvertI64S,
+            F32ConvertI64U, F32DemoteF64, F64ConvertI32S, F64ConvertI32U,
+            F64ConvertI64S, F64ConvertI64U, F64PromoteF32, I32Extend8S,
+            I32Extend16S, I64Extend8S, I64Extend16S, I64Extend32S, I32TruncSatF32S,
+            I32TruncSatF32U, I32TruncSatF64S, I32TruncSatF64U, I64TruncSatF32S,
+            I64TruncSatF32U, I64TruncSatF64S, I64TruncSatF64U,
+        ],
+        binary: [
+            I32Eq, I32Ne, I32LtS, I32LtU, I32GtS, I32GtU, I32LeS, I32LeU, I32GeS,
+            I32GeU, I64Eq, I64Ne, I64LtS, I64LtU, I64GtS, I64GtU, I64LeS, I64LeU,
+            I64GeS, I64GeU, F32Eq, F32Ne, F32Lt, F32Gt, F32Le, F32Ge, F64Eq, F64Ne,
+            F64Lt, F64Gt, F64Le, F64Ge, I32Add, I32Sub, I32Mul, I32DivS, I32DivU,
+            I32RemS, I32RemU, I32And, I32Or, I32Xor, I32Shl, I32ShrS, I32ShrU,
+            I32Rotl, I32Rotr, I64Add, I64Sub, I64Mul, I64DivS, I64DivU, I64RemS,
+            I64RemU, I64And, I64Or, I64Xor, I64Shl, I64ShrS, I64ShrU, I64Rotl,
+            I64Rotr, F32Add, F32Sub, F32Mul, F32Div, F32Min, F32Max, F32Copysign,
+            F64Add, F64Sub, F64Mul, F64Div, F64Min, F64Max, F64Copysign,
+        ]
+    )
+}
+
+/// Returns the instruction canonicalizing the NaN a float operator can
+/// return, if it's not one of its operands unchanged.
+fn canonicalization(op: &Operator) -> Option<Instr> {
+    match op {
+        Operator::F32Ceil
+        | Operator::F32Floor
+        | Operator::F32Trunc
+        | Operator::F32Nearest
+        | Operator::F32Sqrt
+        | Operator::F32Add
+        | Operator::F32Sub
+        | Operator::F32Mul
+        | Operator::F32Div
+        | Operator::F32Min
+        | Operator::F32Max
+        | Operator::F32DemoteF64 => Some(Instr::F32CanonicalizeNan),
+        Operator::F64Ceil
+        | Operator::F64Floor
+        | Operator::F64Trunc
+        | Operator::F64Nearest
+        | Operator::F64Sqrt
+        | Operator::F64Add
+        | Operator::F64Sub
+        | Operator::F64Mul
+        | Operator::F64Div
+        | Operator::F64Min
+        | Operator::F64Max
+        | Operator::F64PromoteF32 => Some(Instr::F64CanonicalizeNan),
+        _ => None,
+    }
+}
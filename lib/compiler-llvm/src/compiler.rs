@@ -199,6 +199,10 @@ impl Compiler for LLVMCompiler {
         "llvm"
     }
 
+    fn deterministic_id(&self) -> String {
+        format!("llvm-{}", self.config.deterministic_id())
+    }
+
     /// Get the middlewares for this compiler
     fn get_middlewares(&self) -> &[Arc<dyn ModuleMiddleware>] {
         &self.config.middlewares
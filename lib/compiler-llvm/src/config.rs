@@ -62,6 +62,14 @@ impl LLVM {
         }
     }
 
+    /// A string identifying the settings that affect the generated code.
+    pub(crate) fn deterministic_id(&self) -> String {
+        format!(
+            "{:?}-nan{}-verifier{}-pic{}",
+            self.opt_level, self.enable_nan_canonicalization, self.enable_verifier, self.is_pic,
+        )
+    }
+
     /// The optimization levels when optimizing the IR.
     pub fn opt_level(&mut self, opt_level: LLVMOptLevel) -> &mut Self {
         self.opt_level = opt_level;
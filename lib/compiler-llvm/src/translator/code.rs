@@ -142,6 +142,7 @@ impl FuncTranslator {
                 .middlewares
                 .generate_function_middleware_chain(*local_func_index),
         );
+        reader.set_num_params(wasm_fn_type.params().len() as u32);
 
         let mut params = vec![];
         let first_param =
@@ -55,6 +55,10 @@ impl Compiler for SinglepassCompiler {
         "singlepass"
     }
 
+    fn deterministic_id(&self) -> String {
+        format!("singlepass-nan{}", self.config.enable_nan_canonicalization)
+    }
+
     /// Get the middlewares for this compiler
     fn get_middlewares(&self) -> &[Arc<dyn ModuleMiddleware>] {
         &self.config.middlewares
@@ -146,6 +150,11 @@ impl Compiler for SinglepassCompiler {
                 let mut reader =
                     MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
                 reader.set_middleware_chain(middleware_chain);
+                reader.set_num_params(
+                    module.signatures[module.functions[module.func_index(i)]]
+                        .params()
+                        .len() as u32,
+                );
 
                 // This local list excludes arguments.
                 let mut locals = vec![];
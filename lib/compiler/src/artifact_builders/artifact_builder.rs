@@ -6,11 +6,23 @@ use super::trampoline::{libcall_trampoline_len, make_libcall_trampolines};
 use crate::ArtifactCreate;
 use crate::EngineInner;
 use crate::Features;
+#[cfg(feature = "compiler")]
+use crate::{FunctionBodyData, ModuleTranslationState};
 use crate::{ModuleEnvironment, ModuleMiddlewareChain};
 use enumset::EnumSet;
-use wasmer_types::entity::PrimaryMap;
+#[cfg(not(target_arch = "wasm32"))]
+use memmap2::Mmap;
+#[cfg(not(target_arch = "wasm32"))]
+use once_cell::sync::OnceCell;
+#[cfg(not(target_arch = "wasm32"))]
+use std::ops::Range;
 #[cfg(feature = "compiler")]
+use std::sync::Arc;
+use wasmer_types::entity::PrimaryMap;
+#[cfg(any(feature = "compiler", not(target_arch = "wasm32")))]
 use wasmer_types::CompileModuleInfo;
+#[cfg(not(target_arch = "wasm32"))]
+use wasmer_types::{ArchivedSerializableCompilation, ArchivedSerializableModule, DeserializeError};
 use wasmer_types::{
     CompileError, CpuFeature, CustomSection, Dwarf, FunctionIndex, LocalFunctionIndex, MemoryIndex,
     MemoryStyle, ModuleInfo, OwnedDataInitializer, Relocation, SectionIndex, SignatureIndex,
@@ -24,6 +36,37 @@ use wasmer_types::{MetadataHeader, SerializeError};
 /// A compiled wasm module, ready to be instantiated.
 pub struct ArtifactBuild {
     serializable: SerializableModule,
+    /// The bodies of the functions, if they're compiled on their first
+    /// call.
+    #[cfg(feature = "compiler")]
+    lazy_function_bodies: Option<Arc<LazyFunctionBodies>>,
+}
+
+/// The bodies of the functions of a module compiled lazily, see
+/// [`Compiler::compiles_lazily`](crate::Compiler::compiles_lazily),
+/// kept to compile them on their first call.
+#[cfg(feature = "compiler")]
+pub struct LazyFunctionBodies {
+    module_translation_state: ModuleTranslationState,
+    /// The bytecode of each function with its offset in the module.
+    bodies: PrimaryMap<LocalFunctionIndex, (Box<[u8]>, usize)>,
+}
+
+#[cfg(feature = "compiler")]
+impl LazyFunctionBodies {
+    /// The translation state of the module.
+    pub fn module_translation_state(&self) -> &ModuleTranslationState {
+        &self.module_translation_state
+    }
+
+    /// The body of the function `index`, to compile it.
+    pub fn function_body_input(&self, index: LocalFunctionIndex) -> FunctionBodyData<'_> {
+        let (data, module_offset) = &self.bodies[index];
+        FunctionBodyData {
+            data,
+            module_offset: *module_offset,
+        }
+    }
 }
 
 impl ArtifactBuild {
@@ -45,10 +88,27 @@ impl ArtifactBuild {
         table_styles: PrimaryMap<TableIndex, TableStyle>,
     ) -> Result<Self, CompileError> {
         let environ = ModuleEnvironment::new();
-        let features = inner_engine.features().clone();
-
         let translation = environ.translate(data).map_err(CompileError::Wasm)?;
+        Self::from_translation(
+            inner_engine,
+            translation,
+            target,
+            memory_styles,
+            table_styles,
+        )
+    }
 
+    /// Compile a translated module into a `ArtifactBuild`, which may then
+    /// be instantiated.
+    #[cfg(feature = "compiler")]
+    pub fn from_translation(
+        inner_engine: &mut EngineInner,
+        translation: ModuleEnvironment<'_>,
+        target: &Target,
+        memory_styles: PrimaryMap<MemoryIndex, MemoryStyle>,
+        mut table_styles: PrimaryMap<TableIndex, TableStyle>,
+    ) -> Result<Self, CompileError> {
+        let features = inner_engine.features().clone();
         let compiler = inner_engine.compiler()?;
 
         // We try to apply the middleware first
@@ -56,6 +116,12 @@ impl ArtifactBuild {
         let middlewares = compiler.get_middlewares();
         middlewares.apply_on_module_info(&mut module);
 
+        // The styles are computed before the middlewares run, which may
+        // add tables.
+        while table_styles.len() < module.tables.len() {
+            table_styles.push(TableStyle::CallerChecksSignature);
+        }
+
         let compile_info = CompileModuleInfo {
             module,
             features,
@@ -63,16 +129,37 @@ impl ArtifactBuild {
             table_styles,
         };
 
+        // The functions are compiled on their first call from a copy of
+        // their bodies, as the module isn't kept.
+        let lazy_bodies = if compiler.compiles_lazily() {
+            Some(
+                translation
+                    .function_body_inputs
+                    .values()
+                    .map(|input| (Box::from(input.data), input.module_offset))
+                    .collect::<PrimaryMap<LocalFunctionIndex, _>>(),
+            )
+        } else {
+            None
+        };
+
         // Compile the Module
         let compilation = compiler.compile_module(
             target,
             &compile_info,
-            // SAFETY: Calling `unwrap` is correct since
-            // `environ.translate()` above will write some data into
+            // SAFETY: Calling `unwrap` is correct since the translation
+            // of the module writes some data into
             // `module_translation_state`.
             translation.module_translation_state.as_ref().unwrap(),
             translation.function_body_inputs,
         )?;
+        let module_translation_state = translation.module_translation_state;
+        let lazy_function_bodies = lazy_bodies.map(|bodies| {
+            Arc::new(LazyFunctionBodies {
+                module_translation_state: module_translation_state.unwrap(),
+                bodies,
+            })
+        });
 
         let data_initializers = translation
             .data_initializers
@@ -101,6 +188,10 @@ impl ArtifactBuild {
         let libcall_trampolines = custom_sections.push(libcall_trampolines_section);
         let libcall_trampoline_len = libcall_trampoline_len(target) as u32;
         let cpu_features = compiler.get_cpu_features_used(target.cpu_features());
+        #[cfg(not(target_arch = "wasm32"))]
+        let interpreted = compiler.interpreter().is_some();
+        #[cfg(target_arch = "wasm32")]
+        let interpreted = false;
 
         let serializable_compilation = SerializableCompilation {
             function_bodies,
@@ -119,8 +210,13 @@ impl ArtifactBuild {
             compile_info,
             data_initializers,
             cpu_features: cpu_features.as_u64(),
+            compiler: compiler.name().to_string(),
+            interpreted,
         };
-        Ok(Self { serializable })
+        Ok(Self {
+            serializable,
+            lazy_function_bodies,
+        })
     }
 
     /// Compile a data buffer into a `ArtifactBuild`, which may then be instantiated.
@@ -140,7 +236,24 @@ impl ArtifactBuild {
 
     /// Create a new ArtifactBuild from a SerializableModule
     pub fn from_serializable(serializable: SerializableModule) -> Self {
-        Self { serializable }
+        Self {
+            serializable,
+            #[cfg(feature = "compiler")]
+            lazy_function_bodies: None,
+        }
+    }
+
+    /// Get the bodies of the functions, if they're compiled on their
+    /// first call.
+    #[cfg(feature = "compiler")]
+    pub fn get_lazy_function_bodies(&self) -> Option<&Arc<LazyFunctionBodies>> {
+        self.lazy_function_bodies.as_ref()
+    }
+
+    /// Get the information the module was compiled with.
+    #[cfg(feature = "compiler")]
+    pub fn get_compile_info_ref(&self) -> &CompileModuleInfo {
+        &self.serializable.compile_info
     }
 
     /// Get Functions Bodies ref
@@ -192,6 +305,12 @@ impl ArtifactBuild {
     pub fn get_frame_info_ref(&self) -> &PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo> {
         &self.serializable.compilation.function_frame_info
     }
+
+    /// Whether the function bodies are the code of an interpreter,
+    /// see [`Compiler::interpreter`](crate::Compiler::interpreter).
+    pub fn is_interpreted(&self) -> bool {
+        self.serializable.interpreted()
+    }
 }
 
 impl ArtifactCreate for ArtifactBuild {
@@ -207,6 +326,10 @@ impl ArtifactCreate for ArtifactBuild {
         EnumSet::from_u64(self.serializable.cpu_features)
     }
 
+    fn compiler(&self) -> &str {
+        self.serializable.compiler()
+    }
+
     fn data_initializers(&self) -> &[OwnedDataInitializer] {
         &self.serializable.data_initializers
     }
@@ -220,13 +343,153 @@ impl ArtifactCreate for ArtifactBuild {
     }
 
     fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        // The functions are only compile stubs, which can't compile them
+        // once deserialized.
+        #[cfg(feature = "compiler")]
+        if self.lazy_function_bodies.is_some() {
+            return Err(SerializeError::Generic(
+                "the modules whose functions are compiled lazily can't be serialized".to_string(),
+            ));
+        }
         let serialized_data = self.serializable.serialize()?;
         assert!(std::mem::align_of::<SerializableModule>() <= MetadataHeader::ALIGN);
 
         let mut metadata_binary = vec![];
         metadata_binary.extend(Self::MAGIC_HEADER);
-        metadata_binary.extend(MetadataHeader::new(serialized_data.len()).into_bytes());
+        metadata_binary.extend(MetadataHeader::new(&serialized_data).into_bytes());
         metadata_binary.extend(serialized_data);
         Ok(metadata_binary)
     }
 }
+
+/// A compiled wasm module read in place from a mapped serialized
+/// `ArtifactBuild`, instead of being deserialized to the heap.
+///
+/// Only the metadata is read in place: the parts of it which aren't
+/// needed to instantiate the module (the data initializers, the frame
+/// info and the relocations) are only deserialized when they are used.
+/// The code of the functions and the custom sections is still copied
+/// from the mapped file to the executable code memory of the artifact,
+/// as it has to be relocated, so it isn't shared between processes.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct ArtifactBuildFromArchive {
+    mmap: Mmap,
+    /// The range of the archived `SerializableModule` in `mmap`.
+    metadata: Range<usize>,
+    compile_info: CompileModuleInfo,
+    data_initializers: OnceCell<Box<[OwnedDataInitializer]>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ArtifactBuildFromArchive {
+    /// Reads a serialized `ArtifactBuild` in place from `mmap`.
+    ///
+    /// With `checked`, the metadata checksum and the structure of the
+    /// serialized data are validated, like with `SerializableModule::deserialize`.
+    ///
+    /// # Safety
+    /// Without `checked`, the file must have been produced by `serialize`
+    /// with this version of Wasmer and must not be corrupted, otherwise the
+    /// behavior is undefined.
+    pub unsafe fn from_mmap(mmap: Mmap, checked: bool) -> Result<Self, DeserializeError> {
+        if !ArtifactBuild::is_deserializable(&mmap) {
+            return Err(DeserializeError::Incompatible(
+                "The provided bytes are not wasmer-universal".to_string(),
+            ));
+        }
+        let bytes = &mmap[ArtifactBuild::MAGIC_HEADER.len()..];
+        let metadata_len = if checked {
+            let metadata_slice = MetadataHeader::parse_checked(bytes)?;
+            SerializableModule::archive(metadata_slice)?;
+            metadata_slice.len()
+        } else {
+            let metadata_len = MetadataHeader::parse(bytes)?;
+            if bytes.len() < MetadataHeader::LEN + metadata_len {
+                return Err(DeserializeError::InvalidByteLength {
+                    expected: MetadataHeader::LEN + metadata_len,
+                    got: bytes.len(),
+                });
+            }
+            metadata_len
+        };
+        let start = ArtifactBuild::MAGIC_HEADER.len() + MetadataHeader::LEN;
+        let metadata = start..start + metadata_len;
+
+        let compile_info =
+            SerializableModule::archive_unchecked(&mmap[metadata.clone()])?.compile_info()?;
+        Ok(Self {
+            mmap,
+            metadata,
+            compile_info,
+            data_initializers: OnceCell::new(),
+        })
+    }
+
+    fn archived(&self) -> &ArchivedSerializableModule {
+        // SAFETY: the metadata was checked, if asked to, when the
+        // file was loaded.
+        unsafe { SerializableModule::archive_unchecked(&self.mmap[self.metadata.clone()]) }
+            .expect("the metadata was already read")
+    }
+
+    /// Get the archived compilation of the module
+    pub fn get_compilation(&self) -> &ArchivedSerializableCompilation {
+        &self.archived().compilation
+    }
+
+    /// Deserialize the frame info of the functions
+    pub fn get_frame_info(
+        &self,
+    ) -> Result<PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>, DeserializeError> {
+        self.get_compilation().function_frame_info()
+    }
+
+    /// Whether the function bodies are the code of an interpreter
+    pub fn is_interpreted(&self) -> bool {
+        self.archived().interpreted()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ArtifactCreate for ArtifactBuildFromArchive {
+    fn create_module_info(&self) -> ModuleInfo {
+        self.compile_info.module.clone()
+    }
+
+    fn features(&self) -> &Features {
+        &self.compile_info.features
+    }
+
+    fn cpu_features(&self) -> EnumSet<CpuFeature> {
+        self.archived().cpu_features()
+    }
+
+    fn compiler(&self) -> &str {
+        self.archived().compiler()
+    }
+
+    fn data_initializers(&self) -> &[OwnedDataInitializer] {
+        self.data_initializers.get_or_init(|| {
+            self.archived()
+                .data_initializers()
+                .expect("the data initializers of the module are corrupted")
+        })
+    }
+
+    fn memory_styles(&self) -> &PrimaryMap<MemoryIndex, MemoryStyle> {
+        &self.compile_info.memory_styles
+    }
+
+    fn table_styles(&self) -> &PrimaryMap<TableIndex, TableStyle> {
+        &self.compile_info.table_styles
+    }
+
+    fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        let metadata = &self.mmap[self.metadata.clone()];
+        let mut metadata_binary = vec![];
+        metadata_binary.extend(ArtifactBuild::MAGIC_HEADER);
+        metadata_binary.extend(MetadataHeader::new(metadata).into_bytes());
+        metadata_binary.extend(metadata);
+        Ok(metadata_binary)
+    }
+}
@@ -4,4 +4,8 @@ mod artifact_builder;
 mod trampoline;
 
 pub use self::artifact_builder::ArtifactBuild;
+#[cfg(feature = "compiler")]
+pub use self::artifact_builder::LazyFunctionBodies;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::artifact_builder::ArtifactBuildFromArchive;
 pub use self::trampoline::*;
@@ -1,9 +1,11 @@
 //! This module mainly outputs the `Compiler` trait that custom
 //! compilers will need to implement.
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::lib::std::any::Any;
 use crate::lib::std::boxed::Box;
 use crate::lib::std::sync::Arc;
-use crate::translator::ModuleMiddleware;
+use crate::translator::{wasm_features, ModuleMiddleware};
 use crate::FunctionBodyData;
 use crate::ModuleTranslationState;
 use enumset::EnumSet;
@@ -13,8 +15,12 @@ use wasmer_types::compilation::symbols::SymbolRegistry;
 use wasmer_types::compilation::target::Target;
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::error::CompileError;
+#[cfg(not(target_arch = "wasm32"))]
+use wasmer_types::ModuleInfo;
 use wasmer_types::{CpuFeature, Features, LocalFunctionIndex};
-use wasmparser::{Validator, WasmFeatures};
+#[cfg(not(target_arch = "wasm32"))]
+use wasmer_vm::{FunctionBodyPtr, VMTrampoline};
+use wasmparser::Validator;
 
 /// The compiler configuration options.
 pub trait CompilerConfig {
@@ -84,6 +90,15 @@ pub trait Compiler: Send {
     /// Note that this is an API breaking change since 3.0
     fn name(&self) -> &str;
 
+    /// Returns a string identifying this compiler and the settings that
+    /// affect the code it generates.
+    ///
+    /// Two compilers with the same identifier generate compatible code, so
+    /// it can be used to key caches of compiled artifacts.
+    fn deterministic_id(&self) -> String {
+        self.name().to_string()
+    }
+
     /// Validates a module.
     ///
     /// It returns the a succesful Result in case is valid, `CompileError` in case is not.
@@ -93,25 +108,7 @@ pub trait Compiler: Send {
         data: &'data [u8],
     ) -> Result<(), CompileError> {
         let mut validator = Validator::new();
-        let wasm_features = WasmFeatures {
-            bulk_memory: features.bulk_memory,
-            threads: features.threads,
-            reference_types: features.reference_types,
-            multi_value: features.multi_value,
-            simd: features.simd,
-            tail_call: features.tail_call,
-            module_linking: features.module_linking,
-            multi_memory: features.multi_memory,
-            memory64: features.memory64,
-            exceptions: features.exceptions,
-            deterministic_only: false,
-            extended_const: features.extended_const,
-            relaxed_simd: features.relaxed_simd,
-            mutable_global: true,
-            saturating_float_to_int: true,
-            sign_extension: true,
-        };
-        validator.wasm_features(wasm_features);
+        validator.wasm_features(wasm_features(features));
         validator
             .validate_all(data)
             .map_err(|e| CompileError::Validate(format!("{}", e)))?;
@@ -130,6 +127,47 @@ pub trait Compiler: Send {
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
     ) -> Result<Compilation, CompileError>;
 
+    /// Whether the functions compiled by [`Compiler::compile_module`] are
+    /// compile stubs, calling the `lazy_compile` builtin, instead of the
+    /// functions themselves.
+    ///
+    /// The functions of such modules are compiled on their first call with
+    /// [`Compiler::compile_function`].
+    fn compiles_lazily(&self) -> bool {
+        false
+    }
+
+    /// Compiles the local function `index` of a module compiled lazily,
+    /// see [`Compiler::compiles_lazily`].
+    ///
+    /// The returned [`Compilation`] holds the function alone, at index 0,
+    /// and its custom sections, without trampolines. The relocations to
+    /// the function `index` refer to the function itself.
+    fn compile_function<'data, 'module>(
+        &self,
+        _target: &Target,
+        _module: &'module CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        _index: LocalFunctionIndex,
+        _function_body_input: &FunctionBodyData<'data>,
+    ) -> Result<Compilation, CompileError> {
+        Err(CompileError::UnsupportedFeature(format!(
+            "compiling the functions lazily with {}",
+            self.name()
+        )))
+    }
+
+    /// The interpreter running the functions compiled by
+    /// [`Compiler::compile_module`], if they're the code of an
+    /// interpreter instead of machine code.
+    ///
+    /// The modules compiled this way don't need executable memory, so
+    /// they run on the platforms forbidding to generate code at runtime.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn interpreter(&self) -> Option<&dyn FunctionInterpreter> {
+        None
+    }
+
     /// Compiles a module into a native object file.
     ///
     /// It returns the bytes as a `&[u8]` or a [`CompileError`].
@@ -155,3 +193,29 @@ pub trait Compiler: Send {
         *cpu_features
     }
 }
+
+/// Loads the functions of the modules compiled for an interpreter, see
+/// [`Compiler::interpreter`].
+#[cfg(not(target_arch = "wasm32"))]
+pub trait FunctionInterpreter {
+    /// Loads the function bodies of `module`, as compiled by
+    /// [`Compiler::compile_module`], to run them.
+    fn load(
+        &self,
+        module: &ModuleInfo,
+        function_bodies: &[&[u8]],
+    ) -> Result<InterpretedFunctions, CompileError>;
+}
+
+/// The functions of a module loaded by a [`FunctionInterpreter`].
+#[cfg(not(target_arch = "wasm32"))]
+pub struct InterpretedFunctions {
+    /// The pointer passed to the call trampoline to run each function.
+    pub function_bodies: PrimaryMap<LocalFunctionIndex, FunctionBodyPtr>,
+    /// The call trampoline running the functions, whatever their
+    /// signature.
+    pub call_trampoline: VMTrampoline,
+    /// The loaded code, kept as long as the engine since the functions
+    /// may outlive their module.
+    pub code: Box<dyn Any + Send + Sync>,
+}
@@ -3,8 +3,11 @@
 
 use crate::engine::link::link_module;
 use crate::ArtifactBuild;
+use crate::ArtifactBuildFromArchive;
 use crate::ArtifactCreate;
 use crate::Features;
+#[cfg(feature = "compiler")]
+use crate::LazyFunctions;
 use crate::ModuleEnvironment;
 use crate::{
     register_frame_info, resolve_imports, FunctionExtent, GlobalFrameInfoRegistration,
@@ -14,6 +17,7 @@ use crate::{
 use crate::{Compiler, FunctionBodyData, ModuleTranslationState};
 use crate::{Engine, EngineInner};
 use enumset::EnumSet;
+use memmap2::Mmap;
 #[cfg(any(feature = "static-artifact-create", feature = "static-artifact-load"))]
 use std::mem;
 use std::sync::Arc;
@@ -22,19 +26,23 @@ use std::sync::Mutex;
 use wasmer_object::{emit_compilation, emit_data, get_object_for_target, Object};
 #[cfg(any(feature = "static-artifact-create", feature = "static-artifact-load"))]
 use wasmer_types::compilation::symbols::ModuleMetadata;
-use wasmer_types::entity::{BoxedSlice, PrimaryMap};
+use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
 #[cfg(feature = "static-artifact-create")]
 use wasmer_types::CompileModuleInfo;
 use wasmer_types::MetadataHeader;
 #[cfg(feature = "static-artifact-load")]
 use wasmer_types::SerializableCompilation;
 use wasmer_types::{
-    CompileError, CpuFeature, DataInitializer, DeserializeError, FunctionIndex, LocalFunctionIndex,
-    MemoryIndex, ModuleInfo, OwnedDataInitializer, SignatureIndex, TableIndex, Target,
+    CompileError, CompiledFunctionFrameInfo, CpuFeature, CustomSectionLike, DataInitializer,
+    DeserializeError, Dwarf, FunctionBodyLike, FunctionIndex, LocalFunctionIndex, MemoryIndex,
+    ModuleInfo, OwnedDataInitializer, Relocation, Relocations, SectionIndex, SignatureIndex,
+    TableIndex, Target,
 };
 use wasmer_types::{SerializableModule, SerializeError};
 use wasmer_vm::{FunctionBodyPtr, MemoryStyle, TableStyle, VMSharedSignatureIndex, VMTrampoline};
-use wasmer_vm::{InstanceAllocator, StoreObjects, TrapHandlerFn, VMExtern, VMInstance};
+use wasmer_vm::{
+    InstanceAllocator, LazyFunctionCompiler, StoreObjects, TrapHandlerFn, VMExtern, VMInstance,
+};
 
 pub struct AllocatedArtifact {
     finished_functions: BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
@@ -44,11 +52,88 @@ pub struct AllocatedArtifact {
     /// Some(_) only if this is not a deserialized static artifact
     frame_info_registration: Option<Mutex<Option<GlobalFrameInfoRegistration>>>,
     finished_function_lengths: BoxedSlice<LocalFunctionIndex, usize>,
+    /// Some(_) only if the functions are compiled on their first call
+    #[cfg(feature = "compiler")]
+    lazy_functions: Option<Arc<LazyFunctions>>,
+}
+
+/// The compiled module an `Artifact` is created from.
+enum ArtifactBuildVariant {
+    Plain(ArtifactBuild),
+    Archived(ArtifactBuildFromArchive),
+}
+
+impl ArtifactBuildVariant {
+    /// The frame info of the functions, `None` if it can't be read.
+    fn get_frame_info(&self) -> Option<PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>> {
+        match self {
+            Self::Plain(artifact) => Some(artifact.get_frame_info_ref().clone()),
+            Self::Archived(artifact) => artifact.get_frame_info().ok(),
+        }
+    }
+}
+
+impl ArtifactCreate for ArtifactBuildVariant {
+    fn create_module_info(&self) -> ModuleInfo {
+        match self {
+            Self::Plain(artifact) => artifact.create_module_info(),
+            Self::Archived(artifact) => artifact.create_module_info(),
+        }
+    }
+
+    fn features(&self) -> &Features {
+        match self {
+            Self::Plain(artifact) => artifact.features(),
+            Self::Archived(artifact) => artifact.features(),
+        }
+    }
+
+    fn cpu_features(&self) -> EnumSet<CpuFeature> {
+        match self {
+            Self::Plain(artifact) => artifact.cpu_features(),
+            Self::Archived(artifact) => artifact.cpu_features(),
+        }
+    }
+
+    fn compiler(&self) -> &str {
+        match self {
+            Self::Plain(artifact) => artifact.compiler(),
+            Self::Archived(artifact) => artifact.compiler(),
+        }
+    }
+
+    fn data_initializers(&self) -> &[OwnedDataInitializer] {
+        match self {
+            Self::Plain(artifact) => artifact.data_initializers(),
+            Self::Archived(artifact) => artifact.data_initializers(),
+        }
+    }
+
+    fn memory_styles(&self) -> &PrimaryMap<MemoryIndex, MemoryStyle> {
+        match self {
+            Self::Plain(artifact) => artifact.memory_styles(),
+            Self::Archived(artifact) => artifact.memory_styles(),
+        }
+    }
+
+    fn table_styles(&self) -> &PrimaryMap<TableIndex, TableStyle> {
+        match self {
+            Self::Plain(artifact) => artifact.table_styles(),
+            Self::Archived(artifact) => artifact.table_styles(),
+        }
+    }
+
+    fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        match self {
+            Self::Plain(artifact) => artifact.serialize(),
+            Self::Archived(artifact) => artifact.serialize(),
+        }
+    }
 }
 
 /// A compiled wasm module, ready to be instantiated.
 pub struct Artifact {
-    artifact: ArtifactBuild,
+    artifact: ArtifactBuildVariant,
     // The artifact will only be allocated in memory in case we can execute it
     // (that means, if the target != host then this will be None).
     allocated: Option<AllocatedArtifact>,
@@ -61,11 +146,39 @@ impl Artifact {
         engine: &Engine,
         data: &[u8],
         tunables: &dyn Tunables,
+    ) -> Result<Self, CompileError> {
+        Self::compile(engine, data, tunables, false)
+    }
+
+    /// Validate and compile a data buffer into a `ArtifactBuild`, which may
+    /// then be instantiated.
+    ///
+    /// The module is validated while it's translated, instead of in a
+    /// separate pass over it beforehand.
+    #[cfg(feature = "compiler")]
+    pub fn new_validated(
+        engine: &Engine,
+        data: &[u8],
+        tunables: &dyn Tunables,
+    ) -> Result<Self, CompileError> {
+        Self::compile(engine, data, tunables, true)
+    }
+
+    #[cfg(feature = "compiler")]
+    fn compile(
+        engine: &Engine,
+        data: &[u8],
+        tunables: &dyn Tunables,
+        validate: bool,
     ) -> Result<Self, CompileError> {
         let mut inner_engine = engine.inner_mut();
         let environ = ModuleEnvironment::new();
-        let translation = environ.translate(data).map_err(CompileError::Wasm)?;
-        let module = translation.module;
+        let translation = if validate {
+            environ.translate_and_validate(data, inner_engine.features())?
+        } else {
+            environ.translate(data).map_err(CompileError::Wasm)?
+        };
+        let module = &translation.module;
         let memory_styles: PrimaryMap<MemoryIndex, MemoryStyle> = module
             .memories
             .values()
@@ -77,15 +190,30 @@ impl Artifact {
             .map(|table_type| tunables.table_style(table_type))
             .collect();
 
-        let artifact = ArtifactBuild::new(
+        let artifact = ArtifactBuild::from_translation(
             &mut inner_engine,
-            data,
+            translation,
             engine.target(),
             memory_styles,
             table_styles,
         )?;
-
-        Self::from_parts(&mut inner_engine, artifact, engine.target())
+        let lazy_function_bodies = artifact
+            .get_lazy_function_bodies()
+            .cloned()
+            .map(|bodies| (bodies, artifact.get_compile_info_ref().clone()));
+
+        let mut artifact = Self::from_parts(&mut inner_engine, artifact, engine.target())?;
+        if let (Some((bodies, compile_info)), Some(allocated)) =
+            (lazy_function_bodies, artifact.allocated.as_mut())
+        {
+            allocated.lazy_functions = Some(Arc::new(LazyFunctions::new(
+                engine.clone(),
+                compile_info,
+                bodies,
+                allocated.finished_functions.clone(),
+            )));
+        }
+        Ok(artifact)
     }
 
     /// This indicates if the Artifact is allocated and can be run by the current
@@ -105,10 +233,36 @@ impl Artifact {
 
     /// Deserialize a ArtifactBuild
     ///
+    /// The metadata checksum and the structure of the serialized data
+    /// are validated before the artifact is loaded.
+    ///
     /// # Safety
-    /// This function is unsafe because rkyv reads directly without validating
-    /// the data.
+    /// This function is unsafe because the artifact contains machine code
+    /// that is going to be executed, so it must come from a trusted source.
     pub unsafe fn deserialize(engine: &Engine, bytes: &[u8]) -> Result<Self, DeserializeError> {
+        Self::deserialize_impl(engine, bytes, true)
+    }
+
+    /// Deserialize a ArtifactBuild without validating it, skipping the
+    /// checks done by [`Artifact::deserialize`].
+    ///
+    /// # Safety
+    /// In addition to the requirements of [`Artifact::deserialize`], the
+    /// bytes must have been produced by `serialize` with this version of
+    /// Wasmer and must not be corrupted, otherwise the behavior is
+    /// undefined.
+    pub unsafe fn deserialize_unchecked(
+        engine: &Engine,
+        bytes: &[u8],
+    ) -> Result<Self, DeserializeError> {
+        Self::deserialize_impl(engine, bytes, false)
+    }
+
+    unsafe fn deserialize_impl(
+        engine: &Engine,
+        bytes: &[u8],
+        checked: bool,
+    ) -> Result<Self, DeserializeError> {
         if !ArtifactBuild::is_deserializable(bytes) {
             let static_artifact = Self::deserialize_object(engine, bytes);
             match static_artifact {
@@ -126,17 +280,90 @@ impl Artifact {
 
         let bytes = Self::get_byte_slice(bytes, ArtifactBuild::MAGIC_HEADER.len(), bytes.len())?;
 
-        let metadata_len = MetadataHeader::parse(bytes)?;
-        let metadata_slice = Self::get_byte_slice(bytes, MetadataHeader::LEN, bytes.len())?;
-        let metadata_slice = Self::get_byte_slice(metadata_slice, 0, metadata_len)?;
+        let serializable = if checked {
+            let metadata_slice = MetadataHeader::parse_checked(bytes)?;
+            SerializableModule::deserialize(metadata_slice)?
+        } else {
+            let metadata_len = MetadataHeader::parse(bytes)?;
+            let metadata_slice = Self::get_byte_slice(bytes, MetadataHeader::LEN, bytes.len())?;
+            let metadata_slice = Self::get_byte_slice(metadata_slice, 0, metadata_len)?;
+            SerializableModule::deserialize_unchecked(metadata_slice)?
+        };
+
+        Self::check_cpu_features(engine, serializable.cpu_features())?;
 
-        let serializable = SerializableModule::deserialize(metadata_slice)?;
         let artifact = ArtifactBuild::from_serializable(serializable);
         let mut inner_engine = engine.inner_mut();
         Self::from_parts(&mut inner_engine, artifact, engine.target())
             .map_err(DeserializeError::Compiler)
     }
 
+    /// Deserialize a ArtifactBuild in place from a mapped file
+    ///
+    /// The artifact keeps the file mapped and reads its metadata in
+    /// place, see [`ArtifactBuildFromArchive`]. The code is copied to
+    /// the code memory of the artifact, like with [`Artifact::deserialize`]. The metadata checksum
+    /// and the structure of the serialized data are validated before
+    /// the artifact is loaded.
+    ///
+    /// # Safety
+    /// See [`Artifact::deserialize`].
+    pub unsafe fn deserialize_from_mmap(
+        engine: &Engine,
+        mmap: Mmap,
+    ) -> Result<Self, DeserializeError> {
+        Self::deserialize_from_mmap_impl(engine, mmap, true)
+    }
+
+    /// Deserialize a ArtifactBuild in place from a mapped file, without
+    /// validating it.
+    ///
+    /// # Safety
+    /// See [`Artifact::deserialize_unchecked`].
+    pub unsafe fn deserialize_from_mmap_unchecked(
+        engine: &Engine,
+        mmap: Mmap,
+    ) -> Result<Self, DeserializeError> {
+        Self::deserialize_from_mmap_impl(engine, mmap, false)
+    }
+
+    unsafe fn deserialize_from_mmap_impl(
+        engine: &Engine,
+        mmap: Mmap,
+        checked: bool,
+    ) -> Result<Self, DeserializeError> {
+        if !ArtifactBuild::is_deserializable(&mmap) {
+            // Static objects link their functions in the executable,
+            // there is nothing to keep mapped.
+            return Self::deserialize_impl(engine, &mmap, checked);
+        }
+
+        let artifact = ArtifactBuildFromArchive::from_mmap(mmap, checked)?;
+        Self::check_cpu_features(engine, artifact.cpu_features())?;
+
+        let mut inner_engine = engine.inner_mut();
+        Self::from_archive(&mut inner_engine, artifact, engine.target())
+    }
+
+    /// Reject artifacts using CPU features the host doesn't support
+    /// before loading their code, so that callers can fall back to
+    /// compiling the module again.
+    fn check_cpu_features(
+        engine: &Engine,
+        cpu_features: EnumSet<CpuFeature>,
+    ) -> Result<(), DeserializeError> {
+        if engine.target().is_native() {
+            let host_cpu_features = CpuFeature::for_host();
+            if !host_cpu_features.is_superset(cpu_features) {
+                return Err(DeserializeError::Incompatible(format!(
+                    "the module was compiled for CPU features not supported by the host: {:?}",
+                    cpu_features.difference(host_cpu_features)
+                )));
+            }
+        }
+        Ok(())
+    }
+
     /// Construct a `ArtifactBuild` from component parts.
     pub fn from_parts(
         engine_inner: &mut EngineInner,
@@ -145,32 +372,230 @@ impl Artifact {
     ) -> Result<Self, CompileError> {
         if !target.is_native() {
             return Ok(Self {
-                artifact,
+                artifact: ArtifactBuildVariant::Plain(artifact),
+                allocated: None,
+            });
+        }
+        let module_info = artifact.create_module_info();
+        Self::check_interpreted(engine_inner, artifact.is_interpreted())?;
+        if artifact.is_interpreted() {
+            let allocated = Self::load_interpreted(
+                engine_inner,
+                &module_info,
+                &artifact
+                    .get_function_bodies_ref()
+                    .values()
+                    .collect::<Vec<_>>(),
+            )?;
+            return Ok(Self {
+                artifact: ArtifactBuildVariant::Plain(artifact),
+                allocated: Some(allocated),
+            });
+        }
+        let allocated = Self::allocate(
+            engine_inner,
+            &module_info,
+            &artifact
+                .get_function_bodies_ref()
+                .values()
+                .collect::<Vec<_>>(),
+            &artifact
+                .get_function_call_trampolines_ref()
+                .values()
+                .collect::<Vec<_>>(),
+            &artifact
+                .get_dynamic_function_trampolines_ref()
+                .values()
+                .collect::<Vec<_>>(),
+            &artifact
+                .get_custom_sections_ref()
+                .values()
+                .collect::<Vec<_>>(),
+            artifact.get_function_relocations(),
+            artifact.get_custom_section_relocations_ref(),
+            artifact.get_libcall_trampolines(),
+            artifact.get_libcall_trampoline_len(),
+            artifact.get_debug_ref().as_ref(),
+        )?;
+
+        Ok(Self {
+            artifact: ArtifactBuildVariant::Plain(artifact),
+            allocated: Some(allocated),
+        })
+    }
+
+    /// Construct a `ArtifactBuild` from an archived one, copying its code
+    /// from the archive to the code memory.
+    pub fn from_archive(
+        engine_inner: &mut EngineInner,
+        artifact: ArtifactBuildFromArchive,
+        target: &Target,
+    ) -> Result<Self, DeserializeError> {
+        if !target.is_native() {
+            return Ok(Self {
+                artifact: ArtifactBuildVariant::Archived(artifact),
                 allocated: None,
             });
         }
         let module_info = artifact.create_module_info();
+        let compilation = artifact.get_compilation();
+        Self::check_interpreted(engine_inner, artifact.is_interpreted())
+            .map_err(DeserializeError::Compiler)?;
+        if artifact.is_interpreted() {
+            let allocated = Self::load_interpreted(
+                engine_inner,
+                &module_info,
+                &compilation.function_bodies.values().collect::<Vec<_>>(),
+            )
+            .map_err(DeserializeError::Compiler)?;
+            return Ok(Self {
+                artifact: ArtifactBuildVariant::Archived(artifact),
+                allocated: Some(allocated),
+            });
+        }
+        // The relocations are only needed to link the code, they are
+        // dropped once it is.
+        let allocated = Self::allocate(
+            engine_inner,
+            &module_info,
+            &compilation.function_bodies.values().collect::<Vec<_>>(),
+            &compilation
+                .function_call_trampolines
+                .values()
+                .collect::<Vec<_>>(),
+            &compilation
+                .dynamic_function_trampolines
+                .values()
+                .collect::<Vec<_>>(),
+            &compilation.custom_sections.values().collect::<Vec<_>>(),
+            compilation.function_relocations()?,
+            &compilation.custom_section_relocations()?,
+            compilation.libcall_trampolines,
+            compilation.libcall_trampoline_len as usize,
+            compilation.debug()?.as_ref(),
+        )
+        .map_err(DeserializeError::Compiler)?;
+
+        Ok(Self {
+            artifact: ArtifactBuildVariant::Archived(artifact),
+            allocated: Some(allocated),
+        })
+    }
+
+    /// Reject the modules whose code doesn't match the one the engine
+    /// runs: the interpreted and the compiled functions can't call each
+    /// other directly.
+    fn check_interpreted(engine_inner: &EngineInner, interpreted: bool) -> Result<(), CompileError> {
+        match (interpreted, engine_inner.interprets()) {
+            (true, false) => Err(CompileError::Codegen(
+                "The module is interpreted, but the engine has no interpreter".to_string(),
+            )),
+            (false, true) => Err(CompileError::Codegen(
+                "The module is compiled to machine code, but the engine interprets the modules"
+                    .to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Load the functions of an interpreted module with the interpreter
+    /// of the engine. The function call trampoline of every signature
+    /// runs the interpreter, and there is neither code memory nor frame
+    /// info.
+    fn load_interpreted<F: FunctionBodyLike + ?Sized>(
+        engine_inner: &mut EngineInner,
+        module_info: &ModuleInfo,
+        function_bodies: &[&F],
+    ) -> Result<AllocatedArtifact, CompileError> {
+        #[cfg(feature = "compiler")]
+        {
+            let function_bodies = function_bodies
+                .iter()
+                .map(|body| body.body())
+                .collect::<Vec<_>>();
+            let (finished_functions, call_trampoline) =
+                engine_inner.load_interpreted(module_info, &function_bodies)?;
+            let signatures = {
+                let signature_registry = engine_inner.signatures();
+                module_info
+                    .signatures
+                    .values()
+                    .map(|sig| signature_registry.register(sig))
+                    .collect::<PrimaryMap<_, _>>()
+            };
+            let finished_function_call_trampolines = module_info
+                .signatures
+                .keys()
+                .map(|_| call_trampoline)
+                .collect::<PrimaryMap<SignatureIndex, _>>();
+            // The interpreter calls the imported functions through their
+            // call trampoline, the dynamic ones included.
+            let finished_dynamic_function_trampolines = (0..module_info.num_imported_functions)
+                .map(|_| FunctionBodyPtr(std::ptr::null()))
+                .collect::<PrimaryMap<FunctionIndex, _>>();
+            let finished_function_lengths = finished_functions
+                .values()
+                .map(|_| 0)
+                .collect::<PrimaryMap<LocalFunctionIndex, usize>>();
+
+            Ok(AllocatedArtifact {
+                finished_functions: finished_functions.into_boxed_slice(),
+                finished_function_call_trampolines: finished_function_call_trampolines
+                    .into_boxed_slice(),
+                finished_dynamic_function_trampolines: finished_dynamic_function_trampolines
+                    .into_boxed_slice(),
+                signatures: signatures.into_boxed_slice(),
+                frame_info_registration: None,
+                finished_function_lengths: finished_function_lengths.into_boxed_slice(),
+                lazy_functions: None,
+            })
+        }
+        #[cfg(not(feature = "compiler"))]
+        {
+            let _ = (engine_inner, module_info, function_bodies);
+            Err(CompileError::Codegen(
+                "The module is interpreted, but the engine has no interpreter".to_string(),
+            ))
+        }
+    }
+
+    /// Allocate the functions and custom sections of a module in the
+    /// code memory, link and publish them.
+    #[allow(clippy::too_many_arguments)]
+    fn allocate<F: FunctionBodyLike + ?Sized, S: CustomSectionLike + ?Sized>(
+        engine_inner: &mut EngineInner,
+        module_info: &ModuleInfo,
+        function_bodies: &[&F],
+        function_call_trampolines: &[&F],
+        dynamic_function_trampolines: &[&F],
+        custom_sections: &[&S],
+        function_relocations: Relocations,
+        custom_section_relocations: &PrimaryMap<SectionIndex, Vec<Relocation>>,
+        libcall_trampolines: SectionIndex,
+        libcall_trampoline_len: usize,
+        debug: Option<&Dwarf>,
+    ) -> Result<AllocatedArtifact, CompileError> {
         let (
             finished_functions,
             finished_function_call_trampolines,
             finished_dynamic_function_trampolines,
-            custom_sections,
+            allocated_custom_sections,
         ) = engine_inner.allocate(
-            &module_info,
-            artifact.get_function_bodies_ref(),
-            artifact.get_function_call_trampolines_ref(),
-            artifact.get_dynamic_function_trampolines_ref(),
-            artifact.get_custom_sections_ref(),
+            module_info,
+            function_bodies,
+            function_call_trampolines,
+            dynamic_function_trampolines,
+            custom_sections,
         )?;
 
         link_module(
-            &module_info,
+            module_info,
             &finished_functions,
-            artifact.get_function_relocations(),
-            &custom_sections,
-            artifact.get_custom_section_relocations_ref(),
-            artifact.get_libcall_trampolines(),
-            artifact.get_libcall_trampoline_len(),
+            function_relocations,
+            &allocated_custom_sections,
+            custom_section_relocations,
+            libcall_trampolines,
+            libcall_trampoline_len,
         );
 
         // Compute indices into the shared signature table.
@@ -183,21 +608,14 @@ impl Artifact {
                 .collect::<PrimaryMap<_, _>>()
         };
 
-        let eh_frame = match artifact.get_debug_ref() {
-            Some(debug) => {
-                let eh_frame_section_size = artifact.get_custom_sections_ref()[debug.eh_frame]
-                    .bytes
-                    .len();
-                let eh_frame_section_pointer = custom_sections[debug.eh_frame];
-                Some(unsafe {
-                    std::slice::from_raw_parts(*eh_frame_section_pointer, eh_frame_section_size)
-                })
-            }
-            None => None,
-        };
+        let eh_frame = debug.map(|debug| {
+            let eh_frame_section_size = custom_sections[debug.eh_frame.index()].bytes().len();
+            let eh_frame_section_pointer = allocated_custom_sections[debug.eh_frame];
+            unsafe { std::slice::from_raw_parts(*eh_frame_section_pointer, eh_frame_section_size) }
+        });
 
         // Make all code compiled thus far executable.
-        engine_inner.publish_compiled_code();
+        engine_inner.publish_compiled_code()?;
 
         engine_inner.publish_eh_frame(eh_frame)?;
 
@@ -217,16 +635,15 @@ impl Artifact {
             finished_dynamic_function_trampolines.into_boxed_slice();
         let signatures = signatures.into_boxed_slice();
 
-        Ok(Self {
-            artifact,
-            allocated: Some(AllocatedArtifact {
-                finished_functions,
-                finished_function_call_trampolines,
-                finished_dynamic_function_trampolines,
-                signatures,
-                frame_info_registration: Some(Mutex::new(None)),
-                finished_function_lengths,
-            }),
+        Ok(AllocatedArtifact {
+            finished_functions,
+            finished_function_call_trampolines,
+            finished_dynamic_function_trampolines,
+            signatures,
+            frame_info_registration: Some(Mutex::new(None)),
+            finished_function_lengths,
+            #[cfg(feature = "compiler")]
+            lazy_functions: None,
         })
     }
 
@@ -249,6 +666,10 @@ impl ArtifactCreate for Artifact {
         self.artifact.cpu_features()
     }
 
+    fn compiler(&self) -> &str {
+        self.artifact.compiler()
+    }
+
     fn data_initializers(&self) -> &[OwnedDataInitializer] {
         self.artifact.data_initializers()
     }
@@ -303,12 +724,15 @@ impl Artifact {
                 .collect::<PrimaryMap<LocalFunctionIndex, _>>()
                 .into_boxed_slice();
 
-            let frame_infos = self.artifact.get_frame_info_ref();
-            *info = register_frame_info(
-                self.artifact.create_module_info(),
-                &finished_function_extents,
-                frame_infos.clone(),
-            );
+            // The traps of an archived artifact can't be symbolicated if
+            // its frame info can't be read.
+            if let Some(frame_infos) = self.artifact.get_frame_info() {
+                *info = register_frame_info(
+                    self.artifact.create_module_info(),
+                    &finished_function_extents,
+                    frame_infos,
+                );
+            }
         }
     }
 
@@ -322,6 +746,20 @@ impl Artifact {
             .finished_functions
     }
 
+    /// Returns the compiler of the functions of this `Artifact`, if they're
+    /// compiled on their first call.
+    fn lazy_functions(&self) -> Option<Arc<dyn LazyFunctionCompiler>> {
+        #[cfg(feature = "compiler")]
+        if let Some(lazy_functions) = self
+            .allocated
+            .as_ref()
+            .and_then(|allocated| allocated.lazy_functions.clone())
+        {
+            return Some(lazy_functions);
+        }
+        None
+    }
+
     /// Returns the function call trampolines allocated in memory of this
     /// `Artifact`, ready to be run.
     pub fn finished_function_call_trampolines(&self) -> &BoxedSlice<SignatureIndex, VMTrampoline> {
@@ -396,7 +834,10 @@ impl Artifact {
         // Get pointers to where metadata about local tables should live in VM memory.
 
         let (allocator, memory_definition_locations, table_definition_locations) =
-            InstanceAllocator::new(&module);
+            match tunables.instance_pool() {
+                Some(pool) => InstanceAllocator::new_in_pool(&module, pool),
+                None => InstanceAllocator::new(&module),
+            };
         let finished_memories = tunables
             .create_memories(
                 context,
@@ -426,13 +867,14 @@ impl Artifact {
             allocator,
             module,
             context,
-            self.finished_functions().clone(),
-            self.finished_function_call_trampolines().clone(),
+            self.finished_functions(),
+            self.finished_function_call_trampolines(),
             finished_memories,
             finished_tables,
             finished_globals,
             imports,
-            self.signatures().clone(),
+            self.signatures(),
+            self.lazy_functions(),
         )
         .map_err(|trap| InstantiationError::Start(RuntimeError::from_trap(trap)))?;
         Ok(handle)
@@ -554,6 +996,7 @@ impl Artifact {
             data_initializers,
             function_body_lengths,
             cpu_features: target.cpu_features().as_u64(),
+            compiler: compiler.name().to_string(),
         };
 
         Ok((metadata, module_translation, function_body_inputs))
@@ -589,6 +1032,14 @@ impl Artifact {
             CompileError::Codegen(format!("{}", err))
         }
 
+        // The code of an interpreter can't be linked in an executable.
+        if compiler.interpreter().is_some() {
+            return Err(CompileError::UnsupportedTarget(format!(
+                "{} doesn't compile to machine code, which an object file must contain",
+                compiler.name()
+            )));
+        }
+
         let target_triple = target.triple();
         let (mut metadata, module_translation, function_body_inputs) =
             Self::metadata(compiler, data, metadata_prefix, target, tunables, features)
@@ -613,7 +1064,7 @@ impl Artifact {
 
         let serialized_data = metadata.serialize().map_err(to_compile_error)?;
         let mut metadata_binary = vec![];
-        metadata_binary.extend(MetadataHeader::new(serialized_data.len()).into_bytes());
+        metadata_binary.extend(MetadataHeader::new(&serialized_data).into_bytes());
         metadata_binary.extend(serialized_data);
 
         let (_compile_info, symbol_registry) = metadata.split();
@@ -681,9 +1132,8 @@ impl Artifact {
         engine: &Engine,
         bytes: &[u8],
     ) -> Result<Self, DeserializeError> {
-        let metadata_len = MetadataHeader::parse(bytes)?;
-        let metadata_slice = Self::get_byte_slice(bytes, MetadataHeader::LEN, bytes.len())?;
-        let metadata_slice = Self::get_byte_slice(metadata_slice, 0, metadata_len)?;
+        let metadata_slice = MetadataHeader::parse_checked(bytes)?;
+        let metadata_len = metadata_slice.len();
         let metadata: ModuleMetadata = ModuleMetadata::deserialize(metadata_slice)?;
 
         const WORD_SIZE: usize = mem::size_of::<usize>();
@@ -761,12 +1211,15 @@ impl Artifact {
             finished_dynamic_function_trampolines.push(fp);
         }
 
-        let artifact = ArtifactBuild::from_serializable(SerializableModule {
-            compilation: SerializableCompilation::default(),
-            compile_info: metadata.compile_info,
-            data_initializers: metadata.data_initializers,
-            cpu_features: metadata.cpu_features,
-        });
+        let artifact =
+            ArtifactBuildVariant::Plain(ArtifactBuild::from_serializable(SerializableModule {
+                compilation: SerializableCompilation::default(),
+                compile_info: metadata.compile_info,
+                data_initializers: metadata.data_initializers,
+                cpu_features: metadata.cpu_features,
+                compiler: metadata.compiler,
+                interpreted: false,
+            }));
 
         let finished_function_lengths = finished_functions
             .values()
@@ -785,6 +1238,8 @@ impl Artifact {
                 signatures: signatures.into_boxed_slice(),
                 finished_function_lengths,
                 frame_info_registration: None,
+                #[cfg(feature = "compiler")]
+                lazy_functions: None,
             }),
         })
     }
@@ -3,7 +3,10 @@
 
 //! Memory management for executable code.
 use super::unwind::UnwindRegistry;
-use wasmer_types::{CompiledFunctionUnwindInfo, CustomSection, FunctionBody};
+use std::ptr::NonNull;
+use std::slice;
+use std::sync::Arc;
+use wasmer_types::{CompiledFunctionUnwindInfoReference, CustomSectionLike, FunctionBodyLike};
 use wasmer_vm::{Mmap, VMFunctionBody};
 
 /// The optimal alignment for functions.
@@ -17,19 +20,124 @@ const ARCH_FUNCTION_ALIGNMENT: usize = 16;
 ///
 const DATA_SECTION_ALIGNMENT: usize = 64;
 
+/// Allocator of the pages holding executable code, for the platforms
+/// with a custom JIT policy. By default, the pages are allocated with
+/// `mmap` and protected with `mprotect`, or their equivalents.
+pub trait CodeMemoryAllocator: Send + Sync {
+    /// Allocate `size` bytes of page-aligned memory, readable and
+    /// writable. `size` is a multiple of the page size.
+    fn allocate(&self, size: usize) -> Result<NonNull<u8>, String>;
+
+    /// Make the pages holding the `size` first bytes of the memory at
+    /// `ptr`, returned by [`CodeMemoryAllocator::allocate`], readable and
+    /// executable.
+    fn make_executable(&self, ptr: NonNull<u8>, size: usize) -> Result<(), String>;
+
+    /// Free the memory at `ptr` returned by
+    /// [`CodeMemoryAllocator::allocate`] for `size` bytes.
+    fn deallocate(&self, ptr: NonNull<u8>, size: usize);
+}
+
+/// The pages holding the code.
+enum Pages {
+    Mmap(Mmap),
+    Custom {
+        allocator: Arc<dyn CodeMemoryAllocator>,
+        ptr: NonNull<u8>,
+        len: usize,
+    },
+}
+
+// The pages are owned by the `CodeMemory`, like an `Mmap`.
+unsafe impl Send for Pages {}
+unsafe impl Sync for Pages {}
+
+impl Pages {
+    fn allocate(
+        allocator: Option<&Arc<dyn CodeMemoryAllocator>>,
+        size: usize,
+    ) -> Result<Self, String> {
+        match allocator {
+            Some(allocator) if size > 0 => {
+                let len = round_up(size, region::page::size());
+                Ok(Self::Custom {
+                    allocator: allocator.clone(),
+                    ptr: allocator.allocate(len)?,
+                    len,
+                })
+            }
+            _ => Ok(Self::Mmap(Mmap::with_at_least(size)?)),
+        }
+    }
+
+    fn as_ptr(&self) -> *const u8 {
+        match self {
+            Self::Mmap(mmap) => mmap.as_ptr(),
+            Self::Custom { ptr, .. } => ptr.as_ptr(),
+        }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        match self {
+            Self::Mmap(mmap) => mmap.as_mut_slice(),
+            Self::Custom { ptr, len, .. } => unsafe {
+                slice::from_raw_parts_mut(ptr.as_ptr(), *len)
+            },
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Mmap(mmap) => mmap.len(),
+            Self::Custom { len, .. } => *len,
+        }
+    }
+
+    fn make_executable(&mut self, size: usize) -> Result<(), String> {
+        match self {
+            Self::Mmap(mmap) => unsafe {
+                region::protect(mmap.as_mut_ptr(), size, region::Protection::READ_EXECUTE)
+            }
+            .map_err(|e| e.to_string()),
+            Self::Custom { allocator, ptr, .. } => allocator.make_executable(*ptr, size),
+        }
+    }
+}
+
+impl Drop for Pages {
+    fn drop(&mut self) {
+        if let Self::Custom {
+            allocator,
+            ptr,
+            len,
+        } = self
+        {
+            allocator.deallocate(*ptr, *len);
+        }
+    }
+}
+
 /// Memory manager for executable code.
 pub struct CodeMemory {
     unwind_registry: UnwindRegistry,
-    mmap: Mmap,
+    allocator: Option<Arc<dyn CodeMemoryAllocator>>,
+    pages: Pages,
     start_of_nonexecutable_pages: usize,
 }
 
 impl CodeMemory {
     /// Create a new `CodeMemory` instance.
     pub fn new() -> Self {
+        Self::with_allocator(None)
+    }
+
+    /// Create a new `CodeMemory` instance, whose pages are allocated by
+    /// `allocator` if any, or with `mmap` otherwise.
+    pub fn with_allocator(allocator: Option<Arc<dyn CodeMemoryAllocator>>) -> Self {
         Self {
             unwind_registry: UnwindRegistry::new(),
-            mmap: Mmap::new(),
+            allocator,
+            pages: Pages::Mmap(Mmap::new()),
             start_of_nonexecutable_pages: 0,
         }
     }
@@ -41,11 +149,11 @@ impl CodeMemory {
 
     /// Allocate a single contiguous block of memory at a fixed virtual address for the functions and custom sections, and copy the data in place.
     #[allow(clippy::type_complexity)]
-    pub fn allocate(
+    pub fn allocate<F: FunctionBodyLike + ?Sized, S: CustomSectionLike + ?Sized>(
         &mut self,
-        functions: &[&FunctionBody],
-        executable_sections: &[&CustomSection],
-        data_sections: &[&CustomSection],
+        functions: &[&F],
+        executable_sections: &[&S],
+        data_sections: &[&S],
     ) -> Result<(Vec<&mut [VMFunctionBody]>, Vec<&mut [u8]>, Vec<&mut [u8]>), String> {
         let mut function_result = vec![];
         let mut data_section_result = vec![];
@@ -66,47 +174,49 @@ impl CodeMemory {
         let total_len = round_up(
             functions.iter().fold(0, |acc, func| {
                 round_up(
-                    acc + Self::function_allocation_size(func),
+                    acc + Self::function_allocation_size(*func),
                     ARCH_FUNCTION_ALIGNMENT,
                 )
             }) + executable_sections.iter().fold(0, |acc, exec| {
-                round_up(acc + exec.bytes.len(), ARCH_FUNCTION_ALIGNMENT)
+                round_up(acc + exec.bytes().len(), ARCH_FUNCTION_ALIGNMENT)
             }),
             page_size,
         ) + data_sections.iter().fold(0, |acc, data| {
-            round_up(acc + data.bytes.len(), DATA_SECTION_ALIGNMENT)
+            round_up(acc + data.bytes().len(), DATA_SECTION_ALIGNMENT)
         });
 
         // 2. Allocate the pages. Mark them all read-write.
 
-        self.mmap = Mmap::with_at_least(total_len)?;
+        self.pages = Pages::allocate(self.allocator.as_ref(), total_len)?;
 
         // 3. Determine where the pointers to each function, executable section
         // or data section are. Copy the functions. Collect the addresses of each and return them.
 
+        let base_address = self.pages.as_ptr() as usize;
         let mut bytes = 0;
-        let mut buf = self.mmap.as_mut_slice();
+        let mut buf = self.pages.as_mut_slice();
         for func in functions {
             let len = round_up(
-                Self::function_allocation_size(func),
+                Self::function_allocation_size(*func),
                 ARCH_FUNCTION_ALIGNMENT,
             );
             let (func_buf, next_buf) = buf.split_at_mut(len);
             buf = next_buf;
             bytes += len;
 
-            let vmfunc = Self::copy_function(&mut self.unwind_registry, func, func_buf);
+            let vmfunc =
+                Self::copy_function(&mut self.unwind_registry, base_address, *func, func_buf);
             assert_eq!(vmfunc.as_ptr() as usize % ARCH_FUNCTION_ALIGNMENT, 0);
             function_result.push(vmfunc);
         }
         for section in executable_sections {
-            let section = &section.bytes;
+            let section = section.bytes();
             assert_eq!(buf.as_mut_ptr() as usize % ARCH_FUNCTION_ALIGNMENT, 0);
             let len = round_up(section.len(), ARCH_FUNCTION_ALIGNMENT);
             let (s, next_buf) = buf.split_at_mut(len);
             buf = next_buf;
             bytes += len;
-            s[..section.len()].copy_from_slice(section.as_slice());
+            s[..section.len()].copy_from_slice(section);
             executable_section_result.push(s);
         }
 
@@ -119,12 +229,12 @@ impl CodeMemory {
             buf = buf.split_at_mut(padding).1;
 
             for section in data_sections {
-                let section = &section.bytes;
+                let section = section.bytes();
                 assert_eq!(buf.as_mut_ptr() as usize % DATA_SECTION_ALIGNMENT, 0);
                 let len = round_up(section.len(), DATA_SECTION_ALIGNMENT);
                 let (s, next_buf) = buf.split_at_mut(len);
                 buf = next_buf;
-                s[..section.len()].copy_from_slice(section.as_slice());
+                s[..section.len()].copy_from_slice(section);
                 data_section_result.push(s);
             }
         }
@@ -137,51 +247,47 @@ impl CodeMemory {
     }
 
     /// Apply the page permissions.
-    pub fn publish(&mut self) {
-        if self.mmap.is_empty() || self.start_of_nonexecutable_pages == 0 {
-            return;
-        }
-        assert!(self.mmap.len() >= self.start_of_nonexecutable_pages);
-        unsafe {
-            region::protect(
-                self.mmap.as_mut_ptr(),
-                self.start_of_nonexecutable_pages,
-                region::Protection::READ_EXECUTE,
-            )
+    pub fn publish(&mut self) -> Result<(), String> {
+        if self.pages.len() == 0 || self.start_of_nonexecutable_pages == 0 {
+            return Ok(());
         }
-        .expect("unable to make memory readonly and executable");
+        assert!(self.pages.len() >= self.start_of_nonexecutable_pages);
+        self.pages
+            .make_executable(self.start_of_nonexecutable_pages)
     }
 
     /// Calculates the allocation size of the given compiled function.
-    fn function_allocation_size(func: &FunctionBody) -> usize {
-        match &func.unwind_info {
-            Some(CompiledFunctionUnwindInfo::WindowsX64(info)) => {
+    fn function_allocation_size<F: FunctionBodyLike + ?Sized>(func: &F) -> usize {
+        match func.unwind_info() {
+            Some(CompiledFunctionUnwindInfoReference::WindowsX64(info)) => {
                 // Windows unwind information is required to be emitted into code memory
                 // This is because it must be a positive relative offset from the start of the memory
                 // Account for necessary unwind information alignment padding (32-bit alignment)
-                ((func.body.len() + 3) & !3) + info.len()
+                ((func.body().len() + 3) & !3) + info.len()
             }
-            _ => func.body.len(),
+            _ => func.body().len(),
         }
     }
 
     /// Copies the data of the compiled function to the given buffer.
     ///
-    /// This will also add the function to the current function table.
-    fn copy_function<'a>(
+    /// This will also add the function to the current function table,
+    /// relative to `base_address`, the start of the code memory.
+    fn copy_function<'a, F: FunctionBodyLike + ?Sized>(
         registry: &mut UnwindRegistry,
-        func: &FunctionBody,
+        base_address: usize,
+        func: &F,
         buf: &'a mut [u8],
     ) -> &'a mut [VMFunctionBody] {
         assert_eq!(buf.as_ptr() as usize % ARCH_FUNCTION_ALIGNMENT, 0);
 
-        let func_len = func.body.len();
+        let func_len = func.body().len();
 
         let (body, remainder) = buf.split_at_mut(func_len);
-        body.copy_from_slice(&func.body);
+        body.copy_from_slice(func.body());
         let vmfunc = Self::view_as_mut_vmfunc_slice(body);
 
-        if let Some(CompiledFunctionUnwindInfo::WindowsX64(info)) = &func.unwind_info {
+        if let Some(CompiledFunctionUnwindInfoReference::WindowsX64(info)) = func.unwind_info() {
             // Windows unwind information is written following the function body
             // Keep unwind information 32-bit aligned (round up to the nearest 4 byte boundary)
             let unwind_start = (func_len + 3) & !3;
@@ -192,9 +298,10 @@ impl CodeMemory {
             slice[padding..].copy_from_slice(info);
         }
 
-        if let Some(info) = &func.unwind_info {
+        if let Some(info) = func.unwind_info() {
+            let func_start = (vmfunc.as_ptr() as usize - base_address) as u32;
             registry
-                .register(vmfunc.as_ptr() as usize, 0, func_len as u32, info)
+                .register(base_address, func_start, func_len as u32, info)
                 .expect("failed to register unwind information");
         }
 
@@ -6,27 +6,36 @@ use crate::Artifact;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::BaseTunables;
 #[cfg(not(target_arch = "wasm32"))]
-use crate::CodeMemory;
-#[cfg(not(target_arch = "wasm32"))]
 use crate::{AsEngineRef, EngineRef};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{CodeMemory, CodeMemoryAllocator};
 #[cfg(feature = "compiler")]
 use crate::{Compiler, CompilerConfig};
+#[cfg(all(feature = "compiler", not(target_arch = "wasm32")))]
+use std::any::Any;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::{FunctionExtent, Tunables};
 #[cfg(not(target_arch = "wasm32"))]
 use memmap2::Mmap;
 #[cfg(not(target_arch = "wasm32"))]
+use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Weak;
 use std::sync::{Arc, Mutex};
 #[cfg(not(target_arch = "wasm32"))]
 use wasmer_types::{
-    entity::PrimaryMap, DeserializeError, FunctionBody, FunctionIndex, FunctionType,
-    LocalFunctionIndex, SignatureIndex,
+    entity::{EntityRef, PrimaryMap},
+    DeserializeError, FunctionBodyLike, FunctionIndex, FunctionType, LocalFunctionIndex,
+    SignatureIndex,
 };
-use wasmer_types::{CompileError, Features, ModuleInfo, Target};
+use wasmer_types::{CompileError, Features, MetadataHeader, ModuleInfo, Target};
 #[cfg(not(target_arch = "wasm32"))]
-use wasmer_types::{CustomSection, CustomSectionProtection, SectionIndex};
+use wasmer_types::{CustomSectionLike, CustomSectionProtection, SectionIndex};
+#[cfg(all(feature = "compiler", not(target_arch = "wasm32")))]
+use wasmer_vm::metrics::{self, Counter};
 #[cfg(not(target_arch = "wasm32"))]
 use wasmer_vm::{
     FunctionBodyPtr, SectionBodyPtr, SignatureRegistry, VMFunctionBody, VMSharedSignatureIndex,
@@ -64,7 +73,15 @@ impl Engine {
                 #[cfg(not(target_arch = "wasm32"))]
                 code_memory: vec![],
                 #[cfg(not(target_arch = "wasm32"))]
+                code_memory_allocator: None,
+                #[cfg(all(feature = "compiler", not(target_arch = "wasm32")))]
+                interpreted_code: vec![],
+                #[cfg(not(target_arch = "wasm32"))]
                 signatures: SignatureRegistry::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                function_call_trampolines: HashMap::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                artifacts: HashMap::new(),
             })),
             target: Arc::new(target),
             engine_id: EngineId::default(),
@@ -88,6 +105,44 @@ impl Engine {
         self.name.as_str()
     }
 
+    /// Returns a string identifying the code this engine generates: the
+    /// serialization format version, the compiler with its settings and
+    /// middlewares, the enabled features, and the target.
+    ///
+    /// Artifacts compiled by engines with the same identifier are
+    /// interchangeable, so it can be used to key caches of compiled
+    /// artifacts. The identifier of engines without a compiler, built
+    /// with or without the `compiler` feature, only covers the format
+    /// version and the target.
+    pub fn deterministic_id(&self) -> String {
+        #[cfg(feature = "compiler")]
+        let compiler = {
+            let inner = self.inner();
+            inner.compiler.as_ref().map(|compiler| {
+                let middlewares = compiler
+                    .get_middlewares()
+                    .iter()
+                    .map(|middleware| middleware.deterministic_id())
+                    .collect::<Vec<_>>();
+                format!(
+                    "{}-{:?}-{:?}",
+                    compiler.deterministic_id(),
+                    middlewares,
+                    inner.features
+                )
+            })
+        };
+        #[cfg(not(feature = "compiler"))]
+        let compiler: Option<String> = None;
+        format!(
+            "v{}-{}-{}-{:?}",
+            MetadataHeader::CURRENT_VERSION,
+            compiler.as_deref().unwrap_or("headless"),
+            self.target.triple(),
+            self.target.cpu_features(),
+        )
+    }
+
     /// Create a headless `Engine`
     ///
     /// A headless engine is an engine without any compiler attached.
@@ -114,7 +169,15 @@ impl Engine {
                 #[cfg(not(target_arch = "wasm32"))]
                 code_memory: vec![],
                 #[cfg(not(target_arch = "wasm32"))]
+                code_memory_allocator: None,
+                #[cfg(all(feature = "compiler", not(target_arch = "wasm32")))]
+                interpreted_code: vec![],
+                #[cfg(not(target_arch = "wasm32"))]
                 signatures: SignatureRegistry::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                function_call_trampolines: HashMap::new(),
+                #[cfg(not(target_arch = "wasm32"))]
+                artifacts: HashMap::new(),
             })),
             target: Arc::new(target),
             engine_id: EngineId::default(),
@@ -163,11 +226,24 @@ impl Engine {
     #[cfg(feature = "compiler")]
     #[cfg(not(target_arch = "wasm32"))]
     pub fn compile(&self, binary: &[u8]) -> Result<Arc<Artifact>, CompileError> {
-        Ok(Arc::new(Artifact::new(
-            self,
-            binary,
-            self.tunables.as_ref(),
-        )?))
+        let key = ArtifactKey::new(self, ArtifactSource::Wasm, false, binary);
+        self.shared_artifact(key, || {
+            let artifact = Artifact::new(self, binary, self.tunables.as_ref())?;
+            metrics::increment_counter(Counter::Compilations);
+            Ok(artifact)
+        })
+    }
+
+    /// Validate and compile a WebAssembly binary, parsing it once
+    #[cfg(feature = "compiler")]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn compile_validated(&self, binary: &[u8]) -> Result<Arc<Artifact>, CompileError> {
+        let key = ArtifactKey::new(self, ArtifactSource::Wasm, true, binary);
+        self.shared_artifact(key, || {
+            let artifact = Artifact::new_validated(self, binary, self.tunables.as_ref())?;
+            metrics::increment_counter(Counter::Compilations);
+            Ok(artifact)
+        })
     }
 
     /// Compile a WebAssembly binary
@@ -190,22 +266,94 @@ impl Engine {
     ///
     /// The serialized content must represent a serialized WebAssembly module.
     pub unsafe fn deserialize(&self, bytes: &[u8]) -> Result<Arc<Artifact>, DeserializeError> {
-        Ok(Arc::new(Artifact::deserialize(self, bytes)?))
+        let key = ArtifactKey::new(self, ArtifactSource::Serialized, true, bytes);
+        self.shared_artifact(key, || Artifact::deserialize(self, bytes))
     }
 
     #[cfg(not(target_arch = "wasm32"))]
     /// Deserializes a WebAssembly module from a path
     ///
+    /// The file is kept mapped by the module, which reads it in place.
+    ///
     /// # Safety
     ///
-    /// The file's content must represent a serialized WebAssembly module.
+    /// The file's content must represent a serialized WebAssembly module,
+    /// and it must not be modified while it's mapped.
     pub unsafe fn deserialize_from_file(
         &self,
         file_ref: &Path,
     ) -> Result<Arc<Artifact>, DeserializeError> {
         let file = std::fs::File::open(file_ref)?;
         let mmap = Mmap::map(&file)?;
-        self.deserialize(&mmap)
+        let key = ArtifactKey::new(self, ArtifactSource::Serialized, true, &mmap);
+        self.shared_artifact(key, || Artifact::deserialize_from_mmap(self, mmap))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Deserializes a WebAssembly module without validating it
+    ///
+    /// # Safety
+    ///
+    /// See [`Artifact::deserialize_unchecked`].
+    pub unsafe fn deserialize_unchecked(
+        &self,
+        bytes: &[u8],
+    ) -> Result<Arc<Artifact>, DeserializeError> {
+        let key = ArtifactKey::new(self, ArtifactSource::Serialized, false, bytes);
+        self.shared_artifact(key, || Artifact::deserialize_unchecked(self, bytes))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Deserializes a WebAssembly module from a path without validating it
+    ///
+    /// The file is kept mapped by the module, which reads it in place.
+    ///
+    /// # Safety
+    ///
+    /// See [`Artifact::deserialize_unchecked`]. The file must not be
+    /// modified while it's mapped.
+    pub unsafe fn deserialize_from_file_unchecked(
+        &self,
+        file_ref: &Path,
+    ) -> Result<Arc<Artifact>, DeserializeError> {
+        let file = std::fs::File::open(file_ref)?;
+        let mmap = Mmap::map(&file)?;
+        let key = ArtifactKey::new(self, ArtifactSource::Serialized, false, &mmap);
+        self.shared_artifact(key, || {
+            Artifact::deserialize_from_mmap_unchecked(self, mmap)
+        })
+    }
+
+    /// Returns the live artifact of this engine registered under `key`,
+    /// or registers the one returned by `create`.
+    ///
+    /// This way, the modules compiled or deserialized from the same
+    /// bytes share their code, and the instances of those modules only
+    /// differ by their `VMContext`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn shared_artifact<E>(
+        &self,
+        key: ArtifactKey,
+        create: impl FnOnce() -> Result<Artifact, E>,
+    ) -> Result<Arc<Artifact>, E> {
+        if let Some(artifact) = self.inner().shared_artifact(&key) {
+            return Ok(artifact);
+        }
+        // The engine can't stay locked while the artifact is created, so
+        // the same bytes may be compiled concurrently. The first artifact
+        // registered is kept, the others are dropped.
+        let artifact = Arc::new(create()?);
+        let mut inner = self.inner_mut();
+        if let Some(artifact) = inner.shared_artifact(&key) {
+            return Ok(artifact);
+        }
+        inner
+            .artifacts
+            .retain(|_, (_, artifact)| artifact.strong_count() > 0);
+        inner
+            .artifacts
+            .insert(key, (self.tunables.clone(), Arc::downgrade(&artifact)));
+        Ok(artifact)
     }
 
     /// A unique identifier for this object.
@@ -233,6 +381,13 @@ impl Engine {
     pub fn tunables(&self) -> &dyn Tunables {
         self.tunables.as_ref()
     }
+
+    /// Allocate the executable code of the modules compiled or
+    /// deserialized from now on with `allocator`, instead of `mmap`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_code_memory_allocator(&mut self, allocator: impl CodeMemoryAllocator + 'static) {
+        self.inner_mut().code_memory_allocator = Some(Arc::new(allocator));
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -264,10 +419,62 @@ pub struct EngineInner {
     /// functions to memory.
     #[cfg(not(target_arch = "wasm32"))]
     code_memory: Vec<CodeMemory>,
+    /// The allocator of the code memory, `mmap` if none.
+    #[cfg(not(target_arch = "wasm32"))]
+    code_memory_allocator: Option<Arc<dyn CodeMemoryAllocator>>,
+    /// The code of the interpreted modules, see
+    /// [`Compiler::interpreter`], kept like the code memory.
+    #[cfg(all(feature = "compiler", not(target_arch = "wasm32")))]
+    interpreted_code: Vec<Box<dyn Any + Send + Sync>>,
     /// The signature registry is used mainly to operate with trampolines
     /// performantly.
     #[cfg(not(target_arch = "wasm32"))]
     signatures: SignatureRegistry,
+    /// The function call trampolines already allocated by this engine.
+    ///
+    /// Function call trampolines only depend on the signature, so they
+    /// are shared by all the artifacts of this engine.
+    #[cfg(not(target_arch = "wasm32"))]
+    function_call_trampolines: HashMap<FunctionType, VMTrampoline>,
+    /// The artifacts created by this engine, by the bytes they were
+    /// created from, along with the tunables they were created with.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[allow(clippy::type_complexity)]
+    artifacts: HashMap<ArtifactKey, (Arc<dyn Tunables + Send + Sync>, Weak<Artifact>)>,
+}
+
+/// What the bytes an artifact is created from contain.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum ArtifactSource {
+    Wasm,
+    Serialized,
+}
+
+/// Identifies the artifacts created by an engine from the same bytes.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct ArtifactKey {
+    source: ArtifactSource,
+    /// Whether the bytes were validated. An artifact created from
+    /// unvalidated bytes isn't given back when they must be validated.
+    validated: bool,
+    /// The address of the tunables of the engine, which are kept alive
+    /// by the registered artifacts so that it's not reused.
+    tunables: usize,
+    hash: [u8; 32],
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ArtifactKey {
+    fn new(engine: &Engine, source: ArtifactSource, validated: bool, bytes: &[u8]) -> Self {
+        Self {
+            source,
+            validated,
+            tunables: Arc::as_ptr(&engine.tunables) as *const () as usize,
+            hash: *blake3::hash(bytes).as_bytes(),
+        }
+    }
 }
 
 impl EngineInner {
@@ -282,6 +489,47 @@ impl EngineInner {
         }
     }
 
+    /// Whether the compiler of this engine compiles the modules for an
+    /// interpreter, see [`Compiler::interpreter`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn interprets(&self) -> bool {
+        #[cfg(feature = "compiler")]
+        if let Some(compiler) = self.compiler.as_ref() {
+            return compiler.interpreter().is_some();
+        }
+        false
+    }
+
+    /// Loads the functions of an interpreted module with the interpreter
+    /// of the compiler of this engine, keeping their code.
+    #[cfg(all(feature = "compiler", not(target_arch = "wasm32")))]
+    pub(crate) fn load_interpreted(
+        &mut self,
+        module: &ModuleInfo,
+        function_bodies: &[&[u8]],
+    ) -> Result<
+        (
+            PrimaryMap<LocalFunctionIndex, FunctionBodyPtr>,
+            VMTrampoline,
+        ),
+        CompileError,
+    > {
+        let interpreter = self.compiler()?.interpreter().ok_or_else(|| {
+            CompileError::Codegen("The compiler of the engine has no interpreter".to_string())
+        })?;
+        let functions = interpreter.load(module, function_bodies)?;
+        self.interpreted_code.push(functions.code);
+        Ok((functions.function_bodies, functions.call_trampoline))
+    }
+
+    /// Gets the artifact registered under `key`, if it's still alive.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn shared_artifact(&self, key: &ArtifactKey) -> Option<Arc<Artifact>> {
+        self.artifacts
+            .get(key)
+            .and_then(|(_, artifact)| artifact.upgrade())
+    }
+
     /// Validate the module
     #[cfg(feature = "compiler")]
     pub fn validate(&self, data: &[u8]) -> Result<(), CompileError> {
@@ -296,15 +544,18 @@ impl EngineInner {
     }
 
     /// Allocate compiled functions into memory
+    ///
+    /// The function call trampolines are given in the order of the
+    /// signatures of `module`.
     #[cfg(not(target_arch = "wasm32"))]
     #[allow(clippy::type_complexity)]
-    pub(crate) fn allocate(
+    pub(crate) fn allocate<F: FunctionBodyLike + ?Sized, S: CustomSectionLike + ?Sized>(
         &mut self,
-        _module: &ModuleInfo,
-        functions: &PrimaryMap<LocalFunctionIndex, FunctionBody>,
-        function_call_trampolines: &PrimaryMap<SignatureIndex, FunctionBody>,
-        dynamic_function_trampolines: &PrimaryMap<FunctionIndex, FunctionBody>,
-        custom_sections: &PrimaryMap<SectionIndex, CustomSection>,
+        module: &ModuleInfo,
+        functions: &[&F],
+        function_call_trampolines: &[&F],
+        dynamic_function_trampolines: &[&F],
+        custom_sections: &[&S],
     ) -> Result<
         (
             PrimaryMap<LocalFunctionIndex, FunctionExtent>,
@@ -314,15 +565,38 @@ impl EngineInner {
         ),
         CompileError,
     > {
+        // Only allocate the function call trampolines for signatures
+        // that this engine hasn't seen yet.
+        let mut new_signatures = Vec::new();
+        let new_function_call_trampolines = function_call_trampolines
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| {
+                let signature = &module.signatures[SignatureIndex::new(*index)];
+                if self.function_call_trampolines.contains_key(signature)
+                    || new_signatures.contains(&signature)
+                {
+                    return false;
+                }
+                new_signatures.push(signature);
+                true
+            })
+            .map(|(_, body)| *body)
+            .collect::<Vec<_>>();
+
         let function_bodies = functions
-            .values()
-            .chain(function_call_trampolines.values())
-            .chain(dynamic_function_trampolines.values())
+            .iter()
+            .chain(new_function_call_trampolines.iter())
+            .chain(dynamic_function_trampolines.iter())
+            .copied()
             .collect::<Vec<_>>();
         let (executable_sections, data_sections): (Vec<_>, _) = custom_sections
-            .values()
-            .partition(|section| section.protection == CustomSectionProtection::ReadExecute);
-        self.code_memory.push(CodeMemory::new());
+            .iter()
+            .copied()
+            .partition(|section| *section.protection() == CustomSectionProtection::ReadExecute);
+        self.code_memory.push(CodeMemory::with_allocator(
+            self.code_memory_allocator.clone(),
+        ));
 
         let (mut allocated_functions, allocated_executable_sections, allocated_data_sections) =
             self.code_memory
@@ -348,16 +622,22 @@ impl EngineInner {
             })
             .collect::<PrimaryMap<LocalFunctionIndex, _>>();
 
-        let mut allocated_function_call_trampolines: PrimaryMap<SignatureIndex, VMTrampoline> =
-            PrimaryMap::new();
-        for ptr in allocated_functions
-            .drain(0..function_call_trampolines.len())
-            .map(|slice| slice.as_ptr())
-        {
+        for (signature, ptr) in new_signatures.into_iter().zip(
+            allocated_functions
+                .drain(0..new_function_call_trampolines.len())
+                .map(|slice| slice.as_ptr()),
+        ) {
             let trampoline =
                 unsafe { std::mem::transmute::<*const VMFunctionBody, VMTrampoline>(ptr) };
-            allocated_function_call_trampolines.push(trampoline);
+            self.function_call_trampolines
+                .insert(signature.clone(), trampoline);
         }
+        let cached_function_call_trampolines = &self.function_call_trampolines;
+        let allocated_function_call_trampolines = (0..function_call_trampolines.len())
+            .map(|index| {
+                cached_function_call_trampolines[&module.signatures[SignatureIndex::new(index)]]
+            })
+            .collect::<PrimaryMap<SignatureIndex, VMTrampoline>>();
 
         let allocated_dynamic_function_trampolines = allocated_functions
             .drain(..)
@@ -368,9 +648,9 @@ impl EngineInner {
         let mut data_iter = allocated_data_sections.iter();
         let allocated_custom_sections = custom_sections
             .iter()
-            .map(|(_, section)| {
+            .map(|section| {
                 SectionBodyPtr(
-                    if section.protection == CustomSectionProtection::ReadExecute {
+                    if *section.protection() == CustomSectionProtection::ReadExecute {
                         exec_iter.next()
                     } else {
                         data_iter.next()
@@ -391,8 +671,17 @@ impl EngineInner {
 
     #[cfg(not(target_arch = "wasm32"))]
     /// Make memory containing compiled code executable.
-    pub(crate) fn publish_compiled_code(&mut self) {
-        self.code_memory.last_mut().unwrap().publish();
+    pub(crate) fn publish_compiled_code(&mut self) -> Result<(), CompileError> {
+        self.code_memory
+            .last_mut()
+            .unwrap()
+            .publish()
+            .map_err(|message| {
+                CompileError::Resource(format!(
+                    "unable to make memory readonly and executable: {}",
+                    message
+                ))
+            })
     }
 
     #[cfg(not(target_arch = "wasm32"))]
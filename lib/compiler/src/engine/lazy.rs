@@ -0,0 +1,128 @@
+//! Compile the functions of the modules compiled lazily on their first
+//! call.
+
+use crate::engine::link::link_function;
+use crate::{
+    libcall_trampoline_len, make_libcall_trampolines, register_function_frame_info, Engine,
+    GlobalFrameInfoRegistration, LazyFunctionBodies,
+};
+use once_cell::sync::OnceCell;
+use std::sync::{Arc, Mutex};
+use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
+use wasmer_types::{
+    CompileError, CompileModuleInfo, CustomSection, FunctionBody, LocalFunctionIndex, ModuleInfo,
+    SectionIndex,
+};
+use wasmer_vm::{FunctionBodyPtr, LazyFunctionCompiler, Trap};
+
+/// The functions of a module compiled lazily, compiled on their first
+/// call in the code memory of the engine.
+pub struct LazyFunctions {
+    engine: Engine,
+    compile_info: CompileModuleInfo,
+    module: Arc<ModuleInfo>,
+    bodies: Arc<LazyFunctionBodies>,
+    /// The compile stubs of the functions.
+    stubs: BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
+    compiled: PrimaryMap<LocalFunctionIndex, OnceCell<FunctionBodyPtr>>,
+    frame_info_registrations: Mutex<Vec<GlobalFrameInfoRegistration>>,
+}
+
+impl LazyFunctions {
+    /// Creates the lazy functions of a module whose compile stubs are
+    /// `stubs`.
+    pub fn new(
+        engine: Engine,
+        compile_info: CompileModuleInfo,
+        bodies: Arc<LazyFunctionBodies>,
+        stubs: BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
+    ) -> Self {
+        let module = Arc::new(compile_info.module.clone());
+        let compiled = stubs.keys().map(|_| OnceCell::new()).collect();
+        Self {
+            engine,
+            compile_info,
+            module,
+            bodies,
+            stubs,
+            compiled,
+            frame_info_registrations: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Compiles the function `index`, links it and publishes it.
+    fn compile(&self, index: LocalFunctionIndex) -> Result<FunctionBodyPtr, CompileError> {
+        let target = self.engine.target();
+        let mut engine_inner = self.engine.inner_mut();
+        let compilation = engine_inner.compiler()?.compile_function(
+            target,
+            &self.compile_info,
+            self.bodies.module_translation_state(),
+            index,
+            &self.bodies.function_body_input(index),
+        )?;
+        let function = &compilation.functions[LocalFunctionIndex::new(0)];
+
+        let mut custom_sections = compilation.custom_sections.clone();
+        let mut custom_section_relocations = compilation
+            .custom_sections
+            .iter()
+            .map(|(_, section)| section.relocations.clone())
+            .collect::<PrimaryMap<SectionIndex, _>>();
+        let libcall_trampolines_section = make_libcall_trampolines(target);
+        custom_section_relocations.push(libcall_trampolines_section.relocations.clone());
+        let libcall_trampolines = custom_sections.push(libcall_trampolines_section);
+
+        let custom_sections = custom_sections.values().collect::<Vec<_>>();
+        let (allocated_functions, _, _, allocated_custom_sections) = engine_inner
+            .allocate::<FunctionBody, CustomSection>(
+                &self.module,
+                &[&function.body],
+                &[],
+                &[],
+                &custom_sections,
+            )?;
+        let extent = &allocated_functions[LocalFunctionIndex::new(0)];
+
+        link_function(
+            index,
+            extent,
+            &function.relocations,
+            &self.stubs,
+            &allocated_custom_sections,
+            &custom_section_relocations,
+            libcall_trampolines,
+            libcall_trampoline_len(target),
+        );
+
+        let eh_frame = compilation.debug.as_ref().map(|debug| {
+            let eh_frame_section_size = custom_sections[debug.eh_frame.index()].bytes.len();
+            let eh_frame_section_pointer = allocated_custom_sections[debug.eh_frame];
+            unsafe { std::slice::from_raw_parts(*eh_frame_section_pointer, eh_frame_section_size) }
+        });
+
+        engine_inner.publish_compiled_code()?;
+        engine_inner.publish_eh_frame(eh_frame)?;
+
+        let registration = register_function_frame_info(
+            self.module.clone(),
+            index,
+            extent,
+            function.frame_info.clone(),
+        );
+        self.frame_info_registrations
+            .lock()
+            .unwrap()
+            .push(registration);
+        Ok(extent.ptr)
+    }
+}
+
+impl LazyFunctionCompiler for LazyFunctions {
+    fn compiled_function(&self, index: LocalFunctionIndex) -> Result<FunctionBodyPtr, Trap> {
+        self.compiled[index]
+            .get_or_try_init(|| self.compile(index))
+            .copied()
+            .map_err(|err| Trap::User(Box::new(err)))
+    }
+}
@@ -3,22 +3,26 @@
 use crate::get_libcall_trampoline;
 use crate::FunctionExtent;
 use std::ptr::{read_unaligned, write_unaligned};
+#[cfg(feature = "compiler")]
+use wasmer_types::entity::BoxedSlice;
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::{LocalFunctionIndex, ModuleInfo};
 use wasmer_types::{Relocation, RelocationKind, RelocationTarget, Relocations, SectionIndex};
 use wasmer_vm::libcalls::function_pointer;
+#[cfg(feature = "compiler")]
+use wasmer_vm::FunctionBodyPtr;
 use wasmer_vm::SectionBodyPtr;
 
 fn apply_relocation(
     body: usize,
     r: &Relocation,
-    allocated_functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+    function_address: &dyn Fn(LocalFunctionIndex) -> usize,
     allocated_sections: &PrimaryMap<SectionIndex, SectionBodyPtr>,
     libcall_trampolines: SectionIndex,
     libcall_trampoline_len: usize,
 ) {
     let target_func_address: usize = match r.reloc_target {
-        RelocationTarget::LocalFunc(index) => *allocated_functions[index].ptr as usize,
+        RelocationTarget::LocalFunc(index) => function_address(index),
         RelocationTarget::LibCall(libcall) => {
             // Use the direct target of the libcall if the relocation supports
             // a full 64-bit address. Otherwise use a trampoline.
@@ -93,6 +97,19 @@ fn apply_relocation(
                 | read_unaligned(reloc_address as *mut u32);
             write_unaligned(reloc_address as *mut u32, reloc_delta);
         },
+        RelocationKind::S390xPCRel32Dbl | RelocationKind::S390xPLTRel32Dbl => unsafe {
+            // The offset is counted in halfwords, and written in the byte
+            // order of the host like the instruction it is part of.
+            let (reloc_address, reloc_delta) = r.for_address(body, target_func_address as u64);
+            let reloc_delta = (reloc_delta as i64) >> 1;
+            if reloc_delta != (reloc_delta as i32) as i64 {
+                panic!(
+                    "Relocation to big for {:?} for {:?} with {:x}",
+                    r.kind, r.reloc_target, reloc_delta,
+                )
+            }
+            write_unaligned(reloc_address as *mut u32, reloc_delta as u32);
+        },
         kind => panic!(
             "Relocation kind unsupported in the current architecture {}",
             kind
@@ -111,13 +128,14 @@ pub fn link_module(
     libcall_trampolines: SectionIndex,
     trampoline_len: usize,
 ) {
+    let function_address = |index| *allocated_functions[index].ptr as usize;
     for (i, section_relocs) in section_relocations.iter() {
         let body = *allocated_sections[i] as usize;
         for r in section_relocs {
             apply_relocation(
                 body,
                 r,
-                allocated_functions,
+                &function_address,
                 allocated_sections,
                 libcall_trampolines,
                 trampoline_len,
@@ -130,11 +148,61 @@ pub fn link_module(
             apply_relocation(
                 body,
                 r,
-                allocated_functions,
+                &function_address,
+                allocated_sections,
+                libcall_trampolines,
+                trampoline_len,
+            );
+        }
+    }
+}
+
+/// Links the function `index` of a module compiled lazily, compiled on
+/// its first call, and its custom sections.
+///
+/// The relocations to the function itself resolve to `body`, and the
+/// ones to the other functions of the module to `finished_functions`,
+/// their compile stubs.
+#[cfg(feature = "compiler")]
+#[allow(clippy::too_many_arguments)]
+pub fn link_function(
+    index: LocalFunctionIndex,
+    body: &FunctionExtent,
+    function_relocations: &[Relocation],
+    finished_functions: &BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
+    allocated_sections: &PrimaryMap<SectionIndex, SectionBodyPtr>,
+    section_relocations: &PrimaryMap<SectionIndex, Vec<Relocation>>,
+    libcall_trampolines: SectionIndex,
+    trampoline_len: usize,
+) {
+    let function_address = |target| {
+        if target == index {
+            *body.ptr as usize
+        } else {
+            *finished_functions[target] as usize
+        }
+    };
+    for (i, section_relocs) in section_relocations.iter() {
+        let section = *allocated_sections[i] as usize;
+        for r in section_relocs {
+            apply_relocation(
+                section,
+                r,
+                &function_address,
                 allocated_sections,
                 libcall_trampolines,
                 trampoline_len,
             );
         }
     }
+    for r in function_relocations {
+        apply_relocation(
+            *body.ptr as usize,
+            r,
+            &function_address,
+            allocated_sections,
+            libcall_trampolines,
+            trampoline_len,
+        );
+    }
 }
@@ -21,6 +21,9 @@ mod builder;
 mod code_memory;
 #[cfg(feature = "translator")]
 mod inner;
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+mod lazy;
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
 mod link;
@@ -37,7 +40,7 @@ pub use self::resolver::resolve_imports;
 #[cfg(not(target_arch = "wasm32"))]
 pub use self::trap::*;
 #[cfg(not(target_arch = "wasm32"))]
-pub use self::tunables::{BaseTunables, Tunables};
+pub use self::tunables::{BaseTunables, MemoryStylePolicy, Tunables};
 
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
@@ -46,9 +49,12 @@ pub use self::artifact::Artifact;
 pub use self::builder::EngineBuilder;
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
-pub use self::code_memory::CodeMemory;
+pub use self::code_memory::{CodeMemory, CodeMemoryAllocator};
 #[cfg(feature = "translator")]
 pub use self::inner::{Engine, EngineInner};
+#[cfg(feature = "compiler")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::lazy::LazyFunctions;
 #[cfg(feature = "translator")]
 #[cfg(not(target_arch = "wasm32"))]
 pub use self::link::link_module;
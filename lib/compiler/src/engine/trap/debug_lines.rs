@@ -0,0 +1,430 @@
+//! Translation of the DWARF line info of a Wasm module, which maps the
+//! instructions of the module to the lines of its sources, into DWARF
+//! line info for its compiled functions, so that the debuggers can
+//! step through the sources and set breakpoints in them.
+//!
+//! The addresses of the Wasm DWARF sections are offsets in the code
+//! section, which are mapped to the compiled code with the address
+//! maps of the functions.
+
+use gimli::write::{Address, AttributeValue, DwarfUnit, EndianVec, LineProgram, LineString};
+use gimli::{EndianSlice, LittleEndian, RunTimeEndian};
+use std::collections::HashMap;
+use wasmer_types::{FunctionAddressMap, ModuleInfo};
+
+/// A compiled function to describe.
+pub struct DebugFunction<'a> {
+    /// The name of the function.
+    pub name: String,
+    /// The address of the code of the function.
+    pub address: u64,
+    /// The size of the code of the function.
+    pub size: u64,
+    /// The map of the code of the function to its Wasm instructions.
+    pub address_map: &'a FunctionAddressMap,
+}
+
+/// The line of the sources of an instruction: the index of the file,
+/// the line and the column.
+type Location = (usize, u64, u64);
+
+/// The line table of a Wasm module.
+struct WasmLines {
+    /// The paths of the source files.
+    files: Vec<String>,
+    /// The rows of the table, sorted by address. The rows without
+    /// location end a sequence.
+    rows: Vec<(u64, Option<Location>)>,
+}
+
+impl WasmLines {
+    /// Reads the line table of `module`, if it has one.
+    fn read(module: &ModuleInfo) -> Result<Option<Self>, gimli::Error> {
+        let section = |name: &str| {
+            module
+                .custom_sections
+                .get(name)
+                .map(|index| &*module.custom_sections_data[*index])
+        };
+        if section(".debug_line").is_none() {
+            return Ok(None);
+        }
+        let dwarf = gimli::Dwarf::load(|id| -> Result<_, gimli::Error> {
+            Ok(EndianSlice::new(
+                section(id.name()).unwrap_or(&[]),
+                LittleEndian,
+            ))
+        })?;
+
+        let mut files = Vec::new();
+        let mut file_indexes = HashMap::new();
+        let mut rows = Vec::new();
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            let program = match unit.line_program.clone() {
+                Some(program) => program,
+                None => continue,
+            };
+
+            let mut program_rows = program.rows();
+            while let Some((header, row)) = program_rows.next_row()? {
+                let (line, file) = match (row.line(), row.file(header)) {
+                    (Some(line), Some(file)) if !row.end_sequence() => (line.get(), file),
+                    _ => {
+                        rows.push((row.address(), None));
+                        continue;
+                    }
+                };
+
+                let mut path = dwarf
+                    .attr_string(&unit, file.path_name())?
+                    .to_string_lossy()
+                    .into_owned();
+                if path.is_empty() {
+                    path = "<unknown>".to_string();
+                }
+                if let Some(directory) = file.directory(header) {
+                    let directory = dwarf.attr_string(&unit, directory)?.to_string_lossy();
+                    if !directory.is_empty() && !path.starts_with('/') {
+                        path = format!("{}/{}", directory, path);
+                    }
+                }
+                let file_index = match file_indexes.get(&path) {
+                    Some(file_index) => *file_index,
+                    None => {
+                        file_indexes.insert(path.clone(), files.len());
+                        files.push(path);
+                        files.len() - 1
+                    }
+                };
+                let column = match row.column() {
+                    gimli::ColumnType::LeftEdge => 0,
+                    gimli::ColumnType::Column(column) => column.get(),
+                };
+                rows.push((row.address(), Some((file_index, line, column))));
+            }
+        }
+
+        // A sequence may start where another one ends.
+        rows.sort_by_key(|(address, location)| (*address, location.is_some()));
+        Ok(Some(Self { files, rows }))
+    }
+
+    /// The location of the instruction at `address` in the code section.
+    fn location(&self, address: u64) -> Option<Location> {
+        match self
+            .rows
+            .partition_point(|(row_address, _)| *row_address <= address)
+        {
+            0 => None,
+            next => self.rows[next - 1].1,
+        }
+    }
+}
+
+/// Generates the DWARF sections describing `functions`, compiled from
+/// `module` in the `text_size` bytes at `text_address`, if the module
+/// has DWARF line info.
+pub fn debug_sections(
+    module: &ModuleInfo,
+    functions: &[DebugFunction],
+    text_address: u64,
+    text_size: u64,
+) -> Vec<(&'static str, Vec<u8>)> {
+    let lines = match WasmLines::read(module) {
+        Ok(Some(lines)) => lines,
+        _ => return Vec::new(),
+    };
+
+    let encoding = gimli::Encoding {
+        format: gimli::Format::Dwarf32,
+        version: 4,
+        address_size: 8,
+    };
+    let mut program = LineProgram::new(
+        encoding,
+        gimli::LineEncoding::default(),
+        LineString::String(Vec::new()),
+        LineString::String(module.name().into_bytes()),
+        None,
+    );
+    let directory = program.default_directory();
+    let file_ids = lines
+        .files
+        .iter()
+        .map(|path| {
+            program.add_file(
+                LineString::String(path.clone().into_bytes()),
+                directory,
+                None,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    for function in functions {
+        program.begin_sequence(Some(Address::Constant(function.address)));
+        let mut previous = None;
+        // The instructions are sorted by their offset in the code.
+        for instruction in &function.address_map.instructions {
+            if instruction.srcloc.is_default() {
+                continue;
+            }
+            let address = match instruction
+                .srcloc
+                .bits()
+                .checked_sub(module.code_section_offset)
+            {
+                Some(address) => address as u64,
+                None => continue,
+            };
+            let location = match lines.location(address) {
+                Some(location) if previous != Some(location) => location,
+                _ => continue,
+            };
+            previous = Some(location);
+
+            let (file, line, column) = location;
+            let row = program.row();
+            row.address_offset = instruction.code_offset as u64;
+            row.file = file_ids[file];
+            row.line = line;
+            row.column = column;
+            program.generate_row();
+        }
+        program.end_sequence(function.size);
+    }
+
+    let mut dwarf = DwarfUnit::new(encoding);
+    dwarf.unit.line_program = program;
+    let root = dwarf.unit.root();
+    let entry = dwarf.unit.get_mut(root);
+    entry.set(
+        gimli::DW_AT_name,
+        AttributeValue::String(module.name().into_bytes()),
+    );
+    entry.set(
+        gimli::DW_AT_producer,
+        AttributeValue::String(b"wasmer".to_vec()),
+    );
+    entry.set(
+        gimli::DW_AT_low_pc,
+        AttributeValue::Address(Address::Constant(text_address)),
+    );
+    entry.set(gimli::DW_AT_high_pc, AttributeValue::Udata(text_size));
+    for function in functions {
+        let id = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+        let entry = dwarf.unit.get_mut(id);
+        entry.set(
+            gimli::DW_AT_name,
+            AttributeValue::String(function.name.clone().into_bytes()),
+        );
+        entry.set(
+            gimli::DW_AT_low_pc,
+            AttributeValue::Address(Address::Constant(function.address)),
+        );
+        entry.set(gimli::DW_AT_high_pc, AttributeValue::Udata(function.size));
+    }
+
+    let endian = if cfg!(target_endian = "little") {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    };
+    let mut sections = gimli::write::Sections::new(EndianVec::new(endian));
+    if dwarf.write(&mut sections).is_err() {
+        return Vec::new();
+    }
+    let mut debug_sections = Vec::new();
+    let _ = sections.for_each(|id, data| -> Result<(), ()> {
+        if !data.slice().is_empty() {
+            debug_sections.push((id.name(), data.slice().to_vec()));
+        }
+        Ok(())
+    });
+    debug_sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gimli::write::Sections;
+    use wasmer_types::{InstructionAddressMap, SourceLoc};
+
+    /// The offset of the code section in the module.
+    const CODE_SECTION_OFFSET: u32 = 0x100;
+
+    /// Generates the DWARF sections of a Wasm module whose instructions
+    /// at `address` in the code section come from `line` of `main.c`.
+    fn wasm_dwarf(rows: &[(u64, u64)], end: u64) -> Vec<(&'static str, Vec<u8>)> {
+        let encoding = gimli::Encoding {
+            format: gimli::Format::Dwarf32,
+            version: 4,
+            address_size: 4,
+        };
+        let mut program = LineProgram::new(
+            encoding,
+            gimli::LineEncoding::default(),
+            LineString::String(b"/src".to_vec()),
+            LineString::String(b"main.c".to_vec()),
+            None,
+        );
+        let directory = program.default_directory();
+        let file = program.add_file(LineString::String(b"main.c".to_vec()), directory, None);
+        program.begin_sequence(Some(Address::Constant(rows[0].0)));
+        for (address, line) in rows {
+            let row = program.row();
+            row.address_offset = address - rows[0].0;
+            row.file = file;
+            row.line = *line;
+            program.generate_row();
+        }
+        program.end_sequence(end - rows[0].0);
+
+        let mut dwarf = DwarfUnit::new(encoding);
+        dwarf.unit.line_program = program;
+        let mut sections = Sections::new(EndianVec::new(LittleEndian));
+        dwarf.write(&mut sections).unwrap();
+        let mut wasm_sections = Vec::new();
+        sections
+            .for_each(|id, data| -> Result<(), ()> {
+                wasm_sections.push((id.name(), data.slice().to_vec()));
+                Ok(())
+            })
+            .unwrap();
+        wasm_sections
+    }
+
+    fn module_with_sections(sections: Vec<(&'static str, Vec<u8>)>) -> ModuleInfo {
+        let mut module = ModuleInfo::new();
+        module.code_section_offset = CODE_SECTION_OFFSET;
+        for (name, data) in sections {
+            let index = module.custom_sections_data.push(data.into_boxed_slice());
+            module.custom_sections.insert(name.to_string(), index);
+        }
+        module
+    }
+
+    /// An instruction at `address` in the code section, compiled at
+    /// `code_offset` in its function.
+    fn instruction(address: u32, code_offset: usize) -> InstructionAddressMap {
+        InstructionAddressMap {
+            srcloc: SourceLoc::new(CODE_SECTION_OFFSET + address),
+            code_offset,
+            code_len: 4,
+        }
+    }
+
+    /// Reads the rows of the line programs of `sections`, as the
+    /// address, the path of the file and the line, or `None` at the end
+    /// of a sequence.
+    fn read_rows(sections: &[(&'static str, Vec<u8>)]) -> Vec<(u64, Option<(String, u64)>)> {
+        let dwarf = gimli::Dwarf::load(|id| -> Result<_, gimli::Error> {
+            let data = sections
+                .iter()
+                .find(|(name, _)| *name == id.name())
+                .map_or(&[][..], |(_, data)| &data[..]);
+            Ok(EndianSlice::new(data, RunTimeEndian::default()))
+        })
+        .unwrap();
+
+        let mut rows = Vec::new();
+        let mut units = dwarf.units();
+        while let Some(header) = units.next().unwrap() {
+            let unit = dwarf.unit(header).unwrap();
+            let program = unit.line_program.clone().unwrap();
+            let mut program_rows = program.rows();
+            while let Some((header, row)) = program_rows.next_row().unwrap() {
+                if row.end_sequence() {
+                    rows.push((row.address(), None));
+                    continue;
+                }
+                let file = row.file(header).unwrap();
+                let path = dwarf.attr_string(&unit, file.path_name()).unwrap();
+                rows.push((
+                    row.address(),
+                    Some((
+                        path.to_string_lossy().into_owned(),
+                        row.line().unwrap().get(),
+                    )),
+                ));
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn lines_are_mapped_to_the_compiled_code() {
+        let module = module_with_sections(wasm_dwarf(&[(0x10, 3), (0x14, 4), (0x18, 7)], 0x20));
+        let address_map = FunctionAddressMap {
+            instructions: vec![
+                instruction(0x10, 0),
+                // On the same line as the previous instruction.
+                instruction(0x12, 4),
+                instruction(0x14, 8),
+                instruction(0x18, 16),
+            ],
+            ..Default::default()
+        };
+        let functions = [DebugFunction {
+            name: "f".to_string(),
+            address: 0x1000,
+            size: 0x20,
+            address_map: &address_map,
+        }];
+
+        let sections = debug_sections(&module, &functions, 0x1000, 0x20);
+        let line = |line| Some(("main.c".to_string(), line));
+        assert_eq!(
+            read_rows(&sections),
+            vec![
+                (0x1000, line(3)),
+                (0x1008, line(4)),
+                (0x1010, line(7)),
+                (0x1020, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn instructions_without_lines_are_skipped() {
+        let module = module_with_sections(wasm_dwarf(&[(0x10, 3)], 0x14));
+        let address_map = FunctionAddressMap {
+            instructions: vec![
+                InstructionAddressMap {
+                    srcloc: SourceLoc::default(),
+                    code_offset: 0,
+                    code_len: 4,
+                },
+                instruction(0x10, 4),
+                // Past the end of the sequence.
+                instruction(0x14, 8),
+            ],
+            ..Default::default()
+        };
+        let functions = [DebugFunction {
+            name: "f".to_string(),
+            address: 0x1000,
+            size: 0x10,
+            address_map: &address_map,
+        }];
+
+        let sections = debug_sections(&module, &functions, 0x1000, 0x10);
+        assert_eq!(
+            read_rows(&sections),
+            vec![(0x1004, Some(("main.c".to_string(), 3))), (0x1010, None),]
+        );
+    }
+
+    #[test]
+    fn modules_without_lines_have_no_sections() {
+        let address_map = FunctionAddressMap::default();
+        let functions = [DebugFunction {
+            name: "f".to_string(),
+            address: 0x1000,
+            size: 0x10,
+            address_map: &address_map,
+        }];
+        assert!(debug_sections(&ModuleInfo::new(), &functions, 0x1000, 0x10).is_empty());
+    }
+}
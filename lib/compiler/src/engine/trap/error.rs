@@ -3,6 +3,7 @@ use backtrace::Backtrace;
 use std::error::Error;
 use std::fmt;
 use std::sync::Arc;
+use wasmer_vm::metrics::{self, Counter};
 use wasmer_vm::{Trap, TrapCode};
 
 /// A struct representing an aborted instruction execution, with a message
@@ -44,6 +45,8 @@ struct RuntimeErrorInner {
     source: RuntimeErrorSource,
     /// The reconstructed Wasm trace (from the native trace and the `GlobalFrameInfo`).
     wasm_trace: Vec<FrameInfo>,
+    /// The positions of the frames of the Wasm trace in the native trace.
+    wasm_frame_positions: Vec<usize>,
     /// The native backtrace
     native_trace: Option<Backtrace>,
 }
@@ -103,13 +106,17 @@ impl RuntimeError {
                     .map_or(signal_trap.unwrap_or(TrapCode::StackOverflow), |info| {
                         info.trap_code
                     });
+                metrics::increment_counter(Counter::Traps(code));
                 Self::new_with_trace(&info, Some(pc), RuntimeErrorSource::Trap(code), backtrace)
             }
             // A trap triggered manually from the Wasmer runtime
             Trap::Lib {
                 trap_code,
                 backtrace,
-            } => Self::new_with_trace(&info, None, RuntimeErrorSource::Trap(trap_code), backtrace),
+            } => {
+                metrics::increment_counter(Counter::Traps(trap_code));
+                Self::new_with_trace(&info, None, RuntimeErrorSource::Trap(trap_code), backtrace)
+            }
         }
     }
 
@@ -162,35 +169,35 @@ impl RuntimeError {
         native_trace: Backtrace,
     ) -> Self {
         // Let's construct the trace
-        let wasm_trace = native_trace
-            .frames()
-            .iter()
-            .filter_map(|frame| {
-                let pc = frame.ip() as usize;
-                if pc == 0 {
-                    None
-                } else {
-                    // Note that we need to be careful about the pc we pass in here to
-                    // lookup frame information. This program counter is used to
-                    // translate back to an original source location in the origin wasm
-                    // module. If this pc is the exact pc that the trap happened at,
-                    // then we look up that pc precisely. Otherwise backtrace
-                    // information typically points at the pc *after* the call
-                    // instruction (because otherwise it's likely a call instruction on
-                    // the stack). In that case we want to lookup information for the
-                    // previous instruction (the call instruction) so we subtract one as
-                    // the lookup.
-                    let pc_to_lookup = if Some(pc) == trap_pc { pc } else { pc - 1 };
-                    Some(pc_to_lookup)
-                }
-            })
-            .filter_map(|pc| info.lookup_frame_info(pc))
-            .collect::<Vec<_>>();
+        let mut wasm_trace = Vec::new();
+        let mut wasm_frame_positions = Vec::new();
+        for (position, frame) in native_trace.frames().iter().enumerate() {
+            let pc = frame.ip() as usize;
+            if pc == 0 {
+                continue;
+            }
+            // Note that we need to be careful about the pc we pass in here to
+            // lookup frame information. This program counter is used to
+            // translate back to an original source location in the origin wasm
+            // module. If this pc is the exact pc that the trap happened at,
+            // then we look up that pc precisely. Otherwise backtrace
+            // information typically points at the pc *after* the call
+            // instruction (because otherwise it's likely a call instruction on
+            // the stack). In that case we want to lookup information for the
+            // previous instruction (the call instruction) so we subtract one as
+            // the lookup.
+            let pc_to_lookup = if Some(pc) == trap_pc { pc } else { pc - 1 };
+            if let Some(frame_info) = info.lookup_frame_info(pc_to_lookup) {
+                wasm_trace.push(frame_info);
+                wasm_frame_positions.push(position);
+            }
+        }
 
         Self {
             inner: Arc::new(RuntimeErrorInner {
                 source,
                 wasm_trace,
+                wasm_frame_positions,
                 native_trace: Some(native_trace),
             }),
         }
@@ -207,6 +214,58 @@ impl RuntimeError {
         &self.inner.wasm_trace
     }
 
+    /// Returns the frames that led to this trap happening, from the
+    /// innermost one: the frames in WebAssembly code, as returned by
+    /// [`RuntimeError::trace`], and the frames of the host functions
+    /// called by them.
+    ///
+    /// The host frames are symbolized from the native backtrace, which
+    /// is slow. The frames of the runtime and of the standard library
+    /// are left out, as are the frames calling the outermost
+    /// WebAssembly frame.
+    pub fn backtrace(&self) -> Vec<BacktraceFrame> {
+        let (native_trace, outermost) = match (
+            &self.inner.native_trace,
+            self.inner.wasm_frame_positions.last(),
+        ) {
+            (Some(native_trace), Some(outermost)) => (native_trace, *outermost),
+            _ => return Vec::new(),
+        };
+        let mut native_trace = native_trace.clone();
+        native_trace.resolve();
+
+        let mut wasm_frames = self
+            .inner
+            .wasm_frame_positions
+            .iter()
+            .zip(self.inner.wasm_trace.iter())
+            .peekable();
+        let mut frames = Vec::new();
+        for (position, frame) in native_trace.frames()[..=outermost].iter().enumerate() {
+            if let Some((_, wasm_frame)) = wasm_frames.next_if(|(p, _)| **p == position) {
+                frames.push(BacktraceFrame::Wasm(wasm_frame.clone()));
+                continue;
+            }
+            // The functions inlined in the frame come first, the
+            // function of the frame last.
+            let symbols = frame.symbols();
+            for (index, symbol) in symbols.iter().enumerate() {
+                let name = match symbol.name() {
+                    Some(name) => format!("{:#}", name),
+                    None => continue,
+                };
+                if is_runtime_symbol(&name) {
+                    continue;
+                }
+                frames.push(BacktraceFrame::Host {
+                    name,
+                    inlined: index + 1 < symbols.len(),
+                });
+            }
+        }
+        frames
+    }
+
     /// Attempts to downcast the `RuntimeError` to a concrete type.
     pub fn downcast<T: Error + 'static>(self) -> Result<T, Self> {
         match Arc::try_unwrap(self.inner) {
@@ -270,29 +329,85 @@ impl fmt::Display for RuntimeError {
             return Ok(());
         }
         for frame in self.trace().iter() {
-            let name = frame.module_name();
-            let func_index = frame.func_index();
             writeln!(f)?;
             write!(f, "    at ")?;
-            match frame.function_name() {
-                Some(name) => match rustc_demangle::try_demangle(name) {
-                    Ok(name) => write!(f, "{}", name)?,
-                    Err(_) => write!(f, "{}", name)?,
-                },
-                None => write!(f, "<unnamed>")?,
-            }
-            write!(
-                f,
-                " ({}[{}]:0x{:x})",
-                name,
-                func_index,
-                frame.module_offset()
-            )?;
+            write_wasm_frame(f, frame)?;
         }
         Ok(())
     }
 }
 
+/// A frame of the backtrace of a [`RuntimeError`], see
+/// [`RuntimeError::backtrace`].
+#[derive(Debug, Clone)]
+pub enum BacktraceFrame {
+    /// A frame in WebAssembly code.
+    Wasm(FrameInfo),
+    /// A frame of a host function.
+    Host {
+        /// The demangled symbol of the function.
+        name: String,
+        /// Whether the function has been inlined in its caller, so it
+        /// shares its native frame.
+        inlined: bool,
+    },
+}
+
+impl fmt::Display for BacktraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wasm(frame) => write_wasm_frame(f, frame),
+            Self::Host {
+                name,
+                inlined: false,
+            } => write!(f, "{} (host)", name),
+            Self::Host {
+                name,
+                inlined: true,
+            } => write!(f, "{} (inlined host)", name),
+        }
+    }
+}
+
+fn write_wasm_frame(f: &mut fmt::Formatter<'_>, frame: &FrameInfo) -> fmt::Result {
+    match frame.function_name() {
+        Some(name) => match rustc_demangle::try_demangle(name) {
+            Ok(name) => write!(f, "{}", name)?,
+            Err(_) => write!(f, "{}", name)?,
+        },
+        None => write!(f, "<unnamed>")?,
+    }
+    write!(
+        f,
+        " ({}[{}]:0x{:x})",
+        frame.module_name(),
+        frame.func_index(),
+        frame.module_offset()
+    )
+}
+
+/// The prefixes of the symbols of the runtime and of the standard
+/// library, which aren't part of the host frames.
+const RUNTIME_SYMBOL_PREFIXES: &[&str] = &[
+    "wasmer::",
+    "wasmer_vm::",
+    "wasmer_compiler::",
+    "wasmer_types::",
+    "backtrace::",
+    "corosensei::",
+    "std::",
+    "core::",
+    "alloc::",
+    "__rust",
+];
+
+fn is_runtime_symbol(name: &str) -> bool {
+    let name = name.trim_start_matches('<');
+    RUNTIME_SYMBOL_PREFIXES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
 impl std::error::Error for RuntimeError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match &self.inner.source {
@@ -13,12 +13,15 @@
 //! ```
 use std::cmp;
 use std::collections::BTreeMap;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{CompiledFunctionFrameInfo, SourceLoc, TrapInformation};
 use wasmer_types::{LocalFunctionIndex, ModuleInfo};
 use wasmer_vm::FunctionBodyPtr;
 
+use super::gdb_jit::{self, GdbJitRegistration};
+use super::perf_map;
+
 lazy_static::lazy_static! {
     /// This is a global cache of backtrace frame information for all active
     ///
@@ -48,21 +51,19 @@ pub struct GlobalFrameInfoRegistration {
     /// The key that will be removed from the global `ranges` map when this is
     /// dropped.
     key: usize,
+    /// The registration of the symbols of the module with the
+    /// debuggers attached to the process.
+    _gdb_jit: Option<GdbJitRegistration>,
 }
 
 #[derive(Debug)]
 struct ModuleInfoFrameInfo {
     start: usize,
     functions: BTreeMap<usize, FunctionInfo>,
-    module: ModuleInfo,
-    frame_infos: PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>,
+    module: Arc<ModuleInfo>,
 }
 
 impl ModuleInfoFrameInfo {
-    fn function_debug_info(&self, local_index: LocalFunctionIndex) -> &CompiledFunctionFrameInfo {
-        self.frame_infos.get(local_index).unwrap()
-    }
-
     /// Gets a function given a pc
     fn function_info(&self, pc: usize) -> Option<&FunctionInfo> {
         let (end, func) = self.functions.range(pc..).next()?;
@@ -78,6 +79,7 @@ impl ModuleInfoFrameInfo {
 struct FunctionInfo {
     start: usize,
     local_index: LocalFunctionIndex,
+    frame_info: CompiledFunctionFrameInfo,
 }
 
 impl GlobalFrameInfo {
@@ -93,7 +95,7 @@ impl GlobalFrameInfo {
         // machine instruction that corresponds to `pc`, which then allows us to
         // map that to a wasm original source location.
         let rel_pos = pc - func.start;
-        let instr_map = &module.function_debug_info(func.local_index).address_map;
+        let instr_map = &func.frame_info.address_map;
         let pos = match instr_map
             .instructions
             .binary_search_by_key(&rel_pos, |map| map.code_offset)
@@ -142,7 +144,7 @@ impl GlobalFrameInfo {
     pub fn lookup_trap_info(&self, pc: usize) -> Option<&TrapInformation> {
         let module = self.module_info(pc)?;
         let func = module.function_info(pc)?;
-        let traps = &module.function_debug_info(func.local_index).traps;
+        let traps = &func.frame_info.traps;
         let idx = traps
             .binary_search_by_key(&((pc - func.start) as u32), |info| info.code_offset)
             .ok()?;
@@ -191,30 +193,67 @@ pub fn register(
     finished_functions: &BoxedSlice<LocalFunctionIndex, FunctionExtent>,
     frame_infos: PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>,
 ) -> Option<GlobalFrameInfoRegistration> {
+    if finished_functions.is_empty() {
+        return None;
+    }
+    let functions = finished_functions
+        .iter()
+        .zip(frame_infos)
+        .map(|((local_index, extent), (_, frame_info))| (local_index, extent, frame_info))
+        .collect();
+    Some(register_functions(Arc::new(module), functions))
+}
+
+/// Registers the frame information of the function `local_index` of
+/// `module`, compiled on its first call, see [`register`].
+pub fn register_function(
+    module: Arc<ModuleInfo>,
+    local_index: LocalFunctionIndex,
+    extent: &FunctionExtent,
+    frame_info: CompiledFunctionFrameInfo,
+) -> GlobalFrameInfoRegistration {
+    register_functions(module, vec![(local_index, extent, frame_info)])
+}
+
+/// Registers the frame information of `functions`, which must not be
+/// empty.
+fn register_functions(
+    module: Arc<ModuleInfo>,
+    functions: Vec<(
+        LocalFunctionIndex,
+        &FunctionExtent,
+        CompiledFunctionFrameInfo,
+    )>,
+) -> GlobalFrameInfoRegistration {
     let mut min = usize::max_value();
     let mut max = 0;
-    let mut functions = BTreeMap::new();
-    for (
-        i,
-        FunctionExtent {
-            ptr: start,
-            length: len,
-        },
-    ) in finished_functions.iter()
-    {
-        let start = **start as usize;
+    for (_, extent, _) in functions.iter() {
+        let start = *extent.ptr as usize;
         // end is "last byte" of the function code
-        let end = start + len - 1;
+        let end = start + extent.length - 1;
         min = cmp::min(min, start);
         max = cmp::max(max, end);
+    }
+
+    let gdb_jit = gdb_jit::register(&module, &functions, min, max + 1);
+    perf_map::register(
+        &module,
+        functions
+            .iter()
+            .map(|(local_index, extent, _)| (*local_index, *extent)),
+    );
+
+    let mut function_infos = BTreeMap::new();
+    for (local_index, extent, frame_info) in functions {
+        let start = *extent.ptr as usize;
         let func = FunctionInfo {
             start,
-            local_index: i,
+            local_index,
+            frame_info,
         };
-        assert!(functions.insert(end, func).is_none());
-    }
-    if functions.is_empty() {
-        return None;
+        assert!(function_infos
+            .insert(start + extent.length - 1, func)
+            .is_none());
     }
 
     let mut info = FRAME_INFO.write().unwrap();
@@ -232,13 +271,25 @@ pub fn register(
         max,
         ModuleInfoFrameInfo {
             start: min,
-            functions,
+            functions: function_infos,
             module,
-            frame_infos,
         },
     );
     assert!(prev.is_none());
-    Some(GlobalFrameInfoRegistration { key: max })
+    GlobalFrameInfoRegistration {
+        key: max,
+        _gdb_jit: gdb_jit,
+    }
+}
+
+/// The name of a function in the symbols given to the debuggers and
+/// profilers: its name in the module, or its index.
+pub(super) fn function_symbol_name(module: &ModuleInfo, local_index: LocalFunctionIndex) -> String {
+    let func_index = module.func_index(local_index);
+    match module.function_names.get(&func_index) {
+        Some(name) => name.clone(),
+        None => format!("{}[{}]", module.name(), func_index.index()),
+    }
 }
 
 /// Description of a frame in a backtrace for a [`RuntimeError::trace`](crate::RuntimeError::trace).
@@ -0,0 +1,415 @@
+//! Registration of the compiled functions with the GDB JIT interface,
+//! so that the debuggers attached to the process, like gdb and lldb,
+//! know the names of the Wasm functions in the backtraces.
+//!
+//! The debuggers set a breakpoint in `__jit_debug_register_code`, and
+//! read the symbol files registered in `__jit_debug_descriptor` when it
+//! is called. Each module is described by a small ELF file, holding a
+//! symbol for each of its functions at the address of its code, and,
+//! when the module has DWARF line info, the lines of the sources of
+//! the functions (see the `debug_lines` module).
+//!
+//! See <https://sourceware.org/gdb/onlinedocs/gdb/JIT-Interface.html>.
+
+use std::ptr;
+use std::sync::Mutex;
+use wasmer_types::{CompiledFunctionFrameInfo, LocalFunctionIndex, ModuleInfo};
+
+use super::debug_lines::{self, DebugFunction};
+use super::frame_info::{function_symbol_name, FunctionExtent};
+
+const JIT_NOACTION: u32 = 0;
+const JIT_REGISTER_FN: u32 = 1;
+const JIT_UNREGISTER_FN: u32 = 2;
+
+/// A registered symbol file.
+#[repr(C)]
+struct JitCodeEntry {
+    next_entry: *mut JitCodeEntry,
+    prev_entry: *mut JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+/// The descriptor of the registered symbol files.
+#[repr(C)]
+pub struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JitCodeEntry,
+    first_entry: *mut JitCodeEntry,
+}
+
+/// The list of the symbol files, read by the debuggers.
+#[no_mangle]
+pub static mut __jit_debug_descriptor: JitDescriptor = JitDescriptor {
+    version: 1,
+    action_flag: JIT_NOACTION,
+    relevant_entry: ptr::null_mut(),
+    first_entry: ptr::null_mut(),
+};
+
+/// The function in which the debuggers set a breakpoint, to be
+/// notified when `__jit_debug_descriptor` changes.
+#[no_mangle]
+#[inline(never)]
+pub extern "C" fn __jit_debug_register_code() {
+    // Keep the function from being optimized away, or merged with
+    // another empty function.
+    unsafe {
+        ptr::read_volatile(ptr::addr_of!(__jit_debug_descriptor.action_flag));
+    }
+}
+
+lazy_static::lazy_static! {
+    /// Serializes the changes of `__jit_debug_descriptor`.
+    static ref JIT_DEBUG_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// An RAII structure used to unregister the symbol file of a module
+/// from the debuggers when the module is destroyed.
+pub struct GdbJitRegistration {
+    entry: *mut JitCodeEntry,
+    _image: Box<[u8]>,
+}
+
+// The entry is only accessed with `JIT_DEBUG_LOCK` held.
+unsafe impl Send for GdbJitRegistration {}
+unsafe impl Sync for GdbJitRegistration {}
+
+/// Registers the symbol file of a module, whose functions lie in the
+/// `start..end` range.
+///
+/// Returns `None` on the targets for which symbol files aren't
+/// generated.
+pub fn register(
+    module: &ModuleInfo,
+    functions: &[(
+        LocalFunctionIndex,
+        &FunctionExtent,
+        CompiledFunctionFrameInfo,
+    )],
+    start: usize,
+    end: usize,
+) -> Option<GdbJitRegistration> {
+    let functions = functions
+        .iter()
+        .map(|(local_index, extent, frame_info)| DebugFunction {
+            name: function_symbol_name(module, *local_index),
+            address: *extent.ptr as u64,
+            size: extent.length as u64,
+            address_map: &frame_info.address_map,
+        })
+        .collect::<Vec<_>>();
+    let symbols = functions
+        .iter()
+        .map(|function| (function.name.clone(), function.address, function.size))
+        .collect::<Vec<_>>();
+    let (text_address, text_size) = (start as u64, (end - start) as u64);
+    let debug_sections = debug_lines::debug_sections(module, &functions, text_address, text_size);
+    let image = elf_image(text_address, text_size, &symbols, &debug_sections)?.into_boxed_slice();
+
+    let entry = Box::into_raw(Box::new(JitCodeEntry {
+        next_entry: ptr::null_mut(),
+        prev_entry: ptr::null_mut(),
+        symfile_addr: image.as_ptr(),
+        symfile_size: image.len() as u64,
+    }));
+
+    let _lock = JIT_DEBUG_LOCK.lock().unwrap();
+    unsafe {
+        let first = __jit_debug_descriptor.first_entry;
+        (*entry).next_entry = first;
+        if !first.is_null() {
+            (*first).prev_entry = entry;
+        }
+        __jit_debug_descriptor.first_entry = entry;
+        notify(entry, JIT_REGISTER_FN);
+    }
+
+    Some(GdbJitRegistration {
+        entry,
+        _image: image,
+    })
+}
+
+impl Drop for GdbJitRegistration {
+    fn drop(&mut self) {
+        let _lock = JIT_DEBUG_LOCK.lock().unwrap();
+        unsafe {
+            let JitCodeEntry {
+                next_entry,
+                prev_entry,
+                ..
+            } = *self.entry;
+            if prev_entry.is_null() {
+                __jit_debug_descriptor.first_entry = next_entry;
+            } else {
+                (*prev_entry).next_entry = next_entry;
+            }
+            if !next_entry.is_null() {
+                (*next_entry).prev_entry = prev_entry;
+            }
+            notify(self.entry, JIT_UNREGISTER_FN);
+            drop(Box::from_raw(self.entry));
+        }
+    }
+}
+
+/// Tells the debuggers that `entry` has been registered or
+/// unregistered.
+///
+/// # Safety
+///
+/// `JIT_DEBUG_LOCK` must be held.
+unsafe fn notify(entry: *mut JitCodeEntry, action: u32) {
+    __jit_debug_descriptor.relevant_entry = entry;
+    __jit_debug_descriptor.action_flag = action;
+    __jit_debug_register_code();
+    __jit_debug_descriptor.action_flag = JIT_NOACTION;
+    __jit_debug_descriptor.relevant_entry = ptr::null_mut();
+}
+
+/// A header of an ELF64 section.
+#[derive(Default)]
+struct SectionHeader {
+    name: u32,
+    ty: u32,
+    flags: u64,
+    address: u64,
+    offset: usize,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entry_size: u64,
+}
+
+impl SectionHeader {
+    fn write(&self, image: &mut Vec<u8>) {
+        image.extend_from_slice(&self.name.to_ne_bytes());
+        image.extend_from_slice(&self.ty.to_ne_bytes());
+        image.extend_from_slice(&self.flags.to_ne_bytes());
+        image.extend_from_slice(&self.address.to_ne_bytes());
+        image.extend_from_slice(&(self.offset as u64).to_ne_bytes());
+        image.extend_from_slice(&self.size.to_ne_bytes());
+        image.extend_from_slice(&self.link.to_ne_bytes());
+        image.extend_from_slice(&self.info.to_ne_bytes());
+        image.extend_from_slice(&self.align.to_ne_bytes());
+        image.extend_from_slice(&self.entry_size.to_ne_bytes());
+    }
+}
+
+/// The ELF machine of the host.
+fn elf_machine() -> Option<u16> {
+    if cfg!(target_arch = "x86_64") {
+        Some(62)
+    } else if cfg!(target_arch = "aarch64") {
+        Some(183)
+    } else if cfg!(target_arch = "riscv64") {
+        Some(243)
+    } else {
+        None
+    }
+}
+
+/// Generates a 64-bit ELF file with a `.text` section without data
+/// covering the `text_size` bytes at `text_address`, a function symbol
+/// for each `(name, address, size)`, and the given DWARF sections.
+fn elf_image(
+    text_address: u64,
+    text_size: u64,
+    symbols: &[(String, u64, u64)],
+    debug_sections: &[(&str, Vec<u8>)],
+) -> Option<Vec<u8>> {
+    const EHDR_SIZE: usize = 64;
+    const SHDR_SIZE: usize = 64;
+    const SYM_SIZE: usize = 24;
+    const SHT_PROGBITS: u32 = 1;
+    const SHT_SYMTAB: u32 = 2;
+    const SHT_STRTAB: u32 = 3;
+    const SHT_NOBITS: u32 = 8;
+    const SHF_ALLOC: u64 = 0x2;
+    const SHF_EXECINSTR: u64 = 0x4;
+    const TEXT_SECTION: u16 = 1;
+    const STRTAB_SECTION: u32 = 3;
+
+    let machine = elf_machine()?;
+
+    let mut strtab = vec![0];
+    let mut symtab = vec![0; SYM_SIZE];
+    for (name, address, size) in symbols {
+        symtab.extend_from_slice(&(strtab.len() as u32).to_ne_bytes());
+        // STB_GLOBAL, STT_FUNC
+        symtab.push((1 << 4) | 2);
+        symtab.push(0);
+        symtab.extend_from_slice(&TEXT_SECTION.to_ne_bytes());
+        symtab.extend_from_slice(&address.to_ne_bytes());
+        symtab.extend_from_slice(&size.to_ne_bytes());
+        strtab.extend_from_slice(name.as_bytes());
+        strtab.push(0);
+    }
+
+    let mut shstrtab = vec![0];
+    let mut section_name = |name: &str| {
+        let offset = shstrtab.len() as u32;
+        shstrtab.extend_from_slice(name.as_bytes());
+        shstrtab.push(0);
+        offset
+    };
+
+    // The contents of the sections follow the ELF header, and the
+    // section headers follow them.
+    let mut offset = EHDR_SIZE;
+    let mut sections = vec![
+        SectionHeader::default(),
+        SectionHeader {
+            name: section_name(".text"),
+            ty: SHT_NOBITS,
+            flags: SHF_ALLOC | SHF_EXECINSTR,
+            address: text_address,
+            size: text_size,
+            align: 16,
+            ..Default::default()
+        },
+        // The symbols are all global, so the first one after the null
+        // symbol is the first non-local one.
+        SectionHeader {
+            name: section_name(".symtab"),
+            ty: SHT_SYMTAB,
+            offset,
+            size: symtab.len() as u64,
+            link: STRTAB_SECTION,
+            info: 1,
+            align: 8,
+            entry_size: SYM_SIZE as u64,
+            ..Default::default()
+        },
+    ];
+    offset += symtab.len();
+    sections.push(SectionHeader {
+        name: section_name(".strtab"),
+        ty: SHT_STRTAB,
+        offset,
+        size: strtab.len() as u64,
+        align: 1,
+        ..Default::default()
+    });
+    offset += strtab.len();
+    for (name, data) in debug_sections {
+        sections.push(SectionHeader {
+            name: section_name(name),
+            ty: SHT_PROGBITS,
+            offset,
+            size: data.len() as u64,
+            align: 1,
+            ..Default::default()
+        });
+        offset += data.len();
+    }
+    let shstrtab_name = section_name(".shstrtab");
+    sections.push(SectionHeader {
+        name: shstrtab_name,
+        ty: SHT_STRTAB,
+        offset,
+        size: shstrtab.len() as u64,
+        align: 1,
+        ..Default::default()
+    });
+    offset += shstrtab.len();
+    let shdrs_offset = (offset + 7) & !7;
+    sections[TEXT_SECTION as usize].offset = shdrs_offset;
+
+    let mut image = Vec::with_capacity(shdrs_offset + sections.len() * SHDR_SIZE);
+    image.extend_from_slice(b"\x7fELF");
+    // ELFCLASS64, the data encoding, EV_CURRENT, ELFOSABI_NONE
+    image.extend_from_slice(&[2, if cfg!(target_endian = "little") { 1 } else { 2 }, 1, 0]);
+    image.extend_from_slice(&[0; 8]);
+    // ET_EXEC
+    image.extend_from_slice(&2u16.to_ne_bytes());
+    image.extend_from_slice(&machine.to_ne_bytes());
+    image.extend_from_slice(&1u32.to_ne_bytes());
+    // The entry point and the program headers.
+    image.extend_from_slice(&0u64.to_ne_bytes());
+    image.extend_from_slice(&0u64.to_ne_bytes());
+    image.extend_from_slice(&(shdrs_offset as u64).to_ne_bytes());
+    image.extend_from_slice(&0u32.to_ne_bytes());
+    image.extend_from_slice(&(EHDR_SIZE as u16).to_ne_bytes());
+    image.extend_from_slice(&0u16.to_ne_bytes());
+    image.extend_from_slice(&0u16.to_ne_bytes());
+    image.extend_from_slice(&(SHDR_SIZE as u16).to_ne_bytes());
+    // The last section holds the names of the sections.
+    image.extend_from_slice(&(sections.len() as u16).to_ne_bytes());
+    image.extend_from_slice(&(sections.len() as u16 - 1).to_ne_bytes());
+
+    image.extend_from_slice(&symtab);
+    image.extend_from_slice(&strtab);
+    for (_, data) in debug_sections {
+        image.extend_from_slice(data);
+    }
+    image.extend_from_slice(&shstrtab);
+    image.resize(shdrs_offset, 0);
+
+    for section in &sections {
+        section.write(&mut image);
+    }
+
+    Some(image)
+}
+
+#[cfg(all(
+    test,
+    any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "riscv64"
+    )
+))]
+mod tests {
+    use super::elf_image;
+    use object::{Object, ObjectSection, ObjectSymbol, SectionKind, SymbolKind};
+
+    #[test]
+    fn elf_image_describes_the_functions() {
+        let symbols = [
+            ("f".to_string(), 0x1000, 0x20),
+            ("g".to_string(), 0x1020, 0x10),
+        ];
+        let debug_sections = [(".debug_line", vec![1, 2, 3])];
+        let image = elf_image(0x1000, 0x30, &symbols, &debug_sections).unwrap();
+
+        let file = object::File::parse(&*image).unwrap();
+        assert_eq!(file.format(), object::BinaryFormat::Elf);
+        assert!(file.is_64());
+
+        let text = file.section_by_name(".text").unwrap();
+        assert_eq!(text.kind(), SectionKind::UninitializedData);
+        assert_eq!((text.address(), text.size()), (0x1000, 0x30));
+
+        let symbols = file
+            .symbols()
+            // The null symbol is listed too.
+            .filter(|symbol| symbol.kind() != SymbolKind::Null)
+            .map(|symbol| {
+                assert_eq!(symbol.kind(), SymbolKind::Text);
+                assert!(symbol.is_global());
+                assert_eq!(symbol.section_index(), Some(text.index()));
+                (
+                    symbol.name().unwrap().to_string(),
+                    symbol.address(),
+                    symbol.size(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            symbols,
+            vec![
+                ("f".to_string(), 0x1000, 0x20),
+                ("g".to_string(), 0x1020, 0x10),
+            ]
+        );
+
+        let debug_line = file.section_by_name(".debug_line").unwrap();
+        assert_eq!(debug_line.data().unwrap(), &[1, 2, 3]);
+    }
+}
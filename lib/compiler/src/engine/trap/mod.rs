@@ -1,7 +1,11 @@
+mod debug_lines;
 mod error;
 mod frame_info;
-pub use error::RuntimeError;
+mod gdb_jit;
+mod perf_map;
+pub use error::{BacktraceFrame, RuntimeError};
 pub use frame_info::{
-    register as register_frame_info, FrameInfo, FunctionExtent, GlobalFrameInfoRegistration,
-    FRAME_INFO,
+    register as register_frame_info, register_function as register_function_frame_info,
+    FrameInfo, FunctionExtent, GlobalFrameInfoRegistration, FRAME_INFO,
 };
+pub use perf_map::enable_perf_map;
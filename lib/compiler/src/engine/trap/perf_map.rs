@@ -0,0 +1,124 @@
+//! Support of the perf map files, from which `perf report` reads the
+//! names of the JIT-compiled functions, so that the samples taken in
+//! the Wasm functions are attributed to them.
+//!
+//! The functions are appended to `/tmp/perf-<pid>.map` when they are
+//! registered, once enabled with [`enable_perf_map`] or by setting the
+//! `WASMER_PERF_MAP` environment variable to `1`. The file is only
+//! written on Linux.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use wasmer_types::{LocalFunctionIndex, ModuleInfo};
+
+use super::frame_info::FunctionExtent;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Writes the functions compiled from now on to the perf map file of
+/// the process, `/tmp/perf-<pid>.map`.
+///
+/// The entries of the functions aren't removed when their module is
+/// dropped, so the names of the functions compiled later at the same
+/// addresses may be wrong.
+pub fn enable_perf_map() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+#[cfg(target_os = "linux")]
+fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+        || std::env::var_os("WASMER_PERF_MAP").map_or(false, |value| value == "1")
+}
+
+/// Appends the functions of a module to the perf map file, if enabled.
+#[cfg(target_os = "linux")]
+pub fn register<'a>(
+    module: &ModuleInfo,
+    functions: impl Iterator<Item = (LocalFunctionIndex, &'a FunctionExtent)>,
+) {
+    use super::frame_info::function_symbol_name;
+    use std::fs::{File, OpenOptions};
+    use std::io::Write;
+    use std::sync::Mutex;
+
+    lazy_static::lazy_static! {
+        static ref PERF_MAP: Mutex<Option<File>> = Mutex::new(None);
+    }
+
+    if !is_enabled() {
+        return;
+    }
+
+    let mut perf_map = PERF_MAP.lock().unwrap();
+    if perf_map.is_none() {
+        let path = format!("/tmp/perf-{}.map", std::process::id());
+        match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => *perf_map = Some(file),
+            Err(_) => return,
+        }
+    }
+
+    // Each line is written at once, as the file may be shared with
+    // other JITs in the process.
+    let file = perf_map.as_mut().unwrap();
+    for (local_index, extent) in functions {
+        let line = perf_map_line(
+            *extent.ptr as usize,
+            extent.length,
+            &function_symbol_name(module, local_index),
+        );
+        let _ = file.write_all(line.as_bytes());
+    }
+}
+
+/// The line of the perf map file describing the function `name` in
+/// the `size` bytes at `address`: `<address> <size> <name>`, with the
+/// numbers in hexadecimal, without prefix.
+#[cfg(any(target_os = "linux", test))]
+fn perf_map_line(address: usize, size: usize, name: &str) -> String {
+    format!("{:x} {:x} {}\n", address, size, name)
+}
+
+/// Appends the functions of a module to the perf map file, if enabled.
+#[cfg(not(target_os = "linux"))]
+pub fn register<'a>(
+    _module: &ModuleInfo,
+    _functions: impl Iterator<Item = (LocalFunctionIndex, &'a FunctionExtent)>,
+) {
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::frame_info::function_symbol_name;
+    use super::perf_map_line;
+    use wasmer_types::entity::EntityRef;
+    use wasmer_types::{FunctionIndex, LocalFunctionIndex, ModuleInfo};
+
+    #[test]
+    fn lines_have_the_perf_map_format() {
+        assert_eq!(
+            perf_map_line(0x1234_5000, 0x2a, "wasm-function[3]"),
+            "12345000 2a wasm-function[3]\n"
+        );
+        assert_eq!(perf_map_line(0x1000, 0, "f"), "1000 0 f\n");
+    }
+
+    #[test]
+    fn functions_are_named_by_their_index_without_name() {
+        let mut module = ModuleInfo::new();
+        module.name = Some("app".to_string());
+        module.num_imported_functions = 1;
+        module
+            .function_names
+            .insert(FunctionIndex::new(2), "add".to_string());
+
+        assert_eq!(
+            function_symbol_name(&module, LocalFunctionIndex::new(0)),
+            "app[1]"
+        );
+        assert_eq!(
+            function_symbol_name(&module, LocalFunctionIndex::new(1)),
+            "add"
+        );
+    }
+}
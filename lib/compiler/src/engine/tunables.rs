@@ -5,7 +5,7 @@ use wasmer_types::{
     GlobalType, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex, MemoryType,
     ModuleInfo, Pages, PointerWidth, TableIndex, TableType, Target,
 };
-use wasmer_vm::{InternalStoreHandle, MemoryError, StoreObjects};
+use wasmer_vm::{InstancePool, InternalStoreHandle, MemoryError, StoreObjects};
 use wasmer_vm::{MemoryStyle, TableStyle};
 use wasmer_vm::{VMGlobal, VMMemory, VMTable};
 use wasmer_vm::{VMMemoryDefinition, VMTableDefinition};
@@ -51,6 +51,12 @@ pub trait Tunables {
         vm_definition_location: NonNull<VMTableDefinition>,
     ) -> Result<VMTable, String>;
 
+    /// The pool to allocate the instances and their memories in, if
+    /// any.
+    fn instance_pool(&self) -> Option<&InstancePool> {
+        None
+    }
+
     /// Create a global with an unset value.
     fn create_global(&self, ty: GlobalType) -> Result<VMGlobal, String> {
         Ok(VMGlobal::new(ty))
@@ -143,6 +149,36 @@ pub trait Tunables {
     }
 }
 
+/// The strategy used by [`BaseTunables`] to choose the [`MemoryStyle`]
+/// of a memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryStylePolicy {
+    /// Use a static memory when its maximum fits in the static memory
+    /// bound, and a dynamic memory otherwise.
+    Auto,
+
+    /// Always use static memories: the whole address space the memory
+    /// may grow into is reserved up front, followed by guard pages, and
+    /// no explicit bounds checks are emitted.
+    ///
+    /// Memories without a maximum reserve 4GiB of address space, which
+    /// is usually not available on 32-bit hosts.
+    Static,
+
+    /// Always use dynamic memories: only the memory in use is
+    /// reserved, and accesses are explicitly bounds-checked.
+    ///
+    /// This is the compact option for 32-bit hosts and
+    /// memory-constrained embedders.
+    Dynamic,
+}
+
+impl Default for MemoryStylePolicy {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
 /// Tunable parameters for WebAssembly compilation.
 /// This is the reference implementation of the `Tunables` trait,
 /// used by default.
@@ -161,9 +197,33 @@ pub struct BaseTunables {
 
     /// The size in bytes of the offset guard for dynamic heaps.
     pub dynamic_memory_offset_guard_size: u64,
+
+    /// The strategy used to choose between static and dynamic heaps,
+    /// see [`BaseTunables::with_memory_style_policy`].
+    memory_style_policy: MemoryStylePolicy,
+
+    /// The pool to allocate the instances and their memories in, if
+    /// any.
+    pub instance_pool: Option<InstancePool>,
 }
 
 impl BaseTunables {
+    /// Creates `BaseTunables` with the given bounds and guard sizes,
+    /// and the [`MemoryStylePolicy::Auto`] policy.
+    pub fn new(
+        static_memory_bound: Pages,
+        static_memory_offset_guard_size: u64,
+        dynamic_memory_offset_guard_size: u64,
+    ) -> Self {
+        Self {
+            static_memory_bound,
+            static_memory_offset_guard_size,
+            dynamic_memory_offset_guard_size,
+            memory_style_policy: MemoryStylePolicy::default(),
+            instance_pool: None,
+        }
+    }
+
     /// Get the `BaseTunables` for a specific Target
     pub fn for_target(target: &Target) -> Self {
         let triple = target.triple();
@@ -191,26 +251,51 @@ impl BaseTunables {
         #[cfg(not(target_os = "windows"))]
         let dynamic_memory_offset_guard_size: u64 = 0x1_0000;
 
-        Self {
+        Self::new(
             static_memory_bound,
             static_memory_offset_guard_size,
             dynamic_memory_offset_guard_size,
-        }
+        )
+    }
+
+    /// Get the strategy used to choose between static and dynamic heaps.
+    pub fn memory_style_policy(&self) -> MemoryStylePolicy {
+        self.memory_style_policy
+    }
+
+    /// Set the strategy used to choose between static and dynamic heaps.
+    pub fn with_memory_style_policy(mut self, memory_style_policy: MemoryStylePolicy) -> Self {
+        self.memory_style_policy = memory_style_policy;
+        self
+    }
+
+    /// Allocate the instances and their memories in `instance_pool`.
+    ///
+    /// The slots of the memories should fit the static memories, which
+    /// take `static_memory_bound` pages followed by
+    /// `static_memory_offset_guard_size` bytes.
+    pub fn with_instance_pool(mut self, instance_pool: InstancePool) -> Self {
+        self.instance_pool = Some(instance_pool);
+        self
     }
 }
 
 impl Tunables for BaseTunables {
     /// Get a `MemoryStyle` for the provided `MemoryType`
     fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
-        // A heap with a maximum that doesn't exceed the static memory bound specified by the
-        // tunables make it static.
-        //
         // If the module doesn't declare an explicit maximum treat it as 4GiB.
         let maximum = memory.maximum.unwrap_or_else(Pages::max_value);
-        if maximum <= self.static_memory_bound {
+        let is_static = match self.memory_style_policy {
+            // A heap with a maximum that doesn't exceed the static memory bound specified by the
+            // tunables make it static.
+            MemoryStylePolicy::Auto => maximum <= self.static_memory_bound,
+            MemoryStylePolicy::Static => true,
+            MemoryStylePolicy::Dynamic => false,
+        };
+        if is_static {
             MemoryStyle::Static {
                 // Bound can be larger than the maximum for performance reasons
-                bound: self.static_memory_bound,
+                bound: std::cmp::max(self.static_memory_bound, maximum),
                 offset_guard_size: self.static_memory_offset_guard_size,
             }
         } else {
@@ -245,7 +330,18 @@ impl Tunables for BaseTunables {
         style: &MemoryStyle,
         vm_definition_location: NonNull<VMMemoryDefinition>,
     ) -> Result<VMMemory, MemoryError> {
-        VMMemory::from_definition(ty, style, vm_definition_location)
+        match &self.instance_pool {
+            Some(pool) => {
+                VMMemory::from_definition_in_pool(ty, style, vm_definition_location, pool)
+            }
+            None => VMMemory::from_definition(ty, style, vm_definition_location),
+        }
+    }
+
+    /// The pool to allocate the instances and their memories in, if
+    /// any.
+    fn instance_pool(&self) -> Option<&InstancePool> {
+        self.instance_pool.as_ref()
     }
 
     /// Create a table owned by the host given a [`TableType`] and a [`TableStyle`].
@@ -308,6 +404,10 @@ impl Tunables for Box<dyn Tunables + Send + Sync> {
         self.as_ref()
             .create_vm_table(ty, style, vm_definition_location)
     }
+
+    fn instance_pool(&self) -> Option<&InstancePool> {
+        self.as_ref().instance_pool()
+    }
 }
 
 impl Tunables for std::sync::Arc<dyn Tunables + Send + Sync> {
@@ -350,4 +450,8 @@ impl Tunables for std::sync::Arc<dyn Tunables + Send + Sync> {
         self.as_ref()
             .create_vm_table(ty, style, vm_definition_location)
     }
+
+    fn instance_pool(&self) -> Option<&InstancePool> {
+        self.as_ref().instance_pool()
+    }
 }
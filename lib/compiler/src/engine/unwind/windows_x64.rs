@@ -2,14 +2,19 @@
 // Attributions: https://github.com/wasmerio/wasmer/blob/master/ATTRIBUTIONS.md
 
 //! Module for Windows x64 ABI unwind registry.
-use std::collections::HashMap;
-use wasmer_types::CompiledFunctionUnwindInfo;
+use wasmer_types::CompiledFunctionUnwindInfoReference;
 use winapi::um::winnt;
 
 /// Represents a registry of function unwind information for Windows x64 ABI.
+///
+/// All the functions of a code memory are published in a single
+/// function table, so that the system unwinder (used for stack
+/// walking and structured exception handling) can find them.
 pub struct UnwindRegistry {
-    // A hashmap mapping the baseaddress with the registered runtime functions
-    functions: HashMap<usize, Vec<winnt::RUNTIME_FUNCTION>>,
+    // The base address of the code memory the functions are relative to
+    base_address: Option<usize>,
+    // The registered runtime functions, relative to `base_address`
+    functions: Vec<winnt::RUNTIME_FUNCTION>,
     published: bool,
 }
 
@@ -17,7 +22,8 @@ impl UnwindRegistry {
     /// Creates a new unwind registry with the given base address.
     pub fn new() -> Self {
         Self {
-            functions: HashMap::new(),
+            base_address: None,
+            functions: Vec::new(),
             published: false,
         }
     }
@@ -28,17 +34,25 @@ impl UnwindRegistry {
         base_address: usize,
         func_start: u32,
         func_len: u32,
-        info: &CompiledFunctionUnwindInfo,
+        info: CompiledFunctionUnwindInfoReference<'_>,
     ) -> Result<(), String> {
         if self.published {
             return Err("unwind registry has already been published".to_string());
         }
 
         match info {
-            CompiledFunctionUnwindInfo::WindowsX64(_) => {}
+            CompiledFunctionUnwindInfoReference::WindowsX64(_) => {}
             _ => return Err("unsupported unwind information".to_string()),
         };
 
+        match self.base_address {
+            None => self.base_address = Some(base_address),
+            Some(registered) if registered != base_address => {
+                return Err("functions must share the same base address".to_string())
+            }
+            Some(_) => {}
+        }
+
         let mut entry = winnt::RUNTIME_FUNCTION::default();
 
         entry.BeginAddress = func_start;
@@ -49,12 +63,8 @@ impl UnwindRegistry {
         unsafe {
             *entry.u.UnwindInfoAddress_mut() = (entry.EndAddress + 3) & !3;
         }
-        let entries = self
-            .functions
-            .entry(base_address)
-            .or_insert_with(|| Vec::new());
 
-        entries.push(entry);
+        self.functions.push(entry);
 
         Ok(())
     }
@@ -67,23 +77,25 @@ impl UnwindRegistry {
 
         self.published = true;
 
-        if !self.functions.is_empty() {
-            for (base_address, functions) in self.functions.iter_mut() {
-                // Windows heap allocations are 32-bit aligned, but assert just in case
-                assert_eq!(
-                    (functions.as_mut_ptr() as u64) % 4,
-                    0,
-                    "function table allocation was not aligned"
-                );
-                unsafe {
-                    if winnt::RtlAddFunctionTable(
-                        functions.as_mut_ptr(),
-                        functions.len() as u32,
-                        *base_address as u64,
-                    ) == 0
-                    {
-                        return Err("failed to register function tables".to_string());
-                    }
+        if let Some(base_address) = self.base_address {
+            // The system unwinder performs a binary search on the table,
+            // so entries must be sorted by their start address
+            self.functions.sort_unstable_by_key(|f| f.BeginAddress);
+
+            // Windows heap allocations are 32-bit aligned, but assert just in case
+            assert_eq!(
+                (self.functions.as_mut_ptr() as u64) % 4,
+                0,
+                "function table allocation was not aligned"
+            );
+            unsafe {
+                if winnt::RtlAddFunctionTable(
+                    self.functions.as_mut_ptr(),
+                    self.functions.len() as u32,
+                    base_address as u64,
+                ) == 0
+                {
+                    return Err("failed to register function tables".to_string());
                 }
             }
         }
@@ -94,11 +106,9 @@ impl UnwindRegistry {
 
 impl Drop for UnwindRegistry {
     fn drop(&mut self) {
-        if self.published {
+        if self.published && self.base_address.is_some() {
             unsafe {
-                for functions in self.functions.values_mut() {
-                    winnt::RtlDeleteFunctionTable(functions.as_mut_ptr());
-                }
+                winnt::RtlDeleteFunctionTable(self.functions.as_mut_ptr());
             }
         }
     }
@@ -41,13 +41,14 @@ mod lib {
     #[cfg(feature = "core")]
     pub mod std {
         pub use alloc::{borrow, boxed, str, string, sync, vec};
+        pub use core::any;
         pub use core::fmt;
         pub use hashbrown as collections;
     }
 
     #[cfg(feature = "std")]
     pub mod std {
-        pub use std::{borrow, boxed, collections, fmt, str, string, sync, vec};
+        pub use std::{any, borrow, boxed, collections, fmt, str, string, sync, vec};
     }
 }
 
@@ -72,10 +73,15 @@ mod translator;
 #[cfg(feature = "translator")]
 pub use crate::compiler::{Compiler, CompilerConfig};
 #[cfg(feature = "translator")]
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::compiler::{FunctionInterpreter, InterpretedFunctions};
+#[cfg(feature = "translator")]
 pub use crate::translator::{
-    from_binaryreadererror_wasmerror, translate_module, wptype_to_type, FunctionBinaryReader,
-    FunctionBodyData, FunctionMiddleware, MiddlewareBinaryReader, MiddlewareReaderState,
-    ModuleEnvironment, ModuleMiddleware, ModuleMiddlewareChain, ModuleTranslationState,
+    from_binaryreadererror_wasmerror, translate_and_validate_module, translate_module,
+    wptype_to_type, FilteredMiddleware, FunctionBinaryReader, FunctionBodyData, FunctionFilter,
+    FunctionMetadata, FunctionMiddleware, FunctionSelector, MiddlewareBinaryReader,
+    MiddlewareLocals, MiddlewareReaderState, ModuleEnvironment, ModuleMiddleware,
+    ModuleMiddlewareChain, ModuleTranslationState,
 };
 
 pub use wasmer_types::{Addend, CodeOffset, Features};
@@ -27,6 +27,9 @@ pub trait ArtifactCreate: Send + Sync + Upcastable {
     /// Returns the CPU features for this Artifact
     fn cpu_features(&self) -> EnumSet<CpuFeature>;
 
+    /// Returns the name of the compiler that produced this Artifact
+    fn compiler(&self) -> &str;
+
     /// Returns the memory styles associated with this `Artifact`.
     fn memory_styles(&self) -> &PrimaryMap<MemoryIndex, MemoryStyle>;
 
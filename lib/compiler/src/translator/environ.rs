@@ -4,11 +4,12 @@ use super::state::ModuleTranslationState;
 use crate::lib::std::string::ToString;
 use crate::lib::std::{boxed::Box, string::String, vec::Vec};
 use crate::translate_module;
+use crate::translator::translate_and_validate_module;
 use crate::wasmparser::{Operator, Range, Type};
 use std::convert::{TryFrom, TryInto};
 use wasmer_types::entity::PrimaryMap;
-use wasmer_types::FunctionType;
 use wasmer_types::WasmResult;
+use wasmer_types::{CompileError, Features, FunctionType};
 use wasmer_types::{
     CustomSectionIndex, DataIndex, DataInitializer, DataInitializerLocation, ElemIndex,
     ExportIndex, FunctionIndex, GlobalIndex, GlobalInit, GlobalType, ImportIndex,
@@ -91,6 +92,20 @@ impl<'data> ModuleEnvironment<'data> {
         Ok(self)
     }
 
+    /// Translate and validate a wasm module using this environment, in a
+    /// single pass over the module. This consumes the `ModuleEnvironment`
+    /// and produces a `ModuleInfoTranslation`.
+    pub fn translate_and_validate(
+        mut self,
+        data: &'data [u8],
+        features: &Features,
+    ) -> Result<ModuleEnvironment<'data>, CompileError> {
+        assert!(self.module_translation_state.is_none());
+        let module_translation_state = translate_and_validate_module(data, &mut self, features)?;
+        self.module_translation_state = Some(module_translation_state);
+        Ok(self)
+    }
+
     pub(crate) fn declare_export(&mut self, export: ExportIndex, name: &str) -> WasmResult<()> {
         self.module.exports.insert(String::from(name), export);
         Ok(())
@@ -437,6 +452,13 @@ impl<'data> ModuleEnvironment<'data> {
         Ok(())
     }
 
+    /// Indicates that the code section has been found in the wasm file,
+    /// with its contents starting at `offset`.
+    pub(crate) fn code_section_start(&mut self, offset: usize) -> WasmResult<()> {
+        self.module.code_section_offset = offset as u32;
+        Ok(())
+    }
+
     /// Indicates that a custom section has been found in the wasm file
     pub(crate) fn custom_section(&mut self, name: &'data str, data: &'data [u8]) -> WasmResult<()> {
         let custom_section = CustomSectionIndex::from_u32(
@@ -1,11 +1,42 @@
 //! The middleware parses the function binary bytecodes and transform them
 //! with the chosen functions.
+//!
+//! A middleware is made of a [`ModuleMiddleware`], pushed to the
+//! `CompilerConfig`, and of the [`FunctionMiddleware`]s it generates for
+//! each function of a module. They see the module in this order:
+//!
+//! 1. [`ModuleMiddleware::transform_module_info`] is called once, before
+//!    the functions are compiled, e.g. to add globals or exports;
+//! 2. for each function, possibly on several compilation threads,
+//!    [`ModuleMiddleware::generate_function_middleware`] creates a
+//!    `FunctionMiddleware`, which is then given:
+//!    - the locals of the function with [`FunctionMiddleware::feed_locals`],
+//!      where it can add its own,
+//!    - every operator of the function with [`FunctionMiddleware::feed`],
+//!      where it can drop, rewrite or insert operators.
+//!
+//! The middlewares of a chain run in the order they are pushed to the
+//! `CompilerConfig`, both when transforming the `ModuleInfo` and when
+//! feeding the operators: each one sees the globals and the locals added
+//! by the previous ones, and the operators they output. The data a
+//! middleware wants to keep about each function can be attached to it
+//! with [`FunctionMetadata`].
+//!
+//! A middleware can be applied to some functions only, e.g. to keep an
+//! expensive instrumentation out of the hot functions, by wrapping it in
+//! a [`FilteredMiddleware`].
 
 use smallvec::SmallVec;
-use std::collections::VecDeque;
-use std::fmt::Debug;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt::{self, Debug};
+use std::hash::{BuildHasher, Hasher};
 use std::ops::Deref;
-use wasmer_types::{LocalFunctionIndex, MiddlewareError, ModuleInfo, WasmResult};
+use std::sync::{Arc, Mutex};
+use wasmer_types::{
+    ExportIndex, FunctionIndex, LocalFunctionIndex, MiddlewareError, ModuleInfo, WasmError,
+    WasmResult,
+};
 use wasmparser::{BinaryReader, Operator, Range, Type};
 
 use super::error::from_binaryreadererror_wasmerror;
@@ -25,11 +56,30 @@ pub trait ModuleMiddleware: Debug + Send + Sync {
 
     /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
     fn transform_module_info(&self, _: &mut ModuleInfo) {}
+
+    /// Returns a string identifying this middleware and the settings that
+    /// affect the code it generates, part of the
+    /// [`Engine::deterministic_id`](crate::Engine::deterministic_id).
+    ///
+    /// Defaults to the name of the type of the middleware, middlewares
+    /// with settings should add them.
+    fn deterministic_id(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
 }
 
 /// A function middleware specialized for a single function.
 pub trait FunctionMiddleware: Debug {
+    /// Processes the locals of the function, before its operators, e.g.
+    /// to add locals for the operators the middleware inserts.
+    fn feed_locals(&mut self, _locals: &mut MiddlewareLocals) -> Result<(), MiddlewareError> {
+        Ok(())
+    }
+
     /// Processes the given operator.
+    ///
+    /// The operators pushed to `state` replace it: pushing it back keeps
+    /// it, and pushing nothing drops it.
     fn feed<'a>(
         &mut self,
         operator: Operator<'a>,
@@ -48,6 +98,148 @@ pub struct MiddlewareBinaryReader<'a> {
 
     /// The backing middleware chain for this reader.
     chain: Vec<Box<dyn FunctionMiddleware>>,
+
+    /// The number of parameters of the function.
+    num_params: u32,
+
+    /// The local declarations of the function, with the ones added by the
+    /// middlewares, that are yet to be read.
+    local_decls: VecDeque<(u32, Type)>,
+}
+
+/// The locals of a function, given to the middlewares before its
+/// operators so that they can add their own.
+#[derive(Debug)]
+pub struct MiddlewareLocals {
+    /// The number of parameters of the function.
+    num_params: u32,
+
+    /// The local declarations, as `(count, type)` pairs.
+    decls: Vec<(u32, Type)>,
+
+    /// The number of locals, parameters included.
+    count: u32,
+}
+
+impl MiddlewareLocals {
+    /// Returns the number of parameters of the function, which are the
+    /// first locals.
+    pub fn num_params(&self) -> u32 {
+        self.num_params
+    }
+
+    /// Returns the number of locals of the function, parameters included,
+    /// which is the index of the next local added.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Returns the local declarations of the function, as `(count, type)`
+    /// pairs following the parameters, including the ones added by the
+    /// previous middlewares of the chain.
+    pub fn declarations(&self) -> &[(u32, Type)] {
+        &self.decls
+    }
+
+    /// Adds `count` locals of type `ty` to the function, and returns the
+    /// index of the first one.
+    ///
+    /// Fails if the number of locals of the function doesn't fit in a
+    /// `u32` anymore.
+    pub fn add(&mut self, count: u32, ty: Type) -> Result<u32, MiddlewareError> {
+        let index = self.count;
+        self.count = self.count.checked_add(count).ok_or_else(|| {
+            MiddlewareError::new(
+                "MiddlewareLocals",
+                format!(
+                    "cannot add {} locals to a function with {} locals",
+                    count, index
+                ),
+            )
+        })?;
+        self.decls.push((count, ty));
+        Ok(index)
+    }
+}
+
+/// Data attached to the functions of a module by a middleware.
+///
+/// A `ModuleMiddleware` keeps one and gives a clone of it to the
+/// `FunctionMiddleware`s it generates, which may run on several threads;
+/// the data is then available once the module is compiled.
+#[derive(Debug)]
+pub struct FunctionMetadata<T> {
+    entries: Arc<Mutex<BTreeMap<LocalFunctionIndex, T>>>,
+}
+
+impl<T> FunctionMetadata<T> {
+    /// Creates an empty `FunctionMetadata`.
+    pub fn new() -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// Attaches `value` to a function, and returns the value it replaces.
+    pub fn insert(&self, local_function_index: LocalFunctionIndex, value: T) -> Option<T> {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(local_function_index, value)
+    }
+
+    /// Updates the value attached to a function, or attaches `f(None)` to
+    /// it.
+    pub fn update(&self, local_function_index: LocalFunctionIndex, f: impl FnOnce(&mut Option<T>)) {
+        let mut entries = self.entries.lock().unwrap();
+        let mut value = entries.remove(&local_function_index);
+        f(&mut value);
+        if let Some(value) = value {
+            entries.insert(local_function_index, value);
+        }
+    }
+
+    /// Removes all the values, and returns them sorted by function.
+    pub fn take(&self) -> Vec<(LocalFunctionIndex, T)> {
+        std::mem::take(&mut *self.entries.lock().unwrap())
+            .into_iter()
+            .collect()
+    }
+}
+
+impl<T: Clone> FunctionMetadata<T> {
+    /// Returns the value attached to a function.
+    pub fn get(&self, local_function_index: LocalFunctionIndex) -> Option<T> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&local_function_index)
+            .cloned()
+    }
+
+    /// Returns all the values, sorted by function.
+    pub fn to_vec(&self) -> Vec<(LocalFunctionIndex, T)> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(index, value)| (*index, value.clone()))
+            .collect()
+    }
+}
+
+impl<T> Clone for FunctionMetadata<T> {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl<T> Default for FunctionMetadata<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// The state of the binary reader. Exposed to middlewares to push their outputs.
@@ -58,17 +250,24 @@ pub struct MiddlewareReaderState<'a> {
 
     /// The pending operations added by the middleware.
     pending_operations: VecDeque<Operator<'a>>,
+
+    /// The offset in the original Wasm binary of the operator currently
+    /// being fed through the chain.
+    current_operator_offset: usize,
 }
 
 /// Trait for generating middleware chains from "prototype" (generator) chains.
+///
+/// The middlewares are applied in the order of the chain.
 pub trait ModuleMiddlewareChain {
-    /// Generates a function middleware chain.
+    /// Generates a function middleware chain, in the order of the chain.
     fn generate_function_middleware_chain(
         &self,
         local_function_index: LocalFunctionIndex,
     ) -> Vec<Box<dyn FunctionMiddleware>>;
 
-    /// Applies the chain on a `ModuleInfo` struct.
+    /// Applies the chain on a `ModuleInfo` struct, in the order of the
+    /// chain.
     fn apply_on_module_info(&self, module_info: &mut ModuleInfo);
 }
 
@@ -91,11 +290,216 @@ impl<T: Deref<Target = dyn ModuleMiddleware>> ModuleMiddlewareChain for [T] {
     }
 }
 
+/// A selection of functions of a module.
+#[derive(Clone)]
+pub enum FunctionSelector {
+    /// The function with this index, imported functions included.
+    Index(FunctionIndex),
+    /// The function with this name, in the name section of the module or
+    /// as an export.
+    Name(String),
+    /// The functions for which the predicate, given the index and the
+    /// names of a function, returns `true`.
+    Predicate(Arc<dyn Fn(FunctionIndex, &[&str]) -> bool + Send + Sync>),
+}
+
+impl FunctionSelector {
+    fn matches(&self, index: FunctionIndex, names: &[&str]) -> bool {
+        match self {
+            Self::Index(selected) => *selected == index,
+            Self::Name(name) => names.contains(&name.as_str()),
+            Self::Predicate(predicate) => predicate(index, names),
+        }
+    }
+}
+
+impl Debug for FunctionSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => f.debug_tuple("Index").field(index).finish(),
+            Self::Name(name) => f.debug_tuple("Name").field(name).finish(),
+            Self::Predicate(_) => f.write_str("Predicate"),
+        }
+    }
+}
+
+/// The functions a [`FilteredMiddleware`] is applied to.
+///
+/// Every function is selected, unless some are included, in which case
+/// only they are; the excluded functions are never selected.
+#[derive(Debug, Clone, Default)]
+pub struct FunctionFilter {
+    include: Vec<FunctionSelector>,
+    exclude: Vec<FunctionSelector>,
+}
+
+impl FunctionFilter {
+    /// Creates a `FunctionFilter` selecting every function.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Includes the functions matching `selector`.
+    pub fn include(mut self, selector: FunctionSelector) -> Self {
+        self.include.push(selector);
+        self
+    }
+
+    /// Excludes the functions matching `selector`.
+    pub fn exclude(mut self, selector: FunctionSelector) -> Self {
+        self.exclude.push(selector);
+        self
+    }
+
+    /// Returns whether some functions are selected by a predicate,
+    /// which can't be told apart from another one.
+    fn has_predicates(&self) -> bool {
+        self.include
+            .iter()
+            .chain(self.exclude.iter())
+            .any(|selector| matches!(selector, FunctionSelector::Predicate(_)))
+    }
+
+    /// Returns whether the function with this index and these names is
+    /// selected.
+    pub fn matches(&self, index: FunctionIndex, names: &[&str]) -> bool {
+        (self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|selector| selector.matches(index, names)))
+            && !self
+                .exclude
+                .iter()
+                .any(|selector| selector.matches(index, names))
+    }
+}
+
+/// A middleware applied to the functions selected by a
+/// [`FunctionFilter`] only.
+///
+/// The wrapped middleware still transforms the `ModuleInfo`, but the
+/// other functions are compiled as if it wasn't in the chain.
+///
+/// The predicates of a [`FunctionSelector::Predicate`] can't be
+/// identified, so a `FilteredMiddleware` using some has a
+/// `deterministic_id` of its own, and the artifacts it compiles are only
+/// shared by the engines using this very middleware.
+#[derive(Debug)]
+pub struct FilteredMiddleware {
+    /// The wrapped middleware.
+    middleware: Arc<dyn ModuleMiddleware>,
+
+    /// The functions the middleware is applied to.
+    filter: FunctionFilter,
+
+    /// A random number identifying this middleware, if its filter uses
+    /// predicates.
+    unique_id: Option<u64>,
+
+    /// The selected local functions, known once the `ModuleInfo` is
+    /// transformed.
+    selected: Mutex<BTreeSet<LocalFunctionIndex>>,
+}
+
+impl FilteredMiddleware {
+    /// Creates a `FilteredMiddleware` applying `middleware` to the
+    /// functions selected by `filter`.
+    pub fn new(middleware: Arc<dyn ModuleMiddleware>, filter: FunctionFilter) -> Self {
+        let unique_id = filter
+            .has_predicates()
+            .then(|| RandomState::new().build_hasher().finish());
+        Self {
+            middleware,
+            filter,
+            unique_id,
+            selected: Mutex::new(BTreeSet::new()),
+        }
+    }
+}
+
+/// The function middleware of the functions a [`FilteredMiddleware`]
+/// isn't applied to, which keeps the operators as they are.
+#[derive(Debug)]
+struct PassThrough;
+
+impl FunctionMiddleware for PassThrough {}
+
+impl ModuleMiddleware for FilteredMiddleware {
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        if self
+            .selected
+            .lock()
+            .unwrap()
+            .contains(&local_function_index)
+        {
+            self.middleware
+                .generate_function_middleware(local_function_index)
+        } else {
+            Box::new(PassThrough)
+        }
+    }
+
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        self.middleware.transform_module_info(module_info);
+
+        let mut export_names: HashMap<FunctionIndex, Vec<&str>> = HashMap::new();
+        for (name, export) in module_info.exports.iter() {
+            if let ExportIndex::Function(index) = export {
+                export_names.entry(*index).or_default().push(name);
+            }
+        }
+
+        let mut selected = self.selected.lock().unwrap();
+        selected.clear();
+        for index in module_info.functions.keys() {
+            let local_function_index = match module_info.local_func_index(index) {
+                Some(local_function_index) => local_function_index,
+                None => continue,
+            };
+            let mut names = export_names.remove(&index).unwrap_or_default();
+            if let Some(name) = module_info.function_names.get(&index) {
+                names.push(name);
+            }
+            if self.filter.matches(index, &names) {
+                selected.insert(local_function_index);
+            }
+        }
+    }
+
+    fn deterministic_id(&self) -> String {
+        let id = format!(
+            "filtered-{}-{:?}",
+            self.middleware.deterministic_id(),
+            self.filter
+        );
+        match self.unique_id {
+            Some(unique_id) => format!("{}-{:016x}", id, unique_id),
+            None => id,
+        }
+    }
+}
+
 impl<'a> MiddlewareReaderState<'a> {
     /// Push an operator.
     pub fn push_operator(&mut self, operator: Operator<'a>) {
         self.pending_operations.push_back(operator);
     }
+
+    /// Returns the offset in the original Wasm binary of the operator
+    /// currently being fed through the middleware chain.
+    ///
+    /// Operators pushed by earlier middlewares in the chain report the
+    /// offset of the original operator they were generated for, so every
+    /// middleware sees the same offset regardless of its position in the
+    /// chain. This allows middlewares to act on specific code locations,
+    /// e.g. to set breakpoints.
+    pub fn current_operator_offset(&self) -> usize {
+        self.current_operator_offset
+    }
 }
 
 impl<'a> Extend<Operator<'a>> for MiddlewareReaderState<'a> {
@@ -118,8 +522,11 @@ impl<'a> MiddlewareBinaryReader<'a> {
             state: MiddlewareReaderState {
                 inner,
                 pending_operations: VecDeque::new(),
+                current_operator_offset: original_offset,
             },
             chain: vec![],
+            num_params: 0,
+            local_decls: VecDeque::new(),
         }
     }
 
@@ -127,17 +534,15 @@ impl<'a> MiddlewareBinaryReader<'a> {
     pub fn set_middleware_chain(&mut self, stages: Vec<Box<dyn FunctionMiddleware>>) {
         self.chain = stages;
     }
-}
 
-impl<'a> FunctionBinaryReader<'a> for MiddlewareBinaryReader<'a> {
-    fn read_local_count(&mut self) -> WasmResult<u32> {
-        self.state
-            .inner
-            .read_var_u32()
-            .map_err(from_binaryreadererror_wasmerror)
+    /// Sets the number of parameters of the function, so that the
+    /// middlewares know the indexes of the locals they add.
+    pub fn set_num_params(&mut self, num_params: u32) {
+        self.num_params = num_params;
     }
 
-    fn read_local_decl(&mut self) -> WasmResult<(u32, Type)> {
+    /// Reads a local declaration from the binary.
+    fn read_raw_local_decl(&mut self) -> WasmResult<(u32, Type)> {
         let count = self
             .state
             .inner
@@ -150,6 +555,45 @@ impl<'a> FunctionBinaryReader<'a> for MiddlewareBinaryReader<'a> {
             .map_err(from_binaryreadererror_wasmerror)?;
         Ok((count, ty))
     }
+}
+
+impl<'a> FunctionBinaryReader<'a> for MiddlewareBinaryReader<'a> {
+    fn read_local_count(&mut self) -> WasmResult<u32> {
+        let num_decls = self
+            .state
+            .inner
+            .read_var_u32()
+            .map_err(from_binaryreadererror_wasmerror)?;
+        if self.chain.is_empty() {
+            return Ok(num_decls);
+        }
+
+        // Read all the declarations, to let the middlewares add theirs.
+        let mut locals = MiddlewareLocals {
+            num_params: self.num_params,
+            decls: Vec::with_capacity(num_decls as usize),
+            count: self.num_params,
+        };
+        for _ in 0..num_decls {
+            let (count, ty) = self.read_raw_local_decl()?;
+            locals.add(count, ty)?;
+        }
+        for stage in &mut self.chain {
+            stage.feed_locals(&mut locals)?;
+        }
+
+        self.local_decls = locals.decls.into();
+        Ok(self.local_decls.len() as u32)
+    }
+
+    fn read_local_decl(&mut self) -> WasmResult<(u32, Type)> {
+        if self.chain.is_empty() {
+            return self.read_raw_local_decl();
+        }
+        self.local_decls
+            .pop_front()
+            .ok_or_else(|| WasmError::Generic("no local declaration left to read".to_string()))
+    }
 
     fn read_operator(&mut self) -> WasmResult<Operator<'a>> {
         if self.chain.is_empty() {
@@ -163,6 +607,7 @@ impl<'a> FunctionBinaryReader<'a> for MiddlewareBinaryReader<'a> {
 
         // Try to fill the `self.pending_operations` buffer, until it is non-empty.
         while self.state.pending_operations.is_empty() {
+            self.state.current_operator_offset = self.state.inner.original_position();
             let raw_op = self
                 .state
                 .inner
@@ -208,3 +653,50 @@ impl<'a> FunctionBinaryReader<'a> for MiddlewareBinaryReader<'a> {
         self.state.inner.range()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locals_count_overflow_is_an_error() {
+        let mut locals = MiddlewareLocals {
+            num_params: 2,
+            decls: vec![],
+            count: 2,
+        };
+        assert_eq!(locals.add(3, Type::I32).unwrap(), 2);
+        assert_eq!(locals.add(1, Type::I64).unwrap(), 5);
+        assert_eq!(locals.count(), 6);
+
+        let error = locals.add(u32::MAX, Type::I32).unwrap_err();
+        assert_eq!(error.name, "MiddlewareLocals");
+        assert_eq!(locals.count(), 6);
+        assert_eq!(locals.declarations(), &[(3, Type::I32), (1, Type::I64)]);
+    }
+
+    #[test]
+    fn filters_with_predicates_are_never_shared() {
+        let id = |filter: FunctionFilter| {
+            FilteredMiddleware::new(Arc::new(PassThroughMiddleware), filter).deterministic_id()
+        };
+        let by_name = || FunctionFilter::new().include(FunctionSelector::Name("f".to_string()));
+        assert_eq!(id(by_name()), id(by_name()));
+
+        let by_predicate =
+            || FunctionFilter::new().exclude(FunctionSelector::Predicate(Arc::new(|_, _| true)));
+        assert_ne!(id(by_predicate()), id(by_predicate()));
+    }
+
+    #[derive(Debug)]
+    struct PassThroughMiddleware;
+
+    impl ModuleMiddleware for PassThroughMiddleware {
+        fn generate_function_middleware(
+            &self,
+            _: LocalFunctionIndex,
+        ) -> Box<dyn FunctionMiddleware> {
+            Box::new(PassThrough)
+        }
+    }
+}
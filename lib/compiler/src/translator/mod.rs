@@ -9,16 +9,20 @@ mod environ;
 mod middleware;
 mod module;
 mod state;
+mod validation;
 #[macro_use]
 mod error;
 mod sections;
 
 pub use self::environ::{FunctionBinaryReader, FunctionBodyData, ModuleEnvironment};
 pub use self::middleware::{
-    FunctionMiddleware, MiddlewareBinaryReader, MiddlewareReaderState, ModuleMiddleware,
+    FilteredMiddleware, FunctionFilter, FunctionMetadata, FunctionMiddleware, FunctionSelector,
+    MiddlewareBinaryReader, MiddlewareLocals, MiddlewareReaderState, ModuleMiddleware,
     ModuleMiddlewareChain,
 };
 pub use self::module::translate_module;
 pub use self::sections::wptype_to_type;
 pub use self::state::ModuleTranslationState;
+pub use self::validation::translate_and_validate_module;
+pub(crate) use self::validation::wasm_features;
 pub use error::from_binaryreadererror_wasmerror;
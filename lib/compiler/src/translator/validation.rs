@@ -0,0 +1,145 @@
+//! Validation of a WebAssembly module along with its translation.
+//!
+//! Instead of validating the whole module before translating it, each
+//! section is validated right before it's translated, so the module is
+//! parsed once. Only the validation of the function bodies overlaps with
+//! the parsing and the translation of the rest of the module: with the
+//! `rayon` feature, they are validated on the threads of the rayon pool,
+//! otherwise one after the other as they are parsed. The functions are
+//! still compiled once the whole module is translated and validated.
+
+use super::environ::ModuleEnvironment;
+use super::module::translate_payload;
+use super::state::ModuleTranslationState;
+use std::sync::Mutex;
+use wasmer_types::{CompileError, Features};
+use wasmparser::{BinaryReaderError, Parser, ValidPayload, Validator, WasmFeatures};
+
+#[cfg(feature = "rayon")]
+use rayon::scope;
+
+/// Runs the validation of the function bodies in the current thread,
+/// in place of `rayon::scope`.
+#[cfg(not(feature = "rayon"))]
+fn scope<R>(op: impl FnOnce(&SerialScope) -> R) -> R {
+    op(&SerialScope)
+}
+
+#[cfg(not(feature = "rayon"))]
+struct SerialScope;
+
+#[cfg(not(feature = "rayon"))]
+impl SerialScope {
+    fn spawn(&self, job: impl FnOnce(&Self)) {
+        job(self)
+    }
+}
+
+/// The features to validate a module with.
+pub(crate) fn wasm_features(features: &Features) -> WasmFeatures {
+    WasmFeatures {
+        bulk_memory: features.bulk_memory,
+        threads: features.threads,
+        reference_types: features.reference_types,
+        multi_value: features.multi_value,
+        simd: features.simd,
+        tail_call: features.tail_call,
+        module_linking: features.module_linking,
+        multi_memory: features.multi_memory,
+        memory64: features.memory64,
+        exceptions: features.exceptions,
+        deterministic_only: false,
+        extended_const: features.extended_const,
+        relaxed_simd: features.relaxed_simd,
+        mutable_global: true,
+        saturating_float_to_int: true,
+        sign_extension: true,
+    }
+}
+
+/// The first error in the module, the one reported by a validation of
+/// the whole module, whatever the order the errors are found in.
+#[derive(Default)]
+struct FirstError(Mutex<Option<BinaryReaderError>>);
+
+impl FirstError {
+    fn record(&self, error: BinaryReaderError) {
+        let mut first = self.0.lock().unwrap();
+        if first
+            .as_ref()
+            .map_or(true, |first| error.offset() < first.offset())
+        {
+            *first = Some(error);
+        }
+    }
+
+    fn into_inner(self) -> Option<BinaryReaderError> {
+        self.0.into_inner().unwrap()
+    }
+}
+
+/// Translate a sequence of bytes forming a Wasm binary into a parsed
+/// ModuleInfo `ModuleTranslationState`, validating it along the way.
+///
+/// The validation errors are reported as [`CompileError::Validate`],
+/// like with [`Compiler::validate_module`](crate::Compiler::validate_module).
+pub fn translate_and_validate_module<'data>(
+    data: &'data [u8],
+    environ: &mut ModuleEnvironment<'data>,
+    features: &Features,
+) -> Result<ModuleTranslationState, CompileError> {
+    let mut validator = Validator::new();
+    validator.wasm_features(wasm_features(features));
+    let first_error = FirstError::default();
+
+    let translation = scope(|scope| {
+        let mut module_translation_state = ModuleTranslationState::new();
+        let mut translation_error = None;
+        for payload in Parser::new(0).parse_all(data) {
+            let payload = match payload {
+                Ok(payload) => payload,
+                Err(error) => {
+                    first_error.record(error);
+                    break;
+                }
+            };
+            match validator.payload(&payload) {
+                Ok(ValidPayload::Func(mut func_validator, body)) => {
+                    let first_error = &first_error;
+                    scope.spawn(move |_| {
+                        if let Err(error) = func_validator.validate(&body) {
+                            first_error.record(error);
+                        }
+                    });
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    first_error.record(error);
+                    break;
+                }
+            }
+            // Once the translation fails, the rest of the module is still
+            // validated, as an invalid module is reported as such even when
+            // it uses an unsupported feature.
+            if translation_error.is_none() {
+                if let Err(error) =
+                    translate_payload(payload, &mut module_translation_state, environ)
+                {
+                    translation_error = Some(error);
+                }
+            }
+        }
+        match translation_error {
+            Some(error) => Err(error),
+            None => Ok(module_translation_state),
+        }
+    });
+
+    // An invalid function body may only be found once the rest of the
+    // module is translated, and takes precedence over the translation
+    // errors.
+    if let Some(error) = first_error.into_inner() {
+        return Err(CompileError::Validate(format!("{}", error)));
+    }
+    translation.map_err(CompileError::Wasm)
+}
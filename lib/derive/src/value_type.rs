@@ -88,6 +88,21 @@ fn zero_padding(fields: &Fields) -> TokenStream {
     out
 }
 
+/// Convert the byte order of every field.
+fn convert_endianness(fields: &Fields) -> TokenStream {
+    let mut out = TokenStream::new();
+    for (i, field) in fields.iter().enumerate() {
+        let name = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(i.into()),
+        };
+        out.extend(quote! {
+            ::wasmer_types::ValueType::convert_endianness(&mut self.#name);
+        });
+    }
+    out
+}
+
 pub fn impl_value_type(input: &DeriveInput) -> TokenStream {
     check_repr(input);
 
@@ -99,6 +114,7 @@ pub fn impl_value_type(input: &DeriveInput) -> TokenStream {
     };
 
     let zero_padding = zero_padding(fields);
+    let convert_endianness = convert_endianness(fields);
 
     quote! {
         unsafe impl #impl_generics ::wasmer_types::ValueType for #struct_name #ty_generics #where_clause {
@@ -106,6 +122,11 @@ pub fn impl_value_type(input: &DeriveInput) -> TokenStream {
             fn zero_padding_bytes(&self, _bytes: &mut [::core::mem::MaybeUninit<u8>]) {
                 #zero_padding
             }
+
+            #[inline]
+            fn convert_endianness(&mut self) {
+                #convert_endianness
+            }
         }
     }
 }
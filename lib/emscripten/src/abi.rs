@@ -0,0 +1,131 @@
+//! Detection of the emscripten release a module has been built with.
+//!
+//! The imports of emscripten modules changed across releases: the
+//! `fastcomp` backend prefixes the C symbols with `_`, the LLVM backend
+//! doesn't, and the recent releases call the syscalls directly, with
+//! `__syscall_*` imports taking their arguments, instead of going through
+//! `___syscallN` imports reading them from a varargs buffer. The latter
+//! aren't implemented, so such modules are rejected with an error telling
+//! why, instead of failing at instantiation on a missing import.
+
+use wasmer::{ExternType, Module};
+
+/// The custom section written with `-s EMIT_EMSCRIPTEN_METADATA=1`
+const METADATA_SECTION: &str = "emscripten_metadata";
+
+/// The major version of the ABI the glue of this crate is written for
+const SUPPORTED_ABI_MAJOR: u32 = 0;
+
+/// The backend of emscripten that compiled a module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmscriptenBackend {
+    /// The `fastcomp` backend, whose imports of C symbols are prefixed
+    /// with `_`
+    Fastcomp,
+    /// The LLVM backend
+    Llvm,
+}
+
+/// The ABI of the emscripten release a module has been built with, see
+/// [`get_emscripten_abi`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmscriptenAbi {
+    pub backend: EmscriptenBackend,
+    /// The `(major, minor)` version of the ABI, only known if the module
+    /// has an `emscripten_metadata` section
+    pub version: Option<(u32, u32)>,
+}
+
+/// The fields of the `emscripten_metadata` section that we need
+struct Metadata {
+    abi_major: u32,
+    abi_minor: u32,
+    llvm_backend: bool,
+}
+
+impl Metadata {
+    fn parse(mut section: &[u8]) -> Result<Self, String> {
+        let mut leb = || {
+            let mut value = 0u32;
+            for shift in (0..35).step_by(7) {
+                let (&byte, rest) = section
+                    .split_first()
+                    .ok_or_else(|| "the `emscripten_metadata` section is truncated".to_string())?;
+                section = rest;
+                value |= ((byte & 0x7f) as u32) << shift;
+                if byte & 0x80 == 0 {
+                    return Ok(value);
+                }
+            }
+            Err("invalid LEB128 in the `emscripten_metadata` section".to_string())
+        };
+
+        let metadata_major = leb()?;
+        let metadata_minor = leb()?;
+        if metadata_major != 0 {
+            return Err(format!(
+                "unsupported version {}.{} of the `emscripten_metadata` section",
+                metadata_major, metadata_minor
+            ));
+        }
+        Ok(Self {
+            abi_major: leb()?,
+            abi_minor: leb()?,
+            llvm_backend: leb()? != 0,
+        })
+    }
+}
+
+/// Detects the ABI of the emscripten release that built `module`, from its
+/// `emscripten_metadata` section if it has one, and from its imports, or
+/// returns why its ABI isn't supported
+pub fn get_emscripten_abi(module: &Module) -> Result<EmscriptenAbi, String> {
+    let metadata = match module.custom_sections(METADATA_SECTION).next() {
+        Some(section) => Some(Metadata::parse(&section)?),
+        None => None,
+    };
+    if let Some(metadata) = &metadata {
+        if metadata.abi_major != SUPPORTED_ABI_MAJOR {
+            return Err(format!(
+                "the module has been built for the version {}.{} of the emscripten ABI, \
+                 only the versions {}.x are supported",
+                metadata.abi_major, metadata.abi_minor, SUPPORTED_ABI_MAJOR
+            ));
+        }
+    }
+
+    let mut fastcomp = false;
+    for import in module.imports() {
+        let name = import.name();
+        match import.ty() {
+            ExternType::Function(_) => {
+                if name.starts_with("__syscall_") || name.starts_with("__sys_") {
+                    return Err(format!(
+                        "the module imports `{}`: it has been built with a release of \
+                         emscripten that calls the syscalls directly, which isn't supported; \
+                         only the syscalls made through the `___syscallN` imports are",
+                        name
+                    ));
+                }
+                fastcomp |= name == "_emscripten_memcpy_big";
+            }
+            ExternType::Global(_) => {
+                fastcomp |= matches!(name, "STACKTOP" | "DYNAMICTOP_PTR" | "tempDoublePtr");
+            }
+            _ => {}
+        }
+    }
+
+    let llvm_backend = match &metadata {
+        Some(metadata) => metadata.llvm_backend,
+        None => !fastcomp,
+    };
+    Ok(EmscriptenAbi {
+        backend: if llvm_backend {
+            EmscriptenBackend::Llvm
+        } else {
+            EmscriptenBackend::Fastcomp
+        },
+        version: metadata.map(|metadata| (metadata.abi_major, metadata.abi_minor)),
+    })
+}
@@ -1,15 +1,12 @@
 #![allow(non_snake_case)]
 
 use crate::env::{get_emscripten_data, get_emscripten_funcs};
+use crate::exception::CxxException;
+use crate::jmp::LongJumpRet;
 use crate::EmEnv;
 #[cfg(target_os = "linux")]
 use libc::getdtablesize;
-use wasmer::FunctionEnvMut;
-
-pub fn asm_const_i(_ctx: FunctionEnvMut<EmEnv>, _val: i32) -> i32 {
-    debug!("emscripten::asm_const_i: {}", _val);
-    0
-}
+use wasmer::{FunctionEnvMut, RuntimeError};
 
 pub fn exit_with_live_runtime(_ctx: FunctionEnvMut<EmEnv>) {
     debug!("emscripten::exit_with_live_runtime");
@@ -51,20 +48,6 @@ pub fn __Unwind_GetIPInfo(_ctx: FunctionEnvMut<EmEnv>, _a: i32, _b: i32) -> i32
     debug!("emscripten::__Unwind_GetIPInfo");
     0
 }
-pub fn ___cxa_find_matching_catch_2(_ctx: FunctionEnvMut<EmEnv>) -> i32 {
-    debug!("emscripten::___cxa_find_matching_catch_2");
-    0
-}
-pub fn ___cxa_find_matching_catch_3(_ctx: FunctionEnvMut<EmEnv>, _a: i32) -> i32 {
-    debug!("emscripten::___cxa_find_matching_catch_3");
-    0
-}
-pub fn ___cxa_free_exception(_ctx: FunctionEnvMut<EmEnv>, _a: i32) {
-    debug!("emscripten::___cxa_free_exception");
-}
-pub fn ___resumeException(_ctx: FunctionEnvMut<EmEnv>, _a: i32) {
-    debug!("emscripten::___resumeException");
-}
 pub fn _dladdr(_ctx: FunctionEnvMut<EmEnv>, _a: i32, _b: i32) -> i32 {
     debug!("emscripten::_dladdr");
     0
@@ -136,44 +119,56 @@ pub fn _getnameinfo(
 }
 
 // Invoke functions
-// They save the stack to allow unwinding
+// They save the stack to allow unwinding, and catch the C++ exceptions and
+// the `longjmp`s, which the caller then handles through `setThrew`
+
+/// Whether an error raised in an `invoke_*` call is a C++ exception or a
+/// `longjmp`, or another error, like a trap, that must be propagated.
+fn is_emscripten_exception(error: &RuntimeError) -> bool {
+    error.is::<CxxException>() || error.is::<LongJumpRet>()
+}
+
+/// The error raised when the module doesn't export a function needed by
+/// the `invoke_*` calls.
+pub(crate) fn missing_function(name: &str) -> RuntimeError {
+    RuntimeError::new(format!("missing function: {}", name))
+}
 
 // Macro definitions
 macro_rules! invoke {
     ($ctx: ident, $name:ident, $name_ref:ident, $( $arg:ident ),*) => {{
         let funcs = get_emscripten_funcs(&$ctx).clone();
-        let sp = funcs.stack_save_ref().expect("stack_save is None").call(&mut $ctx).expect("stack_save call failed");
-        let call = funcs.$name_ref().expect(concat!("Dynamic call is None: ", stringify!($name))).clone();
+        let sp = funcs.stack_save_ref().ok_or_else(|| missing_function("stackSave"))?.call(&mut $ctx)?;
+        let call = funcs.$name_ref().ok_or_else(|| missing_function(stringify!($name)))?.clone();
         match call.call(&mut $ctx, $($arg),*) {
-            Ok(v) => v,
-            Err(_e) => {
-                let stack = funcs.stack_restore_ref().expect("stack_restore is None");
-                stack.call(&mut $ctx, sp).expect("stack_restore call failed");
-                // TODO: We should check if _e != "longjmp" and if that's the case, re-throw the error
-                // JS version is: if (e !== e+0 && e !== 'longjmp') throw e;
-                let threw = funcs.set_threw_ref().expect("set_threw is None");
-                threw.call(&mut $ctx, 1, 0).expect("set_threw call failed");
-                0 as _
+            Ok(v) => Ok(v),
+            Err(e) if is_emscripten_exception(&e) => {
+                let stack = funcs.stack_restore_ref().ok_or_else(|| missing_function("stackRestore"))?;
+                stack.call(&mut $ctx, sp)?;
+                let threw = funcs.set_threw_ref().ok_or_else(|| missing_function("setThrew"))?;
+                threw.call(&mut $ctx, 1, 0)?;
+                Ok(Default::default())
             }
+            Err(e) => Err(e),
         }
     }};
 }
 macro_rules! invoke_no_return {
     ($ctx: ident, $name:ident, $name_ref:ident, $( $arg:ident ),*) => {{
         let funcs = get_emscripten_funcs(&$ctx).clone();
-        let stack = funcs.stack_save_ref().expect("stack_save is None");
-        let sp = stack.call(&mut $ctx).expect("stack_save call failed");
-        let call = funcs.$name_ref().expect(concat!("Dynamic call is None: ", stringify!($name))).clone();
+        let stack = funcs.stack_save_ref().ok_or_else(|| missing_function("stackSave"))?;
+        let sp = stack.call(&mut $ctx)?;
+        let call = funcs.$name_ref().ok_or_else(|| missing_function(stringify!($name)))?.clone();
         match call.call(&mut $ctx, $($arg),*) {
-            Ok(v) => v,
-            Err(_e) => {
-                let stack = funcs.stack_restore_ref().expect("stack_restore is None");
-                stack.call(&mut $ctx, sp).expect("stack_restore call failed");
-                // TODO: We should check if _e != "longjmp" and if that's the case, re-throw the error
-                // JS version is: if (e !== e+0 && e !== 'longjmp') throw e;
-                let threw = funcs.set_threw_ref().expect("set_threw is None");
-                threw.call(&mut $ctx, 1, 0).expect("set_threw call failed");
+            Ok(()) => Ok(()),
+            Err(e) if is_emscripten_exception(&e) => {
+                let stack = funcs.stack_restore_ref().ok_or_else(|| missing_function("stackRestore"))?;
+                stack.call(&mut $ctx, sp)?;
+                let threw = funcs.set_threw_ref().ok_or_else(|| missing_function("setThrew"))?;
+                threw.call(&mut $ctx, 1, 0)?;
+                Ok(())
             }
+            Err(e) => Err(e),
         }
     }};
 }
@@ -181,49 +176,85 @@ macro_rules! invoke_no_return {
 macro_rules! invoke_no_stack_save {
     ($ctx: ident, $name:ident, $name_ref:ident, $( $arg:ident ),*) => {{
         let funcs = get_emscripten_funcs(&$ctx).clone();
-        let call = funcs.$name_ref().expect(concat!(stringify!($name), " is set to None")).clone();
+        let call = funcs.$name_ref().ok_or_else(|| missing_function(stringify!($name)))?.clone();
 
-        call.call(&mut $ctx, $($arg),*).unwrap()
+        match call.call(&mut $ctx, $($arg),*) {
+            Ok(v) => Ok(v),
+            Err(e) if is_emscripten_exception(&e) => {
+                let threw = funcs.set_threw_ref().ok_or_else(|| missing_function("setThrew"))?;
+                threw.call(&mut $ctx, 1, 0)?;
+                Ok(Default::default())
+            }
+            Err(e) => Err(e),
+        }
     }}
 }
 
 // Invoke functions
-pub fn invoke_i(mut ctx: FunctionEnvMut<EmEnv>, index: i32) -> i32 {
+pub fn invoke_i(mut ctx: FunctionEnvMut<EmEnv>, index: i32) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_i");
     invoke!(ctx, dyn_call_i, dyn_call_i_ref, index)
 }
-pub fn invoke_ii(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32) -> i32 {
+pub fn invoke_ii(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_ii");
     invoke!(ctx, dyn_call_ii, dyn_call_ii_ref, index, a1)
 }
-pub fn invoke_iii(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: i32) -> i32 {
+pub fn invoke_iii(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: i32,
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_iii");
     invoke!(ctx, dyn_call_iii, dyn_call_iii_ref, index, a1, a2)
 }
-pub fn invoke_iiii(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: i32, a3: i32) -> i32 {
+pub fn invoke_iiii(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_iiii");
     invoke!(ctx, dyn_call_iiii, dyn_call_iiii_ref, index, a1, a2, a3)
 }
-pub fn invoke_iifi(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: f64, a3: i32) -> i32 {
+pub fn invoke_iifi(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: f64,
+    a3: i32,
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_iifi");
     invoke!(ctx, dyn_call_iifi, dyn_call_iifi_ref, index, a1, a2, a3)
 }
-pub fn invoke_v(mut ctx: FunctionEnvMut<EmEnv>, index: i32) {
+pub fn invoke_v(mut ctx: FunctionEnvMut<EmEnv>, index: i32) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_v");
-    invoke_no_return!(ctx, dyn_call_v, dyn_call_v_ref, index);
+    invoke_no_return!(ctx, dyn_call_v, dyn_call_v_ref, index)
 }
-pub fn invoke_vi(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32) {
+pub fn invoke_vi(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_vi");
-    invoke_no_return!(ctx, dyn_call_vi, dyn_call_vi_ref, index, a1);
+    invoke_no_return!(ctx, dyn_call_vi, dyn_call_vi_ref, index, a1)
 }
-pub fn invoke_vii(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: i32) {
+pub fn invoke_vii(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: i32,
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_vii");
-    invoke_no_return!(ctx, dyn_call_vii, dyn_call_vii_ref, index, a1, a2);
+    invoke_no_return!(ctx, dyn_call_vii, dyn_call_vii_ref, index, a1, a2)
 }
 
-pub fn invoke_viii(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: i32, a3: i32) {
+pub fn invoke_viii(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viii");
-    invoke_no_return!(ctx, dyn_call_viii, dyn_call_viii_ref, index, a1, a2, a3);
+    invoke_no_return!(ctx, dyn_call_viii, dyn_call_viii_ref, index, a1, a2, a3)
 }
 pub fn invoke_viiii(
     mut ctx: FunctionEnvMut<EmEnv>,
@@ -232,7 +263,7 @@ pub fn invoke_viiii(
     a2: i32,
     a3: i32,
     a4: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viiii");
     invoke_no_return!(
         ctx,
@@ -243,9 +274,14 @@ pub fn invoke_viiii(
         a2,
         a3,
         a4
-    );
+    )
 }
-pub fn invoke_dii(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: i32) -> f64 {
+pub fn invoke_dii(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: i32,
+) -> Result<f64, RuntimeError> {
     debug!("emscripten::invoke_dii");
     invoke!(ctx, dyn_call_dii, dyn_call_dii_ref, index, a1, a2)
 }
@@ -256,7 +292,7 @@ pub fn invoke_diiii(
     a2: i32,
     a3: i32,
     a4: i32,
-) -> f64 {
+) -> Result<f64, RuntimeError> {
     debug!("emscripten::invoke_diiii");
     invoke!(
         ctx,
@@ -276,7 +312,7 @@ pub fn invoke_iiiii(
     a2: i32,
     a3: i32,
     a4: i32,
-) -> i32 {
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_iiiii");
     invoke!(
         ctx,
@@ -297,7 +333,7 @@ pub fn invoke_iiiiii(
     a3: i32,
     a4: i32,
     a5: i32,
-) -> i32 {
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_iiiiii");
     invoke!(
         ctx,
@@ -321,7 +357,7 @@ pub fn invoke_iiiiiii(
     a4: i32,
     a5: i32,
     a6: i32,
-) -> i32 {
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_iiiiiii");
     invoke!(
         ctx,
@@ -347,7 +383,7 @@ pub fn invoke_iiiiiiii(
     a5: i32,
     a6: i32,
     a7: i32,
-) -> i32 {
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_iiiiiiii");
     invoke!(
         ctx,
@@ -375,7 +411,7 @@ pub fn invoke_iiiiiiiii(
     a6: i32,
     a7: i32,
     a8: i32,
-) -> i32 {
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_iiiiiiiii");
     invoke!(
         ctx,
@@ -405,7 +441,7 @@ pub fn invoke_iiiiiiiiii(
     a7: i32,
     a8: i32,
     a9: i32,
-) -> i32 {
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_iiiiiiiiii");
     invoke!(
         ctx,
@@ -437,7 +473,7 @@ pub fn invoke_iiiiiiiiiii(
     a8: i32,
     a9: i32,
     a10: i32,
-) -> i32 {
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_iiiiiiiiiii");
     invoke!(
         ctx,
@@ -456,7 +492,7 @@ pub fn invoke_iiiiiiiiiii(
         a10
     )
 }
-pub fn invoke_vd(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: f64) {
+pub fn invoke_vd(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: f64) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_vd");
     invoke_no_return!(ctx, dyn_call_vd, dyn_call_vd_ref, index, a1)
 }
@@ -468,7 +504,7 @@ pub fn invoke_viiiii(
     a3: i32,
     a4: i32,
     a5: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viiiii");
     invoke_no_return!(
         ctx,
@@ -492,7 +528,7 @@ pub fn invoke_viiiiii(
     a4: i32,
     a5: i32,
     a6: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viiiiii");
     invoke_no_return!(
         ctx,
@@ -518,7 +554,7 @@ pub fn invoke_viiiiiii(
     a5: i32,
     a6: i32,
     a7: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viiiiiii");
     invoke_no_return!(
         ctx,
@@ -546,7 +582,7 @@ pub fn invoke_viiiiiiii(
     a6: i32,
     a7: i32,
     a8: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viiiiiiii");
     invoke_no_return!(
         ctx,
@@ -576,7 +612,7 @@ pub fn invoke_viiiiiiiii(
     a7: i32,
     a8: i32,
     a9: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viiiiiiiii");
     invoke_no_return!(
         ctx,
@@ -608,7 +644,7 @@ pub fn invoke_viiiiiiiiii(
     a8: i32,
     a9: i32,
     a10: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viiiiiiiiii");
     invoke_no_return!(
         ctx,
@@ -628,12 +664,24 @@ pub fn invoke_viiiiiiiiii(
     )
 }
 
-pub fn invoke_iij(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: i32, a3: i32) -> i32 {
+pub fn invoke_iij(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_iij");
     invoke!(ctx, dyn_call_iij, dyn_call_iij_ref, index, a1, a2, a3)
 }
 
-pub fn invoke_iji(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: i32, a3: i32) -> i32 {
+pub fn invoke_iji(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_iji");
     invoke!(ctx, dyn_call_iji, dyn_call_iji_ref, index, a1, a2, a3)
 }
@@ -645,7 +693,7 @@ pub fn invoke_iiji(
     a2: i32,
     a3: i32,
     a4: i32,
-) -> i32 {
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_iiji");
     invoke!(ctx, dyn_call_iiji, dyn_call_iiji_ref, index, a1, a2, a3, a4)
 }
@@ -660,7 +708,7 @@ pub fn invoke_iiijj(
     a4: i32,
     a5: i32,
     a6: i32,
-) -> i32 {
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_iiijj");
     invoke!(
         ctx,
@@ -675,20 +723,31 @@ pub fn invoke_iiijj(
         a6
     )
 }
-pub fn invoke_j(mut ctx: FunctionEnvMut<EmEnv>, index: i32) -> i32 {
+pub fn invoke_j(mut ctx: FunctionEnvMut<EmEnv>, index: i32) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_j");
     invoke_no_stack_save!(ctx, dyn_call_j, dyn_call_j_ref, index)
 }
-pub fn invoke_ji(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32) -> i32 {
+pub fn invoke_ji(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_ji");
     invoke_no_stack_save!(ctx, dyn_call_ji, dyn_call_ji_ref, index, a1)
 }
-pub fn invoke_jii(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: i32) -> i32 {
+pub fn invoke_jii(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: i32,
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_jii");
     invoke_no_stack_save!(ctx, dyn_call_jii, dyn_call_jii_ref, index, a1, a2)
 }
 
-pub fn invoke_jij(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: i32, a3: i32) -> i32 {
+pub fn invoke_jij(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_jij");
     invoke_no_stack_save!(ctx, dyn_call_jij, dyn_call_jij_ref, index, a1, a2, a3)
 }
@@ -699,7 +758,7 @@ pub fn invoke_jjj(
     a2: i32,
     a3: i32,
     a4: i32,
-) -> i32 {
+) -> Result<i32, RuntimeError> {
     debug!("emscripten::invoke_jjj");
     invoke_no_stack_save!(ctx, dyn_call_jjj, dyn_call_jjj_ref, index, a1, a2, a3, a4)
 }
@@ -711,7 +770,7 @@ pub fn invoke_viiij(
     a3: i32,
     a4: i32,
     a5: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viiij");
     invoke_no_stack_save!(
         ctx,
@@ -738,7 +797,7 @@ pub fn invoke_viiijiiii(
     a7: i32,
     a8: i32,
     a9: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viiijiiii");
     invoke_no_stack_save!(
         ctx,
@@ -771,7 +830,7 @@ pub fn invoke_viiijiiiiii(
     a9: i32,
     a10: i32,
     a11: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viiijiiiiii");
     invoke_no_stack_save!(
         ctx,
@@ -791,7 +850,14 @@ pub fn invoke_viiijiiiiii(
         a11
     )
 }
-pub fn invoke_viij(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: i32, a3: i32, a4: i32) {
+pub fn invoke_viij(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+    a4: i32,
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viij");
     invoke_no_stack_save!(ctx, dyn_call_viij, dyn_call_viij_ref, index, a1, a2, a3, a4)
 }
@@ -803,7 +869,7 @@ pub fn invoke_viiji(
     a3: i32,
     a4: i32,
     a5: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viiji");
     invoke_no_stack_save!(
         ctx,
@@ -828,7 +894,7 @@ pub fn invoke_viijiii(
     a5: i32,
     a6: i32,
     a7: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viijiii");
     invoke_no_stack_save!(
         ctx,
@@ -854,7 +920,7 @@ pub fn invoke_viijj(
     a4: i32,
     a5: i32,
     a6: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viijj");
     invoke_no_stack_save!(
         ctx,
@@ -869,7 +935,12 @@ pub fn invoke_viijj(
         a6
     )
 }
-pub fn invoke_vj(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: i32) {
+pub fn invoke_vj(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: i32,
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_vj");
     invoke_no_stack_save!(ctx, dyn_call_vj, dyn_call_vj_ref, index, a1, a2)
 }
@@ -881,7 +952,7 @@ pub fn invoke_vjji(
     a3: i32,
     a4: i32,
     a5: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_vjji");
     invoke_no_return!(
         ctx,
@@ -895,11 +966,24 @@ pub fn invoke_vjji(
         a5
     )
 }
-pub fn invoke_vij(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: i32, a3: i32) {
+pub fn invoke_vij(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_vij");
     invoke_no_stack_save!(ctx, dyn_call_vij, dyn_call_vij_ref, index, a1, a2, a3)
 }
-pub fn invoke_viji(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: i32, a3: i32, a4: i32) {
+pub fn invoke_viji(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: i32,
+    a4: i32,
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viji");
     invoke_no_stack_save!(ctx, dyn_call_viji, dyn_call_viji_ref, index, a1, a2, a3, a4)
 }
@@ -913,7 +997,7 @@ pub fn invoke_vijiii(
     a4: i32,
     a5: i32,
     a6: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_vijiii");
     invoke_no_stack_save!(
         ctx,
@@ -936,7 +1020,7 @@ pub fn invoke_vijj(
     a3: i32,
     a4: i32,
     a5: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_vijj");
     invoke_no_stack_save!(
         ctx,
@@ -950,13 +1034,25 @@ pub fn invoke_vijj(
         a5
     )
 }
-pub fn invoke_vidd(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: f64, a3: f64) {
+pub fn invoke_vidd(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: f64,
+    a3: f64,
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viid");
-    invoke_no_return!(ctx, dyn_call_vidd, dyn_call_vidd_ref, index, a1, a2, a3);
+    invoke_no_return!(ctx, dyn_call_vidd, dyn_call_vidd_ref, index, a1, a2, a3)
 }
-pub fn invoke_viid(mut ctx: FunctionEnvMut<EmEnv>, index: i32, a1: i32, a2: i32, a3: f64) {
+pub fn invoke_viid(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    index: i32,
+    a1: i32,
+    a2: i32,
+    a3: f64,
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viid");
-    invoke_no_return!(ctx, dyn_call_viid, dyn_call_viid_ref, index, a1, a2, a3);
+    invoke_no_return!(ctx, dyn_call_viid, dyn_call_viid_ref, index, a1, a2, a3)
 }
 pub fn invoke_viidii(
     mut ctx: FunctionEnvMut<EmEnv>,
@@ -966,7 +1062,7 @@ pub fn invoke_viidii(
     a3: f64,
     a4: i32,
     a5: i32,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viidii");
     invoke_no_return!(
         ctx,
@@ -978,7 +1074,7 @@ pub fn invoke_viidii(
         a3,
         a4,
         a5
-    );
+    )
 }
 #[allow(clippy::too_many_arguments)]
 pub fn invoke_viidddddddd(
@@ -994,7 +1090,7 @@ pub fn invoke_viidddddddd(
     a8: f64,
     a9: f64,
     a10: f64,
-) {
+) -> Result<(), RuntimeError> {
     debug!("emscripten::invoke_viidddddddd");
     invoke_no_return!(
         ctx,
@@ -1011,5 +1107,5 @@ pub fn invoke_viidddddddd(
         a8,
         a9,
         a10
-    );
+    )
 }
@@ -1,7 +1,103 @@
-use super::env;
-use super::process::_abort;
+use super::env::{self, get_emscripten_data, get_emscripten_funcs};
 use crate::EmEnv;
-use wasmer::FunctionEnvMut;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use wasmer::{FunctionEnvMut, RuntimeError, WasmPtr};
+
+/// A thrown C++ exception, unwinding the wasm frames up to the closest
+/// `invoke_*` call, as a thrown pointer in the JS runtime of emscripten.
+#[derive(Copy, Clone, Debug)]
+pub struct CxxException(pub u32);
+
+impl fmt::Display for CxxException {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CxxException({:#x})", self.0)
+    }
+}
+
+impl Error for CxxException {}
+
+/// What is known about a thrown exception.
+#[derive(Debug, Clone)]
+struct ExceptionInfo {
+    ty: u32,
+    destructor: u32,
+    /// The pointers to the exception adjusted to the types of the catch
+    /// clauses, e.g. to a base class.
+    adjusted: Vec<u32>,
+    ref_count: u32,
+    caught: bool,
+    rethrown: bool,
+}
+
+/// The C++ exceptions in flight, kept in `EmscriptenData`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Exceptions {
+    /// The last thrown exception.
+    last: u32,
+    /// The exceptions handled by the current catch blocks, innermost last.
+    caught: Vec<u32>,
+    infos: HashMap<u32, ExceptionInfo>,
+    uncaught: i32,
+    /// A word of memory used to get adjusted pointers from `___cxa_can_catch`.
+    buffer: u32,
+}
+
+impl Exceptions {
+    /// Gets the pointer to the exception from a pointer adjusted by a catch
+    /// clause.
+    fn de_adjust(&self, adjusted: u32) -> u32 {
+        if adjusted == 0 || self.infos.contains_key(&adjusted) {
+            return adjusted;
+        }
+        self.infos
+            .iter()
+            .find(|(_, info)| info.adjusted.contains(&adjusted))
+            .map(|(ptr, _)| *ptr)
+            .unwrap_or(adjusted)
+    }
+
+    fn add_ref(&mut self, ptr: u32) {
+        if let Some(info) = self.infos.get_mut(&ptr) {
+            info.ref_count += 1;
+        }
+    }
+}
+
+fn with_exceptions<T>(ctx: &FunctionEnvMut<EmEnv>, f: impl FnOnce(&mut Exceptions) -> T) -> T {
+    f(&mut get_emscripten_data(ctx).as_mut().unwrap().exceptions)
+}
+
+fn set_temp_ret_0(ctx: &FunctionEnvMut<EmEnv>, value: u32) {
+    get_emscripten_data(ctx).as_mut().unwrap().temp_ret_0 = value as i32;
+}
+
+/// Releases a reference to the exception `ptr`, and destroys it when it
+/// was the last one.
+fn decrement_ref(ctx: &mut FunctionEnvMut<EmEnv>, ptr: u32) -> Result<(), RuntimeError> {
+    let destructor = with_exceptions(ctx, |exceptions| {
+        let info = exceptions.infos.get_mut(&ptr)?;
+        info.ref_count = info.ref_count.saturating_sub(1);
+        if info.ref_count > 0 || info.rethrown {
+            return None;
+        }
+        exceptions.infos.remove(&ptr).map(|info| info.destructor)
+    });
+    let destructor = match destructor {
+        Some(destructor) => destructor,
+        None => return Ok(()),
+    };
+
+    if destructor != 0 {
+        let dyn_call_vi = get_emscripten_funcs(ctx).dyn_call_vi_ref().cloned();
+        if let Some(dyn_call_vi) = dyn_call_vi {
+            dyn_call_vi.call(ctx, destructor as i32, ptr as i32)?;
+        }
+    }
+    ___cxa_free_exception(ctx.as_mut(), ptr);
+    Ok(())
+}
 
 /// emscripten: ___cxa_allocate_exception
 pub fn ___cxa_allocate_exception(mut ctx: FunctionEnvMut<EmEnv>, size: u32) -> u32 {
@@ -9,50 +105,259 @@ pub fn ___cxa_allocate_exception(mut ctx: FunctionEnvMut<EmEnv>, size: u32) -> u
     env::call_malloc(&mut ctx.as_mut(), size as _)
 }
 
-pub fn ___cxa_current_primary_exception(_ctx: FunctionEnvMut<EmEnv>) -> u32 {
+/// emscripten: ___cxa_free_exception
+pub fn ___cxa_free_exception(mut ctx: FunctionEnvMut<EmEnv>, ptr: u32) {
+    debug!("emscripten::___cxa_free_exception({})", ptr);
+    let free = get_emscripten_funcs(&ctx).free_ref().cloned();
+    if let Some(free) = free {
+        // As in emscripten, a failure to free the exception is ignored
+        if free.call(&mut ctx, ptr).is_err() {
+            debug!("emscripten::___cxa_free_exception: free failed");
+        }
+    }
+}
+
+pub fn ___cxa_current_primary_exception(ctx: FunctionEnvMut<EmEnv>) -> u32 {
     debug!("emscripten::___cxa_current_primary_exception");
-    unimplemented!("emscripten::___cxa_current_primary_exception")
+    with_exceptions(&ctx, |exceptions| {
+        let ptr = exceptions.caught.last().copied().unwrap_or(0);
+        let primary = exceptions.de_adjust(ptr);
+        exceptions.add_ref(primary);
+        ptr
+    })
 }
 
-pub fn ___cxa_decrement_exception_refcount(_ctx: FunctionEnvMut<EmEnv>, _a: u32) {
-    debug!("emscripten::___cxa_decrement_exception_refcount({})", _a);
-    unimplemented!("emscripten::___cxa_decrement_exception_refcount({})", _a)
+pub fn ___cxa_decrement_exception_refcount(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    ptr: u32,
+) -> Result<(), RuntimeError> {
+    debug!("emscripten::___cxa_decrement_exception_refcount({})", ptr);
+    let ptr = with_exceptions(&ctx, |exceptions| exceptions.de_adjust(ptr));
+    decrement_ref(&mut ctx, ptr)
 }
 
-pub fn ___cxa_increment_exception_refcount(_ctx: FunctionEnvMut<EmEnv>, _a: u32) {
-    debug!("emscripten::___cxa_increment_exception_refcount({})", _a);
-    unimplemented!("emscripten::___cxa_increment_exception_refcount({})", _a)
+pub fn ___cxa_increment_exception_refcount(ctx: FunctionEnvMut<EmEnv>, ptr: u32) {
+    debug!("emscripten::___cxa_increment_exception_refcount({})", ptr);
+    with_exceptions(&ctx, |exceptions| {
+        let ptr = exceptions.de_adjust(ptr);
+        exceptions.add_ref(ptr);
+    })
 }
 
-pub fn ___cxa_rethrow_primary_exception(_ctx: FunctionEnvMut<EmEnv>, _a: u32) {
-    debug!("emscripten::___cxa_rethrow_primary_exception({})", _a);
-    unimplemented!("emscripten::___cxa_rethrow_primary_exception({})", _a)
+pub fn ___cxa_rethrow_primary_exception(
+    ctx: FunctionEnvMut<EmEnv>,
+    ptr: u32,
+) -> Result<(), RuntimeError> {
+    debug!("emscripten::___cxa_rethrow_primary_exception({})", ptr);
+    if ptr == 0 {
+        return Ok(());
+    }
+    with_exceptions(&ctx, |exceptions| {
+        exceptions.caught.push(ptr);
+        if let Some(info) = exceptions.infos.get_mut(&ptr) {
+            info.rethrown = true;
+        }
+    });
+    ___cxa_rethrow(ctx)
 }
 
 /// emscripten: ___cxa_throw
-/// TODO: We don't have support for exceptions yet
-pub fn ___cxa_throw(ctx: FunctionEnvMut<EmEnv>, _ptr: u32, _ty: u32, _destructor: u32) {
+pub fn ___cxa_throw(
+    ctx: FunctionEnvMut<EmEnv>,
+    ptr: u32,
+    ty: u32,
+    destructor: u32,
+) -> Result<(), CxxException> {
     debug!("emscripten::___cxa_throw");
-    eprintln!("Throwing exceptions not yet implemented: aborting!");
-    _abort(ctx);
+    with_exceptions(&ctx, |exceptions| {
+        exceptions.infos.insert(
+            ptr,
+            ExceptionInfo {
+                ty,
+                destructor,
+                adjusted: vec![ptr],
+                ref_count: 0,
+                caught: false,
+                rethrown: false,
+            },
+        );
+        exceptions.last = ptr;
+        exceptions.uncaught += 1;
+    });
+    Err(CxxException(ptr))
 }
 
-pub fn ___cxa_begin_catch(_ctx: FunctionEnvMut<EmEnv>, _exception_object_ptr: u32) -> i32 {
+/// emscripten: ___cxa_rethrow
+pub fn ___cxa_rethrow(ctx: FunctionEnvMut<EmEnv>) -> Result<(), RuntimeError> {
+    debug!("emscripten::___cxa_rethrow");
+    let ptr = with_exceptions(&ctx, |exceptions| {
+        let ptr = exceptions.caught.pop()?;
+        let ptr = exceptions.de_adjust(ptr);
+        if let Some(info) = exceptions.infos.get_mut(&ptr) {
+            if !info.rethrown {
+                info.rethrown = true;
+                exceptions.caught.push(ptr);
+            }
+        }
+        exceptions.last = ptr;
+        Some(ptr)
+    });
+    match ptr {
+        Some(ptr) => Err(RuntimeError::user(Box::new(CxxException(ptr)))),
+        None => Err(RuntimeError::new(
+            "terminate called without an active exception",
+        )),
+    }
+}
+
+pub fn ___cxa_begin_catch(ctx: FunctionEnvMut<EmEnv>, ptr: u32) -> u32 {
     debug!("emscripten::___cxa_begin_catch");
-    -1
+    with_exceptions(&ctx, |exceptions| {
+        let primary = exceptions.de_adjust(ptr);
+        if let Some(info) = exceptions.infos.get_mut(&primary) {
+            if !info.caught {
+                info.caught = true;
+                exceptions.uncaught -= 1;
+            }
+            info.rethrown = false;
+        }
+        exceptions.caught.push(ptr);
+        exceptions.add_ref(primary);
+    });
+    ptr
 }
 
-pub fn ___cxa_end_catch(_ctx: FunctionEnvMut<EmEnv>) {
+pub fn ___cxa_end_catch(mut ctx: FunctionEnvMut<EmEnv>) -> Result<(), RuntimeError> {
     debug!("emscripten::___cxa_end_catch");
+    let set_threw = get_emscripten_funcs(&ctx).set_threw_ref().cloned();
+    if let Some(set_threw) = set_threw {
+        set_threw.call(&mut ctx, 0, 0)?;
+    }
+
+    let ptr = with_exceptions(&ctx, |exceptions| {
+        let ptr = exceptions.caught.pop()?;
+        exceptions.last = 0;
+        Some(exceptions.de_adjust(ptr))
+    });
+    match ptr {
+        Some(ptr) if ptr != 0 => decrement_ref(&mut ctx, ptr),
+        _ => Ok(()),
+    }
 }
 
-pub fn ___cxa_uncaught_exception(_ctx: FunctionEnvMut<EmEnv>) -> i32 {
+pub fn ___cxa_uncaught_exception(ctx: FunctionEnvMut<EmEnv>) -> i32 {
     debug!("emscripten::___cxa_uncaught_exception");
-    -1
+    (with_exceptions(&ctx, |exceptions| exceptions.uncaught) > 0) as i32
+}
+
+pub fn ___cxa_uncaught_exceptions(ctx: FunctionEnvMut<EmEnv>) -> i32 {
+    debug!("emscripten::___cxa_uncaught_exceptions");
+    with_exceptions(&ctx, |exceptions| exceptions.uncaught)
+}
+
+/// Finds the first of the catch clause types `types` that can catch the
+/// last thrown exception, with `___cxa_can_catch`. Returns the exception,
+/// adjusted to the matching type, and the type in `tempRet0`; the type of
+/// the exception is returned if none matches, or 0 if there is no
+/// exception.
+fn find_matching_catch(
+    ctx: &mut FunctionEnvMut<EmEnv>,
+    types: &[u32],
+) -> Result<u32, RuntimeError> {
+    let (thrown, thrown_ty, buffer) = with_exceptions(ctx, |exceptions| {
+        let ty = exceptions
+            .infos
+            .get(&exceptions.last)
+            .map(|info| info.ty)
+            .unwrap_or(0);
+        (exceptions.last, ty, exceptions.buffer)
+    });
+    if thrown == 0 || thrown_ty == 0 {
+        set_temp_ret_0(ctx, 0);
+        return Ok(thrown);
+    }
+
+    let can_catch = get_emscripten_funcs(ctx).cxa_can_catch_ref().cloned();
+    let buffer = if buffer == 0 {
+        let buffer = env::call_malloc(ctx, 4);
+        with_exceptions(ctx, |exceptions| exceptions.buffer = buffer);
+        buffer
+    } else {
+        buffer
+    };
+    let memory = ctx.data().memory(0);
+
+    for &ty in types.iter().filter(|&&ty| ty != 0) {
+        let caught = match can_catch.as_ref() {
+            Some(can_catch) => {
+                WasmPtr::<u32>::new(buffer).write(&memory.view(ctx), thrown)?;
+                can_catch.call(ctx, ty as i32, thrown_ty as i32, buffer as i32)? != 0
+            }
+            None => ty == thrown_ty,
+        };
+        if caught {
+            let adjusted = if can_catch.is_some() {
+                WasmPtr::<u32>::new(buffer).read(&memory.view(ctx))?
+            } else {
+                thrown
+            };
+            with_exceptions(ctx, |exceptions| {
+                if let Some(info) = exceptions.infos.get_mut(&thrown) {
+                    info.adjusted.push(adjusted);
+                }
+            });
+            set_temp_ret_0(ctx, ty);
+            return Ok(adjusted);
+        }
+    }
+
+    set_temp_ret_0(ctx, thrown_ty);
+    Ok(thrown)
 }
 
-pub fn ___cxa_pure_virtual(_ctx: FunctionEnvMut<EmEnv>) {
+pub fn ___cxa_find_matching_catch_2(mut ctx: FunctionEnvMut<EmEnv>) -> Result<u32, RuntimeError> {
+    debug!("emscripten::___cxa_find_matching_catch_2");
+    find_matching_catch(&mut ctx, &[])
+}
+
+pub fn ___cxa_find_matching_catch_3(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    a: u32,
+) -> Result<u32, RuntimeError> {
+    debug!("emscripten::___cxa_find_matching_catch_3");
+    find_matching_catch(&mut ctx, &[a])
+}
+
+pub fn ___cxa_find_matching_catch_4(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    a: u32,
+    b: u32,
+) -> Result<u32, RuntimeError> {
+    debug!("emscripten::___cxa_find_matching_catch_4");
+    find_matching_catch(&mut ctx, &[a, b])
+}
+
+#[allow(non_snake_case)]
+pub fn ___resumeException(ctx: FunctionEnvMut<EmEnv>, ptr: u32) -> Result<(), CxxException> {
+    debug!("emscripten::___resumeException");
+    with_exceptions(&ctx, |exceptions| {
+        if exceptions.last == 0 {
+            exceptions.last = ptr;
+        }
+    });
+    Err(CxxException(ptr))
+}
+
+pub fn ___cxa_pure_virtual(_ctx: FunctionEnvMut<EmEnv>) -> Result<(), RuntimeError> {
     debug!("emscripten::___cxa_pure_virtual");
-    // ABORT = true
-    panic!("Pure virtual function called!");
+    Err(RuntimeError::new("pure virtual function called"))
+}
+
+/// emscripten: ___cxa_call_unexpected
+pub fn ___cxa_call_unexpected(
+    _ctx: FunctionEnvMut<EmEnv>,
+    _exception: u32,
+) -> Result<(), RuntimeError> {
+    debug!("emscripten::___cxa_call_unexpected");
+    Err(RuntimeError::new("unexpected exception thrown"))
 }
@@ -0,0 +1,505 @@
+//! The filesystem seen by emscripten modules.
+//!
+//! Like with WASI, a module only sees the directories mapped with `--dir`
+//! and `--mapdir`: the paths given to the syscalls are resolved against
+//! them, and the ones outside of them can't be accessed. The files are
+//! those of the host by default, opened as host file descriptors so that
+//! every syscall works on them. An embedder can instead plug any
+//! [`FileSystem`] of `wasmer-vfs` with [`EmEnv::set_filesystem`], the
+//! layer WASI is built on: the paths then go through it, and the files
+//! opened in it get descriptors from [`FIRST_VIRTUAL_FD`] that the
+//! syscalls on descriptors recognize.
+
+use crate::env::get_emscripten_data;
+use crate::utils::copy_metadata_into_wasm;
+use crate::EmEnv;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::future::Future;
+use std::io::SeekFrom;
+use std::os::raw::{c_char, c_int};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use wasmer::{FunctionEnvMut, WasmPtr};
+use wasmer_vfs::{DirEntry, FileSystem, FsError, Metadata, VirtualFile};
+
+/// The first descriptor given to the files of a [`FileSystem`] set with
+/// [`EmEnv::set_filesystem`], far above the ones of the host
+pub(crate) const FIRST_VIRTUAL_FD: c_int = 1 << 30;
+
+/// The devices of the host that can be opened outside of the mapped
+/// directories
+const HOST_DEVICES: &[&str] = &["/dev/null", "/dev/zero", "/dev/random", "/dev/urandom"];
+
+// The errors, as the emscripten libc numbers them
+const ENOENT: c_int = 2;
+const EIO: c_int = 5;
+const EBADF: c_int = 9;
+const EAGAIN: c_int = 11;
+const EACCES: c_int = 13;
+const EEXIST: c_int = 17;
+const ENOTDIR: c_int = 20;
+const EISDIR: c_int = 21;
+const EINVAL: c_int = 22;
+const ENOSYS: c_int = 38;
+const ENOTEMPTY: c_int = 39;
+
+// The flags of `open`, as the emscripten libc numbers them
+const O_ACCMODE: c_int = 0o3;
+const O_RDONLY: c_int = 0o0;
+const O_WRONLY: c_int = 0o1;
+const O_CREAT: c_int = 0o100;
+const O_EXCL: c_int = 0o200;
+const O_TRUNC: c_int = 0o1000;
+const O_APPEND: c_int = 0o2000;
+const O_DIRECTORY: c_int = 0o200000;
+
+/// The size of a `dirent` written by `getdents`
+const DIRENT_SIZE: u32 = 256 + 12;
+
+enum OpenFile {
+    File(Box<dyn VirtualFile + Send + Sync>),
+    Dir { entries: Vec<DirEntry>, next: usize },
+}
+
+/// The filesystem set with [`EmEnv::set_filesystem`], if any, and the
+/// files opened in it
+pub(crate) struct Filesystem {
+    inner: Option<Box<dyn FileSystem>>,
+    files: HashMap<c_int, OpenFile>,
+    next_fd: c_int,
+}
+
+impl Default for Filesystem {
+    fn default() -> Self {
+        Self {
+            inner: None,
+            files: HashMap::new(),
+            next_fd: FIRST_VIRTUAL_FD,
+        }
+    }
+}
+
+impl Filesystem {
+    pub(crate) fn set(&mut self, filesystem: Box<dyn FileSystem>) {
+        self.inner = Some(filesystem);
+    }
+
+    fn insert(&mut self, file: OpenFile) -> c_int {
+        let fd = self.next_fd;
+        self.next_fd += 1;
+        self.files.insert(fd, file);
+        fd
+    }
+}
+
+/// A path of the module, resolved against the mapped directories
+pub(crate) enum ResolvedPath {
+    /// A path of the host
+    Host(CString),
+    /// A path in the filesystem set with [`EmEnv::set_filesystem`]
+    Virtual(PathBuf),
+}
+
+/// Resolves the path at `path` in the memory of the module, see [`resolve`]
+pub(crate) fn resolve_path(
+    ctx: &FunctionEnvMut<EmEnv>,
+    path: *const c_char,
+) -> Result<ResolvedPath, c_int> {
+    match unsafe { CStr::from_ptr(path) }.to_str() {
+        Ok(path) => resolve(ctx, Path::new(path)),
+        Err(_) => Err(-EINVAL),
+    }
+}
+
+/// Resolves `path` against the mapped directories, or returns `-EACCES` if
+/// it's outside of them
+pub(crate) fn resolve(ctx: &FunctionEnvMut<EmEnv>, path: &Path) -> Result<ResolvedPath, c_int> {
+    let virtual_fs = ctx.data().fs.lock().unwrap().inner.is_some();
+    let data = get_emscripten_data(ctx);
+    let mapped_dirs = &data.as_ref().unwrap().mapped_dirs;
+
+    match (map_path(mapped_dirs, path), virtual_fs) {
+        (Some(mapped), true) => Ok(ResolvedPath::Virtual(mapped)),
+        // The filesystem is already isolated from the host
+        (None, true) => Ok(ResolvedPath::Virtual(normalize(&Path::new("/").join(path)))),
+        (Some(mapped), false) => CString::new(mapped.to_string_lossy().as_bytes())
+            .map(ResolvedPath::Host)
+            .map_err(|_| -EINVAL),
+        (None, false) => match path.to_str() {
+            Some(device) if HOST_DEVICES.contains(&device) => {
+                Ok(ResolvedPath::Host(CString::new(device).unwrap()))
+            }
+            _ => {
+                debug!("=> {} is outside of the mapped directories", path.display());
+                Err(-EACCES)
+            }
+        },
+    }
+}
+
+/// Resolves the path at `path` like [`resolve_path`], for the syscalls
+/// that are only implemented for the files of the host
+pub(crate) fn host_path(
+    ctx: &FunctionEnvMut<EmEnv>,
+    path: *const c_char,
+) -> Result<CString, c_int> {
+    match resolve_path(ctx, path)? {
+        ResolvedPath::Host(path) => Ok(path),
+        ResolvedPath::Virtual(_) => Err(-ENOSYS),
+    }
+}
+
+/// Maps `path` to the directory of the longest alias it's in
+fn map_path(mapped_dirs: &HashMap<String, PathBuf>, path: &Path) -> Option<PathBuf> {
+    let path = normalize(path);
+    mapped_dirs
+        .iter()
+        .filter_map(|(alias, dir)| {
+            let alias = normalize(Path::new(alias));
+            // `.` only holds the relative paths
+            if alias.as_os_str().is_empty() && path.is_absolute() {
+                return None;
+            }
+            let rest = path.strip_prefix(&alias).ok()?;
+            // A relative path can't go above the directory it's in
+            if rest.components().any(|c| c == Component::ParentDir) {
+                return None;
+            }
+            Some((alias.components().count(), dir.join(rest)))
+        })
+        .max_by_key(|(depth, _)| *depth)
+        .map(|(_, mapped)| mapped)
+}
+
+/// Removes the `.` and `..` components of `path` without looking at the
+/// filesystem, except for the `..` at the start of a relative path
+fn normalize(path: &Path) -> PathBuf {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match components.last() {
+                Some(Component::Normal(_)) => {
+                    components.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => components.push(component),
+            },
+            _ => components.push(component),
+        }
+    }
+    components.iter().collect()
+}
+
+/// Runs the future of an operation on a [`VirtualFile`] on this thread
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+fn errno(error: FsError) -> c_int {
+    let errno = match error {
+        FsError::EntryNotFound => ENOENT,
+        FsError::AlreadyExists => EEXIST,
+        FsError::PermissionDenied => EACCES,
+        FsError::BaseNotDirectory => ENOTDIR,
+        FsError::NotAFile => EISDIR,
+        FsError::InvalidFd => EBADF,
+        FsError::InvalidInput => EINVAL,
+        FsError::WouldBlock => EAGAIN,
+        FsError::DirectoryNotEmpty => ENOTEMPTY,
+        _ => EIO,
+    };
+    -errno
+}
+
+fn with_filesystem<T>(
+    ctx: &FunctionEnvMut<EmEnv>,
+    f: impl FnOnce(&dyn FileSystem) -> Result<T, FsError>,
+) -> Result<T, c_int> {
+    let fs = ctx.data().fs.lock().unwrap();
+    let filesystem = fs.inner.as_deref().ok_or(-ENOSYS)?;
+    f(filesystem).map_err(|e| {
+        debug!("=> filesystem error: {}", e);
+        errno(e)
+    })
+}
+
+fn result(result: Result<(), c_int>) -> c_int {
+    result.err().unwrap_or(0)
+}
+
+/// Opens `path` in the filesystem set with [`EmEnv::set_filesystem`]
+pub(crate) fn open(ctx: &FunctionEnvMut<EmEnv>, path: &Path, flags: c_int) -> c_int {
+    let access = flags & O_ACCMODE;
+    let file = with_filesystem(ctx, |fs| {
+        match fs.metadata(path) {
+            Ok(metadata) if metadata.is_dir() => {
+                if access != O_RDONLY {
+                    return Err(FsError::NotAFile);
+                }
+                let entries = fs.read_dir(path)?.filter_map(Result::ok).collect();
+                return Ok(OpenFile::Dir { entries, next: 0 });
+            }
+            Ok(_) if flags & O_DIRECTORY != 0 => return Err(FsError::BaseNotDirectory),
+            _ => {}
+        }
+        fs.new_open_options()
+            .read(access != O_WRONLY)
+            .write(access != O_RDONLY)
+            .create(flags & O_CREAT != 0)
+            .create_new(flags & O_CREAT != 0 && flags & O_EXCL != 0)
+            .truncate(flags & O_TRUNC != 0)
+            .append(flags & O_APPEND != 0)
+            .open(path)
+            .map(OpenFile::File)
+    });
+    match file {
+        Ok(file) => ctx.data().fs.lock().unwrap().insert(file),
+        Err(errno) => errno,
+    }
+}
+
+/// stat, or lstat if `follow_symlinks` is false
+pub(crate) fn stat(
+    ctx: &FunctionEnvMut<EmEnv>,
+    path: &Path,
+    follow_symlinks: bool,
+    buf: u32,
+) -> c_int {
+    let metadata = with_filesystem(ctx, |fs| {
+        if follow_symlinks {
+            fs.metadata(path)
+        } else {
+            fs.symlink_metadata(path)
+        }
+    });
+    match metadata {
+        Ok(metadata) => {
+            copy_metadata_into_wasm(ctx, buf, &metadata);
+            0
+        }
+        Err(errno) => errno,
+    }
+}
+
+/// access; the files of the filesystem have no permissions, only their
+/// existence is checked
+pub(crate) fn access(ctx: &FunctionEnvMut<EmEnv>, path: &Path) -> c_int {
+    result(with_filesystem(ctx, |fs| fs.metadata(path).map(drop)))
+}
+
+/// unlink
+pub(crate) fn unlink(ctx: &FunctionEnvMut<EmEnv>, path: &Path) -> c_int {
+    result(with_filesystem(ctx, |fs| fs.remove_file(path)))
+}
+
+/// mkdir
+pub(crate) fn mkdir(ctx: &FunctionEnvMut<EmEnv>, path: &Path) -> c_int {
+    result(with_filesystem(ctx, |fs| fs.create_dir(path)))
+}
+
+/// rmdir
+pub(crate) fn rmdir(ctx: &FunctionEnvMut<EmEnv>, path: &Path) -> c_int {
+    result(with_filesystem(ctx, |fs| fs.remove_dir(path)))
+}
+
+/// rename
+pub(crate) fn rename(ctx: &FunctionEnvMut<EmEnv>, from: &Path, to: &Path) -> c_int {
+    result(with_filesystem(ctx, |fs| fs.rename(from, to)))
+}
+
+/// Runs `f` on the file opened at `fd` in the filesystem set with
+/// [`EmEnv::set_filesystem`], or returns `None` if `fd` is a descriptor of
+/// the host
+fn with_file(
+    ctx: &FunctionEnvMut<EmEnv>,
+    fd: c_int,
+    f: impl FnOnce(&mut OpenFile) -> c_int,
+) -> Option<c_int> {
+    if fd < FIRST_VIRTUAL_FD {
+        return None;
+    }
+    let mut fs = ctx.data().fs.lock().unwrap();
+    Some(match fs.files.get_mut(&fd) {
+        Some(file) => f(file),
+        None => -EBADF,
+    })
+}
+
+/// Reads at most `count` bytes at `buf` from the file opened at `fd`, see
+/// [`with_file`]
+pub(crate) fn read(ctx: &FunctionEnvMut<EmEnv>, fd: c_int, buf: u32, count: u32) -> Option<c_int> {
+    let mut data = vec![0; count as usize];
+    let read = with_file(ctx, fd, |file| match file {
+        OpenFile::File(file) => match block_on(file.read(&mut data)) {
+            Ok(read) => read as c_int,
+            Err(_) => -EIO,
+        },
+        OpenFile::Dir { .. } => -EISDIR,
+    })?;
+    if read < 0 {
+        return Some(read);
+    }
+    let memory = ctx.data().memory(0);
+    let written = WasmPtr::<u8>::new(buf)
+        .slice(&memory.view(ctx), read as u32)
+        .and_then(|slice| slice.write_slice(&data[..read as usize]));
+    Some(if written.is_ok() { read } else { -EINVAL })
+}
+
+/// Writes the `count` bytes at `buf` to the file opened at `fd`, see
+/// [`with_file`]
+pub(crate) fn write(ctx: &FunctionEnvMut<EmEnv>, fd: c_int, buf: u32, count: u32) -> Option<c_int> {
+    if fd < FIRST_VIRTUAL_FD {
+        return None;
+    }
+    let memory = ctx.data().memory(0);
+    let data = WasmPtr::<u8>::new(buf)
+        .slice(&memory.view(ctx), count)
+        .and_then(|slice| slice.read_to_vec());
+    let data = match data {
+        Ok(data) => data,
+        Err(_) => return Some(-EINVAL),
+    };
+    with_file(ctx, fd, |file| match file {
+        OpenFile::File(file) => match block_on(file.write(&data)) {
+            Ok(written) => written as c_int,
+            Err(_) => -EIO,
+        },
+        OpenFile::Dir { .. } => -EISDIR,
+    })
+}
+
+/// Moves the position in the file opened at `fd` and returns the new one,
+/// see [`with_file`]
+pub(crate) fn seek(
+    ctx: &FunctionEnvMut<EmEnv>,
+    fd: c_int,
+    offset: i64,
+    whence: c_int,
+) -> Option<Result<i64, c_int>> {
+    let position = match whence {
+        0 => SeekFrom::Start(offset as u64),
+        1 => SeekFrom::Current(offset),
+        2 => SeekFrom::End(offset),
+        _ => return with_file(ctx, fd, |_| -EINVAL).map(Err),
+    };
+    let mut new_position = 0;
+    let ret = with_file(ctx, fd, |file| match file {
+        OpenFile::File(file) => match block_on(file.seek(position)) {
+            Ok(position) => {
+                new_position = position as i64;
+                0
+            }
+            Err(_) => -EINVAL,
+        },
+        OpenFile::Dir { .. } => -EISDIR,
+    })?;
+    Some(if ret == 0 { Ok(new_position) } else { Err(ret) })
+}
+
+/// Closes the file opened at `fd`, see [`with_file`]
+pub(crate) fn close(ctx: &FunctionEnvMut<EmEnv>, fd: c_int) -> Option<c_int> {
+    if fd < FIRST_VIRTUAL_FD {
+        return None;
+    }
+    let file = ctx.data().fs.lock().unwrap().files.remove(&fd);
+    Some(match file {
+        Some(OpenFile::File(mut file)) => match block_on(file.flush()) {
+            Ok(()) => 0,
+            Err(_) => -EIO,
+        },
+        Some(OpenFile::Dir { .. }) => 0,
+        None => -EBADF,
+    })
+}
+
+/// Writes the `stat` of the file opened at `fd` at `buf`, see
+/// [`with_file`]
+pub(crate) fn fstat(ctx: &FunctionEnvMut<EmEnv>, fd: c_int, buf: u32) -> Option<c_int> {
+    let mut metadata = Metadata::default();
+    let ret = with_file(ctx, fd, |file| {
+        match file {
+            OpenFile::File(file) => {
+                metadata.ft.file = true;
+                metadata.accessed = file.last_accessed();
+                metadata.modified = file.last_modified();
+                metadata.created = file.created_time();
+                metadata.len = file.size();
+            }
+            OpenFile::Dir { .. } => metadata.ft.dir = true,
+        }
+        0
+    })?;
+    if ret == 0 {
+        copy_metadata_into_wasm(ctx, buf, &metadata);
+    }
+    Some(ret)
+}
+
+/// Writes the next entries of the directory opened at `fd` at `dirp`, like
+/// the `getdents` of the host, see [`with_file`]
+pub(crate) fn getdents(
+    ctx: &FunctionEnvMut<EmEnv>,
+    fd: c_int,
+    dirp: u32,
+    count: u32,
+) -> Option<c_int> {
+    let memory = ctx.data().memory(0);
+    let view = memory.view(ctx);
+    with_file(ctx, fd, |file| {
+        let (entries, next) = match file {
+            OpenFile::Dir { entries, next } => (entries, next),
+            OpenFile::File(_) => return -ENOTDIR,
+        };
+        let mut pos = 0;
+        while pos + DIRENT_SIZE <= count && *next < entries.len() {
+            let entry = &entries[*next];
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let name = &name.as_bytes()[..name.len().min(255)];
+            let d_type: u8 = match entry.file_type() {
+                Ok(ft) if ft.is_dir() => 4,
+                Ok(ft) if ft.is_symlink() => 10,
+                Ok(ft) if ft.is_file() => 8,
+                _ => 0,
+            };
+            let mut dirent = vec![0; DIRENT_SIZE as usize];
+            // `d_ino`, the index of the entry stands for the inode
+            dirent[0..4].copy_from_slice(&(*next as u32 + 1).to_le_bytes());
+            dirent[4..8].copy_from_slice(&pos.to_le_bytes());
+            dirent[8..10].copy_from_slice(&(DIRENT_SIZE as u16).to_le_bytes());
+            dirent[10] = d_type;
+            dirent[11..11 + name.len()].copy_from_slice(name);
+            let written = WasmPtr::<u8>::new(dirp + pos)
+                .slice(&view, DIRENT_SIZE)
+                .and_then(|slice| slice.write_slice(&dirent));
+            if written.is_err() {
+                return -EINVAL;
+            }
+            *next += 1;
+            pos += DIRENT_SIZE;
+        }
+        pos as c_int
+    })
+}
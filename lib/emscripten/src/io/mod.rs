@@ -11,7 +11,101 @@ pub use self::unix::*;
 pub use self::windows::*;
 
 use crate::EmEnv;
-use wasmer::FunctionEnvMut;
+use std::io::{Read, Write};
+use wasmer::{FunctionEnvMut, WasmPtr};
+
+/// The standard streams of the module: the ones of the host, unless they
+/// have been replaced with [`EmEnv::set_stdin`], [`EmEnv::set_stdout`] or
+/// [`EmEnv::set_stderr`]
+#[derive(Default)]
+pub(crate) struct Stdio {
+    pub(crate) stdin: Option<Box<dyn Read + Send>>,
+    pub(crate) stdout: Option<Box<dyn Write + Send>>,
+    pub(crate) stderr: Option<Box<dyn Write + Send>>,
+}
+
+impl Stdio {
+    fn output(&mut self, fd: i32) -> Option<&mut Box<dyn Write + Send>> {
+        match fd {
+            1 => self.stdout.as_mut(),
+            2 => self.stderr.as_mut(),
+            _ => None,
+        }
+    }
+}
+
+/// Reads at most `count` bytes at `buf` from the stream replacing the
+/// standard input if `fd` is 0, or returns `None` if the file descriptor
+/// of the host must be used
+pub(crate) fn read_stdio(
+    ctx: &FunctionEnvMut<EmEnv>,
+    fd: i32,
+    buf: u32,
+    count: u32,
+) -> Option<i32> {
+    if fd != 0 {
+        return None;
+    }
+    let mut stdio = ctx.data().stdio.lock().unwrap();
+    let stdin = stdio.stdin.as_mut()?;
+
+    let mut data = vec![0; count as usize];
+    let read = match stdin.read(&mut data) {
+        Ok(read) => read,
+        Err(e) => {
+            debug!("=> stdin error: {}", e);
+            return Some(-1);
+        }
+    };
+    let memory = ctx.data().memory(0);
+    let written = WasmPtr::<u8>::new(buf)
+        .slice(&memory.view(ctx), read as u32)
+        .and_then(|slice| slice.write_slice(&data[..read]));
+    match written {
+        Ok(()) => Some(read as i32),
+        Err(_) => Some(-1),
+    }
+}
+
+/// Writes the `count` bytes at `buf` to the stream replacing the standard
+/// output if `fd` is 1, or the standard error if it's 2, or returns `None`
+/// if the file descriptor of the host must be used
+pub(crate) fn write_stdio(
+    ctx: &FunctionEnvMut<EmEnv>,
+    fd: i32,
+    buf: u32,
+    count: u32,
+) -> Option<i32> {
+    ctx.data().stdio.lock().unwrap().output(fd)?;
+
+    let memory = ctx.data().memory(0);
+    let data = WasmPtr::<u8>::new(buf)
+        .slice(&memory.view(ctx), count)
+        .and_then(|slice| slice.read_to_vec());
+    let data = match data {
+        Ok(data) => data,
+        Err(_) => return Some(-1),
+    };
+    match write_stdio_bytes(ctx, fd, &data)? {
+        Ok(()) => Some(count as i32),
+        Err(e) => {
+            debug!("=> output error: {}", e);
+            Some(-1)
+        }
+    }
+}
+
+/// Writes `data` to the stream replacing the standard output or error, see
+/// [`write_stdio`]
+pub(crate) fn write_stdio_bytes(
+    ctx: &FunctionEnvMut<EmEnv>,
+    fd: i32,
+    data: &[u8],
+) -> Option<std::io::Result<()>> {
+    let mut stdio = ctx.data().stdio.lock().unwrap();
+    let output = stdio.output(fd)?;
+    Some(output.write_all(data).and_then(|()| output.flush()))
+}
 
 /// getprotobyname
 pub fn getprotobyname(_ctx: FunctionEnvMut<EmEnv>, _name_ptr: i32) -> i32 {
@@ -7,8 +7,10 @@ use crate::EmEnv;
 use wasmer::FunctionEnvMut;
 
 /// putchar
-pub fn putchar(_ctx: FunctionEnvMut<EmEnv>, chr: i32) {
-    unsafe { libc::putchar(chr) };
+pub fn putchar(ctx: FunctionEnvMut<EmEnv>, chr: i32) {
+    if super::write_stdio_bytes(&ctx, 1, &[chr as u8]).is_none() {
+        unsafe { libc::putchar(chr) };
+    }
 }
 
 /// printf
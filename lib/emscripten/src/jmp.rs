@@ -1,47 +1,31 @@
-use super::env::get_emscripten_funcs;
+use super::emscripten_target::missing_function;
+use super::env::{call_malloc, get_emscripten_data, get_emscripten_funcs};
 use super::process::abort_with_message;
-use libc::c_int;
-// use std::cell::UnsafeCell;
 use crate::EmEnv;
+use libc::c_int;
 use std::error::Error;
 use std::fmt;
-use wasmer::FunctionEnvMut;
+use wasmer::{FunctionEnvMut, RuntimeError, WasmPtr};
 
 /// setjmp
+///
+/// A host function can't return twice: emscripten lowers the calls to
+/// `setjmp` to `saveSetjmp` and `testSetjmp` instead, so this is only
+/// called by modules built without that support.
 pub fn __setjmp(ctx: FunctionEnvMut<EmEnv>, _env_addr: u32) -> c_int {
     debug!("emscripten::__setjmp (setjmp)");
     abort_with_message(ctx, "missing function: _setjmp");
     unreachable!()
-    // unsafe {
-    //     // Rather than using the env as the holder of the jump buffer pointer,
-    //     // we use the environment address to store the index relative to jumps
-    //     // so the address of the jump it's outside the wasm memory itself.
-    //     let jump_index = emscripten_memory_pointer!(ctx.memory(0), env_addr) as *mut i8;
-    //     // We create the jump buffer outside of the wasm memory
-    //     let jump_buf: UnsafeCell<[u32; 27]> = UnsafeCell::new([0; 27]);
-    //     let jumps = &mut get_emscripten_data(&ctx).jumps;
-    //     let result = setjmp(jump_buf.get() as _);
-    //     // We set the jump index to be the last 3value of jumps
-    //     *jump_index = jumps.len() as _;
-    //     // We hold the reference of the jump buffer
-    //     jumps.push(jump_buf);
-    //     result
-    // }
 }
 
 /// longjmp
-#[allow(unreachable_code)]
-pub fn __longjmp(ctx: FunctionEnvMut<EmEnv>, _env_addr: u32, _val: c_int) {
+pub fn __longjmp(
+    ctx: FunctionEnvMut<EmEnv>,
+    env_addr: u32,
+    val: c_int,
+) -> Result<(), RuntimeError> {
     debug!("emscripten::__longjmp (longmp)");
-    abort_with_message(ctx, "missing function: _longjmp");
-    // unsafe {
-    //     // We retrieve the jump index from the env address
-    //     let jump_index = emscripten_memory_pointer!(ctx.memory(0), env_addr) as *mut i8;
-    //     let jumps = &mut get_emscripten_data(&ctx).jumps;
-    //     // We get the real jump buffer from the jumps vector, using the retrieved index
-    //     let jump_buf = &jumps[*jump_index as usize];
-    //     longjmp(jump_buf.get() as _, val)
-    // };
+    _longjmp(ctx, env_addr as i32, val)
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -62,19 +46,100 @@ pub fn _longjmp(
     mut ctx: FunctionEnvMut<EmEnv>,
     env_addr: i32,
     val: c_int,
-) -> Result<(), LongJumpRet> {
+) -> Result<(), RuntimeError> {
     let val = if val == 0 { 1 } else { val };
     let threw = get_emscripten_funcs(&ctx)
         .set_threw_ref()
-        .expect("set_threw is None")
+        .ok_or_else(|| missing_function("setThrew"))?
         .clone();
-    threw
-        .call(&mut ctx, env_addr, val)
-        .expect("set_threw failed to call");
-    Err(LongJumpRet)
+    threw.call(&mut ctx, env_addr, val)?;
+    Err(RuntimeError::user(Box::new(LongJumpRet)))
+}
+
+/// saveSetjmp
+///
+/// Records a call to `setjmp` in the table of the calling function, made of
+/// `(setjmp id, label)` pairs ended by a null id, and stores the id in the
+/// `jmp_buf`. Every call gets a new id, so that the nested and repeated
+/// calls to `setjmp` can be told apart. The table is reallocated when it's
+/// full, and the new size is returned through `tempRet0`.
+#[allow(non_snake_case)]
+pub fn _saveSetjmp(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    env_addr: u32,
+    label: i32,
+    table: u32,
+    size: u32,
+) -> Result<u32, RuntimeError> {
+    debug!("emscripten::_saveSetjmp");
+    let setjmp_id = {
+        let mut data = get_emscripten_data(&ctx);
+        let data = data.as_mut().unwrap();
+        data.setjmp_id += 1;
+        data.setjmp_id
+    };
+    let memory = ctx.data().memory(0);
+    WasmPtr::<i32>::new(env_addr).write(&memory.view(&ctx), setjmp_id)?;
+
+    let mut table = table;
+    let mut size = size;
+    let mut index = 0;
+    loop {
+        let view = memory.view(&ctx);
+        let slots = WasmPtr::<i32>::new(table).slice(&view, 2 * size + 2)?;
+        while index < size {
+            if slots.read(2 * index as u64)? == 0 {
+                slots.write(2 * index as u64, setjmp_id)?;
+                slots.write(2 * index as u64 + 1, label)?;
+                // The next slot ends the table
+                slots.write(2 * index as u64 + 2, 0)?;
+                get_emscripten_data(&ctx).as_mut().unwrap().temp_ret_0 = size as i32;
+                return Ok(table);
+            }
+            index += 1;
+        }
+
+        // The table is full, double its size
+        let entries = slots.read_to_vec()?;
+        let new_size = (size * 2).max(4);
+        let new_table = call_malloc(&mut ctx, 8 * (new_size + 1));
+        let view = memory.view(&ctx);
+        WasmPtr::<i32>::new(new_table)
+            .slice(&view, 2 * size + 2)?
+            .write_slice(&entries)?;
+        let free = get_emscripten_funcs(&ctx).free_ref().cloned();
+        if let Some(free) = free {
+            free.call(&mut ctx, table)?;
+        }
+        table = new_table;
+        size = new_size;
+    }
 }
 
-// extern "C" {
-//     fn setjmp(env: *mut c_void) -> c_int;
-//     fn longjmp(env: *mut c_void, val: c_int) -> !;
-// }
+/// testSetjmp
+///
+/// Returns the label recorded by `saveSetjmp` for the id `id` in the table
+/// of the calling function, or 0 if the `longjmp` targets a `setjmp` of
+/// another function.
+#[allow(non_snake_case)]
+pub fn _testSetjmp(
+    ctx: FunctionEnvMut<EmEnv>,
+    id: i32,
+    table: u32,
+    size: u32,
+) -> Result<i32, RuntimeError> {
+    debug!("emscripten::_testSetjmp");
+    let memory = ctx.data().memory(0);
+    let view = memory.view(&ctx);
+    let slots = WasmPtr::<i32>::new(table).slice(&view, 2 * size)?;
+    for index in 0..size as u64 {
+        let current = slots.read(2 * index)?;
+        if current == 0 {
+            break;
+        }
+        if current == id {
+            return Ok(slots.read(2 * index + 1)?);
+        }
+    }
+    Ok(0)
+}
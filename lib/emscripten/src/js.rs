@@ -0,0 +1,189 @@
+//! The snippets of JavaScript embedded in the modules with `EM_ASM` and
+//! `EM_JS`.
+//!
+//! They can't be run, but the embedder can adapt a module using them by
+//! registering host callbacks standing for them, with
+//! [`EmEnv::set_em_asm_handler`] and [`EmEnv::set_em_js_function`].
+
+use crate::EmEnv;
+use std::collections::HashMap;
+use std::sync::Arc;
+use wasmer::{
+    AsStoreMut, Exports, Function, FunctionEnv, FunctionEnvMut, FunctionType, Module, RuntimeError,
+    Value, WasmPtr,
+};
+use wasmer_types::Type as ValType;
+
+/// The callback standing for the `EM_ASM` snippets, see
+/// [`EmEnv::set_em_asm_handler`]
+pub type EmAsmHandler =
+    Arc<dyn Fn(&mut FunctionEnvMut<EmEnv>, &EmAsmCall) -> Result<f64, RuntimeError> + Send + Sync>;
+
+/// A callback standing for an `EM_JS` function, see
+/// [`EmEnv::set_em_js_function`]
+pub type EmJsFunction = Arc<
+    dyn Fn(&mut FunctionEnvMut<EmEnv>, &[Value]) -> Result<Vec<Value>, RuntimeError> + Send + Sync,
+>;
+
+/// The snippet run by an `EM_ASM` call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmAsmCode {
+    /// The JavaScript source of the snippet, which the LLVM backend stores
+    /// in the memory of the module
+    Source(String),
+    /// The index of the snippet in the JavaScript glue generated by the
+    /// `fastcomp` backend, which keeps their source
+    Index(i32),
+}
+
+/// A call to an `EM_ASM` snippet
+#[derive(Debug, Clone)]
+pub struct EmAsmCall {
+    pub code: EmAsmCode,
+    /// The arguments of the snippet, as `I32` and `F64` values: the `float`
+    /// arguments are promoted to `double`
+    pub args: Vec<Value>,
+}
+
+/// The callbacks registered by the embedder, shared by the threads
+#[derive(Default)]
+pub(crate) struct JsCallbacks {
+    pub(crate) em_asm: Option<EmAsmHandler>,
+    pub(crate) em_js: HashMap<String, EmJsFunction>,
+}
+
+/// The types of the functions imported from `env`, to create the dynamic
+/// functions standing for the snippets
+pub(crate) fn env_function_types(module: &Module) -> HashMap<String, FunctionType> {
+    module
+        .imports()
+        .functions()
+        .filter(|import| import.module() == "env")
+        .map(|import| (import.name().to_string(), import.ty().clone()))
+        .collect()
+}
+
+/// Runs an `EM_ASM` snippet with the handler of the embedder, or fails
+/// telling which snippet isn't supported
+fn call_em_asm(ctx: &mut FunctionEnvMut<EmEnv>, call: EmAsmCall) -> Result<f64, RuntimeError> {
+    let handler = ctx.data().js.lock().unwrap().em_asm.clone();
+    match handler {
+        // The lock isn't held, as the handler may call back into the module
+        Some(handler) => handler(ctx, &call),
+        None => Err(RuntimeError::new(match call.code {
+            EmAsmCode::Source(source) => format!("unsupported EM_ASM snippet: {}", source),
+            EmAsmCode::Index(index) => format!("unsupported EM_ASM snippet #{}", index),
+        })),
+    }
+}
+
+/// Reads the `EM_ASM` call whose source is at `code`, and whose arguments
+/// are in the buffer at `argbuf`, laid out as described by the signature
+/// at `sig`: one character per argument, `d` (or `f`) for a `double`
+/// aligned on 8 bytes, `i` for an `int`.
+fn read_em_asm_call(
+    ctx: &FunctionEnvMut<EmEnv>,
+    code: u32,
+    sig: u32,
+    argbuf: u32,
+) -> Result<EmAsmCall, RuntimeError> {
+    let memory = ctx.data().memory(0);
+    let view = memory.view(ctx);
+    let source = WasmPtr::<u8>::new(code).read_utf8_string_with_nul(&view)?;
+
+    let mut args = vec![];
+    let mut sig = sig;
+    let mut offset = argbuf;
+    loop {
+        let ch = WasmPtr::<u8>::new(sig).read(&view)?;
+        if ch == 0 {
+            break;
+        }
+        sig += 1;
+        if ch < b'i' {
+            offset = (offset + 7) & !7;
+            args.push(Value::F64(WasmPtr::<f64>::new(offset).read(&view)?));
+            offset += 8;
+        } else {
+            args.push(Value::I32(WasmPtr::<i32>::new(offset).read(&view)?));
+            offset += 4;
+        }
+    }
+
+    Ok(EmAsmCall {
+        code: EmAsmCode::Source(source),
+        args,
+    })
+}
+
+/// emscripten_asm_const_int
+pub fn emscripten_asm_const_int(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    code: u32,
+    sig: u32,
+    argbuf: u32,
+) -> Result<i32, RuntimeError> {
+    debug!("emscripten::emscripten_asm_const_int");
+    let call = read_em_asm_call(&ctx, code, sig, argbuf)?;
+    Ok(call_em_asm(&mut ctx, call)? as i32)
+}
+
+/// emscripten_asm_const_double
+pub fn emscripten_asm_const_double(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    code: u32,
+    sig: u32,
+    argbuf: u32,
+) -> Result<f64, RuntimeError> {
+    debug!("emscripten::emscripten_asm_const_double");
+    let call = read_em_asm_call(&ctx, code, sig, argbuf)?;
+    call_em_asm(&mut ctx, call)
+}
+
+/// Adds to `env_ns` the `_emscripten_asm_const_*` imports of a module built
+/// with `fastcomp`, one per signature of snippet, which take the index of
+/// the snippet and its arguments, and the `EM_JS` functions registered by
+/// the embedder that the module imports
+pub(crate) fn add_js_imports(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<EmEnv>,
+    env_function_types: &HashMap<String, FunctionType>,
+    env_ns: &mut Exports,
+) {
+    for (name, ty) in env_function_types.iter() {
+        if !name.starts_with("_emscripten_asm_const_") || env_ns.contains(name.as_str()) {
+            continue;
+        }
+        let result = ty.results().first().copied();
+        let function = Function::new_with_env(store, env, ty, move |mut ctx, args| {
+            debug!("emscripten::_emscripten_asm_const_*");
+            let call = EmAsmCall {
+                code: EmAsmCode::Index(args.first().and_then(Value::i32).unwrap_or(0)),
+                args: args.iter().skip(1).cloned().collect(),
+            };
+            let value = call_em_asm(&mut ctx, call)?;
+            Ok(match result {
+                Some(ValType::I32) => vec![Value::I32(value as i32)],
+                Some(ValType::I64) => vec![Value::I64(value as i64)],
+                Some(ValType::F32) => vec![Value::F32(value as f32)],
+                Some(ValType::F64) => vec![Value::F64(value)],
+                _ => vec![],
+            })
+        });
+        env_ns.insert(name.as_str(), function);
+    }
+
+    let em_js = env.as_ref(store).js.lock().unwrap().em_js.clone();
+    for (name, callback) in em_js {
+        // `fastcomp` prefixes the C symbols with `_`
+        for import_name in [name.clone(), format!("_{}", name)] {
+            if let Some(ty) = env_function_types.get(&import_name) {
+                let callback = callback.clone();
+                let function = Function::new_with_env(store, env, ty, move |mut ctx, args| {
+                    callback(&mut ctx, args)
+                });
+                env_ns.insert(import_name, function);
+            }
+        }
+    }
+}
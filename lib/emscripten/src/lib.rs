@@ -42,6 +42,7 @@ type LibcDir = u8;
 mod macros;
 
 // EMSCRIPTEN APIS
+mod abi;
 mod bitwise;
 mod emscripten_target;
 mod env;
@@ -49,9 +50,11 @@ mod errno;
 mod exception;
 mod exec;
 mod exit;
+mod fs;
 mod inet;
 mod io;
 mod jmp;
+mod js;
 mod libc;
 mod linking;
 mod lock;
@@ -68,6 +71,8 @@ mod unistd;
 mod utils;
 mod varargs;
 
+pub use self::abi::{get_emscripten_abi, EmscriptenAbi, EmscriptenBackend};
+pub use self::js::{EmAsmCall, EmAsmCode, EmAsmHandler, EmJsFunction};
 pub use self::storage::{align_memory, static_alloc};
 pub use self::utils::{
     allocate_cstr_on_stack, allocate_on_stack, get_emscripten_memory_size, get_emscripten_metadata,
@@ -81,6 +86,9 @@ pub struct EmscriptenState {
     pub env_vars: HashMap<String, String>,
     /// Command line arguments that this module received
     pub cli_args: Vec<String>,
+    /// Whether the module can use the host network through the socket
+    /// syscalls (`socketcall` and `select`); disabled by default
+    pub networking: bool,
 }
 
 impl Default for EmscriptenState {
@@ -90,6 +98,7 @@ impl Default for EmscriptenState {
                 .filter_map(|(k, v)| Some((k.to_str()?.to_string(), v.to_str()?.to_string())))
                 .collect(),
             cli_args: Vec::new(),
+            networking: false,
         }
     }
 }
@@ -100,6 +109,14 @@ pub struct EmEnv {
     memory: Arc<RwLock<Option<Memory>>>,
     data: Arc<Mutex<Option<EmscriptenData>>>,
     funcs: Arc<Mutex<EmscriptenFunctions>>,
+    linker: Arc<Mutex<linking::DynamicLinker>>,
+    // The `pthread_t` of the thread running the instance, and the threads
+    // of the program
+    thread_id: u32,
+    threads: Arc<pthread::Threads>,
+    stdio: Arc<Mutex<io::Stdio>>,
+    fs: Arc<Mutex<fs::Filesystem>>,
+    js: Arc<Mutex<js::JsCallbacks>>,
     // State that is passed to the wasm module (environment variables, CLI args, ...)
     #[allow(dead_code)]
     state: Arc<Mutex<EmscriptenState>>,
@@ -118,6 +135,12 @@ impl EmEnv {
             memory: Arc::new(RwLock::new(None)),
             data: Arc::new(Mutex::new(None)),
             funcs: Arc::new(Mutex::new(EmscriptenFunctions::new())),
+            linker: Arc::new(Mutex::new(linking::DynamicLinker::default())),
+            thread_id: pthread::MAIN_THREAD_ID,
+            threads: Arc::default(),
+            stdio: Arc::default(),
+            fs: Arc::default(),
+            js: Arc::default(),
             state: Arc::new(Mutex::new(EmscriptenState::default())),
         }
     }
@@ -127,10 +150,33 @@ impl EmEnv {
             memory: Arc::new(RwLock::new(None)),
             data: Arc::new(Mutex::new(None)),
             funcs: Arc::new(Mutex::new(EmscriptenFunctions::new())),
+            linker: Arc::new(Mutex::new(linking::DynamicLinker::default())),
+            thread_id: pthread::MAIN_THREAD_ID,
+            threads: Arc::default(),
+            stdio: Arc::default(),
+            fs: Arc::default(),
+            js: Arc::default(),
             state: Arc::new(Mutex::new(emstate)),
         }
     }
 
+    /// Create the environment of a new thread of the program, which shares
+    /// its state with this one
+    pub(crate) fn new_thread(&self, thread_id: u32) -> Self {
+        Self {
+            memory: Arc::new(RwLock::new(None)),
+            data: Arc::new(Mutex::new(None)),
+            funcs: Arc::new(Mutex::new(EmscriptenFunctions::new())),
+            linker: Arc::new(Mutex::new(linking::DynamicLinker::default())),
+            thread_id,
+            threads: self.threads.clone(),
+            stdio: self.stdio.clone(),
+            fs: self.fs.clone(),
+            js: self.js.clone(),
+            state: self.state.clone(),
+        }
+    }
+
     pub fn set_memory(&self, memory: Memory) {
         let mut w = self.memory.write().unwrap();
         *w = Some(memory);
@@ -181,6 +227,77 @@ impl EmEnv {
         let w = self.state.lock().unwrap();
         w.cli_args.clone()
     }
+
+    /// Replace the standard input of the module, e.g. to feed it from the
+    /// embedder; it's the one of the host by default
+    pub fn set_stdin(&self, stdin: Box<dyn std::io::Read + Send>) {
+        self.stdio.lock().unwrap().stdin = Some(stdin);
+    }
+
+    /// Replace the standard output of the module, e.g. to capture it with
+    /// one end of a pipe
+    pub fn set_stdout(&self, stdout: Box<dyn std::io::Write + Send>) {
+        self.stdio.lock().unwrap().stdout = Some(stdout);
+    }
+
+    /// Replace the standard error of the module
+    pub fn set_stderr(&self, stderr: Box<dyn std::io::Write + Send>) {
+        self.stdio.lock().unwrap().stderr = Some(stderr);
+    }
+
+    /// Make the module access its files through `filesystem` instead of
+    /// the filesystem of the host; the mapped directories are then looked
+    /// up in it.
+    ///
+    /// The operations on the files are run on the thread of the syscall,
+    /// so their futures must not need an async runtime.
+    pub fn set_filesystem(&self, filesystem: Box<dyn wasmer_vfs::FileSystem>) {
+        self.fs.lock().unwrap().set(filesystem);
+    }
+
+    /// Run `handler` instead of the JavaScript snippets of the module
+    /// embedded with `EM_ASM`; it gets the snippet and its arguments, and
+    /// returns the value the snippet would, as a JavaScript number.
+    ///
+    /// Without one, calling a snippet traps.
+    pub fn set_em_asm_handler<F>(&self, handler: F)
+    where
+        F: Fn(&mut FunctionEnvMut<EmEnv>, &EmAsmCall) -> Result<f64, RuntimeError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.js.lock().unwrap().em_asm = Some(Arc::new(handler));
+    }
+
+    /// Provide the function `name` defined in JavaScript with `EM_JS`, which
+    /// the module imports; it must be registered before generating the
+    /// imports with [`generate_emscripten_env`].
+    pub fn set_em_js_function<F>(&self, name: &str, function: F)
+    where
+        F: Fn(&mut FunctionEnvMut<EmEnv>, &[Value]) -> Result<Vec<Value>, RuntimeError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.js
+            .lock()
+            .unwrap()
+            .em_js
+            .insert(name.to_string(), Arc::new(function));
+    }
+
+    /// Grant or revoke the access to the host network
+    pub fn set_networking(&self, enabled: bool) {
+        let mut w = self.state.lock().unwrap();
+        w.networking = enabled;
+    }
+
+    /// Whether the module can open sockets on the host network
+    pub fn networking(&self) -> bool {
+        let w = self.state.lock().unwrap();
+        w.networking
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -289,6 +406,7 @@ pub struct EmscriptenFunctions {
     pub stack_save: Option<TypedFunction<(), i32>>,
     pub stack_restore: Option<TypedFunction<i32, ()>>,
     pub set_threw: Option<TypedFunction<(i32, i32), ()>>,
+    pub cxa_can_catch: Option<TypedFunction<(i32, i32, i32), i32>>,
 }
 
 #[derive(Clone, Default)]
@@ -299,6 +417,8 @@ pub struct EmscriptenData {
     pub opened_dirs: HashMap<i32, Box<LibcDirWrapper>>,
 
     pub temp_ret_0: i32,
+    pub(crate) setjmp_id: i32,
+    pub(crate) exceptions: exception::Exceptions,
 
     pub mapped_dirs: HashMap<String, PathBuf>,
 }
@@ -323,6 +443,253 @@ impl EmscriptenFunctions {
             ..Default::default()
         }
     }
+
+    /// Looks up the functions exported by an emscripten `instance`
+    pub fn from_instance(store: &impl AsStoreRef, instance: &Instance) -> EmscriptenFunctions {
+        let mut emfuncs = EmscriptenFunctions::new();
+        if let Ok(func) = instance.exports.get_typed_function(store, "malloc") {
+            emfuncs.malloc = Some(func);
+        } else if let Ok(func) = instance.exports.get_typed_function(store, "_malloc") {
+            emfuncs.malloc = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "free") {
+            emfuncs.free = Some(func);
+        } else if let Ok(func) = instance.exports.get_typed_function(store, "_free") {
+            emfuncs.free = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "memalign") {
+            emfuncs.memalign = Some(func);
+        } else if let Ok(func) = instance.exports.get_typed_function(store, "_memalign") {
+            emfuncs.memalign = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "memset") {
+            emfuncs.memset = Some(func);
+        } else if let Ok(func) = instance.exports.get_typed_function(store, "_memset") {
+            emfuncs.memset = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "stackAlloc") {
+            emfuncs.stack_alloc = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_i") {
+            emfuncs.dyn_call_i = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_ii") {
+            emfuncs.dyn_call_ii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_iii") {
+            emfuncs.dyn_call_iii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_iiii") {
+            emfuncs.dyn_call_iiii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_iifi") {
+            emfuncs.dyn_call_iifi = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_v") {
+            emfuncs.dyn_call_v = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_vi") {
+            emfuncs.dyn_call_vi = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_vii") {
+            emfuncs.dyn_call_vii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_viii") {
+            emfuncs.dyn_call_viii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_viiii") {
+            emfuncs.dyn_call_viiii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_dii") {
+            emfuncs.dyn_call_dii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_diiii") {
+            emfuncs.dyn_call_diiii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_iiiii") {
+            emfuncs.dyn_call_iiiii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_iiiiii") {
+            emfuncs.dyn_call_iiiiii = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "dynCall_iiiiiii")
+        {
+            emfuncs.dyn_call_iiiiiii = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "dynCall_iiiiiiii")
+        {
+            emfuncs.dyn_call_iiiiiiii = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "dynCall_iiiiiiiii")
+        {
+            emfuncs.dyn_call_iiiiiiiii = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "dynCall_iiiiiiiiii")
+        {
+            emfuncs.dyn_call_iiiiiiiiii = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "dynCall_iiiiiiiiiii")
+        {
+            emfuncs.dyn_call_iiiiiiiiiii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_vd") {
+            emfuncs.dyn_call_vd = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_viiiii") {
+            emfuncs.dyn_call_viiiii = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "dynCall_viiiiii")
+        {
+            emfuncs.dyn_call_viiiiii = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "dynCall_viiiiiii")
+        {
+            emfuncs.dyn_call_viiiiiii = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "dynCall_viiiiiiii")
+        {
+            emfuncs.dyn_call_viiiiiiii = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "dynCall_viiiiiiiii")
+        {
+            emfuncs.dyn_call_viiiiiiiii = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "dynCall_viiiiiiiiii")
+        {
+            emfuncs.dyn_call_viiiiiiiiii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_iij") {
+            emfuncs.dyn_call_iij = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_iji") {
+            emfuncs.dyn_call_iji = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_iiji") {
+            emfuncs.dyn_call_iiji = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_iiijj") {
+            emfuncs.dyn_call_iiijj = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_j") {
+            emfuncs.dyn_call_j = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_ji") {
+            emfuncs.dyn_call_ji = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_jii") {
+            emfuncs.dyn_call_jii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_jij") {
+            emfuncs.dyn_call_jij = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_jjj") {
+            emfuncs.dyn_call_jjj = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_viiij") {
+            emfuncs.dyn_call_viiij = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "dynCall_viiijiiii")
+        {
+            emfuncs.dyn_call_viiijiiii = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "dynCall_viiijiiiiii")
+        {
+            emfuncs.dyn_call_viiijiiiiii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_viij") {
+            emfuncs.dyn_call_viij = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_viiji") {
+            emfuncs.dyn_call_viiji = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "dynCall_viijiii")
+        {
+            emfuncs.dyn_call_viijiii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_viijj") {
+            emfuncs.dyn_call_viijj = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_vj") {
+            emfuncs.dyn_call_vj = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_vjji") {
+            emfuncs.dyn_call_vjji = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_vij") {
+            emfuncs.dyn_call_vij = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_viji") {
+            emfuncs.dyn_call_viji = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_vijiii") {
+            emfuncs.dyn_call_vijiii = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_vijj") {
+            emfuncs.dyn_call_vijj = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_viid") {
+            emfuncs.dyn_call_viid = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_vidd") {
+            emfuncs.dyn_call_vidd = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "dynCall_viidii") {
+            emfuncs.dyn_call_viidii = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "dynCall_viidddddddd")
+        {
+            emfuncs.dyn_call_viidddddddd = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "stackSave") {
+            emfuncs.stack_save = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "stackRestore") {
+            emfuncs.stack_restore = Some(func);
+        }
+        if let Ok(func) = instance.exports.get_typed_function(store, "setThrew") {
+            emfuncs.set_threw = Some(func);
+        }
+        if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "___cxa_can_catch")
+        {
+            emfuncs.cxa_can_catch = Some(func);
+        } else if let Ok(func) = instance
+            .exports
+            .get_typed_function(store, "__cxa_can_catch")
+        {
+            emfuncs.cxa_can_catch = Some(func);
+        }
+        emfuncs
+    }
     pub fn malloc_ref(&self) -> Option<&TypedFunction<u32, u32>> {
         self.malloc.as_ref()
     }
@@ -546,6 +913,9 @@ impl EmscriptenFunctions {
     pub fn set_threw_ref(&self) -> Option<&TypedFunction<(i32, i32), ()>> {
         self.set_threw.as_ref()
     }
+    pub fn cxa_can_catch_ref(&self) -> Option<&TypedFunction<(i32, i32, i32), i32>> {
+        self.cxa_can_catch.as_ref()
+    }
 }
 
 /// Call the global constructors for C++ and set up the emscripten environment.
@@ -662,232 +1032,15 @@ pub fn run_emscripten_instance(
     env.data_mut().set_memory(globals.memory.clone());
 
     // get emscripten export
-    let mut emfuncs = EmscriptenFunctions::new();
-    if let Ok(func) = instance.exports.get_typed_function(&env, "malloc") {
-        emfuncs.malloc = Some(func);
-    } else if let Ok(func) = instance.exports.get_typed_function(&env, "_malloc") {
-        emfuncs.malloc = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "free") {
-        emfuncs.free = Some(func);
-    } else if let Ok(func) = instance.exports.get_typed_function(&env, "_free") {
-        emfuncs.free = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "memalign") {
-        emfuncs.memalign = Some(func);
-    } else if let Ok(func) = instance.exports.get_typed_function(&env, "_memalign") {
-        emfuncs.memalign = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "memset") {
-        emfuncs.memset = Some(func);
-    } else if let Ok(func) = instance.exports.get_typed_function(&env, "_memset") {
-        emfuncs.memset = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "stackAlloc") {
-        emfuncs.stack_alloc = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_i") {
-        emfuncs.dyn_call_i = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_ii") {
-        emfuncs.dyn_call_ii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_iii") {
-        emfuncs.dyn_call_iii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_iiii") {
-        emfuncs.dyn_call_iiii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_iifi") {
-        emfuncs.dyn_call_iifi = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_v") {
-        emfuncs.dyn_call_v = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_vi") {
-        emfuncs.dyn_call_vi = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_vii") {
-        emfuncs.dyn_call_vii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_viii") {
-        emfuncs.dyn_call_viii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_viiii") {
-        emfuncs.dyn_call_viiii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_dii") {
-        emfuncs.dyn_call_dii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_diiii") {
-        emfuncs.dyn_call_diiii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_iiiii") {
-        emfuncs.dyn_call_iiiii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_iiiiii") {
-        emfuncs.dyn_call_iiiiii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_iiiiiii") {
-        emfuncs.dyn_call_iiiiiii = Some(func);
-    }
-    if let Ok(func) = instance
-        .exports
-        .get_typed_function(&env, "dynCall_iiiiiiii")
-    {
-        emfuncs.dyn_call_iiiiiiii = Some(func);
-    }
-    if let Ok(func) = instance
-        .exports
-        .get_typed_function(&env, "dynCall_iiiiiiiii")
-    {
-        emfuncs.dyn_call_iiiiiiiii = Some(func);
-    }
-    if let Ok(func) = instance
-        .exports
-        .get_typed_function(&env, "dynCall_iiiiiiiiii")
-    {
-        emfuncs.dyn_call_iiiiiiiiii = Some(func);
-    }
-    if let Ok(func) = instance
-        .exports
-        .get_typed_function(&env, "dynCall_iiiiiiiiiii")
-    {
-        emfuncs.dyn_call_iiiiiiiiiii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_vd") {
-        emfuncs.dyn_call_vd = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_viiiii") {
-        emfuncs.dyn_call_viiiii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_viiiiii") {
-        emfuncs.dyn_call_viiiiii = Some(func);
-    }
-    if let Ok(func) = instance
-        .exports
-        .get_typed_function(&env, "dynCall_viiiiiii")
-    {
-        emfuncs.dyn_call_viiiiiii = Some(func);
-    }
-    if let Ok(func) = instance
-        .exports
-        .get_typed_function(&env, "dynCall_viiiiiiii")
-    {
-        emfuncs.dyn_call_viiiiiiii = Some(func);
-    }
-    if let Ok(func) = instance
-        .exports
-        .get_typed_function(&env, "dynCall_viiiiiiiii")
-    {
-        emfuncs.dyn_call_viiiiiiiii = Some(func);
-    }
-    if let Ok(func) = instance
-        .exports
-        .get_typed_function(&env, "dynCall_viiiiiiiiii")
-    {
-        emfuncs.dyn_call_viiiiiiiiii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_iij") {
-        emfuncs.dyn_call_iij = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_iji") {
-        emfuncs.dyn_call_iji = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_iiji") {
-        emfuncs.dyn_call_iiji = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_iiijj") {
-        emfuncs.dyn_call_iiijj = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_j") {
-        emfuncs.dyn_call_j = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_ji") {
-        emfuncs.dyn_call_ji = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_jii") {
-        emfuncs.dyn_call_jii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_jij") {
-        emfuncs.dyn_call_jij = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_jjj") {
-        emfuncs.dyn_call_jjj = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_viiij") {
-        emfuncs.dyn_call_viiij = Some(func);
-    }
-    if let Ok(func) = instance
-        .exports
-        .get_typed_function(&env, "dynCall_viiijiiii")
-    {
-        emfuncs.dyn_call_viiijiiii = Some(func);
-    }
-    if let Ok(func) = instance
-        .exports
-        .get_typed_function(&env, "dynCall_viiijiiiiii")
-    {
-        emfuncs.dyn_call_viiijiiiiii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_viij") {
-        emfuncs.dyn_call_viij = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_viiji") {
-        emfuncs.dyn_call_viiji = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_viijiii") {
-        emfuncs.dyn_call_viijiii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_viijj") {
-        emfuncs.dyn_call_viijj = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_vj") {
-        emfuncs.dyn_call_vj = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_vjji") {
-        emfuncs.dyn_call_vjji = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_vij") {
-        emfuncs.dyn_call_vij = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_viji") {
-        emfuncs.dyn_call_viji = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_vijiii") {
-        emfuncs.dyn_call_vijiii = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_vijj") {
-        emfuncs.dyn_call_vijj = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_viid") {
-        emfuncs.dyn_call_viid = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_vidd") {
-        emfuncs.dyn_call_vidd = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "dynCall_viidii") {
-        emfuncs.dyn_call_viidii = Some(func);
-    }
-    if let Ok(func) = instance
-        .exports
-        .get_typed_function(&env, "dynCall_viidddddddd")
-    {
-        emfuncs.dyn_call_viidddddddd = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "stackSave") {
-        emfuncs.stack_save = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "stackRestore") {
-        emfuncs.stack_restore = Some(func);
-    }
-    if let Ok(func) = instance.exports.get_typed_function(&env, "setThrew") {
-        emfuncs.set_threw = Some(func);
-    }
-    env.data().set_functions(emfuncs);
+    env.data()
+        .set_functions(EmscriptenFunctions::from_instance(&env, instance));
+
+    linking::set_main_instance(&mut env, instance, globals.data.memory_base)
+        .map_err(RuntimeError::new)?;
 
     set_up_emscripten(&mut env, instance)?;
 
+    let threads = env.data().threads.clone();
     let main_func_names = ["_main", "main"];
     if let Some(ep) = entrypoint.as_ref() {
         debug!("Running entry point: {}", ep);
@@ -899,6 +1052,8 @@ pub fn run_emscripten_instance(
             "No main function found (searched: {main_func_names:?}) and no entrypoint specified"
         )));
     }
+    // The program fails with any of its threads
+    threads.check_failure()?;
 
     // TODO atexit for emscripten
     // println!("{:?}", data);
@@ -965,6 +1120,12 @@ pub struct EmscriptenGlobals {
     pub memory_min: Pages,
     pub memory_max: Option<Pages>,
     pub null_function_names: Vec<String>,
+    // The `GOT.mem` and `GOT.func` imports of a main module
+    pub got_imports: Vec<(String, String)>,
+    // The ABI of the emscripten release that built the module
+    pub abi: EmscriptenAbi,
+    // The types of the functions imported from `env`
+    pub(crate) env_function_types: HashMap<String, FunctionType>,
 }
 
 impl EmscriptenGlobals {
@@ -983,6 +1144,7 @@ impl EmscriptenGlobals {
             }
         }
 
+        let abi = get_emscripten_abi(module)?;
         let (table_min, table_max) = get_emscripten_table_size(module)?;
         let (memory_min, memory_max, shared) = get_emscripten_memory_size(module)?;
 
@@ -1034,16 +1196,7 @@ impl EmscriptenGlobals {
         };
 
         emscripten_set_up_memory(store, env, &memory, &data)?;
-
-        let mut null_function_names = vec![];
-        for import in module.imports().functions() {
-            if import.module() == "env"
-                && (import.name().starts_with("nullFunction_")
-                    || import.name().starts_with("nullFunc_"))
-            {
-                null_function_names.push(import.name().to_string())
-            }
-        }
+        env.as_ref(store).threads.set_module(module);
 
         Ok(Self {
             data,
@@ -1051,11 +1204,59 @@ impl EmscriptenGlobals {
             table,
             memory_min,
             memory_max,
-            null_function_names,
+            null_function_names: null_function_names(module),
+            got_imports: linking::main_got_imports(module),
+            abi,
+            env_function_types: js::env_function_types(module),
+        })
+    }
+
+    /// Create the globals of a new thread of the program, which uses the
+    /// shared `memory` that is already set up, and its own table
+    pub(crate) fn new_thread(
+        mut store: &mut impl AsStoreMut,
+        module: &Module,
+        memory: Memory,
+        data: EmscriptenGlobalsData,
+    ) -> Result<Self, String> {
+        let abi = get_emscripten_abi(module)?;
+        let (table_min, table_max) = get_emscripten_table_size(module)?;
+        let table_type = TableType {
+            ty: ValType::FuncRef,
+            minimum: table_min,
+            maximum: table_max,
+        };
+        let table =
+            Table::new(&mut store, table_type, Value::FuncRef(None)).map_err(|e| e.to_string())?;
+        let memory_type = memory.ty(store);
+
+        Ok(Self {
+            data,
+            memory,
+            table,
+            memory_min: memory_type.minimum,
+            memory_max: memory_type.maximum,
+            null_function_names: null_function_names(module),
+            got_imports: linking::main_got_imports(module),
+            abi,
+            env_function_types: js::env_function_types(module),
         })
     }
 }
 
+fn null_function_names(module: &Module) -> Vec<String> {
+    let mut null_function_names = vec![];
+    for import in module.imports().functions() {
+        if import.module() == "env"
+            && (import.name().starts_with("nullFunction_")
+                || import.name().starts_with("nullFunc_"))
+        {
+            null_function_names.push(import.name().to_string())
+        }
+    }
+    null_function_names
+}
+
 pub fn generate_emscripten_env(
     mut store: &mut impl AsStoreMut,
     env: &FunctionEnv<EmEnv>,
@@ -1074,6 +1275,7 @@ pub fn generate_emscripten_env(
     let mut env_ns: Exports = namespace! {
         "memory" => globals.memory.clone(),
         "table" => globals.table.clone(),
+        "__indirect_function_table" => globals.table.clone(),
 
         // Globals
         "STACKTOP" => Global::new(&mut store, Value::I32(globals.data.stacktop as i32)),
@@ -1282,7 +1484,10 @@ pub fn generate_emscripten_env(
         "_waitpid" => Function::new_typed_with_env(&mut store, env, crate::process::_waitpid),
 
         // Emscripten
-        "_emscripten_asm_const_i" => Function::new_typed_with_env(&mut store, env, crate::emscripten_target::asm_const_i),
+        "emscripten_asm_const_int" => Function::new_typed_with_env(&mut store, env, crate::js::emscripten_asm_const_int),
+        "emscripten_asm_const_int_sync_on_main_thread" => Function::new_typed_with_env(&mut store, env, crate::js::emscripten_asm_const_int),
+        "emscripten_asm_const_double" => Function::new_typed_with_env(&mut store, env, crate::js::emscripten_asm_const_double),
+        "emscripten_asm_const_double_sync_on_main_thread" => Function::new_typed_with_env(&mut store, env, crate::js::emscripten_asm_const_double),
         "_emscripten_exit_with_live_runtime" => Function::new_typed_with_env(&mut store, env, crate::emscripten_target::exit_with_live_runtime),
 
         // Signal
@@ -1313,9 +1518,12 @@ pub fn generate_emscripten_env(
         "___cxa_increment_exception_refcount" => Function::new_typed_with_env(&mut store, env, crate::exception::___cxa_increment_exception_refcount),
         "___cxa_rethrow_primary_exception" => Function::new_typed_with_env(&mut store, env, crate::exception::___cxa_rethrow_primary_exception),
         "___cxa_throw" => Function::new_typed_with_env(&mut store, env, crate::exception::___cxa_throw),
+        "___cxa_rethrow" => Function::new_typed_with_env(&mut store, env, crate::exception::___cxa_rethrow),
+        "___cxa_call_unexpected" => Function::new_typed_with_env(&mut store, env, crate::exception::___cxa_call_unexpected),
         "___cxa_begin_catch" => Function::new_typed_with_env(&mut store, env, crate::exception::___cxa_begin_catch),
         "___cxa_end_catch" => Function::new_typed_with_env(&mut store, env, crate::exception::___cxa_end_catch),
         "___cxa_uncaught_exception" => Function::new_typed_with_env(&mut store, env, crate::exception::___cxa_uncaught_exception),
+        "___cxa_uncaught_exceptions" => Function::new_typed_with_env(&mut store, env, crate::exception::___cxa_uncaught_exceptions),
         "___cxa_pure_virtual" => Function::new_typed_with_env(&mut store, env, crate::exception::___cxa_pure_virtual),
 
         // Time
@@ -1364,6 +1572,9 @@ pub fn generate_emscripten_env(
         "__longjmp" => Function::new_typed_with_env(&mut store, env, crate::jmp::__longjmp),
         "_longjmp" => Function::new_typed_with_env(&mut store, env, crate::jmp::_longjmp),
         "_emscripten_longjmp" => Function::new_typed_with_env(&mut store, env, crate::jmp::_longjmp),
+        "_emscripten_longjmp_jmpbuf" => Function::new_typed_with_env(&mut store, env, crate::jmp::_longjmp),
+        "_saveSetjmp" => Function::new_typed_with_env(&mut store, env, crate::jmp::_saveSetjmp),
+        "_testSetjmp" => Function::new_typed_with_env(&mut store, env, crate::jmp::_testSetjmp),
 
         // Bitwise
         "_llvm_bswap_i64" => Function::new_typed_with_env(&mut store, env, crate::bitwise::_llvm_bswap_i64),
@@ -1410,10 +1621,11 @@ pub fn generate_emscripten_env(
         "__Unwind_Backtrace" => Function::new_typed_with_env(&mut store, env, crate::emscripten_target::__Unwind_Backtrace),
         "__Unwind_FindEnclosingFunction" => Function::new_typed_with_env(&mut store, env, crate::emscripten_target::__Unwind_FindEnclosingFunction),
         "__Unwind_GetIPInfo" => Function::new_typed_with_env(&mut store, env, crate::emscripten_target::__Unwind_GetIPInfo),
-        "___cxa_find_matching_catch_2" => Function::new_typed_with_env(&mut store, env, crate::emscripten_target::___cxa_find_matching_catch_2),
-        "___cxa_find_matching_catch_3" => Function::new_typed_with_env(&mut store, env, crate::emscripten_target::___cxa_find_matching_catch_3),
-        "___cxa_free_exception" => Function::new_typed_with_env(&mut store, env, crate::emscripten_target::___cxa_free_exception),
-        "___resumeException" => Function::new_typed_with_env(&mut store, env, crate::emscripten_target::___resumeException),
+        "___cxa_find_matching_catch_2" => Function::new_typed_with_env(&mut store, env, crate::exception::___cxa_find_matching_catch_2),
+        "___cxa_find_matching_catch_3" => Function::new_typed_with_env(&mut store, env, crate::exception::___cxa_find_matching_catch_3),
+        "___cxa_find_matching_catch_4" => Function::new_typed_with_env(&mut store, env, crate::exception::___cxa_find_matching_catch_4),
+        "___cxa_free_exception" => Function::new_typed_with_env(&mut store, env, crate::exception::___cxa_free_exception),
+        "___resumeException" => Function::new_typed_with_env(&mut store, env, crate::exception::___resumeException),
         "_dladdr" => Function::new_typed_with_env(&mut store, env, crate::emscripten_target::_dladdr),
         "_pthread_attr_destroy" => Function::new_typed_with_env(&mut store, env, crate::pthread::_pthread_attr_destroy),
         "_pthread_attr_getstack" => Function::new_typed_with_env(&mut store, env, crate::pthread::_pthread_attr_getstack),
@@ -1452,6 +1664,9 @@ pub fn generate_emscripten_env(
         "_pthread_setcancelstate" => Function::new_typed_with_env(&mut store, env, crate::pthread::_pthread_setcancelstate),
         "_pthread_setspecific" => Function::new_typed_with_env(&mut store, env, crate::pthread::_pthread_setspecific),
         "_pthread_sigmask" => Function::new_typed_with_env(&mut store, env, crate::pthread::_pthread_sigmask),
+        "_emscripten_futex_wait" => Function::new_typed_with_env(&mut store, env, crate::pthread::_emscripten_futex_wait),
+        "_emscripten_futex_wake" => Function::new_typed_with_env(&mut store, env, crate::pthread::_emscripten_futex_wake),
+        "_emscripten_num_logical_cores" => Function::new_typed_with_env(&mut store, env, crate::pthread::_emscripten_num_logical_cores),
         "___gxx_personality_v0" => Function::new_typed_with_env(&mut store, env, crate::emscripten_target::___gxx_personality_v0),
         "_gai_strerror" => Function::new_typed_with_env(&mut store, env, crate::env::_gai_strerror),
         "_getdtablesize" => Function::new_typed_with_env(&mut store, env, crate::emscripten_target::_getdtablesize),
@@ -1512,12 +1727,14 @@ pub fn generate_emscripten_env(
         "_confstr" => Function::new_typed_with_env(&mut store, env, crate::unistd::confstr),
     };
 
-    // Compatibility with newer versions of Emscripten
+    // The LLVM backend of Emscripten doesn't prefix the C symbols with `_`
     let mut to_insert: Vec<(String, _)> = vec![];
-    for (k, v) in env_ns.iter() {
-        if let Some(k) = k.strip_prefix('_') {
-            if !env_ns.contains(k) {
-                to_insert.push((k.to_string(), v.clone()));
+    if globals.abi.backend == EmscriptenBackend::Llvm {
+        for (k, v) in env_ns.iter() {
+            if let Some(k) = k.strip_prefix('_') {
+                if !env_ns.contains(k) {
+                    to_insert.push((k.to_string(), v.clone()));
+                }
             }
         }
     }
@@ -1526,6 +1743,8 @@ pub fn generate_emscripten_env(
         env_ns.insert(k, v);
     }
 
+    crate::js::add_js_imports(&mut store, env, &globals.env_function_types, &mut env_ns);
+
     for null_function_name in globals.null_function_names.iter() {
         env_ns.insert(
             null_function_name.as_str(),
@@ -1533,7 +1752,13 @@ pub fn generate_emscripten_env(
         );
     }
 
-    let import_object: Imports = imports! {
+    env.as_ref(store)
+        .linker
+        .lock()
+        .unwrap()
+        .set_host(env_ns.clone(), globals.table.clone());
+
+    let mut import_object: Imports = imports! {
         "env" => env_ns,
         "global" => {
           "NaN" => Global::new(&mut store, Value::F64(f64::NAN)),
@@ -1550,6 +1775,17 @@ pub fn generate_emscripten_env(
         },
     };
 
+    // The global offset table of a main module, filled by the dynamic loader
+    for (namespace, name) in globals.got_imports.iter() {
+        let global = Global::new_mut(&mut store, Value::I32(0));
+        env.as_ref(store)
+            .linker
+            .lock()
+            .unwrap()
+            .add_main_got_entry(name, global.clone());
+        import_object.define(namespace, name, global);
+    }
+
     import_object
 }
 
@@ -1,28 +1,577 @@
+//! The emscripten dynamic loader, i.e. `dlopen` and friends for the side
+//! modules (built with `-s SIDE_MODULE=1`) of a main module built with
+//! `-s MAIN_MODULE=1`.
+//!
+//! A side module is a relocatable module described by its `dylink` (or
+//! `dylink.0`) custom section. It shares the memory and the table of the
+//! main module: its data is placed in a block allocated with the
+//! `memalign` of the main module, and its functions in slots appended to
+//! the table. The addresses of the symbols it takes from the other
+//! modules are given through the mutable globals it imports from the
+//! `GOT.mem` and `GOT.func` namespaces.
+//!
+//! Libraries are never unloaded, since pointers to their functions and
+//! data may still be in use after `dlclose`.
+
+use crate::env::{call_malloc, call_memalign, call_memset, get_emscripten_funcs};
+use crate::fs::{self, ResolvedPath};
 use crate::EmEnv;
-use wasmer::FunctionEnvMut;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use wasmer::{
+    AsStoreRef, Exports, Extern, FunctionEnvMut, Global, Imports, Instance, Module, RuntimeError,
+    Table, Value, WasmPtr,
+};
+
+/// The handle of the host functions, also used for `RTLD_DEFAULT`.
+const HOST_HANDLE: u32 = 0;
+
+/// The handle of the main module, returned by `dlopen(NULL, ...)`.
+const MAIN_HANDLE: u32 = 1;
+
+/// The modules that symbols can be resolved from.
+struct Library {
+    path: Option<PathBuf>,
+    exports: Exports,
+    /// The address of the data of the library, which the values of its
+    /// exported globals are relative to.
+    memory_base: u32,
+    /// The table slots of the functions already handed out.
+    func_slots: HashMap<String, u32>,
+    ref_count: usize,
+}
+
+impl Library {
+    fn new(path: Option<PathBuf>, exports: Exports, memory_base: u32) -> Self {
+        Self {
+            path,
+            exports,
+            memory_base,
+            func_slots: HashMap::new(),
+            ref_count: 1,
+        }
+    }
+}
+
+/// An entry of the global offset table of a module, waiting for the
+/// address of `name` until it's resolved.
+struct GotEntry {
+    name: String,
+    global: Global,
+    resolved: bool,
+}
+
+/// The state of the dynamic loader, shared by all the imports.
+#[derive(Default)]
+pub(crate) struct DynamicLinker {
+    /// The host functions first, then the main module and the side
+    /// modules in the order they were loaded: the handle of a library is
+    /// its index.
+    libraries: Vec<Library>,
+    table: Option<Table>,
+    got: Vec<GotEntry>,
+    last_error: Option<String>,
+    /// The message returned by the last `dlerror`, freed by the next one.
+    error_ptr: u32,
+}
+
+impl DynamicLinker {
+    /// Sets the host functions and the table shared with the side modules.
+    pub(crate) fn set_host(&mut self, env: Exports, table: Table) {
+        self.libraries.clear();
+        self.libraries.push(Library::new(None, env, 0));
+        self.table = Some(table);
+    }
+
+    /// Adds a `GOT.mem` or `GOT.func` global imported by the main module,
+    /// resolved once the main module is instantiated.
+    pub(crate) fn add_main_got_entry(&mut self, name: &str, global: Global) {
+        self.got.push(GotEntry {
+            name: name.to_string(),
+            global,
+            resolved: false,
+        });
+    }
 
-// TODO: Need to implement.
+    fn find_loaded(&mut self, path: &Path) -> Option<u32> {
+        let handle = self
+            .libraries
+            .iter()
+            .position(|library| library.path.as_deref() == Some(path))?;
+        self.libraries[handle].ref_count += 1;
+        Some(handle as u32)
+    }
+
+    /// Finds the export `name` of the library `handle` or, without one, of
+    /// the main module, the side modules and the host, in this order.
+    fn find(&self, handle: Option<u32>, name: &str) -> Option<(usize, Extern)> {
+        let export = |index: usize| {
+            let library = self.libraries.get(index)?;
+            let export = library.exports.get_extern(name)?;
+            Some((index, export.clone()))
+        };
+        match handle {
+            Some(handle) => export(handle as usize),
+            None => {
+                let count = self.libraries.len();
+                (1..count).chain(0..count.min(1)).find_map(export)
+            }
+        }
+    }
+
+    /// Returns the address of the symbol `name` as seen by the modules: the
+    /// absolute address of a global, or the table slot of a function.
+    fn address_of(
+        &mut self,
+        ctx: &mut FunctionEnvMut<EmEnv>,
+        handle: Option<u32>,
+        name: &str,
+    ) -> Result<Option<u32>, String> {
+        let (index, export) = match self.find(handle, name) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let library = &mut self.libraries[index];
+        match export {
+            Extern::Global(global) => match global.get(ctx) {
+                Value::I32(value) => Ok(Some((value as u32).wrapping_add(library.memory_base))),
+                _ => Err(format!("symbol `{}` is not an i32 global", name)),
+            },
+            Extern::Function(function) => {
+                if let Some(slot) = library.func_slots.get(name) {
+                    return Ok(Some(*slot));
+                }
+                let table = self
+                    .table
+                    .as_ref()
+                    .ok_or_else(|| "there is no table to link functions".to_string())?;
+                let slot = table
+                    .grow(ctx, 1, Value::FuncRef(Some(function)))
+                    .map_err(|e| e.message())?;
+                library.func_slots.insert(name.to_string(), slot);
+                Ok(Some(slot))
+            }
+            _ => Err(format!(
+                "symbol `{}` is neither a function nor a global",
+                name
+            )),
+        }
+    }
+
+    /// Writes the addresses of the symbols known so far in the global
+    /// offset tables. The entries of symbols that are still undefined stay
+    /// null, as for weak symbols, until a library defining them is loaded.
+    fn resolve_got(&mut self, ctx: &mut FunctionEnvMut<EmEnv>) -> Result<(), String> {
+        for index in 0..self.got.len() {
+            if self.got[index].resolved {
+                continue;
+            }
+            let name = self.got[index].name.clone();
+            if let Some(address) = self.address_of(ctx, None, &name)? {
+                let entry = &mut self.got[index];
+                entry
+                    .global
+                    .set(ctx, Value::I32(address as i32))
+                    .map_err(|e| e.message())?;
+                entry.resolved = true;
+            } else {
+                debug!("emscripten::linking: `{}` is still undefined", name);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Registers the main module once it's instantiated, and fills the global
+/// offset table it imports.
+pub(crate) fn set_main_instance(
+    ctx: &mut FunctionEnvMut<EmEnv>,
+    instance: &Instance,
+    memory_base: u32,
+) -> Result<(), String> {
+    let linker = ctx.data().linker.clone();
+    let mut linker = linker.lock().unwrap();
+    linker.libraries.truncate(MAIN_HANDLE as usize);
+    linker
+        .libraries
+        .push(Library::new(None, instance.exports.clone(), memory_base));
+    if let Ok(table) = instance.exports.get_table("__indirect_function_table") {
+        linker.table = Some(table.clone());
+    }
+    linker.resolve_got(ctx)
+}
+
+/// The information of the `dylink` custom section of a side module.
+#[derive(Debug, Default)]
+struct DylinkInfo {
+    memory_size: u32,
+    memory_align: u32,
+    table_size: u32,
+    needed: Vec<String>,
+}
+
+impl DylinkInfo {
+    fn from_module(module: &Module) -> Result<Self, String> {
+        if let Some(section) = module.custom_sections("dylink.0").next() {
+            Self::parse(&section)
+        } else if let Some(section) = module.custom_sections("dylink").next() {
+            Self::parse_legacy(&section)
+        } else {
+            Err("not a side module, the `dylink` section is missing".to_string())
+        }
+    }
+
+    /// Parses a `dylink.0` section, made of subsections.
+    fn parse(section: &[u8]) -> Result<Self, String> {
+        const MEM_INFO: u8 = 1;
+        const NEEDED: u8 = 2;
+
+        let mut info = Self::default();
+        let mut reader = Reader(section);
+        while !reader.0.is_empty() {
+            let kind = reader.byte()?;
+            let size = reader.leb()?;
+            let mut payload = Reader(reader.bytes(size)?);
+            match kind {
+                MEM_INFO => {
+                    info.memory_size = payload.leb()?;
+                    info.memory_align = payload.leb()?;
+                    info.table_size = payload.leb()?;
+                }
+                NEEDED => info.needed = payload.strings()?,
+                _ => {}
+            }
+        }
+        Ok(info)
+    }
+
+    /// Parses a `dylink` section, as emitted by older versions of
+    /// emscripten.
+    fn parse_legacy(section: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader(section);
+        let memory_size = reader.leb()?;
+        let memory_align = reader.leb()?;
+        let table_size = reader.leb()?;
+        let _table_align = reader.leb()?;
+        Ok(Self {
+            memory_size,
+            memory_align,
+            table_size,
+            needed: reader.strings()?,
+        })
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: u32) -> Result<&'a [u8], String> {
+        let len = len as usize;
+        if self.0.len() < len {
+            return Err("the `dylink` section is truncated".to_string());
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn leb(&mut self) -> Result<u32, String> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u32) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("invalid LEB128 in the `dylink` section".to_string())
+    }
+
+    fn strings(&mut self) -> Result<Vec<String>, String> {
+        let count = self.leb()?;
+        (0..count)
+            .map(|_| {
+                let len = self.leb()?;
+                Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+            })
+            .collect()
+    }
+}
+
+/// Loads the side module at `path`, and the libraries it needs, unless
+/// it's already loaded. Returns its handle.
+///
+/// `loading` holds the libraries whose needed libraries are being
+/// loaded, to report the libraries needing themselves as errors.
+fn load_library(
+    ctx: &mut FunctionEnvMut<EmEnv>,
+    path: &Path,
+    loading: &mut HashSet<PathBuf>,
+) -> Result<u32, String> {
+    let error = |e: String| format!("{}: {}", path.display(), e);
+    let host_path = host_library_path(ctx, path)
+        .ok_or_else(|| error("the file can't be accessed".to_string()))?;
+    let linker = ctx.data().linker.clone();
+    if let Some(handle) = linker.lock().unwrap().find_loaded(&host_path) {
+        return Ok(handle);
+    }
+    if loading.contains(&host_path) {
+        return Err(error("the library needs itself".to_string()));
+    }
+
+    let bytes = std::fs::read(&host_path).map_err(|e| error(e.to_string()))?;
+    let module = Module::new(&ctx.as_store_ref(), bytes).map_err(|e| error(e.to_string()))?;
+    let info = DylinkInfo::from_module(&module).map_err(|e| error(e.to_string()))?;
+
+    // The needed libraries are looked up next to the library first.
+    loading.insert(host_path.clone());
+    for needed in info.needed.iter() {
+        let next_to_library = path.parent().map(|dir| dir.join(needed));
+        let needed_path = match next_to_library {
+            Some(needed_path)
+                if host_library_path(ctx, &needed_path).map_or(false, |path| path.exists()) =>
+            {
+                needed_path
+            }
+            _ => PathBuf::from(needed),
+        };
+        load_library(ctx, &needed_path, loading)?;
+    }
+    loading.remove(&host_path);
+
+    let memory_base = if info.memory_size > 0 {
+        if get_emscripten_funcs(ctx).memalign_ref().is_none() {
+            return Err(error(
+                "the main module doesn't export `memalign`".to_string(),
+            ));
+        }
+        let align = 1u32.checked_shl(info.memory_align).unwrap_or(0).max(1);
+        let memory_base = call_memalign(ctx, align, info.memory_size);
+        if memory_base == 0 {
+            return Err(error(
+                "can't allocate the memory of the library".to_string(),
+            ));
+        }
+        call_memset(ctx, memory_base, 0, info.memory_size);
+        memory_base
+    } else {
+        0
+    };
+
+    let table = linker
+        .lock()
+        .unwrap()
+        .table
+        .clone()
+        .ok_or_else(|| error("there is no table to link functions".to_string()))?;
+    let table_base = if info.table_size > 0 {
+        table
+            .grow(ctx, info.table_size, Value::FuncRef(None))
+            .map_err(|e| error(e.message()))?
+    } else {
+        table.size(&*ctx)
+    };
+
+    let memory = ctx.data().memory(0);
+    let mut imports = Imports::new();
+    let mut got = vec![];
+    for import in module.imports() {
+        let (namespace, name) = (import.module(), import.name());
+        let export: Extern = match (namespace, name) {
+            ("env", "memory") => memory.clone().into(),
+            ("env", "table") | ("env", "__indirect_function_table") => table.clone().into(),
+            ("env", "__memory_base") | ("env", "memoryBase") => {
+                Global::new(ctx, Value::I32(memory_base as i32)).into()
+            }
+            ("env", "__table_base") | ("env", "tableBase") => {
+                Global::new(ctx, Value::I32(table_base as i32)).into()
+            }
+            ("GOT.mem", _) | ("GOT.func", _) => {
+                let global = Global::new_mut(ctx, Value::I32(0));
+                got.push(GotEntry {
+                    name: name.to_string(),
+                    global: global.clone(),
+                    resolved: false,
+                });
+                global.into()
+            }
+            _ => match linker.lock().unwrap().find(None, name) {
+                Some((_, export)) => export,
+                None => return Err(error(format!("undefined symbol `{}`", name))),
+            },
+        };
+        imports.define(namespace, name, export);
+    }
+
+    let instance = Instance::new(ctx, &module, &imports).map_err(|e| error(e.to_string()))?;
+    let handle = {
+        let mut linker = linker.lock().unwrap();
+        linker.got.extend(got);
+        linker.libraries.push(Library::new(
+            Some(host_path),
+            instance.exports.clone(),
+            memory_base,
+        ));
+        linker.resolve_got(ctx).map_err(|e| error(e.to_string()))?;
+        linker.libraries.len() as u32 - 1
+    };
+
+    // The lock is released here, since the constructors may call `dlopen`.
+    for init in [
+        "__wasm_apply_relocs",
+        "__wasm_apply_data_relocs",
+        "__wasm_call_ctors",
+        "__post_instantiate",
+    ] {
+        if let Ok(func) = instance.exports.get_function(init) {
+            func.call(ctx, &[]).map_err(|e| error(e.message()))?;
+        }
+    }
+
+    Ok(handle)
+}
+
+/// Maps `path`, as the module sees it, to the file of the host, only
+/// within the mapped directories.
+fn host_library_path(ctx: &FunctionEnvMut<EmEnv>, path: &Path) -> Option<PathBuf> {
+    match fs::resolve(ctx, path) {
+        Ok(ResolvedPath::Host(host_path)) => {
+            Some(PathBuf::from(host_path.to_string_lossy().into_owned()))
+        }
+        _ => None,
+    }
+}
 
 /// emscripten: dlopen(filename: *const c_char, flag: c_int) -> *mut c_void
-pub fn _dlopen(mut _ctx: FunctionEnvMut<EmEnv>, _filename: u32, _flag: u32) -> i32 {
+pub fn _dlopen(mut ctx: FunctionEnvMut<EmEnv>, filename: u32, _flag: u32) -> i32 {
     debug!("emscripten::_dlopen");
-    -1
+    if filename == 0 {
+        return MAIN_HANDLE as i32;
+    }
+
+    let memory = ctx.data().memory(0);
+    let path = match WasmPtr::<u8>::new(filename).read_utf8_string_with_nul(&memory.view(&ctx)) {
+        Ok(path) => path,
+        Err(e) => {
+            ctx.data().linker.lock().unwrap().last_error = Some(e.to_string());
+            return 0;
+        }
+    };
+    match load_library(&mut ctx, Path::new(&path), &mut HashSet::new()) {
+        Ok(handle) => handle as i32,
+        Err(error) => {
+            ctx.data().linker.lock().unwrap().last_error = Some(error);
+            0
+        }
+    }
 }
 
 /// emscripten: dlclose(handle: *mut c_void) -> c_int
-pub fn _dlclose(mut _ctx: FunctionEnvMut<EmEnv>, _filename: u32) -> i32 {
+pub fn _dlclose(ctx: FunctionEnvMut<EmEnv>, handle: u32) -> i32 {
     debug!("emscripten::_dlclose");
-    -1
+    let mut linker = ctx.data().linker.lock().unwrap();
+    if handle < MAIN_HANDLE || handle as usize >= linker.libraries.len() {
+        linker.last_error = Some(format!("invalid handle {}", handle));
+        return -1;
+    }
+    let library = &mut linker.libraries[handle as usize];
+    library.ref_count = library.ref_count.saturating_sub(1);
+    if library.ref_count == 0 {
+        debug!(
+            "emscripten::_dlclose: library {} is unused but stays loaded",
+            handle
+        );
+    }
+    0
 }
 
 /// emscripten: dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void
-pub fn _dlsym(mut _ctx: FunctionEnvMut<EmEnv>, _filepath: u32, _symbol: u32) -> i32 {
+pub fn _dlsym(mut ctx: FunctionEnvMut<EmEnv>, handle: u32, symbol: u32) -> i32 {
     debug!("emscripten::_dlsym");
-    -1
+    let memory = ctx.data().memory(0);
+    let linker = ctx.data().linker.clone();
+    let mut linker = linker.lock().unwrap();
+    let name = match WasmPtr::<u8>::new(symbol).read_utf8_string_with_nul(&memory.view(&ctx)) {
+        Ok(name) => name,
+        Err(e) => {
+            linker.last_error = Some(e.to_string());
+            return 0;
+        }
+    };
+
+    // The handle of the main module gives access to the global scope.
+    let scope = match handle {
+        HOST_HANDLE | MAIN_HANDLE => None,
+        handle if (handle as usize) < linker.libraries.len() => Some(handle),
+        _ => {
+            linker.last_error = Some(format!("invalid handle {}", handle));
+            return 0;
+        }
+    };
+
+    // Older versions of emscripten prefix the C symbols with `_`.
+    let address = match linker.address_of(&mut ctx, scope, &name) {
+        Ok(None) => linker.address_of(&mut ctx, scope, &format!("_{}", name)),
+        address => address,
+    };
+    match address {
+        Ok(Some(address)) => address as i32,
+        Ok(None) => {
+            linker.last_error = Some(format!("undefined symbol `{}`", name));
+            0
+        }
+        Err(error) => {
+            linker.last_error = Some(error);
+            0
+        }
+    }
 }
 
 /// emscripten: dlerror() -> *mut c_char
-pub fn _dlerror(mut _ctx: FunctionEnvMut<EmEnv>) -> i32 {
+pub fn _dlerror(mut ctx: FunctionEnvMut<EmEnv>) -> Result<i32, RuntimeError> {
     debug!("emscripten::_dlerror");
-    -1
+    let linker = ctx.data().linker.clone();
+    let (error, previous_ptr) = {
+        let mut linker = linker.lock().unwrap();
+        (
+            linker.last_error.take(),
+            std::mem::take(&mut linker.error_ptr),
+        )
+    };
+
+    if previous_ptr != 0 {
+        let free = get_emscripten_funcs(&ctx).free_ref().cloned();
+        if let Some(free) = free {
+            free.call(&mut ctx, previous_ptr)?;
+        }
+    }
+
+    let error = match error {
+        Some(error) => error,
+        None => return Ok(0),
+    };
+    let error_ptr = call_malloc(&mut ctx, error.len() as u32 + 1);
+    let memory = ctx.data().memory(0);
+    let mut bytes = error.into_bytes();
+    bytes.push(0);
+    if memory.view(&ctx).write(error_ptr as u64, &bytes).is_err() {
+        return Ok(0);
+    }
+    linker.lock().unwrap().error_ptr = error_ptr;
+    Ok(error_ptr as i32)
+}
+
+/// The imports of the main module from the `GOT.mem` and `GOT.func`
+/// namespaces, made of mutable globals resolved by the loader.
+pub(crate) fn main_got_imports(module: &Module) -> Vec<(String, String)> {
+    module
+        .imports()
+        .globals()
+        .filter(|import| import.module() == "GOT.mem" || import.module() == "GOT.func")
+        .map(|import| (import.module().to_string(), import.name().to_string()))
+        .collect()
 }
@@ -181,9 +181,11 @@ pub fn _llvm_trap(ctx: FunctionEnvMut<EmEnv>) {
     abort_with_message(ctx, "abort!");
 }
 
-pub fn _llvm_eh_typeid_for(_ctx: FunctionEnvMut<EmEnv>, _type_info_addr: u32) -> i32 {
+/// The type ids are the addresses of the type infos, as returned in
+/// `tempRet0` by `___cxa_find_matching_catch_*`.
+pub fn _llvm_eh_typeid_for(_ctx: FunctionEnvMut<EmEnv>, type_info_addr: u32) -> i32 {
     debug!("emscripten::_llvm_eh_typeid_for");
-    -1
+    type_info_addr as i32
 }
 
 pub fn _system(_ctx: FunctionEnvMut<EmEnv>, _one: i32) -> c_int {
@@ -1,5 +1,113 @@
-use crate::EmEnv;
-use wasmer::FunctionEnvMut;
+//! Emscripten's pthread ABI, with one host thread per pthread.
+//!
+//! Every thread gets its own instance of the module, in its own store,
+//! sharing the memory of the main instance: modules must be built with
+//! `-pthread`, so that the memory is shared and the data segments are
+//! only initialized once. The waits on futexes that go through the host
+//! are emulated here; `memory.atomic.wait` and `memory.atomic.notify`
+//! are handled by the runtime.
+//!
+//! Like in a browser, a thread that fails ends the program: its error is
+//! returned by the next `pthread_join` or futex wait of any thread, and
+//! by [`run_emscripten_instance`][crate::run_emscripten_instance] once
+//! the main thread returns.
+
+use crate::env::{call_memalign, get_emscripten_data, get_emscripten_funcs};
+use crate::{
+    generate_emscripten_env, EmEnv, EmscriptenData, EmscriptenFunctions, EmscriptenGlobals,
+    EmscriptenGlobalsData,
+};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{mpsc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use wasmer::vm::VMMemory;
+use wasmer::{
+    AsStoreRef, FunctionEnv, FunctionEnvMut, Instance, Memory, Module, RuntimeError, Store, Table,
+    Value, WasmPtr,
+};
+
+/// The `pthread_t` of the main thread
+pub(crate) const MAIN_THREAD_ID: u32 = 1;
+
+/// The stack size of the threads created without one
+const DEFAULT_STACK_SIZE: u32 = 2 * 1024 * 1024;
+
+// The errors, as the emscripten libc numbers them
+const ESRCH: i32 = 3;
+const EAGAIN: i32 = 11;
+const EINVAL: i32 = 22;
+const ETIMEDOUT: i32 = 110;
+
+/// The error used to unwind a thread on `pthread_exit`
+#[derive(Copy, Clone, Debug)]
+pub struct PthreadExit(pub i32);
+
+impl fmt::Display for PthreadExit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "thread exited with {}", self.0)
+    }
+}
+
+impl Error for PthreadExit {}
+
+struct Thread {
+    handle: JoinHandle<Result<i32, RuntimeError>>,
+    // Freed when the thread is joined
+    stack: u32,
+}
+
+#[derive(Default)]
+struct FutexWaiters {
+    waiting: u32,
+    woken: u32,
+}
+
+/// The threads of a program, shared by the environments of all of them
+#[derive(Default)]
+pub(crate) struct Threads {
+    module: Mutex<Option<Module>>,
+    next_id: AtomicU32,
+    running: Mutex<HashMap<u32, Thread>>,
+    futexes: Mutex<HashMap<u32, FutexWaiters>>,
+    futex_wakeup: Condvar,
+    // The error of the first thread that failed
+    failure: Mutex<Option<RuntimeError>>,
+}
+
+impl Threads {
+    pub(crate) fn set_module(&self, module: &Module) {
+        *self.module.lock().unwrap() = Some(module.clone());
+    }
+
+    /// Returns the error of the first thread of the program that failed.
+    pub(crate) fn check_failure(&self) -> Result<(), RuntimeError> {
+        match self.failure.lock().unwrap().as_ref() {
+            Some(e) => Err(e.clone()),
+            None => Ok(()),
+        }
+    }
+
+    fn fail(&self, thread_id: u32, error: RuntimeError) -> RuntimeError {
+        let error = self
+            .failure
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| {
+                RuntimeError::new(format!("thread {} failed: {}", thread_id, error))
+            })
+            .clone();
+        // Wake up the waiting threads, with the futexes locked so that the
+        // ones about to wait see the failure
+        let _futexes = self.futexes.lock().unwrap();
+        self.futex_wakeup.notify_all();
+        error
+    }
+}
 
 pub fn _pthread_attr_destroy(mut _ctx: FunctionEnvMut<EmEnv>, _a: i32) -> i32 {
     trace!("emscripten::_pthread_attr_destroy");
@@ -24,14 +132,30 @@ pub fn _pthread_attr_getstack(
     0
 }
 
-pub fn _pthread_attr_init(mut _ctx: FunctionEnvMut<EmEnv>, _a: i32) -> i32 {
-    trace!("emscripten::_pthread_attr_init({})", _a);
-    0
+pub fn _pthread_attr_init(ctx: FunctionEnvMut<EmEnv>, attr: u32) -> i32 {
+    trace!("emscripten::_pthread_attr_init({})", attr);
+    // The first field of the attributes is the stack size, 0 is the default
+    let memory = ctx.data().memory(0);
+    match WasmPtr::<u32>::new(attr).deref(&memory.view(&ctx)).write(0) {
+        Ok(()) => 0,
+        Err(_) => EINVAL,
+    }
 }
 
-pub fn _pthread_attr_setstacksize(mut _ctx: FunctionEnvMut<EmEnv>, _a: i32, _b: i32) -> i32 {
-    trace!("emscripten::_pthread_attr_setstacksize");
-    0
+pub fn _pthread_attr_setstacksize(ctx: FunctionEnvMut<EmEnv>, attr: u32, stacksize: u32) -> i32 {
+    trace!(
+        "emscripten::_pthread_attr_setstacksize({}, {})",
+        attr,
+        stacksize
+    );
+    let memory = ctx.data().memory(0);
+    match WasmPtr::<u32>::new(attr)
+        .deref(&memory.view(&ctx))
+        .write(stacksize)
+    {
+        Ok(()) => 0,
+        Err(_) => EINVAL,
+    }
 }
 
 pub fn _pthread_cleanup_pop(mut _ctx: FunctionEnvMut<EmEnv>, _a: i32) {
@@ -82,24 +206,228 @@ pub fn _pthread_condattr_setclock(mut _ctx: FunctionEnvMut<EmEnv>, _a: i32, _b:
     0
 }
 
-pub fn _pthread_create(mut _ctx: FunctionEnvMut<EmEnv>, _a: i32, _b: i32, _c: i32, _d: i32) -> i32 {
-    trace!("emscripten::_pthread_create");
-    // 11 seems to mean "no"
-    11
-}
-
-pub fn _pthread_detach(mut _ctx: FunctionEnvMut<EmEnv>, _a: i32) -> i32 {
-    trace!("emscripten::_pthread_detach");
-    0
-}
-
-pub fn _pthread_equal(mut _ctx: FunctionEnvMut<EmEnv>, _a: i32, _b: i32) -> i32 {
-    trace!("emscripten::_pthread_equal");
-    0
-}
-
-pub fn _pthread_exit(mut _ctx: FunctionEnvMut<EmEnv>, _a: i32) {
-    trace!("emscripten::_pthread_exit");
+/// pthread_create
+///
+/// Runs `start_routine(arg)` in a new host thread, on a stack allocated in
+/// the shared memory. Returns `EAGAIN` if the memory is not shared.
+pub fn _pthread_create(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    thread: u32,
+    attr: u32,
+    start_routine: i32,
+    arg: i32,
+) -> i32 {
+    trace!(
+        "emscripten::_pthread_create({}, {}, {}, {})",
+        thread,
+        attr,
+        start_routine,
+        arg
+    );
+    let env = ctx.data().clone();
+    let module = match env.threads.module.lock().unwrap().clone() {
+        Some(module) => module,
+        None => return EAGAIN,
+    };
+    let memory = env.memory(0);
+    let thread_memory = match memory.try_clone(&ctx) {
+        Some(thread_memory) => thread_memory,
+        None => {
+            debug!("=> the memory is not shared, the module must be built with -pthread");
+            return EAGAIN;
+        }
+    };
+    let (globals, mapped_dirs) = match get_emscripten_data(&ctx).as_ref() {
+        Some(data) => (data.globals.clone(), data.mapped_dirs.clone()),
+        None => return EAGAIN,
+    };
+
+    let stack_size = if attr == 0 {
+        0
+    } else {
+        WasmPtr::<u32>::new(attr)
+            .deref(&memory.view(&ctx))
+            .read()
+            .unwrap_or(0)
+    };
+    let stack_size = if stack_size == 0 {
+        DEFAULT_STACK_SIZE
+    } else {
+        stack_size
+    };
+    let stack = call_memalign(&mut ctx, 16, stack_size);
+    if stack == 0 {
+        return EAGAIN;
+    }
+
+    let thread_id = MAIN_THREAD_ID + 1 + env.threads.next_id.fetch_add(1, Ordering::SeqCst);
+    let thread_env = env.new_thread(thread_id);
+    let threads = env.threads.clone();
+    let engine = ctx.as_store_ref().engine().clone();
+    let (started_tx, started_rx) = mpsc::channel();
+    let spawned = thread::Builder::new()
+        .name(format!("emscripten-thread-{}", thread_id))
+        .spawn(move || {
+            let mut store = Store::new(engine);
+            let stack_max = stack + stack_size;
+            let thread = instantiate_thread(
+                &mut store,
+                &module,
+                thread_env,
+                thread_memory,
+                EmscriptenGlobalsData {
+                    stacktop: stack,
+                    stack_max,
+                    ..globals
+                },
+                mapped_dirs,
+            );
+            let (env, instance, table) = match thread {
+                Ok(thread) => {
+                    let _ = started_tx.send(Ok(()));
+                    thread
+                }
+                Err(e) => {
+                    let _ = started_tx.send(Err(e));
+                    return Ok(0);
+                }
+            };
+            run_thread(
+                &mut store,
+                &env,
+                &instance,
+                &table,
+                stack_max,
+                start_routine,
+                arg,
+            )
+            .map_err(|e| threads.fail(thread_id, e))
+        });
+    let started = match spawned {
+        Ok(handle) => match started_rx.recv() {
+            Ok(Ok(())) => Ok(handle),
+            Ok(Err(e)) => Err(e),
+            Err(e) => Err(e.to_string()),
+        },
+        Err(e) => Err(e.to_string()),
+    };
+    let handle = match started {
+        Ok(handle) => handle,
+        Err(e) => {
+            debug!("=> failed to start the thread: {}", e);
+            free(&mut ctx, stack);
+            return EAGAIN;
+        }
+    };
+
+    env.threads
+        .running
+        .lock()
+        .unwrap()
+        .insert(thread_id, Thread { handle, stack });
+    let memory = ctx.data().memory(0);
+    let _ = WasmPtr::<u32>::new(thread)
+        .deref(&memory.view(&ctx))
+        .write(thread_id);
+    debug!("=> thread: {}", thread_id);
+    0
+}
+
+fn free(ctx: &mut FunctionEnvMut<EmEnv>, ptr: u32) {
+    let free = get_emscripten_funcs(ctx).free_ref().cloned();
+    if let Some(free) = free {
+        let _ = free.call(ctx, ptr);
+    }
+}
+
+fn instantiate_thread(
+    store: &mut Store,
+    module: &Module,
+    env: EmEnv,
+    memory: VMMemory,
+    globals: EmscriptenGlobalsData,
+    mapped_dirs: HashMap<String, PathBuf>,
+) -> Result<(FunctionEnv<EmEnv>, Instance, Table), String> {
+    let memory = Memory::new_from_existing(store, memory);
+    env.set_memory(memory.clone());
+    *env.data.lock().unwrap() = Some(EmscriptenData::new(globals.clone(), mapped_dirs));
+    let env = FunctionEnv::new(store, env);
+
+    let mut emscripten_globals = EmscriptenGlobals::new_thread(store, module, memory, globals)?;
+    let import_object = generate_emscripten_env(store, &env, &mut emscripten_globals);
+    let instance = Instance::new(store, module, &import_object).map_err(|e| e.to_string())?;
+    env.as_ref(store)
+        .set_functions(EmscriptenFunctions::from_instance(store, &instance));
+
+    Ok((env, instance, emscripten_globals.table))
+}
+
+fn run_thread(
+    store: &mut Store,
+    env: &FunctionEnv<EmEnv>,
+    instance: &Instance,
+    table: &Table,
+    stack_top: u32,
+    start_routine: i32,
+    arg: i32,
+) -> Result<i32, RuntimeError> {
+    // The stack grows down from the end of the stack of the thread
+    let stack_restore = env
+        .as_ref(store)
+        .funcs
+        .lock()
+        .unwrap()
+        .stack_restore
+        .clone();
+    if let Some(stack_restore) = stack_restore {
+        stack_restore.call(store, stack_top as i32)?;
+    }
+    if let Ok(tls_init) = instance
+        .exports
+        .get_typed_function::<(), i32>(store, "_emscripten_tls_init")
+    {
+        tls_init.call(store)?;
+    }
+
+    let start_routine = match table.get(store, start_routine as u32) {
+        Some(Value::FuncRef(Some(start_routine))) => start_routine,
+        _ => {
+            return Err(RuntimeError::new(format!(
+                "invalid thread start routine {}",
+                start_routine
+            )))
+        }
+    };
+    match start_routine.typed::<i32, i32>(store)?.call(store, arg) {
+        Ok(ret) => Ok(ret),
+        Err(e) => match e.downcast::<PthreadExit>() {
+            Ok(PthreadExit(ret)) => Ok(ret),
+            Err(e) => Err(e),
+        },
+    }
+}
+
+pub fn _pthread_detach(ctx: FunctionEnvMut<EmEnv>, thread: u32) -> i32 {
+    trace!("emscripten::_pthread_detach({})", thread);
+    // The host thread is detached when its handle is dropped
+    match ctx.data().threads.running.lock().unwrap().remove(&thread) {
+        Some(_) => 0,
+        None => ESRCH,
+    }
+}
+
+pub fn _pthread_equal(mut _ctx: FunctionEnvMut<EmEnv>, a: u32, b: u32) -> i32 {
+    trace!("emscripten::_pthread_equal({}, {})", a, b);
+    (a == b) as i32
+}
+
+pub fn _pthread_exit(ctx: FunctionEnvMut<EmEnv>, value: i32) -> Result<(), PthreadExit> {
+    trace!("emscripten::_pthread_exit({})", value);
+    if ctx.data().thread_id == MAIN_THREAD_ID {
+        // The program ends with its main thread
+        std::process::exit(0);
+    }
+    Err(PthreadExit(value))
 }
 
 pub fn _pthread_getattr_np(mut _ctx: FunctionEnvMut<EmEnv>, _thread: i32, _attr: i32) -> i32 {
@@ -112,14 +440,36 @@ pub fn _pthread_getspecific(mut _ctx: FunctionEnvMut<EmEnv>, _a: i32) -> i32 {
     0
 }
 
-pub fn _pthread_join(mut _ctx: FunctionEnvMut<EmEnv>, _a: i32, _b: i32) -> i32 {
-    trace!("emscripten::_pthread_join");
-    0
-}
-
-pub fn _pthread_self(mut _ctx: FunctionEnvMut<EmEnv>) -> i32 {
+pub fn _pthread_join(
+    mut ctx: FunctionEnvMut<EmEnv>,
+    thread: u32,
+    retval: u32,
+) -> Result<i32, RuntimeError> {
+    trace!("emscripten::_pthread_join({}, {})", thread, retval);
+    let joined = ctx.data().threads.running.lock().unwrap().remove(&thread);
+    let Thread { handle, stack } = match joined {
+        Some(joined) => joined,
+        None => return Ok(ESRCH),
+    };
+    // A thread that fails ends the program
+    let ret = handle
+        .join()
+        .map_err(|_| RuntimeError::new(format!("thread {} panicked", thread)))??;
+    ctx.data().threads.check_failure()?;
+
+    free(&mut ctx, stack);
+    if retval != 0 {
+        let memory = ctx.data().memory(0);
+        let _ = WasmPtr::<i32>::new(retval)
+            .deref(&memory.view(&ctx))
+            .write(ret);
+    }
+    Ok(0)
+}
+
+pub fn _pthread_self(ctx: FunctionEnvMut<EmEnv>) -> u32 {
     trace!("emscripten::_pthread_self");
-    0
+    ctx.data().thread_id
 }
 
 pub fn _pthread_key_create(mut _ctx: FunctionEnvMut<EmEnv>, _a: i32, _b: i32) -> i32 {
@@ -196,3 +546,106 @@ pub fn _pthread_sigmask(mut _ctx: FunctionEnvMut<EmEnv>, _a: i32, _b: i32, _c: i
     trace!("emscripten::_pthread_sigmask");
     0
 }
+
+/// emscripten_futex_wait
+///
+/// Blocks until the futex at `addr` is woken up with `emscripten_futex_wake`
+/// or `timeout` milliseconds have passed, if it still holds `val`. Fails
+/// when a thread of the program fails.
+pub fn _emscripten_futex_wait(
+    ctx: FunctionEnvMut<EmEnv>,
+    addr: u32,
+    val: u32,
+    timeout: f64,
+) -> Result<i32, RuntimeError> {
+    trace!(
+        "emscripten::_emscripten_futex_wait({}, {}, {})",
+        addr,
+        val,
+        timeout
+    );
+    let threads = ctx.data().threads.clone();
+    let mut futexes = threads.futexes.lock().unwrap();
+    threads.check_failure()?;
+
+    // The value is checked with the futexes locked, so that a wake up
+    // can't be missed
+    let memory = ctx.data().memory(0);
+    match WasmPtr::<u32>::new(addr).deref(&memory.view(&ctx)).read() {
+        Ok(current) if current == val => {}
+        Ok(_) => return Ok(-EAGAIN),
+        Err(_) => return Ok(-EINVAL),
+    }
+
+    let deadline = if timeout.is_finite() && timeout >= 0.0 {
+        Some(Instant::now() + Duration::from_secs_f64(timeout / 1000.0))
+    } else {
+        None
+    };
+    futexes.entry(addr).or_default().waiting += 1;
+    let woken = loop {
+        if let Err(e) = threads.check_failure() {
+            break Err(e);
+        }
+        let waiters = futexes.get_mut(&addr).unwrap();
+        if waiters.woken > 0 {
+            waiters.woken -= 1;
+            break Ok(true);
+        }
+        futexes = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    break Ok(false);
+                }
+                threads
+                    .futex_wakeup
+                    .wait_timeout(futexes, deadline - now)
+                    .unwrap()
+                    .0
+            }
+            None => threads.futex_wakeup.wait(futexes).unwrap(),
+        };
+    };
+
+    let waiters = futexes.get_mut(&addr).unwrap();
+    waiters.waiting -= 1;
+    if waiters.waiting == 0 {
+        futexes.remove(&addr);
+    }
+    if woken? {
+        Ok(0)
+    } else {
+        Ok(-ETIMEDOUT)
+    }
+}
+
+/// emscripten_futex_wake
+///
+/// Wakes up at most `count` threads waiting on the futex at `addr`, and
+/// returns how many have been.
+pub fn _emscripten_futex_wake(ctx: FunctionEnvMut<EmEnv>, addr: u32, count: i32) -> i32 {
+    trace!("emscripten::_emscripten_futex_wake({}, {})", addr, count);
+    if count < 0 {
+        return -EINVAL;
+    }
+    let threads = ctx.data().threads.clone();
+    let mut futexes = threads.futexes.lock().unwrap();
+    let woken = match futexes.get_mut(&addr) {
+        Some(waiters) => {
+            let woken = (waiters.waiting - waiters.woken).min(count as u32);
+            waiters.woken += woken;
+            woken
+        }
+        None => 0,
+    };
+    if woken > 0 {
+        threads.futex_wakeup.notify_all();
+    }
+    woken as i32
+}
+
+pub fn _emscripten_num_logical_cores(mut _ctx: FunctionEnvMut<EmEnv>) -> i32 {
+    trace!("emscripten::_emscripten_num_logical_cores");
+    thread::available_parallelism().map_or(1, |cores| cores.get() as i32)
+}
@@ -11,7 +11,8 @@ pub use self::unix::*;
 pub use self::windows::*;
 
 use crate::{
-    utils::{copy_stat_into_wasm, get_cstr_path, get_current_directory},
+    fs::{self, ResolvedPath},
+    utils::{copy_stat_into_wasm, get_current_directory},
     EmEnv,
 };
 
@@ -40,6 +41,7 @@ use libc::{
     rmdir,
     // writev,
     stat,
+    unlink,
     write,
     // ENOTTY,
 };
@@ -67,6 +69,12 @@ pub fn ___syscall3(ctx: FunctionEnvMut<EmEnv>, _which: i32, mut varargs: VarArgs
     let buf: u32 = varargs.get(&ctx);
     let count: i32 = varargs.get(&ctx);
     debug!("=> fd: {}, buf_offset: {}, count: {}", fd, buf, count);
+    if let Some(ret) = crate::io::read_stdio(&ctx, fd, buf, count as u32)
+        .or_else(|| fs::read(&ctx, fd, buf, count as u32))
+    {
+        debug!("=> ret: {}", ret);
+        return ret;
+    }
     let memory = ctx.data().memory(0);
     let buf_addr = emscripten_memory_pointer!(memory.view(&ctx), buf) as *mut c_void;
     let ret = unsafe { read(fd, buf_addr, count as _) };
@@ -81,6 +89,11 @@ pub fn ___syscall4(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarAr
     let buf: i32 = varargs.get(&ctx);
     let count: i32 = varargs.get(&ctx);
     debug!("=> fd: {}, buf: {}, count: {}", fd, buf, count);
+    if let Some(ret) = crate::io::write_stdio(&ctx, fd, buf as u32, count as u32)
+        .or_else(|| fs::write(&ctx, fd, buf as u32, count as u32))
+    {
+        return ret;
+    }
     let memory = ctx.data().memory(0);
     let buf_addr = emscripten_memory_pointer!(memory.view(&ctx), buf) as *const c_void;
     unsafe { write(fd, buf_addr, count as _) as i32 }
@@ -91,6 +104,9 @@ pub fn ___syscall6(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarAr
     debug!("emscripten::___syscall6 (close) {}", _which);
     let fd: i32 = varargs.get(&ctx);
     debug!("fd: {}", fd);
+    if let Some(ret) = fs::close(&ctx, fd) {
+        return ret;
+    }
     unsafe { close(fd) }
 }
 
@@ -98,24 +114,28 @@ pub fn ___syscall6(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarAr
 pub fn ___syscall12(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall12 (chdir) {}", _which);
     let path_ptr = varargs.get_str(&ctx);
-    let real_path_owned = get_cstr_path(ctx, path_ptr as *const _);
-    let real_path = if let Some(ref rp) = real_path_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        path_ptr
+    let real_path = match fs::host_path(&ctx, path_ptr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
     };
-    let ret = unsafe { chdir(real_path) };
-    debug!(
-        "=> path: {:?}, ret: {}",
-        unsafe { std::ffi::CStr::from_ptr(real_path) },
-        ret
-    );
+    let ret = unsafe { chdir(real_path.as_ptr()) };
+    debug!("=> path: {:?}, ret: {}", real_path, ret);
     ret
 }
 
-pub fn ___syscall10(_ctx: FunctionEnvMut<EmEnv>, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall10");
-    -1
+// unlink
+pub fn ___syscall10(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall10 (unlink) {}", _which);
+    let pathname_addr = varargs.get_str(&ctx);
+    match fs::resolve_path(&ctx, pathname_addr) {
+        Ok(ResolvedPath::Host(real_path)) => {
+            let ret = unsafe { unlink(real_path.as_ptr()) };
+            debug!("=> path: {:?}, ret: {}", real_path, ret);
+            ret
+        }
+        Ok(ResolvedPath::Virtual(path)) => fs::unlink(&ctx, &path),
+        Err(errno) => errno,
+    }
 }
 
 pub fn ___syscall14(_ctx: FunctionEnvMut<EmEnv>, _one: i32, _two: i32) -> i32 {
@@ -154,6 +174,8 @@ pub fn ___syscall32(_ctx: FunctionEnvMut<EmEnv>, _one: i32, _two: i32) -> i32 {
     -1
 }
 
+// access, implemented in `unix` and shadowed by this stub elsewhere
+#[cfg(not(unix))]
 pub fn ___syscall33(_ctx: FunctionEnvMut<EmEnv>, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall33");
     -1
@@ -165,43 +187,39 @@ pub fn ___syscall36(_ctx: FunctionEnvMut<EmEnv>, _one: i32, _two: i32) -> i32 {
 }
 
 // rename
-pub fn ___syscall38(mut ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> i32 {
+pub fn ___syscall38(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> i32 {
     debug!("emscripten::___syscall38 (rename)");
     let old_path = varargs.get_str(&ctx);
     let new_path = varargs.get_str(&ctx);
-    let real_old_path_owned = get_cstr_path(ctx.as_mut(), old_path as *const _);
-    let real_old_path = if let Some(ref rp) = real_old_path_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        old_path
-    };
-    let real_new_path_owned = get_cstr_path(ctx, new_path as *const _);
-    let real_new_path = if let Some(ref rp) = real_new_path_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        new_path
-    };
-    let result = unsafe { rename(real_old_path, real_new_path) };
-    debug!(
-        "=> old_path: {}, new_path: {}, result: {}",
-        unsafe { std::ffi::CStr::from_ptr(real_old_path).to_str().unwrap() },
-        unsafe { std::ffi::CStr::from_ptr(real_new_path).to_str().unwrap() },
-        result
-    );
-    result
+    let paths = fs::resolve_path(&ctx, old_path)
+        .and_then(|old_path| Ok((old_path, fs::resolve_path(&ctx, new_path)?)));
+    match paths {
+        Ok((ResolvedPath::Host(old_path), ResolvedPath::Host(new_path))) => {
+            let result = unsafe { rename(old_path.as_ptr(), new_path.as_ptr()) };
+            debug!(
+                "=> old_path: {:?}, new_path: {:?}, result: {}",
+                old_path, new_path, result
+            );
+            result
+        }
+        Ok((ResolvedPath::Virtual(old_path), ResolvedPath::Virtual(new_path))) => {
+            fs::rename(&ctx, &old_path, &new_path)
+        }
+        // EXDEV
+        Ok(_) => -18,
+        Err(errno) => errno,
+    }
 }
 
 // rmdir
 pub fn ___syscall40(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall40 (rmdir)");
     let pathname_addr = varargs.get_str(&ctx);
-    let real_path_owned = get_cstr_path(ctx, pathname_addr as *const _);
-    let real_path = if let Some(ref rp) = real_path_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        pathname_addr
-    };
-    unsafe { rmdir(real_path) }
+    match fs::resolve_path(&ctx, pathname_addr) {
+        Ok(ResolvedPath::Host(real_path)) => unsafe { rmdir(real_path.as_ptr()) },
+        Ok(ResolvedPath::Virtual(path)) => fs::rmdir(&ctx, &path),
+        Err(errno) => errno,
+    }
 }
 
 // pipe
@@ -414,7 +432,11 @@ pub fn ___syscall140(ctx: FunctionEnvMut<EmEnv>, _which: i32, mut varargs: VarAr
     let result_ptr_value: WasmPtr<i64> = varargs.get(&ctx);
     let whence: i32 = varargs.get(&ctx);
     let offset = offset_low;
-    let ret = unsafe { lseek(fd, offset as _, whence) as i64 };
+    let ret = match fs::seek(&ctx, fd, offset as i64, whence) {
+        Some(Ok(position)) => position,
+        Some(Err(errno)) => return errno,
+        None => unsafe { lseek(fd, offset as _, whence) as i64 },
+    };
     let memory = ctx.data().memory(0);
     let memory = memory.view(&ctx);
 
@@ -460,11 +482,25 @@ pub fn ___syscall145(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
                 as *mut c_void;
             let iov_len = (*guest_iov_addr).iov_len as _;
             // debug!("=> iov_addr: {:?}, {:?}", iov_base, iov_len);
-            let curr = read(fd, iov_base, iov_len);
+            let curr = match crate::io::read_stdio(
+                &ctx,
+                fd,
+                (*guest_iov_addr).iov_base as u32,
+                iov_len as u32,
+            )
+            .or_else(|| fs::read(&ctx, fd, (*guest_iov_addr).iov_base as u32, iov_len as u32))
+            {
+                Some(curr) => curr as _,
+                None => read(fd, iov_base, iov_len),
+            };
             if curr < 0 {
                 return -1;
             }
             ret += curr;
+            // Like `readv`, stop at the first short read
+            if (curr as i64) < iov_len as i64 {
+                break;
+            }
         }
         // debug!(" => ret: {}", ret);
         ret as _
@@ -497,7 +533,17 @@ pub fn ___syscall146(ctx: FunctionEnvMut<EmEnv>, _which: i32, mut varargs: VarAr
                 as *const c_void;
             let iov_len = (*guest_iov_addr).iov_len as _;
             // debug!("=> iov_addr: {:?}, {:?}", iov_base, iov_len);
-            let curr = write(fd, iov_base, iov_len);
+            let curr = match crate::io::write_stdio(
+                &ctx,
+                fd,
+                (*guest_iov_addr).iov_base as u32,
+                iov_len as u32,
+            )
+            .or_else(|| fs::write(&ctx, fd, (*guest_iov_addr).iov_base as u32, iov_len as u32))
+            {
+                Some(curr) => curr as _,
+                None => write(fd, iov_base, iov_len),
+            };
             debug!(
                 "=> iov_base: {}, iov_len: {}, curr = {}",
                 (*guest_iov_addr).iov_base,
@@ -539,27 +585,21 @@ pub fn ___syscall193(_ctx: FunctionEnvMut<EmEnv>, _one: i32, _two: i32) -> i32 {
 }
 
 // stat64
-pub fn ___syscall195(mut ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
+pub fn ___syscall195(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall195 (stat64) {}", _which);
     let pathname_addr = varargs.get_str(&ctx);
     let buf: u32 = varargs.get(&ctx);
 
-    let real_path_owned = get_cstr_path(ctx.as_mut(), pathname_addr as *const _);
-    let real_path = if let Some(ref rp) = real_path_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        pathname_addr
+    let real_path = match fs::resolve_path(&ctx, pathname_addr) {
+        Ok(ResolvedPath::Host(real_path)) => real_path,
+        Ok(ResolvedPath::Virtual(path)) => return fs::stat(&ctx, &path, true, buf),
+        Err(errno) => return errno,
     };
 
     unsafe {
         let mut _stat: stat = std::mem::zeroed();
-        let ret = stat(real_path, &mut _stat);
-        debug!(
-            "=> pathname: {}, buf: {} = {}",
-            std::ffi::CStr::from_ptr(real_path).to_str().unwrap(),
-            buf,
-            ret
-        );
+        let ret = stat(real_path.as_ptr(), &mut _stat);
+        debug!("=> pathname: {:?}, buf: {} = {}", real_path, buf, ret);
         if ret != 0 {
             debug!("=> os error: {}", Error::last_os_error());
             return ret;
@@ -575,6 +615,9 @@ pub fn ___syscall197(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
 
     let fd: c_int = varargs.get(&ctx);
     let buf: u32 = varargs.get(&ctx);
+    if let Some(ret) = fs::fstat(&ctx, fd, buf) {
+        return ret;
+    }
 
     unsafe {
         let mut stat = std::mem::zeroed();
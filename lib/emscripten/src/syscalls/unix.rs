@@ -19,6 +19,7 @@ use libc::{
     fchmod,
     fchown,
     fcntl,
+    fd_set,
     // ENOTTY,
     fsync,
     getegid,
@@ -50,6 +51,7 @@ use libc::{
     pwrite,
     readdir,
     // readv,
+    recv,
     recvfrom,
     recvmsg,
     // ENOTTY,
@@ -57,15 +59,18 @@ use libc::{
     sa_family_t,
     // writev,
     select,
+    send,
     sendmsg,
     sendto,
     setpgid,
     setsockopt,
+    shutdown,
     sockaddr,
     socket,
     socklen_t,
     stat,
     symlink,
+    timeval,
     uid_t,
     uname,
     utsname,
@@ -74,14 +79,17 @@ use libc::{
     FIOCLEX,
     FIONBIO,
     F_GETFD,
+    F_GETFL,
     F_SETFD,
+    F_SETFL,
+    O_NONBLOCK,
     SOL_SOCKET,
     TIOCGWINSZ,
     TIOCSPGRP,
     // TCGETS,
     // TCSETSW,
 };
-use wasmer::{FunctionEnvMut, ValueType, WasmPtr};
+use wasmer::{FunctionEnvMut, MemoryView, ValueType, WasmPtr, WasmSlice};
 
 // They are not exposed in in Rust libc in macOS
 const TCGETS: u64 = 0x5401;
@@ -115,7 +123,8 @@ fn translate_ioctl(wasm_ioctl: u32) -> c_ulong {
 use std::ffi::CStr;
 
 use crate::env::EmSockAddr;
-use crate::utils::{self, get_cstr_path};
+use crate::fs::{self, ResolvedPath};
+use crate::utils;
 use crate::EmEnv;
 #[allow(unused_imports)]
 use std::io::Error;
@@ -155,6 +164,10 @@ use libc::{fdatasync, ftruncate64, lstat, madvise, wait4};
 use libc::SO_NOSIGPIPE;
 #[cfg(not(target_vendor = "apple"))]
 const SO_NOSIGPIPE: c_int = 0;
+#[cfg(not(target_vendor = "apple"))]
+use libc::MSG_NOSIGNAL;
+#[cfg(target_vendor = "apple")]
+const MSG_NOSIGNAL: c_int = 0;
 
 /// open
 pub fn ___syscall5(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
@@ -162,12 +175,12 @@ pub fn ___syscall5(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarAr
     let pathname_addr = varargs.get_str(&ctx);
     let flags: i32 = varargs.get(&ctx);
     let mode: u32 = varargs.get(&ctx);
-    let real_path_owned = utils::get_cstr_path(ctx, pathname_addr as *const _);
-    let real_path = if let Some(ref rp) = real_path_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        pathname_addr
+    let real_path_owned = match fs::resolve_path(&ctx, pathname_addr) {
+        Ok(ResolvedPath::Host(path)) => path,
+        Ok(ResolvedPath::Virtual(path)) => return fs::open(&ctx, &path, flags),
+        Err(errno) => return errno,
     };
+    let real_path = real_path_owned.as_ptr();
     let _path_str = unsafe { std::ffi::CStr::from_ptr(real_path).to_str().unwrap() };
     let fd = unsafe { open(real_path, flags, mode) };
     debug!(
@@ -186,12 +199,18 @@ pub fn ___syscall9(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarAr
 
     let oldname_ptr = varargs.get_str(&ctx);
     let newname_ptr = varargs.get_str(&ctx);
-    let result = unsafe { link(oldname_ptr, newname_ptr) };
+    let oldname = match fs::host_path(&ctx, oldname_ptr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let newname = match fs::host_path(&ctx, newname_ptr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
+    };
+    let result = unsafe { link(oldname.as_ptr(), newname.as_ptr()) };
     debug!(
-        "=> oldname: {}, newname: {}, result: {}",
-        unsafe { std::ffi::CStr::from_ptr(oldname_ptr).to_str().unwrap() },
-        unsafe { std::ffi::CStr::from_ptr(newname_ptr).to_str().unwrap() },
-        result,
+        "=> oldname: {:?}, newname: {:?}, result: {}",
+        oldname, newname, result,
     );
     result
 }
@@ -210,23 +229,21 @@ pub fn ___syscall77(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarA
 }
 
 /// symlink
-pub fn ___syscall83(mut ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
+pub fn ___syscall83(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall83 (symlink) {}", _which);
 
     let path1 = varargs.get_str(&ctx);
     let path2 = varargs.get_str(&ctx);
-    let real_path1_owned = utils::get_cstr_path(ctx.as_mut(), path1 as *const _);
-    let real_path1 = if let Some(ref rp) = real_path1_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        path1
+    let real_path1_owned = match fs::host_path(&ctx, path1) {
+        Ok(path) => path,
+        Err(errno) => return errno,
     };
-    let real_path2_owned = utils::get_cstr_path(ctx, path2 as *const _);
-    let real_path2 = if let Some(ref rp) = real_path2_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        path2
+    let real_path1 = real_path1_owned.as_ptr();
+    let real_path2_owned = match fs::host_path(&ctx, path2) {
+        Ok(path) => path,
+        Err(errno) => return errno,
     };
+    let real_path2 = real_path2_owned.as_ptr();
     let result = unsafe { symlink(real_path1, real_path2) };
     debug!(
         "=> path1: {}, path2: {}, result: {}",
@@ -244,12 +261,11 @@ pub fn ___syscall85(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarA
     let buf = varargs.get_str(&ctx);
     // let buf_addr: i32 = varargs.get(&ctx);
     let buf_size: i32 = varargs.get(&ctx);
-    let real_path_owned = get_cstr_path(ctx, pathname_addr as *const _);
-    let real_path = if let Some(ref rp) = real_path_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        pathname_addr
+    let real_path_owned = match fs::host_path(&ctx, pathname_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
     };
+    let real_path = real_path_owned.as_ptr();
 
     let ret = unsafe { libc::readlink(real_path, buf as _, buf_size as _) as i32 };
     if ret == -1 {
@@ -284,15 +300,14 @@ pub fn ___syscall194(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
 }
 
 /// lchown
-pub fn ___syscall198(mut ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
+pub fn ___syscall198(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall198 (lchown) {}", _which);
     let path_ptr = varargs.get_str(&ctx);
-    let real_path_owned = utils::get_cstr_path(ctx.as_mut(), path_ptr as *const _);
-    let real_path = if let Some(ref rp) = real_path_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        path_ptr
+    let real_path_owned = match fs::host_path(&ctx, path_ptr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
     };
+    let real_path = real_path_owned.as_ptr();
     let uid: uid_t = varargs.get(&ctx);
     let gid: gid_t = varargs.get(&ctx);
     let result = unsafe { lchown(real_path, uid, gid) };
@@ -325,16 +340,15 @@ pub fn ___syscall205(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
 }
 
 // chown
-pub fn ___syscall212(mut ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
+pub fn ___syscall212(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall212 (chown) {}", _which);
 
     let pathname_addr = varargs.get_str(&ctx);
-    let real_path_owned = utils::get_cstr_path(ctx.as_mut(), pathname_addr as *const _);
-    let real_path = if let Some(ref rp) = real_path_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        pathname_addr
+    let real_path_owned = match fs::host_path(&ctx, pathname_addr) {
+        Ok(path) => path,
+        Err(errno) => return errno,
     };
+    let real_path = real_path_owned.as_ptr();
     let owner: u32 = varargs.get(&ctx);
     let group: u32 = varargs.get(&ctx);
 
@@ -356,15 +370,15 @@ pub fn ___syscall219(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
 }
 
 /// access
-pub fn ___syscall33(mut ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
+pub fn ___syscall33(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall33 (access) {}", _which);
     let path = varargs.get_str(&ctx);
-    let real_path_owned = utils::get_cstr_path(ctx.as_mut(), path as *const _);
-    let real_path = if let Some(ref rp) = real_path_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        path
+    let real_path_owned = match fs::resolve_path(&ctx, path) {
+        Ok(ResolvedPath::Host(path)) => path,
+        Ok(ResolvedPath::Virtual(path)) => return fs::access(&ctx, &path),
+        Err(errno) => return errno,
     };
+    let real_path = real_path_owned.as_ptr();
     let amode: c_int = varargs.get(&ctx);
     let result = unsafe { access(real_path, amode) };
     debug!(
@@ -384,15 +398,15 @@ pub fn ___syscall34(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarA
 }
 
 // mkdir
-pub fn ___syscall39(mut ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
+pub fn ___syscall39(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall39 (mkdir) {}", _which);
     let pathname_addr = varargs.get_str(&ctx);
-    let real_path_owned = utils::get_cstr_path(ctx.as_mut(), pathname_addr as *const _);
-    let real_path = if let Some(ref rp) = real_path_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        pathname_addr
+    let real_path_owned = match fs::resolve_path(&ctx, pathname_addr) {
+        Ok(ResolvedPath::Host(path)) => path,
+        Ok(ResolvedPath::Virtual(path)) => return fs::mkdir(&ctx, &path),
+        Err(errno) => return errno,
     };
+    let real_path = real_path_owned.as_ptr();
     let mode: u32 = varargs.get(&ctx);
     unsafe { mkdir(real_path, mode as _) }
 }
@@ -524,6 +538,13 @@ pub fn ___syscall102(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
     debug!("emscripten::___syscall102 (socketcall) {}", _which);
     let call: u32 = varargs.get(&ctx);
     let mut socket_varargs: VarArgs = varargs.get(&ctx);
+
+    if !ctx.data().networking() {
+        debug!("=> the module has not been granted access to the network");
+        // EACCES
+        return -13;
+    }
+
     let memory = ctx.data().memory(0);
     let view = memory.view(&ctx);
 
@@ -551,6 +572,9 @@ pub fn ___syscall102(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
             let protocol: i32 = socket_varargs.get(&ctx);
             let ty = ty_and_flags & (!SOCK_NON_BLOCK) & (!SOCK_CLOEXC);
             let fd = unsafe { socket(domain, ty, protocol) };
+            if fd == -1 {
+                return socket_error();
+            }
 
             if ty_and_flags & SOCK_CLOEXC != 0 {
                 // set_cloexec
@@ -560,22 +584,27 @@ pub fn ___syscall102(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
             }
 
             if ty_and_flags & SOCK_NON_BLOCK != 0 {
-                // do something here
-                unimplemented!("non blocking sockets");
+                unsafe {
+                    let flags = fcntl(fd, F_GETFL, 0);
+                    fcntl(fd, F_SETFL, flags | O_NONBLOCK);
+                };
             }
 
-            // why is this here?
-            type T = u32;
-            let payload = 1 as *const T as _;
-            unsafe {
-                setsockopt(
-                    fd,
-                    SOL_SOCKET,
-                    SO_NOSIGPIPE,
-                    payload,
-                    mem::size_of::<T>() as socklen_t,
-                );
-            };
+            // A closed connection must not kill the host with SIGPIPE, see
+            // also `MSG_NOSIGNAL` for the other platforms
+            if SO_NOSIGPIPE != 0 {
+                type T = u32;
+                let payload: T = 1;
+                unsafe {
+                    setsockopt(
+                        fd,
+                        SOL_SOCKET,
+                        SO_NOSIGPIPE,
+                        &payload as *const T as _,
+                        mem::size_of::<T>() as socklen_t,
+                    );
+                };
+            }
 
             debug!(
                 "=> domain: {}, type: {}, protocol: {} = fd: {}",
@@ -605,8 +634,7 @@ pub fn ___syscall102(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
                 "=> socketfd: {}, address: {:?}, address_len: {} = status: {}",
                 socket, address, address_len, status
             );
-            status
-            // -1
+            socket_result(status as _)
         }
         3 => {
             debug!("socket: connect");
@@ -616,7 +644,9 @@ pub fn ___syscall102(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
             let address: u32 = socket_varargs.get(&ctx);
             let address_len = socket_varargs.get(&ctx);
             let address = emscripten_memory_pointer!(&view, address) as *mut sockaddr;
-            unsafe { connect(socket, address, address_len) }
+            let status = unsafe { connect(socket, address, address_len) };
+            debug!("=> socketfd: {} = status: {}", socket, status);
+            socket_result(status as _)
         }
         4 => {
             debug!("socket: listen");
@@ -628,7 +658,7 @@ pub fn ___syscall102(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
                 "=> socketfd: {}, backlog: {} = status: {}",
                 socket, backlog, status
             );
-            status
+            socket_result(status as _)
         }
         5 => {
             debug!("socket: accept");
@@ -653,6 +683,9 @@ pub fn ___syscall102(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
                 sa_len: Default::default(),
             };
             let fd = unsafe { accept(socket, &mut host_address, &mut address_len_addr) };
+            if fd == -1 {
+                return socket_error();
+            }
             let mut address_addr = address.deref(&view).read().unwrap();
 
             address_addr.sa_family = host_address.sa_family as _;
@@ -702,7 +735,7 @@ pub fn ___syscall102(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
                 socket, address_mut, address_len_addr, ret
             );
 
-            ret
+            socket_result(ret as _)
         }
         7 => {
             debug!("socket: getpeername");
@@ -712,43 +745,121 @@ pub fn ___syscall102(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
             let address_len: u32 = socket_varargs.get(&ctx);
             let address = emscripten_memory_pointer!(view, address) as *mut sockaddr;
             let address_len_addr = emscripten_memory_pointer!(view, address_len) as *mut socklen_t;
-            unsafe { getpeername(socket, address, address_len_addr) }
+            socket_result(unsafe { getpeername(socket, address, address_len_addr) } as _)
+        }
+        9 => {
+            debug!("socket: send");
+            // send (socket: c_int, buf: *const c_void, len: size_t, flags: c_int) -> ssize_t
+            let socket = socket_varargs.get(&ctx);
+            let buf: u32 = socket_varargs.get(&ctx);
+            let len: u32 = socket_varargs.get(&ctx);
+            let flags: i32 = socket_varargs.get(&ctx);
+            let data = match guest_buffer(&view, buf, len).and_then(read_guest_buffer) {
+                Ok(data) => data,
+                Err(errno) => return errno,
+            };
+            let ret = unsafe { send(socket, data.as_ptr() as _, data.len(), flags | MSG_NOSIGNAL) };
+            debug!("=> socketfd: {}, len: {} = {}", socket, len, ret);
+            socket_result(ret)
+        }
+        10 => {
+            debug!("socket: recv");
+            // recv (socket: c_int, buf: *mut c_void, len: size_t, flags: c_int) -> ssize_t
+            let socket = socket_varargs.get(&ctx);
+            let buf: u32 = socket_varargs.get(&ctx);
+            let len: u32 = socket_varargs.get(&ctx);
+            let flags: i32 = socket_varargs.get(&ctx);
+            if let Err(errno) = guest_buffer(&view, buf, len) {
+                return errno;
+            }
+            let mut data = vec![0; len as usize];
+            let ret = unsafe { recv(socket, data.as_mut_ptr() as _, data.len(), flags) };
+            debug!("=> socketfd: {}, len: {} = {}", socket, len, ret);
+            write_received(&view, buf, &data, ret)
         }
         11 => {
             debug!("socket: sendto");
             // sendto (socket: c_int, buf: *const c_void, len: size_t, flags: c_int, addr: *const sockaddr, addrlen: socklen_t) -> ssize_t
             let socket = socket_varargs.get(&ctx);
             let buf: u32 = socket_varargs.get(&ctx);
-            let flags = socket_varargs.get(&ctx);
-            let len: i32 = socket_varargs.get(&ctx);
+            let len: u32 = socket_varargs.get(&ctx);
+            let flags: i32 = socket_varargs.get(&ctx);
             let address: u32 = socket_varargs.get(&ctx);
             let address_len = socket_varargs.get(&ctx);
-            let buf_addr = emscripten_memory_pointer!(view, buf) as _;
-            let address = emscripten_memory_pointer!(view, address) as *mut sockaddr;
-            unsafe { sendto(socket, buf_addr, flags, len, address, address_len) as i32 }
+            let data = match guest_buffer(&view, buf, len).and_then(read_guest_buffer) {
+                Ok(data) => data,
+                Err(errno) => return errno,
+            };
+            // A null address is used by connected sockets
+            let address = if address == 0 {
+                std::ptr::null()
+            } else {
+                if let Err(errno) = guest_buffer(&view, address, address_len) {
+                    return errno;
+                }
+                emscripten_memory_pointer!(view, address) as *const sockaddr
+            };
+            socket_result(unsafe {
+                sendto(
+                    socket,
+                    data.as_ptr() as _,
+                    data.len(),
+                    flags | MSG_NOSIGNAL,
+                    address,
+                    address_len,
+                )
+            })
         }
         12 => {
             debug!("socket: recvfrom");
             // recvfrom (socket: c_int, buf: *const c_void, len: size_t, flags: c_int, addr: *const sockaddr, addrlen: socklen_t) -> ssize_t
             let socket = socket_varargs.get(&ctx);
             let buf: u32 = socket_varargs.get(&ctx);
-            let len: i32 = socket_varargs.get(&ctx);
+            let len: u32 = socket_varargs.get(&ctx);
             let flags: i32 = socket_varargs.get(&ctx);
             let address: u32 = socket_varargs.get(&ctx);
             let address_len: u32 = socket_varargs.get(&ctx);
-            let buf_addr = emscripten_memory_pointer!(view, buf) as _;
-            let address = emscripten_memory_pointer!(view, address) as *mut sockaddr;
-            let address_len_addr = emscripten_memory_pointer!(view, address_len) as *mut socklen_t;
-            unsafe {
+            if let Err(errno) = guest_buffer(&view, buf, len) {
+                return errno;
+            }
+            // The sender address is optional
+            let (address, address_len_addr) = if address == 0 {
+                (std::ptr::null_mut(), std::ptr::null_mut())
+            } else {
+                let capacity = match WasmPtr::<socklen_t>::new(address_len).read(&view) {
+                    Ok(capacity) => capacity,
+                    // EFAULT
+                    Err(_) => return -14,
+                };
+                if let Err(errno) = guest_buffer(&view, address, capacity) {
+                    return errno;
+                }
+                (
+                    emscripten_memory_pointer!(view, address) as *mut sockaddr,
+                    emscripten_memory_pointer!(view, address_len) as *mut socklen_t,
+                )
+            };
+            let mut data = vec![0; len as usize];
+            let ret = unsafe {
                 recvfrom(
                     socket,
-                    buf_addr,
-                    len as usize,
+                    data.as_mut_ptr() as _,
+                    data.len(),
                     flags,
                     address,
                     address_len_addr,
-                ) as i32
-            }
+                )
+            };
+            write_received(&view, buf, &data, ret)
+        }
+        13 => {
+            debug!("socket: shutdown");
+            // shutdown (socket: c_int, how: c_int) -> c_int
+            let socket = socket_varargs.get(&ctx);
+            let how: i32 = socket_varargs.get(&ctx);
+            let status = unsafe { shutdown(socket, how) };
+            debug!("=> socketfd: {}, how: {} = status: {}", socket, how, status);
+            socket_result(status as _)
         }
         14 => {
             debug!("socket: setsockopt");
@@ -768,7 +879,7 @@ pub fn ___syscall102(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
             let ret = unsafe { setsockopt(socket, level, name, value_addr, option_len) };
 
             debug!("=> socketfd: {}, level: {}, name: {}, value_addr: {:?}, option_len: {} = status: {}", socket, level, untranslated_name, value_addr, option_len, ret);
-            ret
+            socket_result(ret as _)
         }
         15 => {
             debug!("socket: getsockopt");
@@ -782,7 +893,9 @@ pub fn ___syscall102(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
             let option_len: u32 = socket_varargs.get(&ctx);
             let value_addr = emscripten_memory_pointer!(view, value) as _;
             let option_len_addr = emscripten_memory_pointer!(view, option_len) as *mut socklen_t;
-            unsafe { getsockopt(socket, level, name, value_addr, option_len_addr) }
+            socket_result(
+                unsafe { getsockopt(socket, level, name, value_addr, option_len_addr) } as _,
+            )
         }
         16 => {
             debug!("socket: sendmsg");
@@ -791,7 +904,7 @@ pub fn ___syscall102(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
             let msg: u32 = socket_varargs.get(&ctx);
             let flags: i32 = socket_varargs.get(&ctx);
             let msg_addr = emscripten_memory_pointer!(view, msg) as *const msghdr;
-            unsafe { sendmsg(socket, msg_addr, flags) as i32 }
+            socket_result(unsafe { sendmsg(socket, msg_addr, flags | MSG_NOSIGNAL) })
         }
         17 => {
             debug!("socket: recvmsg");
@@ -800,15 +913,109 @@ pub fn ___syscall102(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
             let msg: u32 = socket_varargs.get(&ctx);
             let flags: i32 = socket_varargs.get(&ctx);
             let msg_addr = emscripten_memory_pointer!(view, msg) as *mut msghdr;
-            unsafe { recvmsg(socket, msg_addr, flags) as i32 }
+            socket_result(unsafe { recvmsg(socket, msg_addr, flags) })
         }
         _ => {
-            // others
-            -1
+            debug!("=> socketcall {} is not implemented", call);
+            // ENOSYS
+            -38
         }
     }
 }
 
+/// Returns the result of a socket function as the emscripten libc
+/// expects it, i.e. the negated `errno` on failure
+fn socket_result(ret: isize) -> c_int {
+    if ret < 0 {
+        socket_error()
+    } else {
+        ret as c_int
+    }
+}
+
+/// Returns the negated `errno` of the last failed socket function, as
+/// the emscripten libc numbers it
+fn socket_error() -> c_int {
+    let error = Error::last_os_error();
+    debug!("=> last os error: {}", error);
+    -translate_socket_errno(error.raw_os_error().unwrap_or(libc::EIO))
+}
+
+/// Translates an `errno` of the host into the one of the emscripten libc,
+/// which numbers them as Linux does
+fn translate_socket_errno(errno: c_int) -> c_int {
+    match errno {
+        libc::EPERM => 1,
+        libc::ENOENT => 2,
+        libc::EINTR => 4,
+        libc::EIO => 5,
+        libc::EBADF => 9,
+        libc::EAGAIN => 11,
+        libc::ENOMEM => 12,
+        libc::EACCES => 13,
+        libc::EFAULT => 14,
+        libc::EINVAL => 22,
+        libc::ENFILE => 23,
+        libc::EMFILE => 24,
+        libc::EPIPE => 32,
+        libc::ENOTSOCK => 88,
+        libc::EDESTADDRREQ => 89,
+        libc::EMSGSIZE => 90,
+        libc::EPROTOTYPE => 91,
+        libc::ENOPROTOOPT => 92,
+        libc::EPROTONOSUPPORT => 93,
+        libc::EOPNOTSUPP => 95,
+        libc::EAFNOSUPPORT => 97,
+        libc::EADDRINUSE => 98,
+        libc::EADDRNOTAVAIL => 99,
+        libc::ENETDOWN => 100,
+        libc::ENETUNREACH => 101,
+        libc::ECONNABORTED => 103,
+        libc::ECONNRESET => 104,
+        libc::ENOBUFS => 105,
+        libc::EISCONN => 106,
+        libc::ENOTCONN => 107,
+        libc::ETIMEDOUT => 110,
+        libc::ECONNREFUSED => 111,
+        libc::EHOSTUNREACH => 113,
+        libc::EALREADY => 114,
+        libc::EINPROGRESS => 115,
+        _ => 5,
+    }
+}
+
+/// Returns the `len` bytes at `buf` in the memory of the guest, or the
+/// negated `EFAULT` if they're out of its bounds
+fn guest_buffer<'a>(view: &'a MemoryView, buf: u32, len: u32) -> Result<WasmSlice<'a, u8>, c_int> {
+    if u64::from(buf) + u64::from(len) > view.data_size() {
+        // EFAULT
+        return Err(-14);
+    }
+    WasmPtr::<u8>::new(buf).slice(view, len).map_err(|_| -14)
+}
+
+/// Copies a buffer of the guest, or returns the negated `EFAULT`
+fn read_guest_buffer(buffer: WasmSlice<u8>) -> Result<Vec<u8>, c_int> {
+    buffer.read_to_vec().map_err(|_| -14)
+}
+
+/// Copies the `ret` bytes received in `data` to `buf` in the memory of
+/// the guest, and returns the result of the receiving function
+fn write_received(view: &MemoryView, buf: u32, data: &[u8], ret: isize) -> c_int {
+    if ret <= 0 {
+        return socket_result(ret);
+    }
+    let received = &data[..ret as usize];
+    match WasmPtr::<u8>::new(buf)
+        .slice(view, received.len() as u32)
+        .and_then(|buffer| buffer.write_slice(received))
+    {
+        Ok(()) => ret as c_int,
+        // EFAULT
+        Err(_) => -14,
+    }
+}
+
 /// OSX and BSD have completely different values, we must translate from emscripten's Linuxy
 /// value into one that we can pass to native syscalls
 fn translate_socket_name_flag(name: i32) -> i32 {
@@ -957,19 +1164,72 @@ pub fn ___syscall142(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
     let readfds: u32 = varargs.get(&ctx);
     let writefds: u32 = varargs.get(&ctx);
     let exceptfds: u32 = varargs.get(&ctx);
-    let _timeout: i32 = varargs.get(&ctx);
+    let timeout: u32 = varargs.get(&ctx);
 
     if nfds > 1024 {
         // EINVAL
         return -22;
     }
-    assert!(exceptfds == 0, "`exceptfds` is not supporrted");
 
     let memory = ctx.data().memory(0);
-    let readfds_ptr = emscripten_memory_pointer!(memory.view(&ctx), readfds) as _;
-    let writefds_ptr = emscripten_memory_pointer!(memory.view(&ctx), writefds) as _;
+    let view = memory.view(&ctx);
+    // The guest `fd_set`s have the same layout as the host ones, and are
+    // optional
+    let fd_set_ptr = |fds: u32| {
+        if fds == 0 {
+            std::ptr::null_mut()
+        } else {
+            emscripten_memory_pointer!(view, fds) as *mut fd_set
+        }
+    };
+    let readfds_ptr = fd_set_ptr(readfds);
+    let writefds_ptr = fd_set_ptr(writefds);
+    let exceptfds_ptr = fd_set_ptr(exceptfds);
 
-    unsafe { select(nfds, readfds_ptr, writefds_ptr, 0 as _, 0 as _) }
+    // A null timeout blocks until a file descriptor is ready
+    let guest_timeout = if timeout == 0 {
+        None
+    } else {
+        match WasmPtr::<i32>::new(timeout).slice(&view, 2) {
+            Ok(guest_timeout) => Some(guest_timeout),
+            // EFAULT
+            Err(_) => return -14,
+        }
+    };
+    let mut host_timeout = None;
+    if let Some(guest_timeout) = &guest_timeout {
+        match (guest_timeout.read(0), guest_timeout.read(1)) {
+            (Ok(tv_sec), Ok(tv_usec)) => {
+                host_timeout = Some(timeval {
+                    tv_sec: tv_sec as _,
+                    tv_usec: tv_usec as _,
+                })
+            }
+            // EFAULT
+            _ => return -14,
+        }
+    }
+    let timeout_ptr = host_timeout
+        .as_mut()
+        .map_or(std::ptr::null_mut(), |timeout| timeout as *mut timeval);
+
+    let ret = unsafe { select(nfds, readfds_ptr, writefds_ptr, exceptfds_ptr, timeout_ptr) };
+    debug!(
+        "=> nfds: {}, timeout: {:?} = {}",
+        nfds,
+        host_timeout.map(|timeout| (timeout.tv_sec, timeout.tv_usec)),
+        ret
+    );
+    if ret == -1 {
+        return socket_error();
+    }
+
+    // Like on Linux, the timeout is updated with the time not slept
+    if let (Some(guest_timeout), Some(host_timeout)) = (guest_timeout, host_timeout) {
+        let _ = guest_timeout.write(0, host_timeout.tv_sec as _);
+        let _ = guest_timeout.write(1, host_timeout.tv_usec as _);
+    }
+    ret
 }
 
 /// fdatasync
@@ -1007,16 +1267,16 @@ pub fn ___syscall122(ctx: FunctionEnvMut<EmEnv>, _which: c_int, mut varargs: Var
 }
 
 /// lstat64
-pub fn ___syscall196(mut ctx: FunctionEnvMut<EmEnv>, _which: i32, mut varargs: VarArgs) -> i32 {
+pub fn ___syscall196(ctx: FunctionEnvMut<EmEnv>, _which: i32, mut varargs: VarArgs) -> i32 {
     debug!("emscripten::___syscall196 (lstat64) {}", _which);
     let path = varargs.get_str(&ctx);
-    let real_path_owned = utils::get_cstr_path(ctx.as_mut(), path as *const _);
-    let real_path = if let Some(ref rp) = real_path_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        path
-    };
     let buf_ptr: u32 = varargs.get(&ctx);
+    let real_path_owned = match fs::resolve_path(&ctx, path) {
+        Ok(ResolvedPath::Host(path)) => path,
+        Ok(ResolvedPath::Virtual(path)) => return fs::stat(&ctx, &path, false, buf_ptr),
+        Err(errno) => return errno,
+    };
+    let real_path = real_path_owned.as_ptr();
     unsafe {
         let mut stat: stat = std::mem::zeroed();
 
@@ -1060,6 +1320,9 @@ pub fn ___syscall220(ctx: FunctionEnvMut<EmEnv>, _which: i32, mut varargs: VarAr
         "emscripten::___syscall220 (getdents) {} {} {}",
         fd, dirp_addr, count
     );
+    if let Some(ret) = fs::getdents(&ctx, fd, dirp_addr as u32, count) {
+        return ret;
+    }
 
     let dirp = emscripten_memory_pointer!(ctx.data().memory(0).view(&ctx), dirp_addr) as *mut u8;
 
@@ -1,4 +1,5 @@
-use crate::utils::{copy_cstr_into_wasm, get_cstr_path};
+use crate::fs::{self, ResolvedPath};
+use crate::utils::copy_cstr_into_wasm;
 use crate::varargs::VarArgs;
 use crate::EmEnv;
 use libc::mkdir;
@@ -19,14 +20,14 @@ pub fn ___syscall5(mut ctx: FunctionEnvMut<EmEnv>, which: c_int, mut varargs: Va
     #[cfg(not(feature = "debug"))]
     let _ = which;
     let pathname_addr = varargs.get_str(&ctx);
-    let real_path_owned = get_cstr_path(ctx.as_mut(), pathname_addr);
-    let real_path = if let Some(ref rp) = real_path_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        pathname_addr
-    };
     let flags: i32 = varargs.get(&ctx);
     let mode: u32 = varargs.get(&ctx);
+    let real_path_owned = match fs::resolve_path(&ctx, pathname_addr) {
+        Ok(ResolvedPath::Host(path)) => path,
+        Ok(ResolvedPath::Virtual(path)) => return fs::open(&ctx, &path, flags),
+        Err(errno) => return errno,
+    };
+    let real_path = real_path_owned.as_ptr();
     let path_str = unsafe { std::ffi::CStr::from_ptr(real_path).to_str().unwrap() };
 
     match path_str {
@@ -105,13 +106,11 @@ pub fn ___syscall39(ctx: FunctionEnvMut<EmEnv>, which: c_int, mut varargs: VarAr
     #[cfg(not(feature = "debug"))]
     let _ = which;
     let pathname_addr = varargs.get_str(&ctx);
-    let real_path_owned = get_cstr_path(ctx, pathname_addr);
-    let real_path = if let Some(ref rp) = real_path_owned {
-        rp.as_c_str().as_ptr()
-    } else {
-        pathname_addr
-    };
-    unsafe { mkdir(real_path) }
+    match fs::resolve_path(&ctx, pathname_addr) {
+        Ok(ResolvedPath::Host(real_path)) => unsafe { mkdir(real_path.as_ptr()) },
+        Ok(ResolvedPath::Virtual(path)) => fs::mkdir(&ctx, &path),
+        Err(errno) => errno,
+    }
 }
 
 /// dup
@@ -9,6 +9,7 @@ use std::os::raw::c_char;
 use std::path::PathBuf;
 use std::slice;
 use wasmer::{FunctionEnvMut, GlobalInit, MemoryView, Module, Pages, WasmPtr};
+use wasmer_vfs::Metadata;
 
 /// We check if a provided module is an Emscripten generated one
 pub fn is_emscripten_module(module: &Module) -> bool {
@@ -237,6 +238,42 @@ pub unsafe fn copy_stat_into_wasm(ctx: FunctionEnvMut<EmEnv>, buf: u32, stat: &s
     (*stat_ptr).st_ino = stat.st_ino as _;
 }
 
+/// Like [`copy_stat_into_wasm`], for a file of a `wasmer_vfs::FileSystem`,
+/// which has no owner nor permissions
+#[allow(clippy::cast_ptr_alignment)]
+pub fn copy_metadata_into_wasm(ctx: &FunctionEnvMut<EmEnv>, buf: u32, metadata: &Metadata) {
+    let ft = metadata.file_type();
+    let mode = if ft.is_dir() {
+        0o040755
+    } else if ft.is_symlink() {
+        0o120777
+    } else if ft.is_char_device() {
+        0o020644
+    } else if ft.is_block_device() {
+        0o060644
+    } else if ft.is_socket() {
+        0o140644
+    } else if ft.is_fifo() {
+        0o010644
+    } else {
+        0o100644
+    };
+    let memory = ctx.data().memory(0);
+    unsafe {
+        let stat_ptr = emscripten_memory_pointer!(memory.view(ctx), buf) as *mut GuestStat;
+        std::ptr::write_bytes(stat_ptr, 0, 1);
+        (*stat_ptr).st_mode = mode;
+        (*stat_ptr).st_nlink = 1;
+        (*stat_ptr).st_size = metadata.len() as _;
+        (*stat_ptr).st_blksize = 4096;
+        (*stat_ptr).st_blocks = ((metadata.len() + 511) / 512) as _;
+        // The times of the filesystem are in nanoseconds
+        (*stat_ptr).st_atime = metadata.accessed() / 1_000_000_000;
+        (*stat_ptr).st_mtime = metadata.modified() / 1_000_000_000;
+        (*stat_ptr).st_ctime = metadata.created() / 1_000_000_000;
+    }
+}
+
 #[allow(dead_code)] // it's used in `env/windows/mod.rs`.
 pub fn read_string_from_wasm(memory: &MemoryView, offset: u32) -> String {
     WasmPtr::<u8>::new(offset)
@@ -244,44 +281,6 @@ pub fn read_string_from_wasm(memory: &MemoryView, offset: u32) -> String {
         .unwrap()
 }
 
-/// This function trys to find an entry in mapdir
-/// translating paths into their correct value
-pub fn get_cstr_path(ctx: FunctionEnvMut<EmEnv>, path: *const i8) -> Option<std::ffi::CString> {
-    use std::collections::VecDeque;
-
-    let path_str =
-        unsafe { std::ffi::CStr::from_ptr(path as *const _).to_str().unwrap() }.to_string();
-    let data = get_emscripten_data(&ctx);
-    let path = PathBuf::from(path_str);
-    let mut prefix_added = false;
-    let mut components = path.components().collect::<VecDeque<_>>();
-    // TODO(mark): handle absolute/non-canonical/non-relative paths too (this
-    // functionality should be shared among the abis)
-    if components.len() == 1 {
-        components.push_front(std::path::Component::CurDir);
-        prefix_added = true;
-    }
-    let mut cumulative_path = PathBuf::new();
-    for c in components.into_iter() {
-        cumulative_path.push(c);
-        if let Some(val) = data
-            .as_ref()
-            .unwrap()
-            .mapped_dirs
-            .get(&cumulative_path.to_string_lossy().to_string())
-        {
-            let rest_of_path = if !prefix_added {
-                path.strip_prefix(cumulative_path).ok()?
-            } else {
-                &path
-            };
-            let rebased_path = val.join(rest_of_path);
-            return std::ffi::CString::new(rebased_path.to_string_lossy().as_bytes()).ok();
-        }
-    }
-    None
-}
-
 /// gets the current directory
 /// handles mapdir logic
 pub fn get_current_directory(ctx: FunctionEnvMut<EmEnv>) -> Option<PathBuf> {
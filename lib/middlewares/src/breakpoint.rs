@@ -0,0 +1,354 @@
+//! `breakpoint` is a middleware for stopping the execution of a
+//! WebAssembly instance when it reaches given offsets in the original
+//! Wasm binary. It works with every compiler, since it only rewrites
+//! the operator stream.
+//!
+//! When a breakpoint is hit, the instance traps with an `unreachable`
+//! trap and the offset of the breakpoint is recorded in the instance;
+//! it can be retrieved with [`get_breakpoint_hit`].
+
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::Mutex;
+use wasmer::wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
+use wasmer::{
+    AsStoreMut, ExportIndex, FunctionMiddleware, GlobalIndex, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleInfo, ModuleMiddleware,
+    Mutability, Type,
+};
+
+#[derive(Clone)]
+struct BreakpointGlobalIndexes(GlobalIndex, GlobalIndex);
+
+impl BreakpointGlobalIndexes {
+    /// The global index in the current module for a boolean indicating whether breakpoints are
+    /// enabled or not.
+    /// This boolean is represented as a i32 global:
+    ///   * 0: breakpoints are disabled
+    ///   * 1: breakpoints are enabled
+    fn enabled(&self) -> GlobalIndex {
+        self.0
+    }
+
+    /// The global index in the current module for the offset of the last breakpoint hit, or -1
+    /// if no breakpoint has been hit.
+    fn hit(&self) -> GlobalIndex {
+        self.1
+    }
+}
+
+impl fmt::Debug for BreakpointGlobalIndexes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BreakpointGlobalIndexes")
+            .field("enabled", &self.enabled())
+            .field("hit", &self.hit())
+            .finish()
+    }
+}
+
+/// The module-level breakpoint middleware.
+///
+/// # Panic
+///
+/// An instance of `Breakpoints` should _not_ be shared among
+/// different modules, since it tracks module-specific information
+/// like the global index to store breakpoint state. Attempts to use a
+/// `Breakpoints` instance from multiple modules will result in a
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::Breakpoints;
+///
+/// fn create_breakpoint_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     // Stop before executing the operators at these offsets
+///     // of the Wasm binary.
+///     let breakpoints = Arc::new(Breakpoints::new(vec![0x2a, 0x30]));
+///
+///     compiler_config.push_middleware(breakpoints);
+/// }
+/// ```
+pub struct Breakpoints {
+    /// Offsets in the Wasm binary of the operators to break on.
+    offsets: BTreeSet<usize>,
+
+    /// The global indexes for breakpoint state.
+    global_indexes: Mutex<Option<BreakpointGlobalIndexes>>,
+}
+
+/// The function-level breakpoint middleware.
+pub struct FunctionBreakpoints {
+    /// Offsets in the Wasm binary of the operators to break on.
+    offsets: BTreeSet<usize>,
+
+    /// The global indexes for breakpoint state.
+    global_indexes: BreakpointGlobalIndexes,
+}
+
+impl Breakpoints {
+    /// Creates a `Breakpoints` middleware breaking on the operators
+    /// located at the given offsets of the Wasm binary.
+    pub fn new(offsets: impl IntoIterator<Item = usize>) -> Self {
+        Self {
+            offsets: offsets.into_iter().collect(),
+            global_indexes: Mutex::new(None),
+        }
+    }
+}
+
+impl fmt::Debug for Breakpoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Breakpoints")
+            .field("offsets", &self.offsets)
+            .field("global_indexes", &self.global_indexes)
+            .finish()
+    }
+}
+
+impl ModuleMiddleware for Breakpoints {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionBreakpoints {
+            offsets: self.offsets.clone(),
+            global_indexes: self.global_indexes.lock().unwrap().clone().unwrap(),
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut global_indexes = self.global_indexes.lock().unwrap();
+
+        if global_indexes.is_some() {
+            panic!("Breakpoints::transform_module_info: Attempting to use a `Breakpoints` middleware from multiple modules.");
+        }
+
+        // Append a global for the enabled boolean and initialize it.
+        let enabled_global_index = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(1));
+
+        module_info.exports.insert(
+            "wasmer_breakpoints_enabled".to_string(),
+            ExportIndex::Global(enabled_global_index),
+        );
+
+        // Append a global for the offset of the breakpoint hit and initialize it.
+        let hit_global_index = module_info
+            .globals
+            .push(GlobalType::new(Type::I64, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I64Const(-1));
+
+        module_info.exports.insert(
+            "wasmer_breakpoint_hit".to_string(),
+            ExportIndex::Global(hit_global_index),
+        );
+
+        *global_indexes = Some(BreakpointGlobalIndexes(
+            enabled_global_index,
+            hit_global_index,
+        ))
+    }
+}
+
+impl fmt::Debug for FunctionBreakpoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionBreakpoints")
+            .field("offsets", &self.offsets)
+            .field("global_indexes", &self.global_indexes)
+            .finish()
+    }
+}
+
+impl FunctionMiddleware for FunctionBreakpoints {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        let offset = state.current_operator_offset();
+
+        // Operators generated by previous middlewares share the offset of
+        // the original operator, so only break once per offset.
+        if self.offsets.remove(&offset) {
+            state.extend(&[
+                // if globals[enabled_index] { globals[hit_index] = offset; throw(); }
+                Operator::GlobalGet {
+                    global_index: self.global_indexes.enabled().as_u32(),
+                },
+                Operator::If {
+                    ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+                },
+                Operator::I64Const {
+                    value: offset as i64,
+                },
+                Operator::GlobalSet {
+                    global_index: self.global_indexes.hit().as_u32(),
+                },
+                Operator::Unreachable,
+                Operator::End,
+            ]);
+        }
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// Get the offset in the Wasm binary of the last breakpoint hit by an
+/// [`Instance`][wasmer::Instance], if any.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Breakpoints`] middleware at compile time, otherwise this
+/// will panic.
+///
+/// # Example
+///
+/// ```rust
+/// use wasmer::{AsStoreMut, Instance};
+/// use wasmer_middlewares::breakpoint::get_breakpoint_hit;
+///
+/// fn report_breakpoint(store: &mut impl AsStoreMut, instance: &Instance) {
+///     if let Some(offset) = get_breakpoint_hit(store, instance) {
+///         println!("stopped at breakpoint 0x{:x}", offset);
+///     }
+/// }
+/// ```
+pub fn get_breakpoint_hit(ctx: &mut impl AsStoreMut, instance: &Instance) -> Option<usize> {
+    let hit: i64 = instance
+        .exports
+        .get_global("wasmer_breakpoint_hit")
+        .expect("Can't get `wasmer_breakpoint_hit` from Instance")
+        .get(ctx)
+        .try_into()
+        .expect("`wasmer_breakpoint_hit` from Instance has wrong type");
+
+    if hit < 0 {
+        None
+    } else {
+        Some(hit as usize)
+    }
+}
+
+/// Forget the last breakpoint hit by an
+/// [`Instance`][wasmer::Instance], so that [`get_breakpoint_hit`]
+/// returns `None` until another breakpoint is hit.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Breakpoints`] middleware at compile time, otherwise this
+/// will panic.
+pub fn clear_breakpoint_hit(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    instance
+        .exports
+        .get_global("wasmer_breakpoint_hit")
+        .expect("Can't get `wasmer_breakpoint_hit` from Instance")
+        .set(ctx, (-1i64).into())
+        .expect("Can't set `wasmer_breakpoint_hit` in Instance");
+}
+
+/// Enable or disable all breakpoints of an
+/// [`Instance`][wasmer::Instance]. Breakpoints are enabled by
+/// default.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Breakpoints`] middleware at compile time, otherwise this
+/// will panic.
+pub fn set_breakpoints_enabled(ctx: &mut impl AsStoreMut, instance: &Instance, enabled: bool) {
+    instance
+        .exports
+        .get_global("wasmer_breakpoints_enabled")
+        .expect("Can't get `wasmer_breakpoints_enabled` from Instance")
+        .set(ctx, (enabled as i32).into())
+        .expect("Can't set `wasmer_breakpoints_enabled` in Instance");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::wasmparser::{Parser, Payload};
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    fn bytecode() -> Vec<u8> {
+        wat2wasm(
+            br#"
+            (module
+            (type $add_t (func (param i32) (result i32)))
+            (func $add_one_f (type $add_t) (param $value i32) (result i32)
+                local.get $value
+                i32.const 1
+                i32.add)
+            (export "add_one" (func $add_one_f)))
+            "#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    /// Offsets of the operators of the first function body.
+    fn operator_offsets(bytecode: &[u8]) -> Vec<usize> {
+        for payload in Parser::new(0).parse_all(bytecode) {
+            if let Payload::CodeSectionEntry(body) = payload.unwrap() {
+                let mut reader = body.get_operators_reader().unwrap();
+                let mut offsets = vec![];
+                while !reader.eof() {
+                    offsets.push(reader.read_with_offset().unwrap().1);
+                }
+                return offsets;
+            }
+        }
+        unreachable!("no function body")
+    }
+
+    #[test]
+    fn breakpoint_is_hit() {
+        let bytecode = bytecode();
+        let offsets = operator_offsets(&bytecode);
+        // Break on `i32.add`.
+        let breakpoint = offsets[2];
+
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(Breakpoints::new(vec![breakpoint])));
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode).unwrap();
+
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        assert_eq!(get_breakpoint_hit(&mut store, &instance), None);
+
+        let add_one: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("add_one")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        assert!(add_one.call(&mut store, 1).is_err());
+        assert_eq!(get_breakpoint_hit(&mut store, &instance), Some(breakpoint));
+
+        clear_breakpoint_hit(&mut store, &instance);
+        assert_eq!(get_breakpoint_hit(&mut store, &instance), None);
+
+        // Disabled breakpoints let the execution continue.
+        set_breakpoints_enabled(&mut store, &instance, false);
+        assert_eq!(add_one.call(&mut store, 1).unwrap(), 2);
+        assert_eq!(get_breakpoint_hit(&mut store, &instance), None);
+    }
+}
@@ -0,0 +1,683 @@
+//! `coverage` is a middleware for measuring the code coverage of a
+//! WebAssembly instance, e.g. of a test suite compiled to WebAssembly.
+//!
+//! The functions are split into regions, the ranges of the original
+//! Wasm binary that run straight, without branching, and each region
+//! sets a bit when it's entered. The coverage is read with
+//! [`Coverage::report`], and can be written with the source lines,
+//! when the module has DWARF line info, as an lcov tracefile with
+//! [`CoverageReport::write_lcov`] or as JSON with
+//! [`CoverageReport::write_json`].
+//!
+//! The bits live in exported `i64` globals of the instance, named
+//! `wasmer_coverage_*`. Their number is set when the module is
+//! transformed, before its code is read: the regions found once they
+//! are all used aren't instrumented.
+//!
+//! The regions are found in the operators the middleware is given, so
+//! it should come first in the middleware chain, before the ones that
+//! add branches.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use wasmer::wasmparser::{Operator, Parser, Payload};
+use wasmer::{
+    AsStoreMut, ExportIndex, Extern, FunctionIndex, FunctionMetadata, FunctionMiddleware,
+    GlobalInit, GlobalType, Instance, LocalFunctionIndex, MiddlewareError, MiddlewareReaderState,
+    ModuleInfo, ModuleMiddleware, Mutability, Type, Value,
+};
+
+/// The regions tracked by each global.
+const REGIONS_PER_GLOBAL: u32 = 64;
+
+/// The prefix of the names of the globals.
+const GLOBAL_PREFIX: &str = "wasmer_coverage_";
+
+#[derive(Debug, Clone)]
+struct CoverageGlobalIndexes {
+    /// The first global; the other ones follow.
+    first: u32,
+
+    /// The number of globals.
+    count: u32,
+
+    /// The number of imported functions of the module.
+    num_imported_functions: usize,
+}
+
+/// The regions of a function, as their start offset and bit, and the
+/// end offset of the function.
+#[derive(Debug, Clone)]
+struct FunctionRegions {
+    regions: Vec<(usize, Option<u32>)>,
+    end: usize,
+}
+
+/// The module-level coverage middleware.
+///
+/// # Panic
+///
+/// An instance of `Coverage` should _not_ be shared among different
+/// modules, since it tracks module-specific information like the
+/// regions of the functions. Attempts to use a `Coverage` instance
+/// from multiple modules will result in a panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::Coverage;
+///
+/// fn create_coverage_middleware(compiler_config: &mut dyn CompilerConfig) -> Arc<Coverage> {
+///     let coverage = Arc::new(Coverage::new());
+///     compiler_config.push_middleware(coverage.clone());
+///     coverage
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Coverage {
+    /// The number of regions that can be instrumented, or `None` for
+    /// 64 per function.
+    max_regions: Option<u32>,
+
+    /// The global indexes of the bits.
+    global_indexes: Mutex<Option<CoverageGlobalIndexes>>,
+
+    /// The next bit to give to a region.
+    next_bit: Arc<AtomicU32>,
+
+    /// The regions of the functions.
+    regions: FunctionMetadata<FunctionRegions>,
+}
+
+/// The function-level coverage middleware.
+pub struct FunctionCoverage {
+    /// The index of the function in the module.
+    local_function_index: LocalFunctionIndex,
+
+    /// The global indexes of the bits.
+    global_indexes: CoverageGlobalIndexes,
+
+    /// The next bit to give to a region, shared by the functions.
+    next_bit: Arc<AtomicU32>,
+
+    /// The regions found so far.
+    regions: Vec<(usize, Option<u32>)>,
+
+    /// Whether the next operator starts a region.
+    region_pending: bool,
+
+    /// The depth of the block being fed.
+    depth: u32,
+
+    /// Where the regions are stored once the function is fed.
+    metadata: FunctionMetadata<FunctionRegions>,
+}
+
+impl Coverage {
+    /// Creates a `Coverage` middleware, which can instrument 64 regions
+    /// per function, on average.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a `Coverage` middleware, which can instrument
+    /// `max_regions` regions in the whole module.
+    pub fn with_max_regions(max_regions: u32) -> Self {
+        Self {
+            max_regions: Some(max_regions),
+            ..Self::default()
+        }
+    }
+
+    /// Reads the coverage of an [`Instance`][wasmer::Instance] of the
+    /// module this middleware has been used for.
+    ///
+    /// # Panic
+    ///
+    /// The [`Instance`][wasmer::Instance] must have been processed with
+    /// this middleware at compile time, otherwise this will panic.
+    pub fn report(&self, ctx: &mut impl AsStoreMut, instance: &Instance) -> CoverageReport {
+        let global_indexes = self.global_indexes.lock().unwrap().clone().unwrap();
+        let bits = (0..global_indexes.count)
+            .map(|index| {
+                let name = format!("{}{}", GLOBAL_PREFIX, index);
+                let value = instance
+                    .exports
+                    .get_global(&name)
+                    .unwrap_or_else(|_| panic!("Can't get `{}` from Instance", name))
+                    .get(ctx);
+                value
+                    .i64()
+                    .unwrap_or_else(|| panic!("`{}` from Instance has wrong type", name))
+                    as u64
+            })
+            .collect::<Vec<_>>();
+
+        let functions = self
+            .regions
+            .to_vec()
+            .into_iter()
+            .map(|(local_function_index, function)| {
+                let ends = function
+                    .regions
+                    .iter()
+                    .skip(1)
+                    .map(|(start, _)| *start)
+                    .chain(Some(function.end));
+                CoveredFunction {
+                    index: FunctionIndex::from_u32(
+                        global_indexes.num_imported_functions as u32
+                            + local_function_index.as_u32(),
+                    ),
+                    regions: function
+                        .regions
+                        .iter()
+                        .zip(ends)
+                        .map(|((start, bit), end)| RegionCoverage {
+                            start: *start,
+                            end,
+                            executed: bit.map(|bit| {
+                                bits[(bit / REGIONS_PER_GLOBAL) as usize]
+                                    & (1 << (bit % REGIONS_PER_GLOBAL))
+                                    != 0
+                            }),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        CoverageReport { functions }
+    }
+}
+
+impl ModuleMiddleware for Coverage {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionCoverage {
+            local_function_index,
+            global_indexes: self.global_indexes.lock().unwrap().clone().unwrap(),
+            next_bit: self.next_bit.clone(),
+            regions: vec![],
+            region_pending: true,
+            depth: 0,
+            metadata: self.regions.clone(),
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut global_indexes = self.global_indexes.lock().unwrap();
+
+        if global_indexes.is_some() {
+            panic!("Coverage::transform_module_info: Attempting to use a `Coverage` middleware from multiple modules.");
+        }
+
+        let num_imported_functions = module_info.num_imported_functions;
+        let count = match self.max_regions {
+            Some(max_regions) => (max_regions + REGIONS_PER_GLOBAL - 1) / REGIONS_PER_GLOBAL,
+            None => (module_info.functions.len() - num_imported_functions) as u32,
+        };
+
+        let mut first = module_info.globals.len() as u32;
+        for index in 0..count {
+            let global_index = module_info
+                .globals
+                .push(GlobalType::new(Type::I64, Mutability::Var));
+            module_info
+                .global_initializers
+                .push(GlobalInit::I64Const(0));
+            module_info.exports.insert(
+                format!("{}{}", GLOBAL_PREFIX, index),
+                ExportIndex::Global(global_index),
+            );
+            if index == 0 {
+                first = global_index.as_u32();
+            }
+        }
+
+        *global_indexes = Some(CoverageGlobalIndexes {
+            first,
+            count,
+            num_imported_functions,
+        });
+    }
+}
+
+impl fmt::Debug for FunctionCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionCoverage")
+            .field("local_function_index", &self.local_function_index)
+            .field("global_indexes", &self.global_indexes)
+            .finish()
+    }
+}
+
+impl FunctionCoverage {
+    /// Starts a region at `offset`, or, if an earlier middleware has
+    /// added operators for the same offset, enters the region again.
+    fn enter_region(&mut self, offset: usize, state: &mut MiddlewareReaderState<'_>) {
+        let bit = match self.regions.last() {
+            Some((start, bit)) if *start == offset => *bit,
+            _ => {
+                let capacity = self.global_indexes.count * REGIONS_PER_GLOBAL;
+                let bit = self.next_bit.fetch_add(1, Ordering::SeqCst);
+                let bit = if bit < capacity { Some(bit) } else { None };
+                self.regions.push((offset, bit));
+                bit
+            }
+        };
+
+        if let Some(bit) = bit {
+            let global_index = self.global_indexes.first + bit / REGIONS_PER_GLOBAL;
+            state.extend(&[
+                // globals[bit / 64] |= 1 << (bit % 64);
+                Operator::GlobalGet { global_index },
+                Operator::I64Const {
+                    value: 1 << (bit % REGIONS_PER_GLOBAL),
+                },
+                Operator::I64Or,
+                Operator::GlobalSet { global_index },
+            ]);
+        }
+    }
+}
+
+impl FunctionMiddleware for FunctionCoverage {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        let offset = state.current_operator_offset();
+
+        // The operators ending a block belong to the region before
+        // them, which runs up to them.
+        if self.region_pending
+            && (self.regions.is_empty() || !matches!(operator, Operator::Else | Operator::End))
+        {
+            self.region_pending = false;
+            self.enter_region(offset, state);
+        }
+
+        match &operator {
+            Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
+                self.depth += 1;
+                self.region_pending = true;
+            }
+            Operator::End if self.depth == 0 => {
+                self.metadata.insert(
+                    self.local_function_index,
+                    FunctionRegions {
+                        regions: std::mem::take(&mut self.regions),
+                        end: offset + 1,
+                    },
+                );
+            }
+            Operator::End => {
+                self.depth -= 1;
+                self.region_pending = true;
+            }
+            Operator::Else
+            | Operator::Br { .. }
+            | Operator::BrIf { .. }
+            | Operator::BrTable { .. }
+            | Operator::Return
+            | Operator::Unreachable
+            | Operator::Call { .. }
+            | Operator::CallIndirect { .. } => {
+                self.region_pending = true;
+            }
+            _ => {}
+        }
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// The coverage of a region of a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionCoverage {
+    /// The offset of the region in the original Wasm binary.
+    pub start: usize,
+
+    /// The offset of the end of the region, excluded.
+    pub end: usize,
+
+    /// Whether the region has been executed, or `None` if it hasn't
+    /// been instrumented, because all the globals were used.
+    pub executed: Option<bool>,
+}
+
+/// The coverage of a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoveredFunction {
+    /// The index of the function in the module.
+    pub index: FunctionIndex,
+
+    /// The regions of the function, in order.
+    pub regions: Vec<RegionCoverage>,
+}
+
+/// The coverage of an [`Instance`][wasmer::Instance], see
+/// [`Coverage::report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageReport {
+    /// The coverage of the functions defined by the module.
+    pub functions: Vec<CoveredFunction>,
+}
+
+/// The lines of the source files, with whether they have been
+/// executed, sorted by file and line.
+type LineCoverage = BTreeMap<String, BTreeMap<u64, bool>>;
+
+impl CoverageReport {
+    /// Returns whether the region at `offset` has been executed, or
+    /// `None` if there is no instrumented region there.
+    fn executed_at(&self, offset: usize) -> Option<bool> {
+        self.functions
+            .iter()
+            .flat_map(|function| function.regions.iter())
+            .find(|region| region.start <= offset && offset < region.end)
+            .and_then(|region| region.executed)
+    }
+
+    /// Maps the regions to the lines of the source files, with the
+    /// DWARF line info of `wasm`, the binary of the module, or returns
+    /// `None` if it hasn't any.
+    ///
+    /// A line has been executed if one of the regions it's compiled
+    /// to has been.
+    pub fn lines(&self, wasm: &[u8]) -> io::Result<Option<LineCoverage>> {
+        let mut code_start = None;
+        let mut sections = HashMap::new();
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload.map_err(invalid_data)? {
+                Payload::CodeSectionStart { range, .. } => code_start = Some(range.start),
+                Payload::CustomSection { name, data, .. } => {
+                    sections.insert(name, data);
+                }
+                _ => {}
+            }
+        }
+        let code_start = match code_start {
+            Some(code_start) if sections.contains_key(".debug_line") => code_start,
+            _ => return Ok(None),
+        };
+
+        let dwarf = gimli::Dwarf::load(|id| -> Result<_, gimli::Error> {
+            let data = sections.get(id.name()).copied().unwrap_or(&[]);
+            Ok(gimli::EndianSlice::new(data, gimli::LittleEndian))
+        })
+        .map_err(invalid_data)?;
+
+        let mut lines = LineCoverage::new();
+        let mut units = dwarf.units();
+        while let Some(header) = units.next().map_err(invalid_data)? {
+            let unit = dwarf.unit(header).map_err(invalid_data)?;
+            let program = match unit.line_program.clone() {
+                Some(program) => program,
+                None => continue,
+            };
+
+            let mut rows = program.rows();
+            while let Some((header, row)) = rows.next_row().map_err(invalid_data)? {
+                let (line, file) = match (row.line(), row.file(header)) {
+                    (Some(line), Some(file)) if !row.end_sequence() => (line.get(), file),
+                    _ => continue,
+                };
+                // The addresses are relative to the code section.
+                let executed = match self.executed_at(code_start + row.address() as usize) {
+                    Some(executed) => executed,
+                    None => continue,
+                };
+
+                let mut path = dwarf
+                    .attr_string(&unit, file.path_name())
+                    .map_err(invalid_data)?
+                    .to_string_lossy()
+                    .into_owned();
+                if let Some(directory) = file.directory(header) {
+                    let directory = dwarf
+                        .attr_string(&unit, directory)
+                        .map_err(invalid_data)?
+                        .to_string_lossy()
+                        .into_owned();
+                    if !directory.is_empty() && !path.starts_with('/') {
+                        path = format!("{}/{}", directory, path);
+                    }
+                }
+
+                *lines.entry(path).or_default().entry(line).or_default() |= executed;
+            }
+        }
+
+        Ok(Some(lines))
+    }
+
+    /// Writes the coverage of the source lines as an lcov tracefile,
+    /// with the DWARF line info of `wasm`, the binary of the module.
+    ///
+    /// Fails if the module has no DWARF line info.
+    pub fn write_lcov(&self, wasm: &[u8], out: &mut impl Write) -> io::Result<()> {
+        let lines = self.lines(wasm)?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the module has no DWARF line info",
+            )
+        })?;
+
+        writeln!(out, "TN:")?;
+        for (path, lines) in lines {
+            writeln!(out, "SF:{}", path)?;
+            for (line, executed) in &lines {
+                writeln!(out, "DA:{},{}", line, *executed as u8)?;
+            }
+            writeln!(out, "LF:{}", lines.len())?;
+            writeln!(out, "LH:{}", lines.values().filter(|x| **x).count())?;
+            writeln!(out, "end_of_record")?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the coverage as JSON: the regions of every function, and
+    /// the source lines if `wasm`, the binary of the module, has DWARF
+    /// line info.
+    ///
+    /// ```json
+    /// {
+    ///   "functions": [
+    ///     {"index": 1, "regions": [{"start": 42, "end": 50, "executed": true}]}
+    ///   ],
+    ///   "files": {"src/lib.c": {"3": true, "4": false}}
+    /// }
+    /// ```
+    ///
+    /// `executed` is `null` for the regions that aren't instrumented,
+    /// and `files` is `null` without DWARF line info.
+    pub fn write_json(&self, wasm: &[u8], out: &mut impl Write) -> io::Result<()> {
+        write!(out, "{{\"functions\":[")?;
+        for (i, function) in self.functions.iter().enumerate() {
+            if i > 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "{{\"index\":{},\"regions\":[", function.index.as_u32())?;
+            for (j, region) in function.regions.iter().enumerate() {
+                if j > 0 {
+                    write!(out, ",")?;
+                }
+                let executed = match region.executed {
+                    Some(true) => "true",
+                    Some(false) => "false",
+                    None => "null",
+                };
+                write!(
+                    out,
+                    "{{\"start\":{},\"end\":{},\"executed\":{}}}",
+                    region.start, region.end, executed
+                )?;
+            }
+            write!(out, "]}}")?;
+        }
+        write!(out, "],\"files\":")?;
+
+        match self.lines(wasm)? {
+            Some(lines) => {
+                write!(out, "{{")?;
+                for (i, (path, lines)) in lines.iter().enumerate() {
+                    if i > 0 {
+                        write!(out, ",")?;
+                    }
+                    write_json_string(out, path)?;
+                    write!(out, ":{{")?;
+                    for (j, (line, executed)) in lines.iter().enumerate() {
+                        if j > 0 {
+                            write!(out, ",")?;
+                        }
+                        write!(out, "\"{}\":{}", line, executed)?;
+                    }
+                    write!(out, "}}")?;
+                }
+                write!(out, "}}")?;
+            }
+            None => write!(out, "null")?,
+        }
+
+        writeln!(out, "}}")
+    }
+}
+
+fn write_json_string(out: &mut impl Write, s: &str) -> io::Result<()> {
+    write!(out, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(out, "\\\"")?,
+            '\\' => write!(out, "\\\\")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{}", c)?,
+        }
+    }
+    write!(out, "\"")
+}
+
+fn invalid_data(error: impl fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+/// Reset the coverage of an [`Instance`][wasmer::Instance], e.g. to
+/// measure the coverage of each test separately.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Coverage`] middleware at compile time, otherwise this will
+/// panic.
+pub fn reset_coverage(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    let globals = instance
+        .exports
+        .iter()
+        .filter(|(name, _)| name.starts_with(GLOBAL_PREFIX))
+        .filter_map(|(name, export)| match export {
+            Extern::Global(global) => Some((name.clone(), global.clone())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    for (name, global) in globals {
+        global
+            .set(ctx, Value::I64(0))
+            .unwrap_or_else(|_| panic!("Can't set `{}` in Instance", name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    #[test]
+    fn executed_regions_are_reported() {
+        let bytecode = wat2wasm(
+            br#"
+            (module
+            (func (export "abs") (param i32) (result i32)
+                local.get 0
+                i32.const 0
+                i32.lt_s
+                if (result i32)
+                    i32.const 0
+                    local.get 0
+                    i32.sub
+                else
+                    local.get 0
+                end))
+            "#,
+        )
+        .unwrap();
+
+        let coverage = Arc::new(Coverage::new());
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(coverage.clone());
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, &bytecode).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+
+        let abs: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("abs")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        let executed = |store: &mut Store| {
+            let report = coverage.report(store, &instance);
+            assert_eq!(report.functions.len(), 1);
+            report.functions[0]
+                .regions
+                .iter()
+                .map(|region| region.executed.unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // The entry, and the `then` and `else` branches.
+        assert_eq!(executed(&mut store), vec![false, false, false]);
+        assert_eq!(abs.call(&mut store, 3).unwrap(), 3);
+        assert_eq!(executed(&mut store), vec![true, false, true]);
+        assert_eq!(abs.call(&mut store, -3).unwrap(), 3);
+        assert_eq!(executed(&mut store), vec![true, true, true]);
+
+        reset_coverage(&mut store, &instance);
+        assert_eq!(executed(&mut store), vec![false, false, false]);
+
+        // Without DWARF, there are only the regions.
+        let mut json = vec![];
+        coverage
+            .report(&mut store, &instance)
+            .write_json(&bytecode, &mut json)
+            .unwrap();
+        assert!(String::from_utf8(json)
+            .unwrap()
+            .ends_with("],\"files\":null}\n"));
+        assert_eq!(
+            coverage
+                .report(&mut store, &instance)
+                .write_lcov(&bytecode, &mut vec![])
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,539 @@
+//! `determinism` is a middleware for making the execution of a
+//! WebAssembly instance deterministic, so that every host running it
+//! with the same inputs gets the same results, e.g. for the consensus
+//! of a blockchain.
+//!
+//! The WebAssembly semantics are deterministic, except for:
+//!
+//! - the bits of the NaNs computed by the float operators, which are
+//!   replaced by the canonical NaN;
+//! - the threads and the SIMD proposals, whose operators are rejected
+//!   at compile time;
+//! - `memory.grow`, which may fail depending on the resources of the
+//!   host, and which can be made to always fail, see
+//!   [`Determinism::memory_growth`].
+//!
+//! The host functions imported by the module must also be deterministic.
+//! The clocks and the random number generator of WASI can be replaced by
+//! a virtual clock and a seeded generator, see [`deterministic_imports`];
+//! the other host functions are up to the embedder.
+
+use crate::metering::operator_name;
+use wasmer::wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
+use wasmer::{
+    AsStoreMut, Function, FunctionEnv, FunctionEnvMut, FunctionMiddleware, Imports,
+    LocalFunctionIndex, Memory, MiddlewareError, MiddlewareLocals, MiddlewareReaderState,
+    ModuleMiddleware, WasmPtr,
+};
+
+/// The bits of the canonical NaNs.
+const CANONICAL_NAN_F32: i32 = 0x7fc0_0000;
+const CANONICAL_NAN_F64: i64 = 0x7ff8_0000_0000_0000;
+
+/// The namespace of the WASI functions replaced by
+/// [`deterministic_imports`].
+pub const WASI_NAMESPACE: &str = "wasi_snapshot_preview1";
+
+/// The WASI errors returned by the deterministic imports.
+const ERRNO_SUCCESS: i32 = 0;
+const ERRNO_FAULT: i32 = 21;
+const ERRNO_INVAL: i32 = 28;
+
+/// The number of clocks of WASI: realtime, monotonic, and the CPU time
+/// of the process and of the thread.
+const CLOCK_COUNT: i32 = 4;
+
+/// The prefixes of the names of the operators of the threads and SIMD
+/// proposals.
+const NONDETERMINISTIC_PREFIXES: &[&str] = &[
+    "MemoryAtomic",
+    "AtomicFence",
+    "I32Atomic",
+    "I64Atomic",
+    "V128",
+    "I8x16",
+    "I16x8",
+    "I32x4",
+    "I64x2",
+    "F32x4",
+    "F64x2",
+];
+
+/// The module-level determinism middleware.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::Determinism;
+///
+/// fn create_determinism_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     compiler_config.push_middleware(Arc::new(Determinism::new().memory_growth(false)));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Determinism {
+    /// Whether `memory.grow` is kept.
+    memory_growth: bool,
+}
+
+/// The function-level determinism middleware.
+#[derive(Debug)]
+pub struct FunctionDeterminism {
+    /// Whether `memory.grow` is kept.
+    memory_growth: bool,
+
+    /// The local saving a `f32` result.
+    f32_local: u32,
+
+    /// The local saving a `f64` result.
+    f64_local: u32,
+}
+
+impl Determinism {
+    /// Creates a `Determinism` middleware, which keeps `memory.grow`.
+    pub fn new() -> Self {
+        Self {
+            memory_growth: true,
+        }
+    }
+
+    /// Sets whether `memory.grow` is kept, or made to always fail,
+    /// except when it grows the memory by 0 pages, where it returns the
+    /// size of the memory.
+    pub fn memory_growth(mut self, allowed: bool) -> Self {
+        self.memory_growth = allowed;
+        self
+    }
+}
+
+impl Default for Determinism {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModuleMiddleware for Determinism {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionDeterminism {
+            memory_growth: self.memory_growth,
+            f32_local: 0,
+            f64_local: 0,
+        })
+    }
+}
+
+/// Whether `operator` may compute a NaN whose bits aren't specified,
+/// and the type of its result.
+fn float_result(operator: &Operator) -> Option<WpType> {
+    match operator {
+        Operator::F32Add
+        | Operator::F32Sub
+        | Operator::F32Mul
+        | Operator::F32Div
+        | Operator::F32Sqrt
+        | Operator::F32Min
+        | Operator::F32Max
+        | Operator::F32Ceil
+        | Operator::F32Floor
+        | Operator::F32Trunc
+        | Operator::F32Nearest
+        | Operator::F32DemoteF64 => Some(WpType::F32),
+        Operator::F64Add
+        | Operator::F64Sub
+        | Operator::F64Mul
+        | Operator::F64Div
+        | Operator::F64Sqrt
+        | Operator::F64Min
+        | Operator::F64Max
+        | Operator::F64Ceil
+        | Operator::F64Floor
+        | Operator::F64Trunc
+        | Operator::F64Nearest
+        | Operator::F64PromoteF32 => Some(WpType::F64),
+        _ => None,
+    }
+}
+
+impl FunctionMiddleware for FunctionDeterminism {
+    fn feed_locals(&mut self, locals: &mut MiddlewareLocals) -> Result<(), MiddlewareError> {
+        self.f32_local = locals.add(1, WpType::F32)?;
+        self.f64_local = locals.add(1, WpType::F64)?;
+        Ok(())
+    }
+
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        let name = operator_name(&operator);
+        if NONDETERMINISTIC_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+        {
+            return Err(MiddlewareError::new(
+                "Determinism",
+                format!(
+                    "the operator `{}` at offset {} isn't deterministic",
+                    name,
+                    state.current_operator_offset()
+                ),
+            ));
+        }
+
+        match operator {
+            Operator::MemoryGrow { mem, mem_byte } if !self.memory_growth => {
+                // if delta == 0 { memory.size } else { -1 }
+                state.extend(&[
+                    Operator::I32Eqz,
+                    Operator::If {
+                        ty: WpTypeOrFuncType::Type(WpType::I32),
+                    },
+                    Operator::MemorySize { mem, mem_byte },
+                    Operator::Else,
+                    Operator::I32Const { value: -1 },
+                    Operator::End,
+                ]);
+            }
+            operator => match float_result(&operator) {
+                Some(ty) => {
+                    // The result if it isn't a NaN, or the canonical NaN.
+                    state.push_operator(operator);
+                    let local_index = if ty == WpType::F32 {
+                        self.f32_local
+                    } else {
+                        self.f64_local
+                    };
+                    state.push_operator(Operator::LocalTee { local_index });
+                    if ty == WpType::F32 {
+                        state.extend(&[
+                            Operator::I32Const {
+                                value: CANONICAL_NAN_F32,
+                            },
+                            Operator::F32ReinterpretI32,
+                            Operator::LocalGet { local_index },
+                            Operator::LocalGet { local_index },
+                            Operator::F32Eq,
+                        ]);
+                    } else {
+                        state.extend(&[
+                            Operator::I64Const {
+                                value: CANONICAL_NAN_F64,
+                            },
+                            Operator::F64ReinterpretI64,
+                            Operator::LocalGet { local_index },
+                            Operator::LocalGet { local_index },
+                            Operator::F64Eq,
+                        ]);
+                    }
+                    state.push_operator(Operator::Select);
+                }
+                None => state.push_operator(operator),
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// The state of the virtual clock and of the seeded random number
+/// generator of [`deterministic_imports`].
+///
+/// Every clock of WASI reads the same virtual time, in nanoseconds,
+/// which moves forward by a fixed tick each time it's read. The random
+/// bytes are generated by SplitMix64 from the seed.
+#[derive(Debug, Clone)]
+pub struct DeterministicEnv {
+    /// The memory of the instance, set with
+    /// [`DeterministicEnv::set_memory`] once it's instantiated.
+    memory: Option<Memory>,
+
+    /// The time the clocks read next.
+    time: u64,
+
+    /// The nanoseconds the time moves forward by when it's read.
+    tick: u64,
+
+    /// The state of the random number generator.
+    random_state: u64,
+}
+
+impl DeterministicEnv {
+    /// Creates a `DeterministicEnv` whose time starts at 0 and moves
+    /// forward by 1 millisecond, with a generator seeded with `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            memory: None,
+            time: 0,
+            tick: 1_000_000,
+            random_state: seed,
+        }
+    }
+
+    /// Sets the time the clocks start at, and the nanoseconds they move
+    /// forward by each time they're read, which is at least 1.
+    pub fn with_time(mut self, start: u64, tick: u64) -> Self {
+        self.time = start;
+        self.tick = tick.max(1);
+        self
+    }
+
+    /// Sets the memory the results are written to, which is the memory
+    /// exported by the instance as `memory` for WASI.
+    pub fn set_memory(&mut self, memory: Memory) {
+        self.memory = Some(memory);
+    }
+
+    /// Returns the next 8 random bytes.
+    fn next_random(&mut self) -> u64 {
+        self.random_state = self.random_state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.random_state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Returns the imports replacing `clock_res_get`, `clock_time_get` and
+/// `random_get` in the [`WASI_NAMESPACE`] with the virtual clock and the
+/// seeded random number generator of `env`.
+///
+/// They're meant to override the WASI imports, e.g. with
+/// `imports.extend(&deterministic_imports(&mut store, &env))`, and the
+/// memory of the instance must be given to `env` with
+/// [`DeterministicEnv::set_memory`] before they're called.
+pub fn deterministic_imports(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<DeterministicEnv>,
+) -> Imports {
+    let mut imports = Imports::new();
+    imports.define(
+        WASI_NAMESPACE,
+        "clock_res_get",
+        Function::new_typed_with_env(store, env, clock_res_get),
+    );
+    imports.define(
+        WASI_NAMESPACE,
+        "clock_time_get",
+        Function::new_typed_with_env(store, env, clock_time_get),
+    );
+    imports.define(
+        WASI_NAMESPACE,
+        "random_get",
+        Function::new_typed_with_env(store, env, random_get),
+    );
+    imports
+}
+
+fn clock_res_get(
+    ctx: FunctionEnvMut<DeterministicEnv>,
+    clock_id: i32,
+    resolution: WasmPtr<u64>,
+) -> i32 {
+    if !(0..CLOCK_COUNT).contains(&clock_id) {
+        return ERRNO_INVAL;
+    }
+    let env = ctx.data();
+    let memory = match &env.memory {
+        Some(memory) => memory,
+        None => return ERRNO_FAULT,
+    };
+    match resolution.write(&memory.view(&ctx), env.tick) {
+        Ok(()) => ERRNO_SUCCESS,
+        Err(_) => ERRNO_FAULT,
+    }
+}
+
+fn clock_time_get(
+    mut ctx: FunctionEnvMut<DeterministicEnv>,
+    clock_id: i32,
+    _precision: i64,
+    time: WasmPtr<u64>,
+) -> i32 {
+    if !(0..CLOCK_COUNT).contains(&clock_id) {
+        return ERRNO_INVAL;
+    }
+    let (memory, now) = match &ctx.data().memory {
+        Some(memory) => (memory.clone(), ctx.data().time),
+        None => return ERRNO_FAULT,
+    };
+    match time.write(&memory.view(&ctx), now) {
+        Ok(()) => {
+            let env = ctx.data_mut();
+            env.time = env.time.saturating_add(env.tick);
+            ERRNO_SUCCESS
+        }
+        Err(_) => ERRNO_FAULT,
+    }
+}
+
+fn random_get(mut ctx: FunctionEnvMut<DeterministicEnv>, buf: WasmPtr<u8>, len: u32) -> i32 {
+    let memory = match &ctx.data().memory {
+        Some(memory) => memory.clone(),
+        None => return ERRNO_FAULT,
+    };
+    if u64::from(buf.offset()) + u64::from(len) > memory.view(&ctx).data_size() {
+        return ERRNO_FAULT;
+    }
+    let env = ctx.data_mut();
+    let mut bytes = Vec::with_capacity(len as usize);
+    while bytes.len() < len as usize {
+        bytes.extend_from_slice(&env.next_random().to_le_bytes());
+    }
+    bytes.truncate(len as usize);
+    match buf
+        .slice(&memory.view(&ctx), len)
+        .and_then(|slice| slice.write_slice(&bytes))
+    {
+        Ok(()) => ERRNO_SUCCESS,
+        Err(_) => ERRNO_FAULT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, FunctionEnv, Instance, Module,
+        Store, TypedFunction,
+    };
+
+    fn compile(determinism: Determinism, wat: &[u8]) -> (Store, Result<Module, String>) {
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(determinism));
+        let store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, wat2wasm(wat).unwrap()).map_err(|e| e.to_string());
+        (store, module)
+    }
+
+    #[test]
+    fn nans_are_canonicalized() {
+        let (mut store, module) = compile(
+            Determinism::new(),
+            br#"
+            (module
+            (func (export "add") (param f32 f32) (result i32)
+                local.get 0
+                local.get 1
+                f32.add
+                i32.reinterpret_f32))
+            "#,
+        );
+        let instance = Instance::new(&mut store, &module.unwrap(), &imports! {}).unwrap();
+        let add: TypedFunction<(f32, f32), i32> = instance
+            .exports
+            .get_function("add")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        assert_eq!(
+            add.call(&mut store, 1.5, 2.0).unwrap(),
+            3.5f32.to_bits() as i32
+        );
+        let nan = f32::from_bits(0xffc0_1234);
+        assert_eq!(add.call(&mut store, nan, 2.0).unwrap(), CANONICAL_NAN_F32);
+    }
+
+    #[test]
+    fn memory_growth_fails() {
+        let (mut store, module) = compile(
+            Determinism::new().memory_growth(false),
+            br#"
+            (module
+            (memory 1)
+            (func (export "grow") (param i32) (result i32)
+                local.get 0
+                memory.grow))
+            "#,
+        );
+        let instance = Instance::new(&mut store, &module.unwrap(), &imports! {}).unwrap();
+        let grow: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("grow")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        assert_eq!(grow.call(&mut store, 1).unwrap(), -1);
+        assert_eq!(grow.call(&mut store, 0).unwrap(), 1);
+    }
+
+    #[test]
+    fn clocks_and_random_bytes_are_deterministic() {
+        let run = |seed: u64| {
+            let (mut store, module) = compile(
+                Determinism::new(),
+                br#"
+                (module
+                (import "wasi_snapshot_preview1" "clock_time_get"
+                    (func $clock_time_get (param i32 i64 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "random_get"
+                    (func $random_get (param i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "time") (param i32) (result i64)
+                    (drop (call $clock_time_get (local.get 0) (i64.const 0) (i32.const 0)))
+                    (i64.load (i32.const 0)))
+                (func (export "random") (result i64)
+                    (drop (call $random_get (i32.const 8) (i32.const 8)))
+                    (i64.load (i32.const 8)))
+                (func (export "random_out_of_bounds") (result i32)
+                    (call $random_get (i32.const 65532) (i32.const 8))))
+                "#,
+            );
+            let env = FunctionEnv::new(&mut store, DeterministicEnv::new(seed).with_time(10, 5));
+            let imports = deterministic_imports(&mut store, &env);
+            let instance = Instance::new(&mut store, &module.unwrap(), &imports).unwrap();
+            let memory = instance.exports.get_memory("memory").unwrap().clone();
+            env.as_mut(&mut store).set_memory(memory);
+
+            let time: TypedFunction<i32, i64> =
+                instance.exports.get_typed_function(&store, "time").unwrap();
+            let random: TypedFunction<(), i64> = instance
+                .exports
+                .get_typed_function(&store, "random")
+                .unwrap();
+            let random_out_of_bounds: TypedFunction<(), i32> = instance
+                .exports
+                .get_typed_function(&store, "random_out_of_bounds")
+                .unwrap();
+            assert_eq!(random_out_of_bounds.call(&mut store).unwrap(), ERRNO_FAULT);
+            (
+                [
+                    time.call(&mut store, 0).unwrap(),
+                    time.call(&mut store, 1).unwrap(),
+                ],
+                [
+                    random.call(&mut store).unwrap(),
+                    random.call(&mut store).unwrap(),
+                ],
+            )
+        };
+
+        let (times, random) = run(1);
+        assert_eq!(times, [10, 15]);
+        assert_ne!(random[0], random[1]);
+        assert_eq!(run(1), (times, random));
+        assert_ne!(run(2).1, random);
+    }
+
+    #[test]
+    fn simd_is_rejected() {
+        let (_, module) = compile(
+            Determinism::new(),
+            br#"
+            (module
+            (func (param v128) (result v128)
+                local.get 0
+                local.get 0
+                f32x4.add))
+            "#,
+        );
+        assert!(module.unwrap_err().contains("F32x4Add"));
+    }
+}
@@ -0,0 +1,725 @@
+//! `heap_profiling` is a middleware for profiling the allocations
+//! made by a WebAssembly instance in its linear memory, e.g. to find
+//! its leaks.
+//!
+//! The middleware recognizes the allocator functions of the module by
+//! their names, from the name section or the exports: the C ones
+//! (`malloc`, `calloc`, `aligned_alloc`, `realloc` and `free`), their
+//! `dl` prefixed dlmalloc versions, and the Rust ones (`__rust_alloc`,
+//! `__rust_alloc_zeroed`, `__rust_realloc` and `__rust_dealloc`).
+//! Their calls are reported to the host, which keeps the live
+//! allocations and the usage of the heap, once enabled with
+//! [`enable_heap_profiling`]. The allocator functions calling each
+//! other, like `__rust_alloc` calling `malloc`, are reported once.
+//!
+//! Only the modules with a 32-bit memory are supported.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use wasmer::wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
+use wasmer::{
+    AsStoreMut, ExportIndex, Function, FunctionIndex, FunctionMiddleware, FunctionType,
+    GlobalIndex, GlobalInit, GlobalType, Instance, LocalFunctionIndex, MiddlewareError,
+    MiddlewareLocals, MiddlewareReaderState, ModuleInfo, ModuleMiddleware, Mutability,
+    SignatureIndex, TableIndex, TableType, Type, Value,
+};
+
+/// The kind of a call, as passed to the hook.
+const ALLOC: i32 = 0;
+const FREE: i32 = 1;
+const REALLOC: i32 = 2;
+
+/// What an allocator function does, with the indexes of its
+/// parameters.
+#[derive(Debug, Clone, Copy)]
+enum AllocatorFunction {
+    /// Returns a block whose size is the product of the parameters.
+    Alloc { sizes: &'static [u32] },
+    /// Frees the block of the first parameter.
+    Free,
+    /// Resizes the block of the first parameter to the size of the
+    /// parameter `size`, and returns it.
+    Realloc { size: u32 },
+}
+
+/// The allocator functions, with their number of parameters.
+const ALLOCATOR_FUNCTIONS: &[(&str, usize, AllocatorFunction)] = &[
+    ("malloc", 1, AllocatorFunction::Alloc { sizes: &[0] }),
+    ("calloc", 2, AllocatorFunction::Alloc { sizes: &[0, 1] }),
+    ("aligned_alloc", 2, AllocatorFunction::Alloc { sizes: &[1] }),
+    ("realloc", 2, AllocatorFunction::Realloc { size: 1 }),
+    ("free", 1, AllocatorFunction::Free),
+    ("dlmalloc", 1, AllocatorFunction::Alloc { sizes: &[0] }),
+    ("dlcalloc", 2, AllocatorFunction::Alloc { sizes: &[0, 1] }),
+    ("dlrealloc", 2, AllocatorFunction::Realloc { size: 1 }),
+    ("dlfree", 1, AllocatorFunction::Free),
+    ("__rust_alloc", 2, AllocatorFunction::Alloc { sizes: &[0] }),
+    (
+        "__rust_alloc_zeroed",
+        2,
+        AllocatorFunction::Alloc { sizes: &[0] },
+    ),
+    ("__rust_realloc", 4, AllocatorFunction::Realloc { size: 3 }),
+    ("__rust_dealloc", 3, AllocatorFunction::Free),
+];
+
+#[derive(Debug, Clone)]
+struct HeapProfilingIndexes {
+    /// The global indicating whether the hook is set.
+    enabled: GlobalIndex,
+
+    /// The table holding the hook.
+    table: TableIndex,
+
+    /// The signature of the hook.
+    signature: SignatureIndex,
+
+    /// The allocator functions of the module.
+    functions: HashMap<LocalFunctionIndex, AllocatorFunction>,
+}
+
+/// The module-level heap profiling middleware.
+///
+/// # Panic
+///
+/// An instance of `HeapProfiling` should _not_ be shared among
+/// different modules, since it tracks module-specific information like
+/// the allocator functions. Attempts to use a `HeapProfiling` instance
+/// from multiple modules will result in a panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::HeapProfiling;
+///
+/// fn create_heap_profiling_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     compiler_config.push_middleware(Arc::new(HeapProfiling::new()));
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct HeapProfiling {
+    /// The indexes of the state added to the module.
+    indexes: Mutex<Option<HeapProfilingIndexes>>,
+}
+
+/// The function-level heap profiling middleware.
+pub struct FunctionHeapProfiling {
+    /// The indexes of the state added to the module.
+    indexes: HeapProfilingIndexes,
+
+    /// What the function does, if it's an allocator function.
+    function: Option<AllocatorFunction>,
+
+    /// The first of the locals saving the returned block, the size
+    /// and the block to resize.
+    locals: u32,
+
+    /// Whether the entry of the function has been instrumented.
+    entered: bool,
+
+    /// The depth of the block being fed.
+    depth: u32,
+}
+
+impl HeapProfiling {
+    /// Creates a `HeapProfiling` middleware.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Finds the allocator functions of a module, by name.
+fn allocator_functions(module_info: &ModuleInfo) -> HashMap<LocalFunctionIndex, AllocatorFunction> {
+    let exported = module_info
+        .exports
+        .iter()
+        .filter_map(|(name, index)| match index {
+            ExportIndex::Function(index) => Some((*index, name)),
+            _ => None,
+        });
+    let named = module_info
+        .function_names
+        .iter()
+        .map(|(index, name)| (*index, name));
+
+    let mut functions = HashMap::new();
+    for (index, name) in named.chain(exported) {
+        let local_index = match module_info.local_func_index(index) {
+            Some(local_index) => local_index,
+            None => continue,
+        };
+        let function = ALLOCATOR_FUNCTIONS
+            .iter()
+            .find(|(allocator_name, _, _)| *allocator_name == name.as_str());
+        let (num_params, function) = match function {
+            Some((_, num_params, function)) => (*num_params, *function),
+            None => continue,
+        };
+
+        // The functions with another signature aren't the expected
+        // ones.
+        let signature = &module_info.signatures[module_info.functions[index]];
+        let results: &[Type] = match function {
+            AllocatorFunction::Free => &[],
+            _ => &[Type::I32],
+        };
+        if signature.params() == vec![Type::I32; num_params].as_slice()
+            && signature.results() == results
+        {
+            functions.insert(local_index, function);
+        }
+    }
+    functions
+}
+
+impl ModuleMiddleware for HeapProfiling {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        let indexes = self.indexes.lock().unwrap().clone().unwrap();
+        Box::new(FunctionHeapProfiling {
+            function: indexes.functions.get(&local_function_index).copied(),
+            indexes,
+            locals: 0,
+            entered: false,
+            depth: 0,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut indexes = self.indexes.lock().unwrap();
+
+        if indexes.is_some() {
+            panic!("HeapProfiling::transform_module_info: Attempting to use a `HeapProfiling` middleware from multiple modules.");
+        }
+
+        let functions = allocator_functions(module_info);
+
+        // Append a global for the enabled boolean and initialize it.
+        let enabled = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        module_info.exports.insert(
+            "wasmer_heap_profiling_enabled".to_string(),
+            ExportIndex::Global(enabled),
+        );
+
+        // Append a table for the hook, called with the kind of the
+        // call, the returned or freed block, the size and the resized
+        // block.
+        let signature = module_info
+            .signatures
+            .push(FunctionType::new(vec![Type::I32; 4], vec![]));
+
+        let table = module_info
+            .tables
+            .push(TableType::new(Type::FuncRef, 1, Some(1)));
+
+        module_info.exports.insert(
+            "wasmer_heap_profiling_hook".to_string(),
+            ExportIndex::Table(table),
+        );
+
+        *indexes = Some(HeapProfilingIndexes {
+            enabled,
+            table,
+            signature,
+            functions,
+        });
+    }
+}
+
+impl fmt::Debug for FunctionHeapProfiling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionHeapProfiling")
+            .field("function", &self.function)
+            .finish()
+    }
+}
+
+impl FunctionHeapProfiling {
+    fn block_local(&self) -> u32 {
+        self.locals
+    }
+
+    fn size_local(&self) -> u32 {
+        self.locals + 1
+    }
+
+    fn resized_local(&self) -> u32 {
+        self.locals + 2
+    }
+
+    /// Calls the hook with `kind` and the arguments pushed by
+    /// `arguments`, if it's set.
+    fn report<'a>(
+        &self,
+        state: &mut MiddlewareReaderState<'a>,
+        kind: i32,
+        arguments: &[Operator<'a>],
+    ) {
+        state.extend(&[
+            // if globals[enabled] {
+            //     hook(kind, block, size, resized);
+            // }
+            Operator::GlobalGet {
+                global_index: self.indexes.enabled.as_u32(),
+            },
+            Operator::If {
+                ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+            },
+            Operator::I32Const { value: kind },
+        ]);
+        state.extend(arguments.iter().cloned());
+        state.extend(&[
+            Operator::I32Const { value: 0 },
+            Operator::CallIndirect {
+                index: self.indexes.signature.as_u32(),
+                table_index: self.indexes.table.as_u32(),
+            },
+            Operator::End,
+        ]);
+    }
+
+    /// Saves the arguments of an allocator function, or reports a free.
+    fn enter(&self, state: &mut MiddlewareReaderState<'_>) {
+        match self.function {
+            Some(AllocatorFunction::Alloc { sizes }) => {
+                // size = params[sizes[0]] * params[sizes[1]] * ...;
+                for (i, size) in sizes.iter().enumerate() {
+                    state.push_operator(Operator::LocalGet { local_index: *size });
+                    if i > 0 {
+                        state.push_operator(Operator::I32Mul);
+                    }
+                }
+                state.push_operator(Operator::LocalSet {
+                    local_index: self.size_local(),
+                });
+            }
+            Some(AllocatorFunction::Realloc { size }) => state.extend(&[
+                // resized = params[0];
+                // size = params[size];
+                Operator::LocalGet { local_index: 0 },
+                Operator::LocalSet {
+                    local_index: self.resized_local(),
+                },
+                Operator::LocalGet { local_index: size },
+                Operator::LocalSet {
+                    local_index: self.size_local(),
+                },
+            ]),
+            Some(AllocatorFunction::Free) => self.report(
+                state,
+                FREE,
+                &[
+                    Operator::LocalGet { local_index: 0 },
+                    Operator::I32Const { value: 0 },
+                    Operator::I32Const { value: 0 },
+                ],
+            ),
+            None => {}
+        }
+    }
+
+    /// Reports the block returned by an allocator function, on the top
+    /// of the stack.
+    fn leave(&self, state: &mut MiddlewareReaderState<'_>) {
+        let (kind, resized) = match self.function {
+            Some(AllocatorFunction::Alloc { .. }) => (ALLOC, Operator::I32Const { value: 0 }),
+            Some(AllocatorFunction::Realloc { .. }) => (
+                REALLOC,
+                Operator::LocalGet {
+                    local_index: self.resized_local(),
+                },
+            ),
+            _ => return,
+        };
+        state.push_operator(Operator::LocalTee {
+            local_index: self.block_local(),
+        });
+        self.report(
+            state,
+            kind,
+            &[
+                Operator::LocalGet {
+                    local_index: self.block_local(),
+                },
+                Operator::LocalGet {
+                    local_index: self.size_local(),
+                },
+                resized,
+            ],
+        );
+    }
+}
+
+impl FunctionMiddleware for FunctionHeapProfiling {
+    fn feed_locals(&mut self, locals: &mut MiddlewareLocals) -> Result<(), MiddlewareError> {
+        if self.function.is_some() {
+            self.locals = locals.add(3, WpType::I32)?;
+        }
+        Ok(())
+    }
+
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if self.function.is_none() {
+            state.push_operator(operator);
+            return Ok(());
+        }
+
+        if !self.entered {
+            self.entered = true;
+            self.enter(state);
+        }
+
+        match operator {
+            Operator::Block { .. }
+            | Operator::Loop { .. }
+            | Operator::If { .. }
+            | Operator::Try { .. } => {
+                self.depth += 1;
+            }
+            Operator::Return => {
+                self.leave(state);
+            }
+            // The end of the function.
+            Operator::End if self.depth == 0 => {
+                self.leave(state);
+            }
+            Operator::End | Operator::Delegate { .. } => {
+                self.depth -= 1;
+            }
+            _ => {}
+        }
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// A block allocated by an [`Instance`][wasmer::Instance] in its
+/// memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Allocation {
+    /// The address of the block.
+    pub address: u32,
+    /// The size of the block, as requested.
+    pub size: u32,
+}
+
+/// The profile of the heap of an [`Instance`][wasmer::Instance], see
+/// [`HeapProfiler::profile`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeapProfile {
+    /// The number of blocks allocated.
+    pub allocations: u64,
+
+    /// The number of blocks resized.
+    pub reallocations: u64,
+
+    /// The number of blocks freed.
+    pub frees: u64,
+
+    /// The bytes of the live blocks.
+    pub live_bytes: u64,
+
+    /// The largest `live_bytes` reached.
+    pub peak_bytes: u64,
+
+    /// The live blocks, the largest first. Once the instance is done,
+    /// they are the candidates for leaks.
+    pub live_allocations: Vec<Allocation>,
+}
+
+#[derive(Debug, Default)]
+struct HeapState {
+    live: BTreeMap<u32, u32>,
+    profile: HeapProfile,
+}
+
+impl HeapState {
+    /// Records a live block, returning whether it's a new one.
+    fn insert(&mut self, address: u32, size: u32) -> bool {
+        let previous_size = self.live.insert(address, size);
+        if let Some(previous_size) = previous_size {
+            self.profile.live_bytes -= previous_size as u64;
+        }
+        self.profile.live_bytes += size as u64;
+        self.profile.peak_bytes = self.profile.peak_bytes.max(self.profile.live_bytes);
+        previous_size.is_none()
+    }
+
+    /// Forgets a block, returning whether it was live.
+    fn forget(&mut self, address: u32) -> bool {
+        match self.live.remove(&address) {
+            Some(size) => {
+                self.profile.live_bytes -= size as u64;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn report(&mut self, kind: i32, block: u32, size: u32, resized: u32) {
+        match kind {
+            // A null block is a failed allocation. The blocks reported
+            // again by an allocator function calling another one aren't
+            // counted twice.
+            ALLOC if block != 0 => {
+                if self.insert(block, size) {
+                    self.profile.allocations += 1;
+                }
+            }
+            FREE => {
+                if self.forget(block) {
+                    self.profile.frees += 1;
+                }
+            }
+            // Resizing to 0 bytes frees the block.
+            REALLOC if block == 0 && size == 0 => {
+                if self.forget(resized) {
+                    self.profile.frees += 1;
+                }
+            }
+            // A failed reallocation leaves the block as is.
+            REALLOC if block != 0 => {
+                let resized_live = self.forget(resized);
+                if self.insert(block, size) {
+                    if resized_live {
+                        self.profile.reallocations += 1;
+                    } else {
+                        self.profile.allocations += 1;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The profile of the heap of an [`Instance`][wasmer::Instance], kept
+/// up to date while it runs, see [`enable_heap_profiling`].
+#[derive(Debug, Clone)]
+pub struct HeapProfiler {
+    state: Arc<Mutex<HeapState>>,
+}
+
+impl HeapProfiler {
+    /// Get the profile of the heap so far.
+    pub fn profile(&self) -> HeapProfile {
+        let state = self.state.lock().unwrap();
+        let mut live_allocations: Vec<Allocation> = state
+            .live
+            .iter()
+            .map(|(address, size)| Allocation {
+                address: *address,
+                size: *size,
+            })
+            .collect();
+        live_allocations.sort_by(|a, b| b.size.cmp(&a.size).then(a.address.cmp(&b.address)));
+        HeapProfile {
+            live_allocations,
+            ..state.profile.clone()
+        }
+    }
+}
+
+/// Start profiling the heap of an [`Instance`][wasmer::Instance].
+///
+/// The blocks allocated before aren't known, so freeing them isn't
+/// counted.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`HeapProfiling`] middleware at compile time, otherwise this
+/// will panic.
+///
+/// # Example
+///
+/// ```rust
+/// use wasmer::{AsStoreMut, Instance, TypedFunction};
+/// use wasmer_middlewares::heap_profiling::enable_heap_profiling;
+///
+/// fn report_leaks(store: &mut impl AsStoreMut, instance: &Instance, run: TypedFunction<(), ()>) {
+///     let profiler = enable_heap_profiling(store, instance);
+///     run.call(store).unwrap();
+///
+///     let profile = profiler.profile();
+///     println!("peak usage: {} bytes", profile.peak_bytes);
+///     for allocation in profile.live_allocations {
+///         println!("{} bytes leaked at {:#x}", allocation.size, allocation.address);
+///     }
+/// }
+/// ```
+pub fn enable_heap_profiling(ctx: &mut impl AsStoreMut, instance: &Instance) -> HeapProfiler {
+    let state = Arc::new(Mutex::new(HeapState::default()));
+    let hook_state = state.clone();
+    let function = Function::new_typed(
+        ctx,
+        move |kind: i32, block: i32, size: i32, resized: i32| {
+            hook_state
+                .lock()
+                .unwrap()
+                .report(kind, block as u32, size as u32, resized as u32)
+        },
+    );
+
+    instance
+        .exports
+        .get_table("wasmer_heap_profiling_hook")
+        .expect("Can't get `wasmer_heap_profiling_hook` from Instance")
+        .set(ctx, 0, Value::FuncRef(Some(function)))
+        .expect("Can't set `wasmer_heap_profiling_hook` in Instance");
+
+    set_enabled(ctx, instance, true);
+    HeapProfiler { state }
+}
+
+/// Stop profiling the heap of an [`Instance`][wasmer::Instance]. The
+/// profile of the [`HeapProfiler`] stays the one at this point.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`HeapProfiling`] middleware at compile time, otherwise this
+/// will panic.
+pub fn disable_heap_profiling(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    set_enabled(ctx, instance, false);
+
+    instance
+        .exports
+        .get_table("wasmer_heap_profiling_hook")
+        .expect("Can't get `wasmer_heap_profiling_hook` from Instance")
+        .set(ctx, 0, Value::FuncRef(None))
+        .expect("Can't set `wasmer_heap_profiling_hook` in Instance");
+}
+
+/// Get the allocator functions of an [`Instance`][wasmer::Instance]
+/// found by the [`HeapProfiling`] middleware, e.g. to check that its
+/// heap can be profiled.
+pub fn get_allocator_functions(instance: &Instance) -> Vec<FunctionIndex> {
+    let info = instance.module().info();
+    let mut functions: Vec<FunctionIndex> = allocator_functions(info)
+        .into_keys()
+        .map(|local_index| info.func_index(local_index))
+        .collect();
+    functions.sort();
+    functions
+}
+
+fn set_enabled(ctx: &mut impl AsStoreMut, instance: &Instance, enabled: bool) {
+    instance
+        .exports
+        .get_global("wasmer_heap_profiling_enabled")
+        .expect("Can't get `wasmer_heap_profiling_enabled` from Instance")
+        .set(ctx, Value::I32(enabled as i32))
+        .expect("Can't set `wasmer_heap_profiling_enabled` in Instance");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    #[test]
+    fn allocations_are_profiled() {
+        // A bump allocator, whose `free` does nothing.
+        let bytecode = wat2wasm(
+            br#"
+            (module
+            (memory 1)
+            (global $next (mut i32) (i32.const 16))
+            (func $malloc (param $size i32) (result i32)
+                global.get $next
+                global.get $next
+                local.get $size
+                i32.add
+                global.set $next)
+            (func $calloc (param $count i32) (param $size i32) (result i32)
+                local.get $count
+                local.get $size
+                i32.mul
+                call $malloc)
+            (func $free (param $block i32))
+            (func $realloc (param $block i32) (param $size i32) (result i32)
+                (if (i32.eqz (local.get $block))
+                    (then (return (call $malloc (local.get $size)))))
+                (call $free (local.get $block))
+                (call $malloc (local.get $size)))
+            (func (export "run")
+                (drop (call $malloc (i32.const 100)))
+                (call $free (call $calloc (i32.const 4) (i32.const 8)))
+                (drop (call $realloc (call $malloc (i32.const 10)) (i32.const 20)))))
+            "#,
+        )
+        .unwrap();
+
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(HeapProfiling::new()));
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode).unwrap();
+
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        assert_eq!(
+            get_allocator_functions(&instance),
+            (0..4).map(FunctionIndex::from_u32).collect::<Vec<_>>()
+        );
+
+        let run: TypedFunction<(), ()> = instance
+            .exports
+            .get_function("run")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        // The calls before the profiling starts aren't profiled.
+        run.call(&mut store).unwrap();
+        let profiler = enable_heap_profiling(&mut store, &instance);
+        run.call(&mut store).unwrap();
+        disable_heap_profiling(&mut store, &instance);
+        run.call(&mut store).unwrap();
+
+        // The blocks of the second run start after the 162 bytes of
+        // the first one. `realloc` reports its calls of `free` and
+        // `malloc`, and `calloc` its call of `malloc`.
+        assert_eq!(
+            profiler.profile(),
+            HeapProfile {
+                allocations: 4,
+                reallocations: 0,
+                frees: 2,
+                live_bytes: 120,
+                peak_bytes: 132,
+                live_allocations: vec![
+                    Allocation {
+                        address: 178,
+                        size: 100
+                    },
+                    Allocation {
+                        address: 320,
+                        size: 20
+                    },
+                ],
+            }
+        );
+    }
+}
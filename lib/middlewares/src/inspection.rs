@@ -0,0 +1,695 @@
+//! `inspection` is a middleware for finding out what a running
+//! WebAssembly instance is doing, e.g. to report where a stuck guest
+//! spins.
+//!
+//! The middleware keeps the running function, the offset of the last
+//! safepoint it reached and the depth of the Wasm calls in exported
+//! globals of the instance, named `wasmer_inspection_*`. The
+//! safepoints are the entries of the functions, the iterations of the
+//! loops and the calls, so the offset is the one of the innermost loop
+//! or call the function is in.
+//!
+//! The state is read with [`get_instance_state`] when the instance is
+//! paused, e.g. from a host function, or from another thread while it
+//! runs with an [`InspectionHandle`].
+
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::sync::atomic::{AtomicI32, AtomicI64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use wasmer::wasmparser::{Operator, Type as WpType};
+use wasmer::{
+    AsStoreMut, AsStoreRef, ExportIndex, FunctionIndex, FunctionMiddleware, GlobalIndex,
+    GlobalInit, GlobalType, Instance, LocalFunctionIndex, MiddlewareError, MiddlewareLocals,
+    MiddlewareReaderState, ModuleInfo, ModuleMiddleware, Mutability, Pages, Type, Value,
+    WASM_PAGE_SIZE,
+};
+use wasmer_types::MemoryIndex;
+
+/// The running function when the host runs.
+const HOST: i32 = -1;
+
+/// The offset before the first safepoint is reached.
+const NO_OFFSET: i64 = -1;
+
+#[derive(Debug, Clone)]
+struct InspectionGlobalIndexes {
+    /// The global holding the index of the running function, or
+    /// `HOST`.
+    function: GlobalIndex,
+
+    /// The global holding the offset of the last safepoint.
+    offset: GlobalIndex,
+
+    /// The global holding the number of Wasm calls on the stack.
+    depth: GlobalIndex,
+
+    /// The number of imported functions of the module.
+    num_imported_functions: usize,
+}
+
+/// The module-level inspection middleware.
+///
+/// # Panic
+///
+/// An instance of `Inspection` should _not_ be shared among different
+/// modules, since it tracks module-specific information like the
+/// global indexes of the state. Attempts to use an `Inspection`
+/// instance from multiple modules will result in a panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::Inspection;
+///
+/// fn create_inspection_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     compiler_config.push_middleware(Arc::new(Inspection::new()));
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Inspection {
+    /// The global indexes of the state.
+    global_indexes: Mutex<Option<InspectionGlobalIndexes>>,
+}
+
+/// The function-level inspection middleware.
+pub struct FunctionInspection {
+    /// The index of the function in the module.
+    function_index: u32,
+
+    /// The global indexes of the state.
+    global_indexes: InspectionGlobalIndexes,
+
+    /// The first of the locals saving the function and the offset of
+    /// the caller.
+    caller_locals: u32,
+
+    /// Whether the entry of the function has been instrumented.
+    entered: bool,
+
+    /// The depth of the block being fed.
+    depth: u32,
+}
+
+impl Inspection {
+    /// Creates an `Inspection` middleware.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ModuleMiddleware for Inspection {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        let global_indexes = self.global_indexes.lock().unwrap().clone().unwrap();
+        Box::new(FunctionInspection {
+            function_index: (global_indexes.num_imported_functions as u32)
+                + local_function_index.as_u32(),
+            global_indexes,
+            caller_locals: 0,
+            entered: false,
+            depth: 0,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut global_indexes = self.global_indexes.lock().unwrap();
+
+        if global_indexes.is_some() {
+            panic!("Inspection::transform_module_info: Attempting to use an `Inspection` middleware from multiple modules.");
+        }
+
+        let function = push_global(
+            module_info,
+            Type::I32,
+            GlobalInit::I32Const(HOST),
+            "wasmer_inspection_function",
+        );
+        let offset = push_global(
+            module_info,
+            Type::I64,
+            GlobalInit::I64Const(NO_OFFSET),
+            "wasmer_inspection_offset",
+        );
+        let depth = push_global(
+            module_info,
+            Type::I32,
+            GlobalInit::I32Const(0),
+            "wasmer_inspection_depth",
+        );
+
+        // The memory may not be exported by the module itself.
+        if !module_info.memories.is_empty() {
+            module_info.exports.insert(
+                "wasmer_inspection_memory".to_string(),
+                ExportIndex::Memory(MemoryIndex::from_u32(0)),
+            );
+        }
+
+        *global_indexes = Some(InspectionGlobalIndexes {
+            function,
+            offset,
+            depth,
+            num_imported_functions: module_info.num_imported_functions,
+        });
+    }
+}
+
+/// Appends an exported global and initializes it.
+fn push_global(
+    module_info: &mut ModuleInfo,
+    ty: Type,
+    init: GlobalInit,
+    name: &str,
+) -> GlobalIndex {
+    let index = module_info
+        .globals
+        .push(GlobalType::new(ty, Mutability::Var));
+    module_info.global_initializers.push(init);
+    module_info
+        .exports
+        .insert(name.to_string(), ExportIndex::Global(index));
+    index
+}
+
+impl fmt::Debug for FunctionInspection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionInspection")
+            .field("function_index", &self.function_index)
+            .field("global_indexes", &self.global_indexes)
+            .finish()
+    }
+}
+
+impl FunctionInspection {
+    /// Saves the state of the caller and makes the function the
+    /// running one.
+    fn enter(&self, state: &mut MiddlewareReaderState<'_>) {
+        let function = self.global_indexes.function.as_u32();
+        let depth = self.global_indexes.depth.as_u32();
+        state.extend(&[
+            // caller_function = globals[function];
+            // caller_offset = globals[offset];
+            Operator::GlobalGet {
+                global_index: function,
+            },
+            Operator::LocalSet {
+                local_index: self.caller_locals,
+            },
+            Operator::GlobalGet {
+                global_index: self.global_indexes.offset.as_u32(),
+            },
+            Operator::LocalSet {
+                local_index: self.caller_locals + 1,
+            },
+            // globals[function] = function_index;
+            Operator::I32Const {
+                value: self.function_index as i32,
+            },
+            Operator::GlobalSet {
+                global_index: function,
+            },
+            // globals[depth] += 1;
+            Operator::GlobalGet {
+                global_index: depth,
+            },
+            Operator::I32Const { value: 1 },
+            Operator::I32Add,
+            Operator::GlobalSet {
+                global_index: depth,
+            },
+        ]);
+    }
+
+    /// Gives the running function back to the caller.
+    fn leave(&self, state: &mut MiddlewareReaderState<'_>) {
+        let depth = self.global_indexes.depth.as_u32();
+        state.extend(&[
+            // globals[function] = caller_function;
+            // globals[offset] = caller_offset;
+            Operator::LocalGet {
+                local_index: self.caller_locals,
+            },
+            Operator::GlobalSet {
+                global_index: self.global_indexes.function.as_u32(),
+            },
+            Operator::LocalGet {
+                local_index: self.caller_locals + 1,
+            },
+            Operator::GlobalSet {
+                global_index: self.global_indexes.offset.as_u32(),
+            },
+            // globals[depth] -= 1;
+            Operator::GlobalGet {
+                global_index: depth,
+            },
+            Operator::I32Const { value: 1 },
+            Operator::I32Sub,
+            Operator::GlobalSet {
+                global_index: depth,
+            },
+        ]);
+    }
+
+    /// Records that the function reached the operator at `offset`.
+    fn safepoint(&self, state: &mut MiddlewareReaderState<'_>, offset: usize) {
+        state.extend(&[
+            // globals[offset] = offset;
+            Operator::I64Const {
+                value: offset as i64,
+            },
+            Operator::GlobalSet {
+                global_index: self.global_indexes.offset.as_u32(),
+            },
+        ]);
+    }
+}
+
+impl FunctionMiddleware for FunctionInspection {
+    fn feed_locals(&mut self, locals: &mut MiddlewareLocals) -> Result<(), MiddlewareError> {
+        self.caller_locals = locals.add(1, WpType::I32)?;
+        locals.add(1, WpType::I64)?;
+        Ok(())
+    }
+
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        let offset = state.current_operator_offset();
+        if !self.entered {
+            self.entered = true;
+            self.enter(state);
+            self.safepoint(state, offset);
+        }
+
+        match operator {
+            Operator::Block { .. } | Operator::If { .. } | Operator::Try { .. } => {
+                self.depth += 1;
+            }
+            // Check at the start of the body of the loop, so that every
+            // iteration is recorded.
+            Operator::Loop { .. } => {
+                self.depth += 1;
+                state.push_operator(operator);
+                self.safepoint(state, offset);
+                return Ok(());
+            }
+            Operator::Call { .. } | Operator::CallIndirect { .. } => {
+                self.safepoint(state, offset);
+            }
+            Operator::Return => {
+                self.leave(state);
+            }
+            // The end of the function.
+            Operator::End if self.depth == 0 => {
+                self.leave(state);
+            }
+            Operator::End | Operator::Delegate { .. } => {
+                self.depth -= 1;
+            }
+            _ => {}
+        }
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// What an [`Instance`][wasmer::Instance] is doing, see
+/// [`get_instance_state`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstanceState {
+    /// The running Wasm function, `None` when no function of the
+    /// instance is called. While a host function runs, it's the Wasm
+    /// function which called it.
+    pub function: Option<FunctionIndex>,
+
+    /// The offset in the Wasm binary of the last safepoint reached by
+    /// the running function, which approximates the program counter.
+    pub offset: Option<usize>,
+
+    /// The number of calls of Wasm functions on the stack.
+    pub depth: u32,
+
+    /// The size of the first memory of the instance.
+    pub memory_size: Option<Pages>,
+}
+
+impl InstanceState {
+    fn from_globals(function: i32, offset: i64, depth: i32, memory_size: Option<usize>) -> Self {
+        Self {
+            function: if function == HOST {
+                None
+            } else {
+                Some(FunctionIndex::from_u32(function as u32))
+            },
+            offset: if offset == NO_OFFSET {
+                None
+            } else {
+                Some(offset as usize)
+            },
+            depth: depth as u32,
+            memory_size: memory_size.map(|size| Pages((size / WASM_PAGE_SIZE) as u32)),
+        }
+    }
+}
+
+fn get_global<T: TryFrom<Value>>(ctx: &mut impl AsStoreMut, instance: &Instance, name: &str) -> T {
+    instance
+        .exports
+        .get_global(name)
+        .unwrap_or_else(|_| panic!("Can't get `{}` from Instance", name))
+        .get(ctx)
+        .try_into()
+        .unwrap_or_else(|_| panic!("`{}` from Instance has wrong type", name))
+}
+
+/// Get the state of an [`Instance`][wasmer::Instance] while it's
+/// paused, e.g. from a host function it called.
+///
+/// A trap leaves the state of the trapping function until
+/// [`reset_instance_state`] is called.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Inspection`] middleware at compile time, otherwise this will
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use wasmer::{AsStoreMut, Instance};
+/// use wasmer_middlewares::inspection::get_instance_state;
+///
+/// fn report_state(store: &mut impl AsStoreMut, instance: &Instance) {
+///     let state = get_instance_state(store, instance);
+///     if let (Some(function), Some(offset)) = (state.function, state.offset) {
+///         println!("in function {:?}, near 0x{:x}", function, offset);
+///     }
+/// }
+/// ```
+pub fn get_instance_state(ctx: &mut impl AsStoreMut, instance: &Instance) -> InstanceState {
+    let memory_size = instance
+        .exports
+        .get_memory("wasmer_inspection_memory")
+        .ok()
+        .map(|memory| memory.view(ctx).data_size() as usize);
+    InstanceState::from_globals(
+        get_global(ctx, instance, "wasmer_inspection_function"),
+        get_global(ctx, instance, "wasmer_inspection_offset"),
+        get_global(ctx, instance, "wasmer_inspection_depth"),
+        memory_size,
+    )
+}
+
+/// Reset the state of an [`Instance`][wasmer::Instance] after a trap,
+/// which leaves the state of the trapping function.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Inspection`] middleware at compile time, otherwise this will
+/// panic.
+pub fn reset_instance_state(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    for (name, value) in [
+        ("wasmer_inspection_function", Value::I32(HOST)),
+        ("wasmer_inspection_offset", Value::I64(NO_OFFSET)),
+        ("wasmer_inspection_depth", Value::I32(0)),
+    ] {
+        instance
+            .exports
+            .get_global(name)
+            .unwrap_or_else(|_| panic!("Can't get `{}` from Instance", name))
+            .set(ctx, value)
+            .unwrap_or_else(|_| panic!("Can't set `{}` in Instance", name));
+    }
+}
+
+/// A handle to inspect an [`Instance`][wasmer::Instance] processed
+/// with the [`Inspection`] middleware while it runs, which can be sent
+/// to other threads.
+///
+/// The fields of the state are read one after the other, so they may
+/// come from slightly different points of the execution.
+#[derive(Debug, Clone)]
+pub struct InspectionHandle {
+    function: *const AtomicI32,
+    offset: *const AtomicI64,
+    depth: *const AtomicI32,
+    memory_size: Option<*const AtomicUsize>,
+}
+
+// The state is only accessed atomically.
+unsafe impl Send for InspectionHandle {}
+unsafe impl Sync for InspectionHandle {}
+
+impl InspectionHandle {
+    /// Get the current state of the instance.
+    pub fn inspect(&self) -> InstanceState {
+        unsafe {
+            InstanceState::from_globals(
+                (*self.function).load(Ordering::SeqCst),
+                (*self.offset).load(Ordering::SeqCst),
+                (*self.depth).load(Ordering::SeqCst),
+                self.memory_size
+                    .map(|memory_size| (*memory_size).load(Ordering::SeqCst)),
+            )
+        }
+    }
+}
+
+/// Get a handle to inspect an [`Instance`][wasmer::Instance] from
+/// another thread.
+///
+/// # Safety
+///
+/// The handle accesses the storage of the instance directly, so it
+/// must not be used after the store of the instance is dropped.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Inspection`] middleware at compile time, otherwise this will
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::time::Duration;
+/// use wasmer::{AsStoreRef, Instance};
+/// use wasmer_middlewares::inspection::get_inspection_handle;
+///
+/// fn watch(store: &impl AsStoreRef, instance: &Instance) {
+///     let handle = unsafe { get_inspection_handle(store, instance) };
+///     std::thread::spawn(move || loop {
+///         std::thread::sleep(Duration::from_secs(10));
+///         eprintln!("the guest is at {:?}", handle.inspect());
+///     });
+/// }
+/// ```
+pub unsafe fn get_inspection_handle(
+    ctx: &impl AsStoreRef,
+    instance: &Instance,
+) -> InspectionHandle {
+    let global = |name: &str| {
+        instance
+            .exports
+            .get_global(name)
+            .unwrap_or_else(|_| panic!("Can't get `{}` from Instance", name))
+            .vm_definition(ctx)
+            .as_ptr()
+    };
+    let memory_size = instance
+        .exports
+        .get_memory("wasmer_inspection_memory")
+        .ok()
+        .map(|memory| {
+            let definition = memory.vm_definition(ctx).as_ptr();
+            std::ptr::addr_of!((*definition).current_length) as *const AtomicUsize
+        });
+
+    // The value of a global is stored at the start of its definition.
+    InspectionHandle {
+        function: global("wasmer_inspection_function") as *const AtomicI32,
+        offset: global("wasmer_inspection_offset") as *const AtomicI64,
+        depth: global("wasmer_inspection_depth") as *const AtomicI32,
+        memory_size,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use wasmer::wasmparser::{Parser, Payload};
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Function, FunctionEnv,
+        FunctionEnvMut, Module, Store, TypedFunction,
+    };
+
+    use crate::interrupt::get_interrupt_handle;
+    use crate::Interrupt;
+
+    fn bytecode() -> Vec<u8> {
+        wat2wasm(
+            br#"
+            (module
+            (import "env" "inspect" (func $inspect))
+            (memory 2)
+            (func $outer (export "outer")
+                call $inner)
+            (func $inner
+                call $inspect)
+            (func $spin (export "spin")
+                (loop $forever
+                    br $forever)))
+            "#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    /// Offsets of the operators of the function bodies.
+    fn operator_offsets(bytecode: &[u8]) -> Vec<Vec<usize>> {
+        let mut functions = vec![];
+        for payload in Parser::new(0).parse_all(bytecode) {
+            if let Payload::CodeSectionEntry(body) = payload.unwrap() {
+                let mut reader = body.get_operators_reader().unwrap();
+                let mut offsets = vec![];
+                while !reader.eof() {
+                    offsets.push(reader.read_with_offset().unwrap().1);
+                }
+                functions.push(offsets);
+            }
+        }
+        functions
+    }
+
+    #[derive(Default)]
+    struct Env {
+        instance: Option<Instance>,
+        states: Vec<InstanceState>,
+    }
+
+    fn inspect(mut env: FunctionEnvMut<Env>) {
+        let instance = env.data().instance.clone().unwrap();
+        let state = get_instance_state(&mut env, &instance);
+        env.data_mut().states.push(state);
+    }
+
+    #[test]
+    fn paused_instance_is_inspected() {
+        let bytecode = bytecode();
+        let offsets = operator_offsets(&bytecode);
+
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(Inspection::new()));
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode).unwrap();
+
+        let env = FunctionEnv::new(&mut store, Env::default());
+        let imports = imports! {
+            "env" => {
+                "inspect" => Function::new_typed_with_env(&mut store, &env, inspect),
+            },
+        };
+        let instance = Instance::new(&mut store, &module, &imports).unwrap();
+        env.as_mut(&mut store).instance = Some(instance.clone());
+
+        let idle = InstanceState {
+            function: None,
+            offset: None,
+            depth: 0,
+            memory_size: Some(Pages(2)),
+        };
+        assert_eq!(get_instance_state(&mut store, &instance), idle);
+
+        let outer: TypedFunction<(), ()> = instance
+            .exports
+            .get_function("outer")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        outer.call(&mut store).unwrap();
+
+        // The host runs, called by `$inner` at its call.
+        assert_eq!(
+            env.as_ref(&store).states,
+            vec![InstanceState {
+                function: Some(FunctionIndex::from_u32(2)),
+                offset: Some(offsets[1][0]),
+                depth: 2,
+                memory_size: Some(Pages(2)),
+            }]
+        );
+        assert_eq!(get_instance_state(&mut store, &instance), idle);
+    }
+
+    #[test]
+    fn running_instance_is_inspected() {
+        let bytecode = bytecode();
+        let offsets = operator_offsets(&bytecode);
+
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(Inspection::new()));
+        compiler_config.push_middleware(Arc::new(Interrupt::new()));
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode).unwrap();
+
+        let env = FunctionEnv::new(&mut store, Env::default());
+        let imports = imports! {
+            "env" => {
+                "inspect" => Function::new_typed_with_env(&mut store, &env, inspect),
+            },
+        };
+        let instance = Instance::new(&mut store, &module, &imports).unwrap();
+        let spin: TypedFunction<(), ()> = instance
+            .exports
+            .get_function("spin")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        let inspection = unsafe { get_inspection_handle(&store, &instance) };
+        let interrupt = get_interrupt_handle(&store, &instance);
+        let supervisor = std::thread::spawn(move || {
+            let start = Instant::now();
+            let state = loop {
+                let state = inspection.inspect();
+                if state.depth > 0 || start.elapsed() > Duration::from_secs(10) {
+                    break state;
+                }
+                std::thread::yield_now();
+            };
+            interrupt.interrupt();
+            state
+        });
+
+        assert!(spin.call(&mut store).is_err());
+        // `$spin` is the third function, after the import.
+        assert_eq!(
+            supervisor.join().unwrap(),
+            InstanceState {
+                function: Some(FunctionIndex::from_u32(3)),
+                offset: Some(offsets[2][0]),
+                depth: 1,
+                memory_size: Some(Pages(2)),
+            }
+        );
+
+        // The trap left the state of `$spin`.
+        reset_instance_state(&mut store, &instance);
+        assert_eq!(get_instance_state(&mut store, &instance).depth, 0);
+    }
+}
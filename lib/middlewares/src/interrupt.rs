@@ -0,0 +1,298 @@
+//! `interrupt` is a middleware for stopping the execution of a
+//! WebAssembly instance from another thread, e.g. to enforce a
+//! wall-clock timeout.
+//!
+//! The middleware checks a flag at the entry of every function and at
+//! every iteration of every loop, and traps with an `unreachable` trap
+//! when it is set. The flag is set with an [`InterruptHandle`], which
+//! can be sent to other threads.
+//!
+//! # Memory ordering
+//!
+//! The handle reads and writes the flag with sequentially consistent
+//! atomic operations. The instance reads it with a plain load, preceded
+//! by an `atomic.fence` so that compilers don't reuse a value loaded
+//! earlier, e.g. at the entry of the function, and load it again at
+//! every check. An interruption is thus seen at the next check after it
+//! is visible to the thread running the instance, which is enough for a
+//! flag that doesn't publish any other data. The fence costs a memory
+//! barrier per check with Singlepass and Cranelift.
+
+use std::fmt;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+use wasmer::vm::VMSharedGlobalDefinition;
+use wasmer::wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
+use wasmer::{
+    AsStoreRef, ExportIndex, FunctionMiddleware, GlobalIndex, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleInfo, ModuleMiddleware,
+    Mutability, Type,
+};
+
+/// The module-level interrupt middleware.
+///
+/// # Panic
+///
+/// An instance of `Interrupt` should _not_ be shared among different
+/// modules, since it tracks module-specific information like the
+/// global index of the interrupt flag. Attempts to use an `Interrupt`
+/// instance from multiple modules will result in a panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::Interrupt;
+///
+/// fn create_interrupt_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     compiler_config.push_middleware(Arc::new(Interrupt::new()));
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Interrupt {
+    /// The global index of the interrupt flag.
+    global_index: Mutex<Option<GlobalIndex>>,
+}
+
+/// The function-level interrupt middleware.
+pub struct FunctionInterrupt {
+    /// The global index of the interrupt flag.
+    global_index: GlobalIndex,
+
+    /// Whether the check at the entry of the function has been
+    /// emitted.
+    entry_checked: bool,
+}
+
+impl Interrupt {
+    /// Creates an `Interrupt` middleware.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ModuleMiddleware for Interrupt {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionInterrupt {
+            global_index: self.global_index.lock().unwrap().unwrap(),
+            entry_checked: false,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut global_index = self.global_index.lock().unwrap();
+
+        if global_index.is_some() {
+            panic!("Interrupt::transform_module_info: Attempting to use an `Interrupt` middleware from multiple modules.");
+        }
+
+        // Append a global for the interrupt flag and initialize it.
+        let interrupted_global_index = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        module_info.exports.insert(
+            "wasmer_interrupted".to_string(),
+            ExportIndex::Global(interrupted_global_index),
+        );
+
+        *global_index = Some(interrupted_global_index);
+    }
+}
+
+impl fmt::Debug for FunctionInterrupt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionInterrupt")
+            .field("global_index", &self.global_index)
+            .finish()
+    }
+}
+
+impl FunctionInterrupt {
+    fn check(&self, state: &mut MiddlewareReaderState<'_>) {
+        state.extend(&[
+            // Keep the flag from being loaded once for all the checks.
+            Operator::AtomicFence { flags: 0 },
+            // if globals[interrupted_index] { throw(); }
+            Operator::GlobalGet {
+                global_index: self.global_index.as_u32(),
+            },
+            Operator::If {
+                ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+            },
+            Operator::Unreachable,
+            Operator::End,
+        ]);
+    }
+}
+
+impl FunctionMiddleware for FunctionInterrupt {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if !self.entry_checked {
+            self.entry_checked = true;
+            self.check(state);
+        }
+
+        match operator {
+            // Check at the start of the body of the loop, so that
+            // every iteration is checked.
+            Operator::Loop { .. } => {
+                state.push_operator(operator);
+                self.check(state);
+            }
+            _ => state.push_operator(operator),
+        }
+
+        Ok(())
+    }
+}
+
+/// A handle to interrupt an [`Instance`][wasmer::Instance] processed
+/// with the [`Interrupt`] middleware, which can be sent to other
+/// threads.
+///
+/// Interrupting the instance makes it trap at the next function call
+/// or loop iteration, including the ones of the calls that start
+/// after the interruption, until the handle is cleared.
+///
+/// The handle keeps the flag alive, so it can outlive the instance and
+/// its store.
+#[derive(Debug, Clone)]
+pub struct InterruptHandle {
+    flag: Arc<VMSharedGlobalDefinition>,
+}
+
+impl InterruptHandle {
+    fn flag(&self) -> &AtomicI32 {
+        // SAFETY: the `i32` value of the global is stored at the start
+        // of its definition, which is aligned to 16 bytes and kept alive
+        // by `self.flag`.
+        unsafe { &*(self.flag.as_ptr().as_ptr() as *const AtomicI32) }
+    }
+
+    /// Interrupt the instance.
+    pub fn interrupt(&self) {
+        self.flag().store(1, Ordering::SeqCst);
+    }
+
+    /// Let the instance run again after an interruption.
+    pub fn clear(&self) {
+        self.flag().store(0, Ordering::SeqCst);
+    }
+
+    /// Check whether the instance has been interrupted, i.e. whether
+    /// the trap of a call comes from an interruption.
+    pub fn is_interrupted(&self) -> bool {
+        self.flag().load(Ordering::SeqCst) != 0
+    }
+}
+
+/// Get a handle to interrupt an [`Instance`][wasmer::Instance].
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Interrupt`] middleware at compile time, otherwise this will
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::time::Duration;
+/// use wasmer::{AsStoreRef, Instance};
+/// use wasmer_middlewares::interrupt::get_interrupt_handle;
+///
+/// fn interrupt_after(store: &impl AsStoreRef, instance: &Instance, timeout: Duration) {
+///     let handle = get_interrupt_handle(store, instance);
+///     std::thread::spawn(move || {
+///         std::thread::sleep(timeout);
+///         handle.interrupt();
+///     });
+/// }
+/// ```
+pub fn get_interrupt_handle(ctx: &impl AsStoreRef, instance: &Instance) -> InterruptHandle {
+    let flag = instance
+        .exports
+        .get_global("wasmer_interrupted")
+        .expect("Can't get `wasmer_interrupted` from Instance")
+        .shared_definition(ctx);
+
+    InterruptHandle { flag }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::time::Duration;
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    #[test]
+    fn infinite_loop_is_interrupted() {
+        let bytecode = wat2wasm(
+            br#"
+            (module
+            (func $spin (export "spin")
+                (loop $forever
+                    br $forever))
+            (func $add_one (export "add_one") (param i32) (result i32)
+                local.get 0
+                i32.const 1
+                i32.add))
+            "#,
+        )
+        .unwrap();
+
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(Interrupt::new()));
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+
+        let spin: TypedFunction<(), ()> = instance
+            .exports
+            .get_function("spin")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        let add_one: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("add_one")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        let handle = get_interrupt_handle(&store, &instance);
+        assert!(!handle.is_interrupted());
+
+        let interrupter = {
+            let handle = handle.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(100));
+                handle.interrupt();
+            })
+        };
+        assert!(spin.call(&mut store).is_err());
+        interrupter.join().unwrap();
+        assert!(handle.is_interrupted());
+
+        // The instance stays interrupted until the handle is cleared.
+        assert!(add_one.call(&mut store, 1).is_err());
+        handle.clear();
+        assert_eq!(add_one.call(&mut store, 1).unwrap(), 2);
+    }
+}
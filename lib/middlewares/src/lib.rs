@@ -1,6 +1,28 @@
+pub mod breakpoint;
+pub mod coverage;
+pub mod determinism;
+pub mod heap_profiling;
+pub mod inspection;
+pub mod interrupt;
+pub mod memory_trace;
 pub mod metering;
+pub mod opcode_policy;
+pub mod profiling;
+pub mod watchpoint;
+pub mod yielding;
 
 // The most commonly used symbol are exported at top level of the
 // module. Others are available via modules,
 // e.g. `wasmer_middlewares::metering::get_remaining_points`
+pub use breakpoint::Breakpoints;
+pub use coverage::Coverage;
+pub use determinism::Determinism;
+pub use heap_profiling::HeapProfiling;
+pub use inspection::Inspection;
+pub use interrupt::Interrupt;
+pub use memory_trace::MemoryTrace;
 pub use metering::Metering;
+pub use opcode_policy::OpcodePolicy;
+pub use profiling::Profiling;
+pub use watchpoint::Watchpoints;
+pub use yielding::Yielding;
@@ -0,0 +1,457 @@
+//! `memory_trace` is a middleware for tracing the accesses of a
+//! WebAssembly instance to its linear memory, e.g. to analyze them or
+//! to prototype taint tracking.
+//!
+//! Every load and store calls a callback of the embedder, set with
+//! [`set_memory_trace_callback`], with its address, size and kind,
+//! before the access is made. The callback is stored in a table added
+//! to the module, and the accesses aren't traced until it is set.
+//!
+//! The SIMD stores, the atomic operators and the bulk memory operators
+//! aren't traced.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use wasmer::wasmparser::{
+    MemoryImmediate, Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType,
+};
+use wasmer::{
+    AsStoreMut, ExportIndex, Function, FunctionMiddleware, FunctionType, GlobalIndex, GlobalInit,
+    GlobalType, Instance, LocalFunctionIndex, MiddlewareError, MiddlewareLocals,
+    MiddlewareReaderState, ModuleInfo, ModuleMiddleware, Mutability, SignatureIndex, TableIndex,
+    TableType, Type, Value,
+};
+
+/// The kind of an access, as passed to the callback.
+const LOAD: i32 = 0;
+const STORE: i32 = 1;
+
+#[derive(Debug, Clone)]
+struct MemoryTraceIndexes {
+    /// The global indicating whether the callback is set.
+    enabled: GlobalIndex,
+
+    /// The table holding the callback.
+    table: TableIndex,
+
+    /// The signature of the callback.
+    signature: SignatureIndex,
+}
+
+/// The module-level memory tracing middleware.
+///
+/// # Panic
+///
+/// An instance of `MemoryTrace` should _not_ be shared among
+/// different modules, since it tracks module-specific information like
+/// the index of the table of the callback. Attempts to use a
+/// `MemoryTrace` instance from multiple modules will result in a
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::MemoryTrace;
+///
+/// fn create_memory_trace_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     compiler_config.push_middleware(Arc::new(MemoryTrace::new()));
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct MemoryTrace {
+    /// The indexes of the state added to the module.
+    indexes: Mutex<Option<MemoryTraceIndexes>>,
+}
+
+/// The function-level memory tracing middleware.
+pub struct FunctionMemoryTrace {
+    /// The indexes of the state added to the module.
+    indexes: MemoryTraceIndexes,
+
+    /// The local saving the address of an access.
+    address_local: u32,
+
+    /// The locals saving the value of a store while the access is
+    /// traced, per type of value.
+    value_locals: [u32; 4],
+}
+
+impl MemoryTrace {
+    /// Creates a `MemoryTrace` middleware.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ModuleMiddleware for MemoryTrace {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionMemoryTrace {
+            indexes: self.indexes.lock().unwrap().clone().unwrap(),
+            address_local: 0,
+            value_locals: [0; 4],
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut indexes = self.indexes.lock().unwrap();
+
+        if indexes.is_some() {
+            panic!("MemoryTrace::transform_module_info: Attempting to use a `MemoryTrace` middleware from multiple modules.");
+        }
+
+        // Append a global for the enabled boolean and initialize it.
+        let enabled = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        module_info.exports.insert(
+            "wasmer_memory_trace_enabled".to_string(),
+            ExportIndex::Global(enabled),
+        );
+
+        // Append a table for the callback, called with the address,
+        // the size and the kind of the access.
+        let signature = module_info.signatures.push(FunctionType::new(
+            vec![Type::I64, Type::I32, Type::I32],
+            vec![],
+        ));
+
+        let table = module_info
+            .tables
+            .push(TableType::new(Type::FuncRef, 1, Some(1)));
+
+        module_info.exports.insert(
+            "wasmer_memory_trace_callback".to_string(),
+            ExportIndex::Table(table),
+        );
+
+        *indexes = Some(MemoryTraceIndexes {
+            enabled,
+            table,
+            signature,
+        });
+    }
+}
+
+impl fmt::Debug for FunctionMemoryTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionMemoryTrace")
+            .field("indexes", &self.indexes)
+            .finish()
+    }
+}
+
+/// The value types of the locals saving the value of a store.
+pub(crate) const VALUE_TYPES: [WpType; 4] = [WpType::I32, WpType::I64, WpType::F32, WpType::F64];
+
+/// Returns the memory access of `operator`, if it's a traced load or
+/// store: its immediate, its size, and for a store, the index in
+/// `VALUE_TYPES` of the type of the value stored.
+pub(crate) fn operator_access(
+    operator: &Operator,
+) -> Option<(MemoryImmediate, u32, Option<usize>)> {
+    Some(match operator {
+        Operator::I32Load8S { memarg }
+        | Operator::I32Load8U { memarg }
+        | Operator::I64Load8S { memarg }
+        | Operator::I64Load8U { memarg } => (*memarg, 1, None),
+        Operator::I32Load16S { memarg }
+        | Operator::I32Load16U { memarg }
+        | Operator::I64Load16S { memarg }
+        | Operator::I64Load16U { memarg } => (*memarg, 2, None),
+        Operator::I32Load { memarg }
+        | Operator::F32Load { memarg }
+        | Operator::I64Load32S { memarg }
+        | Operator::I64Load32U { memarg } => (*memarg, 4, None),
+        Operator::I64Load { memarg } | Operator::F64Load { memarg } => (*memarg, 8, None),
+        Operator::V128Load { memarg } => (*memarg, 16, None),
+        Operator::I32Store8 { memarg } => (*memarg, 1, Some(0)),
+        Operator::I32Store16 { memarg } => (*memarg, 2, Some(0)),
+        Operator::I32Store { memarg } => (*memarg, 4, Some(0)),
+        Operator::I64Store8 { memarg } => (*memarg, 1, Some(1)),
+        Operator::I64Store16 { memarg } => (*memarg, 2, Some(1)),
+        Operator::I64Store32 { memarg } => (*memarg, 4, Some(1)),
+        Operator::I64Store { memarg } => (*memarg, 8, Some(1)),
+        Operator::F32Store { memarg } => (*memarg, 4, Some(2)),
+        Operator::F64Store { memarg } => (*memarg, 8, Some(3)),
+        _ => return None,
+    })
+}
+
+impl FunctionMemoryTrace {
+    /// Calls the callback for the access at the address on the top of
+    /// the stack, if it's set.
+    fn trace(
+        &self,
+        memarg: &MemoryImmediate,
+        size: i32,
+        kind: i32,
+        state: &mut MiddlewareReaderState<'_>,
+    ) {
+        state.extend(&[
+            // if globals[enabled] {
+            //     callback(u64(address) + offset, size, kind);
+            // }
+            Operator::LocalTee {
+                local_index: self.address_local,
+            },
+            Operator::GlobalGet {
+                global_index: self.indexes.enabled.as_u32(),
+            },
+            Operator::If {
+                ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+            },
+            Operator::LocalGet {
+                local_index: self.address_local,
+            },
+            Operator::I64ExtendI32U,
+            Operator::I64Const {
+                value: memarg.offset as i64,
+            },
+            Operator::I64Add,
+            Operator::I32Const { value: size },
+            Operator::I32Const { value: kind },
+            Operator::I32Const { value: 0 },
+            Operator::CallIndirect {
+                index: self.indexes.signature.as_u32(),
+                table_index: self.indexes.table.as_u32(),
+            },
+            Operator::End,
+        ]);
+    }
+
+    /// Like [`trace`](Self::trace), for a store whose value of type
+    /// `VALUE_TYPES[value_type]` is on the top of the stack.
+    fn trace_store(
+        &self,
+        memarg: &MemoryImmediate,
+        size: i32,
+        value_type: usize,
+        state: &mut MiddlewareReaderState<'_>,
+    ) {
+        let value_local = self.value_locals[value_type];
+        state.push_operator(Operator::LocalSet {
+            local_index: value_local,
+        });
+        self.trace(memarg, size, STORE, state);
+        state.push_operator(Operator::LocalGet {
+            local_index: value_local,
+        });
+    }
+}
+
+impl FunctionMiddleware for FunctionMemoryTrace {
+    fn feed_locals(&mut self, locals: &mut MiddlewareLocals) -> Result<(), MiddlewareError> {
+        self.address_local = locals.add(1, WpType::I32)?;
+        for (value_local, ty) in self.value_locals.iter_mut().zip(VALUE_TYPES.iter()) {
+            *value_local = locals.add(1, *ty)?;
+        }
+        Ok(())
+    }
+
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        match operator_access(&operator) {
+            Some((memarg, size, None)) => self.trace(&memarg, size as i32, LOAD, state),
+            Some((memarg, size, Some(value_type))) => {
+                self.trace_store(&memarg, size as i32, value_type, state)
+            }
+            None => {}
+        }
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// The kind of a memory access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryAccessKind {
+    /// A read of the memory.
+    Load,
+    /// A write to the memory.
+    Store,
+}
+
+/// An access of an [`Instance`][wasmer::Instance] to its memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    /// The address of the access, offset included.
+    pub address: u64,
+    /// The number of bytes accessed.
+    pub size: u32,
+    /// Whether the memory is read or written.
+    pub kind: MemoryAccessKind,
+}
+
+/// Set the callback called with the memory accesses of an
+/// [`Instance`][wasmer::Instance], which starts the tracing.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`MemoryTrace`] middleware at compile time, otherwise this will
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use wasmer::{AsStoreMut, Instance};
+/// use wasmer_middlewares::memory_trace::{set_memory_trace_callback, MemoryAccessKind};
+///
+/// fn print_stores(store: &mut impl AsStoreMut, instance: &Instance) {
+///     set_memory_trace_callback(store, instance, |access| {
+///         if access.kind == MemoryAccessKind::Store {
+///             println!("{} bytes written at {:#x}", access.size, access.address);
+///         }
+///     });
+/// }
+/// ```
+pub fn set_memory_trace_callback(
+    ctx: &mut impl AsStoreMut,
+    instance: &Instance,
+    callback: impl Fn(MemoryAccess) + Send + Sync + 'static,
+) {
+    let function = Function::new_typed(ctx, move |address: i64, size: i32, kind: i32| {
+        callback(MemoryAccess {
+            address: address as u64,
+            size: size as u32,
+            kind: if kind == STORE {
+                MemoryAccessKind::Store
+            } else {
+                MemoryAccessKind::Load
+            },
+        })
+    });
+
+    instance
+        .exports
+        .get_table("wasmer_memory_trace_callback")
+        .expect("Can't get `wasmer_memory_trace_callback` from Instance")
+        .set(ctx, 0, Value::FuncRef(Some(function)))
+        .expect("Can't set `wasmer_memory_trace_callback` in Instance");
+
+    set_enabled(ctx, instance, true);
+}
+
+/// Stop tracing the memory accesses of an
+/// [`Instance`][wasmer::Instance].
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`MemoryTrace`] middleware at compile time, otherwise this will
+/// panic.
+pub fn clear_memory_trace_callback(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    set_enabled(ctx, instance, false);
+
+    instance
+        .exports
+        .get_table("wasmer_memory_trace_callback")
+        .expect("Can't get `wasmer_memory_trace_callback` from Instance")
+        .set(ctx, 0, Value::FuncRef(None))
+        .expect("Can't set `wasmer_memory_trace_callback` in Instance");
+}
+
+/// Record the memory accesses of an [`Instance`][wasmer::Instance] in
+/// the returned log, instead of streaming them to a callback.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`MemoryTrace`] middleware at compile time, otherwise this will
+/// panic.
+pub fn record_memory_accesses(
+    ctx: &mut impl AsStoreMut,
+    instance: &Instance,
+) -> Arc<Mutex<Vec<MemoryAccess>>> {
+    let log = Arc::new(Mutex::new(vec![]));
+    let accesses = log.clone();
+    set_memory_trace_callback(ctx, instance, move |access| {
+        accesses.lock().unwrap().push(access)
+    });
+    log
+}
+
+fn set_enabled(ctx: &mut impl AsStoreMut, instance: &Instance, enabled: bool) {
+    instance
+        .exports
+        .get_global("wasmer_memory_trace_enabled")
+        .expect("Can't get `wasmer_memory_trace_enabled` from Instance")
+        .set(ctx, Value::I32(enabled as i32))
+        .expect("Can't set `wasmer_memory_trace_enabled` in Instance");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    #[test]
+    fn loads_and_stores_are_traced() {
+        let bytecode = wat2wasm(
+            br#"
+            (module
+            (memory 1)
+            (func (export "copy") (param $from i32) (param $to i32)
+                local.get $to
+                local.get $from
+                i64.load offset=8
+                i64.store16 offset=2))
+            "#,
+        )
+        .unwrap();
+
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(MemoryTrace::new()));
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+
+        let copy: TypedFunction<(i32, i32), ()> = instance
+            .exports
+            .get_function("copy")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        // Nothing is traced until a callback is set.
+        copy.call(&mut store, 16, 32).unwrap();
+
+        let log = record_memory_accesses(&mut store, &instance);
+        copy.call(&mut store, 16, 32).unwrap();
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                MemoryAccess {
+                    address: 24,
+                    size: 8,
+                    kind: MemoryAccessKind::Load,
+                },
+                MemoryAccess {
+                    address: 34,
+                    size: 2,
+                    kind: MemoryAccessKind::Store,
+                },
+            ]
+        );
+
+        clear_memory_trace_callback(&mut store, &instance);
+        copy.call(&mut store, 16, 32).unwrap();
+        assert_eq!(log.lock().unwrap().len(), 2);
+    }
+}
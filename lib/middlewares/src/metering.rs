@@ -3,20 +3,29 @@
 //! operators executed. The WebAssembly instance execution is stopped
 //! when the limit is reached.
 //!
+//! The cost of the operators is given by a cost function, or by a
+//! [`CostTable`]. The bulk memory and table operators can also be
+//! charged per byte or element they process, when they run.
+//!
 //! # Example
 //!
 //! [See the `metering` detailed and complete
 //! example](https://github.com/wasmerio/wasmer/blob/master/examples/metering.rs).
 
+use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::fmt;
 use std::sync::{Arc, Mutex};
 use wasmer::wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
 use wasmer::{
-    AsStoreMut, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance,
-    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+    AsStoreMut, ExportIndex, FunctionMiddleware, GlobalIndex, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareLocals, MiddlewareReaderState, ModuleInfo,
+    ModuleMiddleware, Mutability, Type,
 };
-use wasmer_types::{GlobalIndex, ModuleInfo};
+
+/// A function that maps an operator to a cost in "points" per byte or
+/// element it processes.
+type CostPerUnitFunction = Arc<dyn Fn(&Operator) -> u64 + Send + Sync>;
 
 #[derive(Clone)]
 struct MeteringGlobalIndexes(GlobalIndex, GlobalIndex);
@@ -87,6 +96,14 @@ pub struct Metering<F: Fn(&Operator) -> u64 + Send + Sync> {
     /// Function that maps each operator to a cost in "points".
     cost_function: Arc<F>,
 
+    /// Function that maps the bulk operators to a cost in "points" per
+    /// unit of length.
+    cost_per_unit_function: Option<CostPerUnitFunction>,
+
+    /// Identifies the costs in the `deterministic_id`: the type of the
+    /// cost functions, or the contents of the `CostTable` they charge.
+    costs_id: String,
+
     /// The global indexes for metering points.
     global_indexes: Mutex<Option<MeteringGlobalIndexes>>,
 }
@@ -96,11 +113,219 @@ pub struct FunctionMetering<F: Fn(&Operator) -> u64 + Send + Sync> {
     /// Function that maps each operator to a cost in "points".
     cost_function: Arc<F>,
 
+    /// Function that maps the bulk operators to a cost in "points" per
+    /// unit of length.
+    cost_per_unit_function: Option<CostPerUnitFunction>,
+
     /// The global indexes for metering points.
     global_indexes: MeteringGlobalIndexes,
 
     /// Accumulated cost of the current basic block.
     accumulated_cost: u64,
+
+    /// The locals holding the length processed by a bulk operator, and
+    /// its cost.
+    length_locals: Option<(u32, u32)>,
+}
+
+/// A table of the costs of the operators, for auditable schedules.
+///
+/// The operators are named after the variants of
+/// [`Operator`][wasmer::wasmparser::Operator], e.g. `I32Add` or
+/// `MemoryCopy`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::metering::{CostTable, Metering};
+///
+/// fn create_metering_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     // Every operator costs 1 point, except the divisions, and
+///     // copying memory costs 1 more point per byte.
+///     let cost_table = CostTable::new(1)
+///         .with_cost("I32DivS", 10)
+///         .with_cost("I32DivU", 10)
+///         .with_cost_per_unit("MemoryCopy", 1);
+///
+///     compiler_config.push_middleware(Arc::new(Metering::with_cost_table(1_000_000, cost_table)));
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CostTable {
+    /// The cost of the operators missing from `costs`.
+    pub default_cost: u64,
+
+    /// The cost of the operators, by name.
+    pub costs: BTreeMap<String, u64>,
+
+    /// The cost per byte or element processed of the bulk memory and
+    /// table operators (`MemoryCopy`, `MemoryFill`, `MemoryInit`,
+    /// `TableCopy`, `TableFill` and `TableInit`), by name, which is
+    /// charged in addition to their cost when they run.
+    pub costs_per_unit: BTreeMap<String, u64>,
+}
+
+impl CostTable {
+    /// Creates a `CostTable` where every operator costs `default_cost`.
+    pub fn new(default_cost: u64) -> Self {
+        Self {
+            default_cost,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the cost of the operator named `operator`.
+    ///
+    /// The costs of the operators of a basic block saturate at
+    /// `u64::MAX` when they are added up.
+    pub fn with_cost(mut self, operator: &str, cost: u64) -> Self {
+        self.costs.insert(operator.to_string(), cost);
+        self
+    }
+
+    /// Sets the cost per byte or element of the bulk operator named
+    /// `operator`.
+    ///
+    /// The cost saturates at [`MAX_COST_PER_UNIT`].
+    pub fn with_cost_per_unit(mut self, operator: &str, cost: u64) -> Self {
+        self.costs_per_unit
+            .insert(operator.to_string(), cost.min(MAX_COST_PER_UNIT));
+        self
+    }
+
+    /// Returns the cost of `operator`.
+    pub fn cost(&self, operator: &Operator) -> u64 {
+        if self.costs.is_empty() {
+            return self.default_cost;
+        }
+        self.costs
+            .get(operator_name(operator))
+            .copied()
+            .unwrap_or(self.default_cost)
+    }
+
+    /// Returns the cost per byte or element of `operator`, which is 0
+    /// for the operators that aren't bulk operators.
+    pub fn cost_per_unit(&self, operator: &Operator) -> u64 {
+        if !is_bulk_operator(operator) {
+            return 0;
+        }
+        self.costs_per_unit
+            .get(operator_name(operator))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// Matches an operator on the variants of `Operator`, to their names.
+macro_rules! operator_names {
+    ($operator:expr; $($name:ident)*) => {
+        match $operator {
+            $(Operator::$name { .. } => stringify!($name),)*
+        }
+    };
+}
+
+/// Returns the name of the variant of `operator`.
+pub(crate) fn operator_name(operator: &Operator) -> &'static str {
+    operator_names!(operator;
+        Unreachable Nop Block Loop If Else Try Catch Throw Rethrow End Br BrIf BrTable Return Call
+        CallIndirect ReturnCall ReturnCallIndirect Delegate CatchAll Drop Select TypedSelect
+        LocalGet LocalSet LocalTee GlobalGet GlobalSet I32Load I64Load F32Load F64Load I32Load8S
+        I32Load8U I32Load16S I32Load16U I64Load8S I64Load8U I64Load16S I64Load16U I64Load32S
+        I64Load32U I32Store I64Store F32Store F64Store I32Store8 I32Store16 I64Store8 I64Store16
+        I64Store32 MemorySize MemoryGrow I32Const I64Const F32Const F64Const RefNull RefIsNull
+        RefFunc I32Eqz I32Eq I32Ne I32LtS I32LtU I32GtS I32GtU I32LeS I32LeU I32GeS I32GeU I64Eqz
+        I64Eq I64Ne I64LtS I64LtU I64GtS I64GtU I64LeS I64LeU I64GeS I64GeU F32Eq F32Ne F32Lt F32Gt
+        F32Le F32Ge F64Eq F64Ne F64Lt F64Gt F64Le F64Ge I32Clz I32Ctz I32Popcnt I32Add I32Sub
+        I32Mul I32DivS I32DivU I32RemS I32RemU I32And I32Or I32Xor I32Shl I32ShrS I32ShrU I32Rotl
+        I32Rotr I64Clz I64Ctz I64Popcnt I64Add I64Sub I64Mul I64DivS I64DivU I64RemS I64RemU I64And
+        I64Or I64Xor I64Shl I64ShrS I64ShrU I64Rotl I64Rotr F32Abs F32Neg F32Ceil F32Floor F32Trunc
+        F32Nearest F32Sqrt F32Add F32Sub F32Mul F32Div F32Min F32Max F32Copysign F64Abs F64Neg
+        F64Ceil F64Floor F64Trunc F64Nearest F64Sqrt F64Add F64Sub F64Mul F64Div F64Min F64Max
+        F64Copysign I32WrapI64 I32TruncF32S I32TruncF32U I32TruncF64S I32TruncF64U I64ExtendI32S
+        I64ExtendI32U I64TruncF32S I64TruncF32U I64TruncF64S I64TruncF64U F32ConvertI32S
+        F32ConvertI32U F32ConvertI64S F32ConvertI64U F32DemoteF64 F64ConvertI32S F64ConvertI32U
+        F64ConvertI64S F64ConvertI64U F64PromoteF32 I32ReinterpretF32 I64ReinterpretF64
+        F32ReinterpretI32 F64ReinterpretI64 I32Extend8S I32Extend16S I64Extend8S I64Extend16S
+        I64Extend32S I32TruncSatF32S I32TruncSatF32U I32TruncSatF64S I32TruncSatF64U
+        I64TruncSatF32S I64TruncSatF32U I64TruncSatF64S I64TruncSatF64U MemoryInit DataDrop
+        MemoryCopy MemoryFill TableInit ElemDrop TableCopy TableFill TableGet TableSet TableGrow
+        TableSize MemoryAtomicNotify MemoryAtomicWait32 MemoryAtomicWait64 AtomicFence
+        I32AtomicLoad I64AtomicLoad I32AtomicLoad8U I32AtomicLoad16U I64AtomicLoad8U
+        I64AtomicLoad16U I64AtomicLoad32U I32AtomicStore I64AtomicStore I32AtomicStore8
+        I32AtomicStore16 I64AtomicStore8 I64AtomicStore16 I64AtomicStore32 I32AtomicRmwAdd
+        I64AtomicRmwAdd I32AtomicRmw8AddU I32AtomicRmw16AddU I64AtomicRmw8AddU I64AtomicRmw16AddU
+        I64AtomicRmw32AddU I32AtomicRmwSub I64AtomicRmwSub I32AtomicRmw8SubU I32AtomicRmw16SubU
+        I64AtomicRmw8SubU I64AtomicRmw16SubU I64AtomicRmw32SubU I32AtomicRmwAnd I64AtomicRmwAnd
+        I32AtomicRmw8AndU I32AtomicRmw16AndU I64AtomicRmw8AndU I64AtomicRmw16AndU
+        I64AtomicRmw32AndU I32AtomicRmwOr I64AtomicRmwOr I32AtomicRmw8OrU I32AtomicRmw16OrU
+        I64AtomicRmw8OrU I64AtomicRmw16OrU I64AtomicRmw32OrU I32AtomicRmwXor I64AtomicRmwXor
+        I32AtomicRmw8XorU I32AtomicRmw16XorU I64AtomicRmw8XorU I64AtomicRmw16XorU
+        I64AtomicRmw32XorU I32AtomicRmwXchg I64AtomicRmwXchg I32AtomicRmw8XchgU I32AtomicRmw16XchgU
+        I64AtomicRmw8XchgU I64AtomicRmw16XchgU I64AtomicRmw32XchgU I32AtomicRmwCmpxchg
+        I64AtomicRmwCmpxchg I32AtomicRmw8CmpxchgU I32AtomicRmw16CmpxchgU I64AtomicRmw8CmpxchgU
+        I64AtomicRmw16CmpxchgU I64AtomicRmw32CmpxchgU V128Load V128Load8x8S V128Load8x8U
+        V128Load16x4S V128Load16x4U V128Load32x2S V128Load32x2U V128Load8Splat V128Load16Splat
+        V128Load32Splat V128Load64Splat V128Load32Zero V128Load64Zero V128Store V128Load8Lane
+        V128Load16Lane V128Load32Lane V128Load64Lane V128Store8Lane V128Store16Lane V128Store32Lane
+        V128Store64Lane V128Const I8x16Shuffle I8x16ExtractLaneS I8x16ExtractLaneU I8x16ReplaceLane
+        I16x8ExtractLaneS I16x8ExtractLaneU I16x8ReplaceLane I32x4ExtractLane I32x4ReplaceLane
+        I64x2ExtractLane I64x2ReplaceLane F32x4ExtractLane F32x4ReplaceLane F64x2ExtractLane
+        F64x2ReplaceLane I8x16Swizzle I8x16Splat I16x8Splat I32x4Splat I64x2Splat F32x4Splat
+        F64x2Splat I8x16Eq I8x16Ne I8x16LtS I8x16LtU I8x16GtS I8x16GtU I8x16LeS I8x16LeU I8x16GeS
+        I8x16GeU I16x8Eq I16x8Ne I16x8LtS I16x8LtU I16x8GtS I16x8GtU I16x8LeS I16x8LeU I16x8GeS
+        I16x8GeU I32x4Eq I32x4Ne I32x4LtS I32x4LtU I32x4GtS I32x4GtU I32x4LeS I32x4LeU I32x4GeS
+        I32x4GeU I64x2Eq I64x2Ne I64x2LtS I64x2GtS I64x2LeS I64x2GeS F32x4Eq F32x4Ne F32x4Lt
+        F32x4Gt F32x4Le F32x4Ge F64x2Eq F64x2Ne F64x2Lt F64x2Gt F64x2Le F64x2Ge V128Not V128And
+        V128AndNot V128Or V128Xor V128Bitselect V128AnyTrue I8x16Abs I8x16Neg I8x16Popcnt
+        I8x16AllTrue I8x16Bitmask I8x16NarrowI16x8S I8x16NarrowI16x8U I8x16Shl I8x16ShrS I8x16ShrU
+        I8x16Add I8x16AddSatS I8x16AddSatU I8x16Sub I8x16SubSatS I8x16SubSatU I8x16MinS I8x16MinU
+        I8x16MaxS I8x16MaxU I8x16RoundingAverageU I16x8ExtAddPairwiseI8x16S
+        I16x8ExtAddPairwiseI8x16U I16x8Abs I16x8Neg I16x8Q15MulrSatS I16x8AllTrue I16x8Bitmask
+        I16x8NarrowI32x4S I16x8NarrowI32x4U I16x8ExtendLowI8x16S I16x8ExtendHighI8x16S
+        I16x8ExtendLowI8x16U I16x8ExtendHighI8x16U I16x8Shl I16x8ShrS I16x8ShrU I16x8Add
+        I16x8AddSatS I16x8AddSatU I16x8Sub I16x8SubSatS I16x8SubSatU I16x8Mul I16x8MinS I16x8MinU
+        I16x8MaxS I16x8MaxU I16x8RoundingAverageU I16x8ExtMulLowI8x16S I16x8ExtMulHighI8x16S
+        I16x8ExtMulLowI8x16U I16x8ExtMulHighI8x16U I32x4ExtAddPairwiseI16x8S
+        I32x4ExtAddPairwiseI16x8U I32x4Abs I32x4Neg I32x4AllTrue I32x4Bitmask I32x4ExtendLowI16x8S
+        I32x4ExtendHighI16x8S I32x4ExtendLowI16x8U I32x4ExtendHighI16x8U I32x4Shl I32x4ShrS
+        I32x4ShrU I32x4Add I32x4Sub I32x4Mul I32x4MinS I32x4MinU I32x4MaxS I32x4MaxU I32x4DotI16x8S
+        I32x4ExtMulLowI16x8S I32x4ExtMulHighI16x8S I32x4ExtMulLowI16x8U I32x4ExtMulHighI16x8U
+        I64x2Abs I64x2Neg I64x2AllTrue I64x2Bitmask I64x2ExtendLowI32x4S I64x2ExtendHighI32x4S
+        I64x2ExtendLowI32x4U I64x2ExtendHighI32x4U I64x2Shl I64x2ShrS I64x2ShrU I64x2Add I64x2Sub
+        I64x2Mul I64x2ExtMulLowI32x4S I64x2ExtMulHighI32x4S I64x2ExtMulLowI32x4U
+        I64x2ExtMulHighI32x4U F32x4Ceil F32x4Floor F32x4Trunc F32x4Nearest F32x4Abs F32x4Neg
+        F32x4Sqrt F32x4Add F32x4Sub F32x4Mul F32x4Div F32x4Min F32x4Max F32x4PMin F32x4PMax
+        F64x2Ceil F64x2Floor F64x2Trunc F64x2Nearest F64x2Abs F64x2Neg F64x2Sqrt F64x2Add F64x2Sub
+        F64x2Mul F64x2Div F64x2Min F64x2Max F64x2PMin F64x2PMax I32x4TruncSatF32x4S
+        I32x4TruncSatF32x4U F32x4ConvertI32x4S F32x4ConvertI32x4U I32x4TruncSatF64x2SZero
+        I32x4TruncSatF64x2UZero F64x2ConvertLowI32x4S F64x2ConvertLowI32x4U F32x4DemoteF64x2Zero
+        F64x2PromoteLowF32x4 I8x16RelaxedSwizzle I32x4RelaxedTruncSatF32x4S
+        I32x4RelaxedTruncSatF32x4U I32x4RelaxedTruncSatF64x2SZero I32x4RelaxedTruncSatF64x2UZero
+        F32x4Fma F32x4Fms F64x2Fma F64x2Fms I8x16LaneSelect I16x8LaneSelect I32x4LaneSelect
+        I64x2LaneSelect F32x4RelaxedMin F32x4RelaxedMax F64x2RelaxedMin F64x2RelaxedMax
+    )
+}
+
+/// The highest cost per byte or element of the bulk operators, so that
+/// the cost of the longest length, `u32::MAX`, fits in 64 bits. Higher
+/// costs saturate to it.
+pub const MAX_COST_PER_UNIT: u64 = u64::MAX / u32::MAX as u64;
+
+/// Whether `operator` processes a length given by its last operand.
+fn is_bulk_operator(operator: &Operator) -> bool {
+    matches!(
+        operator,
+        Operator::MemoryCopy { .. }
+            | Operator::MemoryFill { .. }
+            | Operator::MemoryInit { .. }
+            | Operator::TableCopy { .. }
+            | Operator::TableFill { .. }
+            | Operator::TableInit { .. }
+    )
 }
 
 /// Represents the type of the metering points, either `Remaining` or
@@ -128,9 +353,52 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> Metering<F> {
         Self {
             initial_limit,
             cost_function: Arc::new(cost_function),
+            cost_per_unit_function: None,
+            costs_id: std::any::type_name::<F>().to_string(),
             global_indexes: Mutex::new(None),
         }
     }
+
+    /// Charges the bulk memory and table operators (`memory.copy`,
+    /// `memory.fill`, `memory.init`, `table.copy`, `table.fill` and
+    /// `table.init`) `cost_per_unit_function(operator)` points per byte
+    /// or element they process, in addition to their cost, when they run.
+    ///
+    /// The costs saturate at [`MAX_COST_PER_UNIT`]. Only the operators
+    /// on 32-bit memories are supported.
+    pub fn with_cost_per_unit_function<P: Fn(&Operator) -> u64 + Send + Sync + 'static>(
+        mut self,
+        cost_per_unit_function: P,
+    ) -> Self {
+        self.cost_per_unit_function = Some(Arc::new(cost_per_unit_function));
+        self.costs_id = format!("{}-{}", self.costs_id, std::any::type_name::<P>());
+        self
+    }
+}
+
+impl Metering<Box<dyn Fn(&Operator) -> u64 + Send + Sync>> {
+    /// Creates a `Metering` middleware charging the costs of
+    /// `cost_table`.
+    pub fn with_cost_table(initial_limit: u64, cost_table: CostTable) -> Self {
+        let costs_id = format!("{:?}", cost_table);
+        let cost_table = Arc::new(cost_table);
+        let metering = {
+            let cost_table = cost_table.clone();
+            Self::new(
+                initial_limit,
+                Box::new(move |operator: &Operator| cost_table.cost(operator)),
+            )
+        };
+        let metering = if cost_table.costs_per_unit.is_empty() {
+            metering
+        } else {
+            metering.with_cost_per_unit_function(move |operator| cost_table.cost_per_unit(operator))
+        };
+        Self {
+            costs_id,
+            ..metering
+        }
+    }
 }
 
 impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for Metering<F> {
@@ -148,8 +416,10 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync + 'static> ModuleMiddleware for Meter
     fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
         Box::new(FunctionMetering {
             cost_function: self.cost_function.clone(),
+            cost_per_unit_function: self.cost_per_unit_function.clone(),
             global_indexes: self.global_indexes.lock().unwrap().clone().unwrap(),
             accumulated_cost: 0,
+            length_locals: None,
         })
     }
 
@@ -194,6 +464,18 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync + 'static> ModuleMiddleware for Meter
             points_exhausted_global_index,
         ))
     }
+
+    /// The costs of a `CostTable` are identified by its contents, but
+    /// the cost functions are only identified by their type, so two
+    /// closures of the same type must charge the same costs.
+    fn deterministic_id(&self) -> String {
+        format!(
+            "{}-{}-{}",
+            std::any::type_name::<Self>(),
+            self.initial_limit,
+            self.costs_id
+        )
+    }
 }
 
 impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for FunctionMetering<F> {
@@ -205,7 +487,71 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> fmt::Debug for FunctionMetering<F> {
     }
 }
 
+impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMetering<F> {
+    /// Charges the length processed by a bulk operator, the last operand
+    /// of the operator, times `cost_per_unit`.
+    fn charge_length(&self, cost_per_unit: u64, state: &mut MiddlewareReaderState<'_>) {
+        let (length_local, cost_local) = self.length_locals.unwrap();
+        // The product can't overflow.
+        let cost_per_unit = cost_per_unit.min(MAX_COST_PER_UNIT);
+        state.extend(&[
+            // cost_local = u64(length) * cost_per_unit;
+            Operator::LocalTee {
+                local_index: length_local,
+            },
+            Operator::LocalGet {
+                local_index: length_local,
+            },
+            Operator::I64ExtendI32U,
+            Operator::I64Const {
+                value: cost_per_unit as i64,
+            },
+            Operator::I64Mul,
+            Operator::LocalSet {
+                local_index: cost_local,
+            },
+            // if unsigned(globals[remaining_points_index]) < unsigned(cost_local) { throw(); }
+            Operator::GlobalGet {
+                global_index: self.global_indexes.remaining_points().as_u32(),
+            },
+            Operator::LocalGet {
+                local_index: cost_local,
+            },
+            Operator::I64LtU,
+            Operator::If {
+                ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+            },
+            Operator::I32Const { value: 1 },
+            Operator::GlobalSet {
+                global_index: self.global_indexes.points_exhausted().as_u32(),
+            },
+            Operator::Unreachable,
+            Operator::End,
+            // globals[remaining_points_index] -= cost_local;
+            Operator::GlobalGet {
+                global_index: self.global_indexes.remaining_points().as_u32(),
+            },
+            Operator::LocalGet {
+                local_index: cost_local,
+            },
+            Operator::I64Sub,
+            Operator::GlobalSet {
+                global_index: self.global_indexes.remaining_points().as_u32(),
+            },
+        ]);
+    }
+}
+
 impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMiddleware for FunctionMetering<F> {
+    fn feed_locals(&mut self, locals: &mut MiddlewareLocals) -> Result<(), MiddlewareError> {
+        if self.cost_per_unit_function.is_some() {
+            let length_local = locals.add(1, WpType::I32)?;
+            let cost_local = locals.add(1, WpType::I64)?;
+            self.length_locals = Some((length_local, cost_local));
+        }
+        Ok(())
+    }
+
     fn feed<'a>(
         &mut self,
         operator: Operator<'a>,
@@ -214,7 +560,10 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMiddleware for FunctionMeter
         // Get the cost of the current operator, and add it to the accumulator.
         // This needs to be done before the metering logic, to prevent operators like `Call` from escaping metering in some
         // corner cases.
-        self.accumulated_cost += (self.cost_function)(&operator);
+        // The cost saturates, since no limit can cover it anyway.
+        self.accumulated_cost = self
+            .accumulated_cost
+            .saturating_add((self.cost_function)(&operator));
 
         // Possible sources and targets of a branch. Finalize the cost of the previous basic block and perform necessary checks.
         match operator {
@@ -252,6 +601,15 @@ impl<F: Fn(&Operator) -> u64 + Send + Sync> FunctionMiddleware for FunctionMeter
             }
             _ => {}
         }
+
+        if let Some(cost_per_unit_function) = &self.cost_per_unit_function {
+            if is_bulk_operator(&operator) {
+                let cost_per_unit = cost_per_unit_function(&operator);
+                if cost_per_unit > 0 {
+                    self.charge_length(cost_per_unit, state);
+                }
+            }
+        }
         state.push_operator(operator);
 
         Ok(())
@@ -485,4 +843,107 @@ mod tests {
             MeteringPoints::Remaining(4)
         );
     }
+
+    #[test]
+    fn cost_table_charges_bulk_operators_per_byte() {
+        let cost_table = CostTable::new(1)
+            .with_cost("LocalGet", 0)
+            .with_cost_per_unit("MemoryFill", 2);
+        let metering = Arc::new(Metering::with_cost_table(100, cost_table));
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(metering);
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let bytecode = wat2wasm(
+            br#"
+            (module
+            (memory 1)
+            (func (export "fill") (param $length i32)
+                i32.const 0
+                i32.const 0
+                local.get $length
+                memory.fill))
+            "#,
+        )
+        .unwrap();
+        let module = Module::new(&store, bytecode).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        let fill: TypedFunction<i32, ()> = instance
+            .exports
+            .get_function("fill")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        // The 4 operators other than `local.get` cost 1 point, and the
+        // 10 bytes filled 2 points each.
+        fill.call(&mut store, 10).unwrap();
+        assert_eq!(
+            get_remaining_points(&mut store, &instance),
+            MeteringPoints::Remaining(76)
+        );
+
+        assert!(fill.call(&mut store, 100).is_err());
+        assert_eq!(
+            get_remaining_points(&mut store, &instance),
+            MeteringPoints::Exhausted
+        );
+    }
+
+    #[test]
+    fn cost_tables_are_identified_by_their_costs() {
+        let id =
+            |cost_table: CostTable| Metering::with_cost_table(10, cost_table).deterministic_id();
+        let cost_table = CostTable::new(1).with_cost("LocalGet", 0);
+        assert_eq!(id(cost_table.clone()), id(cost_table.clone()));
+        assert_ne!(id(cost_table.clone()), id(CostTable::new(1)));
+        assert_ne!(
+            id(cost_table.clone()),
+            id(cost_table.with_cost_per_unit("MemoryFill", 2))
+        );
+    }
+
+    #[test]
+    fn cost_per_unit_saturates() {
+        let cost_table = CostTable::new(0).with_cost_per_unit("MemoryFill", u64::MAX);
+        assert_eq!(cost_table.costs_per_unit["MemoryFill"], MAX_COST_PER_UNIT);
+        assert_eq!(
+            u64::from(u32::MAX).checked_mul(MAX_COST_PER_UNIT),
+            Some(u64::MAX)
+        );
+
+        let metering = Arc::new(
+            Metering::new(u64::MAX, |_: &Operator| 0)
+                .with_cost_per_unit_function(|_: &Operator| u64::MAX),
+        );
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(metering);
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let bytecode = wat2wasm(
+            br#"
+            (module
+            (memory 1)
+            (func (export "fill") (param $length i32)
+                i32.const 0
+                i32.const 0
+                local.get $length
+                memory.fill))
+            "#,
+        )
+        .unwrap();
+        let module = Module::new(&store, bytecode).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        let fill: TypedFunction<i32, ()> = instance
+            .exports
+            .get_function("fill")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        // The charge of the bytes filled doesn't wrap around.
+        fill.call(&mut store, 2).unwrap();
+        assert_eq!(
+            get_remaining_points(&mut store, &instance),
+            MeteringPoints::Remaining(u64::MAX - 2 * MAX_COST_PER_UNIT)
+        );
+    }
 }
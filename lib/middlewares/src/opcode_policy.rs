@@ -0,0 +1,256 @@
+//! `opcode_policy` is a middleware for restricting the operators a
+//! WebAssembly module may use, for embedders with strict determinism
+//! or security policies.
+//!
+//! The operators of a forbidden [`OpcodeClass`] either make the
+//! compilation of the module fail, or make the instance trap when they
+//! are executed, depending on the [`PolicyAction`] of the class.
+
+use crate::metering::operator_name;
+use wasmer::wasmparser::Operator;
+use wasmer::{
+    FunctionMiddleware, LocalFunctionIndex, MiddlewareError, MiddlewareReaderState,
+    ModuleMiddleware,
+};
+
+/// A class of operators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpcodeClass {
+    /// The operators taking or returning floats, including the loads,
+    /// stores and conversions.
+    Float,
+
+    /// `memory.grow`.
+    MemoryGrow,
+
+    /// `call_indirect` and `return_call_indirect`.
+    IndirectCall,
+
+    /// The operators of the SIMD proposal.
+    Simd,
+
+    /// The operators of the threads proposal.
+    Atomic,
+
+    /// The operators of the bulk memory proposal.
+    BulkMemory,
+}
+
+impl OpcodeClass {
+    /// Whether `operator` belongs to the class.
+    pub fn matches(&self, operator: &Operator) -> bool {
+        match self {
+            Self::Float => {
+                let name = operator_name(operator);
+                name.contains("F32") || name.contains("F64")
+            }
+            Self::MemoryGrow => matches!(operator, Operator::MemoryGrow { .. }),
+            Self::IndirectCall => matches!(
+                operator,
+                Operator::CallIndirect { .. } | Operator::ReturnCallIndirect { .. }
+            ),
+            Self::Simd => {
+                let name = operator_name(operator);
+                ["V128", "I8x16", "I16x8", "I32x4", "I64x2", "F32x4", "F64x2"]
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+            }
+            Self::Atomic => {
+                let name = operator_name(operator);
+                ["MemoryAtomic", "AtomicFence", "I32Atomic", "I64Atomic"]
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+            }
+            Self::BulkMemory => matches!(
+                operator,
+                Operator::MemoryCopy { .. }
+                    | Operator::MemoryFill { .. }
+                    | Operator::MemoryInit { .. }
+                    | Operator::DataDrop { .. }
+                    | Operator::TableCopy { .. }
+                    | Operator::TableInit { .. }
+                    | Operator::ElemDrop { .. }
+            ),
+        }
+    }
+}
+
+/// What happens to the operators of a forbidden class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyAction {
+    /// The compilation of the module fails.
+    Reject,
+
+    /// The operators are replaced with `unreachable`, so the instance
+    /// traps with [`TrapCode::UnreachableCodeReached`] when they are
+    /// executed.
+    ///
+    /// [`TrapCode::UnreachableCodeReached`]: wasmer_types::TrapCode::UnreachableCodeReached
+    Trap,
+}
+
+/// The module-level opcode policy middleware.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::opcode_policy::{OpcodeClass, OpcodePolicy};
+///
+/// fn create_opcode_policy_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     let policy = OpcodePolicy::new()
+///         .reject(OpcodeClass::Float)
+///         .reject(OpcodeClass::IndirectCall)
+///         .trap(OpcodeClass::MemoryGrow);
+///     compiler_config.push_middleware(Arc::new(policy));
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OpcodePolicy {
+    /// The forbidden classes, and what happens to their operators.
+    rules: Vec<(OpcodeClass, PolicyAction)>,
+}
+
+/// The function-level opcode policy middleware.
+#[derive(Debug)]
+pub struct FunctionOpcodePolicy {
+    /// The forbidden classes, and what happens to their operators.
+    rules: Vec<(OpcodeClass, PolicyAction)>,
+}
+
+impl OpcodePolicy {
+    /// Creates an `OpcodePolicy` middleware, which allows every
+    /// operator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the compilation fail on the operators of `class`.
+    pub fn reject(self, class: OpcodeClass) -> Self {
+        self.forbid(class, PolicyAction::Reject)
+    }
+
+    /// Makes the instance trap on the operators of `class`.
+    pub fn trap(self, class: OpcodeClass) -> Self {
+        self.forbid(class, PolicyAction::Trap)
+    }
+
+    /// Forbids the operators of `class`, replacing the action set for
+    /// it before, if any.
+    pub fn forbid(mut self, class: OpcodeClass, action: PolicyAction) -> Self {
+        self.rules.retain(|(forbidden, _)| *forbidden != class);
+        self.rules.push((class, action));
+        self
+    }
+}
+
+impl ModuleMiddleware for OpcodePolicy {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionOpcodePolicy {
+            rules: self.rules.clone(),
+        })
+    }
+}
+
+impl FunctionMiddleware for FunctionOpcodePolicy {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        // An operator may belong to several classes, in which case
+        // rejecting it wins over trapping.
+        let mut action = None;
+        for (class, class_action) in &self.rules {
+            if class.matches(&operator) {
+                match class_action {
+                    PolicyAction::Reject => {
+                        return Err(MiddlewareError::new(
+                            "OpcodePolicy",
+                            format!(
+                                "the operator `{}` at offset {} isn't allowed ({:?})",
+                                operator_name(&operator),
+                                state.current_operator_offset(),
+                                class
+                            ),
+                        ));
+                    }
+                    PolicyAction::Trap => action = Some(PolicyAction::Trap),
+                }
+            }
+        }
+
+        match action {
+            // The operands left on the stack don't matter, as the code
+            // following `unreachable` isn't reachable.
+            Some(_) => state.push_operator(Operator::Unreachable),
+            None => state.push_operator(operator),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Instance, Module, Store,
+        TypedFunction,
+    };
+
+    const WAT: &[u8] = br#"
+        (module
+        (memory 1)
+        (func (export "grow") (param i32) (result i32)
+            local.get 0
+            memory.grow)
+        (func (export "half") (param i32) (result i32)
+            local.get 0
+            f32.convert_i32_s
+            f32.const 2
+            f32.div
+            i32.trunc_f32_s))
+        "#;
+
+    fn compile(policy: OpcodePolicy) -> (Store, Result<Module, String>) {
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(policy));
+        let store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, wat2wasm(WAT).unwrap()).map_err(|e| e.to_string());
+        (store, module)
+    }
+
+    #[test]
+    fn forbidden_operators_are_rejected() {
+        let (_, module) = compile(OpcodePolicy::new().reject(OpcodeClass::Float));
+        let error = module.unwrap_err();
+        assert!(error.contains("F32ConvertI32S"), "{}", error);
+        assert!(error.contains("Float"), "{}", error);
+    }
+
+    #[test]
+    fn forbidden_operators_trap() {
+        let (mut store, module) = compile(OpcodePolicy::new().trap(OpcodeClass::MemoryGrow));
+        let instance = Instance::new(&mut store, &module.unwrap(), &imports! {}).unwrap();
+        let grow: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("grow")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        let half: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("half")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        assert!(grow.call(&mut store, 1).is_err());
+        assert_eq!(half.call(&mut store, 7).unwrap(), 3);
+    }
+}
@@ -0,0 +1,755 @@
+//! `profiling` is a middleware for finding the hot functions of a
+//! WebAssembly instance, without a native profiler.
+//!
+//! It counts the calls of every function, per caller, and the
+//! operators each function executes itself, which stand for its self
+//! time: a middleware can't read a clock, and unlike time the count is
+//! deterministic. The profile is read with [`get_profile`], and can be
+//! written as folded stacks with [`Profile::write_folded`], the input of
+//! the flamegraph tools.
+//!
+//! The counters live in exported globals of the instance, named
+//! `wasmer_profiling_*`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use wasmer::wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
+use wasmer::{
+    AsStoreMut, ExportIndex, FunctionIndex, FunctionMiddleware, GlobalIndex, GlobalInit,
+    GlobalType, Instance, LocalFunctionIndex, MiddlewareError, MiddlewareLocals,
+    MiddlewareReaderState, ModuleInfo, ModuleMiddleware, Mutability, Type,
+};
+
+/// The number of callers of a function whose calls are counted
+/// separately; the calls from the other ones are counted together.
+pub const MAX_CALLERS: usize = 4;
+
+/// The caller of the functions called by the host.
+const HOST: i32 = -1;
+
+/// A caller slot that isn't used yet.
+const NO_CALLER: i32 = -2;
+
+/// The globals of a function: its calls, its cost, its calls from the
+/// untracked callers, then a caller and its calls for each slot.
+const GLOBALS_PER_FUNCTION: usize = 3 + 2 * MAX_CALLERS;
+
+/// The deepest stack written by [`Profile::write_folded`].
+const MAX_STACK_DEPTH: usize = 64;
+
+#[derive(Clone)]
+struct ProfilingGlobalIndexes {
+    /// The global holding the index of the running function, or
+    /// `HOST`.
+    current: GlobalIndex,
+
+    /// The first global of the first local function; the ones of the
+    /// other functions follow.
+    first: GlobalIndex,
+
+    /// The number of imported functions of the module.
+    num_imported_functions: usize,
+}
+
+impl ProfilingGlobalIndexes {
+    fn function_globals(&self, local_function_index: LocalFunctionIndex) -> FunctionGlobals {
+        FunctionGlobals(
+            self.first.as_u32() + (local_function_index.as_u32() * GLOBALS_PER_FUNCTION as u32),
+        )
+    }
+}
+
+impl fmt::Debug for ProfilingGlobalIndexes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProfilingGlobalIndexes")
+            .field("current", &self.current)
+            .field("first", &self.first)
+            .finish()
+    }
+}
+
+/// The indexes of the globals of a function.
+#[derive(Debug, Clone, Copy)]
+struct FunctionGlobals(u32);
+
+impl FunctionGlobals {
+    fn calls(self) -> u32 {
+        self.0
+    }
+
+    fn cost(self) -> u32 {
+        self.0 + 1
+    }
+
+    fn other_calls(self) -> u32 {
+        self.0 + 2
+    }
+
+    fn caller(self, slot: usize) -> u32 {
+        self.0 + 3 + 2 * slot as u32
+    }
+
+    fn caller_calls(self, slot: usize) -> u32 {
+        self.caller(slot) + 1
+    }
+}
+
+/// The module-level profiling middleware.
+///
+/// # Panic
+///
+/// An instance of `Profiling` should _not_ be shared among different
+/// modules, since it tracks module-specific information like the
+/// global indexes of the counters. Attempts to use a `Profiling`
+/// instance from multiple modules will result in a panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::Profiling;
+///
+/// fn create_profiling_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     compiler_config.push_middleware(Arc::new(Profiling::new()));
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Profiling {
+    /// The global indexes of the counters.
+    global_indexes: Mutex<Option<ProfilingGlobalIndexes>>,
+}
+
+/// The function-level profiling middleware.
+pub struct FunctionProfiling {
+    /// The index of the function in the module.
+    function_index: u32,
+
+    /// The globals of the function.
+    globals: FunctionGlobals,
+
+    /// The global holding the index of the running function.
+    current: u32,
+
+    /// The local saving the caller of the function.
+    caller_local: u32,
+
+    /// Whether the entry of the function has been instrumented.
+    entered: bool,
+
+    /// The depth of the block being fed.
+    depth: u32,
+
+    /// The operators executed since the last update of the cost.
+    accumulated_cost: u64,
+}
+
+impl Profiling {
+    /// Creates a `Profiling` middleware.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ModuleMiddleware for Profiling {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        let global_indexes = self.global_indexes.lock().unwrap().clone().unwrap();
+        Box::new(FunctionProfiling {
+            function_index: (global_indexes.num_imported_functions as u32)
+                + local_function_index.as_u32(),
+            globals: global_indexes.function_globals(local_function_index),
+            current: global_indexes.current.as_u32(),
+            caller_local: 0,
+            entered: false,
+            depth: 0,
+            accumulated_cost: 0,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut global_indexes = self.global_indexes.lock().unwrap();
+
+        if global_indexes.is_some() {
+            panic!("Profiling::transform_module_info: Attempting to use a `Profiling` middleware from multiple modules.");
+        }
+
+        let current = push_global(
+            module_info,
+            Type::I32,
+            GlobalInit::I32Const(HOST),
+            "wasmer_profiling_current".to_string(),
+        );
+
+        let num_imported_functions = module_info.num_imported_functions;
+        let mut first = None;
+        for index in num_imported_functions..module_info.functions.len() {
+            let calls = push_global(
+                module_info,
+                Type::I64,
+                GlobalInit::I64Const(0),
+                format!("wasmer_profiling_calls_{}", index),
+            );
+            first.get_or_insert(calls);
+            push_global(
+                module_info,
+                Type::I64,
+                GlobalInit::I64Const(0),
+                format!("wasmer_profiling_cost_{}", index),
+            );
+            push_global(
+                module_info,
+                Type::I64,
+                GlobalInit::I64Const(0),
+                format!("wasmer_profiling_other_calls_{}", index),
+            );
+            for slot in 0..MAX_CALLERS {
+                push_global(
+                    module_info,
+                    Type::I32,
+                    GlobalInit::I32Const(NO_CALLER),
+                    format!("wasmer_profiling_caller_{}_{}", index, slot),
+                );
+                push_global(
+                    module_info,
+                    Type::I64,
+                    GlobalInit::I64Const(0),
+                    format!("wasmer_profiling_caller_calls_{}_{}", index, slot),
+                );
+            }
+        }
+
+        *global_indexes = Some(ProfilingGlobalIndexes {
+            current,
+            // Without local functions, the first global is never used.
+            first: first.unwrap_or(current),
+            num_imported_functions,
+        });
+    }
+}
+
+/// Appends an exported global and initializes it.
+fn push_global(
+    module_info: &mut ModuleInfo,
+    ty: Type,
+    init: GlobalInit,
+    name: String,
+) -> GlobalIndex {
+    let index = module_info
+        .globals
+        .push(GlobalType::new(ty, Mutability::Var));
+    module_info.global_initializers.push(init);
+    module_info.exports.insert(name, ExportIndex::Global(index));
+    index
+}
+
+impl fmt::Debug for FunctionProfiling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionProfiling")
+            .field("function_index", &self.function_index)
+            .field("globals", &self.globals)
+            .finish()
+    }
+}
+
+impl FunctionProfiling {
+    /// Counts the call, per caller, and makes the function the running
+    /// one.
+    fn enter(&self, state: &mut MiddlewareReaderState<'_>) {
+        state.extend(&[
+            // caller_local = globals[current];
+            Operator::GlobalGet {
+                global_index: self.current,
+            },
+            Operator::LocalSet {
+                local_index: self.caller_local,
+            },
+        ]);
+        increment(state, self.globals.calls());
+
+        state.push_operator(Operator::Block {
+            ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+        });
+        for slot in 0..MAX_CALLERS {
+            let caller = self.globals.caller(slot);
+            state.extend(&[
+                // if globals[caller] == caller_local || globals[caller] == NO_CALLER {
+                //     globals[caller] = caller_local;
+                //     globals[caller_calls] += 1;
+                //     break;
+                // }
+                Operator::GlobalGet {
+                    global_index: caller,
+                },
+                Operator::LocalGet {
+                    local_index: self.caller_local,
+                },
+                Operator::I32Eq,
+                Operator::GlobalGet {
+                    global_index: caller,
+                },
+                Operator::I32Const { value: NO_CALLER },
+                Operator::I32Eq,
+                Operator::I32Or,
+                Operator::If {
+                    ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+                },
+                Operator::LocalGet {
+                    local_index: self.caller_local,
+                },
+                Operator::GlobalSet {
+                    global_index: caller,
+                },
+            ]);
+            increment(state, self.globals.caller_calls(slot));
+            state.extend(&[Operator::Br { relative_depth: 1 }, Operator::End]);
+        }
+        // All the slots are used by other callers.
+        increment(state, self.globals.other_calls());
+        state.push_operator(Operator::End);
+
+        state.extend(&[
+            // globals[current] = function_index;
+            Operator::I32Const {
+                value: self.function_index as i32,
+            },
+            Operator::GlobalSet {
+                global_index: self.current,
+            },
+        ]);
+    }
+
+    /// Gives the running function back to the caller.
+    fn leave(&self, state: &mut MiddlewareReaderState<'_>) {
+        state.extend(&[
+            // globals[current] = caller_local;
+            Operator::LocalGet {
+                local_index: self.caller_local,
+            },
+            Operator::GlobalSet {
+                global_index: self.current,
+            },
+        ]);
+    }
+
+    /// Adds the operators executed since the last update to the cost.
+    fn update_cost(&mut self, state: &mut MiddlewareReaderState<'_>) {
+        if self.accumulated_cost > 0 {
+            state.extend(&[
+                // globals[cost] += accumulated_cost;
+                Operator::GlobalGet {
+                    global_index: self.globals.cost(),
+                },
+                Operator::I64Const {
+                    value: self.accumulated_cost as i64,
+                },
+                Operator::I64Add,
+                Operator::GlobalSet {
+                    global_index: self.globals.cost(),
+                },
+            ]);
+            self.accumulated_cost = 0;
+        }
+    }
+}
+
+/// Pushes the operators adding 1 to the `i64` global `global_index`.
+fn increment(state: &mut MiddlewareReaderState<'_>, global_index: u32) {
+    state.extend(&[
+        Operator::GlobalGet { global_index },
+        Operator::I64Const { value: 1 },
+        Operator::I64Add,
+        Operator::GlobalSet { global_index },
+    ]);
+}
+
+impl FunctionMiddleware for FunctionProfiling {
+    fn feed_locals(&mut self, locals: &mut MiddlewareLocals) -> Result<(), MiddlewareError> {
+        self.caller_local = locals.add(1, WpType::I32)?;
+        Ok(())
+    }
+
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if !self.entered {
+            self.entered = true;
+            self.enter(state);
+        }
+
+        self.accumulated_cost += 1;
+
+        match operator {
+            Operator::Block { .. } | Operator::If { .. } | Operator::Try { .. } => {
+                self.depth += 1;
+            }
+            // Loop headers are branch targets.
+            Operator::Loop { .. } => {
+                self.update_cost(state);
+                self.depth += 1;
+            }
+            Operator::Return => {
+                self.update_cost(state);
+                self.leave(state);
+            }
+            // The end of the function.
+            Operator::End if self.depth == 0 => {
+                self.update_cost(state);
+                self.leave(state);
+            }
+            Operator::End | Operator::Delegate { .. } => {
+                self.update_cost(state);
+                self.depth -= 1;
+            }
+            // Other sources and targets of branches, and calls, which
+            // run other functions.
+            Operator::Else
+            | Operator::Br { .. }
+            | Operator::BrIf { .. }
+            | Operator::BrTable { .. }
+            | Operator::Call { .. }
+            | Operator::CallIndirect { .. } => {
+                self.update_cost(state);
+            }
+            _ => {}
+        }
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// The profile of a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionProfile {
+    /// The index of the function in the module.
+    pub index: FunctionIndex,
+
+    /// The name of the function, from the name section of the module.
+    pub name: String,
+
+    /// The number of calls of the function.
+    pub calls: u64,
+
+    /// The number of operators executed by the function itself.
+    pub self_cost: u64,
+
+    /// The number of calls per caller, `None` standing for the host.
+    pub callers: Vec<(Option<FunctionIndex>, u64)>,
+
+    /// The number of calls from the callers beyond the first
+    /// [`MAX_CALLERS`] ones.
+    pub other_calls: u64,
+}
+
+/// The profile of an [`Instance`][wasmer::Instance], see [`get_profile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// The profiles of the functions defined by the module.
+    pub functions: Vec<FunctionProfile>,
+}
+
+impl Profile {
+    /// Writes the profile as folded stacks, one line per stack with the
+    /// operators executed by the last function.
+    ///
+    /// Only the callers of each function are known, so the stacks are
+    /// rebuilt like `gprof` does, assuming that a call of a function
+    /// costs the same whatever its caller. They stop at the recursive
+    /// calls and at the untracked callers.
+    pub fn write_folded(&self, out: &mut impl Write) -> io::Result<()> {
+        let functions: HashMap<FunctionIndex, &FunctionProfile> = self
+            .functions
+            .iter()
+            .map(|function| (function.index, function))
+            .collect();
+
+        let mut stacks = BTreeMap::new();
+        for function in &self.functions {
+            if function.self_cost > 0 {
+                let mut stack = vec![function];
+                attribute_cost(
+                    &functions,
+                    &mut stack,
+                    function.self_cost as f64,
+                    &mut stacks,
+                );
+            }
+        }
+
+        for (stack, cost) in stacks {
+            let cost = cost.round() as u64;
+            if cost > 0 {
+                writeln!(out, "{} {}", stack, cost)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits `cost`, spent by the function at the bottom of `stack`
+/// (which starts with the callee), between the callers of the
+/// function at its top, and records the stacks in `stacks`.
+fn attribute_cost<'p>(
+    functions: &HashMap<FunctionIndex, &'p FunctionProfile>,
+    stack: &mut Vec<&'p FunctionProfile>,
+    cost: f64,
+    stacks: &mut BTreeMap<String, f64>,
+) {
+    let function = *stack.last().unwrap();
+    if function.calls == 0 {
+        record_stack(stacks, stack, cost);
+        return;
+    }
+
+    let share = |calls: u64| cost * calls as f64 / function.calls as f64;
+    for (caller, calls) in &function.callers {
+        let cost = share(*calls);
+        let caller = caller.and_then(|caller| functions.get(&caller)).copied();
+        match caller {
+            Some(caller)
+                if stack.len() < MAX_STACK_DEPTH
+                    && cost >= 1.
+                    && !stack.iter().any(|frame| frame.index == caller.index) =>
+            {
+                stack.push(caller);
+                attribute_cost(functions, stack, cost, stacks);
+                stack.pop();
+            }
+            _ => record_stack(stacks, stack, cost),
+        }
+    }
+    if function.other_calls > 0 {
+        record_stack(stacks, stack, share(function.other_calls));
+    }
+}
+
+/// Adds `cost` to `stack`, which starts with the callee.
+fn record_stack(stacks: &mut BTreeMap<String, f64>, stack: &[&FunctionProfile], cost: f64) {
+    let frames: Vec<String> = stack
+        .iter()
+        .rev()
+        .map(|function| function.name.replace(';', ":"))
+        .collect();
+    *stacks.entry(frames.join(";")).or_insert(0.) += cost;
+}
+
+fn get_global<T: TryFrom<wasmer::Value>>(
+    ctx: &mut impl AsStoreMut,
+    instance: &Instance,
+    name: &str,
+) -> T {
+    instance
+        .exports
+        .get_global(name)
+        .unwrap_or_else(|_| panic!("Can't get `{}` from Instance", name))
+        .get(ctx)
+        .try_into()
+        .unwrap_or_else(|_| panic!("`{}` from Instance has wrong type", name))
+}
+
+/// Get the profile of an [`Instance`][wasmer::Instance].
+///
+/// Note: This can be used in a headless engine after an ahead-of-time
+/// compilation as all required state lives in the instance.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Profiling`] middleware at compile time, otherwise this will
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::fs::File;
+/// use std::io;
+/// use wasmer::{AsStoreMut, Instance};
+/// use wasmer_middlewares::profiling::get_profile;
+///
+/// fn save_profile(store: &mut impl AsStoreMut, instance: &Instance) -> io::Result<()> {
+///     let mut file = File::create("profile.folded")?;
+///     get_profile(store, instance).write_folded(&mut file)
+/// }
+/// ```
+pub fn get_profile(ctx: &mut impl AsStoreMut, instance: &Instance) -> Profile {
+    let info = instance.module().info();
+    let mut functions = vec![];
+    for index in info.num_imported_functions..info.functions.len() {
+        let mut callers = vec![];
+        for slot in 0..MAX_CALLERS {
+            let caller: i32 = get_global(
+                ctx,
+                instance,
+                &format!("wasmer_profiling_caller_{}_{}", index, slot),
+            );
+            if caller == NO_CALLER {
+                break;
+            }
+            let calls: i64 = get_global(
+                ctx,
+                instance,
+                &format!("wasmer_profiling_caller_calls_{}_{}", index, slot),
+            );
+            let caller = if caller == HOST {
+                None
+            } else {
+                Some(FunctionIndex::from_u32(caller as u32))
+            };
+            callers.push((caller, calls as u64));
+        }
+
+        let function_index = FunctionIndex::from_u32(index as u32);
+        let calls: i64 = get_global(ctx, instance, &format!("wasmer_profiling_calls_{}", index));
+        let self_cost: i64 = get_global(ctx, instance, &format!("wasmer_profiling_cost_{}", index));
+        let other_calls: i64 = get_global(
+            ctx,
+            instance,
+            &format!("wasmer_profiling_other_calls_{}", index),
+        );
+        functions.push(FunctionProfile {
+            index: function_index,
+            name: info
+                .function_names
+                .get(&function_index)
+                .cloned()
+                .unwrap_or_else(|| format!("wasm-function[{}]", index)),
+            calls: calls as u64,
+            self_cost: self_cost as u64,
+            callers,
+            other_calls: other_calls as u64,
+        });
+    }
+
+    Profile { functions }
+}
+
+/// Reset the profile of an [`Instance`][wasmer::Instance].
+///
+/// A trap leaves the function it stopped as the running one, so that
+/// the calls made afterwards by the host are attributed to it: the
+/// profile should be reset before being used again.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Profiling`] middleware at compile time, otherwise this will
+/// panic.
+pub fn reset_profile(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    let mut set = |name: String, value: wasmer::Value| {
+        instance
+            .exports
+            .get_global(&name)
+            .unwrap_or_else(|_| panic!("Can't get `{}` from Instance", name))
+            .set(ctx, value)
+            .unwrap_or_else(|_| panic!("Can't set `{}` in Instance", name));
+    };
+
+    set("wasmer_profiling_current".to_string(), HOST.into());
+    let info = instance.module().info();
+    for index in info.num_imported_functions..info.functions.len() {
+        set(format!("wasmer_profiling_calls_{}", index), 0i64.into());
+        set(format!("wasmer_profiling_cost_{}", index), 0i64.into());
+        set(
+            format!("wasmer_profiling_other_calls_{}", index),
+            0i64.into(),
+        );
+        for slot in 0..MAX_CALLERS {
+            set(
+                format!("wasmer_profiling_caller_{}_{}", index, slot),
+                NO_CALLER.into(),
+            );
+            set(
+                format!("wasmer_profiling_caller_calls_{}_{}", index, slot),
+                0i64.into(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    #[test]
+    fn calls_are_counted_per_caller() {
+        let bytecode = wat2wasm(
+            br#"
+            (module
+            (func $leaf (param i32) (result i32)
+                local.get 0
+                i32.const 1
+                i32.add)
+            (func $middle (param i32) (result i32)
+                local.get 0
+                call $leaf
+                call $leaf)
+            (func $main (export "main") (param i32) (result i32)
+                local.get 0
+                call $middle
+                call $leaf))
+            "#,
+        )
+        .unwrap();
+
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(Profiling::new()));
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+
+        let main: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("main")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        assert_eq!(main.call(&mut store, 1).unwrap(), 4);
+        assert_eq!(main.call(&mut store, 1).unwrap(), 4);
+
+        let profile = get_profile(&mut store, &instance);
+        assert_eq!(profile.functions.len(), 3);
+        let (leaf, middle, main) = (
+            &profile.functions[0],
+            &profile.functions[1],
+            &profile.functions[2],
+        );
+        assert_eq!(leaf.name, "leaf");
+        assert_eq!(leaf.calls, 6);
+        assert_eq!(
+            leaf.callers,
+            vec![(Some(middle.index), 4), (Some(main.index), 2)]
+        );
+        assert_eq!(middle.callers, vec![(Some(main.index), 2)]);
+        assert_eq!(main.callers, vec![(None, 2)]);
+        // Each call of `leaf` runs its 4 operators, `end` included.
+        assert_eq!(leaf.self_cost, 24);
+
+        let mut folded = vec![];
+        profile.write_folded(&mut folded).unwrap();
+        let folded = String::from_utf8(folded).unwrap();
+        assert!(folded.lines().any(|line| line == "main;middle;leaf 16"));
+        assert!(folded.lines().any(|line| line == "main;leaf 8"));
+
+        reset_profile(&mut store, &instance);
+        let profile = get_profile(&mut store, &instance);
+        assert!(profile
+            .functions
+            .iter()
+            .all(|function| function.calls == 0 && function.callers.is_empty()));
+    }
+}
@@ -0,0 +1,601 @@
+//! `watchpoint` is a middleware for stopping the execution of a
+//! WebAssembly instance when it reads or writes given ranges of its
+//! linear memory, e.g. to find what corrupts the memory of a guest.
+//!
+//! The watchpoints are set and cleared at runtime with
+//! [`set_watchpoint`] and [`clear_watchpoint`], in [`MAX_WATCHPOINTS`]
+//! slots. When a watchpoint is hit, the instance traps with an
+//! `unreachable` trap, before the access is made, and the access is
+//! recorded in the instance; it can be retrieved with
+//! [`get_watchpoint_hit`].
+//!
+//! The accesses are checked like with the
+//! [`memory_trace`](crate::memory_trace) middleware: the SIMD stores,
+//! the atomic operators and the bulk memory operators aren't.
+
+use crate::memory_trace::{operator_access, MemoryAccess, MemoryAccessKind, VALUE_TYPES};
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::ops::Range;
+use std::sync::Mutex;
+use wasmer::wasmparser::{
+    MemoryImmediate, Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType,
+};
+use wasmer::{
+    AsStoreMut, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareLocals, MiddlewareReaderState, ModuleInfo,
+    ModuleMiddleware, Mutability, Type, Value,
+};
+
+/// The number of watchpoints that can be set at the same time.
+pub const MAX_WATCHPOINTS: usize = 4;
+
+/// The bits of the kinds of accesses watched by a slot.
+const WATCH_LOAD: i32 = 1;
+const WATCH_STORE: i32 = 2;
+
+/// The globals of the watchpoints: whether one is set, the start, the
+/// end and the kinds of each slot, then the slot, the address, the
+/// size, the kind and the offset of the hit.
+#[derive(Debug, Clone, Copy)]
+struct WatchpointGlobalIndexes(u32);
+
+impl WatchpointGlobalIndexes {
+    fn armed(self) -> u32 {
+        self.0
+    }
+
+    fn start(self, slot: usize) -> u32 {
+        self.0 + 1 + 3 * slot as u32
+    }
+
+    fn end(self, slot: usize) -> u32 {
+        self.start(slot) + 1
+    }
+
+    fn kinds(self, slot: usize) -> u32 {
+        self.start(slot) + 2
+    }
+
+    fn hit_slot(self) -> u32 {
+        self.start(MAX_WATCHPOINTS)
+    }
+
+    fn hit_address(self) -> u32 {
+        self.hit_slot() + 1
+    }
+
+    fn hit_size(self) -> u32 {
+        self.hit_slot() + 2
+    }
+
+    fn hit_kind(self) -> u32 {
+        self.hit_slot() + 3
+    }
+
+    fn hit_offset(self) -> u32 {
+        self.hit_slot() + 4
+    }
+}
+
+/// The module-level watchpoint middleware.
+///
+/// # Panic
+///
+/// An instance of `Watchpoints` should _not_ be shared among
+/// different modules, since it tracks module-specific information
+/// like the global indexes to store the watchpoints. Attempts to use
+/// a `Watchpoints` instance from multiple modules will result in a
+/// panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::Watchpoints;
+///
+/// fn create_watchpoint_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     compiler_config.push_middleware(Arc::new(Watchpoints::new()));
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Watchpoints {
+    /// The global indexes for watchpoint state.
+    global_indexes: Mutex<Option<WatchpointGlobalIndexes>>,
+}
+
+/// The function-level watchpoint middleware.
+pub struct FunctionWatchpoints {
+    /// The global indexes for watchpoint state.
+    global_indexes: WatchpointGlobalIndexes,
+
+    /// The local saving the address of an access, offset included.
+    address_local: u32,
+
+    /// The locals saving the value of a store while the access is
+    /// checked, per type of value.
+    value_locals: [u32; 4],
+}
+
+impl Watchpoints {
+    /// Creates a `Watchpoints` middleware.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ModuleMiddleware for Watchpoints {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionWatchpoints {
+            global_indexes: self.global_indexes.lock().unwrap().unwrap(),
+            address_local: 0,
+            value_locals: [0; 4],
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut global_indexes = self.global_indexes.lock().unwrap();
+
+        if global_indexes.is_some() {
+            panic!("Watchpoints::transform_module_info: Attempting to use a `Watchpoints` middleware from multiple modules.");
+        }
+
+        let mut push_global = |ty: Type, init: GlobalInit, name: String| {
+            let index = module_info
+                .globals
+                .push(GlobalType::new(ty, Mutability::Var));
+            module_info.global_initializers.push(init);
+            module_info.exports.insert(name, ExportIndex::Global(index));
+            index.as_u32()
+        };
+
+        let first = push_global(
+            Type::I32,
+            GlobalInit::I32Const(0),
+            "wasmer_watchpoints_armed".to_string(),
+        );
+        for slot in 0..MAX_WATCHPOINTS {
+            push_global(
+                Type::I64,
+                GlobalInit::I64Const(0),
+                format!("wasmer_watchpoint_start_{}", slot),
+            );
+            push_global(
+                Type::I64,
+                GlobalInit::I64Const(0),
+                format!("wasmer_watchpoint_end_{}", slot),
+            );
+            push_global(
+                Type::I32,
+                GlobalInit::I32Const(0),
+                format!("wasmer_watchpoint_kinds_{}", slot),
+            );
+        }
+        for (ty, init, name) in [
+            (Type::I32, GlobalInit::I32Const(-1), "slot"),
+            (Type::I64, GlobalInit::I64Const(0), "address"),
+            (Type::I32, GlobalInit::I32Const(0), "size"),
+            (Type::I32, GlobalInit::I32Const(0), "kind"),
+            (Type::I64, GlobalInit::I64Const(0), "offset"),
+        ] {
+            push_global(ty, init, format!("wasmer_watchpoint_hit_{}", name));
+        }
+
+        *global_indexes = Some(WatchpointGlobalIndexes(first));
+    }
+}
+
+impl fmt::Debug for FunctionWatchpoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionWatchpoints")
+            .field("global_indexes", &self.global_indexes)
+            .finish()
+    }
+}
+
+impl FunctionWatchpoints {
+    /// Checks the access at the address on the top of the stack against
+    /// the watchpoints, if any is set.
+    fn check(
+        &self,
+        memarg: &MemoryImmediate,
+        size: i32,
+        kind: i32,
+        offset: usize,
+        state: &mut MiddlewareReaderState<'_>,
+    ) {
+        let globals = self.global_indexes;
+        let address_local = self.address_local;
+
+        state.extend(&[
+            // address = u64(address) + offset;
+            // if globals[armed] {
+            //     for slot in watchpoints { ... }
+            // }
+            Operator::I64ExtendI32U,
+            Operator::I64Const {
+                value: memarg.offset as i64,
+            },
+            Operator::I64Add,
+            Operator::LocalSet {
+                local_index: address_local,
+            },
+            Operator::GlobalGet {
+                global_index: globals.armed(),
+            },
+            Operator::If {
+                ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+            },
+        ]);
+
+        for slot in 0..MAX_WATCHPOINTS {
+            state.extend(&[
+                // if address < end && address + size > start && (kinds & kind) != 0 {
+                //     record the hit;
+                //     throw();
+                // }
+                Operator::LocalGet {
+                    local_index: address_local,
+                },
+                Operator::GlobalGet {
+                    global_index: globals.end(slot),
+                },
+                Operator::I64LtU,
+                Operator::LocalGet {
+                    local_index: address_local,
+                },
+                Operator::I64Const { value: size as i64 },
+                Operator::I64Add,
+                Operator::GlobalGet {
+                    global_index: globals.start(slot),
+                },
+                Operator::I64GtU,
+                Operator::I32And,
+                Operator::GlobalGet {
+                    global_index: globals.kinds(slot),
+                },
+                Operator::I32Const { value: kind },
+                Operator::I32And,
+                Operator::I32Eqz,
+                Operator::I32Eqz,
+                Operator::I32And,
+                Operator::If {
+                    ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+                },
+                Operator::I32Const { value: slot as i32 },
+                Operator::GlobalSet {
+                    global_index: globals.hit_slot(),
+                },
+                Operator::LocalGet {
+                    local_index: address_local,
+                },
+                Operator::GlobalSet {
+                    global_index: globals.hit_address(),
+                },
+                Operator::I32Const { value: size },
+                Operator::GlobalSet {
+                    global_index: globals.hit_size(),
+                },
+                Operator::I32Const { value: kind },
+                Operator::GlobalSet {
+                    global_index: globals.hit_kind(),
+                },
+                Operator::I64Const {
+                    value: offset as i64,
+                },
+                Operator::GlobalSet {
+                    global_index: globals.hit_offset(),
+                },
+                Operator::Unreachable,
+                Operator::End,
+            ]);
+        }
+
+        state.extend(&[
+            Operator::End,
+            // The address of the access is given back to it without its
+            // offset, which the access adds again.
+            Operator::LocalGet {
+                local_index: address_local,
+            },
+            Operator::I64Const {
+                value: memarg.offset as i64,
+            },
+            Operator::I64Sub,
+            Operator::I32WrapI64,
+        ]);
+    }
+}
+
+impl FunctionMiddleware for FunctionWatchpoints {
+    fn feed_locals(&mut self, locals: &mut MiddlewareLocals) -> Result<(), MiddlewareError> {
+        self.address_local = locals.add(1, WpType::I64)?;
+        for (value_local, ty) in self.value_locals.iter_mut().zip(VALUE_TYPES.iter()) {
+            *value_local = locals.add(1, *ty)?;
+        }
+        Ok(())
+    }
+
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        let offset = state.current_operator_offset();
+        match operator_access(&operator) {
+            Some((memarg, size, None)) => {
+                self.check(&memarg, size as i32, WATCH_LOAD, offset, state)
+            }
+            Some((memarg, size, Some(value_type))) => {
+                let value_local = self.value_locals[value_type];
+                state.push_operator(Operator::LocalSet {
+                    local_index: value_local,
+                });
+                self.check(&memarg, size as i32, WATCH_STORE, offset, state);
+                state.push_operator(Operator::LocalGet {
+                    local_index: value_local,
+                });
+            }
+            None => {}
+        }
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// The kinds of accesses a watchpoint stops on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    /// Stop on the reads of the memory.
+    Read,
+    /// Stop on the writes to the memory.
+    Write,
+    /// Stop on every access.
+    ReadWrite,
+}
+
+/// A watchpoint hit by an [`Instance`][wasmer::Instance], see
+/// [`get_watchpoint_hit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchpointHit {
+    /// The slot of the watchpoint.
+    pub slot: usize,
+
+    /// The access that hit the watchpoint, which hasn't been made.
+    pub access: MemoryAccess,
+
+    /// The offset in the Wasm binary of the operator making the access.
+    pub offset: usize,
+}
+
+fn get_global<T: TryFrom<Value>>(ctx: &mut impl AsStoreMut, instance: &Instance, name: &str) -> T {
+    instance
+        .exports
+        .get_global(name)
+        .unwrap_or_else(|_| panic!("Can't get `{}` from Instance", name))
+        .get(ctx)
+        .try_into()
+        .unwrap_or_else(|_| panic!("`{}` from Instance has wrong type", name))
+}
+
+fn set_global(ctx: &mut impl AsStoreMut, instance: &Instance, name: &str, value: Value) {
+    instance
+        .exports
+        .get_global(name)
+        .unwrap_or_else(|_| panic!("Can't get `{}` from Instance", name))
+        .set(ctx, value)
+        .unwrap_or_else(|_| panic!("Can't set `{}` in Instance", name));
+}
+
+/// Arms or disarms the watchpoints, depending on whether any slot is
+/// used.
+fn update_armed(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    let armed = (0..MAX_WATCHPOINTS).any(|slot| {
+        get_global::<i32>(ctx, instance, &format!("wasmer_watchpoint_kinds_{}", slot)) != 0
+    });
+    set_global(
+        ctx,
+        instance,
+        "wasmer_watchpoints_armed",
+        (armed as i32).into(),
+    );
+}
+
+/// Set a watchpoint of an [`Instance`][wasmer::Instance] in `slot`,
+/// stopping on the accesses of kind `kind` to the bytes in `range`.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Watchpoints`] middleware at compile time, and `slot` must be
+/// lower than [`MAX_WATCHPOINTS`], otherwise this will panic.
+///
+/// # Example
+///
+/// ```rust
+/// use wasmer::{AsStoreMut, Instance};
+/// use wasmer_middlewares::watchpoint::{set_watchpoint, WatchKind};
+///
+/// fn watch_header(store: &mut impl AsStoreMut, instance: &Instance) {
+///     // Stop when something writes to the first 16 bytes.
+///     set_watchpoint(store, instance, 0, 0..16, WatchKind::Write);
+/// }
+/// ```
+pub fn set_watchpoint(
+    ctx: &mut impl AsStoreMut,
+    instance: &Instance,
+    slot: usize,
+    range: Range<u64>,
+    kind: WatchKind,
+) {
+    assert!(slot < MAX_WATCHPOINTS, "invalid watchpoint slot {}", slot);
+
+    let kinds = match kind {
+        WatchKind::Read => WATCH_LOAD,
+        WatchKind::Write => WATCH_STORE,
+        WatchKind::ReadWrite => WATCH_LOAD | WATCH_STORE,
+    };
+    set_global(
+        ctx,
+        instance,
+        &format!("wasmer_watchpoint_start_{}", slot),
+        (range.start as i64).into(),
+    );
+    set_global(
+        ctx,
+        instance,
+        &format!("wasmer_watchpoint_end_{}", slot),
+        (range.end as i64).into(),
+    );
+    set_global(
+        ctx,
+        instance,
+        &format!("wasmer_watchpoint_kinds_{}", slot),
+        kinds.into(),
+    );
+    update_armed(ctx, instance);
+}
+
+/// Clear the watchpoint of an [`Instance`][wasmer::Instance] in
+/// `slot`.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Watchpoints`] middleware at compile time, and `slot` must be
+/// lower than [`MAX_WATCHPOINTS`], otherwise this will panic.
+pub fn clear_watchpoint(ctx: &mut impl AsStoreMut, instance: &Instance, slot: usize) {
+    assert!(slot < MAX_WATCHPOINTS, "invalid watchpoint slot {}", slot);
+
+    set_global(
+        ctx,
+        instance,
+        &format!("wasmer_watchpoint_kinds_{}", slot),
+        0i32.into(),
+    );
+    update_armed(ctx, instance);
+}
+
+/// Get the last watchpoint hit by an [`Instance`][wasmer::Instance],
+/// if any.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Watchpoints`] middleware at compile time, otherwise this
+/// will panic.
+pub fn get_watchpoint_hit(ctx: &mut impl AsStoreMut, instance: &Instance) -> Option<WatchpointHit> {
+    let slot: i32 = get_global(ctx, instance, "wasmer_watchpoint_hit_slot");
+    if slot < 0 {
+        return None;
+    }
+
+    let address: i64 = get_global(ctx, instance, "wasmer_watchpoint_hit_address");
+    let size: i32 = get_global(ctx, instance, "wasmer_watchpoint_hit_size");
+    let kind: i32 = get_global(ctx, instance, "wasmer_watchpoint_hit_kind");
+    let offset: i64 = get_global(ctx, instance, "wasmer_watchpoint_hit_offset");
+    Some(WatchpointHit {
+        slot: slot as usize,
+        access: MemoryAccess {
+            address: address as u64,
+            size: size as u32,
+            kind: if kind == WATCH_STORE {
+                MemoryAccessKind::Store
+            } else {
+                MemoryAccessKind::Load
+            },
+        },
+        offset: offset as usize,
+    })
+}
+
+/// Forget the last watchpoint hit by an
+/// [`Instance`][wasmer::Instance], so that [`get_watchpoint_hit`]
+/// returns `None` until another watchpoint is hit.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Watchpoints`] middleware at compile time, otherwise this
+/// will panic.
+pub fn clear_watchpoint_hit(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    set_global(ctx, instance, "wasmer_watchpoint_hit_slot", (-1i32).into());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    #[test]
+    fn watchpoint_is_hit() {
+        let bytecode = wat2wasm(
+            br#"
+            (module
+            (memory 1)
+            (func (export "store") (param $address i32) (param $value i32)
+                local.get $address
+                local.get $value
+                i32.store offset=4)
+            (func (export "load") (param $address i32) (result i32)
+                local.get $address
+                i32.load offset=4))
+            "#,
+        )
+        .unwrap();
+
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(Watchpoints::new()));
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+
+        let store_i32: TypedFunction<(i32, i32), ()> = instance
+            .exports
+            .get_function("store")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        let load_i32: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("load")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+
+        // Watch the writes to the bytes 100 and 101.
+        set_watchpoint(&mut store, &instance, 1, 100..102, WatchKind::Write);
+        store_i32.call(&mut store, 90, 7).unwrap();
+        store_i32.call(&mut store, 98, 7).unwrap();
+        assert_eq!(load_i32.call(&mut store, 98).unwrap(), 7);
+        assert_eq!(get_watchpoint_hit(&mut store, &instance), None);
+
+        // Bytes 99 to 102.
+        assert!(store_i32.call(&mut store, 95, 42).is_err());
+        let hit = get_watchpoint_hit(&mut store, &instance).unwrap();
+        assert_eq!(hit.slot, 1);
+        assert_eq!(
+            hit.access,
+            MemoryAccess {
+                address: 99,
+                size: 4,
+                kind: MemoryAccessKind::Store,
+            }
+        );
+        // The store hasn't been made.
+        assert_eq!(load_i32.call(&mut store, 94).unwrap(), 0);
+
+        clear_watchpoint_hit(&mut store, &instance);
+        clear_watchpoint(&mut store, &instance, 1);
+        store_i32.call(&mut store, 95, 42).unwrap();
+        assert_eq!(get_watchpoint_hit(&mut store, &instance), None);
+    }
+}
@@ -0,0 +1,550 @@
+//! `yielding` is a middleware for running many WebAssembly instances
+//! on a few threads, by making their calls give the thread back to
+//! the host from time to time, and resume later where they stopped.
+//!
+//! The middleware checks at the entry of every function and at every
+//! iteration of every loop whether the call should yield: once it has
+//! run a quantum of checks, set with [`set_yield_quantum`], or when
+//! another thread requests it with a [`YieldHandle`]. The call then
+//! calls a host function, which suspends the stack of the call if it
+//! runs in a [`YieldingCall`], and does nothing otherwise. Like the
+//! interrupt flag, the yield request is loaded again, after a fence, at
+//! every check.
+//!
+//! ```rust
+//! use wasmer::{Instance, Store, TypedFunction};
+//! use wasmer_middlewares::yielding::{enable_yielding, YieldingCall};
+//!
+//! fn run_in_slices(mut store: Store, instance: &Instance, run: TypedFunction<(), ()>) {
+//!     enable_yielding(&mut store, instance);
+//!     let mut call = YieldingCall::new(move || run.call(&mut store));
+//!     while call.resume().is_none() {
+//!         // Run the other calls.
+//!     }
+//! }
+//! ```
+
+use corosensei::{Coroutine, CoroutineResult, Yielder};
+use std::cell::Cell;
+use std::fmt;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+use wasmer::vm::VMSharedGlobalDefinition;
+use wasmer::wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType};
+use wasmer::{
+    AsStoreMut, AsStoreRef, ExportIndex, Function, FunctionMiddleware, FunctionType, GlobalIndex,
+    GlobalInit, GlobalType, Instance, LocalFunctionIndex, MiddlewareError, MiddlewareReaderState,
+    ModuleInfo, ModuleMiddleware, Mutability, RuntimeError, SignatureIndex, TableIndex, TableType,
+    Type, Value,
+};
+
+#[derive(Debug, Clone)]
+struct YieldingIndexes {
+    /// The global indicating whether the yield function is set.
+    enabled: GlobalIndex,
+
+    /// The global holding the checks left before the next yield.
+    fuel: GlobalIndex,
+
+    /// The global holding the checks between two yields.
+    quantum: GlobalIndex,
+
+    /// The global set when another thread requests a yield.
+    requested: GlobalIndex,
+
+    /// The table holding the yield function.
+    table: TableIndex,
+
+    /// The signature of the yield function.
+    signature: SignatureIndex,
+}
+
+/// The module-level yielding middleware.
+///
+/// # Panic
+///
+/// An instance of `Yielding` should _not_ be shared among different
+/// modules, since it tracks module-specific information like the
+/// global indexes of the fuel. Attempts to use a `Yielding` instance
+/// from multiple modules will result in a panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use wasmer::CompilerConfig;
+/// use wasmer_middlewares::Yielding;
+///
+/// fn create_yielding_middleware(compiler_config: &mut dyn CompilerConfig) {
+///     // Yield every 10000 function calls or loop iterations.
+///     compiler_config.push_middleware(Arc::new(Yielding::new(10_000)));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Yielding {
+    /// The checks between two yields.
+    quantum: u64,
+
+    /// The indexes of the state added to the module.
+    indexes: Mutex<Option<YieldingIndexes>>,
+}
+
+/// The function-level yielding middleware.
+pub struct FunctionYielding {
+    /// The indexes of the state added to the module.
+    indexes: YieldingIndexes,
+
+    /// Whether the check at the entry of the function has been
+    /// emitted.
+    entry_checked: bool,
+}
+
+impl Yielding {
+    /// Creates a `Yielding` middleware, yielding every `quantum`
+    /// function calls or loop iterations.
+    pub fn new(quantum: u64) -> Self {
+        Self {
+            quantum,
+            indexes: Mutex::new(None),
+        }
+    }
+}
+
+impl ModuleMiddleware for Yielding {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionYielding {
+            indexes: self.indexes.lock().unwrap().clone().unwrap(),
+            entry_checked: false,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut indexes = self.indexes.lock().unwrap();
+
+        if indexes.is_some() {
+            panic!("Yielding::transform_module_info: Attempting to use a `Yielding` middleware from multiple modules.");
+        }
+
+        let mut push_global = |ty: Type, init: GlobalInit, name: &str| {
+            let index = module_info
+                .globals
+                .push(GlobalType::new(ty, Mutability::Var));
+            module_info.global_initializers.push(init);
+            module_info
+                .exports
+                .insert(name.to_string(), ExportIndex::Global(index));
+            index
+        };
+
+        let quantum = self.quantum as i64;
+        let enabled = push_global(Type::I32, GlobalInit::I32Const(0), "wasmer_yield_enabled");
+        let fuel = push_global(
+            Type::I64,
+            GlobalInit::I64Const(quantum),
+            "wasmer_yield_fuel",
+        );
+        let quantum = push_global(
+            Type::I64,
+            GlobalInit::I64Const(quantum),
+            "wasmer_yield_quantum",
+        );
+        let requested = push_global(Type::I32, GlobalInit::I32Const(0), "wasmer_yield_requested");
+
+        // Append a table for the yield function, which takes no
+        // argument and returns nothing.
+        let signature = module_info
+            .signatures
+            .push(FunctionType::new(vec![], vec![]));
+
+        let table = module_info
+            .tables
+            .push(TableType::new(Type::FuncRef, 1, Some(1)));
+
+        module_info.exports.insert(
+            "wasmer_yield_function".to_string(),
+            ExportIndex::Table(table),
+        );
+
+        *indexes = Some(YieldingIndexes {
+            enabled,
+            fuel,
+            quantum,
+            requested,
+            table,
+            signature,
+        });
+    }
+}
+
+impl fmt::Debug for FunctionYielding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionYielding")
+            .field("indexes", &self.indexes)
+            .finish()
+    }
+}
+
+impl FunctionYielding {
+    fn check(&self, state: &mut MiddlewareReaderState<'_>) {
+        let fuel = self.indexes.fuel.as_u32();
+        let requested = self.indexes.requested.as_u32();
+
+        state.extend(&[
+            // globals[fuel] -= 1;
+            // if (globals[fuel] < 0 || globals[requested]) && globals[enabled] {
+            //     yield();
+            //     globals[fuel] = globals[quantum];
+            //     globals[requested] = 0;
+            // }
+            Operator::GlobalGet { global_index: fuel },
+            Operator::I64Const { value: 1 },
+            Operator::I64Sub,
+            Operator::GlobalSet { global_index: fuel },
+            Operator::GlobalGet { global_index: fuel },
+            Operator::I64Const { value: 0 },
+            Operator::I64LtS,
+            // Load the flag again at every check, see the interrupt
+            // middleware.
+            Operator::AtomicFence { flags: 0 },
+            Operator::GlobalGet {
+                global_index: requested,
+            },
+            Operator::I32Or,
+            Operator::GlobalGet {
+                global_index: self.indexes.enabled.as_u32(),
+            },
+            Operator::I32And,
+            Operator::If {
+                ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+            },
+            Operator::I32Const { value: 0 },
+            Operator::CallIndirect {
+                index: self.indexes.signature.as_u32(),
+                table_index: self.indexes.table.as_u32(),
+            },
+            Operator::GlobalGet {
+                global_index: self.indexes.quantum.as_u32(),
+            },
+            Operator::GlobalSet { global_index: fuel },
+            Operator::I32Const { value: 0 },
+            Operator::GlobalSet {
+                global_index: requested,
+            },
+            Operator::End,
+        ]);
+    }
+}
+
+impl FunctionMiddleware for FunctionYielding {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if !self.entry_checked {
+            self.entry_checked = true;
+            self.check(state);
+        }
+
+        match operator {
+            // Check at the start of the body of the loop, so that
+            // every iteration is checked.
+            Operator::Loop { .. } => {
+                state.push_operator(operator);
+                self.check(state);
+            }
+            _ => state.push_operator(operator),
+        }
+
+        Ok(())
+    }
+}
+
+thread_local! {
+    /// The yielder of the [`YieldingCall`] running on the thread.
+    static CURRENT: Cell<Option<NonNull<Yielder<bool, ()>>>> = Cell::new(None);
+}
+
+/// The yield function called by the instances: it suspends the
+/// running [`YieldingCall`], and traps if it's cancelled.
+fn yield_current() -> Result<(), RuntimeError> {
+    let yielder = match CURRENT.with(|cell| cell.get()) {
+        Some(yielder) => yielder,
+        None => return Ok(()),
+    };
+
+    // The yielder lives on the stack of the call, which is suspended
+    // here.
+    let cancelled = wasmer_vm::suspend_host_stack(|| unsafe { yielder.as_ref() }.suspend(()));
+    CURRENT.with(|cell| cell.set(Some(yielder)));
+
+    if cancelled {
+        Err(RuntimeError::new("the call has been cancelled"))
+    } else {
+        Ok(())
+    }
+}
+
+/// A call running on its own stack, which is suspended when the
+/// [`Instance`][wasmer::Instance]s it calls yield.
+///
+/// Dropping a suspended call cancels it: the instance traps where it
+/// yielded, and the call is run to its end.
+pub struct YieldingCall<R: 'static> {
+    coroutine: Coroutine<bool, (), R>,
+}
+
+impl<R: 'static> YieldingCall<R> {
+    /// Creates a `YieldingCall` running `f`, which typically calls a
+    /// function of an [`Instance`][wasmer::Instance] processed with the
+    /// [`Yielding`] middleware, with its store.
+    ///
+    /// `f` doesn't run until the call is resumed.
+    pub fn new<F: FnOnce() -> R + 'static>(f: F) -> Self {
+        Self {
+            coroutine: Coroutine::new(move |yielder: &Yielder<bool, ()>, _| {
+                CURRENT.with(|cell| cell.set(Some(NonNull::from(yielder))));
+                f()
+            }),
+        }
+    }
+
+    /// Runs the call until it yields, returning `None`, or returns,
+    /// returning its result.
+    ///
+    /// # Panic
+    ///
+    /// Resuming a call that has returned will panic.
+    pub fn resume(&mut self) -> Option<R> {
+        self.resume_with(false)
+    }
+
+    /// Returns whether the call has returned.
+    pub fn is_done(&self) -> bool {
+        self.coroutine.done()
+    }
+
+    fn resume_with(&mut self, cancelled: bool) -> Option<R> {
+        let previous = CURRENT.with(|cell| cell.get());
+        let result = self.coroutine.resume(cancelled);
+        CURRENT.with(|cell| cell.set(previous));
+
+        match result {
+            CoroutineResult::Yield(()) => None,
+            CoroutineResult::Return(result) => Some(result),
+        }
+    }
+}
+
+impl<R: 'static> Drop for YieldingCall<R> {
+    fn drop(&mut self) {
+        // The stack of the call can't be unwound through the frames of
+        // the instances, so the call is made to return.
+        while self.coroutine.started() && !self.coroutine.done() {
+            self.resume_with(true);
+        }
+    }
+}
+
+impl<R: 'static> fmt::Debug for YieldingCall<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("YieldingCall")
+            .field("started", &self.coroutine.started())
+            .field("done", &self.coroutine.done())
+            .finish()
+    }
+}
+
+/// Let an [`Instance`][wasmer::Instance] yield, which it doesn't
+/// until this is called.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Yielding`] middleware at compile time, otherwise this will
+/// panic.
+pub fn enable_yielding(ctx: &mut impl AsStoreMut, instance: &Instance) {
+    let function = Function::new_typed(ctx, yield_current);
+
+    instance
+        .exports
+        .get_table("wasmer_yield_function")
+        .expect("Can't get `wasmer_yield_function` from Instance")
+        .set(ctx, 0, Value::FuncRef(Some(function)))
+        .expect("Can't set `wasmer_yield_function` in Instance");
+
+    instance
+        .exports
+        .get_global("wasmer_yield_enabled")
+        .expect("Can't get `wasmer_yield_enabled` from Instance")
+        .set(ctx, 1i32.into())
+        .expect("Can't set `wasmer_yield_enabled` in Instance");
+}
+
+/// Set the number of function calls or loop iterations an
+/// [`Instance`][wasmer::Instance] runs between two yields, starting
+/// from the next yield.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Yielding`] middleware at compile time, otherwise this will
+/// panic.
+pub fn set_yield_quantum(ctx: &mut impl AsStoreMut, instance: &Instance, quantum: u64) {
+    instance
+        .exports
+        .get_global("wasmer_yield_quantum")
+        .expect("Can't get `wasmer_yield_quantum` from Instance")
+        .set(ctx, (quantum as i64).into())
+        .expect("Can't set `wasmer_yield_quantum` in Instance");
+}
+
+/// A handle to make an [`Instance`][wasmer::Instance] processed with
+/// the [`Yielding`] middleware yield, which can be sent to other
+/// threads, e.g. to enforce time slices.
+///
+/// The handle keeps the flag it sets alive, so it can outlive the
+/// store of the instance, in which case it has no effect anymore.
+#[derive(Debug, Clone)]
+pub struct YieldHandle {
+    flag: Arc<VMSharedGlobalDefinition>,
+}
+
+impl YieldHandle {
+    /// Make the instance yield at the next function call or loop
+    /// iteration.
+    pub fn request_yield(&self) {
+        // SAFETY: the `i32` value of the global is stored at the start
+        // of its definition, which is aligned to 16 bytes and kept alive
+        // by `self.flag`.
+        let flag = unsafe { &*(self.flag.as_ptr().as_ptr() as *const AtomicI32) };
+        flag.store(1, Ordering::SeqCst);
+    }
+}
+
+/// Get a handle to make an [`Instance`][wasmer::Instance] yield.
+///
+/// # Panic
+///
+/// The [`Instance`][wasmer::Instance] must have been processed with
+/// the [`Yielding`] middleware at compile time, otherwise this will
+/// panic.
+pub fn get_yield_handle(ctx: &impl AsStoreRef, instance: &Instance) -> YieldHandle {
+    let flag = instance
+        .exports
+        .get_global("wasmer_yield_requested")
+        .expect("Can't get `wasmer_yield_requested` from Instance")
+        .shared_definition(ctx);
+
+    YieldHandle { flag }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, EngineBuilder, Module, Store, TypedFunction,
+    };
+
+    fn count_call(quantum: u64, count: i32) -> YieldingCall<Result<i32, RuntimeError>> {
+        count_call_with_handle(quantum, count).0
+    }
+
+    fn count_call_with_handle(
+        quantum: u64,
+        count: i32,
+    ) -> (YieldingCall<Result<i32, RuntimeError>>, YieldHandle) {
+        let bytecode = wat2wasm(
+            br#"
+            (module
+            (func (export "count") (param $n i32) (result i32) (local $i i32)
+                (loop $again
+                    local.get $i
+                    i32.const 1
+                    i32.add
+                    local.tee $i
+                    local.get $n
+                    i32.lt_u
+                    br_if $again)
+                local.get $i))
+            "#,
+        )
+        .unwrap();
+
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(Yielding::new(quantum)));
+        let mut store = Store::new(EngineBuilder::new(compiler_config));
+        let module = Module::new(&store, bytecode).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+        enable_yielding(&mut store, &instance);
+        let handle = get_yield_handle(&store, &instance);
+
+        let count_fn: TypedFunction<i32, i32> = instance
+            .exports
+            .get_function("count")
+            .unwrap()
+            .typed(&store)
+            .unwrap();
+        let call = YieldingCall::new(move || count_fn.call(&mut store, count));
+        (call, handle)
+    }
+
+    #[test]
+    fn calls_yield_and_resume() {
+        // The entry and the 100 iterations are checked.
+        let mut call = count_call(10, 100);
+        let mut yields = 0;
+        let result = loop {
+            match call.resume() {
+                Some(result) => break result,
+                None => yields += 1,
+            }
+        };
+        assert_eq!(result.unwrap(), 100);
+        assert_eq!(yields, 9);
+        assert!(call.is_done());
+    }
+
+    #[test]
+    fn calls_are_interleaved() {
+        let mut calls = vec![count_call(5, 50), count_call(5, 20)];
+        let mut results = vec![None, None];
+        while results.iter().any(Option::is_none) {
+            for (call, result) in calls.iter_mut().zip(results.iter_mut()) {
+                if result.is_none() {
+                    *result = call.resume();
+                }
+            }
+        }
+        let results = results
+            .into_iter()
+            .map(|result| result.unwrap().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(results, vec![50, 20]);
+    }
+
+    #[test]
+    fn calls_yield_on_request() {
+        let (mut call, handle) = count_call_with_handle(1_000_000, 100);
+        handle.request_yield();
+        assert!(call.resume().is_none());
+        // The request is cleared once the call yields.
+        assert_eq!(call.resume().unwrap().unwrap(), 100);
+
+        // The handle outlives the store of the instance.
+        drop(call);
+        handle.request_yield();
+    }
+
+    #[test]
+    fn dropped_calls_are_cancelled() {
+        let mut call = count_call(10, 100);
+        assert!(call.resume().is_none());
+        drop(call);
+    }
+}
@@ -317,6 +317,14 @@ pub fn emit_compilation(
                     RelocationEncoding::Generic,
                     32,
                 ),
+                Reloc::S390xPCRel32Dbl => {
+                    (RelocationKind::Relative, RelocationEncoding::S390xDbl, 32)
+                }
+                Reloc::S390xPLTRel32Dbl => (
+                    RelocationKind::PltRelative,
+                    RelocationEncoding::S390xDbl,
+                    32,
+                ),
                 other => {
                     return Err(ObjectError::UnsupportedArchitecture(format!(
                         "{} (relocation: {}",
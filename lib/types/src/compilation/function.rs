@@ -7,7 +7,7 @@
 use super::trap::TrapInformation;
 use crate::entity::PrimaryMap;
 use crate::lib::std::vec::Vec;
-use crate::{CompiledFunctionUnwindInfo, FunctionAddressMap};
+use crate::{CompiledFunctionUnwindInfo, CompiledFunctionUnwindInfoReference, FunctionAddressMap};
 use crate::{
     CustomSection, FunctionIndex, LocalFunctionIndex, Relocation, SectionIndex, SignatureIndex,
 };
@@ -21,6 +21,7 @@ use serde::{Deserialize, Serialize};
 /// the frame information after a `Trap`.
 #[cfg_attr(feature = "enable-serde", derive(Deserialize, Serialize))]
 #[derive(RkyvSerialize, RkyvDeserialize, Archive, Debug, Clone, PartialEq, Eq, Default)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 pub struct CompiledFunctionFrameInfo {
     /// The traps (in the function body).
     ///
@@ -34,6 +35,7 @@ pub struct CompiledFunctionFrameInfo {
 /// The function body.
 #[cfg_attr(feature = "enable-serde", derive(Deserialize, Serialize))]
 #[derive(RkyvSerialize, RkyvDeserialize, Archive, Debug, Clone, PartialEq, Eq)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 pub struct FunctionBody {
     /// The function body bytes.
     #[cfg_attr(feature = "enable-serde", serde(with = "serde_bytes"))]
@@ -43,6 +45,38 @@ pub struct FunctionBody {
     pub unwind_info: Option<CompiledFunctionUnwindInfo>,
 }
 
+/// A function body, either deserialized or read in place from a
+/// serialized module.
+pub trait FunctionBodyLike {
+    /// The function body bytes.
+    fn body(&self) -> &[u8];
+
+    /// The function unwind info
+    fn unwind_info(&self) -> Option<CompiledFunctionUnwindInfoReference<'_>>;
+}
+
+impl FunctionBodyLike for FunctionBody {
+    fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    fn unwind_info(&self) -> Option<CompiledFunctionUnwindInfoReference<'_>> {
+        self.unwind_info
+            .as_ref()
+            .map(CompiledFunctionUnwindInfo::get)
+    }
+}
+
+impl FunctionBodyLike for ArchivedFunctionBody {
+    fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    fn unwind_info(&self) -> Option<CompiledFunctionUnwindInfoReference<'_>> {
+        self.unwind_info.as_ref().map(|info| info.get())
+    }
+}
+
 /// The result of compiling a WebAssembly function.
 ///
 /// This structure only have the compiled information data
@@ -50,6 +84,7 @@ pub struct FunctionBody {
 /// and unwind information).
 #[cfg_attr(feature = "enable-serde", derive(Deserialize, Serialize))]
 #[derive(RkyvSerialize, RkyvDeserialize, Archive, Debug, Clone, PartialEq, Eq)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 pub struct CompiledFunction {
     /// The function body.
     pub body: FunctionBody,
@@ -74,7 +109,9 @@ pub type CustomSections = PrimaryMap<SectionIndex, CustomSection>;
 /// In the future this structure may also hold other information useful
 /// for debugging.
 #[cfg_attr(feature = "enable-serde", derive(Deserialize, Serialize))]
-#[derive(RkyvSerialize, RkyvDeserialize, Archive, Debug, PartialEq, Eq, Clone)]
+#[derive(
+    RkyvSerialize, RkyvDeserialize, Archive, Debug, PartialEq, Eq, Clone, rkyv::CheckBytes,
+)]
 #[archive(as = "Self")]
 pub struct Dwarf {
     /// The section index in the [`Compilation`] that corresponds to the exception frames.
@@ -21,7 +21,10 @@ use serde::{Deserialize, Serialize};
 
 /// Relocation kinds for every ISA.
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
-#[derive(RkyvSerialize, RkyvDeserialize, Archive, Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(
+    RkyvSerialize, RkyvDeserialize, Archive, Copy, Clone, Debug, PartialEq, Eq, rkyv::CheckBytes,
+)]
+#[repr(u8)]
 #[archive(as = "Self")]
 pub enum RelocationKind {
     /// absolute 4-byte
@@ -54,6 +57,10 @@ pub enum RelocationKind {
     // RiscvCall,
     /// Elf x86_64 32 bit signed PC relative offset to two GOT entries for GD symbol.
     ElfX86_64TlsGd,
+    /// s390x PC-relative 4-byte offset, counted in halfwords
+    S390xPCRel32Dbl,
+    /// s390x call to PLT-relative 4-byte offset, counted in halfwords
+    S390xPLTRel32Dbl,
     // /// Mach-O x86_64 32 bit signed PC relative offset to a `__thread_vars` entry.
     // MachOX86_64Tlv,
 }
@@ -76,6 +83,8 @@ impl fmt::Display for RelocationKind {
             Self::Arm64Movw2 => write!(f, "Arm64MovwG2"),
             Self::Arm64Movw3 => write!(f, "Arm64MovwG3"),
             Self::ElfX86_64TlsGd => write!(f, "ElfX86_64TlsGd"),
+            Self::S390xPCRel32Dbl => write!(f, "PCRel32Dbl"),
+            Self::S390xPLTRel32Dbl => write!(f, "PLTRel32Dbl"),
             // Self::MachOX86_64Tlv => write!(f, "MachOX86_64Tlv"),
         }
     }
@@ -84,6 +93,7 @@ impl fmt::Display for RelocationKind {
 /// A record of a relocation to perform.
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 #[derive(RkyvSerialize, RkyvDeserialize, Archive, Debug, Clone, PartialEq, Eq)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 pub struct Relocation {
     /// The relocation kind.
     pub kind: RelocationKind,
@@ -97,7 +107,10 @@ pub struct Relocation {
 
 /// Destination function. Can be either user function or some special one, like `memory.grow`.
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
-#[derive(RkyvSerialize, RkyvDeserialize, Archive, Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(
+    RkyvSerialize, RkyvDeserialize, Archive, Debug, Copy, Clone, PartialEq, Eq, rkyv::CheckBytes,
+)]
+#[repr(u8)]
 #[archive(as = "Self")]
 pub enum RelocationTarget {
     /// A relocation to a function defined locally in the wasm (not an imported one).
@@ -153,7 +166,9 @@ impl Relocation {
                     .wrapping_add(reloc_addend as u32);
                 (reloc_address, reloc_delta_u32 as u64)
             }
-            RelocationKind::Arm64Call => {
+            RelocationKind::Arm64Call
+            | RelocationKind::S390xPCRel32Dbl
+            | RelocationKind::S390xPLTRel32Dbl => {
                 let reloc_address = start + self.offset as usize;
                 let reloc_addend = self.addend as isize;
                 let reloc_delta_u32 = target_func_address
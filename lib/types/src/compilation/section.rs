@@ -26,6 +26,7 @@ use serde::{Deserialize, Serialize};
     Ord,
     Debug,
     Default,
+    rkyv::CheckBytes,
 )]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 #[archive(as = "Self")]
@@ -37,7 +38,10 @@ entity_impl!(SectionIndex);
 ///
 /// Determines how a custom section may be used.
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
-#[derive(RkyvSerialize, RkyvDeserialize, Archive, Debug, Clone, PartialEq, Eq)]
+#[derive(
+    RkyvSerialize, RkyvDeserialize, Archive, Debug, Clone, PartialEq, Eq, rkyv::CheckBytes,
+)]
+#[repr(u8)]
 #[archive(as = "Self")]
 pub enum CustomSectionProtection {
     /// A custom section with read permission.
@@ -53,6 +57,7 @@ pub enum CustomSectionProtection {
 /// in the emitted module.
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 #[derive(RkyvSerialize, RkyvDeserialize, Archive, Debug, Clone, PartialEq, Eq)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 pub struct CustomSection {
     /// Memory protection that applies to this section.
     pub protection: CustomSectionProtection,
@@ -69,9 +74,40 @@ pub struct CustomSection {
     pub relocations: Vec<Relocation>,
 }
 
+/// A custom section, either deserialized or read in place from a
+/// serialized module.
+pub trait CustomSectionLike {
+    /// Memory protection that applies to this section.
+    fn protection(&self) -> &CustomSectionProtection;
+
+    /// The bytes corresponding to this section.
+    fn bytes(&self) -> &[u8];
+}
+
+impl CustomSectionLike for CustomSection {
+    fn protection(&self) -> &CustomSectionProtection {
+        &self.protection
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self.bytes.as_slice()
+    }
+}
+
+impl CustomSectionLike for ArchivedCustomSection {
+    fn protection(&self) -> &CustomSectionProtection {
+        &self.protection
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self.bytes.0.as_slice()
+    }
+}
+
 /// The bytes in the section.
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 #[derive(RkyvSerialize, RkyvDeserialize, Archive, Debug, Clone, PartialEq, Eq, Default)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 pub struct SectionBody(#[cfg_attr(feature = "enable-serde", serde(with = "serde_bytes"))] Vec<u8>);
 
 impl SectionBody {
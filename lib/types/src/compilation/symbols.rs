@@ -1,4 +1,8 @@
 //! This module define the required structures for compilation symbols.
+use crate::lib::std::boxed::Box;
+use crate::lib::std::format;
+use crate::lib::std::string::String;
+use crate::lib::std::vec::Vec;
 use crate::{
     entity::{EntityRef, PrimaryMap},
     CompileModuleInfo, DeserializeError, FunctionIndex, LocalFunctionIndex, OwnedDataInitializer,
@@ -14,8 +18,19 @@ use serde::{Deserialize, Serialize};
 
 /// The kinds of wasmer_types objects that might be found in a native object file.
 #[derive(
-    RkyvSerialize, RkyvDeserialize, Archive, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug,
+    RkyvSerialize,
+    RkyvDeserialize,
+    Archive,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Debug,
+    rkyv::CheckBytes,
 )]
+#[repr(u8)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 #[archive(as = "Self")]
 pub enum Symbol {
@@ -49,6 +64,7 @@ pub trait SymbolRegistry: Send + Sync {
 
 /// Serializable struct that represents the compiled metadata.
 #[derive(Debug, RkyvSerialize, RkyvDeserialize, Archive)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub struct ModuleMetadata {
     /// Compile info
@@ -61,6 +77,8 @@ pub struct ModuleMetadata {
     pub function_body_lengths: PrimaryMap<LocalFunctionIndex, u64>,
     /// CPU features used (See [`CpuFeature`])
     pub cpu_features: u64,
+    /// Name of the compiler that produced the module
+    pub compiler: String,
 }
 
 /// A simple metadata registry
@@ -8,9 +8,9 @@
 #![allow(clippy::use_self)]
 
 use crate::error::ParseCpuFeatureError;
+use crate::lib::std::str::FromStr;
+use crate::lib::std::string::{String, ToString};
 use enumset::{EnumSet, EnumSetType};
-use std::str::FromStr;
-use std::string::{String, ToString};
 pub use target_lexicon::{
     Aarch64Architecture, Architecture, BinaryFormat, CallingConvention, Endianness, Environment,
     OperatingSystem, PointerWidth, Triple, Vendor,
@@ -51,7 +51,7 @@ pub enum CpuFeature {
 }
 
 impl CpuFeature {
-    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
     /// Retrieves the features for the current Host
     pub fn for_host() -> EnumSet<Self> {
         let mut features = EnumSet::new();
@@ -100,7 +100,7 @@ impl CpuFeature {
         }
         features
     }
-    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    #[cfg(not(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64"))))]
     /// Retrieves the features for the current Host
     pub fn for_host() -> EnumSet<Self> {
         // We default to an empty hash set
@@ -19,6 +19,7 @@ use serde::{Deserialize, Serialize};
 /// [unwind info]: https://docs.microsoft.com/en-us/cpp/build/exception-handling-x64?view=vs-2019
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 #[derive(RkyvSerialize, RkyvDeserialize, Archive, Debug, Clone, PartialEq, Eq)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 pub enum CompiledFunctionUnwindInfo {
     /// Windows UNWIND_INFO.
     WindowsX64(Vec<u8>),
@@ -26,3 +27,34 @@ pub enum CompiledFunctionUnwindInfo {
     /// The unwind info is added to the Dwarf section in `Compilation`.
     Dwarf,
 }
+
+/// A borrowed [`CompiledFunctionUnwindInfo`], deserialized or read in
+/// place from a serialized module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompiledFunctionUnwindInfoReference<'a> {
+    /// Windows UNWIND_INFO.
+    WindowsX64(&'a [u8]),
+
+    /// The unwind info is added to the Dwarf section in `Compilation`.
+    Dwarf,
+}
+
+impl CompiledFunctionUnwindInfo {
+    /// Borrows the unwind information.
+    pub fn get(&self) -> CompiledFunctionUnwindInfoReference<'_> {
+        match self {
+            Self::WindowsX64(info) => CompiledFunctionUnwindInfoReference::WindowsX64(info),
+            Self::Dwarf => CompiledFunctionUnwindInfoReference::Dwarf,
+        }
+    }
+}
+
+impl ArchivedCompiledFunctionUnwindInfo {
+    /// Borrows the unwind information.
+    pub fn get(&self) -> CompiledFunctionUnwindInfoReference<'_> {
+        match self {
+            Self::WindowsX64(info) => CompiledFunctionUnwindInfoReference::WindowsX64(info),
+            Self::Dwarf => CompiledFunctionUnwindInfoReference::Dwarf,
+        }
+    }
+}
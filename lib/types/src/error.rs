@@ -1,42 +1,52 @@
 //! The WebAssembly possible errors
+use crate::lib::std::string::String;
 use crate::{ExternType, Pages};
+#[cfg(feature = "std")]
 use std::io;
+#[cfg(feature = "std")]
 use thiserror::Error;
 
 /// The Serialize error can occur when serializing a
 /// compiled Module into a binary.
-#[derive(Error, Debug)]
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
 pub enum SerializeError {
     /// An IO error
+    #[cfg(feature = "std")]
     #[error(transparent)]
     Io(#[from] io::Error),
     /// A generic serialization error
-    #[error("{0}")]
+    #[cfg_attr(feature = "std", error("{0}"))]
     Generic(String),
 }
 
 /// The Deserialize error can occur when loading a
 /// compiled Module from a binary.
-#[derive(Error, Debug)]
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
 pub enum DeserializeError {
     /// An IO error
+    #[cfg(feature = "std")]
     #[error(transparent)]
     Io(#[from] io::Error),
     /// A generic deserialization error
-    #[error("{0}")]
+    #[cfg_attr(feature = "std", error("{0}"))]
     Generic(String),
     /// Incompatible serialized binary
-    #[error("incompatible binary: {0}")]
+    #[cfg_attr(feature = "std", error("incompatible binary: {0}"))]
     Incompatible(String),
     /// The provided binary is corrupted
-    #[error("corrupted binary: {0}")]
+    #[cfg_attr(feature = "std", error("corrupted binary: {0}"))]
     CorruptedBinary(String),
     /// The binary was valid, but we got an error when
     /// trying to allocate the required resources.
-    #[error(transparent)]
-    Compiler(#[from] CompileError),
+    #[cfg_attr(feature = "std", error(transparent))]
+    Compiler(#[cfg_attr(feature = "std", from)] CompileError),
     /// Input artifact bytes have an invalid length
-    #[error("invalid input bytes: expected {expected} bytes, got {got}")]
+    #[cfg_attr(
+        feature = "std",
+        error("invalid input bytes: expected {expected} bytes, got {got}")
+    )]
     InvalidByteLength {
         /// How many bytes were expected
         expected: usize,
@@ -45,15 +55,23 @@ pub enum DeserializeError {
     },
 }
 
+#[cfg(not(feature = "std"))]
+impl From<CompileError> for DeserializeError {
+    fn from(original: CompileError) -> Self {
+        Self::Compiler(original)
+    }
+}
+
 /// Error type describing things that can go wrong when operating on Wasm Memories.
-#[derive(Error, Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "std", derive(Error))]
 pub enum MemoryError {
     /// Low level error with mmap.
-    #[error("Error when allocating memory: {0}")]
+    #[cfg_attr(feature = "std", error("Error when allocating memory: {0}"))]
     Region(String),
     /// The operation would cause the size of the memory to exceed the maximum or would cause
     /// an overflow leading to unindexable memory.
-    #[error("The memory could not grow: current size {} pages, requested increase: {} pages", current.0, attempted_delta.0)]
+    #[cfg_attr(feature = "std", error("The memory could not grow: current size {} pages, requested increase: {} pages", current.0, attempted_delta.0))]
     CouldNotGrow {
         /// The current size in pages.
         current: Pages,
@@ -61,13 +79,13 @@ pub enum MemoryError {
         attempted_delta: Pages,
     },
     /// The operation would cause the size of the memory size exceed the maximum.
-    #[error("The memory is invalid because {}", reason)]
+    #[cfg_attr(feature = "std", error("The memory is invalid because {}", reason))]
     InvalidMemory {
         /// The reason why the provided memory is invalid.
         reason: String,
     },
     /// Caller asked for more minimum memory than we can give them.
-    #[error("The minimum requested ({} pages) memory is greater than the maximum allowed memory ({} pages)", min_requested.0, max_allowed.0)]
+    #[cfg_attr(feature = "std", error("The minimum requested ({} pages) memory is greater than the maximum allowed memory ({} pages)", min_requested.0, max_allowed.0))]
     MinimumMemoryTooLarge {
         /// The number of pages requested as the minimum amount of memory.
         min_requested: Pages,
@@ -75,7 +93,7 @@ pub enum MemoryError {
         max_allowed: Pages,
     },
     /// Caller asked for a maximum memory greater than we can give them.
-    #[error("The maximum requested memory ({} pages) is greater than the maximum allowed memory ({} pages)", max_requested.0, max_allowed.0)]
+    #[cfg_attr(feature = "std", error("The maximum requested memory ({} pages) is greater than the maximum allowed memory ({} pages)", max_requested.0, max_allowed.0))]
     MaximumMemoryTooLarge {
         /// The number of pages requested as the maximum amount of memory.
         max_requested: Pages,
@@ -83,7 +101,7 @@ pub enum MemoryError {
         max_allowed: Pages,
     },
     /// A user defined error value, used for error cases not listed above.
-    #[error("A user-defined error occurred: {0}")]
+    #[cfg_attr(feature = "std", error("A user-defined error occurred: {0}"))]
     Generic(String),
 }
 
@@ -91,35 +109,41 @@ pub enum MemoryError {
 ///
 /// Note: this error is not standard to WebAssembly, but it's
 /// useful to determine the import issue on the API side.
-#[derive(Error, Debug)]
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
 pub enum ImportError {
     /// Incompatible Import Type.
     /// This error occurs when the import types mismatch.
-    #[error("incompatible import type. Expected {0:?} but received {1:?}")]
+    #[cfg_attr(
+        feature = "std",
+        error("incompatible import type. Expected {0:?} but received {1:?}")
+    )]
     IncompatibleType(ExternType, ExternType),
 
     /// Unknown Import.
     /// This error occurs when an import was expected but not provided.
-    #[error("unknown import. Expected {0:?}")]
+    #[cfg_attr(feature = "std", error("unknown import. Expected {0:?}"))]
     UnknownImport(ExternType),
 
     /// Memory Error
-    #[error("memory error. {0}")]
+    #[cfg_attr(feature = "std", error("memory error. {0}"))]
     MemoryError(String),
 }
 
 /// An error while preinstantiating a module.
 ///
-#[derive(Error, Debug)]
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
 pub enum PreInstantiationError {
     /// The module was compiled with a CPU feature that is not available on
     /// the current host.
-    #[error("module compiled with CPU feature that is missing from host")]
+    #[cfg_attr(
+        feature = "std",
+        error("module compiled with CPU feature that is missing from host")
+    )]
     CpuFeature(String),
 }
 
-use crate::lib::std::string::String;
-
 // Compilation Errors
 //
 // If `std` feature is enable, we can't use `thiserror` until
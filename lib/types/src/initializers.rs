@@ -7,6 +7,7 @@ use serde::{Deserialize, Serialize};
 
 /// A WebAssembly table initializer.
 #[derive(Clone, Debug, Hash, PartialEq, Eq, RkyvSerialize, RkyvDeserialize, Archive)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub struct TableInitializer {
     /// The index of a table to initialize.
@@ -22,6 +23,7 @@ pub struct TableInitializer {
 /// A memory index and offset within that memory where a data initialization
 /// should be performed.
 #[derive(Clone, Debug, PartialEq, Eq, RkyvSerialize, RkyvDeserialize, Archive)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub struct DataInitializerLocation {
     /// The index of the memory to initialize.
@@ -48,6 +50,7 @@ pub struct DataInitializer<'data> {
 /// As `DataInitializer` but owning the data rather than
 /// holding a reference to it
 #[derive(Debug, Clone, PartialEq, Eq, RkyvSerialize, RkyvDeserialize, Archive)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub struct OwnedDataInitializer {
     /// The location where the initialization is to be performed.
@@ -39,16 +39,28 @@ pub mod lib {
     /// Custom `std` module.
     #[cfg(feature = "core")]
     pub mod std {
-        pub use alloc::{borrow, boxed, format, iter, rc, slice, string, vec};
-        pub use core::{any, cell, cmp, convert, fmt, hash, marker, mem, ops, ptr, sync, u32};
+        pub use alloc::{borrow, boxed, format, rc, slice, string, vec};
+        pub use core::{any, cell, cmp, convert, fmt, hash, iter, marker, mem, ops, ptr, str, u32};
+
+        /// The collections of `alloc`, and the hash maps of `hashbrown`.
+        pub mod collections {
+            pub use alloc::collections::*;
+            pub use hashbrown::{HashMap, HashSet};
+        }
+
+        /// The synchronization primitives of `alloc` and `core`.
+        pub mod sync {
+            pub use alloc::sync::*;
+            pub use core::sync::*;
+        }
     }
 
     /// Custom `std` module.
     #[cfg(feature = "std")]
     pub mod std {
         pub use std::{
-            any, borrow, boxed, cell, cmp, convert, fmt, format, hash, iter, marker, mem, ops, ptr,
-            rc, slice, string, sync, u32, vec,
+            any, borrow, boxed, cell, cmp, collections, convert, fmt, format, hash, iter, marker,
+            mem, ops, ptr, rc, slice, str, string, sync, u32, vec,
         };
     }
 }
@@ -74,7 +86,10 @@ pub use crate::compilation::target::{
     Aarch64Architecture, Architecture, BinaryFormat, CallingConvention, CpuFeature, Endianness,
     Environment, OperatingSystem, PointerWidth, Target, Triple, Vendor,
 };
-pub use crate::serialize::{MetadataHeader, SerializableCompilation, SerializableModule};
+pub use crate::serialize::{
+    ArchivedSerializableCompilation, ArchivedSerializableModule, MetadataHeader,
+    SerializableCompilation, SerializableModule,
+};
 pub use error::{
     CompileError, DeserializeError, ImportError, MemoryError, MiddlewareError,
     ParseCpuFeatureError, PreInstantiationError, SerializeError, WasmError, WasmResult,
@@ -102,32 +117,37 @@ pub use types::{
 };
 pub use value::{RawValue, ValueType};
 
-pub use crate::libcalls::LibCall;
+pub use crate::libcalls::{LibCall, SoftFloatConversion, SoftFloatOp};
 pub use crate::memory::MemoryStyle;
 pub use crate::table::TableStyle;
 // TODO: OnCalledAction is needed for asyncify. It will be refactored with https://github.com/wasmerio/wasmer/issues/3451
-pub use crate::trapcode::{OnCalledAction, TrapCode};
+#[cfg(feature = "std")]
+pub use crate::trapcode::OnCalledAction;
+pub use crate::trapcode::TrapCode;
 pub use crate::vmoffsets::{TargetSharedSignatureIndex, VMBuiltinFunctionIndex, VMOffsets};
 
-pub use crate::utils::is_wasm;
+pub use crate::utils::{is_component, is_wasm};
 
 pub use crate::compilation::relocation::{
     Relocation, RelocationKind, RelocationTarget, Relocations,
 };
 pub use crate::compilation::section::{
-    CustomSection, CustomSectionProtection, SectionBody, SectionIndex,
+    ArchivedCustomSection, CustomSection, CustomSectionLike, CustomSectionProtection, SectionBody,
+    SectionIndex,
 };
 
 pub use crate::compilation::address_map::{FunctionAddressMap, InstructionAddressMap};
 pub use crate::compilation::function::{
-    Compilation, CompiledFunction, CompiledFunctionFrameInfo, CustomSections, Dwarf, FunctionBody,
-    Functions,
+    ArchivedFunctionBody, Compilation, CompiledFunction, CompiledFunctionFrameInfo, CustomSections,
+    Dwarf, FunctionBody, FunctionBodyLike, Functions,
 };
 pub use crate::compilation::module::CompileModuleInfo;
 pub use crate::compilation::sourceloc::SourceLoc;
 pub use crate::compilation::symbols::{Symbol, SymbolRegistry};
 pub use crate::compilation::trap::TrapInformation;
-pub use crate::compilation::unwind::CompiledFunctionUnwindInfo;
+pub use crate::compilation::unwind::{
+    CompiledFunctionUnwindInfo, CompiledFunctionUnwindInfoReference,
+};
 
 /// Offset in bytes from the beginning of the function.
 pub type CodeOffset = u32;
@@ -140,8 +160,8 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 mod native {
     use super::Type;
+    use crate::lib::std::fmt;
     use crate::memory::{Memory32, Memory64, MemorySize};
-    use std::fmt;
 
     /// `NativeWasmType` represents a Wasm type that has a direct
     /// representation on the host (hence the “native” term).
@@ -1,8 +1,8 @@
+use crate::lib::std::fmt;
 use enum_iterator::IntoEnumIterator;
 use rkyv::{Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
-use std::fmt;
 
 /// The name of a runtime library routine.
 ///
@@ -18,7 +18,9 @@ use std::fmt;
     RkyvSerialize,
     RkyvDeserialize,
     Archive,
+    rkyv::CheckBytes,
 )]
+#[repr(u8)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 #[archive(as = "Self")]
 pub enum LibCall {
@@ -133,6 +135,15 @@ pub enum LibCall {
 
     /// memory.atomic.botify for imported memories
     ImportedMemory32AtomicNotify,
+
+    /// software implementation of f32 arithmetic
+    F32SoftOp,
+
+    /// software implementation of f64 arithmetic
+    F64SoftOp,
+
+    /// software implementation of the conversions from and to floats
+    SoftFloatConvert,
 }
 
 impl LibCall {
@@ -181,6 +192,9 @@ impl LibCall {
             Self::ImportedMemory32AtomicWait64 => "wasmer_vm_imported_memory32_atomic_wait64",
             Self::Memory32AtomicNotify => "wasmer_vm_memory32_atomic_notify",
             Self::ImportedMemory32AtomicNotify => "wasmer_vm_imported_memory32_atomic_notify",
+            Self::F32SoftOp => "wasmer_vm_f32_soft_op",
+            Self::F64SoftOp => "wasmer_vm_f64_soft_op",
+            Self::SoftFloatConvert => "wasmer_vm_soft_float_convert",
         }
     }
 }
@@ -190,3 +204,137 @@ impl fmt::Display for LibCall {
         fmt::Debug::fmt(self, f)
     }
 }
+
+/// A floating point operation performed by the `F32SoftOp`/`F64SoftOp`
+/// builtins when a compiler is configured to use soft-float.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, IntoEnumIterator)]
+#[repr(u32)]
+pub enum SoftFloatOp {
+    /// f32.add / f64.add
+    Add,
+    /// f32.sub / f64.sub
+    Sub,
+    /// f32.mul / f64.mul
+    Mul,
+    /// f32.div / f64.div
+    Div,
+    /// f32.min / f64.min
+    Min,
+    /// f32.max / f64.max
+    Max,
+    /// f32.sqrt / f64.sqrt, the second operand is ignored
+    Sqrt,
+    /// f32.ceil / f64.ceil, the second operand is ignored
+    Ceil,
+    /// f32.floor / f64.floor, the second operand is ignored
+    Floor,
+    /// f32.trunc / f64.trunc, the second operand is ignored
+    Trunc,
+    /// f32.nearest / f64.nearest, the second operand is ignored
+    Nearest,
+    /// f32.abs / f64.abs, the second operand is ignored
+    Abs,
+    /// f32.neg / f64.neg, the second operand is ignored
+    Neg,
+    /// f32.copysign / f64.copysign
+    Copysign,
+    /// f32.eq / f64.eq
+    Eq,
+    /// f32.ne / f64.ne
+    Ne,
+    /// f32.lt / f64.lt
+    Lt,
+    /// f32.gt / f64.gt
+    Gt,
+    /// f32.le / f64.le
+    Le,
+    /// f32.ge / f64.ge
+    Ge,
+}
+
+impl SoftFloatOp {
+    /// Converts the raw operation code passed to the builtins back into a
+    /// `SoftFloatOp`.
+    pub fn from_u32(op: u32) -> Option<Self> {
+        Self::into_enum_iter().find(|candidate| *candidate as u32 == op)
+    }
+
+    /// Whether the operation is a comparison, returning `1` or `0`
+    /// instead of a float.
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            Self::Eq | Self::Ne | Self::Lt | Self::Gt | Self::Le | Self::Ge
+        )
+    }
+}
+
+/// A conversion from or to a float performed by the `SoftFloatConvert`
+/// builtin when a compiler is configured to use soft-float.
+///
+/// The operand and the result are passed as the raw bits of the value,
+/// zero-extended to 64 bits.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, IntoEnumIterator)]
+#[repr(u32)]
+pub enum SoftFloatConversion {
+    /// f32.convert_i32_s
+    F32ConvertI32S,
+    /// f32.convert_i32_u
+    F32ConvertI32U,
+    /// f32.convert_i64_s
+    F32ConvertI64S,
+    /// f32.convert_i64_u
+    F32ConvertI64U,
+    /// f64.convert_i32_s
+    F64ConvertI32S,
+    /// f64.convert_i32_u
+    F64ConvertI32U,
+    /// f64.convert_i64_s
+    F64ConvertI64S,
+    /// f64.convert_i64_u
+    F64ConvertI64U,
+    /// f32.demote_f64
+    F32DemoteF64,
+    /// f64.promote_f32
+    F64PromoteF32,
+    /// i32.trunc_f32_s
+    I32TruncF32S,
+    /// i32.trunc_f32_u
+    I32TruncF32U,
+    /// i32.trunc_f64_s
+    I32TruncF64S,
+    /// i32.trunc_f64_u
+    I32TruncF64U,
+    /// i64.trunc_f32_s
+    I64TruncF32S,
+    /// i64.trunc_f32_u
+    I64TruncF32U,
+    /// i64.trunc_f64_s
+    I64TruncF64S,
+    /// i64.trunc_f64_u
+    I64TruncF64U,
+    /// i32.trunc_sat_f32_s
+    I32TruncSatF32S,
+    /// i32.trunc_sat_f32_u
+    I32TruncSatF32U,
+    /// i32.trunc_sat_f64_s
+    I32TruncSatF64S,
+    /// i32.trunc_sat_f64_u
+    I32TruncSatF64U,
+    /// i64.trunc_sat_f32_s
+    I64TruncSatF32S,
+    /// i64.trunc_sat_f32_u
+    I64TruncSatF32U,
+    /// i64.trunc_sat_f64_s
+    I64TruncSatF64S,
+    /// i64.trunc_sat_f64_u
+    I64TruncSatF64U,
+}
+
+impl SoftFloatConversion {
+    /// Converts the raw conversion code passed to the builtin back into a
+    /// `SoftFloatConversion`.
+    pub fn from_u32(op: u32) -> Option<Self> {
+        Self::into_enum_iter().find(|candidate| *candidate as u32 == op)
+    }
+}
@@ -1,13 +1,26 @@
+use crate::lib::std::convert::{TryFrom, TryInto};
+use crate::lib::std::fmt;
+use crate::lib::std::iter::Sum;
+use crate::lib::std::ops::{Add, AddAssign};
 use crate::{Pages, ValueType};
 use rkyv::{Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
-use std::convert::{TryFrom, TryInto};
-use std::iter::Sum;
-use std::ops::{Add, AddAssign};
 
 /// Implementation styles for WebAssembly linear memory.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, RkyvSerialize, RkyvDeserialize, Archive)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    RkyvSerialize,
+    RkyvDeserialize,
+    Archive,
+    rkyv::CheckBytes,
+)]
+#[repr(u8)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 #[archive(as = "Self")]
 pub enum MemoryStyle {
@@ -51,8 +64,8 @@ impl MemoryStyle {
 pub unsafe trait MemorySize: Copy {
     /// Type used to represent an offset into a memory. This is `u32` or `u64`.
     type Offset: Default
-        + std::fmt::Debug
-        + std::fmt::Display
+        + fmt::Debug
+        + fmt::Display
         + Eq
         + Ord
         + PartialEq<Self::Offset>
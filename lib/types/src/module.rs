@@ -5,6 +5,15 @@
 //! `wasmer::Module`.
 
 use crate::entity::{EntityRef, PrimaryMap};
+use crate::lib::std::boxed::Box;
+use crate::lib::std::collections::BTreeMap;
+use crate::lib::std::collections::HashMap;
+use crate::lib::std::fmt;
+use crate::lib::std::format;
+use crate::lib::std::iter::ExactSizeIterator;
+use crate::lib::std::string::{String, ToString};
+use crate::lib::std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use crate::lib::std::vec::Vec;
 use crate::{
     CustomSectionIndex, DataIndex, ElemIndex, ExportIndex, ExportType, ExternType, FunctionIndex,
     FunctionType, GlobalIndex, GlobalInit, GlobalType, ImportIndex, ImportType, LocalFunctionIndex,
@@ -19,13 +28,9 @@ use rkyv::{
 };
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
-use std::collections::HashMap;
-use std::fmt;
-use std::iter::ExactSizeIterator;
-use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 
 #[derive(Debug, Clone, RkyvSerialize, RkyvDeserialize, Archive)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 pub struct ModuleId {
     id: usize,
 }
@@ -47,6 +52,7 @@ impl Default for ModuleId {
 
 /// Hash key of an import
 #[derive(Debug, Hash, Eq, PartialEq, Clone, Default, RkyvSerialize, RkyvDeserialize, Archive)]
+#[archive_attr(derive(rkyv::CheckBytes, PartialEq, Eq, Hash))]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub struct ImportKey {
     /// Module name
@@ -162,6 +168,10 @@ pub struct ModuleInfo {
     /// The data for each CustomSection in the module.
     pub custom_sections_data: PrimaryMap<CustomSectionIndex, Box<[u8]>>,
 
+    /// The offset of the contents of the code section in the wasm file,
+    /// to which the addresses of the DWARF custom sections are relative.
+    pub code_section_offset: u32,
+
     /// Number of imported functions in the module.
     pub num_imported_functions: usize,
 
@@ -177,6 +187,7 @@ pub struct ModuleInfo {
 
 /// Mirror version of ModuleInfo that can derive rkyv traits
 #[derive(RkyvSerialize, RkyvDeserialize, Archive)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 pub struct ArchivableModuleInfo {
     name: Option<String>,
     imports: IndexMap<ImportKey, ImportIndex>,
@@ -194,6 +205,7 @@ pub struct ArchivableModuleInfo {
     globals: PrimaryMap<GlobalIndex, GlobalType>,
     custom_sections: IndexMap<String, CustomSectionIndex>,
     custom_sections_data: PrimaryMap<CustomSectionIndex, Box<[u8]>>,
+    code_section_offset: u32,
     num_imported_functions: usize,
     num_imported_tables: usize,
     num_imported_memories: usize,
@@ -219,6 +231,7 @@ impl From<ModuleInfo> for ArchivableModuleInfo {
             globals: it.globals,
             custom_sections: it.custom_sections,
             custom_sections_data: it.custom_sections_data,
+            code_section_offset: it.code_section_offset,
             num_imported_functions: it.num_imported_functions,
             num_imported_tables: it.num_imported_tables,
             num_imported_memories: it.num_imported_memories,
@@ -247,6 +260,7 @@ impl From<ArchivableModuleInfo> for ModuleInfo {
             globals: it.globals,
             custom_sections: it.custom_sections,
             custom_sections_data: it.custom_sections_data,
+            code_section_offset: it.code_section_offset,
             num_imported_functions: it.num_imported_functions,
             num_imported_tables: it.num_imported_tables,
             num_imported_memories: it.num_imported_memories,
@@ -307,6 +321,7 @@ impl PartialEq for ModuleInfo {
             && self.globals == other.globals
             && self.custom_sections == other.custom_sections
             && self.custom_sections_data == other.custom_sections_data
+            && self.code_section_offset == other.code_section_offset
             && self.num_imported_functions == other.num_imported_functions
             && self.num_imported_tables == other.num_imported_tables
             && self.num_imported_memories == other.num_imported_memories
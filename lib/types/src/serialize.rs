@@ -1,4 +1,11 @@
 use crate::entity::PrimaryMap;
+use crate::lib::std::boxed::Box;
+use crate::lib::std::convert::TryInto;
+use crate::lib::std::fmt;
+use crate::lib::std::format;
+use crate::lib::std::mem;
+use crate::lib::std::string::{String, ToString};
+use crate::lib::std::vec::Vec;
 use crate::{
     compilation::target::CpuFeature, CompileModuleInfo, CompiledFunctionFrameInfo, CustomSection,
     DeserializeError, Dwarf, Features, FunctionBody, FunctionIndex, LocalFunctionIndex,
@@ -7,16 +14,18 @@ use crate::{
 };
 use enumset::EnumSet;
 use rkyv::{
-    archived_value, de::deserializers::SharedDeserializeMap, ser::serializers::AllocSerializer,
-    ser::Serializer as RkyvSerializer, Archive, Deserialize as RkyvDeserialize,
-    Serialize as RkyvSerialize,
+    archived_value, check_archived_value, de::deserializers::SharedDeserializeMap,
+    ser::serializers::AllocSerializer, ser::Serializer as RkyvSerializer, Archive,
+    Deserialize as RkyvDeserialize, Serialize as RkyvSerialize,
 };
-use std::convert::TryInto;
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
 use std::path::Path;
-use std::{fs, mem};
 
 /// The compilation related data for a serialized modules
 #[derive(Archive, Default, RkyvDeserialize, RkyvSerialize)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 #[allow(missing_docs)]
 pub struct SerializableCompilation {
     pub function_bodies: PrimaryMap<LocalFunctionIndex, FunctionBody>,
@@ -51,6 +60,7 @@ impl SerializableCompilation {
 
 /// Serializable struct that is able to serialize from and to a `ArtifactInfo`.
 #[derive(Archive, RkyvDeserialize, RkyvSerialize)]
+#[archive_attr(derive(rkyv::CheckBytes))]
 #[allow(missing_docs)]
 pub struct SerializableModule {
     /// The main serializable compilation object
@@ -61,12 +71,83 @@ pub struct SerializableModule {
     pub data_initializers: Box<[OwnedDataInitializer]>,
     /// CPU Feature flags for this compilation
     pub cpu_features: u64,
+    /// Name of the compiler that produced this compilation
+    pub compiler: String,
+    /// Whether the function bodies are the code of an interpreter, see
+    /// `Compiler::interpreter`, instead of machine code
+    pub interpreted: bool,
 }
 
-fn to_serialize_error(err: impl std::error::Error) -> SerializeError {
+fn to_serialize_error(err: impl fmt::Display) -> SerializeError {
     SerializeError::Generic(format!("{}", err))
 }
 
+/// Deserializes a part of an archived module.
+fn deserialize_archived<T: Archive>(archived: &T::Archived) -> Result<T, DeserializeError>
+where
+    T::Archived: RkyvDeserialize<T, SharedDeserializeMap>,
+{
+    let mut deserializer = SharedDeserializeMap::new();
+    RkyvDeserialize::deserialize(archived, &mut deserializer)
+        .map_err(|e| DeserializeError::CorruptedBinary(format!("{:?}", e)))
+}
+
+impl ArchivedSerializableCompilation {
+    /// Deserializes the relocations of the functions.
+    pub fn function_relocations(
+        &self,
+    ) -> Result<PrimaryMap<LocalFunctionIndex, Vec<Relocation>>, DeserializeError> {
+        deserialize_archived(&self.function_relocations)
+    }
+
+    /// Deserializes the frame info of the functions.
+    pub fn function_frame_info(
+        &self,
+    ) -> Result<PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>, DeserializeError> {
+        deserialize_archived(&self.function_frame_info)
+    }
+
+    /// Deserializes the relocations of the custom sections.
+    pub fn custom_section_relocations(
+        &self,
+    ) -> Result<PrimaryMap<SectionIndex, Vec<Relocation>>, DeserializeError> {
+        deserialize_archived(&self.custom_section_relocations)
+    }
+
+    /// Deserializes the section indices corresponding to the Dwarf
+    /// debug info.
+    pub fn debug(&self) -> Result<Option<Dwarf>, DeserializeError> {
+        deserialize_archived(&self.debug)
+    }
+}
+
+impl ArchivedSerializableModule {
+    /// Deserializes the compilation informations.
+    pub fn compile_info(&self) -> Result<CompileModuleInfo, DeserializeError> {
+        deserialize_archived(&self.compile_info)
+    }
+
+    /// Deserializes the data initializers.
+    pub fn data_initializers(&self) -> Result<Box<[OwnedDataInitializer]>, DeserializeError> {
+        deserialize_archived(&self.data_initializers)
+    }
+
+    /// Returns the CPU features for this Artifact
+    pub fn cpu_features(&self) -> EnumSet<CpuFeature> {
+        EnumSet::from_u64(self.cpu_features)
+    }
+
+    /// Returns the name of the compiler that produced this Artifact
+    pub fn compiler(&self) -> &str {
+        self.compiler.as_str()
+    }
+
+    /// Returns whether the functions of this Artifact are interpreted
+    pub fn interpreted(&self) -> bool {
+        self.interpreted
+    }
+}
+
 impl SerializableModule {
     /// Serialize a Module into bytes
     /// The bytes will have the following format:
@@ -85,25 +166,55 @@ impl SerializableModule {
     /// The slice must have the following format:
     /// RKYV serialization (any length) + POS (8 bytes)
     ///
+    /// The archived data is validated (via `rkyv::check_archived_value`)
+    /// before being deserialized, so malformed data results in an error.
+    pub fn deserialize(metadata_slice: &[u8]) -> Result<Self, DeserializeError> {
+        let (data, pos) = Self::split_pos(metadata_slice)?;
+        let archived = check_archived_value::<Self>(data, pos).map_err(|e| {
+            DeserializeError::CorruptedBinary(format!("invalid serialized module: {}", e))
+        })?;
+        Self::deserialize_from_archive(archived)
+    }
+
+    /// Deserialize a Module from a slice, without validating it.
+    /// The slice must have the following format:
+    /// RKYV serialization (any length) + POS (8 bytes)
+    ///
     /// # Safety
     ///
     /// This method is unsafe since it deserializes data directly
-    /// from memory.
-    /// Right now we are not doing any extra work for validation, but
-    /// `rkyv` has an option to do bytecheck on the serialized data before
-    /// serializing (via `rkyv::check_archived_value`).
-    pub unsafe fn deserialize(metadata_slice: &[u8]) -> Result<Self, DeserializeError> {
-        let archived = Self::archive_from_slice(metadata_slice)?;
-        Self::deserialize_from_archive(archived)
+    /// from memory: the slice must have been produced by
+    /// `SerializableModule::serialize`.
+    pub unsafe fn deserialize_unchecked(metadata_slice: &[u8]) -> Result<Self, DeserializeError> {
+        Self::deserialize_from_archive(Self::archive_unchecked(metadata_slice)?)
+    }
+
+    /// Access a Module archived in a slice, without deserializing it.
+    /// The slice must have the following format:
+    /// RKYV serialization (any length) + POS (8 bytes)
+    ///
+    /// The archived data is validated like with [`Self::deserialize`].
+    pub fn archive(metadata_slice: &[u8]) -> Result<&ArchivedSerializableModule, DeserializeError> {
+        let (data, pos) = Self::split_pos(metadata_slice)?;
+        check_archived_value::<Self>(data, pos).map_err(|e| {
+            DeserializeError::CorruptedBinary(format!("invalid serialized module: {}", e))
+        })
     }
 
+    /// Access a Module archived in a slice, without deserializing nor
+    /// validating it.
+    ///
     /// # Safety
     ///
-    /// This method is unsafe.
-    /// Please check `SerializableModule::deserialize` for more details.
-    unsafe fn archive_from_slice(
+    /// The slice must have been produced by `SerializableModule::serialize`.
+    pub unsafe fn archive_unchecked(
         metadata_slice: &[u8],
     ) -> Result<&ArchivedSerializableModule, DeserializeError> {
+        let (data, pos) = Self::split_pos(metadata_slice)?;
+        Ok(archived_value::<Self>(data, pos))
+    }
+
+    fn split_pos(metadata_slice: &[u8]) -> Result<(&[u8], usize), DeserializeError> {
         if metadata_slice.len() < 8 {
             return Err(DeserializeError::Incompatible(
                 "invalid serialized data".into(),
@@ -112,19 +223,14 @@ impl SerializableModule {
         let mut pos: [u8; 8] = Default::default();
         pos.copy_from_slice(&metadata_slice[metadata_slice.len() - 8..metadata_slice.len()]);
         let pos: u64 = u64::from_le_bytes(pos);
-        Ok(archived_value::<Self>(
-            &metadata_slice[..metadata_slice.len() - 8],
-            pos as usize,
-        ))
+        Ok((&metadata_slice[..metadata_slice.len() - 8], pos as usize))
     }
 
     /// Deserialize a compilation module from an archive
     pub fn deserialize_from_archive(
         archived: &ArchivedSerializableModule,
     ) -> Result<Self, DeserializeError> {
-        let mut deserializer = SharedDeserializeMap::new();
-        RkyvDeserialize::deserialize(archived, &mut deserializer)
-            .map_err(|e| DeserializeError::CorruptedBinary(format!("{:?}", e)))
+        deserialize_archived(archived)
     }
 
     /// Create a `ModuleInfo` for instantiation
@@ -142,6 +248,16 @@ impl SerializableModule {
         EnumSet::from_u64(self.cpu_features)
     }
 
+    /// Returns the name of the compiler that produced this Artifact
+    pub fn compiler(&self) -> &str {
+        &self.compiler
+    }
+
+    /// Returns whether the functions of this Artifact are interpreted
+    pub fn interpreted(&self) -> bool {
+        self.interpreted
+    }
+
     /// Returns data initializers to pass to `VMInstance::initialize`
     pub fn data_initializers(&self) -> &[OwnedDataInitializer] {
         &self.data_initializers
@@ -158,6 +274,7 @@ impl SerializableModule {
     }
 
     /// Serializes an artifact into a file path
+    #[cfg(feature = "std")]
     pub fn serialize_to_file(&self, path: &Path) -> Result<(), SerializeError> {
         let serialized = self.serialize()?;
         fs::write(path, serialized)?;
@@ -165,53 +282,62 @@ impl SerializableModule {
     }
 }
 
-/// Metadata header which holds an ABI version and the length of the remaining
-/// metadata.
+/// Metadata header which holds an ABI version, the length of the remaining
+/// metadata and a checksum of it.
+///
+/// The checksum only detects accidental corruption (truncated downloads,
+/// bit flips); it doesn't authenticate the metadata.
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct MetadataHeader {
     magic: [u8; 8],
     version: u32,
     len: u32,
+    checksum: u64,
+    reserved: u64,
 }
 
 impl MetadataHeader {
     /// Current ABI version. Increment this any time breaking changes are made
     /// to the format of the serialized data.
-    const CURRENT_VERSION: u32 = 2;
+    pub const CURRENT_VERSION: u32 = 9;
 
     /// Magic number to identify wasmer metadata.
     const MAGIC: [u8; 8] = *b"WASMER\0\0";
 
     /// Length of the metadata header.
-    pub const LEN: usize = 16;
+    pub const LEN: usize = 32;
 
     /// Alignment of the metadata.
     pub const ALIGN: usize = 16;
 
-    /// Creates a new header for metadata of the given length.
-    pub fn new(len: usize) -> Self {
+    /// Creates a new header for the given metadata.
+    pub fn new(metadata: &[u8]) -> Self {
         Self {
             magic: Self::MAGIC,
             version: Self::CURRENT_VERSION,
-            len: len.try_into().expect("metadata exceeds maximum length"),
+            len: metadata
+                .len()
+                .try_into()
+                .expect("metadata exceeds maximum length"),
+            checksum: checksum(metadata),
+            reserved: 0,
         }
     }
 
     /// Convert the header into its bytes representation.
-    pub fn into_bytes(self) -> [u8; 16] {
+    pub fn into_bytes(self) -> [u8; 32] {
         unsafe { mem::transmute(self) }
     }
 
-    /// Parses the header and returns the length of the metadata following it.
-    pub fn parse(bytes: &[u8]) -> Result<usize, DeserializeError> {
+    fn read(bytes: &[u8]) -> Result<Self, DeserializeError> {
         if bytes.as_ptr() as usize % 8 != 0 {
             return Err(DeserializeError::CorruptedBinary(
                 "misaligned metadata".to_string(),
             ));
         }
-        let bytes: [u8; 16] = bytes
-            .get(..16)
+        let bytes: [u8; 32] = bytes
+            .get(..Self::LEN)
             .ok_or_else(|| {
                 DeserializeError::CorruptedBinary("invalid metadata header".to_string())
             })?
@@ -229,6 +355,33 @@ impl MetadataHeader {
                     .to_string(),
             ));
         }
-        Ok(header.len as usize)
+        Ok(header)
+    }
+
+    /// Parses the header and returns the length of the metadata following it.
+    pub fn parse(bytes: &[u8]) -> Result<usize, DeserializeError> {
+        Ok(Self::read(bytes)?.len as usize)
+    }
+
+    /// Parses the header and verifies the checksum of the metadata
+    /// following it. Returns the metadata.
+    pub fn parse_checked(bytes: &[u8]) -> Result<&[u8], DeserializeError> {
+        let header = Self::read(bytes)?;
+        let metadata = bytes
+            .get(Self::LEN..Self::LEN + header.len as usize)
+            .ok_or_else(|| DeserializeError::CorruptedBinary("truncated metadata".to_string()))?;
+        if checksum(metadata) != header.checksum {
+            return Err(DeserializeError::CorruptedBinary(
+                "metadata checksum mismatch".to_string(),
+            ));
+        }
+        Ok(metadata)
     }
 }
+
+/// The 64 bits FNV-1a hash of `bytes`.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
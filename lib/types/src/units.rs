@@ -1,10 +1,11 @@
 use crate::lib::std::convert::TryFrom;
+use crate::lib::std::convert::TryInto;
 use crate::lib::std::fmt;
 use crate::lib::std::ops::{Add, Sub};
 use rkyv::{Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
-use std::convert::TryInto;
+#[cfg(feature = "std")]
 use thiserror::Error;
 
 /// WebAssembly page sizes are fixed to be 64KiB.
@@ -21,7 +22,17 @@ pub const WASM_MIN_PAGES: u32 = 0x100;
 
 /// Units of WebAssembly pages (as specified to be 65,536 bytes).
 #[derive(
-    Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, RkyvSerialize, RkyvDeserialize, Archive,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    RkyvSerialize,
+    RkyvDeserialize,
+    Archive,
+    rkyv::CheckBytes,
 )]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 #[archive(as = "Self")]
@@ -115,8 +126,9 @@ where
 }
 
 /// The only error that can happen when converting `Bytes` to `Pages`
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
-#[error("Number of pages exceeds uint32 range")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Error))]
+#[cfg_attr(feature = "std", error("Number of pages exceeds uint32 range"))]
 pub struct PageCountOutOfRange;
 
 impl TryFrom<Bytes> for Pages {
@@ -2,3 +2,13 @@
 pub fn is_wasm(bytes: impl AsRef<[u8]>) -> bool {
     bytes.as_ref().starts_with(b"\0asm")
 }
+
+/// Check if the provided bytes are a WebAssembly component, as opposed
+/// to a core module.
+///
+/// Components share the `\0asm` magic with core modules, but their
+/// preamble has the layer field, following the version, set to `1`.
+pub fn is_component(bytes: impl AsRef<[u8]>) -> bool {
+    let bytes = bytes.as_ref();
+    is_wasm(bytes) && bytes.len() >= 8 && bytes[6..8] == [1, 0]
+}
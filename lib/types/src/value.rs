@@ -82,6 +82,16 @@ pub unsafe trait ValueType: Copy {
     /// representation of `self`. It must zero out any bytes which are
     /// uninitialized (e.g. padding bytes).
     fn zero_padding_bytes(&self, bytes: &mut [MaybeUninit<u8>]);
+
+    /// Converts `self` between the byte order of the host and the
+    /// little-endian byte order of WebAssembly linear memory.
+    ///
+    /// The conversion is its own inverse, so it is applied both after
+    /// reading a value from Wasm memory and before writing one. It is a
+    /// no-op on little-endian hosts. Types containing multi-byte scalars
+    /// must override it to be usable on big-endian hosts.
+    #[inline]
+    fn convert_endianness(&mut self) {}
 }
 
 // Trivial implementations for primitive types and arrays of them.
@@ -100,12 +110,48 @@ macro_rules! primitives {
 primitives! {
     bool
     i8 u8
-    i16 u16
-    i32 u32
-    i64 u64
-    i128 u128
-    isize usize
-    f32 f64
+}
+
+// Multi-byte primitives additionally need to be byte-swapped on big-endian
+// hosts, since Wasm memory is always little-endian.
+macro_rules! multi_byte_primitives {
+    ($($t:ident: |$v:ident| $from_le:expr;)*) => ($(
+        unsafe impl ValueType for $t {
+            #[inline]
+            fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
+
+            #[inline]
+            fn convert_endianness(&mut self) {
+                let $v = *self;
+                *self = $from_le;
+            }
+        }
+        unsafe impl<const N: usize> ValueType for [$t; N] {
+            #[inline]
+            fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
+
+            #[inline]
+            fn convert_endianness(&mut self) {
+                for item in self.iter_mut() {
+                    item.convert_endianness();
+                }
+            }
+        }
+    )*)
+}
+multi_byte_primitives! {
+    i16: |v| Self::from_le(v);
+    u16: |v| Self::from_le(v);
+    i32: |v| Self::from_le(v);
+    u32: |v| Self::from_le(v);
+    i64: |v| Self::from_le(v);
+    u64: |v| Self::from_le(v);
+    i128: |v| Self::from_le(v);
+    u128: |v| Self::from_le(v);
+    isize: |v| Self::from_le(v);
+    usize: |v| Self::from_le(v);
+    f32: |v| Self::from_bits(u32::from_le(v.to_bits()));
+    f64: |v| Self::from_bits(u64::from_le(v.to_bits()));
 }
 
 // This impl for PhantomData allows #[derive(ValueType)] to work with types
@@ -114,3 +160,34 @@ unsafe impl<T: ?Sized> ValueType for PhantomData<T> {
     #[inline]
     fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ValueType;
+
+    #[test]
+    fn convert_endianness_from_wasm_memory() {
+        let mut val = u32::from_ne_bytes([0x78, 0x56, 0x34, 0x12]);
+        val.convert_endianness();
+        assert_eq!(val, 0x1234_5678);
+
+        let mut val = f64::from_ne_bytes(1.5f64.to_le_bytes());
+        val.convert_endianness();
+        assert_eq!(val, 1.5);
+
+        let mut val = [
+            u16::from_ne_bytes([0x01, 0x02]),
+            u16::from_ne_bytes([0x03, 0x04]),
+        ];
+        val.convert_endianness();
+        assert_eq!(val, [0x0201, 0x0403]);
+    }
+
+    #[test]
+    fn convert_endianness_is_an_involution() {
+        let mut val = 0x0102_0304_0506_0708u64;
+        val.convert_endianness();
+        val.convert_endianness();
+        assert_eq!(val, 0x0102_0304_0506_0708);
+    }
+}
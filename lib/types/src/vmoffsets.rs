@@ -6,12 +6,12 @@
 
 #![deny(broken_intra_doc_links)]
 
+use crate::lib::std::convert::TryFrom;
 use crate::{
     FunctionIndex, GlobalIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex,
     ModuleInfo, SignatureIndex, TableIndex,
 };
 use more_asserts::assert_lt;
-use std::convert::TryFrom;
 
 /// An index type for builtin functions.
 #[derive(Copy, Clone, Debug)]
@@ -139,9 +139,26 @@ impl VMBuiltinFunctionIndex {
     pub const fn get_imported_memory_atomic_notify_index() -> Self {
         Self(29)
     }
+    /// Returns an index for the soft-float `f32` arithmetic builtin function.
+    pub const fn get_f32_soft_op_index() -> Self {
+        Self(30)
+    }
+    /// Returns an index for the soft-float `f64` arithmetic builtin function.
+    pub const fn get_f64_soft_op_index() -> Self {
+        Self(31)
+    }
+    /// Returns an index for the soft-float conversion builtin function.
+    pub const fn get_soft_float_convert_index() -> Self {
+        Self(32)
+    }
+    /// Returns an index for the builtin function compiling the lazily
+    /// compiled functions.
+    pub const fn get_lazy_compile_index() -> Self {
+        Self(33)
+    }
     /// Returns the total number of builtin functions.
     pub const fn builtin_functions_total_number() -> u32 {
-        30
+        34
     }
 
     /// Return the index as an u32 number.
@@ -286,10 +303,15 @@ impl VMOffsets {
         }
 
         self.vmctx_signature_ids_begin = 0;
-        self.vmctx_imported_functions_begin = offset_by(
-            self.vmctx_signature_ids_begin,
-            self.num_signature_ids,
-            u32::from(self.size_of_vmshared_signature_index()),
+        // The signature ids are 32-bit, while the next structs hold
+        // pointers and must be aligned on them.
+        self.vmctx_imported_functions_begin = align(
+            offset_by(
+                self.vmctx_signature_ids_begin,
+                self.num_signature_ids,
+                u32::from(self.size_of_vmshared_signature_index()),
+            ),
+            u32::from(self.pointer_size),
         );
         self.vmctx_imported_tables_begin = offset_by(
             self.vmctx_imported_functions_begin,
@@ -484,7 +506,7 @@ impl VMOffsets {
 
     /// The size of the `current_length` field.
     pub const fn size_of_vmmemory_definition_current_length(&self) -> u8 {
-        4
+        self.pointer_size
     }
 
     /// Return the size of `VMMemoryDefinition`.
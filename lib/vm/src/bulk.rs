@@ -0,0 +1,193 @@
+//! Copies and fills of linear memories, for the bulk memory operations
+//! and the initialization of the data segments.
+//!
+//! The large operations use the widest vector instructions supported by
+//! the CPU, picked once at runtime. The small ones, which are the most
+//! common, are left to `ptr::copy` and `ptr::write_bytes`.
+
+use crate::lib::std::ptr;
+use lazy_static::lazy_static;
+
+/// The length from which the operations are vectorized.
+const VECTORIZED_MIN_LEN: usize = 256;
+
+/// The implementations of the operations for a CPU.
+struct Implementation {
+    copy: unsafe fn(*mut u8, *const u8, usize),
+    fill: unsafe fn(*mut u8, u8, usize),
+}
+
+impl Implementation {
+    const GENERIC: Self = Self {
+        copy: generic_copy,
+        fill: generic_fill,
+    };
+
+    fn detect() -> Self {
+        #[cfg(all(feature = "std", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx") {
+                return Self {
+                    copy: avx::copy,
+                    fill: avx::fill,
+                };
+            }
+        }
+        // Without `std` the features can't be detected at runtime, only
+        // the ones enabled at compile time are used.
+        #[cfg(all(not(feature = "std"), target_arch = "x86_64", target_feature = "avx"))]
+        {
+            return Self {
+                copy: avx::copy,
+                fill: avx::fill,
+            };
+        }
+        #[allow(unreachable_code)]
+        Self::GENERIC
+    }
+}
+
+lazy_static! {
+    static ref IMPLEMENTATION: Implementation = Implementation::detect();
+}
+
+unsafe fn generic_copy(dst: *mut u8, src: *const u8, len: usize) {
+    ptr::copy(src, dst, len)
+}
+
+unsafe fn generic_fill(dst: *mut u8, val: u8, len: usize) {
+    ptr::write_bytes(dst, val, len)
+}
+
+/// Copies `len` bytes from `src` to `dst`, like `ptr::copy`. The ranges
+/// may overlap.
+///
+/// # Safety
+/// - `src` must be valid for `len` bytes of reads and `dst` for `len`
+///   bytes of writes.
+pub(crate) unsafe fn copy(dst: *mut u8, src: *const u8, len: usize) {
+    if len < VECTORIZED_MIN_LEN {
+        generic_copy(dst, src, len)
+    } else {
+        (IMPLEMENTATION.copy)(dst, src, len)
+    }
+}
+
+/// Sets `len` bytes at `dst` to `val`, like `ptr::write_bytes`.
+///
+/// # Safety
+/// - `dst` must be valid for `len` bytes of writes.
+pub(crate) unsafe fn fill(dst: *mut u8, val: u8, len: usize) {
+    if len < VECTORIZED_MIN_LEN {
+        generic_fill(dst, val, len)
+    } else {
+        (IMPLEMENTATION.fill)(dst, val, len)
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", any(feature = "std", target_feature = "avx")))]
+mod avx {
+    use crate::lib::std::ptr;
+    use core::arch::x86_64::*;
+
+    /// The number of bytes moved by each iteration of the loops.
+    const BLOCK: usize = 4 * 32;
+
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn copy(dst: *mut u8, src: *const u8, len: usize) {
+        let blocks = len / BLOCK * BLOCK;
+        // Each block is loaded before being stored, so copying forwards
+        // is correct unless the destination overlaps the end of the
+        // source.
+        if (dst as usize).wrapping_sub(src as usize) >= len {
+            let mut offset = 0;
+            while offset < blocks {
+                copy_block(dst.add(offset), src.add(offset));
+                offset += BLOCK;
+            }
+            ptr::copy(src.add(blocks), dst.add(blocks), len - blocks);
+        } else {
+            let head = len - blocks;
+            let mut offset = len;
+            while offset > head {
+                offset -= BLOCK;
+                copy_block(dst.add(offset), src.add(offset));
+            }
+            ptr::copy(src, dst, head);
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn copy_block(dst: *mut u8, src: *const u8) {
+        let src = src as *const __m256i;
+        let dst = dst as *mut __m256i;
+        let a = _mm256_loadu_si256(src);
+        let b = _mm256_loadu_si256(src.add(1));
+        let c = _mm256_loadu_si256(src.add(2));
+        let d = _mm256_loadu_si256(src.add(3));
+        _mm256_storeu_si256(dst, a);
+        _mm256_storeu_si256(dst.add(1), b);
+        _mm256_storeu_si256(dst.add(2), c);
+        _mm256_storeu_si256(dst.add(3), d);
+    }
+
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn fill(dst: *mut u8, val: u8, len: usize) {
+        let blocks = len / BLOCK * BLOCK;
+        let value = _mm256_set1_epi8(val as i8);
+        let mut offset = 0;
+        while offset < blocks {
+            let block = dst.add(offset) as *mut __m256i;
+            _mm256_storeu_si256(block, value);
+            _mm256_storeu_si256(block.add(1), value);
+            _mm256_storeu_si256(block.add(2), value);
+            _mm256_storeu_si256(block.add(3), value);
+            offset += BLOCK;
+        }
+        ptr::write_bytes(dst.add(blocks), val, len - blocks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LENGTHS: &[usize] = &[0, 1, 255, 256, 300, 4096, 4099];
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 + 3) as u8).collect()
+    }
+
+    #[test]
+    fn copies_match_ptr_copy() {
+        for &len in LENGTHS {
+            // Disjoint ranges, and ranges overlapping both ways.
+            for &(src, dst) in &[(0, 5000), (0, 3), (3, 0), (0, 0), (64, 1)] {
+                let mut expected = pattern(10000);
+                let mut actual = expected.clone();
+                unsafe {
+                    ptr::copy(
+                        expected.as_ptr().add(src),
+                        expected.as_mut_ptr().add(dst),
+                        len,
+                    );
+                    copy(actual.as_mut_ptr().add(dst), actual.as_ptr().add(src), len);
+                }
+                assert!(expected == actual, "len {}, src {}, dst {}", len, src, dst);
+            }
+        }
+    }
+
+    #[test]
+    fn fills_match_ptr_write_bytes() {
+        for &len in LENGTHS {
+            let mut expected = pattern(5000);
+            let mut actual = expected.clone();
+            unsafe {
+                ptr::write_bytes(expected.as_mut_ptr().add(1), 0xab, len);
+                fill(actual.as_mut_ptr().add(1), 0xab, len);
+            }
+            assert!(expected == actual, "len {}", len);
+        }
+    }
+}
@@ -2,13 +2,14 @@
 // Attributions: https://github.com/wasmerio/wasmer/blob/master/ATTRIBUTIONS.md
 
 use crate::global::VMGlobal;
+use crate::lib::std::any::Any;
+use crate::lib::std::boxed::Box;
 use crate::memory::VMMemory;
 use crate::store::InternalStoreHandle;
 use crate::table::VMTable;
 use crate::vmcontext::VMFunctionKind;
 use crate::{MaybeInstanceOwned, VMCallerCheckedAnyfunc};
 use derivative::Derivative;
-use std::any::Any;
 use wasmer_types::FunctionType;
 
 /// The value of an export passed from one instance to another.
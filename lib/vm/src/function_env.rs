@@ -1,5 +1,6 @@
+use crate::lib::std::any::Any;
+use crate::lib::std::boxed::Box;
 use derivative::Derivative;
-use std::any::Any;
 
 /// Underlying FunctionEnvironment used by a `VMFunction`.
 #[derive(Derivative)]
@@ -1,6 +1,6 @@
-use crate::{store::MaybeInstanceOwned, vmcontext::VMGlobalDefinition};
+use crate::lib::std::{cell::UnsafeCell, ptr::NonNull, sync::Arc};
+use crate::vmcontext::VMGlobalDefinition;
 use derivative::Derivative;
-use std::{cell::UnsafeCell, ptr::NonNull};
 use wasmer_types::GlobalType;
 
 /// A Global instance
@@ -9,7 +9,29 @@ use wasmer_types::GlobalType;
 pub struct VMGlobal {
     ty: GlobalType,
     #[derivative(Debug = "ignore")]
-    vm_global_definition: MaybeInstanceOwned<VMGlobalDefinition>,
+    vm_global_definition: Arc<VMSharedGlobalDefinition>,
+}
+
+/// The storage of the value of a [`VMGlobal`], which can be shared with
+/// the handles accessing the global from outside of its store, see
+/// [`VMGlobal::shared_definition`].
+#[derive(Debug)]
+pub struct VMSharedGlobalDefinition(UnsafeCell<VMGlobalDefinition>);
+
+// The definition is only accessed through raw pointers, by the store
+// owning the global, and by the handles with atomic operations.
+unsafe impl Send for VMSharedGlobalDefinition {}
+unsafe impl Sync for VMSharedGlobalDefinition {}
+
+impl VMSharedGlobalDefinition {
+    fn new(definition: VMGlobalDefinition) -> Arc<Self> {
+        Arc::new(Self(UnsafeCell::new(definition)))
+    }
+
+    /// Get a pointer to the definition, valid as long as it's alive.
+    pub fn as_ptr(&self) -> NonNull<VMGlobalDefinition> {
+        unsafe { NonNull::new_unchecked(self.0.get()) }
+    }
 }
 
 impl VMGlobal {
@@ -19,9 +41,7 @@ impl VMGlobal {
             ty: global_type,
             // TODO: Currently all globals are host-owned, we should inline the
             // VMGlobalDefinition in VMContext for instance-defined globals.
-            vm_global_definition: MaybeInstanceOwned::Host(Box::new(UnsafeCell::new(
-                VMGlobalDefinition::new(),
-            ))),
+            vm_global_definition: VMSharedGlobalDefinition::new(VMGlobalDefinition::new()),
         }
     }
 
@@ -35,14 +55,21 @@ impl VMGlobal {
         self.vm_global_definition.as_ptr()
     }
 
+    /// Get the underlying definition used by the generated code, which
+    /// stays allocated as long as the returned reference is alive, even
+    /// after the global is dropped.
+    pub fn shared_definition(&self) -> Arc<VMSharedGlobalDefinition> {
+        self.vm_global_definition.clone()
+    }
+
     /// Copies this global
     pub fn copy_on_write(&self) -> Self {
         unsafe {
             Self {
                 ty: self.ty,
-                vm_global_definition: MaybeInstanceOwned::Host(Box::new(UnsafeCell::new(
+                vm_global_definition: VMSharedGlobalDefinition::new(
                     self.vm_global_definition.as_ptr().as_ref().clone(),
-                ))),
+                ),
             }
         }
     }
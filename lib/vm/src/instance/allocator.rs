@@ -1,13 +1,19 @@
+use super::arena::ArenaSlice;
 use super::{Instance, VMInstance};
-use crate::vmcontext::VMTableDefinition;
-use crate::VMMemoryDefinition;
-use std::alloc::{self, Layout};
-use std::convert::TryFrom;
-use std::mem;
-use std::ptr::{self, NonNull};
+use crate::lib::std::alloc::{self, Layout};
+use crate::lib::std::convert::TryFrom;
+use crate::lib::std::mem;
+use crate::lib::std::ptr::{self, NonNull};
+use crate::lib::std::vec::Vec;
+use crate::metrics::{self, Gauge};
+use crate::pool::{InstancePool, PoolSlot};
+use crate::vmcontext::{VMCallerCheckedAnyfunc, VMContext, VMTableDefinition};
+use crate::{FunctionBodyPtr, VMMemoryDefinition};
 use wasmer_types::entity::EntityRef;
 use wasmer_types::VMOffsets;
-use wasmer_types::{LocalMemoryIndex, LocalTableIndex, ModuleInfo};
+use wasmer_types::{
+    FunctionIndex, LocalFunctionIndex, LocalMemoryIndex, LocalTableIndex, ModuleInfo,
+};
 
 /// This is an intermediate type that manages the raw allocation and
 /// metadata when creating an [`Instance`].
@@ -27,6 +33,11 @@ use wasmer_types::{LocalMemoryIndex, LocalTableIndex, ModuleInfo};
 /// layout to represent the wanted [`Instance`].
 ///
 /// Then we use this layout to allocate an empty `Instance` properly.
+///
+/// The arrays the `Instance` needs for its whole life, like its
+/// `VMCallerCheckedAnyfunc`s, are allocated after the `VMContext` in
+/// the same buffer, see `ArenaLayout`. The buffer is thus the only
+/// allocation of the `Instance` itself, and it's freed at once.
 pub struct InstanceAllocator {
     /// The buffer that will contain the [`Instance`] and dynamic fields.
     instance_ptr: NonNull<Instance>,
@@ -38,21 +49,68 @@ pub struct InstanceAllocator {
     /// the dynamic fields.
     offsets: VMOffsets,
 
+    /// The layout of the arrays allocated after the `VMContext`.
+    arena: ArenaLayout,
+
     /// Whether or not this type has transferred ownership of the
     /// `instance_ptr` buffer. If it has not when being dropped,
     /// the buffer should be freed.
     consumed: bool,
+
+    /// The slot of an [`InstancePool`] holding the buffer, if any. It's
+    /// returned to the pool instead of freeing the buffer.
+    slot: Option<PoolSlot>,
+}
+
+/// The location of the arrays allocated after the `VMContext` in the
+/// `instance_ptr` buffer, and their lengths.
+#[derive(Debug, Clone, Copy)]
+struct ArenaLayout {
+    num_local_functions: usize,
+    num_imported_functions: usize,
+    functions: usize,
+    funcrefs: usize,
+    imported_funcrefs: usize,
+}
+
+impl ArenaLayout {
+    /// Extends `layout` with the arrays for the instances of `module`.
+    fn extend(layout: Layout, module: &ModuleInfo) -> (Layout, Self) {
+        fn array<T>(layout: Layout, len: usize) -> (Layout, usize) {
+            layout
+                .extend(Layout::array::<T>(len).expect("Failed to create a layout for an array"))
+                .expect("Failed to extend the `Instance` layout with an array")
+        }
+
+        let num_imported_functions = module.num_imported_functions;
+        let num_local_functions = module.functions.len() - num_imported_functions;
+        let (layout, functions) = array::<FunctionBodyPtr>(layout, num_local_functions);
+        let (layout, funcrefs) = array::<VMCallerCheckedAnyfunc>(layout, num_local_functions);
+        let (layout, imported_funcrefs) =
+            array::<NonNull<VMCallerCheckedAnyfunc>>(layout, num_imported_functions);
+
+        (
+            layout,
+            Self {
+                num_local_functions,
+                num_imported_functions,
+                functions,
+                funcrefs,
+                imported_funcrefs,
+            },
+        )
+    }
 }
 
 impl Drop for InstanceAllocator {
     fn drop(&mut self) {
-        if !self.consumed {
+        if !self.consumed && self.slot.is_none() {
             // If `consumed` has not been set, then we still have ownership
             // over the buffer and must free it.
             let instance_ptr = self.instance_ptr.as_ptr();
 
             unsafe {
-                std::alloc::dealloc(instance_ptr as *mut u8, self.instance_layout);
+                crate::lib::std::alloc::dealloc(instance_ptr as *mut u8, self.instance_layout);
             }
         }
     }
@@ -74,12 +132,43 @@ impl InstanceAllocator {
         Self,
         Vec<NonNull<VMMemoryDefinition>>,
         Vec<NonNull<VMTableDefinition>>,
+    ) {
+        Self::allocate(module, None)
+    }
+
+    /// Allocates instance data in a slot of `pool`, like
+    /// [`InstanceAllocator::new`].
+    ///
+    /// The data is allocated as usual if it doesn't fit in a slot or if
+    /// the pool is exhausted.
+    pub fn new_in_pool(
+        module: &ModuleInfo,
+        pool: &InstancePool,
+    ) -> (
+        Self,
+        Vec<NonNull<VMMemoryDefinition>>,
+        Vec<NonNull<VMTableDefinition>>,
+    ) {
+        Self::allocate(module, Some(pool))
+    }
+
+    fn allocate(
+        module: &ModuleInfo,
+        pool: Option<&InstancePool>,
+    ) -> (
+        Self,
+        Vec<NonNull<VMMemoryDefinition>>,
+        Vec<NonNull<VMTableDefinition>>,
     ) {
         let offsets = VMOffsets::new(mem::size_of::<usize>() as u8, module);
-        let instance_layout = Self::instance_layout(&offsets);
+        let (instance_layout, arena) = Self::instance_layout(&offsets, module);
 
+        let slot = pool.and_then(|pool| pool.take_instance(instance_layout));
         #[allow(clippy::cast_ptr_alignment)]
-        let instance_ptr = unsafe { alloc::alloc(instance_layout) as *mut Instance };
+        let instance_ptr = match &slot {
+            Some(slot) => slot.as_mut_ptr() as *mut Instance,
+            None => unsafe { alloc::alloc(instance_layout) as *mut Instance },
+        };
 
         let instance_ptr = if let Some(ptr) = NonNull::new(instance_ptr) {
             ptr
@@ -91,7 +180,9 @@ impl InstanceAllocator {
             instance_ptr,
             instance_layout,
             offsets,
+            arena,
             consumed: false,
+            slot,
         };
 
         // # Safety
@@ -104,8 +195,9 @@ impl InstanceAllocator {
         (allocator, memories, tables)
     }
 
-    /// Calculate the appropriate layout for the [`Instance`].
-    fn instance_layout(offsets: &VMOffsets) -> Layout {
+    /// Calculate the appropriate layout for the [`Instance`], and the
+    /// arrays allocated after it.
+    fn instance_layout(offsets: &VMOffsets, module: &ModuleInfo) -> (Layout, ArenaLayout) {
         let vmctx_size = usize::try_from(offsets.size_of_vmctx())
             .expect("Failed to convert the size of `vmctx` to a `usize`");
 
@@ -115,8 +207,68 @@ impl InstanceAllocator {
         let (instance_layout, _offset) = Layout::new::<Instance>()
             .extend(instance_vmctx_layout)
             .expect("Failed to extend to `Instance` layout to include `VMContext`");
+        let (instance_layout, arena) = ArenaLayout::extend(instance_layout, module);
+
+        (instance_layout.pad_to_align(), arena)
+    }
+
+    /// Get the pointer to the `VMContext` of the [`Instance`].
+    pub(crate) fn vmctx_ptr(&self) -> *mut VMContext {
+        unsafe {
+            self.instance_ptr
+                .cast::<u8>()
+                .as_ptr()
+                .offset(Instance::vmctx_offset()) as *mut VMContext
+        }
+    }
+
+    /// Writes `values` in the array at `offset` in the buffer.
+    ///
+    /// # Safety
+    /// - `offset` must be the offset of an array of `len` values of
+    ///   type `V` in `Self.arena`.
+    unsafe fn arena_slice<K: EntityRef, V: Copy>(
+        &self,
+        offset: usize,
+        len: usize,
+        values: impl IntoIterator<Item = V>,
+    ) -> ArenaSlice<K, V> {
+        let ptr = self.instance_ptr.cast::<u8>().as_ptr().add(offset);
+        ArenaSlice::new(NonNull::new_unchecked(ptr).cast(), len, values)
+    }
 
-        instance_layout.pad_to_align()
+    /// Allocates the pointers to the bodies of the local functions.
+    pub(crate) fn functions(
+        &self,
+        values: impl IntoIterator<Item = FunctionBodyPtr>,
+    ) -> ArenaSlice<LocalFunctionIndex, FunctionBodyPtr> {
+        let arena = &self.arena;
+        unsafe { self.arena_slice(arena.functions, arena.num_local_functions, values) }
+    }
+
+    /// Allocates the `VMCallerCheckedAnyfunc`s of the local functions.
+    pub(crate) fn funcrefs(
+        &self,
+        values: impl IntoIterator<Item = VMCallerCheckedAnyfunc>,
+    ) -> ArenaSlice<LocalFunctionIndex, VMCallerCheckedAnyfunc> {
+        let arena = &self.arena;
+        unsafe { self.arena_slice(arena.funcrefs, arena.num_local_functions, values) }
+    }
+
+    /// Allocates the pointers to the `VMCallerCheckedAnyfunc`s of the
+    /// imported functions.
+    pub(crate) fn imported_funcrefs(
+        &self,
+        values: impl IntoIterator<Item = NonNull<VMCallerCheckedAnyfunc>>,
+    ) -> ArenaSlice<FunctionIndex, NonNull<VMCallerCheckedAnyfunc>> {
+        let arena = &self.arena;
+        unsafe {
+            self.arena_slice(
+                arena.imported_funcrefs,
+                arena.num_imported_functions,
+                values,
+            )
+        }
     }
 
     /// Get the locations of where the local [`VMMemoryDefinition`]s should be stored.
@@ -171,7 +323,7 @@ impl InstanceAllocator {
 
         // We need to do some pointer arithmetic now. The unit is `u8`.
         let ptr = self.instance_ptr.cast::<u8>().as_ptr();
-        let base_ptr = ptr.add(std::mem::size_of::<Instance>());
+        let base_ptr = ptr.add(crate::lib::std::mem::size_of::<Instance>());
 
         for i in 0..num_tables {
             let table_offset = self
@@ -205,9 +357,11 @@ impl InstanceAllocator {
 
         // This is correct because of the invariants of `Self` and
         // because we write `Instance` to the pointer in this function.
+        metrics::increase_gauge(Gauge::InstancesAlive, 1);
         VMInstance {
             instance,
             instance_layout,
+            slot: self.slot.take(),
         }
     }
 
@@ -0,0 +1,56 @@
+use crate::lib::std::marker::PhantomData;
+use crate::lib::std::ops::Index;
+use crate::lib::std::ptr::NonNull;
+use crate::lib::std::slice;
+use wasmer_types::entity::EntityRef;
+
+/// An array of `V` indexed by `K`, allocated after the `VMContext` in
+/// the buffer of an [`Instance`](super::Instance) by the
+/// [`InstanceAllocator`](super::InstanceAllocator).
+///
+/// The array lives as long as the buffer, and is freed with it instead
+/// of having an allocation of its own. The values are never dropped.
+pub(crate) struct ArenaSlice<K: EntityRef, V: Copy> {
+    ptr: NonNull<V>,
+    len: usize,
+    unused: PhantomData<K>,
+}
+
+impl<K: EntityRef, V: Copy> ArenaSlice<K, V> {
+    /// Writes `values` in the array of `len` values at `ptr`.
+    ///
+    /// # Safety
+    /// - `ptr` must be valid for writes of `len` values, and stay valid
+    ///   for the lifetime of the returned `ArenaSlice`.
+    pub(crate) unsafe fn new(
+        ptr: NonNull<V>,
+        len: usize,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        let mut written = 0;
+        for value in values {
+            assert!(written < len, "too many values for the array");
+            ptr.as_ptr().add(written).write(value);
+            written += 1;
+        }
+        assert_eq!(written, len, "missing values for the array");
+        Self {
+            ptr,
+            len,
+            unused: PhantomData,
+        }
+    }
+
+    /// Returns the values as a slice.
+    pub(crate) fn as_slice(&self) -> &[V] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<K: EntityRef, V: Copy> Index<K> for ArenaSlice<K, V> {
+    type Output = V;
+
+    fn index(&self, index: K) -> &V {
+        &self.as_slice()[index.index()]
+    }
+}
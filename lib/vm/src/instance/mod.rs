@@ -7,39 +7,48 @@
 //! how it is allocated and deallocated.
 
 mod allocator;
+mod arena;
 
+use self::arena::ArenaSlice;
 use crate::export::VMExtern;
 use crate::imports::Imports;
+use crate::lib::std::alloc::Layout;
+use crate::lib::std::boxed::Box;
+use crate::lib::std::cell::RefCell;
+use crate::lib::std::collections::HashMap;
+use crate::lib::std::convert::TryFrom;
+use crate::lib::std::fmt;
+use crate::lib::std::mem;
+use crate::lib::std::ptr::{self, NonNull};
+use crate::lib::std::slice;
+use crate::lib::std::string::{String, ToString};
+use crate::lib::std::sync::{Arc, Mutex};
+use crate::lib::std::vec::Vec;
+use crate::metrics::{self, Gauge};
+use crate::pool::PoolSlot;
 use crate::store::{InternalStoreHandle, StoreObjects};
 use crate::table::TableElement;
-use crate::trap::{catch_traps, Trap, TrapCode};
+use crate::trap::{wasmer_call_trampoline, Trap, TrapCode};
 use crate::vmcontext::{
     memory32_atomic_check32, memory32_atomic_check64, memory_copy, memory_fill,
     VMBuiltinFunctionsArray, VMCallerCheckedAnyfunc, VMContext, VMFunctionContext,
     VMFunctionImport, VMFunctionKind, VMGlobalDefinition, VMGlobalImport, VMMemoryDefinition,
     VMMemoryImport, VMSharedSignatureIndex, VMTableDefinition, VMTableImport, VMTrampoline,
 };
-use crate::LinearMemory;
-use crate::{FunctionBodyPtr, MaybeInstanceOwned, TrapHandlerFn, VMFunctionBody};
+use crate::{FunctionBodyPtr, MaybeInstanceOwned, TrapHandlerFn};
+use crate::{LazyFunctionCompiler, LinearMemory};
 use crate::{VMFuncRef, VMFunction, VMGlobal, VMMemory, VMTable};
 pub use allocator::InstanceAllocator;
 use memoffset::offset_of;
 use more_asserts::assert_lt;
-use std::alloc::Layout;
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::convert::TryFrom;
-use std::fmt;
-use std::mem;
-use std::ptr::{self, NonNull};
-use std::slice;
-use std::sync::{Arc, Mutex};
+#[cfg(feature = "std")]
 use std::thread::{current, park, park_timeout, Thread};
-use wasmer_types::entity::{packed_option::ReservedValue, BoxedSlice, EntityRef, PrimaryMap};
+use wasmer_types::entity::{packed_option::ReservedValue, BoxedSlice, EntityRef};
 use wasmer_types::{
     DataIndex, DataInitializer, ElemIndex, ExportIndex, FunctionIndex, GlobalIndex, GlobalInit,
     LocalFunctionIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryError,
-    MemoryIndex, ModuleInfo, Pages, SignatureIndex, TableIndex, TableInitializer, VMOffsets,
+    MemoryIndex, ModuleInfo, Pages, RawValue, SignatureIndex, TableIndex, TableInitializer,
+    VMOffsets,
 };
 
 #[derive(Hash, Eq, PartialEq, Clone, Copy)]
@@ -49,6 +58,7 @@ struct NotifyLocation {
 }
 
 struct NotifyWaiter {
+    #[cfg(feature = "std")]
     thread: Thread,
     notified: bool,
 }
@@ -84,10 +94,7 @@ pub(crate) struct Instance {
     globals: BoxedSlice<LocalGlobalIndex, InternalStoreHandle<VMGlobal>>,
 
     /// Pointers to functions in executable memory.
-    functions: BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
-
-    /// Pointers to function call trampolines in executable memory.
-    function_call_trampolines: BoxedSlice<SignatureIndex, VMTrampoline>,
+    functions: ArenaSlice<LocalFunctionIndex, FunctionBodyPtr>,
 
     /// Passive elements in this instantiation. As `elem.drop`s happen, these
     /// entries get removed.
@@ -99,15 +106,18 @@ pub(crate) struct Instance {
 
     /// Mapping of function indices to their func ref backing data. `VMFuncRef`s
     /// will point to elements here for functions defined by this instance.
-    funcrefs: BoxedSlice<LocalFunctionIndex, VMCallerCheckedAnyfunc>,
+    funcrefs: ArenaSlice<LocalFunctionIndex, VMCallerCheckedAnyfunc>,
 
     /// Mapping of function indices to their func ref backing data. `VMFuncRef`s
     /// will point to elements here for functions imported by this instance.
-    imported_funcrefs: BoxedSlice<FunctionIndex, NonNull<VMCallerCheckedAnyfunc>>,
+    imported_funcrefs: ArenaSlice<FunctionIndex, NonNull<VMCallerCheckedAnyfunc>>,
 
     /// The Hasmap with the Notify for the Notify/wait opcodes
     conditions: Arc<Mutex<NotifyMap>>,
 
+    /// The compiler of the functions, if the module is compiled lazily.
+    lazy_functions: Option<Arc<dyn LazyFunctionCompiler>>,
+
     /// Additional context used by compiled WebAssembly code. This
     /// field is last, and represents a dynamically-sized array that
     /// extends beyond the nominal end of the struct (similar to a
@@ -139,6 +149,18 @@ impl Instance {
         &self.module
     }
 
+    /// Get the compiled body of the local function `index` of a module
+    /// compiled lazily, compiling it on its first call.
+    pub(crate) fn lazy_compiled_function(
+        &self,
+        index: LocalFunctionIndex,
+    ) -> Result<FunctionBodyPtr, Trap> {
+        match self.lazy_functions.as_ref() {
+            Some(lazy_functions) => lazy_functions.compiled_function(index),
+            None => Err(Trap::lib(TrapCode::UnreachableCodeReached)),
+        }
+    }
+
     fn context(&self) -> &StoreObjects {
         unsafe { &*self.context }
     }
@@ -326,34 +348,28 @@ impl Instance {
             None => return Ok(()),
         };
 
-        let (callee_address, callee_vmctx) = match self.module.local_func_index(start_index) {
-            Some(local_index) => {
-                let body = self
-                    .functions
-                    .get(local_index)
-                    .expect("function index is out of bounds")
-                    .0;
-                (
-                    body as *const _,
-                    VMFunctionContext {
-                        vmctx: self.vmctx_ptr(),
-                    },
-                )
-            }
-            None => {
-                assert_lt!(start_index.index(), self.module.num_imported_functions);
-                let import = self.imported_function(start_index);
-                (import.body, import.environment)
-            }
+        // The start function is called through its call trampoline, like
+        // the exported functions, so that it runs whether its body is
+        // native code or not, e.g. interpreted.
+        let callee = unsafe {
+            *self
+                .func_ref(start_index)
+                .expect("the start function index is valid")
+                .0
+                .as_ref()
         };
+        // The start function has no parameters nor results.
+        let mut values = [RawValue { i128: 0 }];
 
         // Make the call.
         unsafe {
-            catch_traps(trap_handler, || {
-                mem::transmute::<*const VMFunctionBody, unsafe extern "C" fn(VMFunctionContext)>(
-                    callee_address,
-                )(callee_vmctx)
-            })
+            wasmer_call_trampoline(
+                trap_handler,
+                callee.vmctx,
+                callee.call_trampoline,
+                callee.func_ptr,
+                values.as_mut_ptr() as *mut u8,
+            )
         }
     }
 
@@ -805,6 +821,7 @@ impl Instance {
     // once unparked, the waiter thread will remove it's mark on the HashMap
     // timeout / awake is tracked with a boolean in the HashMap
     // because `park_timeout` doesn't gives any information on why it returns
+    #[cfg(feature = "std")]
     fn do_wait(&mut self, index: u32, dst: u32, timeout: i64) -> u32 {
         // fetch the notifier
         let key = NotifyLocation {
@@ -844,6 +861,13 @@ impl Instance {
         ret
     }
 
+    // Without `std` there are no other threads to notify the waiter, so
+    // the wait times out right away.
+    #[cfg(not(feature = "std"))]
+    fn do_wait(&mut self, _index: u32, _dst: u32, _timeout: i64) -> u32 {
+        2
+    }
+
     /// Perform an Atomic.Wait32
     pub(crate) fn local_memory_wait32(
         &mut self,
@@ -969,6 +993,7 @@ impl Instance {
             for waiter in v {
                 if cnt < count {
                     waiter.notified = true; // mark as was waiked up
+                    #[cfg(feature = "std")]
                     waiter.thread.unpark(); // wakeup!
                     cnt += 1;
                 }
@@ -1023,7 +1048,7 @@ impl Instance {
 ///
 /// This is more or less a public facade of the private `Instance`,
 /// providing useful higher-level API.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug)]
 pub struct VMInstance {
     /// The layout of `Instance` (which can vary).
     instance_layout: Layout,
@@ -1038,8 +1063,20 @@ pub struct VMInstance {
     /// No one in the code has a copy of the `Instance`'s
     /// pointer. `Self` is the only one.
     instance: NonNull<Instance>,
+
+    /// The slot of an `InstancePool` holding the `Instance`, if any.
+    /// The memory is returned to the pool instead of being freed.
+    slot: Option<PoolSlot>,
 }
 
+impl PartialEq for VMInstance {
+    fn eq(&self, other: &Self) -> bool {
+        self.instance_layout == other.instance_layout && self.instance == other.instance
+    }
+}
+
+impl Eq for VMInstance {}
+
 /// VMInstance are created with an InstanceAllocator
 /// and it will "consume" the memory
 /// So the Drop here actualy free it (else it would be leaked)
@@ -1050,9 +1087,13 @@ impl Drop for VMInstance {
         unsafe {
             // Need to drop all the actual Instance members
             instance_ptr.drop_in_place();
-            // And then free the memory allocated for the Instance itself
-            std::alloc::dealloc(instance_ptr as *mut u8, self.instance_layout);
+            // And then free the memory allocated for the Instance itself,
+            // unless it's in a slot which will be returned to its pool
+            if self.slot.is_none() {
+                crate::lib::std::alloc::dealloc(instance_ptr as *mut u8, self.instance_layout);
+            }
         }
+        metrics::decrease_gauge(Gauge::InstancesAlive, 1);
     }
 }
 
@@ -1078,24 +1119,22 @@ impl VMInstance {
     ///   all the local tables.
     /// - The memory at `instance.memories_ptr()` must be initialized with data for
     ///   all the local memories.
+    /// - `lazy_functions` must be given when the `finished_functions` are
+    ///   compile stubs, see [`LazyFunctionCompiler`].
     #[allow(clippy::too_many_arguments)]
     pub unsafe fn new(
         allocator: InstanceAllocator,
         module: Arc<ModuleInfo>,
         context: &mut StoreObjects,
-        finished_functions: BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
-        finished_function_call_trampolines: BoxedSlice<SignatureIndex, VMTrampoline>,
+        finished_functions: &BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
+        finished_function_call_trampolines: &BoxedSlice<SignatureIndex, VMTrampoline>,
         finished_memories: BoxedSlice<LocalMemoryIndex, InternalStoreHandle<VMMemory>>,
         finished_tables: BoxedSlice<LocalTableIndex, InternalStoreHandle<VMTable>>,
         finished_globals: BoxedSlice<LocalGlobalIndex, InternalStoreHandle<VMGlobal>>,
         imports: Imports,
-        vmshared_signatures: BoxedSlice<SignatureIndex, VMSharedSignatureIndex>,
+        vmshared_signatures: &BoxedSlice<SignatureIndex, VMSharedSignatureIndex>,
+        lazy_functions: Option<Arc<dyn LazyFunctionCompiler>>,
     ) -> Result<Self, Trap> {
-        let vmctx_globals = finished_globals
-            .values()
-            .map(|m| m.get(context).vmglobal())
-            .collect::<PrimaryMap<LocalGlobalIndex, _>>()
-            .into_boxed_slice();
         let passive_data = RefCell::new(
             module
                 .passive_data
@@ -1107,9 +1146,15 @@ impl VMInstance {
 
         let handle = {
             let offsets = allocator.offsets().clone();
-            // use dummy value to create an instance so we can get the vmctx pointer
-            let funcrefs = PrimaryMap::new().into_boxed_slice();
-            let imported_funcrefs = PrimaryMap::new().into_boxed_slice();
+            let (funcrefs, imported_funcrefs) = build_funcrefs(
+                &allocator,
+                &module,
+                context,
+                &imports,
+                finished_functions,
+                vmshared_signatures,
+                finished_function_call_trampolines,
+            );
             // Create the `Instance`. The unique, the One.
             let instance = Instance {
                 module,
@@ -1118,8 +1163,7 @@ impl VMInstance {
                 memories: finished_memories,
                 tables: finished_tables,
                 globals: finished_globals,
-                functions: finished_functions,
-                function_call_trampolines: finished_function_call_trampolines,
+                functions: allocator.functions(finished_functions.values().copied()),
                 passive_elements: Default::default(),
                 passive_data,
                 funcrefs,
@@ -1128,26 +1172,10 @@ impl VMInstance {
                 conditions: Arc::new(Mutex::new(NotifyMap {
                     map: HashMap::new(),
                 })),
+                lazy_functions,
             };
 
-            let mut instance_handle = allocator.into_vminstance(instance);
-
-            // Set the funcrefs after we've built the instance
-            {
-                let instance = instance_handle.instance_mut();
-                let vmctx_ptr = instance.vmctx_ptr();
-                (instance.funcrefs, instance.imported_funcrefs) = build_funcrefs(
-                    &instance.module,
-                    context,
-                    &imports,
-                    &instance.functions,
-                    &vmshared_signatures,
-                    &instance.function_call_trampolines,
-                    vmctx_ptr,
-                );
-            }
-
-            instance_handle
+            allocator.into_vminstance(instance)
         };
         let instance = handle.instance();
 
@@ -1179,11 +1207,10 @@ impl VMInstance {
         // these should already be set, add asserts here? for:
         // - instance.tables_ptr() as *mut VMTableDefinition
         // - instance.memories_ptr() as *mut VMMemoryDefinition
-        ptr::copy(
-            vmctx_globals.values().as_slice().as_ptr(),
-            instance.globals_ptr() as *mut NonNull<VMGlobalDefinition>,
-            vmctx_globals.len(),
-        );
+        let globals_ptr = instance.globals_ptr() as *mut NonNull<VMGlobalDefinition>;
+        for (i, global) in instance.globals.values().enumerate() {
+            ptr::write(globals_ptr.add(i), global.get(context).vmglobal());
+        }
         ptr::write(
             instance.builtin_functions_ptr() as *mut VMBuiltinFunctionsArray,
             VMBuiltinFunctionsArray::initialized(),
@@ -1229,6 +1256,54 @@ impl VMInstance {
         Ok(())
     }
 
+    /// Restores the instance to the state it was in right after
+    /// `Instance::new`, to instantiate it again in place.
+    ///
+    /// The memories and the tables defined by the module are shrunk back
+    /// to their minimum size and cleared, the memories giving their pages
+    /// back to the system. The globals, the passive elements and the
+    /// passive data segments are initialized again. The imported entities
+    /// are left untouched.
+    ///
+    /// The reset is completed by calling
+    /// [`VMInstance::finish_instantiation`] again. The instances defining
+    /// a shared memory can't be reset, and are left untouched.
+    ///
+    /// # Safety
+    ///
+    /// The instance must not be running, and no references to the
+    /// contents of its memories and tables may be alive.
+    pub unsafe fn reset(&mut self) -> Result<(), MemoryError> {
+        let instance = self.instance_mut();
+        let module = Arc::clone(&instance.module);
+
+        if instance
+            .memories
+            .values()
+            .any(|memory| memory.get(&*instance.context).ty().shared)
+        {
+            return Err(MemoryError::Generic(
+                "the instances defining a shared memory can't be reset".to_string(),
+            ));
+        }
+        for memory in instance.memories.values() {
+            memory.get_mut(&mut *instance.context).reset()?;
+        }
+        for table in instance.tables.values() {
+            table.get_mut(&mut *instance.context).reset();
+        }
+
+        instance.passive_elements.borrow_mut().clear();
+        initialize_passive_elements(instance);
+        *instance.passive_data.borrow_mut() = module
+            .passive_data
+            .iter()
+            .map(|(idx, bytes)| (*idx, Arc::from(&bytes[..])))
+            .collect();
+        initialize_globals(instance);
+        Ok(())
+    }
+
     /// Return a reference to the vmctx used by compiled wasm code.
     pub fn vmctx(&self) -> &VMContext {
         self.instance().vmctx()
@@ -1562,45 +1637,44 @@ fn initialize_globals(instance: &Instance) {
 
 /// Eagerly builds all the `VMFuncRef`s for imported and local functions so that all
 /// future funcref operations are just looking up this data.
+///
+/// They are allocated in the buffer of the instance, by `allocator`.
 fn build_funcrefs(
+    allocator: &InstanceAllocator,
     module_info: &ModuleInfo,
     ctx: &StoreObjects,
     imports: &Imports,
     finished_functions: &BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
     vmshared_signatures: &BoxedSlice<SignatureIndex, VMSharedSignatureIndex>,
     function_call_trampolines: &BoxedSlice<SignatureIndex, VMTrampoline>,
-    vmctx_ptr: *mut VMContext,
 ) -> (
-    BoxedSlice<LocalFunctionIndex, VMCallerCheckedAnyfunc>,
-    BoxedSlice<FunctionIndex, NonNull<VMCallerCheckedAnyfunc>>,
+    ArenaSlice<LocalFunctionIndex, VMCallerCheckedAnyfunc>,
+    ArenaSlice<FunctionIndex, NonNull<VMCallerCheckedAnyfunc>>,
 ) {
-    let mut func_refs =
-        PrimaryMap::with_capacity(module_info.functions.len() - module_info.num_imported_functions);
-    let mut imported_func_refs = PrimaryMap::with_capacity(module_info.num_imported_functions);
+    let vmctx_ptr = allocator.vmctx_ptr();
 
     // do imported functions
-    for import in imports.functions.values() {
-        imported_func_refs.push(import.handle.get(ctx).anyfunc.as_ptr());
-    }
+    let imported_func_refs = allocator.imported_funcrefs(
+        imports
+            .functions
+            .values()
+            .map(|import| import.handle.get(ctx).anyfunc.as_ptr()),
+    );
 
     // do local functions
-    for (local_index, func_ptr) in finished_functions.iter() {
+    let func_refs = allocator.funcrefs(finished_functions.iter().map(|(local_index, func_ptr)| {
         let index = module_info.func_index(local_index);
         let sig_index = module_info.functions[index];
         let type_index = vmshared_signatures[sig_index];
         let call_trampoline = function_call_trampolines[sig_index];
-        let anyfunc = VMCallerCheckedAnyfunc {
+        VMCallerCheckedAnyfunc {
             func_ptr: func_ptr.0,
             type_index,
             vmctx: VMFunctionContext { vmctx: vmctx_ptr },
             call_trampoline,
-        };
-        func_refs.push(anyfunc);
-    }
-    (
-        func_refs.into_boxed_slice(),
-        imported_func_refs.into_boxed_slice(),
-    )
+        }
+    }));
+    (func_refs, imported_func_refs)
 }
 
 /// This type is deprecated, it has been replaced by VMinstance.
@@ -0,0 +1,22 @@
+//! Support for the modules whose functions are compiled on their first
+//! call.
+
+use crate::trap::Trap;
+use crate::FunctionBodyPtr;
+use wasmer_types::LocalFunctionIndex;
+
+/// Compiles the functions of a module compiled lazily.
+///
+/// The functions of such a module are compile stubs, which call the
+/// `lazy_compile` builtin with the index of their function on each call
+/// and then jump to the body it returns, compiled by the
+/// `LazyFunctionCompiler` of the instance on the first call.
+pub trait LazyFunctionCompiler: Send + Sync {
+    /// Gets the compiled body of the function `index`, compiling it if
+    /// it's called for the first time.
+    ///
+    /// The body is shared by all the instances of the module, so it must
+    /// be compiled once, even when the function is first called by several
+    /// threads at the same time.
+    fn compiled_function(&self, index: LocalFunctionIndex) -> Result<FunctionBodyPtr, Trap>;
+}
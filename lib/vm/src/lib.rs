@@ -3,6 +3,7 @@
 #![deny(missing_docs, trivial_numeric_casts, unused_extern_crates)]
 #![deny(trivial_numeric_casts, unused_extern_crates)]
 #![warn(unused_import_braces)]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(
     feature = "cargo-clippy",
     allow(clippy::new_without_default, clippy::vtable_address_comparisons)
@@ -20,24 +21,71 @@
     )
 )]
 
+#[cfg(all(feature = "std", feature = "core"))]
+compile_error!(
+    "The `std` and `core` features are both enabled, which is an error. Please enable only once."
+);
+
+#[cfg(all(not(feature = "std"), not(feature = "core")))]
+compile_error!("Both the `std` and `core` features are disabled. Please enable one of them.");
+
+#[cfg(feature = "core")]
+extern crate alloc;
+
+mod lib {
+    #[cfg(feature = "core")]
+    pub mod std {
+        pub use ::alloc::{alloc, boxed, format, string, vec};
+        pub use core::{
+            any, cell, cmp, convert, ffi, fmt, hash, marker, mem, num, ops, ptr, slice, u32,
+        };
+
+        pub mod collections {
+            pub use hashbrown::{hash_map, HashMap};
+        }
+
+        pub mod sync {
+            pub use crate::lock::{Mutex, RwLock};
+            pub use ::alloc::sync::Arc;
+            pub use core::sync::atomic;
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub mod std {
+        pub use std::{
+            alloc, any, boxed, cell, cmp, collections, convert, ffi, fmt, format, hash, marker,
+            mem, num, ops, ptr, slice, string, sync, u32, vec,
+        };
+    }
+}
+
+mod bulk;
 mod export;
 mod extern_ref;
 mod function_env;
 mod global;
 mod imports;
 mod instance;
+mod lazy;
+#[cfg(feature = "core")]
+mod lock;
 mod memory;
 mod mmap;
+mod pool;
 mod probestack;
 mod sig_registry;
+mod snapshot;
+mod soft_float;
 mod store;
 mod table;
 mod trap;
 mod vmcontext;
 
 pub mod libcalls;
+pub mod metrics;
 
-use std::ptr::NonNull;
+use crate::lib::std::ptr::NonNull;
 
 pub use crate::export::*;
 pub use crate::extern_ref::{VMExternObj, VMExternRef};
@@ -46,12 +94,15 @@ pub use crate::global::*;
 pub use crate::imports::Imports;
 #[allow(deprecated)]
 pub use crate::instance::{InstanceAllocator, InstanceHandle, VMInstance};
+pub use crate::lazy::LazyFunctionCompiler;
 pub use crate::memory::{
     initialize_memory_with_data, LinearMemory, VMMemory, VMOwnedMemory, VMSharedMemory,
 };
 pub use crate::mmap::Mmap;
+pub use crate::pool::{InstancePool, InstancePoolConfig};
 pub use crate::probestack::PROBESTACK;
 pub use crate::sig_registry::SignatureRegistry;
+pub use crate::snapshot::MemorySnapshot;
 pub use crate::store::{
     InternalStoreHandle, MaybeInstanceOwned, StoreHandle, StoreId, StoreObjects,
 };
@@ -83,7 +134,7 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 #[repr(transparent)]
 pub struct SectionBodyPtr(pub *const u8);
 
-impl std::ops::Deref for SectionBodyPtr {
+impl crate::lib::std::ops::Deref for SectionBodyPtr {
     type Target = *const u8;
 
     fn deref(&self) -> &Self::Target {
@@ -101,7 +152,7 @@ pub struct VMFunctionBody(u8);
 #[cfg(test)]
 mod test_vmfunction_body {
     use super::VMFunctionBody;
-    use std::mem::size_of;
+    use crate::lib::std::mem::size_of;
 
     #[test]
     fn check_vmfunction_body_offsets() {
@@ -114,7 +165,7 @@ mod test_vmfunction_body {
 #[repr(transparent)]
 pub struct FunctionBodyPtr(pub *const VMFunctionBody);
 
-impl std::ops::Deref for FunctionBodyPtr {
+impl crate::lib::std::ops::Deref for FunctionBodyPtr {
     type Target = *const VMFunctionBody;
 
     fn deref(&self) -> &Self::Target {
@@ -38,14 +38,15 @@
 #![allow(missing_docs)] // For some reason lint fails saying that `LibCall` is not documented, when it actually is
 
 use crate::probestack::PROBESTACK;
+use crate::soft_float;
 use crate::table::{RawTableElement, TableElement};
 use crate::trap::{raise_lib_trap, Trap, TrapCode};
 use crate::vmcontext::VMContext;
-use crate::{on_host_stack, VMFuncRef};
+use crate::{on_host_stack, VMFuncRef, VMFunctionBody};
 pub use wasmer_types::LibCall;
 use wasmer_types::{
-    DataIndex, ElemIndex, FunctionIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex,
-    TableIndex, Type,
+    DataIndex, ElemIndex, FunctionIndex, LocalFunctionIndex, LocalMemoryIndex, LocalTableIndex,
+    MemoryIndex, SoftFloatConversion, SoftFloatOp, TableIndex, Type,
 };
 
 /// Implementation of f32.ceil
@@ -815,6 +816,98 @@ pub unsafe extern "C" fn wasmer_vm_imported_memory32_atomic_notify(
     result.unwrap()
 }
 
+/// Implementation of f32 arithmetic for soft-float code.
+///
+/// Operands and result are passed as raw bit patterns, so calling it
+/// doesn't require any floating point registers, and the operation is
+/// computed with integer arithmetic only. It follows the WebAssembly
+/// semantics, including the handling of NaNs and signed zeros by `min`
+/// and `max`. Comparisons return `1` or `0`.
+///
+/// # Safety
+///
+/// To be called from compiled code only. An invalid `op` raises a trap.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_vm_f32_soft_op(
+    _vmctx: *mut VMContext,
+    op: u32,
+    a: u32,
+    b: u32,
+) -> u32 {
+    match SoftFloatOp::from_u32(op) {
+        Some(op) => soft_float::operation(soft_float::F32, op, a as u64, b as u64) as u32,
+        None => raise_lib_trap(Trap::lib(TrapCode::UnreachableCodeReached)),
+    }
+}
+
+/// Implementation of f64 arithmetic for soft-float code.
+///
+/// See [`wasmer_vm_f32_soft_op`].
+///
+/// # Safety
+///
+/// To be called from compiled code only. An invalid `op` raises a trap.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_vm_f64_soft_op(
+    _vmctx: *mut VMContext,
+    op: u32,
+    a: u64,
+    b: u64,
+) -> u64 {
+    match SoftFloatOp::from_u32(op) {
+        Some(op) => soft_float::operation(soft_float::F64, op, a, b),
+        None => raise_lib_trap(Trap::lib(TrapCode::UnreachableCodeReached)),
+    }
+}
+
+/// Implementation of the conversions from and to floats for soft-float
+/// code.
+///
+/// The operand and the result are passed as raw bit patterns, zero-extended
+/// to 64 bits. Like the native instructions, the trapping truncations raise
+/// a trap when the operand is NaN or out of the range of the result type.
+///
+/// # Safety
+///
+/// To be called from compiled code only. An invalid `op` raises a trap.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_vm_soft_float_convert(
+    _vmctx: *mut VMContext,
+    op: u32,
+    x: u64,
+) -> u64 {
+    let result = SoftFloatConversion::from_u32(op)
+        .ok_or(TrapCode::UnreachableCodeReached)
+        .and_then(|op| soft_float::conversion(op, x));
+    match result {
+        Ok(bits) => bits,
+        Err(trap_code) => raise_lib_trap(Trap::lib(trap_code)),
+    }
+}
+
+/// Implementation of the compile stubs of the lazily compiled functions:
+/// gets the body of the function `function_index`, compiling it on its
+/// first call.
+///
+/// # Safety
+///
+/// `vmctx` must be dereferenceable.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_vm_lazy_compile(
+    vmctx: *mut VMContext,
+    function_index: u32,
+) -> *const VMFunctionBody {
+    // Compiling a function may need more stack than the Wasm stack has left.
+    let result = on_host_stack(|| {
+        let instance = (*vmctx).instance();
+        instance.lazy_compiled_function(LocalFunctionIndex::from_u32(function_index))
+    });
+    match result {
+        Ok(body) => *body,
+        Err(trap) => raise_lib_trap(trap),
+    }
+}
+
 /// The function pointer to a libcall
 pub fn function_pointer(libcall: LibCall) -> usize {
     match libcall {
@@ -855,5 +948,8 @@ pub fn function_pointer(libcall: LibCall) -> usize {
         LibCall::ImportedMemory32AtomicWait64 => wasmer_vm_imported_memory32_atomic_wait64 as usize,
         LibCall::Memory32AtomicNotify => wasmer_vm_memory32_atomic_notify as usize,
         LibCall::ImportedMemory32AtomicNotify => wasmer_vm_imported_memory32_atomic_notify as usize,
+        LibCall::F32SoftOp => wasmer_vm_f32_soft_op as usize,
+        LibCall::F64SoftOp => wasmer_vm_f64_soft_op as usize,
+        LibCall::SoftFloatConvert => wasmer_vm_soft_float_convert as usize,
     }
 }
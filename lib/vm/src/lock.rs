@@ -0,0 +1,49 @@
+//! The locks of `crate::lib::std::sync`, on top of spin locks, for the builds
+//! without `std`.
+//!
+//! They keep the interface of the `std` locks, so that the rest of the
+//! crate locks them the same way with both builds. Spin locks can't be
+//! poisoned, so locking them never fails.
+
+use core::convert::Infallible;
+
+/// A mutual exclusion lock, see `crate::lib::std::sync::Mutex`.
+#[derive(Debug, Default)]
+pub struct Mutex<T: ?Sized>(spin::Mutex<T>);
+
+impl<T> Mutex<T> {
+    /// Creates a new unlocked mutex.
+    pub const fn new(value: T) -> Self {
+        Self(spin::Mutex::new(value))
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Acquires the mutex, spinning until it's available.
+    pub fn lock(&self) -> Result<spin::MutexGuard<'_, T>, Infallible> {
+        Ok(self.0.lock())
+    }
+}
+
+/// A reader-writer lock, see `crate::lib::std::sync::RwLock`.
+#[derive(Debug, Default)]
+pub struct RwLock<T: ?Sized>(spin::RwLock<T>);
+
+impl<T> RwLock<T> {
+    /// Creates a new unlocked reader-writer lock.
+    pub const fn new(value: T) -> Self {
+        Self(spin::RwLock::new(value))
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Locks with shared read access, spinning until it's available.
+    pub fn read(&self) -> Result<spin::RwLockReadGuard<'_, T>, Infallible> {
+        Ok(self.0.read())
+    }
+
+    /// Locks with exclusive write access, spinning until it's available.
+    pub fn write(&self) -> Result<spin::RwLockWriteGuard<'_, T>, Infallible> {
+        Ok(self.0.write())
+    }
+}
@@ -5,14 +5,20 @@
 //!
 //! `Memory` is to WebAssembly linear memories what `Table` is to WebAssembly tables.
 
+use crate::lib::std::boxed::Box;
+use crate::lib::std::cell::UnsafeCell;
+use crate::lib::std::convert::TryInto;
+use crate::lib::std::format;
+use crate::lib::std::ptr::NonNull;
+use crate::lib::std::slice;
+use crate::lib::std::string::ToString;
+use crate::lib::std::sync::{Arc, RwLock};
 use crate::trap::Trap;
-use crate::{mmap::Mmap, store::MaybeInstanceOwned, vmcontext::VMMemoryDefinition};
+use crate::{
+    bulk, mmap::Mmap, pool::InstancePool, snapshot::MemorySnapshot, store::MaybeInstanceOwned,
+    vmcontext::VMMemoryDefinition,
+};
 use more_asserts::assert_ge;
-use std::cell::UnsafeCell;
-use std::convert::TryInto;
-use std::ptr::NonNull;
-use std::slice;
-use std::sync::{Arc, RwLock};
 use wasmer_types::{Bytes, MemoryError, MemoryStyle, MemoryType, Pages};
 
 // The memory mapped area
@@ -117,6 +123,24 @@ impl WasmMmap {
         Ok(prev_pages)
     }
 
+    /// Shrinks the memory back to `size`, zeroed, releasing the pages of
+    /// the mapping and the snapshots mapped in it.
+    fn reset(&mut self, size: Pages) -> Result<(), MemoryError> {
+        self.alloc
+            .reset(size.bytes().0)
+            .map_err(MemoryError::Region)?;
+        self.size = size;
+
+        // update memory definition
+        unsafe {
+            let mut md_ptr = self.vm_memory_definition.as_ptr();
+            let md = md_ptr.as_mut();
+            md.current_length = size.bytes().0;
+            md.base = self.alloc.as_mut_ptr() as _;
+        }
+        Ok(())
+    }
+
     /// Copies the memory
     /// (in this case it performs a copy-on-write to save memory)
     pub fn duplicate(&mut self) -> Result<Self, MemoryError> {
@@ -184,7 +208,7 @@ impl VMOwnedMemory {
     /// This creates a `Memory` with owned metadata: this can be used to create a memory
     /// that will be imported into Wasm modules.
     pub fn new(memory: &MemoryType, style: &MemoryStyle) -> Result<Self, MemoryError> {
-        unsafe { Self::new_internal(memory, style, None) }
+        unsafe { Self::new_internal(memory, style, None, None) }
     }
 
     /// Create a new linear memory instance with specified minimum and maximum number of wasm pages.
@@ -199,7 +223,24 @@ impl VMOwnedMemory {
         style: &MemoryStyle,
         vm_memory_location: NonNull<VMMemoryDefinition>,
     ) -> Result<Self, MemoryError> {
-        Self::new_internal(memory, style, Some(vm_memory_location))
+        Self::new_internal(memory, style, Some(vm_memory_location), None)
+    }
+
+    /// Create a new linear memory instance in a slot of `pool`, like
+    /// [`VMOwnedMemory::from_definition`].
+    ///
+    /// The memory is allocated as usual if it doesn't fit in a slot or if
+    /// the pool is exhausted.
+    ///
+    /// # Safety
+    /// - `vm_memory_location` must point to a valid location in VM memory.
+    pub unsafe fn from_definition_in_pool(
+        memory: &MemoryType,
+        style: &MemoryStyle,
+        vm_memory_location: NonNull<VMMemoryDefinition>,
+        pool: &InstancePool,
+    ) -> Result<Self, MemoryError> {
+        Self::new_internal(memory, style, Some(vm_memory_location), Some(pool))
     }
 
     /// Build a `Memory` with either self-owned or VM owned metadata.
@@ -207,6 +248,7 @@ impl VMOwnedMemory {
         memory: &MemoryType,
         style: &MemoryStyle,
         vm_memory_location: Option<NonNull<VMMemoryDefinition>>,
+        pool: Option<&InstancePool>,
     ) -> Result<Self, MemoryError> {
         if memory.minimum > Pages::max_value() {
             return Err(MemoryError::MinimumMemoryTooLarge {
@@ -232,6 +274,16 @@ impl VMOwnedMemory {
             }
         }
 
+        // Without `std` the memories are allocated on the heap, without
+        // guard pages, so all their accesses must be bounds-checked.
+        #[cfg(not(feature = "std"))]
+        if matches!(style, MemoryStyle::Static { .. }) || style.offset_guard_size() != 0 {
+            return Err(MemoryError::InvalidMemory {
+                reason: "only the dynamic memories without guard pages are supported without `std`"
+                    .to_string(),
+            });
+        }
+
         let offset_guard_bytes = style.offset_guard_size() as usize;
 
         let minimum_pages = match style {
@@ -246,8 +298,17 @@ impl VMOwnedMemory {
         let mapped_pages = memory.minimum;
         let mapped_bytes = mapped_pages.bytes();
 
-        let mut alloc = Mmap::accessible_reserved(mapped_bytes.0, request_bytes)
-            .map_err(MemoryError::Region)?;
+        let pooled = match pool {
+            Some(pool) => pool
+                .take_memory(mapped_bytes.0, request_bytes)
+                .map_err(MemoryError::Region)?,
+            None => None,
+        };
+        let mut alloc = match pooled {
+            Some(alloc) => alloc,
+            None => Mmap::accessible_reserved(mapped_bytes.0, request_bytes)
+                .map_err(MemoryError::Region)?,
+        };
         let base_ptr = alloc.as_mut_ptr();
         let mem_length = memory.minimum.bytes().0;
         let mmap = WasmMmap {
@@ -337,6 +398,17 @@ impl LinearMemory for VMOwnedMemory {
         let forked = Self::duplicate(self)?;
         Ok(Box::new(forked))
     }
+
+    /// Restores the contents of a snapshot, mapping it copy-on-write
+    fn restore_snapshot(&mut self, snapshot: &MemorySnapshot) -> Result<(), MemoryError> {
+        snapshot.grow_memory(self)?;
+        unsafe { snapshot.map_to(self.vmmemory().as_ref()) }
+    }
+
+    /// Resets the memory to its minimum size, zeroed
+    fn reset(&mut self) -> Result<(), MemoryError> {
+        self.mmap.reset(self.config.memory.minimum)
+    }
 }
 
 /// A shared linear memory instance.
@@ -431,6 +503,19 @@ impl LinearMemory for VMSharedMemory {
         let forked = Self::duplicate(self)?;
         Ok(Box::new(forked))
     }
+
+    /// Restores the contents of a snapshot, mapping it copy-on-write
+    fn restore_snapshot(&mut self, snapshot: &MemorySnapshot) -> Result<(), MemoryError> {
+        snapshot.grow_memory(self)?;
+        unsafe { snapshot.map_to(self.vmmemory().as_ref()) }
+    }
+
+    /// Shared memories can't be reset, as other threads may be using them
+    fn reset(&mut self) -> Result<(), MemoryError> {
+        Err(MemoryError::Generic(
+            "shared memories can't be reset".to_string(),
+        ))
+    }
 }
 
 impl From<VMOwnedMemory> for VMMemory {
@@ -498,6 +583,16 @@ impl LinearMemory for VMMemory {
     fn duplicate(&mut self) -> Result<Box<dyn LinearMemory + 'static>, MemoryError> {
         self.0.duplicate()
     }
+
+    /// Restores the contents of a snapshot
+    fn restore_snapshot(&mut self, snapshot: &MemorySnapshot) -> Result<(), MemoryError> {
+        self.0.restore_snapshot(snapshot)
+    }
+
+    /// Resets the memory to its minimum size, zeroed
+    fn reset(&mut self) -> Result<(), MemoryError> {
+        self.0.reset()
+    }
 }
 
 impl VMMemory {
@@ -546,6 +641,26 @@ impl VMMemory {
         })
     }
 
+    /// Create a new linear memory instance in a slot of `pool`, like
+    /// [`VMMemory::from_definition`].
+    ///
+    /// # Safety
+    /// - `vm_memory_location` must point to a valid location in VM memory.
+    pub unsafe fn from_definition_in_pool(
+        memory: &MemoryType,
+        style: &MemoryStyle,
+        vm_memory_location: NonNull<VMMemoryDefinition>,
+        pool: &InstancePool,
+    ) -> Result<Self, MemoryError> {
+        let memory_in_pool =
+            VMOwnedMemory::from_definition_in_pool(memory, style, vm_memory_location, pool)?;
+        Ok(if memory.shared {
+            Self(Box::new(memory_in_pool.to_shared()))
+        } else {
+            Self(Box::new(memory_in_pool))
+        })
+    }
+
     /// Creates VMMemory from a custom implementation - the following into implementations
     /// are natively supported
     /// - VMOwnedMemory -> VMMemory
@@ -573,7 +688,7 @@ pub unsafe fn initialize_memory_with_data(
     let mem_slice = slice::from_raw_parts_mut(memory.base, memory.current_length);
     let end = start + data.len();
     let to_init = &mut mem_slice[start..end];
-    to_init.copy_from_slice(data);
+    bulk::copy(to_init.as_mut_ptr(), data.as_ptr(), data.len());
 
     Ok(())
 }
@@ -581,7 +696,7 @@ pub unsafe fn initialize_memory_with_data(
 /// Represents memory that is used by the WebAsssembly module
 pub trait LinearMemory
 where
-    Self: std::fmt::Debug + Send,
+    Self: crate::lib::std::fmt::Debug + Send,
 {
     /// Returns the type for this memory.
     fn ty(&self) -> MemoryType;
@@ -616,4 +731,20 @@ where
 
     /// Copies this memory to a new memory
     fn duplicate(&mut self) -> Result<Box<dyn LinearMemory + 'static>, MemoryError>;
+
+    /// Restores the contents of `snapshot`, growing the memory to its
+    /// size if needed and zeroing the rest of the memory
+    fn restore_snapshot(&mut self, snapshot: &MemorySnapshot) -> Result<(), MemoryError> {
+        snapshot.grow_memory(self)?;
+        unsafe { snapshot.copy_to(self.vmmemory().as_ref()) };
+        Ok(())
+    }
+
+    /// Resets the memory to the size it was created with, zeroed,
+    /// releasing the memory it grew by
+    fn reset(&mut self) -> Result<(), MemoryError> {
+        Err(MemoryError::Generic(
+            "this memory doesn't support being reset".to_string(),
+        ))
+    }
 }
@@ -0,0 +1,128 @@
+//! Hooks reporting the activity of the runtime, for the embedders to
+//! bridge it to a metrics system like Prometheus or statsd.
+//!
+//! The runtime increments the [`Counter`]s and updates the [`Gauge`]s
+//! of the whole process, and reports them to the [`Metrics`] sink
+//! installed with [`set_metrics`]. The values of the gauges can also be
+//! read at any time with [`gauge`].
+
+use crate::lib::std::sync::atomic::{AtomicU64, Ordering};
+use crate::lib::std::sync::{Arc, RwLock};
+use wasmer_types::TrapCode;
+
+/// A value which only increases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Counter {
+    /// A module was compiled.
+    Compilations,
+    /// A module was found in a cache.
+    CacheHits,
+    /// A module wasn't found in a cache.
+    CacheMisses,
+    /// A Wasm function trapped with the given code.
+    Traps(TrapCode),
+}
+
+/// A value which can increase and decrease.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Gauge {
+    /// The number of instances which haven't been dropped yet.
+    InstancesAlive,
+    /// The number of bytes mapped for the memories, tables and code,
+    /// including the reserved guard pages.
+    MemoryMapped,
+}
+
+impl Counter {
+    /// The conventional name of the counter, without its labels.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Compilations => "wasmer_compilations_total",
+            Self::CacheHits => "wasmer_cache_hits_total",
+            Self::CacheMisses => "wasmer_cache_misses_total",
+            Self::Traps(_) => "wasmer_traps_total",
+        }
+    }
+}
+
+impl Gauge {
+    /// The conventional name of the gauge.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::InstancesAlive => "wasmer_instances_alive",
+            Self::MemoryMapped => "wasmer_memory_mapped_bytes",
+        }
+    }
+
+    fn value(&self) -> &'static AtomicU64 {
+        match self {
+            Self::InstancesAlive => &INSTANCES_ALIVE,
+            Self::MemoryMapped => &MEMORY_MAPPED,
+        }
+    }
+}
+
+/// A sink of the runtime metrics.
+///
+/// The methods are called from the threads running the runtime, so
+/// they should return quickly.
+pub trait Metrics: Send + Sync {
+    /// Called when `counter` is incremented by `value`.
+    fn increment_counter(&self, counter: Counter, value: u64);
+
+    /// Called when `gauge` changes to `value`.
+    fn set_gauge(&self, gauge: Gauge, value: u64);
+}
+
+static INSTANCES_ALIVE: AtomicU64 = AtomicU64::new(0);
+static MEMORY_MAPPED: AtomicU64 = AtomicU64::new(0);
+
+lazy_static::lazy_static! {
+    static ref METRICS: RwLock<Option<Arc<dyn Metrics>>> = RwLock::new(None);
+}
+
+/// Reports the metrics of the runtime to `metrics` from now on,
+/// replacing the previous sink.
+///
+/// The current values of the gauges are reported to it right away.
+pub fn set_metrics(metrics: Arc<dyn Metrics>) {
+    for gauge in [Gauge::InstancesAlive, Gauge::MemoryMapped] {
+        metrics.set_gauge(gauge, self::gauge(gauge));
+    }
+    *METRICS.write().unwrap() = Some(metrics);
+}
+
+/// Stops reporting the metrics of the runtime.
+pub fn clear_metrics() {
+    *METRICS.write().unwrap() = None;
+}
+
+/// The current value of `gauge`.
+pub fn gauge(gauge: Gauge) -> u64 {
+    gauge.value().load(Ordering::SeqCst)
+}
+
+/// Increments `counter` by one.
+pub fn increment_counter(counter: Counter) {
+    if let Some(metrics) = METRICS.read().unwrap().as_ref() {
+        metrics.increment_counter(counter, 1);
+    }
+}
+
+/// Increases `gauge` by `delta`.
+pub(crate) fn increase_gauge(gauge: Gauge, delta: u64) {
+    let value = gauge.value().fetch_add(delta, Ordering::SeqCst) + delta;
+    report_gauge(gauge, value);
+}
+
+/// Decreases `gauge` by `delta`.
+pub(crate) fn decrease_gauge(gauge: Gauge, delta: u64) {
+    let value = gauge.value().fetch_sub(delta, Ordering::SeqCst) - delta;
+    report_gauge(gauge, value);
+}
+
+fn report_gauge(gauge: Gauge, value: u64) {
+    if let Some(metrics) = METRICS.read().unwrap().as_ref() {
+        metrics.set_gauge(gauge, value);
+    }
+}
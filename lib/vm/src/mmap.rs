@@ -4,11 +4,34 @@
 //! Low-level abstraction for allocating and managing zero-filled pages
 //! of memory.
 
+#[cfg(not(feature = "std"))]
+use crate::lib::std::alloc::{self, Layout};
+#[cfg(not(feature = "std"))]
+use crate::lib::std::format;
+#[cfg(feature = "std")]
+use crate::lib::std::ptr;
+use crate::lib::std::slice;
+use crate::lib::std::string::{String, ToString};
+use crate::lib::std::vec::Vec;
+use crate::metrics::{self, Gauge};
+use crate::pool::{self, PoolSlot};
 use more_asserts::assert_le;
 use more_asserts::assert_lt;
+#[cfg(feature = "std")]
 use std::io;
-use std::ptr;
-use std::slice;
+
+/// The size of the pages of the mappings.
+#[cfg(feature = "std")]
+pub(crate) fn page_size() -> usize {
+    region::page::size()
+}
+
+/// The size of the pages of the mappings. Without `std` they are
+/// allocated on the heap, aligned to this size.
+#[cfg(not(feature = "std"))]
+pub(crate) fn page_size() -> usize {
+    0x1000
+}
 
 /// Round `size` up to the nearest multiple of `page_size`.
 fn round_up_to_page_size(size: usize, page_size: usize) -> usize {
@@ -26,6 +49,9 @@ pub struct Mmap {
     ptr: usize,
     total_size: usize,
     accessible_size: usize,
+    // The slot of an `InstancePool` holding the mapping, if any. It's
+    // returned to the pool instead of being unmapped.
+    slot: Option<PoolSlot>,
 }
 
 impl Mmap {
@@ -39,25 +65,51 @@ impl Mmap {
             ptr: empty.as_ptr() as usize,
             total_size: 0,
             accessible_size: 0,
+            slot: None,
         }
     }
 
     /// Create a new `Mmap` pointing to at least `size` bytes of page-aligned accessible memory.
     pub fn with_at_least(size: usize) -> Result<Self, String> {
-        let page_size = region::page::size();
+        let page_size = page_size();
         let rounded_size = round_up_to_page_size(size, page_size);
         Self::accessible_reserved(rounded_size, rounded_size)
     }
 
+    /// Create a new `Mmap` in a slot of an `InstancePool`, like
+    /// [`Mmap::accessible_reserved`]. The mapping must fit in the slot.
+    pub(crate) fn from_slot(
+        slot: PoolSlot,
+        accessible_size: usize,
+        mapping_size: usize,
+    ) -> Result<Self, String> {
+        let page_size = page_size();
+        assert_le!(accessible_size, mapping_size);
+        assert_eq!(mapping_size & (page_size - 1), 0);
+        assert_eq!(accessible_size & (page_size - 1), 0);
+
+        let ptr = slot.as_mut_ptr();
+        if accessible_size != 0 {
+            pool::commit(ptr, accessible_size)?;
+        }
+
+        Ok(Self {
+            ptr: ptr as usize,
+            total_size: mapping_size,
+            accessible_size,
+            slot: Some(slot),
+        })
+    }
+
     /// Create a new `Mmap` pointing to `accessible_size` bytes of page-aligned accessible memory,
     /// within a reserved mapping of `mapping_size` bytes. `accessible_size` and `mapping_size`
     /// must be native page-size multiples.
-    #[cfg(not(target_os = "windows"))]
+    #[cfg(all(feature = "std", not(target_os = "windows")))]
     pub fn accessible_reserved(
         accessible_size: usize,
         mapping_size: usize,
     ) -> Result<Self, String> {
-        let page_size = region::page::size();
+        let page_size = page_size();
         assert_le!(accessible_size, mapping_size);
         assert_eq!(mapping_size & (page_size - 1), 0);
         assert_eq!(accessible_size & (page_size - 1), 0);
@@ -83,11 +135,13 @@ impl Mmap {
             if ptr as isize == -1_isize {
                 return Err(io::Error::last_os_error().to_string());
             }
+            metrics::increase_gauge(Gauge::MemoryMapped, mapping_size as u64);
 
             Self {
                 ptr: ptr as usize,
                 total_size: mapping_size,
                 accessible_size,
+                slot: None,
             }
         } else {
             // Reserve the mapping size.
@@ -104,11 +158,13 @@ impl Mmap {
             if ptr as isize == -1_isize {
                 return Err(io::Error::last_os_error().to_string());
             }
+            metrics::increase_gauge(Gauge::MemoryMapped, mapping_size as u64);
 
             let mut result = Self {
                 ptr: ptr as usize,
                 total_size: mapping_size,
                 accessible_size,
+                slot: None,
             };
 
             if accessible_size != 0 {
@@ -123,7 +179,7 @@ impl Mmap {
     /// Create a new `Mmap` pointing to `accessible_size` bytes of page-aligned accessible memory,
     /// within a reserved mapping of `mapping_size` bytes. `accessible_size` and `mapping_size`
     /// must be native page-size multiples.
-    #[cfg(target_os = "windows")]
+    #[cfg(all(feature = "std", target_os = "windows"))]
     pub fn accessible_reserved(
         accessible_size: usize,
         mapping_size: usize,
@@ -131,7 +187,7 @@ impl Mmap {
         use winapi::um::memoryapi::VirtualAlloc;
         use winapi::um::winnt::{MEM_COMMIT, MEM_RESERVE, PAGE_NOACCESS, PAGE_READWRITE};
 
-        let page_size = region::page::size();
+        let page_size = page_size();
         assert_le!(accessible_size, mapping_size);
         assert_eq!(mapping_size & (page_size - 1), 0);
         assert_eq!(accessible_size & (page_size - 1), 0);
@@ -155,11 +211,13 @@ impl Mmap {
             if ptr.is_null() {
                 return Err(io::Error::last_os_error().to_string());
             }
+            metrics::increase_gauge(Gauge::MemoryMapped, mapping_size as u64);
 
             Self {
                 ptr: ptr as usize,
                 total_size: mapping_size,
                 accessible_size,
+                slot: None,
             }
         } else {
             // Reserve the mapping size.
@@ -168,11 +226,13 @@ impl Mmap {
             if ptr.is_null() {
                 return Err(io::Error::last_os_error().to_string());
             }
+            metrics::increase_gauge(Gauge::MemoryMapped, mapping_size as u64);
 
             let mut result = Self {
                 ptr: ptr as usize,
                 total_size: mapping_size,
                 accessible_size,
+                slot: None,
             };
 
             if accessible_size != 0 {
@@ -184,12 +244,47 @@ impl Mmap {
         })
     }
 
+    /// Create a new `Mmap` pointing to `accessible_size` bytes of page-aligned accessible memory,
+    /// within a reserved mapping of `mapping_size` bytes. `accessible_size` and `mapping_size`
+    /// must be native page-size multiples.
+    ///
+    /// Without `std` there is no virtual memory to reserve: the whole
+    /// mapping is allocated on the heap, zeroed and accessible.
+    #[cfg(not(feature = "std"))]
+    pub fn accessible_reserved(
+        accessible_size: usize,
+        mapping_size: usize,
+    ) -> Result<Self, String> {
+        let page_size = page_size();
+        assert_le!(accessible_size, mapping_size);
+        assert_eq!(mapping_size & (page_size - 1), 0);
+        assert_eq!(accessible_size & (page_size - 1), 0);
+
+        if mapping_size == 0 {
+            return Ok(Self::new());
+        }
+
+        let layout = Layout::from_size_align(mapping_size, page_size).map_err(|e| e.to_string())?;
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        if ptr.is_null() {
+            return Err(format!("failed to allocate {} bytes", mapping_size));
+        }
+        metrics::increase_gauge(Gauge::MemoryMapped, mapping_size as u64);
+
+        Ok(Self {
+            ptr: ptr as usize,
+            total_size: mapping_size,
+            accessible_size,
+            slot: None,
+        })
+    }
+
     /// Make the memory starting at `start` and extending for `len` bytes accessible.
     /// `start` and `len` must be native page-size multiples and describe a range within
     /// `self`'s reserved memory.
-    #[cfg(not(target_os = "windows"))]
+    #[cfg(all(feature = "std", not(target_os = "windows")))]
     pub fn make_accessible(&mut self, start: usize, len: usize) -> Result<(), String> {
-        let page_size = region::page::size();
+        let page_size = page_size();
         assert_eq!(start & (page_size - 1), 0);
         assert_eq!(len & (page_size - 1), 0);
         assert_lt!(len, self.total_size);
@@ -204,12 +299,12 @@ impl Mmap {
     /// Make the memory starting at `start` and extending for `len` bytes accessible.
     /// `start` and `len` must be native page-size multiples and describe a range within
     /// `self`'s reserved memory.
-    #[cfg(target_os = "windows")]
+    #[cfg(all(feature = "std", target_os = "windows"))]
     pub fn make_accessible(&mut self, start: usize, len: usize) -> Result<(), String> {
         use winapi::ctypes::c_void;
         use winapi::um::memoryapi::VirtualAlloc;
         use winapi::um::winnt::{MEM_COMMIT, PAGE_READWRITE};
-        let page_size = region::page::size();
+        let page_size = page_size();
         assert_eq!(start & (page_size - 1), 0);
         assert_eq!(len & (page_size - 1), 0);
         assert_lt!(len, self.len());
@@ -233,6 +328,41 @@ impl Mmap {
         Ok(())
     }
 
+    /// Make the memory starting at `start` and extending for `len` bytes accessible.
+    /// `start` and `len` must be native page-size multiples and describe a range within
+    /// `self`'s reserved memory.
+    ///
+    /// Without `std` the whole mapping is already accessible.
+    #[cfg(not(feature = "std"))]
+    pub fn make_accessible(&mut self, start: usize, len: usize) -> Result<(), String> {
+        let page_size = page_size();
+        assert_eq!(start & (page_size - 1), 0);
+        assert_eq!(len & (page_size - 1), 0);
+        assert_lt!(len, self.total_size);
+        assert_lt!(start, self.total_size - len);
+        Ok(())
+    }
+
+    /// Releases all the memory, and makes the first `accessible_size`
+    /// bytes accessible again, zeroed. `accessible_size` must be a native
+    /// page-size multiple within `self`'s reserved memory.
+    pub fn reset(&mut self, accessible_size: usize) -> Result<(), String> {
+        let page_size = page_size();
+        assert_eq!(accessible_size & (page_size - 1), 0);
+        assert_le!(accessible_size, self.total_size);
+
+        if self.total_size == 0 {
+            return Ok(());
+        }
+        let ptr = self.ptr as *mut u8;
+        pool::decommit(ptr, self.total_size)?;
+        if accessible_size != 0 {
+            pool::commit(ptr, accessible_size)?;
+        }
+        self.accessible_size = accessible_size;
+        Ok(())
+    }
+
     /// Return the allocated memory as a slice of u8.
     pub fn as_slice(&self) -> &[u8] {
         unsafe { slice::from_raw_parts(self.ptr as *const u8, self.total_size) }
@@ -283,22 +413,45 @@ impl Mmap {
 }
 
 impl Drop for Mmap {
-    #[cfg(not(target_os = "windows"))]
+    #[cfg(all(feature = "std", not(target_os = "windows")))]
     fn drop(&mut self) {
+        // The slot resets the mapping when it's dropped.
+        if self.slot.is_some() {
+            return;
+        }
         if self.total_size != 0 {
             let r = unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.total_size) };
             assert_eq!(r, 0, "munmap failed: {}", io::Error::last_os_error());
+            metrics::decrease_gauge(Gauge::MemoryMapped, self.total_size as u64);
         }
     }
 
-    #[cfg(target_os = "windows")]
+    #[cfg(all(feature = "std", target_os = "windows"))]
     fn drop(&mut self) {
+        // The slot resets the mapping when it's dropped.
+        if self.slot.is_some() {
+            return;
+        }
         if self.len() != 0 {
             use winapi::ctypes::c_void;
             use winapi::um::memoryapi::VirtualFree;
             use winapi::um::winnt::MEM_RELEASE;
             let r = unsafe { VirtualFree(self.ptr as *mut c_void, 0, MEM_RELEASE) };
             assert_ne!(r, 0);
+            metrics::decrease_gauge(Gauge::MemoryMapped, self.total_size as u64);
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn drop(&mut self) {
+        // The slot resets the mapping when it's dropped.
+        if self.slot.is_some() {
+            return;
+        }
+        if self.total_size != 0 {
+            let layout = Layout::from_size_align(self.total_size, page_size()).unwrap();
+            unsafe { alloc::dealloc(self.ptr as *mut u8, layout) };
+            metrics::decrease_gauge(Gauge::MemoryMapped, self.total_size as u64);
         }
     }
 }
@@ -0,0 +1,352 @@
+//! A pool of pre-reserved virtual memory for the instances and their
+//! linear memories.
+//!
+//! Creating an instance maps its `VMContext` and reserves the address
+//! space of its memories, including their guard pages, and dropping it
+//! unmaps them. With an [`InstancePool`], this is done once up front:
+//! the instances and memories are given slots of the pool, which are
+//! reset and handed out again once they are dropped. This makes the
+//! instantiation cheap enough for workloads instantiating a module per
+//! request.
+
+use crate::lib::std::alloc::Layout;
+use crate::lib::std::fmt;
+use crate::lib::std::string::{String, ToString};
+use crate::lib::std::sync::{Arc, Mutex};
+use crate::lib::std::vec::Vec;
+use crate::mmap::{self, Mmap};
+#[cfg(feature = "std")]
+use std::io;
+
+/// The sizes of the slots of an [`InstancePool`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstancePoolConfig {
+    /// The number of instances which can be allocated in the pool at
+    /// once.
+    pub max_instances: usize,
+
+    /// The size in bytes of the slot of an instance, holding its
+    /// `VMContext`. The instances of modules needing more are allocated
+    /// outside of the pool.
+    pub instance_size: usize,
+
+    /// The number of memories which can be allocated in the pool at
+    /// once.
+    pub max_memories: usize,
+
+    /// The size in bytes of the slot of a memory, including its guard
+    /// pages. It should be the bound of the static memories plus their
+    /// offset guard size, see `BaseTunables`. The memories needing more
+    /// are allocated outside of the pool.
+    pub memory_size: usize,
+}
+
+/// What a slot holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SlotKind {
+    Instance,
+    Memory,
+}
+
+struct PoolInner {
+    config: InstancePoolConfig,
+    instances: Mmap,
+    memories: Mmap,
+    free_instances: Mutex<Vec<usize>>,
+    free_memories: Mutex<Vec<usize>>,
+}
+
+impl PoolInner {
+    fn free_slots(&self, kind: SlotKind) -> &Mutex<Vec<usize>> {
+        match kind {
+            SlotKind::Instance => &self.free_instances,
+            SlotKind::Memory => &self.free_memories,
+        }
+    }
+}
+
+/// A pool of slots for the instances and their memories.
+///
+/// The pool is cheap to clone, the clones sharing the same slots. It
+/// is used through `BaseTunables::with_instance_pool`. When the pool is
+/// exhausted, the instances and memories are allocated as usual.
+///
+/// # Example
+///
+/// ```rust
+/// use wasmer_vm::{InstancePool, InstancePoolConfig};
+///
+/// let pool = InstancePool::new(InstancePoolConfig {
+///     max_instances: 16,
+///     instance_size: 64 * 1024,
+///     max_memories: 16,
+///     memory_size: 64 * 1024 * 1024,
+/// })
+/// .unwrap();
+/// assert_eq!(pool.available_memories(), 16);
+/// ```
+#[derive(Clone)]
+pub struct InstancePool {
+    inner: Arc<PoolInner>,
+}
+
+impl fmt::Debug for InstancePool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstancePool")
+            .field("config", &self.inner.config)
+            .field("available_instances", &self.available_instances())
+            .field("available_memories", &self.available_memories())
+            .finish()
+    }
+}
+
+impl InstancePool {
+    /// Reserves the address space of all the slots of a pool.
+    ///
+    /// The slot sizes are rounded up to the page size. Nothing is
+    /// committed until the slots are used.
+    pub fn new(config: InstancePoolConfig) -> Result<Self, String> {
+        let page_size = mmap::page_size();
+        let config = InstancePoolConfig {
+            instance_size: round_up_to_page_size(config.instance_size, page_size),
+            memory_size: round_up_to_page_size(config.memory_size, page_size),
+            ..config
+        };
+
+        let instances_size = config
+            .max_instances
+            .checked_mul(config.instance_size)
+            .ok_or_else(|| "the instance slots overflow the address space".to_string())?;
+        let memories_size = config
+            .max_memories
+            .checked_mul(config.memory_size)
+            .ok_or_else(|| "the memory slots overflow the address space".to_string())?;
+
+        Ok(Self {
+            inner: Arc::new(PoolInner {
+                config,
+                // The instance slots are kept committed once used.
+                instances: Mmap::accessible_reserved(instances_size, instances_size)?,
+                memories: Mmap::accessible_reserved(0, memories_size)?,
+                // The slots are handed out from the end, so the slots
+                // used last are reused first.
+                free_instances: Mutex::new((0..config.max_instances).rev().collect()),
+                free_memories: Mutex::new((0..config.max_memories).rev().collect()),
+            }),
+        })
+    }
+
+    /// The configuration of the pool, with the slot sizes rounded up to
+    /// the page size.
+    pub fn config(&self) -> InstancePoolConfig {
+        self.inner.config
+    }
+
+    /// The number of free instance slots.
+    pub fn available_instances(&self) -> usize {
+        self.inner.free_instances.lock().unwrap().len()
+    }
+
+    /// The number of free memory slots.
+    pub fn available_memories(&self) -> usize {
+        self.inner.free_memories.lock().unwrap().len()
+    }
+
+    fn take(&self, kind: SlotKind) -> Option<PoolSlot> {
+        let index = self.inner.free_slots(kind).lock().unwrap().pop()?;
+        Some(PoolSlot {
+            pool: self.inner.clone(),
+            kind,
+            index,
+        })
+    }
+
+    /// Takes a slot for an instance with the given layout, if it fits
+    /// and the pool isn't exhausted.
+    pub(crate) fn take_instance(&self, layout: Layout) -> Option<PoolSlot> {
+        if layout.size() > self.inner.config.instance_size || layout.align() > mmap::page_size() {
+            return None;
+        }
+        self.take(SlotKind::Instance)
+    }
+
+    /// Takes a slot for a memory of `mapping_size` bytes, the first
+    /// `accessible_size` ones of which are made accessible, if it fits
+    /// and the pool isn't exhausted.
+    pub(crate) fn take_memory(
+        &self,
+        accessible_size: usize,
+        mapping_size: usize,
+    ) -> Result<Option<Mmap>, String> {
+        if mapping_size > self.inner.config.memory_size {
+            return Ok(None);
+        }
+        match self.take(SlotKind::Memory) {
+            Some(slot) => Mmap::from_slot(slot, accessible_size, mapping_size).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A slot taken from an [`InstancePool`], returned to it when dropped.
+pub(crate) struct PoolSlot {
+    pool: Arc<PoolInner>,
+    kind: SlotKind,
+    index: usize,
+}
+
+impl fmt::Debug for PoolSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolSlot")
+            .field("kind", &self.kind)
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+impl PoolSlot {
+    /// The start of the slot.
+    pub(crate) fn as_mut_ptr(&self) -> *mut u8 {
+        let (region, size) = self.region();
+        unsafe { (region.as_ptr() as *mut u8).add(self.index * size) }
+    }
+
+    fn region(&self) -> (&Mmap, usize) {
+        let config = &self.pool.config;
+        match self.kind {
+            SlotKind::Instance => (&self.pool.instances, config.instance_size),
+            SlotKind::Memory => (&self.pool.memories, config.memory_size),
+        }
+    }
+}
+
+impl Drop for PoolSlot {
+    fn drop(&mut self) {
+        if self.kind == SlotKind::Memory {
+            // The next memory must start zeroed and inaccessible. If the
+            // slot can't be reset, it isn't reused.
+            let (_, size) = self.region();
+            if decommit(self.as_mut_ptr(), size).is_err() {
+                return;
+            }
+        }
+        self.pool
+            .free_slots(self.kind)
+            .lock()
+            .unwrap()
+            .push(self.index);
+    }
+}
+
+/// Round `size` up to the nearest multiple of `page_size`.
+fn round_up_to_page_size(size: usize, page_size: usize) -> usize {
+    (size + (page_size - 1)) & !(page_size - 1)
+}
+
+/// Makes the `len` bytes at `ptr` accessible.
+#[cfg(all(feature = "std", not(target_os = "windows")))]
+pub(crate) fn commit(ptr: *mut u8, len: usize) -> Result<(), String> {
+    unsafe { region::protect(ptr, len, region::Protection::READ_WRITE) }.map_err(|e| e.to_string())
+}
+
+/// Makes the `len` bytes at `ptr` accessible.
+#[cfg(all(feature = "std", target_os = "windows"))]
+pub(crate) fn commit(ptr: *mut u8, len: usize) -> Result<(), String> {
+    use winapi::ctypes::c_void;
+    use winapi::um::memoryapi::VirtualAlloc;
+    use winapi::um::winnt::{MEM_COMMIT, PAGE_READWRITE};
+
+    if unsafe { VirtualAlloc(ptr as *mut c_void, len, MEM_COMMIT, PAGE_READWRITE) }.is_null() {
+        return Err(io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+/// Releases the `len` bytes at `ptr` and makes them inaccessible,
+/// keeping them reserved. They read as zeros once committed again.
+#[cfg(all(feature = "std", not(target_os = "windows")))]
+pub(crate) fn decommit(ptr: *mut u8, len: usize) -> Result<(), String> {
+    // Mapping fresh pages over the old ones both zeroes them and gives
+    // their memory back to the system.
+    let ret = unsafe {
+        libc::mmap(
+            ptr as *mut libc::c_void,
+            len,
+            libc::PROT_NONE,
+            libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_FIXED,
+            -1,
+            0,
+        )
+    };
+    if ret as isize == -1_isize {
+        return Err(io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+/// Releases the `len` bytes at `ptr` and makes them inaccessible,
+/// keeping them reserved. They read as zeros once committed again.
+#[cfg(all(feature = "std", target_os = "windows"))]
+pub(crate) fn decommit(ptr: *mut u8, len: usize) -> Result<(), String> {
+    use winapi::ctypes::c_void;
+    use winapi::um::memoryapi::VirtualFree;
+    use winapi::um::winnt::MEM_DECOMMIT;
+
+    if unsafe { VirtualFree(ptr as *mut c_void, len, MEM_DECOMMIT) } == 0 {
+        return Err(io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+/// Makes the `len` bytes at `ptr` accessible. Without `std` the memory
+/// is allocated on the heap, always accessible.
+#[cfg(not(feature = "std"))]
+pub(crate) fn commit(_ptr: *mut u8, _len: usize) -> Result<(), String> {
+    Ok(())
+}
+
+/// Zeroes the `len` bytes at `ptr`. Without `std` the memory is
+/// allocated on the heap, and can't be given back to the system.
+#[cfg(not(feature = "std"))]
+pub(crate) fn decommit(ptr: *mut u8, len: usize) -> Result<(), String> {
+    unsafe { crate::lib::std::ptr::write_bytes(ptr, 0, len) };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_slots_are_reset() {
+        let page_size = region::page::size();
+        let pool = InstancePool::new(InstancePoolConfig {
+            max_instances: 1,
+            instance_size: 1,
+            max_memories: 2,
+            memory_size: 4 * page_size,
+        })
+        .unwrap();
+        assert_eq!(pool.config().instance_size, page_size);
+
+        let mut first = pool.take_memory(page_size, 3 * page_size).unwrap().unwrap();
+        assert_eq!(first.len(), 3 * page_size);
+        first.as_mut_slice_accessible().fill(42);
+        first.make_accessible(page_size, page_size).unwrap();
+
+        // The memories larger than a slot aren't pooled.
+        assert!(pool.take_memory(0, 5 * page_size).unwrap().is_none());
+
+        let second = pool.take_memory(0, 4 * page_size).unwrap().unwrap();
+        assert_eq!(pool.available_memories(), 0);
+        assert!(pool.take_memory(0, page_size).unwrap().is_none());
+
+        drop(second);
+        drop(first);
+        assert_eq!(pool.available_memories(), 2);
+
+        // The slot freed last is reused first, zeroed.
+        let first = pool.take_memory(page_size, 2 * page_size).unwrap().unwrap();
+        assert!(first.as_slice_accessible().iter().all(|byte| *byte == 0));
+    }
+}
@@ -0,0 +1,165 @@
+//! Snapshots of linear memories, to create memories in the same state
+//! without initializing them again.
+
+use crate::lib::std::ptr;
+use crate::lib::std::slice;
+use crate::lib::std::vec::Vec;
+use crate::memory::LinearMemory;
+use crate::vmcontext::VMMemoryDefinition;
+#[cfg(all(feature = "std", target_os = "linux"))]
+use wasmer_types::WASM_PAGE_SIZE;
+use wasmer_types::{MemoryError, Pages};
+
+/// The contents of a memory, captured by [`MemorySnapshot::capture`].
+#[derive(Debug)]
+enum MemoryImage {
+    /// An anonymous file, mapped copy-on-write by the restored memories.
+    #[cfg(all(feature = "std", target_os = "linux"))]
+    File(std::fs::File),
+    /// A copy of the contents.
+    Bytes(Vec<u8>),
+}
+
+/// A snapshot of the contents of a linear memory.
+///
+/// The snapshot is restored in a memory with
+/// [`LinearMemory::restore_snapshot`]. On Linux, the memories created by
+/// the runtime map the snapshot copy-on-write, so restoring it is cheap
+/// no matter the size of the memory and the pages which aren't written
+/// to are shared by all the memories restored from it. Elsewhere, the
+/// contents are copied.
+#[derive(Debug)]
+pub struct MemorySnapshot {
+    size: Pages,
+    image: MemoryImage,
+}
+
+impl MemorySnapshot {
+    /// Captures the current contents of `memory`.
+    pub fn capture(memory: &dyn LinearMemory) -> Self {
+        let size = memory.size();
+        let contents = unsafe {
+            let definition = memory.vmmemory().as_ref();
+            slice::from_raw_parts(definition.base as *const u8, definition.current_length)
+        };
+
+        #[cfg(all(feature = "std", target_os = "linux"))]
+        {
+            // Fall back to a copy if no file can be created, e.g. in
+            // a sandbox.
+            if let Ok(file) = Self::create_file(contents) {
+                return Self {
+                    size,
+                    image: MemoryImage::File(file),
+                };
+            }
+        }
+
+        Self {
+            size,
+            image: MemoryImage::Bytes(contents.to_vec()),
+        }
+    }
+
+    #[cfg(all(feature = "std", target_os = "linux"))]
+    fn create_file(contents: &[u8]) -> std::io::Result<std::fs::File> {
+        use std::os::unix::fs::FileExt;
+        use std::os::unix::io::FromRawFd;
+
+        let fd = unsafe {
+            libc::memfd_create(
+                b"wasmer-memory-snapshot\0".as_ptr() as *const libc::c_char,
+                libc::MFD_CLOEXEC,
+            )
+        };
+        if fd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let file = unsafe { std::fs::File::from_raw_fd(fd) };
+
+        // Only the pages which aren't zeroed are written, the others are
+        // left as holes in the file.
+        file.set_len(contents.len() as u64)?;
+        for (index, page) in contents.chunks(WASM_PAGE_SIZE).enumerate() {
+            if page.iter().any(|byte| *byte != 0) {
+                file.write_all_at(page, (index * WASM_PAGE_SIZE) as u64)?;
+            }
+        }
+        Ok(file)
+    }
+
+    /// The size of the snapshot, in Wasm pages.
+    pub fn size(&self) -> Pages {
+        self.size
+    }
+
+    /// Grows `memory` to the size of the snapshot, if it's smaller.
+    pub(crate) fn grow_memory<M: LinearMemory + ?Sized>(
+        &self,
+        memory: &mut M,
+    ) -> Result<(), MemoryError> {
+        let size = memory.size();
+        if size < self.size {
+            memory.grow(Pages(self.size.0 - size.0))?;
+        }
+        Ok(())
+    }
+
+    /// Copies the snapshot at the start of the memory of `definition`,
+    /// zeroing the rest of it.
+    ///
+    /// # Safety
+    /// - `definition` must describe a memory at least as large as the
+    ///   snapshot.
+    pub(crate) unsafe fn copy_to(&self, definition: &VMMemoryDefinition) {
+        let len = self.size.bytes().0;
+        let contents = slice::from_raw_parts_mut(definition.base, len);
+        match &self.image {
+            #[cfg(all(feature = "std", target_os = "linux"))]
+            MemoryImage::File(file) => {
+                use std::os::unix::fs::FileExt;
+                file.read_exact_at(contents, 0)
+                    .expect("failed to read a memory snapshot");
+            }
+            MemoryImage::Bytes(bytes) => contents.copy_from_slice(bytes),
+        }
+        ptr::write_bytes(definition.base.add(len), 0, definition.current_length - len);
+    }
+
+    /// Maps the snapshot copy-on-write at the start of the memory of
+    /// `definition` if possible, and copies it otherwise, zeroing the
+    /// rest of the memory.
+    ///
+    /// # Safety
+    /// - `definition` must describe a memory at least as large as the
+    ///   snapshot, whose pages are in a mapping owned by the memory.
+    pub(crate) unsafe fn map_to(&self, definition: &VMMemoryDefinition) -> Result<(), MemoryError> {
+        #[cfg(all(feature = "std", target_os = "linux"))]
+        {
+            use std::os::unix::io::AsRawFd;
+
+            let len = self.size.bytes().0;
+            let page_aligned = definition.base as usize % region::page::size() == 0;
+            if let (MemoryImage::File(file), true, true) = (&self.image, page_aligned, len != 0) {
+                let ptr = libc::mmap(
+                    definition.base as *mut libc::c_void,
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE | libc::MAP_FIXED,
+                    file.as_raw_fd(),
+                    0,
+                );
+                if ptr as isize == -1_isize {
+                    return Err(MemoryError::Region(
+                        std::io::Error::last_os_error().to_string(),
+                    ));
+                }
+                ptr::write_bytes(definition.base.add(len), 0, definition.current_length - len);
+                return Ok(());
+            }
+        }
+
+        self.copy_to(definition);
+        Ok(())
+    }
+}
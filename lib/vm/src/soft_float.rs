@@ -0,0 +1,907 @@
+//! Software implementation of the WebAssembly float operations.
+//!
+//! The operations work on the raw bits of IEEE 754 binary32 and binary64
+//! values with integer arithmetic only, so that the soft-float builtins
+//! give the same, correctly rounded, results on CPUs without a usable
+//! floating point unit. Both formats share the same code, parameterized
+//! by a [`Format`]; the values are passed in the low bits of a `u64`.
+//!
+//! Every result is rounded to nearest, ties to even, as required by the
+//! WebAssembly specification. An operation with a NaN operand returns
+//! that NaN with its quiet bit set, and an invalid operation (`0 / 0`,
+//! `inf - inf`, the square root of a negative number, ...) returns the
+//! positive canonical NaN.
+
+use crate::lib::std::cmp::Ordering;
+use wasmer_types::{SoftFloatConversion, SoftFloatOp, TrapCode};
+
+/// The layout of a binary floating point format.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Format {
+    /// The number of bits of the exponent.
+    exp_bits: u32,
+    /// The number of bits of the fraction, without the implicit bit.
+    frac_bits: u32,
+}
+
+/// IEEE 754 binary32, `f32`.
+pub(crate) const F32: Format = Format {
+    exp_bits: 8,
+    frac_bits: 23,
+};
+
+/// IEEE 754 binary64, `f64`.
+pub(crate) const F64: Format = Format {
+    exp_bits: 11,
+    frac_bits: 52,
+};
+
+/// A finite value, equal to `(-1)^sign * sig * 2^exp`.
+#[derive(Clone, Copy, Debug)]
+struct Unpacked {
+    sign: bool,
+    exp: i32,
+    sig: u64,
+}
+
+impl Format {
+    fn sign_mask(self) -> u64 {
+        1 << (self.exp_bits + self.frac_bits)
+    }
+
+    fn frac_mask(self) -> u64 {
+        (1 << self.frac_bits) - 1
+    }
+
+    fn exp_max(self) -> u64 {
+        (1 << self.exp_bits) - 1
+    }
+
+    fn exp_mask(self) -> u64 {
+        self.exp_max() << self.frac_bits
+    }
+
+    fn quiet_bit(self) -> u64 {
+        1 << (self.frac_bits - 1)
+    }
+
+    fn bias(self) -> i32 {
+        (1 << (self.exp_bits - 1)) - 1
+    }
+
+    /// The exponent of the smallest normal numbers.
+    fn exp_min(self) -> i32 {
+        1 - self.bias()
+    }
+
+    fn canonical_nan(self) -> u64 {
+        self.exp_mask() | self.quiet_bit()
+    }
+
+    fn infinity(self, sign: bool) -> u64 {
+        self.signed(sign, self.exp_mask())
+    }
+
+    fn zero(self, sign: bool) -> u64 {
+        self.signed(sign, 0)
+    }
+
+    fn one(self, sign: bool) -> u64 {
+        self.signed(sign, (self.bias() as u64) << self.frac_bits)
+    }
+
+    fn signed(self, sign: bool, magnitude: u64) -> u64 {
+        if sign {
+            magnitude | self.sign_mask()
+        } else {
+            magnitude
+        }
+    }
+
+    fn sign(self, bits: u64) -> bool {
+        bits & self.sign_mask() != 0
+    }
+
+    fn magnitude(self, bits: u64) -> u64 {
+        bits & !self.sign_mask()
+    }
+
+    fn is_nan(self, bits: u64) -> bool {
+        self.magnitude(bits) > self.exp_mask()
+    }
+
+    fn is_infinite(self, bits: u64) -> bool {
+        self.magnitude(bits) == self.exp_mask()
+    }
+
+    fn is_zero(self, bits: u64) -> bool {
+        self.magnitude(bits) == 0
+    }
+
+    fn quiet(self, bits: u64) -> u64 {
+        bits | self.quiet_bit()
+    }
+
+    /// Returns the NaN resulting from an operation on `a` and `b`, one of
+    /// them at least being a NaN.
+    fn propagate_nan(self, a: u64, b: u64) -> u64 {
+        if self.is_nan(a) {
+            self.quiet(a)
+        } else {
+            self.quiet(b)
+        }
+    }
+
+    /// Decomposes a finite value.
+    fn unpack(self, bits: u64) -> Unpacked {
+        let biased = ((bits & self.exp_mask()) >> self.frac_bits) as i32;
+        let frac = bits & self.frac_mask();
+        let (exp, sig) = if biased == 0 {
+            (self.exp_min(), frac)
+        } else {
+            (biased - self.bias(), frac | (1 << self.frac_bits))
+        };
+        Unpacked {
+            sign: self.sign(bits),
+            exp: exp - self.frac_bits as i32,
+            sig,
+        }
+    }
+
+    /// Rounds `(-1)^sign * sig * 2^exp` to the nearest value of the format,
+    /// ties to even.
+    ///
+    /// `sig` may be the truncation of a longer significand, as long as its
+    /// lowest bit is set when any of the dropped bits was, and it is at
+    /// least two bits under the rounding position.
+    fn round(self, sign: bool, exp: i32, sig: u128) -> u64 {
+        if sig == 0 {
+            return self.zero(sign);
+        }
+        let precision = self.frac_bits + 1;
+        // The value is in `[2^msb, 2^(msb + 1))`, and is rounded to a
+        // multiple of `2^quantum`, the distance between two numbers of
+        // the format of this magnitude.
+        let msb = exp + (127 - sig.leading_zeros() as i32);
+        let quantum = msb.max(self.exp_min()) - self.frac_bits as i32;
+        let mut sig = if quantum >= exp {
+            shift_right_rounding(sig, (quantum - exp) as u32)
+        } else {
+            // The value is a multiple of the quantum, which makes it exact.
+            sig << (exp - quantum) as u32
+        };
+        let mut quantum = quantum;
+        if sig == 1 << precision {
+            // Rounding up carried into the next binade.
+            sig >>= 1;
+            quantum += 1;
+        }
+        if sig < 1 << self.frac_bits {
+            // Subnormal, or zero after rounding.
+            return self.signed(sign, sig as u64);
+        }
+        let biased = quantum + self.frac_bits as i32 + self.bias();
+        if biased >= self.exp_max() as i32 {
+            return self.infinity(sign);
+        }
+        self.signed(
+            sign,
+            ((biased as u64) << self.frac_bits) | (sig as u64 & self.frac_mask()),
+        )
+    }
+
+    /// Compares two values, or returns `None` if one of them is a NaN.
+    fn compare(self, a: u64, b: u64) -> Option<Ordering> {
+        if self.is_nan(a) || self.is_nan(b) {
+            return None;
+        }
+        // The encoding orders the magnitudes, so the values can be
+        // ordered by their signed magnitudes, both zeros being equal.
+        let key = |bits: u64| {
+            let magnitude = self.magnitude(bits) as i64;
+            if self.sign(bits) {
+                -magnitude
+            } else {
+                magnitude
+            }
+        };
+        Some(key(a).cmp(&key(b)))
+    }
+
+    fn add(self, a: u64, b: u64) -> u64 {
+        if self.is_nan(a) || self.is_nan(b) {
+            return self.propagate_nan(a, b);
+        }
+        match (self.is_infinite(a), self.is_infinite(b)) {
+            (true, true) if self.sign(a) != self.sign(b) => return self.canonical_nan(),
+            (true, _) => return a,
+            (false, true) => return b,
+            (false, false) => {}
+        }
+        if self.is_zero(a) && self.is_zero(b) {
+            return self.zero(self.sign(a) && self.sign(b));
+        }
+        let (mut x, mut y) = (self.unpack(a), self.unpack(b));
+        if x.exp < y.exp {
+            crate::lib::std::mem::swap(&mut x, &mut y);
+        }
+        let distance = (x.exp - y.exp) as u32;
+        let (exp, x_sig, y_sig) = if distance <= 64 {
+            // Aligned exactly, in at most 53 + 64 bits.
+            (y.exp, (x.sig as u128) << distance, y.sig as u128)
+        } else {
+            // `y` is far under half of the quantum of `x`, which is
+            // normal: it only matters as a sticky bit under guard bits.
+            (x.exp - 3, (x.sig as u128) << 3, (y.sig != 0) as u128)
+        };
+        if x.sign == y.sign {
+            self.round(x.sign, exp, x_sig + y_sig)
+        } else {
+            match x_sig.cmp(&y_sig) {
+                Ordering::Greater => self.round(x.sign, exp, x_sig - y_sig),
+                Ordering::Less => self.round(y.sign, exp, y_sig - x_sig),
+                Ordering::Equal => self.zero(false),
+            }
+        }
+    }
+
+    fn mul(self, a: u64, b: u64) -> u64 {
+        if self.is_nan(a) || self.is_nan(b) {
+            return self.propagate_nan(a, b);
+        }
+        let sign = self.sign(a) != self.sign(b);
+        if self.is_infinite(a) || self.is_infinite(b) {
+            if self.is_zero(a) || self.is_zero(b) {
+                return self.canonical_nan();
+            }
+            return self.infinity(sign);
+        }
+        let (x, y) = (self.unpack(a), self.unpack(b));
+        self.round(sign, x.exp + y.exp, x.sig as u128 * y.sig as u128)
+    }
+
+    fn div(self, a: u64, b: u64) -> u64 {
+        if self.is_nan(a) || self.is_nan(b) {
+            return self.propagate_nan(a, b);
+        }
+        let sign = self.sign(a) != self.sign(b);
+        match (self.is_infinite(a), self.is_infinite(b)) {
+            (true, true) => return self.canonical_nan(),
+            (true, false) => return self.infinity(sign),
+            (false, true) => return self.zero(sign),
+            (false, false) => {}
+        }
+        match (self.is_zero(a), self.is_zero(b)) {
+            (true, true) => return self.canonical_nan(),
+            (true, false) => return self.zero(sign),
+            (false, true) => return self.infinity(sign),
+            (false, false) => {}
+        }
+        let (x, y) = (self.unpack(a), self.unpack(b));
+        // With both significands normalized to 64 bits, the quotient has
+        // at least 64 bits, way more than needed to round it.
+        let (x_shift, y_shift) = (x.sig.leading_zeros(), y.sig.leading_zeros());
+        let dividend = ((x.sig << x_shift) as u128) << 64;
+        let divisor = (y.sig << y_shift) as u128;
+        let quotient = dividend / divisor;
+        let sticky = (dividend % divisor != 0) as u128;
+        let exp = x.exp - x_shift as i32 - 64 - (y.exp - y_shift as i32);
+        self.round(sign, exp, quotient | sticky)
+    }
+
+    fn sqrt(self, a: u64) -> u64 {
+        if self.is_nan(a) {
+            return self.quiet(a);
+        }
+        if self.is_zero(a) {
+            return a;
+        }
+        if self.sign(a) {
+            return self.canonical_nan();
+        }
+        if self.is_infinite(a) {
+            return a;
+        }
+        let x = self.unpack(a);
+        // Widen the significand to 125 or 126 bits, keeping an even
+        // exponent, so that its square root has at least 62 bits.
+        let mut shift = x.sig.leading_zeros() + 62;
+        if (x.exp - shift as i32) % 2 != 0 {
+            shift += 1;
+        }
+        let sig = (x.sig as u128) << shift;
+        let root = isqrt(sig);
+        let sticky = (root * root != sig) as u128;
+        self.round(false, (x.exp - shift as i32) / 2, root | sticky)
+    }
+
+    fn min_max(self, a: u64, b: u64, max: bool) -> u64 {
+        match self.compare(a, b) {
+            None => self.propagate_nan(a, b),
+            // The zeros are ordered by sign, `-0` being the smallest.
+            Some(Ordering::Equal) if max => a & b,
+            Some(Ordering::Equal) => a | b,
+            Some(Ordering::Less) if max => b,
+            Some(Ordering::Greater) if !max => b,
+            Some(_) => a,
+        }
+    }
+
+    /// Rounds to an integral value, in the direction given by `up`, which
+    /// is called with the sign, whether the dropped fraction is over a half,
+    /// exactly a half, and whether the truncated value is odd, and tells
+    /// if the magnitude must be rounded up.
+    fn round_to_integral(self, a: u64, up: impl Fn(bool, Ordering, bool) -> bool) -> u64 {
+        if self.is_nan(a) {
+            return self.quiet(a);
+        }
+        if self.is_infinite(a) || self.is_zero(a) {
+            return a;
+        }
+        let sign = self.sign(a);
+        let biased = ((a & self.exp_mask()) >> self.frac_bits) as i32;
+        let exp = biased - self.bias();
+        if exp >= self.frac_bits as i32 {
+            // Already integral.
+            return a;
+        }
+        if exp < 0 {
+            // The magnitude is under 1, and truncates to 0.
+            let half = (self.bias() as u64 - 1) << self.frac_bits;
+            let fraction = self.magnitude(a).cmp(&half);
+            return if up(sign, fraction, false) {
+                self.one(sign)
+            } else {
+                self.zero(sign)
+            };
+        }
+        let fraction_bits = self.frac_bits - exp as u32;
+        let fraction_mask = (1 << fraction_bits) - 1;
+        let fraction = a & fraction_mask;
+        if fraction == 0 {
+            return a;
+        }
+        let truncated = a & !fraction_mask;
+        let odd = truncated & (1 << fraction_bits) != 0;
+        if up(sign, fraction.cmp(&(1 << (fraction_bits - 1))), odd) {
+            // Carrying into the exponent gives the next power of two.
+            truncated + (1 << fraction_bits)
+        } else {
+            truncated
+        }
+    }
+
+    /// Converts `(-1)^sign * magnitude` to the format.
+    fn convert_int(self, sign: bool, magnitude: u64) -> u64 {
+        self.round(sign, 0, magnitude as u128)
+    }
+
+    /// Converts a value of the format `from` to this format.
+    fn convert(self, from: Self, bits: u64) -> u64 {
+        let sign = from.sign(bits);
+        if from.is_nan(bits) {
+            // Keep the top bits of the payload.
+            let payload = bits & from.frac_mask();
+            let payload = if self.frac_bits > from.frac_bits {
+                payload << (self.frac_bits - from.frac_bits)
+            } else {
+                payload >> (from.frac_bits - self.frac_bits)
+            };
+            return self.signed(sign, self.canonical_nan() | payload);
+        }
+        if from.is_infinite(bits) {
+            return self.infinity(sign);
+        }
+        let x = from.unpack(bits);
+        self.round(sign, x.exp, x.sig as u128)
+    }
+
+    /// Truncates a value to an integer, returning its sign and its
+    /// magnitude, or `None` for NaNs. The magnitude saturates to
+    /// `u128::MAX`, which is out of the range of every integer type.
+    fn truncate(self, bits: u64) -> Option<(bool, u128)> {
+        if self.is_nan(bits) {
+            return None;
+        }
+        let sign = self.sign(bits);
+        if self.is_infinite(bits) {
+            return Some((sign, u128::MAX));
+        }
+        let x = self.unpack(bits);
+        let sig = x.sig as u128;
+        let magnitude = if x.exp >= 0 {
+            if x.exp >= 64 {
+                u128::MAX
+            } else {
+                sig << x.exp
+            }
+        } else {
+            sig.checked_shr(x.exp.unsigned_abs()).unwrap_or(0)
+        };
+        Some((sign, magnitude))
+    }
+}
+
+/// Shifts `sig` right, rounding to nearest, ties to even.
+fn shift_right_rounding(sig: u128, shift: u32) -> u128 {
+    if shift == 0 {
+        return sig;
+    }
+    if shift > 128 {
+        // Under half of the quantum.
+        return 0;
+    }
+    let truncated = sig.checked_shr(shift).unwrap_or(0);
+    let dropped = sig & (u128::MAX >> (128 - shift));
+    let half = 1 << (shift - 1);
+    if dropped > half || (dropped == half && truncated & 1 == 1) {
+        truncated + 1
+    } else {
+        truncated
+    }
+}
+
+/// The integer square root of `x`, rounded down.
+fn isqrt(x: u128) -> u128 {
+    let mut remainder = x;
+    let mut root = 0;
+    let mut bit = 1 << (126 - (x.leading_zeros() & !1));
+    while bit != 0 {
+        if remainder >= root + bit {
+            remainder -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+/// Performs a float operation on the raw bits of its operands, returning
+/// the raw bits of the result, or `1`/`0` for the comparisons.
+pub(crate) fn operation(format: Format, op: SoftFloatOp, a: u64, b: u64) -> u64 {
+    use SoftFloatOp::*;
+
+    let sign = format.sign_mask();
+    let ordering = || format.compare(a, b);
+    match op {
+        Add => format.add(a, b),
+        Sub => format.add(a, b ^ sign),
+        Mul => format.mul(a, b),
+        Div => format.div(a, b),
+        Sqrt => format.sqrt(a),
+        Min => format.min_max(a, b, false),
+        Max => format.min_max(a, b, true),
+        Ceil => format.round_to_integral(a, |sign, _, _| !sign),
+        Floor => format.round_to_integral(a, |sign, _, _| sign),
+        Trunc => format.round_to_integral(a, |_, _, _| false),
+        Nearest => format.round_to_integral(a, |_, fraction, odd| {
+            fraction == Ordering::Greater || (fraction == Ordering::Equal && odd)
+        }),
+        // The sign operations only touch the sign bit, NaNs included.
+        Abs => a & !sign,
+        Neg => a ^ sign,
+        Copysign => (a & !sign) | (b & sign),
+        Eq => (ordering() == Some(Ordering::Equal)) as u64,
+        Ne => (ordering() != Some(Ordering::Equal)) as u64,
+        Lt => (ordering() == Some(Ordering::Less)) as u64,
+        Gt => (ordering() == Some(Ordering::Greater)) as u64,
+        Le => matches!(ordering(), Some(Ordering::Less | Ordering::Equal)) as u64,
+        Ge => matches!(ordering(), Some(Ordering::Greater | Ordering::Equal)) as u64,
+    }
+}
+
+/// Performs a conversion from or to a float, on the raw bits of the operand
+/// and of the result, zero-extended to 64 bits.
+pub(crate) fn conversion(op: SoftFloatConversion, x: u64) -> Result<u64, TrapCode> {
+    use SoftFloatConversion::*;
+
+    let signed32 = || ((x as i32) < 0, (x as i32).unsigned_abs() as u64);
+    let signed64 = || ((x as i64) < 0, (x as i64).unsigned_abs());
+    let unsigned32 = || (false, x as u32 as u64);
+    let unsigned64 = || (false, x);
+    let from_int =
+        |format: Format, (sign, magnitude): (bool, u64)| format.convert_int(sign, magnitude);
+    Ok(match op {
+        F32ConvertI32S => from_int(F32, signed32()),
+        F32ConvertI32U => from_int(F32, unsigned32()),
+        F32ConvertI64S => from_int(F32, signed64()),
+        F32ConvertI64U => from_int(F32, unsigned64()),
+        F64ConvertI32S => from_int(F64, signed32()),
+        F64ConvertI32U => from_int(F64, unsigned32()),
+        F64ConvertI64S => from_int(F64, signed64()),
+        F64ConvertI64U => from_int(F64, unsigned64()),
+        F32DemoteF64 => F32.convert(F64, x),
+        F64PromoteF32 => F64.convert(F32, x),
+        I32TruncF32S => checked_trunc(F32, x, 32, true)?,
+        I32TruncF32U => checked_trunc(F32, x, 32, false)?,
+        I32TruncF64S => checked_trunc(F64, x, 32, true)?,
+        I32TruncF64U => checked_trunc(F64, x, 32, false)?,
+        I64TruncF32S => checked_trunc(F32, x, 64, true)?,
+        I64TruncF32U => checked_trunc(F32, x, 64, false)?,
+        I64TruncF64S => checked_trunc(F64, x, 64, true)?,
+        I64TruncF64U => checked_trunc(F64, x, 64, false)?,
+        I32TruncSatF32S => saturating_trunc(F32, x, 32, true),
+        I32TruncSatF32U => saturating_trunc(F32, x, 32, false),
+        I32TruncSatF64S => saturating_trunc(F64, x, 32, true),
+        I32TruncSatF64U => saturating_trunc(F64, x, 32, false),
+        I64TruncSatF32S => saturating_trunc(F32, x, 64, true),
+        I64TruncSatF32U => saturating_trunc(F32, x, 64, false),
+        I64TruncSatF64S => saturating_trunc(F64, x, 64, true),
+        I64TruncSatF64U => saturating_trunc(F64, x, 64, false),
+    })
+}
+
+/// Truncates a float to an integer of `width` bits, returning its bits
+/// zero-extended to 64 bits, or an error if it doesn't fit.
+fn checked_trunc(format: Format, x: u64, width: u32, signed: bool) -> Result<u64, TrapCode> {
+    let (sign, magnitude) = format.truncate(x).ok_or(TrapCode::BadConversionToInteger)?;
+    let (min, max) = int_range(width, signed);
+    if (sign && magnitude > min) || (!sign && magnitude > max) {
+        return Err(TrapCode::IntegerOverflow);
+    }
+    Ok(int_bits(sign, magnitude, width))
+}
+
+/// Truncates a float to an integer of `width` bits, saturating to its
+/// bounds, and turning NaNs into 0.
+fn saturating_trunc(format: Format, x: u64, width: u32, signed: bool) -> u64 {
+    let (sign, magnitude) = match format.truncate(x) {
+        Some(truncated) => truncated,
+        None => return 0,
+    };
+    let (min, max) = int_range(width, signed);
+    let magnitude = if sign {
+        magnitude.min(min)
+    } else {
+        magnitude.min(max)
+    };
+    int_bits(sign, magnitude, width)
+}
+
+/// The magnitudes of the bounds of an integer type.
+fn int_range(width: u32, signed: bool) -> (u128, u128) {
+    if signed {
+        (1 << (width - 1), (1 << (width - 1)) - 1)
+    } else {
+        (0, (1 << width) - 1)
+    }
+}
+
+/// The bits of `(-1)^sign * magnitude`, as an integer of `width` bits,
+/// zero-extended to 64 bits.
+fn int_bits(sign: bool, magnitude: u128, width: u32) -> u64 {
+    let value = if sign {
+        (magnitude as u64).wrapping_neg()
+    } else {
+        magnitude as u64
+    };
+    if width == 32 {
+        value as u32 as u64
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_arithmetic, clippy::float_cmp)]
+mod tests {
+    use super::*;
+
+    /// Interesting values, and a deterministic sample of random ones.
+    fn f64_samples() -> Vec<f64> {
+        let mut samples = vec![
+            0.0,
+            -0.0,
+            0.5,
+            -0.5,
+            1.0,
+            -1.0,
+            1.5,
+            2.5,
+            -2.5,
+            3.0,
+            0.49999999999999994,
+            4503599627370495.5,
+            4503599627370497.0,
+            9007199254740993.0,
+            f64::MIN_POSITIVE,
+            -f64::MIN_POSITIVE,
+            f64::from_bits(1),
+            f64::from_bits(0x000f_ffff_ffff_ffff),
+            f64::MAX,
+            f64::MIN,
+            f64::EPSILON,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f32::MAX as f64,
+            f32::MIN_POSITIVE as f64,
+            f32::from_bits(1) as f64,
+            2147483647.0,
+            2147483648.0,
+            -2147483648.0,
+            -2147483649.0,
+            4294967295.0,
+            4294967296.0,
+            9223372036854775807.0,
+            -9223372036854775808.0,
+            18446744073709551615.0,
+        ];
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        for _ in 0..200 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let value = f64::from_bits(state);
+            if !value.is_nan() {
+                samples.push(value);
+                // Values of close magnitudes exercise the cancellations.
+                samples.push(f64::from_bits(state ^ (state >> 40)));
+            }
+        }
+        samples
+    }
+
+    fn f32_samples() -> Vec<f32> {
+        let mut samples: Vec<f32> = f64_samples().into_iter().map(|x| x as f32).collect();
+        samples.extend([
+            f32::from_bits(1),
+            f32::from_bits(0x007f_ffff),
+            f32::MIN_POSITIVE,
+            8388607.5,
+            16777217.0,
+        ]);
+        let mut state = 0x9e37_79b9_u32;
+        for _ in 0..200 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let value = f32::from_bits(state);
+            if !value.is_nan() {
+                samples.push(value);
+            }
+        }
+        samples
+    }
+
+    /// Checks a result against the host, NaNs only having to be arithmetic.
+    fn check(format: Format, op: impl crate::lib::std::fmt::Debug, got: u64, expected: u64) {
+        if format.is_nan(expected) {
+            assert!(
+                format.is_nan(got) && got & format.quiet_bit() != 0,
+                "{:?}: expected an arithmetic NaN, got {:#x}",
+                op,
+                got
+            );
+        } else {
+            assert_eq!(
+                got, expected,
+                "{:?}: got {:#x}, expected {:#x}",
+                op, got, expected
+            );
+        }
+    }
+
+    fn nearest_f64(x: f64) -> f64 {
+        let rounded = x.round();
+        if (x - x.trunc()).abs() == 0.5 && rounded % 2.0 != 0.0 {
+            (rounded - x.signum()).copysign(x)
+        } else {
+            rounded
+        }
+    }
+
+    fn nearest_f32(x: f32) -> f32 {
+        nearest_f64(x as f64) as f32
+    }
+
+    macro_rules! check_operations {
+        ($format:expr, $samples:expr, $float:ty, $nearest:ident) => {{
+            let samples = $samples;
+            let bits = |x: $float| u64::from(x.to_bits());
+            let bool_bits = |b: bool| b as u64;
+            for &x in &samples {
+                let unary = [
+                    (SoftFloatOp::Sqrt, bits(x.sqrt())),
+                    (SoftFloatOp::Ceil, bits(x.ceil())),
+                    (SoftFloatOp::Floor, bits(x.floor())),
+                    (SoftFloatOp::Trunc, bits(x.trunc())),
+                    (SoftFloatOp::Nearest, bits($nearest(x))),
+                ];
+                for (op, expected) in unary {
+                    let got = operation($format, op, bits(x), 0);
+                    check($format, (op, x), got, expected);
+                }
+                for &y in &samples {
+                    let binary = [
+                        (SoftFloatOp::Add, bits(x + y)),
+                        (SoftFloatOp::Sub, bits(x - y)),
+                        (SoftFloatOp::Mul, bits(x * y)),
+                        (SoftFloatOp::Div, bits(x / y)),
+                        (SoftFloatOp::Eq, bool_bits(x == y)),
+                        (SoftFloatOp::Ne, bool_bits(x != y)),
+                        (SoftFloatOp::Lt, bool_bits(x < y)),
+                        (SoftFloatOp::Gt, bool_bits(x > y)),
+                        (SoftFloatOp::Le, bool_bits(x <= y)),
+                        (SoftFloatOp::Ge, bool_bits(x >= y)),
+                    ];
+                    for (op, expected) in binary {
+                        let got = operation($format, op, bits(x), bits(y));
+                        check($format, (op, x, y), got, expected);
+                    }
+                    if x != 0.0 || y != 0.0 {
+                        let got = operation($format, SoftFloatOp::Min, bits(x), bits(y));
+                        check($format, ("min", x, y), got, bits(x.min(y)));
+                        let got = operation($format, SoftFloatOp::Max, bits(x), bits(y));
+                        check($format, ("max", x, y), got, bits(x.max(y)));
+                    }
+                }
+            }
+        }};
+    }
+
+    #[test]
+    fn f64_operations_match_the_host() {
+        check_operations!(F64, f64_samples(), f64, nearest_f64);
+    }
+
+    #[test]
+    fn f32_operations_match_the_host() {
+        check_operations!(F32, f32_samples(), f32, nearest_f32);
+    }
+
+    #[test]
+    fn signed_zeros_and_nans() {
+        let (zero, neg_zero) = (0.0f64.to_bits(), (-0.0f64).to_bits());
+        let min = |a, b| operation(F64, SoftFloatOp::Min, a, b);
+        let max = |a, b| operation(F64, SoftFloatOp::Max, a, b);
+        assert_eq!(min(zero, neg_zero), neg_zero);
+        assert_eq!(min(neg_zero, zero), neg_zero);
+        assert_eq!(max(zero, neg_zero), zero);
+        assert_eq!(max(neg_zero, zero), zero);
+        assert_eq!(
+            operation(F64, SoftFloatOp::Add, neg_zero, neg_zero),
+            neg_zero
+        );
+        assert_eq!(operation(F64, SoftFloatOp::Sub, neg_zero, neg_zero), zero);
+        assert_eq!(operation(F64, SoftFloatOp::Sqrt, neg_zero, 0), neg_zero);
+
+        // A signaling NaN operand comes back quiet, with its payload.
+        let signaling = 0x7ff0_0000_0000_0001;
+        assert_eq!(
+            operation(F64, SoftFloatOp::Add, signaling, zero),
+            0x7ff8_0000_0000_0001
+        );
+        assert_eq!(
+            operation(F64, SoftFloatOp::Nearest, signaling, 0),
+            0x7ff8_0000_0000_0001
+        );
+        assert_eq!(
+            operation(F64, SoftFloatOp::Neg, signaling, 0),
+            0xfff0_0000_0000_0001
+        );
+        // Invalid operations give the canonical NaN.
+        let infinity = f64::INFINITY.to_bits();
+        assert_eq!(
+            operation(F64, SoftFloatOp::Sub, infinity, infinity),
+            0x7ff8_0000_0000_0000
+        );
+        assert_eq!(
+            operation(F64, SoftFloatOp::Sqrt, (-1.0f64).to_bits(), 0),
+            0x7ff8_0000_0000_0000
+        );
+    }
+
+    #[test]
+    fn conversions_match_the_host() {
+        use SoftFloatConversion::*;
+
+        let integers = [
+            0u64,
+            1,
+            0x7fff_ffff,
+            0x8000_0000,
+            0xffff_ffff,
+            0x0100_0001,
+            0x0100_0003,
+            0x0020_0000_0000_0001,
+            0x7fff_ffff_ffff_ffff,
+            0x8000_0000_0000_0000,
+            0xffff_ffff_ffff_ffff,
+            0xffff_ff7f_ffff_ffff,
+            0x1234_5678_9abc_def0,
+        ];
+        for &x in &integers {
+            let cases = [
+                (F32ConvertI32S, (x as i32 as f32).to_bits() as u64),
+                (F32ConvertI32U, (x as u32 as f32).to_bits() as u64),
+                (F32ConvertI64S, (x as i64 as f32).to_bits() as u64),
+                (F32ConvertI64U, (x as f32).to_bits() as u64),
+                (F64ConvertI32S, (x as i32 as f64).to_bits()),
+                (F64ConvertI32U, (x as u32 as f64).to_bits()),
+                (F64ConvertI64S, (x as i64 as f64).to_bits()),
+                (F64ConvertI64U, (x as f64).to_bits()),
+            ];
+            for (op, expected) in cases {
+                assert_eq!(conversion(op, x), Ok(expected), "{:?}({:#x})", op, x);
+            }
+        }
+
+        let in_range =
+            |x: f64, min: f64, end: f64| !x.is_nan() && x.trunc() >= min && x.trunc() < end;
+        let checked = |x: f64, min: f64, end: f64, value: u64| {
+            if x.is_nan() {
+                Err(TrapCode::BadConversionToInteger)
+            } else if in_range(x, min, end) {
+                Ok(value)
+            } else {
+                Err(TrapCode::IntegerOverflow)
+            }
+        };
+        let (i32_end, i64_end) = (2147483648.0, 9223372036854775808.0);
+        for x in f64_samples() {
+            assert_eq!(
+                conversion(F32DemoteF64, x.to_bits()),
+                Ok((x as f32).to_bits() as u64)
+            );
+            let cases = [
+                (
+                    I32TruncF64S,
+                    checked(x, -i32_end, i32_end, x as i32 as u32 as u64),
+                ),
+                (
+                    I32TruncF64U,
+                    checked(x, 0.0, 2.0 * i32_end, x as u32 as u64),
+                ),
+                (I64TruncF64S, checked(x, -i64_end, i64_end, x as i64 as u64)),
+                (I64TruncF64U, checked(x, 0.0, 2.0 * i64_end, x as u64)),
+                (I32TruncSatF64S, Ok(x as i32 as u32 as u64)),
+                (I32TruncSatF64U, Ok(x as u32 as u64)),
+                (I64TruncSatF64S, Ok(x as i64 as u64)),
+                (I64TruncSatF64U, Ok(x as u64)),
+            ];
+            for (op, expected) in cases {
+                assert_eq!(conversion(op, x.to_bits()), expected, "{:?}({})", op, x);
+            }
+        }
+        for x in f32_samples() {
+            let bits = x.to_bits() as u64;
+            let wide = x as f64;
+            assert_eq!(conversion(F64PromoteF32, bits), Ok(wide.to_bits()));
+            let cases = [
+                (
+                    I32TruncF32S,
+                    checked(wide, -i32_end, i32_end, x as i32 as u32 as u64),
+                ),
+                (
+                    I32TruncF32U,
+                    checked(wide, 0.0, 2.0 * i32_end, x as u32 as u64),
+                ),
+                (
+                    I64TruncF32S,
+                    checked(wide, -i64_end, i64_end, x as i64 as u64),
+                ),
+                (I64TruncF32U, checked(wide, 0.0, 2.0 * i64_end, x as u64)),
+                (I32TruncSatF32S, Ok(x as i32 as u32 as u64)),
+                (I32TruncSatF32U, Ok(x as u32 as u64)),
+                (I64TruncSatF32S, Ok(x as i64 as u64)),
+                (I64TruncSatF32U, Ok(x as u64)),
+            ];
+            for (op, expected) in cases {
+                assert_eq!(conversion(op, bits), expected, "{:?}({})", op, x);
+            }
+        }
+
+        // NaNs keep their sign and the top bits of their payload.
+        assert_eq!(
+            conversion(F64PromoteF32, 0xff80_0001),
+            Ok(0xfff8_0000_2000_0000)
+        );
+        assert_eq!(
+            conversion(F32DemoteF64, 0x7ff4_0000_0000_0000),
+            Ok(0x7fe0_0000)
+        );
+        assert_eq!(conversion(I32TruncSatF64S, f64::NAN.to_bits()), Ok(0));
+    }
+}
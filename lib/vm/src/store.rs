@@ -1,5 +1,6 @@
-use core::slice::Iter;
-use std::{
+use crate::lib::std::boxed::Box;
+use crate::lib::std::vec::Vec;
+use crate::lib::std::{
     cell::UnsafeCell,
     fmt,
     marker::PhantomData,
@@ -7,6 +8,7 @@ use std::{
     ptr::NonNull,
     sync::atomic::{AtomicU64, Ordering},
 };
+use core::slice::Iter;
 
 use crate::{
     VMExternObj, VMFunction, VMFunctionEnvironment, VMGlobal, VMInstance, VMMemory, VMTable,
@@ -137,8 +139,8 @@ impl<T> Clone for StoreHandle<T> {
     }
 }
 
-impl<T> std::hash::Hash for StoreHandle<T> {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+impl<T> crate::lib::std::hash::Hash for StoreHandle<T> {
+    fn hash<H: crate::lib::std::hash::Hasher>(&self, state: &mut H) {
         self.id.hash(state);
         self.internal.idx.hash(state);
     }
@@ -293,9 +295,9 @@ impl<T> MaybeInstanceOwned<T> {
     }
 }
 
-impl<T> std::fmt::Debug for MaybeInstanceOwned<T>
+impl<T> crate::lib::std::fmt::Debug for MaybeInstanceOwned<T>
 where
-    T: std::fmt::Debug,
+    T: crate::lib::std::fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
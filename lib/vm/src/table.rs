@@ -5,16 +5,21 @@
 //!
 //! `Table` is to WebAssembly tables what `Memory` is to WebAssembly linear memories.
 
+use crate::lib::std::boxed::Box;
+use crate::lib::std::cell::UnsafeCell;
+use crate::lib::std::convert::TryFrom;
+use crate::lib::std::fmt;
+use crate::lib::std::format;
+use crate::lib::std::ptr::NonNull;
+use crate::lib::std::string::{String, ToString};
+use crate::lib::std::vec;
+use crate::lib::std::vec::Vec;
 use crate::store::MaybeInstanceOwned;
 use crate::vmcontext::VMTableDefinition;
 use crate::Trap;
 use crate::VMExternRef;
 use crate::VMFuncRef;
 use derivative::Derivative;
-use std::cell::UnsafeCell;
-use std::convert::TryFrom;
-use std::fmt;
-use std::ptr::NonNull;
 use wasmer_types::TableStyle;
 use wasmer_types::{TableType, TrapCode, Type as ValType};
 
@@ -46,7 +51,7 @@ pub union RawTableElement {
 #[cfg(test)]
 #[test]
 fn table_element_size_test() {
-    use std::mem::size_of;
+    use crate::lib::std::mem::size_of;
     assert_eq!(size_of::<RawTableElement>(), size_of::<VMExternRef>());
     assert_eq!(size_of::<RawTableElement>(), size_of::<VMFuncRef>());
 }
@@ -217,6 +222,21 @@ impl VMTable {
         Some(size)
     }
 
+    /// Shrinks the table back to its minimum size, with null elements.
+    pub fn reset(&mut self) {
+        let minimum = usize::try_from(self.table.minimum).unwrap();
+        self.vec.clear();
+        self.vec.resize(minimum, RawTableElement::default());
+
+        // update table definition
+        unsafe {
+            let mut td_ptr = self.get_vm_table_definition();
+            let td = td_ptr.as_mut();
+            td.current_elements = self.table.minimum;
+            td.base = self.vec.as_mut_ptr() as _;
+        }
+    }
+
     /// Get reference to the specified element.
     ///
     /// Returns `None` if the index is out of bounds.
@@ -4,14 +4,24 @@
 //! This is the module that facilitates the usage of Traps
 //! in Wasmer Runtime
 
+#[cfg(not(feature = "std"))]
+mod platform;
 #[allow(clippy::module_inception)]
 mod trap;
+#[cfg(feature = "std")]
 mod traphandlers;
 
+#[cfg(not(feature = "std"))]
+pub use platform::{
+    catch_traps, init_traps, on_host_stack, raise_lib_trap, raise_user_trap, raise_wasm_trap,
+    suspend_host_stack, wasmer_call_trampoline, Platform, TrapHandler, TrapHandlerFn,
+};
 pub use trap::Trap;
+#[cfg(feature = "std")]
 pub use traphandlers::{
-    catch_traps, on_host_stack, raise_lib_trap, raise_user_trap, wasmer_call_trampoline,
-    TrapHandler, TrapHandlerFn,
+    catch_traps, on_host_stack, raise_lib_trap, raise_user_trap, suspend_host_stack,
+    wasmer_call_trampoline, TrapHandler, TrapHandlerFn,
 };
+#[cfg(feature = "std")]
 pub use traphandlers::{init_traps, resume_panic};
 pub use wasmer_types::TrapCode;
@@ -0,0 +1,218 @@
+//! Trap handling for the builds without `std`, delegated to the
+//! [`Platform`] provided by the embedder.
+//!
+//! Without an operating system, there are no signal handlers to catch
+//! the faults of Wasm code and no threads to keep the trap state in.
+//! Instead, the embedder provides a way to unwind to the caller of the
+//! Wasm code, like `setjmp`/`longjmp`, and reports the processor faults
+//! of the Wasm code with [`raise_wasm_trap`]. Only one thread may run
+//! Wasm code at a time, since the state of the trap being raised is
+//! global.
+
+use crate::lib::std::any::Any;
+use crate::lib::std::boxed::Box;
+use crate::lib::std::mem;
+use crate::lib::std::sync::Mutex;
+use crate::vmcontext::{VMFunctionContext, VMTrampoline};
+use crate::{Trap, VMFunctionBody};
+use spin::Once;
+use wasmer_types::TrapCode;
+
+/// The services of the target needed to run Wasm code without `std`,
+/// provided by the embedder with [`init_traps`].
+///
+/// # Safety
+///
+/// [`Platform::catch_unwind`] and [`Platform::unwind`] must behave as
+/// documented, Wasm code relying on them to return from traps.
+pub unsafe trait Platform: Sync {
+    /// Calls `f(data)`, and returns `true` once it returns, or `false`
+    /// if [`Platform::unwind`] is called while it runs.
+    ///
+    /// The calls may be nested, e.g. when a host function called by
+    /// Wasm code calls Wasm code again.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be valid for `f`.
+    unsafe fn catch_unwind(&self, f: unsafe fn(*mut u8), data: *mut u8) -> bool;
+
+    /// Returns from the innermost call to [`Platform::catch_unwind`] in
+    /// progress, without running the destructors of the frames in
+    /// between.
+    ///
+    /// # Safety
+    ///
+    /// A call to [`Platform::catch_unwind`] must be in progress.
+    unsafe fn unwind(&self) -> !;
+}
+
+/// A package of functionality needed by `catch_traps` to figure out what to do
+/// when handling a trap.
+///
+/// # Safety
+///
+/// The custom trap handlers are never called in the builds without
+/// `std`, the traps being handled by the [`Platform`].
+pub unsafe trait TrapHandler {
+    /// Uses `call` to call a custom signal handler, if one is specified.
+    ///
+    /// Returns `true` if `call` returns true, otherwise returns `false`.
+    fn custom_trap_handler(&self, call: &dyn Fn(&TrapHandlerFn) -> bool) -> bool;
+}
+
+/// Function which may handle custom traps, see [`TrapHandler`].
+pub type TrapHandlerFn<'a> = dyn Fn() -> bool + Send + Sync + 'a;
+
+static PLATFORM: Once<&'static dyn Platform> = Once::new();
+
+/// The trap being raised, taken by the innermost `catch_traps`.
+static UNWIND_REASON: Mutex<Option<Trap>> = Mutex::new(None);
+
+fn platform() -> &'static dyn Platform {
+    *PLATFORM
+        .get()
+        .expect("`init_traps` must be called before entering WebAssembly")
+}
+
+/// Provides the [`Platform`] handling the traps of the Wasm code.
+///
+/// This function must be called before entering WebAssembly. Only the
+/// first call sets the platform, the next ones are ignored.
+pub fn init_traps(platform: &'static dyn Platform) {
+    PLATFORM.call_once(|| platform);
+}
+
+/// Raises a user-defined trap immediately.
+///
+/// This function performs as-if a wasm trap was just executed, only the trap
+/// has a dynamic payload associated with it which is user-provided. This trap
+/// payload is then returned from `catch_traps` below.
+///
+/// # Safety
+///
+/// Only safe to call when wasm code is on the stack, aka `catch_traps` must
+/// have been previous called and not yet returned.
+/// Additionally no Rust destructors may be on the stack.
+/// They will be skipped and not executed.
+pub unsafe fn raise_user_trap(data: Box<dyn Any + Send + Sync>) -> ! {
+    unwind_with(Trap::User(data))
+}
+
+/// Raises a trap from inside library code immediately.
+///
+/// This function performs as-if a wasm trap was just executed. This trap
+/// payload is then returned from `catch_traps` below.
+///
+/// # Safety
+///
+/// Only safe to call when wasm code is on the stack, aka `catch_traps` must
+/// have been previous called and not yet returned.
+/// Additionally no Rust destructors may be on the stack.
+/// They will be skipped and not executed.
+pub unsafe fn raise_lib_trap(trap: Trap) -> ! {
+    unwind_with(trap)
+}
+
+/// Raises the trap of a processor fault of the Wasm code at `pc`, e.g.
+/// an illegal instruction or a division by zero.
+///
+/// It's called by the exception handler of the [`Platform`], once it has
+/// left the exception context, with the trap code of the fault when it
+/// knows it. The trap code of the other faults is found from `pc` by the
+/// engine.
+///
+/// # Safety
+///
+/// Only safe to call when wasm code is on the stack, aka `catch_traps` must
+/// have been previous called and not yet returned.
+pub unsafe fn raise_wasm_trap(pc: usize, signal_trap: Option<TrapCode>) -> ! {
+    unwind_with(Trap::wasm(pc, signal_trap))
+}
+
+/// Call the wasm function pointed to by `callee`.
+///
+/// * `vmctx` - the callee vmctx argument
+/// * `caller_vmctx` - the caller vmctx argument
+/// * `trampoline` - the jit-generated trampoline whose ABI takes 4 values, the
+///   callee vmctx, the caller vmctx, the `callee` argument below, and then the
+///   `values_vec` argument.
+/// * `callee` - the third argument to the `trampoline` function
+/// * `values_vec` - points to a buffer which holds the incoming arguments, and to
+///   which the outgoing return values will be written.
+///
+/// # Safety
+///
+/// Wildly unsafe because it calls raw function pointers and reads/writes raw
+/// function pointers.
+pub unsafe fn wasmer_call_trampoline(
+    trap_handler: Option<*const TrapHandlerFn<'static>>,
+    vmctx: VMFunctionContext,
+    trampoline: VMTrampoline,
+    callee: *const VMFunctionBody,
+    values_vec: *mut u8,
+) -> Result<(), Trap> {
+    catch_traps(trap_handler, || {
+        mem::transmute::<_, extern "C" fn(VMFunctionContext, *const VMFunctionBody, *mut u8)>(
+            trampoline,
+        )(vmctx, callee, values_vec);
+    })
+}
+
+/// Catches any wasm traps that happen within the execution of `closure`,
+/// returning them as a `Result`.
+///
+/// # Safety
+///
+/// Highly unsafe since `closure` won't have any dtors run.
+pub unsafe fn catch_traps<F, R>(
+    _trap_handler: Option<*const TrapHandlerFn<'static>>,
+    closure: F,
+) -> Result<R, Trap>
+where
+    F: FnOnce() -> R,
+{
+    struct Call<F, R> {
+        closure: Option<F>,
+        result: Option<R>,
+    }
+
+    unsafe fn call<F: FnOnce() -> R, R>(data: *mut u8) {
+        let call = &mut *(data as *mut Call<F, R>);
+        let closure = call.closure.take().unwrap();
+        call.result = Some(closure());
+    }
+
+    let mut state = Call {
+        closure: Some(closure),
+        result: None,
+    };
+    if platform().catch_unwind(call::<F, R>, &mut state as *mut Call<F, R> as *mut u8) {
+        Ok(state.result.take().unwrap())
+    } else {
+        Err(UNWIND_REASON
+            .lock()
+            .unwrap()
+            .take()
+            .expect("unwound without a trap"))
+    }
+}
+
+unsafe fn unwind_with(trap: Trap) -> ! {
+    // The lock is released before unwinding, which skips the destructors.
+    *UNWIND_REASON.lock().unwrap() = Some(trap);
+    platform().unwind()
+}
+
+/// Runs `f` on the host stack. Without `std` the Wasm code runs on the
+/// stack of its caller, so it's called directly.
+pub fn on_host_stack<F: FnOnce() -> T, T>(f: F) -> T {
+    f()
+}
+
+/// Runs `f`, which suspends the stack of a host function called by Wasm
+/// code. Without `std` there is no trap handler state to save, so it's
+/// called directly.
+pub fn suspend_host_stack<F: FnOnce() -> T, T>(f: F) -> T {
+    f()
+}
@@ -877,9 +877,11 @@ fn on_wasm_stack<F: FnOnce() -> T, T>(
         Ok(f())
     });
 
-    // Ensure that YIELDER is reset on exit even if the coroutine panics,
+    // Ensure that YIELDER is restored on exit even if the coroutine panics,
+    // so that a nested call leaves the one of the outer coroutine in place.
+    let previous_yielder = YIELDER.with(|cell| cell.get());
     defer! {
-        YIELDER.with(|cell| cell.set(None));
+        YIELDER.with(|cell| cell.set(previous_yielder));
     }
 
     // Set up metadata for the trap handler for the duration of the coroutine
@@ -933,6 +935,26 @@ pub fn on_host_stack<F: FnOnce() -> T, T>(f: F) -> T {
     yielder.on_parent_stack(move || (wrapped.0)())
 }
 
+/// Runs `f`, which suspends the stack of a host function called by Wasm
+/// code, e.g. by switching to another coroutine, so that other Wasm code
+/// can run on the thread until the stack is resumed.
+///
+/// The trap handler of the suspended Wasm code is uninstalled while `f`
+/// runs, and installed again when it returns.
+pub fn suspend_host_stack<F: FnOnce() -> T, T>(f: F) -> T {
+    let yielder_ptr = YIELDER.with(|cell| cell.replace(None));
+    compiler_fence(Ordering::Release);
+    let trap_handler = TRAP_HANDLER.with(|ptr| ptr.swap(ptr::null_mut(), Ordering::Relaxed));
+
+    defer! {
+        TRAP_HANDLER.with(|ptr| ptr.store(trap_handler, Ordering::Relaxed));
+        compiler_fence(Ordering::Acquire);
+        YIELDER.with(|cell| cell.set(yielder_ptr));
+    }
+
+    f()
+}
+
 #[cfg(windows)]
 pub fn lazy_per_thread_init() -> Result<(), Trap> {
     // We need additional space on the stack to handle stack overflow
@@ -4,18 +4,19 @@
 //! This file declares `VMContext` and several related structs which contain
 //! fields that compiled wasm code accesses directly.
 
+use crate::bulk;
 use crate::global::VMGlobal;
 use crate::instance::Instance;
+use crate::lib::std::convert::TryFrom;
+use crate::lib::std::ptr::NonNull;
+use crate::lib::std::sync::atomic::{AtomicPtr, Ordering};
+use crate::lib::std::u32;
 use crate::memory::VMMemory;
 use crate::store::InternalStoreHandle;
 use crate::trap::{Trap, TrapCode};
 use crate::VMFunctionBody;
 use crate::VMTable;
 use crate::{VMBuiltinFunctionIndex, VMFunction};
-use std::convert::TryFrom;
-use std::ptr::{self, NonNull};
-use std::sync::atomic::{AtomicPtr, Ordering};
-use std::u32;
 use wasmer_types::RawValue;
 
 /// Union representing the first parameter passed when calling a function.
@@ -27,7 +28,7 @@ pub union VMFunctionContext {
     /// Wasm functions take a pointer to [`VMContext`].
     pub vmctx: *mut VMContext,
     /// Host functions can have custom environments.
-    pub host_env: *mut std::ffi::c_void,
+    pub host_env: *mut crate::lib::std::ffi::c_void,
 }
 
 impl VMFunctionContext {
@@ -37,22 +38,22 @@ impl VMFunctionContext {
     }
 }
 
-impl std::fmt::Debug for VMFunctionContext {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl crate::lib::std::fmt::Debug for VMFunctionContext {
+    fn fmt(&self, f: &mut crate::lib::std::fmt::Formatter) -> crate::lib::std::fmt::Result {
         f.debug_struct("VMFunctionContext")
             .field("vmctx_or_hostenv", unsafe { &self.host_env })
             .finish()
     }
 }
 
-impl std::cmp::PartialEq for VMFunctionContext {
+impl crate::lib::std::cmp::PartialEq for VMFunctionContext {
     fn eq(&self, rhs: &Self) -> bool {
         unsafe { self.host_env as usize == rhs.host_env as usize }
     }
 }
 
-impl std::hash::Hash for VMFunctionContext {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+impl crate::lib::std::hash::Hash for VMFunctionContext {
+    fn hash<H: crate::lib::std::hash::Hasher>(&self, state: &mut H) {
         unsafe {
             self.vmctx.hash(state);
         }
@@ -76,8 +77,8 @@ pub struct VMFunctionImport {
 #[cfg(test)]
 mod test_vmfunction_import {
     use super::VMFunctionImport;
+    use crate::lib::std::mem::size_of;
     use memoffset::offset_of;
-    use std::mem::size_of;
     use wasmer_types::ModuleInfo;
     use wasmer_types::VMOffsets;
 
@@ -97,6 +98,10 @@ mod test_vmfunction_import {
             offset_of!(VMFunctionImport, environment),
             usize::from(offsets.vmfunction_import_vmctx())
         );
+        assert_eq!(
+            offset_of!(VMFunctionImport, handle),
+            usize::from(offsets.vmfunction_import_handle())
+        );
     }
 }
 
@@ -139,9 +144,9 @@ impl<T: Sized + Clone + Send + Sync> Clone for VMDynamicFunctionContext<T> {
 #[cfg(test)]
 mod test_vmdynamicfunction_import_context {
     use super::VMDynamicFunctionContext;
+    use crate::lib::std::mem::size_of;
     use crate::VMOffsets;
     use memoffset::offset_of;
-    use std::mem::size_of;
     use wasmer_types::ModuleInfo;
 
     #[test]
@@ -198,9 +203,9 @@ pub struct VMTableImport {
 #[cfg(test)]
 mod test_vmtable_import {
     use super::VMTableImport;
+    use crate::lib::std::mem::size_of;
     use crate::VMOffsets;
     use memoffset::offset_of;
-    use std::mem::size_of;
     use wasmer_types::ModuleInfo;
 
     #[test]
@@ -215,6 +220,10 @@ mod test_vmtable_import {
             offset_of!(VMTableImport, definition),
             usize::from(offsets.vmtable_import_definition())
         );
+        assert_eq!(
+            offset_of!(VMTableImport, handle),
+            usize::from(offsets.vmtable_import_handle())
+        );
     }
 }
 
@@ -233,9 +242,9 @@ pub struct VMMemoryImport {
 #[cfg(test)]
 mod test_vmmemory_import {
     use super::VMMemoryImport;
+    use crate::lib::std::mem::size_of;
     use crate::VMOffsets;
     use memoffset::offset_of;
-    use std::mem::size_of;
     use wasmer_types::ModuleInfo;
 
     #[test]
@@ -284,9 +293,9 @@ unsafe impl Sync for VMGlobalImport {}
 #[cfg(test)]
 mod test_vmglobal_import {
     use super::VMGlobalImport;
+    use crate::lib::std::mem::size_of;
     use crate::VMOffsets;
     use memoffset::offset_of;
-    use std::mem::size_of;
     use wasmer_types::ModuleInfo;
 
     #[test]
@@ -301,6 +310,10 @@ mod test_vmglobal_import {
             offset_of!(VMGlobalImport, definition),
             usize::from(offsets.vmglobal_import_definition())
         );
+        assert_eq!(
+            offset_of!(VMGlobalImport, handle),
+            usize::from(offsets.vmglobal_import_handle())
+        );
     }
 }
 
@@ -338,7 +351,7 @@ pub(crate) unsafe fn memory_copy(
     // everything is safe.
     let dst = mem.base.add(dst);
     let src = mem.base.add(src);
-    ptr::copy(src, dst, len as usize);
+    bulk::copy(dst, src, len as usize);
 
     Ok(())
 }
@@ -372,7 +385,7 @@ pub(crate) unsafe fn memory_fill(
     // Bounds and casts are checked above, by this point we know that
     // everything is safe.
     let dst = mem.base.offset(dst);
-    ptr::write_bytes(dst, val, len as usize);
+    bulk::fill(dst, val, len as usize);
 
     Ok(())
 }
@@ -403,7 +416,8 @@ pub(crate) unsafe fn memory32_atomic_check32(
     // everything is safe.
     let dst = mem.base.offset(dst) as *mut u32;
     let atomic_dst = AtomicPtr::new(dst);
-    let read_val = *atomic_dst.load(Ordering::Acquire);
+    // Wasm memory is little-endian regardless of the host byte order.
+    let read_val = u32::from_le(*atomic_dst.load(Ordering::Acquire));
     let ret = if read_val == val { 0 } else { 1 };
     Ok(ret)
 }
@@ -434,7 +448,8 @@ pub(crate) unsafe fn memory32_atomic_check64(
     // everything is safe.
     let dst = mem.base.offset(dst) as *mut u64;
     let atomic_dst = AtomicPtr::new(dst);
-    let read_val = *atomic_dst.load(Ordering::Acquire);
+    // Wasm memory is little-endian regardless of the host byte order.
+    let read_val = u64::from_le(*atomic_dst.load(Ordering::Acquire));
     let ret = if read_val == val { 0 } else { 1 };
     Ok(ret)
 }
@@ -454,9 +469,9 @@ pub struct VMTableDefinition {
 #[cfg(test)]
 mod test_vmtable_definition {
     use super::VMTableDefinition;
+    use crate::lib::std::mem::size_of;
     use crate::VMOffsets;
     use memoffset::offset_of;
-    use std::mem::size_of;
     use wasmer_types::ModuleInfo;
 
     #[test]
@@ -475,6 +490,10 @@ mod test_vmtable_definition {
             offset_of!(VMTableDefinition, current_elements),
             usize::from(offsets.vmtable_definition_current_elements())
         );
+        assert_eq!(
+            size_of::<u32>(),
+            usize::from(offsets.size_of_vmtable_definition_current_elements())
+        );
     }
 }
 
@@ -492,9 +511,9 @@ pub struct VMGlobalDefinition {
 #[cfg(test)]
 mod test_vmglobal_definition {
     use super::VMGlobalDefinition;
+    use crate::lib::std::mem::{align_of, size_of};
     use crate::{VMFuncRef, VMOffsets};
     use more_asserts::assert_ge;
-    use std::mem::{align_of, size_of};
     use wasmer_types::ModuleInfo;
 
     #[test]
@@ -523,6 +542,30 @@ mod test_vmglobal_definition {
         let offsets = VMOffsets::new(size_of::<*mut u8>() as u8, &module);
         assert_eq!(offsets.vmctx_globals_begin() % 16, 0);
     }
+
+    #[test]
+    fn check_vmglobal_definition_values_start_at_offset_0() {
+        // The generated code loads and stores the values of the globals at
+        // the start of their definition, in the byte order of the host.
+        let mut definition = VMGlobalDefinition::new();
+        definition.val.i32 = 0x0102_0304;
+        assert_eq!(
+            unsafe { &definition.val.bytes[..4] },
+            0x0102_0304i32.to_ne_bytes()
+        );
+        definition.val.i64 = 0x0102_0304_0506_0708;
+        assert_eq!(
+            unsafe { &definition.val.bytes[..8] },
+            0x0102_0304_0506_0708i64.to_ne_bytes()
+        );
+        definition.val.f32 = 1.5;
+        assert_eq!(unsafe { &definition.val.bytes[..4] }, 1.5f32.to_ne_bytes());
+        definition.val.funcref = 0x1234;
+        assert_eq!(
+            unsafe { &definition.val.bytes[..size_of::<usize>()] },
+            0x1234usize.to_ne_bytes()
+        );
+    }
 }
 
 impl VMGlobalDefinition {
@@ -543,7 +586,7 @@ pub struct VMSharedSignatureIndex(u32);
 #[cfg(test)]
 mod test_vmshared_signature_index {
     use super::VMSharedSignatureIndex;
-    use std::mem::size_of;
+    use crate::lib::std::mem::size_of;
     use wasmer_types::{ModuleInfo, TargetSharedSignatureIndex, VMOffsets};
 
     #[test]
@@ -599,9 +642,9 @@ pub struct VMCallerCheckedAnyfunc {
 #[cfg(test)]
 mod test_vmcaller_checked_anyfunc {
     use super::VMCallerCheckedAnyfunc;
+    use crate::lib::std::mem::size_of;
     use crate::VMOffsets;
     use memoffset::offset_of;
-    use std::mem::size_of;
     use wasmer_types::ModuleInfo;
 
     #[test]
@@ -624,6 +667,10 @@ mod test_vmcaller_checked_anyfunc {
             offset_of!(VMCallerCheckedAnyfunc, vmctx),
             usize::from(offsets.vmcaller_checked_anyfunc_vmctx())
         );
+        assert_eq!(
+            offset_of!(VMCallerCheckedAnyfunc, call_trampoline),
+            usize::from(offsets.vmcaller_checked_anyfunc_call_trampoline())
+        );
     }
 }
 
@@ -710,12 +757,45 @@ impl VMBuiltinFunctionsArray {
         ptrs[VMBuiltinFunctionIndex::get_imported_memory_atomic_notify_index().index() as usize] =
             wasmer_vm_imported_memory32_atomic_notify as usize;
 
+        ptrs[VMBuiltinFunctionIndex::get_f32_soft_op_index().index() as usize] =
+            wasmer_vm_f32_soft_op as usize;
+        ptrs[VMBuiltinFunctionIndex::get_f64_soft_op_index().index() as usize] =
+            wasmer_vm_f64_soft_op as usize;
+        ptrs[VMBuiltinFunctionIndex::get_soft_float_convert_index().index() as usize] =
+            wasmer_vm_soft_float_convert as usize;
+
+        ptrs[VMBuiltinFunctionIndex::get_lazy_compile_index().index() as usize] =
+            wasmer_vm_lazy_compile as usize;
+
         debug_assert!(ptrs.iter().cloned().all(|p| p != 0));
 
         Self { ptrs }
     }
 }
 
+#[cfg(test)]
+mod test_vmbuiltin_functions_array {
+    use super::VMBuiltinFunctionsArray;
+    use crate::lib::std::mem::size_of;
+    use crate::VMOffsets;
+    use wasmer_types::{ModuleInfo, VMBuiltinFunctionIndex};
+
+    #[test]
+    fn check_vmbuiltin_functions_array_offsets() {
+        let module = ModuleInfo::new();
+        let offsets = VMOffsets::new(size_of::<*mut u8>() as u8, &module);
+        let last = VMBuiltinFunctionIndex::get_lazy_compile_index();
+        assert_eq!(
+            last.index() + 1,
+            VMBuiltinFunctionIndex::builtin_functions_total_number()
+        );
+        assert_eq!(
+            offsets.vmctx_builtin_function(last) as usize + size_of::<usize>(),
+            offsets.vmctx_builtin_functions_begin() as usize + size_of::<VMBuiltinFunctionsArray>()
+        );
+    }
+}
+
 /// The VM "context", which is pointed to by the `vmctx` arg in the compiler.
 /// This has information about globals, memories, tables, and other runtime
 /// state associated with the current instance.
@@ -747,6 +827,96 @@ impl VMContext {
     }
 }
 
+#[cfg(test)]
+mod test_vmctx {
+    use super::{
+        VMBuiltinFunctionsArray, VMFunctionImport, VMGlobalDefinition, VMGlobalImport,
+        VMMemoryDefinition, VMMemoryImport, VMSharedSignatureIndex, VMTableDefinition,
+        VMTableImport,
+    };
+    use crate::lib::std::mem::{align_of, size_of};
+    use crate::VMOffsets;
+    use wasmer_types::{
+        FunctionType, GlobalType, MemoryType, ModuleInfo, Mutability, TableType, Type,
+    };
+
+    #[test]
+    fn check_vmctx_regions() {
+        let mut module = ModuleInfo::new();
+        for _ in 0..3 {
+            module.signatures.push(FunctionType::new(vec![], vec![]));
+            module.tables.push(TableType::new(Type::FuncRef, 1, None));
+            module.memories.push(MemoryType::new(1, None, false));
+            module
+                .globals
+                .push(GlobalType::new(Type::I32, Mutability::Const));
+        }
+        module.num_imported_functions = 1;
+        module.num_imported_tables = 1;
+        module.num_imported_memories = 1;
+        module.num_imported_globals = 1;
+        let offsets = VMOffsets::new(size_of::<*mut u8>() as u8, &module);
+
+        // Each region follows the previous one, and starts aligned for the
+        // structs it holds, the `VMContext` being aligned on 16 bytes.
+        let regions = [
+            (
+                offsets.vmctx_signature_ids_begin(),
+                3 * size_of::<VMSharedSignatureIndex>(),
+                align_of::<VMSharedSignatureIndex>(),
+            ),
+            (
+                offsets.vmctx_imported_functions_begin(),
+                size_of::<VMFunctionImport>(),
+                align_of::<VMFunctionImport>(),
+            ),
+            (
+                offsets.vmctx_imported_tables_begin(),
+                size_of::<VMTableImport>(),
+                align_of::<VMTableImport>(),
+            ),
+            (
+                offsets.vmctx_imported_memories_begin(),
+                size_of::<VMMemoryImport>(),
+                align_of::<VMMemoryImport>(),
+            ),
+            (
+                offsets.vmctx_imported_globals_begin(),
+                size_of::<VMGlobalImport>(),
+                align_of::<VMGlobalImport>(),
+            ),
+            (
+                offsets.vmctx_tables_begin(),
+                3 * size_of::<VMTableDefinition>(),
+                align_of::<VMTableDefinition>(),
+            ),
+            (
+                offsets.vmctx_memories_begin(),
+                3 * size_of::<VMMemoryDefinition>(),
+                align_of::<VMMemoryDefinition>(),
+            ),
+            (
+                offsets.vmctx_globals_begin(),
+                3 * size_of::<*mut VMGlobalDefinition>(),
+                align_of::<VMGlobalDefinition>(),
+            ),
+            (
+                offsets.vmctx_builtin_functions_begin(),
+                size_of::<VMBuiltinFunctionsArray>(),
+                align_of::<VMBuiltinFunctionsArray>(),
+            ),
+        ];
+        let mut end = 0;
+        for (begin, size, align) in regions {
+            let begin = begin as usize;
+            assert_eq!(begin % align, 0);
+            assert!(begin >= end && begin < end + align);
+            end = begin + size;
+        }
+        assert!(offsets.size_of_vmctx() as usize >= end);
+    }
+}
+
 ///
 pub type VMTrampoline = unsafe extern "C" fn(
     *mut VMContext,        // callee vmctx
@@ -781,10 +951,10 @@ unsafe impl Sync for VMMemoryDefinition {}
 #[cfg(test)]
 mod test_vmmemory_definition {
     use super::VMMemoryDefinition;
+    use crate::lib::std::mem::size_of;
     use crate::ModuleInfo;
     use crate::VMOffsets;
     use memoffset::offset_of;
-    use std::mem::size_of;
 
     #[test]
     fn check_vmmemory_definition_offsets() {
@@ -802,5 +972,37 @@ mod test_vmmemory_definition {
             offset_of!(VMMemoryDefinition, current_length),
             usize::from(offsets.vmmemory_definition_current_length())
         );
+        assert_eq!(
+            size_of::<usize>(),
+            usize::from(offsets.size_of_vmmemory_definition_current_length())
+        );
+    }
+
+    #[test]
+    fn check_atomic_check_reads_little_endian() {
+        use super::{memory32_atomic_check32, memory32_atomic_check64};
+
+        #[repr(align(8))]
+        struct Buffer([u8; 16]);
+        let mut buffer = Buffer([0; 16]);
+        buffer.0[..4].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+        buffer.0[8..].copy_from_slice(&0x0102_0304_0506_0708u64.to_le_bytes());
+        let mem = VMMemoryDefinition {
+            base: buffer.0.as_mut_ptr(),
+            current_length: buffer.0.len(),
+        };
+
+        unsafe {
+            assert_eq!(memory32_atomic_check32(&mem, 0, 0x1234_5678).unwrap(), 0);
+            assert_eq!(memory32_atomic_check32(&mem, 0, 0x7856_3412).unwrap(), 1);
+            assert_eq!(
+                memory32_atomic_check64(&mem, 8, 0x0102_0304_0506_0708).unwrap(),
+                0
+            );
+            assert_eq!(
+                memory32_atomic_check64(&mem, 8, 0x0807_0605_0403_0201).unwrap(),
+                1
+            );
+        }
     }
 }
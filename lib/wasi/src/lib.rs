@@ -293,6 +293,31 @@ pub fn generate_import_object_from_env(
     }
 }
 
+/// The namespaces of the WASI preview1 snapshots.
+pub(crate) const WASI_NAMESPACES: [&str; 2] = ["wasi_unstable", "wasi_snapshot_preview1"];
+
+/// Returns the exports providing the WASI `namespace`.
+///
+/// When a version is forced with [`WasiEnv::wasi_version`], its exports are
+/// provided under both namespaces, for modules with mixed or mislabelled
+/// imports.
+pub(crate) fn wasi_namespace_exports(
+    store: &mut impl AsStoreMut,
+    env: &FunctionEnv<WasiEnv>,
+    namespace: &str,
+) -> Exports {
+    let snapshot0 = match env.as_ref(store).wasi_version {
+        Some(WasiVersion::Snapshot0) => true,
+        Some(WasiVersion::Snapshot1 | WasiVersion::Latest) => false,
+        _ => namespace == "wasi_unstable",
+    };
+    if snapshot0 {
+        wasi_unstable_exports(store, env)
+    } else {
+        wasi_snapshot_preview1_exports(store, env)
+    }
+}
+
 fn wasi_unstable_exports(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>) -> Exports {
     use syscalls::*;
     let namespace = namespace! {
@@ -667,19 +692,26 @@ fn import_object_for_all_wasi_versions(
     store: &mut impl AsStoreMut,
     env: &FunctionEnv<WasiEnv>,
 ) -> (Imports, ModuleInitializer) {
-    let exports_wasi_unstable = wasi_unstable_exports(store, env);
-    let exports_wasi_snapshot_preview1 = wasi_snapshot_preview1_exports(store, env);
-    let exports_wasix_32v1 = wasix_exports_32(store, env);
-    let exports_wasix_64v1 = wasix_exports_64(store, env);
+    // Only the namespaces the module imports from are created, as creating
+    // all the host functions for every instance is costly.
+    let imports_from = |ns: &str| module.imports().any(|import| import.module() == ns);
 
     // Allowed due to JS feature flag complications.
     #[allow(unused_mut)]
-    let mut imports = imports! {
-        "wasi_unstable" => exports_wasi_unstable,
-        "wasi_snapshot_preview1" => exports_wasi_snapshot_preview1,
-        "wasix_32v1" => exports_wasix_32v1,
-        "wasix_64v1" => exports_wasix_64v1,
-    };
+    let mut imports = Imports::new();
+
+    for namespace in WASI_NAMESPACES {
+        if imports_from(namespace) {
+            let exports = wasi_namespace_exports(store, env, namespace);
+            imports.register_namespace(namespace, exports);
+        }
+    }
+    if imports_from("wasix_32v1") {
+        imports.register_namespace("wasix_32v1", wasix_exports_32(store, env));
+    }
+    if imports_from("wasix_64v1") {
+        imports.register_namespace("wasix_64v1", wasix_exports_64(store, env));
+    }
 
     // TODO: clean this up!
     cfg_if::cfg_if! {
@@ -18,7 +18,7 @@ use crate::{
     state::WasiState,
     syscalls::types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO},
     Capabilities, PluggableRuntimeImplementation, WasiEnv, WasiFunctionEnv, WasiRuntime,
-    WasiRuntimeError,
+    WasiRuntimeError, WasiVersion,
 };
 
 use super::env::WasiEnvInit;
@@ -66,6 +66,11 @@ pub struct WasiEnvBuilder {
     pub(super) map_commands: HashMap<String, PathBuf>,
 
     pub(super) capabilites: Capabilities,
+
+    /// WASI version to provide under both the `wasi_unstable` and
+    /// `wasi_snapshot_preview1` namespaces, regardless of what the
+    /// module imports.
+    pub(super) wasi_version: Option<WasiVersion>,
 }
 
 impl std::fmt::Debug for WasiEnvBuilder {
@@ -537,6 +542,20 @@ impl WasiEnvBuilder {
         self.capabilites = capabilities;
     }
 
+    /// Forces the WASI version of the imports, instead of providing
+    /// each version under its own namespace. This is useful for modules
+    /// that mix or mislabel the `wasi_unstable` and
+    /// `wasi_snapshot_preview1` namespaces.
+    pub fn wasi_version(mut self, version: WasiVersion) -> Self {
+        self.set_wasi_version(version);
+        self
+    }
+
+    /// Forces the WASI version of the imports.
+    pub fn set_wasi_version(&mut self, version: WasiVersion) {
+        self.wasi_version = Some(version);
+    }
+
     /// Consumes the [`WasiEnvBuilder`] and produces a [`WasiEnvInit`], which
     /// can be used to construct a new [`WasiEnv`] with [`WasiEnv::new`].
     ///
@@ -699,6 +718,7 @@ impl WasiEnvBuilder {
             process: None,
             thread: None,
             call_initialize: true,
+            wasi_version: self.wasi_version,
         };
 
         Ok(init)
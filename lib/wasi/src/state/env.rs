@@ -29,7 +29,7 @@ use crate::{
     runtime::SpawnType,
     syscalls::platform_clock_time_get,
     SpawnedMemory, VirtualTaskManager, WasiControlPlane, WasiEnvBuilder, WasiError,
-    WasiFunctionEnv, WasiRuntime, WasiRuntimeError, WasiStateCreationError, WasiVFork,
+    WasiFunctionEnv, WasiRuntime, WasiRuntimeError, WasiStateCreationError, WasiVFork, WasiVersion,
     DEFAULT_STACK_SIZE,
 };
 
@@ -217,6 +217,10 @@ pub struct WasiEnvInit {
     /// Whether to call the `_initialize` function in the WASI module.
     /// Will be true for regular new instances, but false for threads.
     pub call_initialize: bool,
+
+    /// WASI version forced for both the `wasi_unstable` and
+    /// `wasi_snapshot_preview1` namespaces, if any.
+    pub wasi_version: Option<WasiVersion>,
 }
 
 impl WasiEnvInit {
@@ -253,6 +257,7 @@ impl WasiEnvInit {
             process: None,
             thread: None,
             call_initialize: self.call_initialize,
+            wasi_version: self.wasi_version,
         }
     }
 }
@@ -287,6 +292,10 @@ pub struct WasiEnv {
     pub module_cache: Arc<ModuleCache>,
 
     pub capabilities: Capabilities,
+
+    /// WASI version forced for both the `wasi_unstable` and
+    /// `wasi_snapshot_preview1` namespaces, if any.
+    pub wasi_version: Option<WasiVersion>,
 }
 
 // FIXME: remove unsafe impls!
@@ -325,6 +334,7 @@ impl WasiEnv {
             runtime: self.runtime.clone(),
             module_cache: self.module_cache.clone(),
             capabilities: self.capabilities.clone(),
+            wasi_version: self.wasi_version,
         }
     }
 
@@ -354,6 +364,7 @@ impl WasiEnv {
             runtime: self.runtime.clone(),
             capabilities: self.capabilities.clone(),
             module_cache: self.module_cache.clone(),
+            wasi_version: self.wasi_version,
         };
         Ok((new_env, handle))
     }
@@ -392,6 +403,7 @@ impl WasiEnv {
             bin_factory: init.bin_factory,
             module_cache: init.module_cache.clone(),
             capabilities: init.capabilities,
+            wasi_version: init.wasi_version,
         };
         env.owned_handles.push(thread);
 
@@ -5,7 +5,7 @@ use wasmer_wasi_types::wasi::ExitCode;
 use crate::{
     state::WasiInstanceHandles,
     utils::{get_wasi_version, get_wasi_versions},
-    WasiEnv, WasiError, DEFAULT_STACK_SIZE,
+    WasiEnv, WasiError, WasiVersion, DEFAULT_STACK_SIZE,
 };
 
 pub struct WasiFunctionEnv {
@@ -25,6 +25,17 @@ impl WasiFunctionEnv {
         store: &mut impl AsStoreMut,
         module: &Module,
     ) -> Result<Imports, WasiError> {
+        if let Some(WasiVersion::Snapshot0 | WasiVersion::Snapshot1 | WasiVersion::Latest) =
+            self.data(&*store).wasi_version
+        {
+            let mut resolver = Imports::new();
+            for namespace in crate::WASI_NAMESPACES {
+                let exports = crate::wasi_namespace_exports(store, &self.env, namespace);
+                resolver.register_namespace(namespace, exports);
+            }
+            return Ok(resolver);
+        }
+
         let wasi_version = get_wasi_version(module, false).ok_or(WasiError::UnknownWasiVersion)?;
         Ok(crate::generate_import_object_from_env(
             store,
@@ -0,0 +1,118 @@
+use anyhow::Result;
+use wasmer::*;
+
+fn get_module(store: &Store) -> Result<Module> {
+    let wat = r#"
+        (module
+            (memory (export "memory") 1)
+            (global $next (mut i32) (i32.const 16))
+            (global $live (export "live") (mut i32) (i32.const 0))
+
+            (func $alloc (export "alloc") (param $len i32) (result i32)
+                (global.set $live (i32.add (global.get $live) (i32.const 1)))
+                (global.get $next)
+                (global.set $next (i32.add (global.get $next) (local.get $len))))
+            (func (export "dealloc") (param $ptr i32) (param $len i32)
+                (global.set $live (i32.sub (global.get $live) (i32.const 1))))
+
+            ;; Returns a copy of its input, with the ASCII lowercase letters
+            ;; turned into uppercase ones.
+            (func (export "upper") (param $ptr i32) (param $len i32) (result i64)
+                (local $out i32) (local $i i32) (local $c i32)
+                (local.set $out (call $alloc (local.get $len)))
+                (block $done
+                    (loop $next
+                        (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+                        (local.set $c (i32.load8_u (i32.add (local.get $ptr) (local.get $i))))
+                        (if (i32.and
+                                (i32.ge_u (local.get $c) (i32.const 97))
+                                (i32.le_u (local.get $c) (i32.const 122)))
+                            (then (local.set $c (i32.sub (local.get $c) (i32.const 32)))))
+                        (i32.store8 (i32.add (local.get $out) (local.get $i)) (local.get $c))
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br $next)))
+                (i64.or
+                    (i64.shl (i64.extend_i32_u (local.get $out)) (i64.const 32))
+                    (i64.extend_i32_u (local.get $len))))
+
+            ;; Returns the first byte of its input, which isn't valid UTF-8
+            ;; when the input starts with a multi-byte character.
+            (func (export "first") (param $ptr i32) (param $len i32) (result i64)
+                (local $out i32)
+                (local.set $out (call $alloc (i32.const 1)))
+                (i32.store8 (local.get $out) (i32.load8_u (local.get $ptr)))
+                (i64.or
+                    (i64.shl (i64.extend_i32_u (local.get $out)) (i64.const 32))
+                    (i64.const 1)))
+
+            (func (export "fail") (param i32 i32) (result i64)
+                (unreachable))
+        )
+    "#;
+    Ok(Module::new(store, wat)?)
+}
+
+fn live_buffers(store: &mut Store, instance: &Instance) -> Result<Value> {
+    Ok(instance.exports.get_global("live")?.get(store))
+}
+
+#[compiler_test(adapter)]
+fn buffers_are_passed_and_freed(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let module = get_module(&store)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let adapter = BufferAdapter::new(&store, &instance)?;
+
+    assert_eq!(
+        adapter.call_str(&mut store, "upper", "hello, wasmer")?,
+        "HELLO, WASMER"
+    );
+    assert_eq!(adapter.call_bytes(&mut store, "upper", b"")?, b"");
+    assert_eq!(live_buffers(&mut store, &instance)?, Value::I32(0));
+
+    // The input is freed even when the call traps.
+    assert!(matches!(
+        adapter.call_str(&mut store, "fail", "input"),
+        Err(AdapterError::Runtime(_))
+    ));
+    assert_eq!(live_buffers(&mut store, &instance)?, Value::I32(0));
+
+    Ok(())
+}
+
+#[compiler_test(adapter)]
+fn adapter_errors(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let module = get_module(&store)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let adapter = BufferAdapter::new(&store, &instance)?;
+
+    assert!(matches!(
+        adapter.call_bytes(&mut store, "missing", b""),
+        Err(AdapterError::Export(ExportError::Missing(_)))
+    ));
+    assert!(matches!(
+        adapter.call_str(&mut store, "alloc", ""),
+        Err(AdapterError::Export(ExportError::IncompatibleType))
+    ));
+    assert_eq!(
+        adapter.call_bytes(&mut store, "first", "\u{e9}".as_bytes())?,
+        [0xc3]
+    );
+    assert!(matches!(
+        adapter.call_str(&mut store, "first", "\u{e9}"),
+        Err(AdapterError::MemoryAccess(MemoryAccessError::NonUtf8String))
+    ));
+
+    // Without a deallocator, the buffers are left to the guest.
+    let adapter = BufferAdapter::with_exports(&store, &instance, "memory", "alloc", None)?;
+    adapter.call_str(&mut store, "upper", "leak")?;
+    assert_eq!(live_buffers(&mut store, &instance)?, Value::I32(2));
+
+    assert!(matches!(
+        BufferAdapter::with_exports(&store, &instance, "memory", "missing", None),
+        Err(AdapterError::Export(ExportError::Missing(_)))
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::thread::{self, Thread};
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (func (export "add_one") (param i32) (result i32)
+            (i32.add (local.get 0) (i32.const 1))))
+"#;
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs `future` to completion on the current thread, parking it while
+/// the future is pending.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match Pin::as_mut(&mut future).poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+fn add_one(store: &mut Store, module: &Module) -> Result<i32> {
+    let instance = Instance::new(store, module, &imports! {})?;
+    let add_one: TypedFunction<i32, i32> = instance.exports.get_typed_function(store, "add_one")?;
+    Ok(add_one.call(store, 41)?)
+}
+
+#[compiler_test(background)]
+fn modules_are_compiled_in_background(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+
+    let module = Module::compile_in_background(&store, WAT).wait()?;
+    assert_eq!(add_one(&mut store, &module)?, 42);
+
+    let compiling = Module::compile_in_background(&store, WAT.as_bytes().to_vec());
+    let module = block_on(compiling)?;
+    assert!(module.exports().functions().any(|f| f.name() == "add_one"));
+    assert_eq!(add_one(&mut store, &module)?, 42);
+
+    Ok(())
+}
+
+#[compiler_test(background)]
+fn background_compilation_errors(config: crate::Config) -> Result<()> {
+    let store = config.store();
+
+    let compiling = Module::compile_in_background(&store, "(module (func (result i32)))");
+    assert!(matches!(
+        block_on(compiling),
+        Err(CompileError::Validate(_))
+    ));
+
+    let compiling = Module::compile_in_background(&store, b"\0asm\x01".to_vec());
+    while !compiling.is_finished() {
+        thread::yield_now();
+    }
+    assert!(compiling.wait().is_err());
+
+    Ok(())
+}
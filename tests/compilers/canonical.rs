@@ -0,0 +1,274 @@
+use anyhow::Result;
+use wasmer::*;
+
+fn get_instance(store: &mut Store) -> Result<Instance> {
+    let wat = r#"
+        (module
+            (memory (export "memory") 1)
+            (global $next (mut i32) (i32.const 1024))
+            (global $post_returns (export "post_returns") (mut i32) (i32.const 0))
+
+            (func (export "cabi_realloc")
+                (param $old_ptr i32) (param $old_len i32) (param $align i32) (param $len i32)
+                (result i32)
+                (local $ptr i32)
+                (local.set $ptr
+                    (i32.and
+                        (i32.add (global.get $next) (i32.sub (local.get $align) (i32.const 1)))
+                        (i32.sub (i32.const 0) (local.get $align))))
+                (global.set $next (i32.add (local.get $ptr) (local.get $len)))
+                (local.get $ptr))
+
+            ;; sum: func(values: list<u32>) -> u64
+            (func (export "sum") (param $ptr i32) (param $len i32) (result i64)
+                (local $total i64)
+                (block $done
+                    (loop $next
+                        (br_if $done (i32.eqz (local.get $len)))
+                        (local.set $total
+                            (i64.add (local.get $total) (i64.load32_u (local.get $ptr))))
+                        (local.set $ptr (i32.add (local.get $ptr) (i32.const 4)))
+                        (local.set $len (i32.sub (local.get $len) (i32.const 1)))
+                        (br $next)))
+                (local.get $total))
+
+            ;; echo: func(s: string) -> string
+            (func (export "echo") (param $ptr i32) (param $len i32) (result i32)
+                (i32.store (i32.const 8) (local.get $ptr))
+                (i32.store (i32.const 12) (local.get $len))
+                (i32.const 8))
+            (func (export "cabi_post_echo") (param i32)
+                (global.set $post_returns (i32.add (global.get $post_returns) (i32.const 1))))
+
+            ;; move: func(p: point, dx: s32) -> point
+            ;; with point: record { x: s32, y: s32, label: string }
+            (func (export "move")
+                (param $x i32) (param $y i32) (param $ptr i32) (param $len i32) (param $dx i32)
+                (result i32)
+                (i32.store (i32.const 16) (i32.add (local.get $x) (local.get $dx)))
+                (i32.store (i32.const 20) (local.get $y))
+                (i32.store (i32.const 24) (local.get $ptr))
+                (i32.store (i32.const 28) (local.get $len))
+                (i32.const 16))
+
+            ;; first: func(l: list<string>) -> string
+            (func (export "first") (param $ptr i32) (param $len i32) (result i32)
+                (local.get $ptr))
+
+            ;; total: func(a: u8, ..., q: u8) -> u32, with 17 parameters
+            (func (export "total") (param $ptr i32) (result i32)
+                (local $i i32) (local $total i32)
+                (block $done
+                    (loop $next
+                        (br_if $done (i32.eq (local.get $i) (i32.const 17)))
+                        (local.set $total
+                            (i32.add
+                                (local.get $total)
+                                (i32.load8_u (i32.add (local.get $ptr) (local.get $i)))))
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br $next)))
+                (local.get $total))
+
+            ;; surrogate: func() -> char
+            (func (export "surrogate") (result i32)
+                (i32.const 0xd800))
+
+            ;; huge: func() -> string, or list<record {}>, of 0xffffffff bytes
+            ;; or elements
+            (func (export "huge") (result i32)
+                (i32.store (i32.const 32) (i32.const 0))
+                (i32.store (i32.const 36) (i32.const -1))
+                (i32.const 32))
+        )
+    "#;
+    let module = Module::new(store, wat)?;
+    Ok(Instance::new(store, &module, &imports! {})?)
+}
+
+fn point() -> InterfaceType {
+    InterfaceType::Record(vec![
+        ("x".to_string(), InterfaceType::S32),
+        ("y".to_string(), InterfaceType::S32),
+        ("label".to_string(), InterfaceType::String),
+    ])
+}
+
+#[compiler_test(canonical)]
+fn interface_functions_are_called(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let instance = get_instance(&mut store)?;
+    let list = |ty| InterfaceType::List(Box::new(ty));
+
+    let sum = CanonicalFunction::new(
+        &store,
+        &instance,
+        "sum",
+        vec![list(InterfaceType::U32)],
+        vec![InterfaceType::U64],
+    )?;
+    let values = vec![1u32.into(), 2u32.into(), u32::MAX.into()];
+    assert_eq!(
+        sum.call(&mut store, &[InterfaceValue::List(values)])?,
+        [InterfaceValue::U64(u32::MAX as u64 + 3)]
+    );
+    assert_eq!(
+        sum.call(&mut store, &[InterfaceValue::List(vec![])])?,
+        [InterfaceValue::U64(0)]
+    );
+
+    // The results are passed in the memory, and freed by the guest after
+    // they're lifted.
+    let echo = CanonicalFunction::new(
+        &store,
+        &instance,
+        "echo",
+        vec![InterfaceType::String],
+        vec![InterfaceType::String],
+    )?;
+    assert_eq!(
+        echo.call(&mut store, &["héllo".into()])?,
+        [InterfaceValue::from("héllo")]
+    );
+    let post_returns = instance.exports.get_global("post_returns")?;
+    assert_eq!(post_returns.get(&mut store).i32(), Some(1));
+
+    let moved = CanonicalFunction::new(
+        &store,
+        &instance,
+        "move",
+        vec![point(), InterfaceType::S32],
+        vec![point()],
+    )?;
+    let p = InterfaceValue::Record(vec![
+        InterfaceValue::S32(1),
+        InterfaceValue::S32(-2),
+        "p".into(),
+    ]);
+    assert_eq!(
+        moved.call(&mut store, &[p, InterfaceValue::S32(3)])?,
+        [InterfaceValue::Record(vec![
+            InterfaceValue::S32(4),
+            InterfaceValue::S32(-2),
+            "p".into()
+        ])]
+    );
+
+    let first = CanonicalFunction::new(
+        &store,
+        &instance,
+        "first",
+        vec![list(InterfaceType::String)],
+        vec![InterfaceType::String],
+    )?;
+    let strings = InterfaceValue::List(vec!["abc".into(), "de".into()]);
+    assert_eq!(
+        first.call(&mut store, &[strings])?,
+        [InterfaceValue::from("abc")]
+    );
+
+    // More than 16 core parameters are passed in the memory.
+    let total = CanonicalFunction::new(
+        &store,
+        &instance,
+        "total",
+        vec![InterfaceType::U8; 17],
+        vec![InterfaceType::U32],
+    )?;
+    let args = (1..=17u8).map(InterfaceValue::from).collect::<Vec<_>>();
+    assert_eq!(total.call(&mut store, &args)?, [InterfaceValue::U32(153)]);
+
+    Ok(())
+}
+
+#[compiler_test(canonical)]
+fn canonical_errors(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let instance = get_instance(&mut store)?;
+
+    assert!(matches!(
+        CanonicalFunction::new(
+            &store,
+            &instance,
+            "sum",
+            vec![InterfaceType::String],
+            vec![InterfaceType::U32],
+        ),
+        Err(CanonicalError::Signature { .. })
+    ));
+
+    let sum = CanonicalFunction::new(
+        &store,
+        &instance,
+        "sum",
+        vec![InterfaceType::List(Box::new(InterfaceType::U32))],
+        vec![InterfaceType::U64],
+    )?;
+    assert!(matches!(
+        sum.call(&mut store, &[]),
+        Err(CanonicalError::Arity {
+            expected: 1,
+            given: 0
+        })
+    ));
+    assert!(matches!(
+        sum.call(&mut store, &[InterfaceValue::List(vec!["1".into()])]),
+        Err(CanonicalError::Type {
+            expected: InterfaceType::U32,
+            ..
+        })
+    ));
+
+    let surrogate = CanonicalFunction::new(
+        &store,
+        &instance,
+        "surrogate",
+        vec![],
+        vec![InterfaceType::Char],
+    )?;
+    assert!(matches!(
+        surrogate.call(&mut store, &[]),
+        Err(CanonicalError::InvalidValue(_))
+    ));
+
+    // The lengths returned by the guest are checked against the size of
+    // the memory before anything is copied out of it.
+    let huge_string = CanonicalFunction::new(
+        &store,
+        &instance,
+        "huge",
+        vec![],
+        vec![InterfaceType::String],
+    )?;
+    assert!(matches!(
+        huge_string.call(&mut store, &[]),
+        Err(CanonicalError::MemoryAccess(
+            MemoryAccessError::HeapOutOfBounds
+        ))
+    ));
+    let huge_list = CanonicalFunction::new(
+        &store,
+        &instance,
+        "huge",
+        vec![],
+        vec![InterfaceType::List(Box::new(InterfaceType::Record(vec![])))],
+    )?;
+    assert!(matches!(
+        huge_list.call(&mut store, &[]),
+        Err(CanonicalError::InvalidValue(_))
+    ));
+
+    Ok(())
+}
+
+#[compiler_test(canonical)]
+fn components_are_recognized(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let component = b"\0asm\x0d\x00\x01\x00";
+    assert!(is_component(component));
+    assert!(!is_component(b"\0asm\x01\x00\x00\x00"));
+    assert!(matches!(
+        Module::new(&store, component),
+        Err(CompileError::UnsupportedFeature(_))
+    ));
+    Ok(())
+}
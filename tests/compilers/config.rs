@@ -7,6 +7,7 @@ pub enum Compiler {
     LLVM,
     Cranelift,
     Singlepass,
+    Interpreter,
 }
 
 #[derive(Clone)]
@@ -15,6 +16,8 @@ pub struct Config {
     pub features: Option<Features>,
     pub middlewares: Vec<Arc<dyn ModuleMiddleware>>,
     pub canonicalize_nans: bool,
+    pub soft_float: bool,
+    pub lazy_compilation: bool,
 }
 
 impl Config {
@@ -23,6 +26,8 @@ impl Config {
             compiler,
             features: None,
             canonicalize_nans: false,
+            soft_float: false,
+            lazy_compilation: false,
             middlewares: vec![],
         }
     }
@@ -39,6 +44,14 @@ impl Config {
         self.canonicalize_nans = canonicalize_nans;
     }
 
+    pub fn set_soft_float(&mut self, soft_float: bool) {
+        self.soft_float = soft_float;
+    }
+
+    pub fn set_lazy_compilation(&mut self, lazy_compilation: bool) {
+        self.lazy_compilation = lazy_compilation;
+    }
+
     pub fn store(&self) -> Store {
         let compiler_config = self.compiler_config(self.canonicalize_nans);
         let engine = self.engine(compiler_config);
@@ -59,6 +72,12 @@ impl Config {
     }
 
     pub fn engine_headless(&self) -> Engine {
+        // The interpreted artifacts are run by the interpreter, which
+        // doesn't generate any machine code to load them.
+        #[cfg(feature = "interpreter")]
+        if self.compiler == Compiler::Interpreter {
+            return self.engine(self.compiler_config(false));
+        }
         wasmer_compiler::EngineBuilder::headless().engine()
     }
 
@@ -71,6 +90,8 @@ impl Config {
             Compiler::Cranelift => {
                 let mut compiler = wasmer_compiler_cranelift::Cranelift::new();
                 compiler.canonicalize_nans(canonicalize_nans);
+                compiler.soft_float(self.soft_float);
+                compiler.lazy_compilation(self.lazy_compilation);
                 compiler.enable_verifier();
                 self.add_middlewares(&mut compiler);
                 Box::new(compiler)
@@ -91,6 +112,13 @@ impl Config {
                 self.add_middlewares(&mut compiler);
                 Box::new(compiler)
             }
+            #[cfg(feature = "interpreter")]
+            Compiler::Interpreter => {
+                let mut compiler = wasmer_compiler_interpreter::Interpreter::new();
+                compiler.canonicalize_nans(canonicalize_nans);
+                self.add_middlewares(&mut compiler);
+                Box::new(compiler)
+            }
             #[allow(unreachable_patterns)]
             compiler => {
                 panic!(
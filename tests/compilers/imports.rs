@@ -385,7 +385,6 @@ fn dynamic_function_with_env_wasmer_env_init_works(config: crate::Config) -> Res
     let module = get_module2(&store)?;
 
     #[allow(dead_code)]
-    #[derive(Clone)]
     struct Env {
         memory: Option<Memory>,
     }
@@ -423,7 +422,6 @@ fn multi_use_host_fn_manages_memory_correctly(config: crate::Config) -> Result<(
     let module = get_module2(&store)?;
 
     #[allow(dead_code)]
-    #[derive(Clone)]
     struct Env {
         memory: Option<Memory>,
     }
@@ -507,3 +505,139 @@ fn instance_local_memory_lifetime(config: crate::Config) -> Result<()> {
 
     Ok(())
 }
+
+#[compiler_test(imports)]
+fn record_and_replay_host_calls(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let wat = r#"(module
+    (import "host" "fill" (func $fill (param i32)))
+    (import "host" "time" (func $time (result i64)))
+    (memory (export "memory") 1)
+    (func (export "run") (result i64)
+      (call $fill (i32.const 100))
+      (i64.add
+        (call $time)
+        (i64.load8_u (i32.const 100))))
+)"#;
+    let module = Module::new(&store, wat)?;
+
+    // The host functions write to the memory and return a value that
+    // changes at each call.
+    struct Env {
+        memory: Option<Memory>,
+        calls: Arc<AtomicUsize>,
+    }
+    let host_imports = |store: &mut Store, byte: u8| {
+        let env = FunctionEnv::new(
+            store,
+            Env {
+                memory: None,
+                calls: Arc::new(AtomicUsize::new(0)),
+            },
+        );
+        let fill = Function::new_typed_with_env(
+            store,
+            &env,
+            move |env: FunctionEnvMut<Env>, offset: i32| {
+                let memory = env.data().memory.clone().unwrap();
+                memory.view(&env).write_u8(offset as u64, byte).unwrap();
+            },
+        );
+        let time = Function::new_typed_with_env(store, &env, |env: FunctionEnvMut<Env>| {
+            1000 * (env.data().calls.fetch_add(1, SeqCst) as i64 + 1)
+        });
+        let imports = imports! {
+            "host" => {
+                "fill" => fill,
+                "time" => time,
+            },
+        };
+        (env, imports)
+    };
+
+    let (env, imports) = host_imports(&mut store, 7);
+    let recorder = Recorder::new();
+    let imports = recorder.wrap_imports(&mut store, &imports);
+    let instance = Instance::new(&mut store, &module, &imports)?;
+    let memory = instance.exports.get_memory("memory")?.clone();
+    env.as_mut(&mut store).memory = Some(memory.clone());
+    recorder.set_memory(memory);
+    let run: TypedFunction<(), i64> = instance.exports.get_typed_function(&store, "run")?;
+    assert_eq!(run.call(&mut store)?, 1007);
+    assert_eq!(run.call(&mut store)?, 2007);
+
+    let log = ExecutionLog::deserialize(&recorder.log().serialize())?;
+    assert_eq!(log.len(), 4);
+
+    // The replayed instance sees the recorded inputs, not the ones of
+    // the new host functions.
+    let (_env, imports) = host_imports(&mut store, 9);
+    let replayer = Replayer::new(log);
+    let imports = replayer.wrap_imports(&mut store, &imports);
+    let instance = Instance::new(&mut store, &module, &imports)?;
+    replayer.set_memory(instance.exports.get_memory("memory")?.clone());
+    let run: TypedFunction<(), i64> = instance.exports.get_typed_function(&store, "run")?;
+    assert_eq!(run.call(&mut store)?, 1007);
+    assert_eq!(run.call(&mut store)?, 2007);
+    assert_eq!(replayer.remaining(), 0);
+
+    // Calls past the end of the log diverge.
+    let error = run.call(&mut store).unwrap_err();
+    assert!(error.message().contains("diverged"), "{}", error.message());
+
+    Ok(())
+}
+
+#[compiler_test(imports)]
+fn lazy_namespaces(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let module = get_module(&store)?;
+
+    let host_inits = Arc::new(AtomicUsize::new(0));
+    let unused_inits = Arc::new(AtomicUsize::new(0));
+    let mut imports = Imports::new();
+    {
+        let host_inits = host_inits.clone();
+        imports.register_lazy_namespace("host", move |store| {
+            host_inits.fetch_add(1, SeqCst);
+            namespace! {
+                "0" => Function::new_typed(store, || {}),
+                "1" => Function::new_typed(store, |x: i32| -> i32 { x + 1 }),
+                "2" => Function::new_typed(store, |_: i32, _: i64| {}),
+            }
+        });
+    }
+    {
+        let unused_inits = unused_inits.clone();
+        imports.register_lazy_namespace("unused", move |_| {
+            unused_inits.fetch_add(1, SeqCst);
+            Exports::new()
+        });
+    }
+    // The functions defined up front take precedence over the lazy ones.
+    let hits = Arc::new(AtomicUsize::new(0));
+    {
+        let hits = hits.clone();
+        imports.define(
+            "host",
+            "3",
+            Function::new_typed(&mut store, move |_: i32, _: i64, _: i32, _: f32, _: f64| {
+                hits.fetch_add(1, SeqCst);
+            }),
+        );
+    }
+    assert!(imports.get_export("host", "0").is_none());
+    assert!(imports.get_export("host", "3").is_some());
+
+    // The externs are created by the first instantiation, and shared by
+    // the clones of the imports.
+    let cloned = imports.clone();
+    Instance::new(&mut store, &module, &imports)?;
+    Instance::new(&mut store, &module, &cloned)?;
+    assert_eq!(host_inits.load(SeqCst), 1);
+    assert_eq!(unused_inits.load(SeqCst), 0);
+    assert_eq!(hits.load(SeqCst), 2);
+    assert!(imports.get_export("host", "0").is_some());
+    assert_eq!(imports.iter().count(), 4);
+    Ok(())
+}
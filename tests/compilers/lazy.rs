@@ -0,0 +1,125 @@
+//! Runs modules with Cranelift compiling their functions on their first
+//! call.
+
+#[cfg(feature = "cranelift")]
+mod cranelift {
+    use crate::{run_wast, Compiler, Config};
+    use anyhow::Result;
+    use wasmer::*;
+
+    fn lazy_config() -> Config {
+        let mut config = Config::new(Compiler::Cranelift);
+        config.set_lazy_compilation(true);
+        config
+    }
+
+    fn run_lazy_wast(name: &str) -> Result<()> {
+        run_wast(lazy_config(), &format!("tests/wast/spec/{}.wast", name))
+    }
+
+    #[test]
+    fn call() -> Result<()> {
+        run_lazy_wast("call")
+    }
+
+    #[test]
+    fn call_indirect() -> Result<()> {
+        run_lazy_wast("call_indirect")
+    }
+
+    #[test]
+    fn fac() -> Result<()> {
+        run_lazy_wast("fac")
+    }
+
+    #[test]
+    fn traps() -> Result<()> {
+        run_lazy_wast("traps")
+    }
+
+    #[test]
+    fn functions_are_shared_by_the_instances() -> Result<()> {
+        let mut store = lazy_config().store();
+        let wat = r#"
+            (module
+              (global $calls (mut i32) (i32.const 0))
+              (func $fib (param i32) (result i32)
+                local.get 0
+                i32.const 2
+                i32.lt_u
+                if (result i32)
+                  local.get 0
+                else
+                  local.get 0
+                  i32.const 1
+                  i32.sub
+                  call $fib
+                  local.get 0
+                  i32.const 2
+                  i32.sub
+                  call $fib
+                  i32.add
+                end)
+              (func (export "run") (param i32) (result i32)
+                global.get $calls
+                i32.const 1
+                i32.add
+                global.set $calls
+                local.get 0
+                call $fib)
+              (func (export "calls") (result i32)
+                global.get $calls))
+        "#;
+        let module = Module::new(&store, wat)?;
+        for _ in 0..2 {
+            let instance = Instance::new(&mut store, &module, &imports! {})?;
+            let run: TypedFunction<i32, i32> =
+                instance.exports.get_typed_function(&store, "run")?;
+            let calls: TypedFunction<(), i32> =
+                instance.exports.get_typed_function(&store, "calls")?;
+            assert_eq!(run.call(&mut store, 20)?, 6765);
+            assert_eq!(run.call(&mut store, 10)?, 55);
+            assert_eq!(calls.call(&mut store)?, 2);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn trap_in_a_function_compiled_lazily() -> Result<()> {
+        let mut store = lazy_config().store();
+        let wat = r#"
+            (module
+              (func $div (param i32 i32) (result i32)
+                local.get 0
+                local.get 1
+                i32.div_u)
+              (func (export "run") (param i32 i32) (result i32)
+                local.get 0
+                local.get 1
+                call $div))
+        "#;
+        let module = Module::new(&store, wat)?;
+        let instance = Instance::new(&mut store, &module, &imports! {})?;
+        let run: TypedFunction<(i32, i32), i32> =
+            instance.exports.get_typed_function(&store, "run")?;
+        assert_eq!(run.call(&mut store, 6, 3)?, 2);
+        let err = run.call(&mut store, 1, 0).unwrap_err();
+        assert_eq!(err.to_trap(), Some(TrapCode::IntegerDivisionByZero));
+        Ok(())
+    }
+
+    #[test]
+    fn modules_compiled_lazily_cant_be_serialized() -> Result<()> {
+        let store = lazy_config().store();
+        let module = Module::new(&store, r#"(module (func (export "run")))"#)?;
+        assert!(module.serialize().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_functions_are_rejected_up_front() {
+        let store = lazy_config().store();
+        let wat = r#"(module (func (result i32) i64.const 0))"#;
+        assert!(Module::new(&store, wat).is_err());
+    }
+}
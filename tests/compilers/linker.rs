@@ -0,0 +1,112 @@
+use anyhow::Result;
+use wasmer::*;
+
+fn module(store: &Store, wat: &str) -> Result<Module> {
+    Ok(Module::new(store, wat)?)
+}
+
+#[compiler_test(linker)]
+fn graphs_of_modules_are_instantiated(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let memory = module(&store, r#"(module (memory (export "memory") 1))"#)?;
+    let counter = module(
+        &store,
+        r#"
+        (module
+            (import "memory" "memory" (memory 1))
+            (global $starts (export "starts") (mut i32) (i32.const 0))
+            (func $start (global.set $starts (i32.add (global.get $starts) (i32.const 1))))
+            (start $start)
+            (func (export "increment")
+                (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (i32.const 1)))))
+        "#,
+    )?;
+    let app = module(
+        &store,
+        r#"
+        (module
+            (import "memory" "memory" (memory 1))
+            (import "counter" "increment" (func $increment))
+            (import "host" "twice" (func $twice (param i32) (result i32)))
+            (func (export "run") (result i32)
+                (call $increment)
+                (call $increment)
+                (call $twice (i32.load (i32.const 0)))))
+        "#,
+    )?;
+
+    let mut linker = Linker::new();
+    linker
+        .register_module("memory", &memory)
+        .register_module("counter", &counter)
+        .define(
+            "host",
+            "twice",
+            Function::new_typed(&mut store, |x: i32| x * 2),
+        );
+
+    let instance = linker.instantiate(&mut store, &app)?;
+    let run: TypedFunction<(), i32> = instance.exports.get_typed_function(&store, "run")?;
+    assert_eq!(run.call(&mut store)?, 4);
+
+    // The registered modules are instantiated once, and their memory is
+    // shared by the modules importing it.
+    let instance = linker.instantiate(&mut store, &app)?;
+    let run: TypedFunction<(), i32> = instance.exports.get_typed_function(&store, "run")?;
+    assert_eq!(run.call(&mut store)?, 8);
+    let counter = linker.instantiate_registered(&mut store, "counter")?;
+    assert_eq!(
+        counter.exports.get_global("starts")?.get(&mut store),
+        Value::I32(1)
+    );
+    let memory = linker.instantiate_registered(&mut store, "memory")?;
+    let view = memory.exports.get_memory("memory")?.view(&store);
+    assert_eq!(view.read_u8(0)?, 4);
+
+    Ok(())
+}
+
+#[compiler_test(linker)]
+fn linker_errors(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let ping = module(
+        &store,
+        r#"(module (import "pong" "f" (func)) (func (export "f")))"#,
+    )?;
+    let pong = module(
+        &store,
+        r#"(module (import "ping" "f" (func)) (func (export "f")))"#,
+    )?;
+    let app = module(&store, r#"(module (import "ping" "f" (func)))"#)?;
+
+    let mut linker = Linker::new();
+    linker
+        .register_module("ping", &ping)
+        .register_module("pong", &pong);
+    match linker.instantiate(&mut store, &app) {
+        Err(LinkerError::Cycle(cycle)) => assert_eq!(cycle, ["ping", "pong", "ping"]),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+
+    // A registered module which can't be instantiated is reported by name.
+    let broken = module(
+        &store,
+        r#"(module (import "host" "missing" (func)) (func (export "f")))"#,
+    )?;
+    let mut linker = Linker::new();
+    linker.register_module("ping", &broken);
+    assert!(matches!(
+        linker.instantiate(&mut store, &pong),
+        Err(LinkerError::Dependency { name, .. }) if name == "ping"
+    ));
+    assert!(matches!(
+        linker.instantiate(&mut store, &broken),
+        Err(LinkerError::Instantiation(_))
+    ));
+    assert!(matches!(
+        linker.instantiate_registered(&mut store, "missing"),
+        Err(LinkerError::UnknownModule(_))
+    ));
+
+    Ok(())
+}
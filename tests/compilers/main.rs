@@ -5,14 +5,24 @@
 #[macro_use]
 extern crate compiler_test_derive;
 
+mod adapter;
+mod background;
+mod canonical;
 mod config;
 mod deterministic;
 mod imports;
 mod issues;
+mod lazy;
+mod linker;
 mod metering;
+mod metrics;
 mod middlewares;
+mod pooling;
 // mod multi_value_imports;
+mod reset;
 mod serialize;
+mod snapshot;
+mod soft_float;
 mod traps;
 mod typed_functions;
 mod wasi;
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use wasmer::metrics::{self, Counter, Gauge, Metrics};
+use wasmer::*;
+
+#[derive(Default)]
+struct Recording {
+    counters: Mutex<HashMap<Counter, u64>>,
+}
+
+impl Recording {
+    fn counter(&self, counter: Counter) -> u64 {
+        self.counters
+            .lock()
+            .unwrap()
+            .get(&counter)
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+impl Metrics for Recording {
+    fn increment_counter(&self, counter: Counter, value: u64) {
+        *self.counters.lock().unwrap().entry(counter).or_insert(0) += value;
+    }
+
+    fn set_gauge(&self, _gauge: Gauge, _value: u64) {}
+}
+
+lazy_static::lazy_static! {
+    // The metrics are reported for the whole process, so the tests share
+    // the sink and only check that the counters increase.
+    static ref RECORDING: Arc<Recording> = {
+        let recording = Arc::new(Recording::default());
+        metrics::set_metrics(recording.clone());
+        recording
+    };
+}
+
+#[compiler_test(metrics)]
+fn metrics_of_a_trapping_instance(config: crate::Config) -> Result<()> {
+    let recording = RECORDING.clone();
+    let mut store = config.store();
+    let wat = r#"
+        (module
+            (memory 1)
+            (func (export "run") (unreachable))
+        )
+    "#;
+
+    let compilations = recording.counter(Counter::Compilations);
+    let module = Module::new(&store, wat)?;
+    assert!(recording.counter(Counter::Compilations) > compilations);
+
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    assert!(metrics::gauge(Gauge::InstancesAlive) >= 1);
+    assert!(metrics::gauge(Gauge::MemoryMapped) >= WASM_PAGE_SIZE as u64);
+
+    let traps = recording.counter(Counter::Traps(TrapCode::UnreachableCodeReached));
+    let run = instance.exports.get_function("run")?;
+    assert!(run.call(&mut store, &[]).is_err());
+    assert!(recording.counter(Counter::Traps(TrapCode::UnreachableCodeReached)) > traps);
+
+    Ok(())
+}
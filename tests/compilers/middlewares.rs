@@ -88,6 +88,64 @@ impl FunctionMiddleware for Fusion {
     }
 }
 
+#[derive(Debug)]
+struct DoubleAddGen {
+    locals: FunctionMetadata<u32>,
+}
+
+#[derive(Debug)]
+struct DoubleAdd {
+    local_function_index: LocalFunctionIndex,
+    locals: FunctionMetadata<u32>,
+    local: u32,
+}
+
+impl ModuleMiddleware for DoubleAddGen {
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        Box::new(DoubleAdd {
+            local_function_index,
+            locals: self.locals.clone(),
+            local: 0,
+        })
+    }
+}
+
+impl FunctionMiddleware for DoubleAdd {
+    fn feed_locals(&mut self, locals: &mut MiddlewareLocals) -> Result<(), MiddlewareError> {
+        self.local = locals.add(1, wasmparser::Type::I32)?;
+        self.locals.insert(self.local_function_index, self.local);
+        Ok(())
+    }
+
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        match operator {
+            Operator::I32Add => {
+                state.extend(&[
+                    Operator::I32Add,
+                    Operator::LocalTee {
+                        local_index: self.local,
+                    },
+                    Operator::LocalGet {
+                        local_index: self.local,
+                    },
+                    Operator::I32Add,
+                ]);
+            }
+            _ => {
+                state.push_operator(operator);
+            }
+        }
+        Ok(())
+    }
+}
+
 #[compiler_test(middlewares)]
 fn middleware_basic(mut config: crate::Config) -> Result<()> {
     config.set_middlewares(vec![
@@ -210,3 +268,70 @@ fn middleware_chain_order_2(mut config: crate::Config) -> Result<()> {
     assert_eq!(result, 48);
     Ok(())
 }
+
+#[compiler_test(middlewares)]
+fn middleware_add_local(mut config: crate::Config) -> Result<()> {
+    let locals = FunctionMetadata::new();
+    config.set_middlewares(vec![Arc::new(DoubleAddGen {
+        locals: locals.clone(),
+    }) as Arc<dyn ModuleMiddleware>]);
+    let mut store = config.store();
+    let wat = r#"(module
+        (func (export "add") (param i32 i32) (result i32) (local i64)
+           (i32.add (local.get 0)
+                    (local.get 1)))
+)"#;
+    let module = Module::new(&store, wat).unwrap();
+    let import_object = imports! {};
+
+    let instance = Instance::new(&mut store, &module, &import_object)?;
+
+    let f: TypedFunction<(i32, i32), i32> =
+        instance.exports.get_typed_function(&mut store, "add")?;
+    let result = f.call(&mut store, 4, 6)?;
+    assert_eq!(result, 20);
+    // The local is added after the 2 parameters and the declared local
+    let added: Vec<u32> = locals
+        .to_vec()
+        .into_iter()
+        .map(|(_, local)| local)
+        .collect();
+    assert_eq!(added, vec![3]);
+    Ok(())
+}
+
+#[compiler_test(middlewares)]
+fn middleware_filtered(mut config: crate::Config) -> Result<()> {
+    config.set_middlewares(vec![
+        Arc::new(FilteredMiddleware::new(
+            Arc::new(Add2MulGen { value_off: 2 }),
+            FunctionFilter::new().exclude(FunctionSelector::Index(FunctionIndex::from_u32(0))),
+        )) as Arc<dyn ModuleMiddleware>,
+        Arc::new(FilteredMiddleware::new(
+            Arc::new(Add2MulGen { value_off: 1 }),
+            FunctionFilter::new().include(FunctionSelector::Name("first".to_string())),
+        )) as Arc<dyn ModuleMiddleware>,
+    ]);
+    let mut store = config.store();
+    let wat = r#"(module
+        (func $first (param i32 i32) (result i32)
+           (i32.add (local.get 0)
+                    (local.get 1)))
+        (func (export "second") (param i32 i32) (result i32)
+           (i32.add (local.get 0)
+                    (local.get 1)))
+        (export "first" (func $first))
+)"#;
+    let module = Module::new(&store, wat).unwrap();
+    let import_object = imports! {};
+
+    let instance = Instance::new(&mut store, &module, &import_object)?;
+
+    let first: TypedFunction<(i32, i32), i32> =
+        instance.exports.get_typed_function(&mut store, "first")?;
+    assert_eq!(first.call(&mut store, 4, 6)?, 25);
+    let second: TypedFunction<(i32, i32), i32> =
+        instance.exports.get_typed_function(&mut store, "second")?;
+    assert_eq!(second.call(&mut store, 4, 6)?, 26);
+    Ok(())
+}
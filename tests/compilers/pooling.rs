@@ -0,0 +1,49 @@
+use anyhow::Result;
+use wasmer::*;
+
+#[compiler_test(pooling)]
+fn instances_reuse_the_slots_of_the_pool(config: crate::Config) -> Result<()> {
+    let tunables = BaseTunables::for_target(&Target::default());
+    let memory_size =
+        tunables.static_memory_bound.bytes().0 + tunables.static_memory_offset_guard_size as usize;
+    let pool = InstancePool::new(InstancePoolConfig {
+        max_instances: 1,
+        instance_size: 64 * 1024,
+        max_memories: 1,
+        memory_size,
+    })
+    .map_err(anyhow::Error::msg)?;
+
+    let mut engine = config.engine(config.compiler_config(config.canonicalize_nans));
+    engine.set_tunables(tunables.with_instance_pool(pool.clone()));
+    let wat = r#"
+        (module
+            (memory 1)
+            (func (export "load") (result i32)
+                (i32.load (i32.const 0)))
+            (func (export "store") (param i32)
+                (i32.store (i32.const 0) (local.get 0)))
+        )
+    "#;
+    let module = Module::new(&engine, wat)?;
+
+    for _ in 0..2 {
+        let mut store = Store::new(engine.clone());
+        let instance = Instance::new(&mut store, &module, &imports! {})?;
+        assert_eq!(pool.available_instances(), 0);
+        assert_eq!(pool.available_memories(), 0);
+
+        // The memory starts zeroed, even in a slot used before.
+        let load: TypedFunction<(), i32> = instance.exports.get_typed_function(&store, "load")?;
+        let store_value: TypedFunction<i32, ()> =
+            instance.exports.get_typed_function(&store, "store")?;
+        assert_eq!(load.call(&mut store)?, 0);
+        store_value.call(&mut store, 42)?;
+        assert_eq!(load.call(&mut store)?, 42);
+    }
+
+    assert_eq!(pool.available_instances(), 1);
+    assert_eq!(pool.available_memories(), 1);
+
+    Ok(())
+}
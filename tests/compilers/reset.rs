@@ -0,0 +1,131 @@
+use anyhow::Result;
+use wasmer::*;
+
+fn get_module(store: &Store) -> Result<Module> {
+    let wat = r#"
+        (module
+            (memory (export "memory") 1)
+            (table (export "table") 1 funcref)
+            (global $starts (export "starts") (mut i32) (i32.const 0))
+            (global $counter (mut i32) (i32.const 10))
+            (data (i32.const 16) "\2a")
+            (elem (i32.const 0) $forty_two)
+            (type $ret_i32 (func (result i32)))
+
+            (func $forty_two (result i32)
+                (i32.const 42))
+            (func $start
+                (global.set $starts (i32.add (global.get $starts) (i32.const 1))))
+            (start $start)
+
+            (func (export "dirty")
+                (drop (memory.grow (i32.const 2)))
+                (i32.store8 (i32.const 16) (i32.const 7))
+                (i32.store (i32.const 70000) (i32.const 7))
+                (global.set $counter (i32.const 7)))
+            (func (export "counter") (result i32)
+                (global.get $counter))
+            (func (export "call_first") (result i32)
+                (call_indirect (type $ret_i32) (i32.const 0)))
+        )
+    "#;
+    Ok(Module::new(store, wat)?)
+}
+
+fn dirty(store: &mut Store, instance: &Instance) -> Result<()> {
+    let dirty: TypedFunction<(), ()> = instance.exports.get_typed_function(store, "dirty")?;
+    dirty.call(store)?;
+    let table = instance.exports.get_table("table")?;
+    table.grow(store, 3, Value::FuncRef(None))?;
+    table.set(store, 0, Value::FuncRef(None))?;
+    Ok(())
+}
+
+fn assert_initial_state(store: &mut Store, instance: &Instance) -> Result<()> {
+    let view = instance.exports.get_memory("memory")?.view(store);
+    assert_eq!(view.size(), Pages(1));
+    assert_eq!(view.read_u8(16)?, 42);
+    assert_eq!(view.read_u8(17)?, 0);
+    assert_eq!(instance.exports.get_table("table")?.size(store), 1);
+
+    let counter: TypedFunction<(), i32> = instance.exports.get_typed_function(store, "counter")?;
+    let call_first: TypedFunction<(), i32> =
+        instance.exports.get_typed_function(store, "call_first")?;
+    assert_eq!(counter.call(store)?, 10);
+    assert_eq!(call_first.call(store)?, 42);
+
+    // The start function is run again, on the initial globals.
+    assert_eq!(
+        instance.exports.get_global("starts")?.get(store),
+        Value::I32(1)
+    );
+    Ok(())
+}
+
+#[compiler_test(reset)]
+fn instances_are_reset(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let module = get_module(&store)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    assert_initial_state(&mut store, &instance)?;
+
+    for _ in 0..2 {
+        dirty(&mut store, &instance)?;
+        let memory = instance.exports.get_memory("memory")?;
+        assert_eq!(memory.view(&store).size(), Pages(3));
+
+        unsafe { instance.reset(&mut store)? };
+        assert_initial_state(&mut store, &instance)?;
+
+        // The pages the memory grew by are zeroed when it grows again.
+        memory.grow(&mut store, 2)?;
+        assert_eq!(memory.view(&store).read_u8(70000)?, 0);
+        unsafe { instance.reset(&mut store)? };
+    }
+
+    Ok(())
+}
+
+#[compiler_test(reset)]
+fn instances_restored_from_a_snapshot_are_reset(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let module = get_module(&store)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let dirty: TypedFunction<(), ()> = instance.exports.get_typed_function(&store, "dirty")?;
+    dirty.call(&mut store)?;
+    let snapshot = InstanceSnapshot::capture(&mut store, &instance)?;
+
+    let instance = snapshot.instantiate(&mut store, &imports! {})?;
+    let memory = instance.exports.get_memory("memory")?;
+    assert_eq!(memory.view(&store).read_u8(16)?, 7);
+
+    // The memory doesn't map the snapshot anymore.
+    unsafe { instance.reset(&mut store)? };
+    assert_initial_state(&mut store, &instance)?;
+    let memory = instance.exports.get_memory("memory")?;
+    memory.grow(&mut store, 2)?;
+    assert_eq!(memory.view(&store).read_u8(70000)?, 0);
+
+    Ok(())
+}
+
+#[compiler_test(reset)]
+fn instances_with_shared_memories_are_not_reset(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (memory (export "memory") 1 1 shared)
+            (global (export "counter") (mut i32) (i32.const 0)))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let counter = instance.exports.get_global("counter")?;
+    counter.set(&mut store, Value::I32(1))?;
+
+    assert!(unsafe { instance.reset(&mut store) }.is_err());
+    assert_eq!(counter.get(&mut store), Value::I32(1));
+
+    Ok(())
+}
@@ -85,3 +85,90 @@ fn test_deserialize(config: crate::Config) -> Result<()> {
     assert_eq!(result.to_vec(), vec![Value::I64(1500)]);
     Ok(())
 }
+
+#[compiler_test(serialize)]
+fn test_deserialize_checks_cpu_features(config: crate::Config) -> Result<()> {
+    if !cfg!(target_arch = "x86_64") {
+        return Ok(());
+    }
+
+    let cpu_features = CpuFeature::set()
+        | CpuFeature::SSE2
+        | CpuFeature::SSE42
+        | CpuFeature::AVX
+        | CpuFeature::AVX2
+        | CpuFeature::AVX512F;
+    let engine = wasmer_compiler::EngineBuilder::new(config.compiler_config(false))
+        .set_target(Some(Target::new(Triple::host(), cpu_features)))
+        .engine();
+    let module = Module::new(&engine, "(module (func (export \"run\")))")?;
+    let serialized_bytes = module.serialize()?;
+
+    let headless_store = config.headless_store();
+    let result = unsafe { Module::deserialize(&headless_store, serialized_bytes) };
+    if CpuFeature::for_host().is_superset(cpu_features) {
+        assert!(result.is_ok());
+    } else {
+        assert!(matches!(result, Err(DeserializeError::Incompatible(_))));
+    }
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_detects_corruption(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, "(module (func (export \"run\")))")?;
+    let mut serialized_bytes = module.serialize()?.to_vec();
+    *serialized_bytes.last_mut().unwrap() ^= 0xff;
+
+    let headless_store = config.headless_store();
+    let result = unsafe { Module::deserialize(&headless_store, serialized_bytes) };
+    assert!(matches!(result, Err(DeserializeError::CorruptedBinary(_))));
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_unchecked(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, "(module (func (export \"run\")))")?;
+    let serialized_bytes = module.serialize()?;
+
+    let headless_store = config.headless_store();
+    let deserialized_module =
+        unsafe { Module::deserialize_unchecked(&headless_store, serialized_bytes)? };
+    assert_eq!(
+        deserialized_module.exports().collect::<Vec<_>>(),
+        module.exports().collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_from_file(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wat = r#"
+        (module
+            (memory (export "memory") 1)
+            (data (i32.const 16) "\2a")
+            (func (export "load") (result i32)
+                (i32.load8_u (i32.const 16)))
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let serialized_bytes = module.serialize()?;
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("module.wasmu");
+    module.serialize_to_file(&path)?;
+
+    let mut headless_store = config.headless_store();
+    let deserialized_module = unsafe { Module::deserialize_from_file(&headless_store, &path)? };
+    assert_eq!(deserialized_module.serialize()?, serialized_bytes);
+
+    let instance = Instance::new(&mut headless_store, &deserialized_module, &imports! {})?;
+    let load: TypedFunction<(), i32> = instance
+        .exports
+        .get_typed_function(&headless_store, "load")?;
+    assert_eq!(load.call(&mut headless_store)?, 42);
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use anyhow::Result;
+use wasmer::*;
+
+#[compiler_test(snapshot)]
+fn instances_are_restored_from_a_snapshot(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let wat = r#"
+        (module
+            (memory (export "memory") 1)
+            (global $initialized (mut i32) (i32.const 0))
+            (func (export "initialize")
+                (drop (memory.grow (i32.const 1)))
+                (i32.store (i32.const 70000) (i32.const 42))
+                (global.set $initialized (i32.const 1)))
+            (func (export "initialized") (result i32)
+                (global.get $initialized))
+            (func (export "load") (result i32)
+                (i32.load (i32.const 70000)))
+            (func (export "store") (param i32)
+                (i32.store (i32.const 70000) (local.get 0)))
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let initialize: TypedFunction<(), ()> =
+        instance.exports.get_typed_function(&store, "initialize")?;
+    initialize.call(&mut store)?;
+    let snapshot = InstanceSnapshot::capture(&mut store, &instance)?;
+
+    for _ in 0..2 {
+        let mut store = Store::new(store.engine().clone());
+        let instance = snapshot.instantiate(&mut store, &imports! {})?;
+        let memory = instance.exports.get_memory("memory")?;
+        assert_eq!(memory.view(&store).size(), Pages(2));
+
+        let initialized: TypedFunction<(), i32> =
+            instance.exports.get_typed_function(&store, "initialized")?;
+        let load: TypedFunction<(), i32> = instance.exports.get_typed_function(&store, "load")?;
+        let store_value: TypedFunction<i32, ()> =
+            instance.exports.get_typed_function(&store, "store")?;
+        assert_eq!(initialized.call(&mut store)?, 1);
+
+        // The writes of an instance don't change the snapshot.
+        assert_eq!(load.call(&mut store)?, 42);
+        store_value.call(&mut store, 7)?;
+        assert_eq!(load.call(&mut store)?, 7);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! Runs the float spectests with Cranelift routing the float operations
+//! through the soft-float builtins of the runtime.
+
+#[cfg(feature = "cranelift")]
+mod cranelift {
+    use crate::{run_wast, Compiler, Config};
+
+    fn run_soft_float_wast(name: &str) -> anyhow::Result<()> {
+        let mut config = Config::new(Compiler::Cranelift);
+        config.set_soft_float(true);
+        run_wast(config, &format!("tests/wast/spec/{}.wast", name))
+    }
+
+    #[test]
+    fn f32() -> anyhow::Result<()> {
+        run_soft_float_wast("f32")
+    }
+
+    #[test]
+    fn f32_bitwise() -> anyhow::Result<()> {
+        run_soft_float_wast("f32_bitwise")
+    }
+
+    #[test]
+    fn f32_cmp() -> anyhow::Result<()> {
+        run_soft_float_wast("f32_cmp")
+    }
+
+    #[test]
+    fn f64() -> anyhow::Result<()> {
+        run_soft_float_wast("f64")
+    }
+
+    #[test]
+    fn f64_bitwise() -> anyhow::Result<()> {
+        run_soft_float_wast("f64_bitwise")
+    }
+
+    #[test]
+    fn f64_cmp() -> anyhow::Result<()> {
+        run_soft_float_wast("f64_cmp")
+    }
+
+    #[test]
+    fn conversions() -> anyhow::Result<()> {
+        run_soft_float_wast("conversions")
+    }
+
+    #[test]
+    fn float_exprs() -> anyhow::Result<()> {
+        run_soft_float_wast("float_exprs")
+    }
+
+    #[test]
+    fn float_misc() -> anyhow::Result<()> {
+        run_soft_float_wast("float_misc")
+    }
+}
@@ -80,6 +80,99 @@ fn test_trap_trace(config: crate::Config) -> Result<()> {
     Ok(())
 }
 
+#[cfg_attr(target_env = "musl", ignore)]
+#[compiler_test(traps)]
+fn test_trap_backtrace(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let wat = r#"
+        (module $hello_mod
+            (func (export "run") (call $hello))
+            (func $hello (unreachable))
+        )
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let run_func = instance
+        .exports
+        .get_function("run")
+        .expect("expected function export");
+
+    let e = run_func
+        .call(&mut store, &[])
+        .expect_err("error calling function");
+
+    // The Wasm frames of the backtrace are the ones of the trace, in
+    // the same order.
+    let wasm_frames = e
+        .backtrace()
+        .into_iter()
+        .filter_map(|frame| match frame {
+            BacktraceFrame::Wasm(frame) => Some(frame),
+            BacktraceFrame::Host { .. } => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(wasm_frames.len(), 2);
+    assert_eq!(wasm_frames[0].function_name(), Some("hello"));
+    assert_eq!(wasm_frames[1].func_index(), 0);
+    assert_eq!(
+        BacktraceFrame::Wasm(wasm_frames[0].clone()).to_string(),
+        format!(
+            "hello (hello_mod[1]:0x{:x})",
+            wasm_frames[0].module_offset()
+        )
+    );
+
+    Ok(())
+}
+
+#[compiler_test(traps)]
+fn test_trap_core_dump(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let wat = r#"
+        (module $dump_mod
+            (memory 1)
+            (global $counter (mut i32) (i32.const 0))
+            (global f64 (f64.const 2.5))
+            (func (export "run")
+                (global.set $counter (i32.const 7))
+                (i32.store8 (i32.const 16) (i32.const 42))
+                (call $crash))
+            (func $crash (unreachable))
+        )
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let run_func = instance
+        .exports
+        .get_function("run")
+        .expect("expected function export");
+
+    let e = run_func
+        .call(&mut store, &[])
+        .expect_err("error calling function");
+
+    let dump = CoreDump::capture(&mut store, &instance, &e)?.with_module_hash("abc");
+    assert_eq!(dump.module_name, "dump_mod");
+    assert_eq!(dump.module_hash.as_deref(), Some("abc"));
+    assert_eq!(dump.frames.len(), 2);
+    assert_eq!(dump.frames[0].function_name.as_deref(), Some("crash"));
+    assert_eq!(
+        dump.globals,
+        [CoreDumpValue::I32(7), CoreDumpValue::F64(2.5)]
+    );
+    assert_eq!(dump.memories.len(), 1);
+    assert_eq!(dump.memories[0].len(), 0x10000);
+    assert_eq!(dump.memories[0][16], 42);
+
+    let bytes = dump.serialize();
+    assert_eq!(CoreDump::deserialize(&bytes)?, dump);
+    assert!(CoreDump::deserialize(&bytes[..bytes.len() - 1]).is_err());
+
+    Ok(())
+}
+
 #[compiler_test(traps)]
 fn test_trap_trace_cb(config: crate::Config) -> Result<()> {
     let mut store = config.store();
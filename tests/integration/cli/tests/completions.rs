@@ -0,0 +1,31 @@
+use anyhow::bail;
+use std::process::Command;
+use wasmer_integration_tests_cli::get_wasmer_path;
+
+#[test]
+fn completions_include_subcommand_flags() -> anyhow::Result<()> {
+    let wasmer_path = get_wasmer_path();
+
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        let output = Command::new(&wasmer_path)
+            .arg("completions")
+            .arg(shell)
+            .output()?;
+
+        if !output.status.success() {
+            bail!(
+                "completions for {} failed with: stdout: {}\n\nstderr: {}",
+                shell,
+                std::str::from_utf8(&output.stdout)
+                    .expect("stdout is not utf8! need to handle arbitrary bytes"),
+                std::str::from_utf8(&output.stderr)
+                    .expect("stderr is not utf8! need to handle arbitrary bytes")
+            );
+        }
+
+        let stdout_output = std::str::from_utf8(&output.stdout).unwrap();
+        assert!(stdout_output.contains("mapdir"), "{shell}: {stdout_output}");
+    }
+
+    Ok(())
+}
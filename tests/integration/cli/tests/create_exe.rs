@@ -305,6 +305,61 @@ fn create_exe_works() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Tests that create-exe works with a module precompiled with `wasmer compile`
+// Ignored because of -lunwind linker issue on Windows
+// see https://github.com/wasmerio/wasmer/issues/3459
+#[cfg_attr(target_os = "windows", ignore)]
+#[test]
+fn create_exe_precompiled_works() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasmu_path = operating_dir.join("qjs.wasmu");
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    let output = Command::new(get_wasmer_path())
+        .arg("compile")
+        .arg(create_exe_test_wasm_path())
+        .arg("--cranelift")
+        .arg("-o")
+        .arg(&wasmu_path)
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "wasmer compile failed with: stdout: {}\n\nstderr: {}",
+            std::str::from_utf8(&output.stdout)
+                .expect("stdout is not utf8! need to handle arbitrary bytes"),
+            std::str::from_utf8(&output.stderr)
+                .expect("stderr is not utf8! need to handle arbitrary bytes")
+        );
+    }
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path: wasmu_path,
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe precompiled module with Wasmer")?;
+
+    let result = run_code(
+        &operating_dir,
+        &executable_path,
+        &["--eval".to_string(), "function greet(name) { return JSON.stringify('Hello, ' + name); }; print(greet('World'));".to_string()],
+        false,
+    )
+    .context("Failed to run generated executable")?;
+    let result_lines = result.lines().collect::<Vec<&str>>();
+    assert_eq!(result_lines, vec!["\"Hello, World\""],);
+
+    Ok(())
+}
+
 /// Tests that "-c" and "-- -c" are treated differently
 // Ignored because of -lunwind linker issue on Windows
 // see https://github.com/wasmerio/wasmer/issues/3459
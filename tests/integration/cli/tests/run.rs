@@ -616,6 +616,37 @@ fn run_no_imports_wasm_works() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn run_wasi_redirects_stdio_to_files() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let stdout_path = temp_dir.path().join("stdout.txt");
+
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(wasi_test_wasm_path())
+        .arg("--stdout")
+        .arg(&stdout_path)
+        .arg("--")
+        .arg("-e")
+        .arg("print(3 * (4 + 5))")
+        .output()?;
+
+    if !output.status.success() {
+        bail!(
+            "running failed with: stdout: {}\n\nstderr: {}",
+            std::str::from_utf8(&output.stdout)
+                .expect("stdout is not utf8! need to handle arbitrary bytes"),
+            std::str::from_utf8(&output.stderr)
+                .expect("stderr is not utf8! need to handle arbitrary bytes")
+        );
+    }
+
+    assert!(output.stdout.is_empty());
+    assert_eq!(std::fs::read_to_string(&stdout_path)?, "27\n");
+
+    Ok(())
+}
+
 #[test]
 fn run_wasi_works_non_existent() -> anyhow::Result<()> {
     let output = Command::new(get_wasmer_path())
@@ -857,3 +888,40 @@ fn test_wasmer_run_complex_url() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn run_json_format_reports_result() -> anyhow::Result<()> {
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(Path::new(ASSET_PATH).join("add.wat"))
+        .arg("--format")
+        .arg("json")
+        .arg("--invoke")
+        .arg("add")
+        .arg("1")
+        .arg("2")
+        .output()?;
+
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(output.status.success(), "stderr: {stderr}");
+    assert!(output.stdout.is_empty());
+    assert!(stderr.contains(r#""exit_code":0,"result":["3"]"#), "{stderr}");
+    assert!(stderr.contains(r#""execution":"#), "{stderr}");
+    Ok(())
+}
+
+#[test]
+fn run_json_format_reports_trap() -> anyhow::Result<()> {
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(Path::new(ASSET_PATH).join("trap.wat"))
+        .arg("--format")
+        .arg("json")
+        .output()?;
+
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stderr.contains(r#""trap":{"#), "{stderr}");
+    assert!(stderr.contains(r#""code":"IntegerDivisionByZero""#), "{stderr}");
+    Ok(())
+}
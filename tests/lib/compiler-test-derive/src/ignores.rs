@@ -119,7 +119,7 @@ impl Ignores {
                             engine = Some(alias.to_string());
                         }
                         // Compilers
-                        "cranelift" | "llvm" | "singlepass" => {
+                        "cranelift" | "llvm" | "singlepass" | "interpreter" => {
                             compiler = Some(alias.to_string());
                         }
                         other => {
@@ -89,6 +89,19 @@ gen_tests! {
                     ))
                 }
             }
+
+            #[cfg(feature = "interpreter")]
+            mod interpreter {
+                use super::*;
+                #[test_log::test]
+                #[cold]
+                #[cfg(feature = "universal")]
+                fn universal() {
+                    foo(crate::Config::new(
+                        crate::Compiler::Interpreter
+                    ))
+                }
+            }
         }
     };
 }